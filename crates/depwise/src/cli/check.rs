@@ -1,27 +1,507 @@
-use crate::cli::CheckArgs;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use depwise_analysis::env_backend::ResolutionFailure;
+use depwise_analysis::{AnalysisError, CancellationToken};
+
+use crate::cli::report;
+use crate::cli::{CheckArgs, OutputFormat};
+
+/// Exit code used when the check is stopped by `--timeout`
+const TIMEOUT_EXIT_CODE: i32 = 3;
+
+/// Exit code used when the analysis has warnings and `--fail-on-warnings` is given
+const WARNINGS_EXIT_CODE: i32 = 4;
 
 pub fn execute(check_args: CheckArgs) -> Result<(), Box<dyn std::error::Error>> {
-    println!(
-        "Checking dependencies for {}",
-        check_args.path.to_string_lossy()
+    let cancel = CancellationToken::new();
+    let timeout = check_args.timeout;
+    match timeout {
+        Some(0) => cancel.cancel(),
+        Some(secs) => {
+            let cancel = cancel.clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_secs(secs));
+                cancel.cancel();
+            });
+        }
+        None => {}
+    }
+
+    match check(check_args, &cancel) {
+        Err(e) => match (e.downcast_ref::<AnalysisError>(), timeout) {
+            (Some(AnalysisError::Cancelled(phase)), Some(secs)) => {
+                eprintln!("Timed out after {}s while {}", secs, phase);
+                std::process::exit(TIMEOUT_EXIT_CODE);
+            }
+            _ => Err(e),
+        },
+        result => result,
+    }
+}
+
+/// A project that was checked, with what its report is rendered with
+struct CheckedProject {
+    /// The path it was checked at, as it was given
+    path: PathBuf,
+    analysis: depwise_analysis::AnalysisReport,
+    backend: depwise_analysis::EnvironmentBackend,
+    backend_timeout: Option<Duration>,
+    /// The edits that fix each finding, for JSON output
+    fixes: Vec<Option<depwise_analysis::fix::SuggestedFix>>,
+    /// Why the imports weren't checked, when the backend failed to resolve the dependencies
+    resolution_failure: Option<ResolutionFailure>,
+    /// The environment given with `--env-manifest`, which the SBOM lists
+    manifest: Option<Arc<depwise_analysis::ResolvedEnvironment>>,
+}
+
+impl CheckedProject {
+    /// The directory paths are reported relative to, which a single script is part of
+    fn root(&self) -> &Path {
+        if self.path.is_file() {
+            self.path.parent().unwrap_or(Path::new("."))
+        } else {
+            &self.path
+        }
+    }
+
+    fn report(&self) -> report::Project<'_> {
+        report::Project {
+            path: &self.path,
+            root: self.root(),
+            analysis: &self.analysis,
+            fixes: &self.fixes,
+            resolution_failure: self.resolution_failure.as_ref(),
+        }
+    }
+}
+
+fn check(
+    check_args: CheckArgs,
+    cancel: &CancellationToken,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if check_args.paths.len() > 1 {
+        for (flag, given) in [
+            ("--emit-sbom", check_args.emit_sbom.is_some()),
+            ("--state-file", check_args.state_file.is_some()),
+        ] {
+            if given {
+                return Err(format!("{} can only be used with a single project path", flag).into());
+            }
+        }
+    }
+
+    // Text reports are printed as each project is checked, unless they're merged
+    let separate_text = check_args.report.is_text() && !check_args.aggregate;
+    let mut projects = Vec::new();
+    for path in &check_args.paths {
+        if check_args.report.is_text() {
+            println!("Checking dependencies for {}", path.to_string_lossy());
+        }
+        let project = check_project(&check_args, path, cancel)?;
+        if separate_text {
+            print!("{}", report::render(&project.report(), &check_args.report)?);
+            apply_actions(&check_args, &project, cancel)?;
+        }
+        if check_args.verbose
+            && check_args.report.is_text()
+            && let Some(failure) = &project.resolution_failure
+        {
+            print!("{}", report::render_resolver_output(failure));
+        }
+        projects.push(project);
+    }
+    if check_args.aggregate {
+        let reports: Vec<report::Project> = projects.iter().map(CheckedProject::report).collect();
+        print!(
+            "{}",
+            report::render_aggregated(&reports, &check_args.report)?
+        );
+    } else if let [project] = projects.as_slice()
+        && !separate_text
+    {
+        print!("{}", report::render(&project.report(), &check_args.report)?);
+    } else if !separate_text {
+        let reports: Vec<report::Project> = projects.iter().map(CheckedProject::report).collect();
+        print!("{}", report::render_projects(&reports, &check_args.report)?);
+    }
+    if !separate_text {
+        for project in &projects {
+            apply_actions(&check_args, project, cancel)?;
+        }
+    }
+
+    report::exit_on_findings(
+        projects.iter().map(|project| &project.analysis),
+        &check_args.report,
     );
+    let warnings: usize = projects.iter().map(|p| p.analysis.warnings().len()).sum();
+    if check_args.fail_on_warnings && warnings > 0 {
+        eprintln!(
+            "Failing because of {} (--fail-on-warnings)",
+            report::counted(warnings, "warning")
+        );
+        std::process::exit(WARNINGS_EXIT_CODE);
+    }
+    Ok(())
+}
+
+/// Check the project at `path`
+fn check_project(
+    check_args: &CheckArgs,
+    path: &Path,
+    cancel: &CancellationToken,
+) -> Result<CheckedProject, Box<dyn std::error::Error>> {
+    let environment = check_args.environment.clone().into_source();
+    let inferred = environment.is_none();
+    let config = depwise_analysis::Config::load(path)?;
+    let backend_timeout = check_args
+        .backend_timeout
+        .map(Duration::from_secs)
+        .or(config.backend_timeout);
+
+    let backend = check_args.backend.into();
+    let mut options = config
+        .apply(depwise_analysis::AnalysisOptions::builder(path))
+        .backend(backend)
+        .check_unused_imports(check_args.check_unused_imports)
+        .include_tests(!check_args.exclude_tests)
+        .include_docs(check_args.include_docs)
+        .default_excludes(!check_args.no_default_excludes)
+        .follow_symlinks(check_args.follow_symlinks)
+        .offline(check_args.offline)
+        .strict_parse(check_args.strict_parse)
+        .cancellation_token(cancel.clone());
+    if let Some(timeout) = backend_timeout {
+        options = options.backend_timeout(timeout);
+    }
+    if let Some(version) = check_args.python_version {
+        options = options.python_version(version);
+    }
+    if let Some(bytes) = check_args.max_file_size {
+        options = options.max_file_size((bytes > 0).then_some(bytes));
+    }
+    if let Some(count) = check_args.max_requirements {
+        options = options.max_requirements((count > 0).then_some(count));
+    }
+    for path in &check_args.excludes {
+        options = options.exclude(path);
+    }
+    if let Some(name_map) = &check_args.name_map {
+        options = options.name_map(name_map);
+    }
+    for module in &check_args.provided_modules {
+        options = options.provided_module(module);
+    }
+    for profile in &check_args.profiles {
+        for module in depwise_analysis::name_map::profile_modules(profile).unwrap_or_default() {
+            options = options.provided_module(*module);
+        }
+    }
+    for name in &check_args.config_names {
+        options = options.configuration(name);
+    }
+    for rule in &check_args.skip_rules {
+        options = options.skip_rule(*rule);
+    }
+    let root = if path.is_file() {
+        path.parent().unwrap_or(Path::new("."))
+    } else {
+        path
+    };
+    if let Some(base) = &check_args.changed_since {
+        let changed = changed_files(root, base, "--changed-since")?;
+        options = options.files(
+            changed
+                .into_iter()
+                .filter(|path| is_python(path))
+                .collect::<Vec<_>>(),
+        );
+    }
+    for platform in &check_args.platforms {
+        options = options.platform(platform);
+    }
+    if let Some(source) = environment {
+        options = options.source(source);
+    }
+    let mut manifest = None;
+    if let Some(path) = &check_args.env_manifest {
+        let environment = Arc::new(depwise_analysis::ResolvedEnvironment::from_json(
+            &fs::read_to_string(path)?,
+        )?);
+        // The interpreter the environment was resolved with is the one targeted
+        if check_args.python_version.is_none()
+            && let Some(version) = environment.python_version.as_deref()
+        {
+            options = options.python_version(version.parse()?);
+        }
+        options = options.custom_backend(environment.clone());
+        manifest = Some(environment);
+    }
+    let mut session = match depwise_analysis::AnalysisSession::new(options.build()) {
+        Ok(session) => session,
+        // The problems the resolver reported on the declared dependencies are the findings
+        Err(AnalysisError::ResolutionFailed(failure)) => {
+            return Ok(CheckedProject {
+                path: path.to_path_buf(),
+                analysis: depwise_analysis::AnalysisReport::from(&*failure),
+                backend,
+                backend_timeout,
+                fixes: Vec::new(),
+                resolution_failure: Some(*failure),
+                manifest,
+            });
+        }
+        Err(e) => return Err(e.into()),
+    };
+    if let (Some(base), Some(state_file)) = (&check_args.since, &check_args.state_file) {
+        let reused = match fs::read_to_string(state_file) {
+            Ok(state) => session.load_state(&state, &changed_files(root, base, "--since")?)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => false,
+            Err(e) => return Err(e.into()),
+        };
+        if !reused && check_args.report.is_text() {
+            println!(
+                "Note: the state in {} can't be reused, parsing every file",
+                state_file.display()
+            );
+        }
+    }
+    let analysis = session.check()?;
+    if check_args.since.is_none()
+        && let Some(state_file) = &check_args.state_file
+    {
+        fs::write(state_file, session.save_state())?;
+    }
+    if check_args.report.is_text()
+        && inferred
+        && path.is_file()
+        && let Some(source) = analysis.sources().first()
+        && source.path() != path
+    {
+        println!(
+            "Note: {} has no inline script metadata, checking it against {} without reporting unused dependencies",
+            path.display(),
+            source.path().display()
+        );
+    }
+    // JSON consumers get the edits `--fix` and `--add-missing` would make, to apply them
+    // themselves
+    let fixes = if check_args.report.format == OutputFormat::Json {
+        depwise_analysis::fix::suggested_fixes(
+            &analysis,
+            &backend,
+            check_args.include_optional,
+            check_args.pin_style.map(Into::into),
+            check_args.offline,
+            backend_timeout,
+            cancel,
+        )?
+    } else {
+        Vec::new()
+    };
+    Ok(CheckedProject {
+        path: path.to_path_buf(),
+        analysis,
+        backend,
+        backend_timeout,
+        fixes,
+        resolution_failure: None,
+        manifest,
+    })
+}
+
+/// Do what's asked for after a project's report: print its dependency usage, fix its
+/// dependency file, and write its SBOM
+fn apply_actions(
+    check_args: &CheckArgs,
+    project: &CheckedProject,
+    cancel: &CancellationToken,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Nothing was checked to act on
+    if project.resolution_failure.is_some() {
+        return Ok(());
+    }
+    let analysis = &project.analysis;
+    // Reports of merged findings have no project to print the usage of, so it's printed
+    // with each project's other actions
+    if check_args.aggregate && check_args.report.stats_deps && check_args.report.is_text() {
+        print!("{}", report::render_dependency_usage(analysis));
+    }
+    if check_args.fix {
+        fix_unused(analysis, check_args)?;
+    }
+    if check_args.add_missing {
+        add_missing(
+            analysis,
+            &project.backend,
+            check_args,
+            project.backend_timeout,
+            cancel,
+        )?;
+    }
+    if let Some(path) = &check_args.emit_sbom {
+        let environment = analysis.environment().or(project.manifest.as_deref());
+        emit_sbom(analysis, &project.path, environment, check_args, path)?;
+    }
+    Ok(())
+}
+
+/// The files under `root`, relative to it, changed on the branch since it forked from
+/// `base`, along with the uncommitted and untracked ones. `flag` is the option that needs
+/// them, for the error when git can't be run.
+fn changed_files(
+    root: &Path,
+    base: &str,
+    flag: &str,
+) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let range = format!("{}...HEAD", base);
+    let listings = [
+        vec!["diff", "--name-only", "-z", "--relative", range.as_str()],
+        vec!["diff", "--name-only", "-z", "--relative", "HEAD"],
+        vec!["ls-files", "-z", "--others", "--exclude-standard"],
+    ];
+    let root = if root.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        root
+    };
+    let mut files = Vec::new();
+    for args in listings {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(root)
+            .args(&args)
+            .output()
+            .map_err(|e| format!("{} needs git, which couldn't be run: {}", flag, e))?;
+        if !output.status.success() {
+            return Err(format!(
+                "git {} failed: {}",
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            )
+            .into());
+        }
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        for name in stdout.split('\0').filter(|name| !name.is_empty()) {
+            let path = PathBuf::from(name);
+            if !files.contains(&path) {
+                files.push(path);
+            }
+        }
+    }
+    Ok(files)
+}
+
+fn is_python(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension == "py" || extension == "pyi")
+}
+
+fn emit_sbom(
+    analysis: &depwise_analysis::AnalysisReport,
+    project: &Path,
+    environment: Option<&depwise_analysis::ResolvedEnvironment>,
+    check_args: &CheckArgs,
+    path: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let project = std::fs::canonicalize(project)?;
+    let name = project
+        .file_stem()
+        .map_or_else(|| "project".into(), |name| name.to_string_lossy());
+    let sbom = depwise_analysis::sbom::cyclonedx(
+        &name,
+        analysis.sources(),
+        environment,
+        check_args.offline,
+    )?;
+    std::fs::write(path, serde_json::to_string_pretty(&sbom)? + "\n")?;
+    // JSON output is only the report
+    if check_args.report.is_text() {
+        println!("Wrote SBOM to {}", path.display());
+    }
+    Ok(())
+}
+
+/// Print what `--fix` or `--add-missing` changed, on stderr when stdout is the JSON report
+fn print_action(check_args: &CheckArgs, message: &str) {
+    if check_args.report.is_text() {
+        println!("{}", message);
+    } else {
+        eprintln!("{}", message);
+    }
+}
+
+fn fix_unused(
+    analysis: &depwise_analysis::AnalysisReport,
+    check_args: &CheckArgs,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if analysis.unused_dependencies().is_empty() {
+        print_action(check_args, "No unused dependencies to remove");
+        return Ok(());
+    }
+    let Some(source) = analysis.source() else {
+        return Err("No dependency file was found to fix".into());
+    };
+
+    let dry_run = check_args.dry_run;
+    let fix = depwise_analysis::fix::remove_unused_dependencies(
+        source,
+        analysis.unused_dependencies(),
+        dry_run,
+    )?;
+
+    let action = if dry_run { "Would remove" } else { "Removed" };
+    for declaration in &fix.removed {
+        let message = format!("{} `{}` from {}", action, declaration, fix.path.display());
+        print_action(check_args, &message);
+    }
+    for name in &fix.skipped {
+        let message = format!(
+            "Skipped `{}`: it can't be safely removed from {}",
+            name,
+            fix.path.display()
+        );
+        print_action(check_args, &message);
+    }
+    Ok(())
+}
 
-    let environment = match check_args.environment {
-        env if env.current_environment => None,
-        env if env.pyproject.is_some() => env
-            .pyproject
-            .map(depwise_analysis::EnvironmentBuilderSource::PyProjectToml),
-        env if env.requirements.is_some() => env
-            .requirements
-            .map(depwise_analysis::EnvironmentBuilderSource::RequirementsTxt),
-        env if env.condayml.is_some() => env
-            .condayml
-            .map(depwise_analysis::EnvironmentBuilderSource::CondaEnvironmentYml),
-        _ => None,
+fn add_missing(
+    analysis: &depwise_analysis::AnalysisReport,
+    backend: &depwise_analysis::EnvironmentBackend,
+    check_args: &CheckArgs,
+    backend_timeout: Option<Duration>,
+    cancel: &CancellationToken,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let requirements = depwise_analysis::fix::missing_requirements(
+        analysis,
+        backend,
+        check_args.include_optional,
+        check_args.pin_style.map(Into::into),
+        check_args.offline,
+        backend_timeout,
+        cancel,
+    )?;
+    if requirements.is_empty() {
+        print_action(check_args, "No missing dependencies to add");
+        return Ok(());
+    }
+    let Some(source) = analysis.source() else {
+        return Err("No dependency file was found to fix".into());
     };
 
-    let analysis =
-        depwise_analysis::analyze_project(environment, check_args.backend.into(), &check_args.path);
+    let dry_run = check_args.dry_run;
+    let fix = depwise_analysis::fix::add_missing_dependencies(source, &requirements, dry_run)?;
 
+    let action = if dry_run { "Would add" } else { "Added" };
+    for requirement in &fix.added {
+        let message = format!("{} `{}` to {}", action, requirement, fix.path.display());
+        print_action(check_args, &message);
+    }
     Ok(())
 }
@@ -1,9 +1,91 @@
+use std::path::{Path, PathBuf};
+
+use depwise_analysis::EnvironmentBuilderSource;
+
 use crate::cli::CheckPackageArgs;
+use crate::cli::report;
 
 pub fn execute(args: CheckPackageArgs) -> Result<(), Box<dyn std::error::Error>> {
-    println!(
-        "Checking dependencies for {}",
-        args.package.to_string_lossy()
-    );
+    if args.report.is_text() {
+        println!(
+            "Checking dependencies for {}",
+            args.package.to_string_lossy()
+        );
+    }
+
+    // Archives are unpacked to read their metadata and scan their sources
+    let unpacked = match is_archive(&args.package) {
+        true => {
+            let directory = tempfile::tempdir()?;
+            let root = depwise_analysis::project::unpack_archive(&args.package, directory.path())?;
+            Some((directory, root))
+        }
+        false => None,
+    };
+    let package = unpacked
+        .as_ref()
+        .map_or(args.package.as_path(), |(_, root)| root);
+    let (root, metadata) = find_metadata(package)?;
+    let mut options = depwise_analysis::AnalysisOptions::builder(&root)
+        .backend(args.backend.into())
+        .source(EnvironmentBuilderSource::PackageMetadata(metadata));
+    for extra in args.extras {
+        options = options.extra(extra);
+    }
+    let analysis = depwise_analysis::Analysis::run(&options.build())?;
+    let project = report::Project {
+        path: &args.package,
+        root: &root,
+        analysis: &analysis,
+        fixes: &[],
+        resolution_failure: None,
+    };
+    print!("{}", report::render(&project, &args.report)?);
+    report::exit_on_findings([&analysis], &args.report);
     Ok(())
 }
+
+/// Whether a package is given as a wheel or source distribution archive
+fn is_archive(package: &Path) -> bool {
+    let name = package.to_string_lossy();
+    package.is_file()
+        && [".whl", ".tar.gz", ".tgz", ".zip"]
+            .iter()
+            .any(|extension| name.ends_with(extension))
+}
+
+/// Find the core metadata of an unpacked package and the directory its sources are in: a
+/// source distribution's `PKG-INFO`, or the `METADATA` in a wheel's `.dist-info` directory.
+/// The metadata file can also be given directly.
+fn find_metadata(package: &Path) -> Result<(PathBuf, PathBuf), Box<dyn std::error::Error>> {
+    if package.is_file() {
+        let parent = package.parent().unwrap_or(Path::new("."));
+        let root = match parent.extension() {
+            Some(extension) if extension == "dist-info" => parent.parent().unwrap_or(parent),
+            _ => parent,
+        };
+        return Ok((root.to_path_buf(), package.to_path_buf()));
+    }
+
+    let pkg_info = package.join("PKG-INFO");
+    if pkg_info.is_file() {
+        return Ok((package.to_path_buf(), pkg_info));
+    }
+    let mut entries: Vec<PathBuf> = std::fs::read_dir(package)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .collect();
+    entries.sort();
+    entries
+        .into_iter()
+        .filter(|path| path.extension().is_some_and(|e| e == "dist-info"))
+        .map(|path| path.join("METADATA"))
+        .find(|metadata| metadata.is_file())
+        .map(|metadata| (package.to_path_buf(), metadata))
+        .ok_or_else(|| {
+            format!(
+                "No PKG-INFO or .dist-info/METADATA found in {}",
+                package.display()
+            )
+            .into()
+        })
+}
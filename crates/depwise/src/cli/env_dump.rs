@@ -0,0 +1,48 @@
+use std::fs;
+use std::time::Duration;
+
+use depwise_analysis::AnalysisError;
+
+use crate::cli::EnvDumpArgs;
+
+/// The exit code when the backend fails to resolve the declared dependencies or times out
+const RESOLUTION_FAILED_EXIT_CODE: i32 = 1;
+
+pub fn execute(args: EnvDumpArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let config = depwise_analysis::Config::load(&args.path)?;
+    let mut options = config
+        .apply(depwise_analysis::AnalysisOptions::builder(&args.path))
+        .backend(args.backend.into());
+    if let Some(timeout) = args
+        .backend_timeout
+        .map(Duration::from_secs)
+        .or(config.backend_timeout)
+    {
+        options = options.backend_timeout(timeout);
+    }
+    if let Some(source) = args.environment.into_source() {
+        options = options.source(source);
+    }
+    let environment = match depwise_analysis::Analysis::resolve_environment(&options.build()) {
+        Ok(environment) => environment,
+        // The problems are printed as findings on the declarations they're about, rather
+        // than as the error's debug output
+        Err(AnalysisError::ResolutionFailed(failure)) => {
+            eprintln!("{}", failure);
+            if args.verbose {
+                eprintln!("\n{} output:\n{}", failure.program, failure.output);
+            }
+            std::process::exit(RESOLUTION_FAILED_EXIT_CODE);
+        }
+        Err(e @ AnalysisError::ResolutionTimeout(..)) => {
+            eprintln!("{}", e);
+            std::process::exit(RESOLUTION_FAILED_EXIT_CODE);
+        }
+        Err(e) => return Err(e.into()),
+    };
+    match &args.output {
+        Some(path) => fs::write(path, format!("{}\n", environment.to_json()))?,
+        None => println!("{}", environment.to_json()),
+    }
+    Ok(())
+}
@@ -0,0 +1,100 @@
+use std::path::Path;
+
+use depwise_analysis::project::{self, IncludeTree};
+use depwise_analysis::{Dependency, EnvironmentBuilderSource};
+use serde_json::{Value, json};
+
+use crate::cli::{ListDepsArgs, OutputFormat};
+
+pub fn execute(args: ListDepsArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let source = match args.environment.into_source() {
+        Some(source) => source,
+        None => EnvironmentBuilderSource::infer_from_source_path(&args.path)?,
+    };
+    let mut diagnostics = Vec::new();
+    let configurations = project::extract_configurations_with_diagnostics(
+        source.clone(),
+        args.offline,
+        Some(depwise_analysis::DEFAULT_MAX_REQUIREMENTS),
+        &mut diagnostics,
+    )?;
+    for diagnostic in &diagnostics {
+        eprintln!(
+            "warning: skipped `{}` at {}: {}",
+            diagnostic.text, diagnostic.location, diagnostic.message
+        );
+    }
+    // Paths are shown relative to the directory of the dependency file
+    let base = source.path().parent().unwrap_or(Path::new(""));
+
+    match args.format {
+        OutputFormat::Json => {
+            let listed: Vec<Value> = configurations
+                .iter()
+                .map(|configuration| {
+                    let dependencies: Vec<Value> = configuration
+                        .dependencies()
+                        .iter()
+                        .map(|dependency| {
+                            let provenance = dependency
+                                .name()
+                                .and_then(|name| configuration.provenance(name));
+                            json!({
+                                "requirement": requirement(dependency),
+                                "declared_in": provenance.map(|p| p.declared.to_string()),
+                                "included_via": provenance.map_or_else(Vec::new, |p| {
+                                    p.included_via.iter().map(ToString::to_string).collect()
+                                }),
+                            })
+                        })
+                        .collect();
+                    json!({
+                        "name": configuration.name(),
+                        "extra": configuration.extra(),
+                        "environment": configuration.environment(),
+                        "dependencies": dependencies,
+                    })
+                })
+                .collect();
+            // Every configuration of a requirements file has the same includes
+            let includes: Option<&IncludeTree> = configurations
+                .first()
+                .and_then(|configuration| configuration.includes());
+            let output = json!({
+                "source": source.path().display().to_string(),
+                "includes": includes,
+                "configurations": listed,
+            });
+            println!("{}", serde_json::to_string_pretty(&output)?);
+        }
+        OutputFormat::Text => {
+            for configuration in &configurations {
+                println!("{}:", configuration.name());
+                for dependency in configuration.dependencies() {
+                    let provenance = dependency
+                        .name()
+                        .and_then(|name| configuration.provenance(name));
+                    match provenance {
+                        Some(provenance) => println!(
+                            "  {}  (declared in {})",
+                            requirement(dependency),
+                            provenance.describe(base)
+                        ),
+                        None => println!("  {}", requirement(dependency)),
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The dependency as it would be declared
+fn requirement(dependency: &Dependency) -> String {
+    match dependency {
+        Dependency::PyPI(requirement) => requirement.to_string(),
+        Dependency::Conda(spec) => spec.name().to_string(),
+        Dependency::PackageUrl { url, .. } | Dependency::PackageVcs { url, .. } => url.clone(),
+        Dependency::PackagePath { path, .. } => path.display().to_string(),
+    }
+}
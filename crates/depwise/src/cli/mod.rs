@@ -1,7 +1,11 @@
 mod check;
 mod check_package;
+mod env_dump;
+mod list_deps;
+mod report;
+mod serve;
 
-use clap::{Args, Parser, Subcommand, ValueEnum};
+use clap::{ArgGroup, Args, Parser, Subcommand, ValueEnum};
 use std::path::PathBuf;
 /// CLI for depwise
 #[derive(Debug, Parser)]
@@ -13,11 +17,14 @@ pub struct Cli {
 
 #[derive(Debug, Subcommand)]
 pub enum Commands {
-    Check(CheckArgs),
+    Check(Box<CheckArgs>),
     CheckPackage(CheckPackageArgs),
+    EnvDump(EnvDumpArgs),
+    ListDeps(ListDepsArgs),
+    Serve(ServeArgs),
 }
 
-#[derive(Debug, Args)]
+#[derive(Debug, Clone, Args)]
 #[group(required = false, multiple = false)]
 pub struct Environment {
     /// Path to the pyproject.toml file
@@ -32,12 +39,46 @@ pub struct Environment {
     #[arg(short, long, value_name = "FILE", value_hint = clap::ValueHint::FilePath)]
     condayml: Option<PathBuf>,
 
+    /// Path to the pixi.toml manifest
+    #[arg(long, value_name = "FILE", value_hint = clap::ValueHint::FilePath)]
+    pixi: Option<PathBuf>,
+
+    /// Path to the poetry.lock file, whose pins are checked against instead of the
+    /// dependencies declared in pyproject.toml
+    #[arg(long, value_name = "FILE", value_hint = clap::ValueHint::FilePath)]
+    poetry_lock: Option<PathBuf>,
+
     /// Current environment to use for validation.
     /// A `python3` bin from the environment must be on the $PATH.
     #[arg(short = 'e', long)]
     current_environment: bool,
 }
 
+impl Environment {
+    /// The dependency source selected on the command line, if any
+    fn into_source(self) -> Option<depwise_analysis::EnvironmentBuilderSource> {
+        match self {
+            env if env.current_environment => None,
+            env if env.pyproject.is_some() => env
+                .pyproject
+                .map(depwise_analysis::EnvironmentBuilderSource::PyProjectToml),
+            env if env.requirements.is_some() => env
+                .requirements
+                .map(depwise_analysis::EnvironmentBuilderSource::RequirementsTxt),
+            env if env.condayml.is_some() => env
+                .condayml
+                .map(depwise_analysis::EnvironmentBuilderSource::CondaEnvironmentYml),
+            env if env.pixi.is_some() => env
+                .pixi
+                .map(depwise_analysis::EnvironmentBuilderSource::PixiToml),
+            env if env.poetry_lock.is_some() => env
+                .poetry_lock
+                .map(depwise_analysis::EnvironmentBuilderSource::PoetryLock),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
 pub enum EnvironmentBackend {
     /// Automatically choose the best backend for the environment file
@@ -74,12 +115,169 @@ impl From<EnvironmentBackend> for depwise_analysis::EnvironmentBackend {
     }
 }
 
+/// How dependencies added for missing imports are pinned
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum PinStyle {
+    /// The version itself, like `==1.2.3`
+    Exact,
+
+    /// Releases compatible with its minor version, like `~=1.2`
+    Compatible,
+
+    /// The version or any later one, like `>=1.2.3`
+    Minimum,
+}
+
+impl From<PinStyle> for depwise_analysis::fix::PinStyle {
+    fn from(style: PinStyle) -> Self {
+        match style {
+            PinStyle::Exact => depwise_analysis::fix::PinStyle::Exact,
+            PinStyle::Compatible => depwise_analysis::fix::PinStyle::Compatible,
+            PinStyle::Minimum => depwise_analysis::fix::PinStyle::Minimum,
+        }
+    }
+}
+
+/// How the findings of a check are printed
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// One line per finding, grouped as `--group-by` asks
+    Text,
+
+    /// A JSON object with every finding and how many findings each rule reported
+    Json,
+}
+
+/// How the findings of a check are grouped in text output
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum GroupBy {
+    /// Under the source they were reported for: the dependency file, or the extra, group,
+    /// or environment of one. Fixes one source at a time.
+    Source,
+
+    /// Under the file they are located in. Findings about a declaration rather than an
+    /// import are under the file declaring it.
+    File,
+
+    /// Under the distribution they are about, with where it's imported and declared. Imports
+    /// count for the distribution providing the module, like `pyyaml` for `yaml`.
+    Dependency,
+
+    /// Under the rule that reported them
+    Rule,
+}
+
+/// The lowest severity of the findings that are printed
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum MinSeverity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl From<MinSeverity> for depwise_analysis::Severity {
+    fn from(severity: MinSeverity) -> Self {
+        match severity {
+            MinSeverity::Info => depwise_analysis::Severity::Info,
+            MinSeverity::Warning => depwise_analysis::Severity::Warning,
+            MinSeverity::Error => depwise_analysis::Severity::Error,
+        }
+    }
+}
+
+/// The lowest severity of the findings that fail the check
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum FailOn {
+    Info,
+    Warning,
+    Error,
+
+    /// Exit with code 0 whatever was found
+    Never,
+}
+
+impl FailOn {
+    /// The lowest severity that fails the check, `None` if nothing does
+    pub(crate) fn severity(self) -> Option<depwise_analysis::Severity> {
+        match self {
+            FailOn::Info => Some(depwise_analysis::Severity::Info),
+            FailOn::Warning => Some(depwise_analysis::Severity::Warning),
+            FailOn::Error => Some(depwise_analysis::Severity::Error),
+            FailOn::Never => None,
+        }
+    }
+}
+
+/// How the paths of reported locations are printed
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum PathStyle {
+    /// Relative to the project root
+    #[default]
+    Relative,
+
+    /// Absolute, from the filesystem root
+    Absolute,
+}
+
+/// Options for printing findings, shared by the commands that report them
+#[derive(Debug, Args)]
+pub struct ReportArgs {
+    /// How to print the findings
+    #[arg(long, value_enum, default_value = "text")]
+    format: OutputFormat,
+
+    /// Print only how many findings each rule reported. JSON output still lists every
+    /// finding.
+    #[arg(long)]
+    summary_only: bool,
+
+    /// Hide findings below this severity from the output. The exit code isn't affected.
+    #[arg(long, value_enum, value_name = "SEVERITY", default_value = "info")]
+    min_severity: MinSeverity,
+
+    /// Exit with code 1 if there are findings of this severity or above, whether or not
+    /// they are printed
+    #[arg(long, value_enum, value_name = "SEVERITY", default_value = "error")]
+    fail_on: FailOn,
+
+    /// Group the findings of the text output by the source they were reported for, by
+    /// dependency, or by rule. JSON output stays a flat list.
+    #[arg(long, value_enum, value_name = "GROUPING", default_value = "source")]
+    group_by: GroupBy,
+
+    /// Print the paths of findings relative to the project root (the default)
+    #[arg(long, overrides_with = "absolute_paths")]
+    relative_paths: bool,
+
+    /// Print the paths of findings as absolute paths
+    #[arg(long, overrides_with = "relative_paths")]
+    absolute_paths: bool,
+
+    /// Print why each missing or unused dependency was reported: where it's imported or
+    /// declared, and what was checked before reporting it
+    #[arg(long)]
+    explain: bool,
+
+    /// Print how many files were found, parsed, and reused, and how long each phase took,
+    /// after the findings. JSON output lists them under `stats`.
+    #[arg(long)]
+    stats: bool,
+
+    /// Print how many files and import statements use each declared dependency, most
+    /// used first, noting dependencies only imported when guarded, in tests, or for type
+    /// checking, and the ones only used in type annotations. JSON output lists them under
+    /// `dependencies`.
+    #[arg(long)]
+    stats_deps: bool,
+}
+
 /// Check a wheel, sdist, or conda package that all declared dependencies match what is used in the package.
 #[derive(Debug, Parser)]
 #[command(name = "check-package")]
 #[command(about = "Check a wheel, sdist, or conda package")]
 pub struct CheckPackageArgs {
-    /// Path to the package
+    /// Path to the wheel or sdist (.whl, .tar.gz, or .zip), unpacked or not, or its METADATA or
+    /// PKG-INFO file
     #[arg(value_hint = clap::ValueHint::FilePath, value_name = "FILE", required = true)]
     package: PathBuf,
 
@@ -90,13 +288,272 @@ pub struct CheckPackageArgs {
     /// Package extras (python wheel or sdist only)
     #[arg(long, name = "extra", value_name = "EXTRA")]
     extras: Vec<String>,
+
+    #[command(flatten)]
+    report: ReportArgs,
 }
 
 /// Subcommand for checking dependencies
 #[derive(Debug, Parser)]
 #[command(name = "check")]
 #[command(about = "Check a project")]
+#[command(group(ArgGroup::new("fixes").multiple(true)))]
 pub struct CheckArgs {
+    /// Path to the project src root. Several projects, like the subprojects of a monorepo,
+    /// are checked one after another and reported separately unless `--aggregate` is given.
+    #[arg(value_name = "PATH", default_value = ".")]
+    paths: Vec<PathBuf>,
+
+    /// Merge the same finding reported by several of the projects, like a module every one
+    /// of them imports without declaring, into one listing the projects it occurred in.
+    /// Paths are printed relative to the current directory, and JSON output leaves out the
+    /// fixes, stats, and resolver output, which are specific to a project.
+    #[arg(long)]
+    aggregate: bool,
+
+    #[command(flatten)]
+    environment: Environment,
+
+    /// Backend to use for checking dependencies
+    #[arg(long, value_enum, default_value = "auto")]
+    backend: EnvironmentBackend,
+
+    /// Report imported names that are never used in their own file (DW201).
+    /// Imports that are never used don't count towards using a dependency.
+    #[arg(long)]
+    check_unused_imports: bool,
+
+    /// Check test files (`tests/`, `test_*.py`, `*_test.py`, `conftest.py`) against the
+    /// test or dev extra instead of the runtime dependencies (the default)
+    #[arg(long, overrides_with = "exclude_tests")]
+    include_tests: bool,
+
+    /// Ignore test files entirely
+    #[arg(long, overrides_with = "include_tests")]
+    exclude_tests: bool,
+
+    /// Check the imports of the Python code blocks of Markdown and reStructuredText files
+    /// against the docs extra. Snippets that don't parse are skipped.
+    #[arg(long)]
+    include_docs: bool,
+
+    /// Skip the Python files under a path, relative to the project root. Can be given more
+    /// than once.
+    #[arg(long = "exclude", value_name = "PATH")]
+    excludes: Vec<PathBuf>,
+
+    /// Analyze the directories skipped by default (hidden ones like `.venv`, `venv`,
+    /// `node_modules`, `site-packages`, `__pycache__`). Paths given to `--exclude` are
+    /// still skipped.
+    #[arg(long)]
+    no_default_excludes: bool,
+
+    /// Walk symlinked directories too. A directory reached again through a link is only
+    /// analyzed once, and virtual environments are never analyzed.
+    #[arg(long)]
+    follow_symlinks: bool,
+
+    /// A TOML or JSON file mapping modules to the distributions that provide them, on top
+    /// of the built-in mapping, like the modules of internal packages
+    #[arg(long, value_name = "FILE")]
+    name_map: Option<PathBuf>,
+
+    /// Treat a module, and the modules under it, as provided by the runtime the project runs
+    /// in, so importing it never needs a declared dependency. Can be given more than once.
+    #[arg(long = "provided", value_name = "MODULE")]
+    provided_modules: Vec<String>,
+
+    /// Treat the modules a runtime provides to the code it runs as provided: `databricks`
+    /// (`pyspark`, `dbutils`, `delta`, ...) or `glue` (`awsglue`, `pyspark`). Can be given
+    /// more than once.
+    #[arg(
+        long = "profile",
+        value_name = "NAME",
+        value_parser = clap::builder::PossibleValuesParser::new(depwise_analysis::name_map::profiles())
+    )]
+    profiles: Vec<String>,
+
+    /// Only check a configuration, named by its extra or environment, like `dev`, or by its
+    /// full name. Can be given more than once.
+    #[arg(long = "config-name", value_name = "NAME")]
+    config_names: Vec<String>,
+
+    /// Only check the Python files changed since a git revision, like `origin/main`: the
+    /// ones changed on the branch since it forked from the revision, and uncommitted and
+    /// untracked ones. The whole project is still read for its own modules. Unused
+    /// dependencies aren't reported, since they need every file.
+    #[arg(long, value_name = "REF")]
+    changed_since: Option<String>,
+
+    /// Parse only the Python files changed since a git revision, like `origin/main`, and
+    /// reuse the imports of the others from the state `--state-file` saved at that revision,
+    /// so unused dependencies are still reported. Every file is parsed again when a
+    /// dependency manifest changed or the state is missing or was saved by another version.
+    /// Files changed between the state's revision and REF's merge base keep their saved
+    /// imports, so the state should be saved on the branch REF names.
+    #[arg(
+        long,
+        value_name = "REF",
+        requires = "state_file",
+        conflicts_with = "changed_since"
+    )]
+    since: Option<String>,
+
+    /// Save the imports of every file here after the check, for `--since` to reuse. With
+    /// `--since` the state is only read.
+    #[arg(long, value_name = "FILE", value_hint = clap::ValueHint::FilePath)]
+    state_file: Option<PathBuf>,
+
+    /// Remove unused dependencies from the requirements.txt or pyproject.toml file
+    #[arg(long, group = "fixes")]
+    fix: bool,
+
+    /// Add dependencies for missing imports to the requirements.txt or pyproject.toml file
+    #[arg(long, group = "fixes")]
+    add_missing: bool,
+
+    /// Also add dependencies for optional imports (guarded by an `ImportError` handler)
+    #[arg(long, requires = "add_missing")]
+    include_optional: bool,
+
+    /// Pin added dependencies to the version in use: the installed one, or the latest one on
+    /// PyPI with the simulated backend
+    #[arg(long, value_name = "STYLE", requires = "add_missing")]
+    pin_style: Option<PinStyle>,
+
+    /// Don't download remote requirements files included with `-r <url>`, the repodata of
+    /// conda channels (the repodata cached by earlier runs is still used), or the releases
+    /// of declared dependencies on PyPI (DW404 isn't checked)
+    #[arg(long)]
+    offline: bool,
+
+    /// Don't report a rule, given by code like `DW404` or by name like
+    /// `unavailable-version`. Skipping DW404 also skips looking the declared versions up on
+    /// PyPI. Can be given more than once.
+    #[arg(long = "skip-rule", value_name = "RULE")]
+    skip_rules: Vec<depwise_analysis::Rule>,
+
+    /// Fail on the first dependency file with a declaration that can't be parsed, instead of
+    /// skipping the declaration and reporting it
+    #[arg(long)]
+    strict_parse: bool,
+
+    /// Skip Python files larger than this many bytes with a warning instead of parsing them,
+    /// like runaway generated modules. Defaults to 5 MiB (5242880); 0 parses files of any size.
+    #[arg(long, value_name = "BYTES")]
+    max_file_size: Option<u64>,
+
+    /// Fail when a requirements file declares more than this many requirements with the files
+    /// it includes with `-r`, like an include tree fanning out by accident. Defaults to
+    /// 100000; 0 allows any number.
+    #[arg(long, value_name = "COUNT")]
+    max_requirements: Option<usize>,
+
+    /// The Python version the project targets, like 3.11, which decides what is in the
+    /// standard library. Defaults to the interpreter a `.python-version` file names with
+    /// `--backend current`, otherwise the one a conda environment or pixi manifest pins.
+    #[arg(long, value_name = "X.Y")]
+    python_version: Option<depwise_analysis::PythonVersion>,
+
+    /// Check only the configurations of this platform, like linux-64, when a pixi manifest
+    /// declares dependencies for some platforms only. Can be given more than once.
+    #[arg(long = "platform", value_name = "PLATFORM")]
+    platforms: Vec<String>,
+
+    /// Stop the check if it takes longer than this many seconds (exits with code 3)
+    #[arg(long, value_name = "SECS")]
+    timeout: Option<u64>,
+
+    /// Stop commands run by the environment backend after this many seconds. Overrides
+    /// `backend-timeout` from the project's depwise settings.
+    #[arg(long, value_name = "SECS")]
+    backend_timeout: Option<u64>,
+
+    /// Print the changes `--fix` or `--add-missing` would make without writing them
+    #[arg(long, requires = "fixes")]
+    dry_run: bool,
+
+    /// Check against an environment manifest written by `depwise env-dump` instead of
+    /// resolving the environment with the backend
+    #[arg(long, value_name = "FILE", value_hint = clap::ValueHint::FilePath)]
+    env_manifest: Option<PathBuf>,
+
+    /// Exit with code 4 if the analysis skipped or worked around anything, like a file
+    /// that couldn't be parsed or a backend that fell back to the built-in mapping
+    #[arg(long)]
+    fail_on_warnings: bool,
+
+    /// Write a CycloneDX 1.5 SBOM of the packages the declared dependencies install to this
+    /// file
+    #[arg(long, value_name = "FILE")]
+    emit_sbom: Option<PathBuf>,
+
+    /// Print everything the backend's tools printed when they fail to resolve the
+    /// environment, not only the problems recognized in it. JSON output always includes it.
+    #[arg(short, long)]
+    verbose: bool,
+
+    #[command(flatten)]
+    report: ReportArgs,
+}
+
+/// Print a JSON manifest of the environment a backend checks against, to check against later
+/// with `check --env-manifest`
+#[derive(Debug, Parser)]
+#[command(name = "env-dump")]
+#[command(about = "Print a JSON manifest of the environment a backend checks against")]
+pub struct EnvDumpArgs {
+    /// Path to the project src root
+    #[arg(default_value = ".")]
+    path: PathBuf,
+
+    #[command(flatten)]
+    environment: Environment,
+
+    /// Backend whose environment is described
+    #[arg(long, value_enum, default_value = "auto")]
+    backend: EnvironmentBackend,
+
+    /// Write the manifest to this file instead of stdout
+    #[arg(short, long, value_name = "FILE")]
+    output: Option<PathBuf>,
+
+    /// Stop commands run by the environment backend after this many seconds
+    #[arg(long, value_name = "SECS")]
+    backend_timeout: Option<u64>,
+
+    /// Print everything the backend's tools printed when they fail to resolve the
+    /// environment, not only the problems recognized in it
+    #[arg(short, long)]
+    verbose: bool,
+}
+
+/// List the dependencies a project declares, with where each is declared
+#[derive(Debug, Parser)]
+#[command(name = "list-deps")]
+#[command(about = "List the declared dependencies and the files they're declared in")]
+pub struct ListDepsArgs {
+    /// Path to the project src root
+    #[arg(default_value = ".")]
+    path: PathBuf,
+
+    #[command(flatten)]
+    environment: Environment,
+
+    /// How the dependencies are printed
+    #[arg(long, value_enum, default_value = "text")]
+    format: OutputFormat,
+
+    /// Don't download remote requirements files included with `-r <url>`
+    #[arg(long)]
+    offline: bool,
+}
+
+/// Run a long-lived server answering dependency checks over JSON-RPC on stdio
+#[derive(Debug, Parser)]
+#[command(name = "serve")]
+#[command(about = "Serve diagnostics over JSON-RPC on stdin/stdout")]
+pub struct ServeArgs {
     /// Path to the project src root
     #[arg(default_value = ".")]
     path: PathBuf,
@@ -107,11 +564,18 @@ pub struct CheckArgs {
     /// Backend to use for checking dependencies
     #[arg(long, value_enum, default_value = "auto")]
     backend: EnvironmentBackend,
+
+    /// Report imported names that are never used in their own file (DW201)
+    #[arg(long)]
+    check_unused_imports: bool,
 }
 
 pub fn execute(args: Cli) -> Result<(), Box<dyn std::error::Error>> {
     match args.command {
-        Commands::Check(check_args) => check::execute(check_args),
+        Commands::Check(check_args) => check::execute(*check_args),
         Commands::CheckPackage(check_package_args) => check_package::execute(check_package_args),
+        Commands::EnvDump(env_dump_args) => env_dump::execute(env_dump_args),
+        Commands::ListDeps(list_deps_args) => list_deps::execute(list_deps_args),
+        Commands::Serve(serve_args) => serve::execute(serve_args),
     }
 }
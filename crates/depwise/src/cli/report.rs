@@ -0,0 +1,969 @@
+//! Rendering the findings of a check, shared by every command that prints them.
+//!
+//! The JSON report is an object with `findings`, a list of diagnostics in the shape
+//! `depwise serve` returns them, and `summary`, how many findings each rule code reported.
+//! Each finding has a `fingerprint` that stays the same across runs as long as its rule,
+//! subject, and file do, even when its line moves, for baselines and external tracking.
+//! Findings about more than one place, like an unguarded platform import and the
+//! dependency's declaration, list the others as `related`, printed as `see <path>:<line>`
+//! under the finding in text.
+//! `--min-severity` applies to every format; `--summary-only` only shortens the text
+//! output, since JSON consumers can count the findings themselves. Paths are printed with
+//! `render_path` in every format, relative to the project root unless `--absolute-paths`
+//! is given. Findings keep the stable order the analysis sorts them in, so the output of
+//! two runs over the same tree is identical. With `--explain` the reasoning behind each
+//! missing or unused dependency is printed under it, or added to it as `explanation`.
+//!
+//! JSON findings that can be fixed by editing the dependency file carry a `fix`: a
+//! description and the `edits` to make, each replacing the bytes from `start` to `end` of
+//! a file with `replacement`. Splicing in every unused dependency's edits, or every
+//! missing dependency's, gives the file `--fix` or `--add-missing` would write.
+//!
+//! Text output is grouped with `--group-by`: by the dependency file the findings were
+//! reported for (the default), by the file they are in, by the distribution they are about,
+//! or by rule. Groups are printed in name or rule order and keep the order of their
+//! findings, and outside the default grouping each finding names the dependency file it was
+//! reported for. A dependency's section starts with every place it's imported.
+//!
+//! Warnings about the analysis itself, like files that couldn't be parsed, are printed in a
+//! `Warnings:` section after the findings, or listed under `warnings` in JSON. They aren't
+//! findings, so `--min-severity` doesn't hide them.
+//!
+//! When the backend fails to resolve the declared dependencies, the imports aren't checked.
+//! The report says which tool failed on what before the problems recognized in its output,
+//! which are the findings, and JSON output adds a `resolution_failure` with the `program`,
+//! its `target`, and everything it printed as `output`.
+//!
+//! Several projects checked together are reported one after another, or in JSON as a
+//! `projects` list of reports, each with the `path` it was checked at. With `--aggregate`
+//! the findings that are the same in several projects, by rule, subject, and message, are
+//! merged instead: the first one found stands for the others and lists every project it
+//! occurred in, under `projects` in JSON.
+//!
+//! With `--stats-deps`, how much each declared dependency is used is printed after the
+//! findings as a table, or listed under `dependencies` in JSON in the shape
+//! `depwise/dependencyUsage` returns them. With `--stats`, what the analysis did and how
+//! long each phase took is printed last, in a `Stats:` section or under `stats` in JSON.
+//! Rendering is timed up to that point, so the time the stats themselves take to print
+//! isn't counted.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use depwise_analysis::env_backend::ResolutionFailure;
+use depwise_analysis::fix::SuggestedFix;
+use depwise_analysis::name_map::normalize_name;
+use depwise_analysis::{
+    AnalysisReport, AnalysisWarning, DependencyUsage, Explanation, Finding, Rule, Severity, Stats,
+};
+use serde::Serialize;
+
+use crate::cli::{GroupBy, OutputFormat, PathStyle, ReportArgs};
+
+/// Exit code used when there are findings at or above the `--fail-on` severity
+pub(crate) const FINDINGS_EXIT_CODE: i32 = 1;
+
+/// A finding as it is serialized in JSON output
+#[derive(Debug, Serialize)]
+pub(crate) struct Diagnostic {
+    code: &'static str,
+    rule: &'static str,
+    severity: String,
+    subject: String,
+    message: String,
+    path: Option<String>,
+    line: Option<usize>,
+    /// Other places the finding is about, as `path:line`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    related: Vec<String>,
+    configuration: Option<String>,
+    fingerprint: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    explanation: Option<DiagnosticExplanation>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fix: Option<DiagnosticFix>,
+    /// The projects an aggregated finding occurred in
+    #[serde(skip_serializing_if = "Option::is_none")]
+    projects: Option<Vec<String>>,
+}
+
+/// The reasoning behind a finding as it is serialized in JSON output
+#[derive(Debug, Serialize)]
+struct DiagnosticExplanation {
+    import_sites: Vec<String>,
+    reasons: Vec<String>,
+}
+
+/// The edits that fix a finding as they are serialized in JSON output
+#[derive(Debug, Serialize)]
+struct DiagnosticFix {
+    description: String,
+    edits: Vec<DiagnosticEdit>,
+}
+
+/// A replacement of the bytes from `start` to `end` of a file
+#[derive(Debug, Serialize)]
+struct DiagnosticEdit {
+    path: String,
+    start: usize,
+    end: usize,
+    replacement: String,
+}
+
+impl Diagnostic {
+    pub(crate) fn new(finding: &Finding, style: PathStyle, root: &Path) -> Self {
+        Self {
+            code: finding.rule.code(),
+            rule: finding.rule.name(),
+            severity: finding.severity.to_string(),
+            subject: finding.subject.clone(),
+            message: finding.message.clone(),
+            path: finding
+                .location
+                .as_ref()
+                .map(|l| render_path(&l.path, style, root)),
+            line: finding.location.as_ref().map(|l| l.line),
+            related: finding
+                .related
+                .iter()
+                .map(|l| format!("{}:{}", render_path(&l.path, style, root), l.line))
+                .collect(),
+            configuration: finding.configuration.clone(),
+            fingerprint: finding.fingerprint(root),
+            explanation: None,
+            fix: None,
+            projects: None,
+        }
+    }
+
+    /// Include the edits that fix the finding, if there are any
+    fn fixed_by(mut self, fix: Option<&SuggestedFix>, style: PathStyle, root: &Path) -> Self {
+        self.fix = fix.map(|fix| DiagnosticFix {
+            description: fix.description.clone(),
+            edits: fix
+                .edits
+                .iter()
+                .map(|edit| DiagnosticEdit {
+                    path: render_path(&edit.path, style, root),
+                    start: edit.range.start,
+                    end: edit.range.end,
+                    replacement: edit.replacement.clone(),
+                })
+                .collect(),
+        });
+        self
+    }
+
+    /// Include the reasoning behind the finding, if it has any
+    fn explained(mut self, finding: &Finding, style: PathStyle, root: &Path) -> Self {
+        self.explanation = finding
+            .explanation
+            .as_ref()
+            .map(|explanation| DiagnosticExplanation {
+                import_sites: explanation
+                    .import_sites
+                    .iter()
+                    .map(|site| format!("{}:{}", render_path(&site.path, style, root), site.line))
+                    .collect(),
+                reasons: explanation.reasons.clone(),
+            });
+        self
+    }
+}
+
+/// A warning about the analysis as it is serialized in JSON output
+#[derive(Debug, Serialize)]
+struct WarningNotification {
+    code: &'static str,
+    message: String,
+    path: Option<String>,
+}
+
+impl WarningNotification {
+    fn new(warning: &AnalysisWarning, style: PathStyle, root: &Path) -> Self {
+        Self {
+            code: warning.code(),
+            message: warning.message(),
+            path: warning.path().map(|path| render_path(path, style, root)),
+        }
+    }
+}
+
+/// How much a declared dependency is used, as it is serialized in JSON output
+#[derive(Debug, Serialize)]
+pub(crate) struct Usage {
+    name: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    extras: Vec<String>,
+    files: usize,
+    import_sites: usize,
+    guarded_only: bool,
+    test_only: bool,
+    type_checking_only: bool,
+    type_only: bool,
+    script_only: bool,
+    main_guard_only: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    unused_exemption: Option<String>,
+}
+
+impl From<&DependencyUsage> for Usage {
+    fn from(usage: &DependencyUsage) -> Self {
+        Self {
+            name: usage.name().to_string(),
+            extras: usage.extras().to_vec(),
+            files: usage.files(),
+            import_sites: usage.import_sites(),
+            guarded_only: usage.is_guarded_only(),
+            test_only: usage.is_test_only(),
+            type_checking_only: usage.is_type_checking_only(),
+            type_only: usage.is_type_only(),
+            script_only: usage.is_script_only(),
+            main_guard_only: usage.is_main_guard_only(),
+            unused_exemption: usage.unused_exemption().map(|e| e.to_string()),
+        }
+    }
+}
+
+/// The stats of an analysis as they are serialized in JSON output, with times in
+/// milliseconds
+#[derive(Debug, Serialize)]
+struct StatsReport {
+    files_discovered: usize,
+    files_cached: usize,
+    files_parsed: usize,
+    files_without_imports: usize,
+    files_skipped: usize,
+    bytes_parsed: u64,
+    repodata_downloads: usize,
+    repodata_cache_hits: usize,
+    timings_ms: BTreeMap<&'static str, f64>,
+}
+
+impl StatsReport {
+    fn new(stats: &Stats, rendering: Duration) -> Self {
+        Self {
+            files_discovered: stats.files_discovered,
+            files_cached: stats.files_cached,
+            files_parsed: stats.files_parsed,
+            files_without_imports: stats.files_without_imports,
+            files_skipped: stats.files_skipped,
+            bytes_parsed: stats.bytes_parsed,
+            repodata_downloads: stats.repodata_downloads,
+            repodata_cache_hits: stats.repodata_cache_hits,
+            timings_ms: phases(stats, rendering)
+                .into_iter()
+                .map(|(phase, time)| (phase, time.as_secs_f64() * 1000.0))
+                .collect(),
+        }
+    }
+}
+
+/// A backend command that failed to resolve the declared dependencies, in JSON output
+#[derive(Debug, Serialize)]
+struct JsonResolutionFailure<'a> {
+    program: &'a str,
+    target: &'a str,
+    output: &'a str,
+}
+
+impl<'a> From<&'a ResolutionFailure> for JsonResolutionFailure<'a> {
+    fn from(failure: &'a ResolutionFailure) -> Self {
+        Self {
+            program: &failure.program,
+            target: &failure.target,
+            output: &failure.output,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct JsonReport<'a> {
+    findings: Vec<Diagnostic>,
+    summary: BTreeMap<&'static str, usize>,
+    warnings: Vec<WarningNotification>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    resolution_failure: Option<JsonResolutionFailure<'a>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dependencies: Option<Vec<Usage>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stats: Option<StatsReport>,
+}
+
+/// The report of one of several projects checked together, in JSON output
+#[derive(Debug, Serialize)]
+struct JsonProject<'a> {
+    path: String,
+    #[serde(flatten)]
+    report: JsonReport<'a>,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonProjects<'a> {
+    projects: Vec<JsonProject<'a>>,
+}
+
+/// One of several projects checked together
+pub(crate) struct Project<'a> {
+    /// The path the project was checked at, as it was given
+    pub(crate) path: &'a Path,
+    /// The directory its paths are rendered relative to
+    pub(crate) root: &'a Path,
+    pub(crate) analysis: &'a AnalysisReport,
+    /// The edits that fix each of its findings, in the order of the findings
+    pub(crate) fixes: &'a [Option<SuggestedFix>],
+    /// Why its imports weren't checked, when the backend failed to resolve its dependencies
+    pub(crate) resolution_failure: Option<&'a ResolutionFailure>,
+}
+
+impl ReportArgs {
+    /// Whether the output is meant to be read by people, so progress messages can be
+    /// printed along with it
+    pub(crate) fn is_text(&self) -> bool {
+        self.format == OutputFormat::Text
+    }
+
+    /// How many of the findings are severe enough to fail the check with `--fail-on`,
+    /// counting those hidden by `--min-severity`
+    pub(crate) fn failing_findings(&self, analysis: &AnalysisReport) -> usize {
+        match self.fail_on.severity() {
+            Some(severity) => analysis
+                .findings()
+                .iter()
+                .filter(|finding| finding.severity >= severity)
+                .count(),
+            None => 0,
+        }
+    }
+
+    pub(crate) fn path_style(&self) -> PathStyle {
+        if self.absolute_paths {
+            PathStyle::Absolute
+        } else {
+            PathStyle::Relative
+        }
+    }
+}
+
+/// Exit with [`FINDINGS_EXIT_CODE`] if any of the `analyses` has findings that fail the
+/// check with `--fail-on`
+pub(crate) fn exit_on_findings<'a>(
+    analyses: impl IntoIterator<Item = &'a AnalysisReport>,
+    args: &ReportArgs,
+) {
+    let failing: usize = analyses
+        .into_iter()
+        .map(|analysis| args.failing_findings(analysis))
+        .sum();
+    if failing > 0 {
+        eprintln!(
+            "Failing because of {} (--fail-on)",
+            counted(failing, "finding")
+        );
+        std::process::exit(FINDINGS_EXIT_CODE);
+    }
+}
+
+/// `count` followed by `noun`, made plural unless there is exactly one
+pub(crate) fn counted(count: usize, noun: &str) -> String {
+    match count {
+        1 => format!("1 {}", noun),
+        _ => format!("{} {}s", count, noun),
+    }
+}
+
+/// Render a reported path. Relative paths are relative to the project `root`; a path
+/// outside the root is printed as it was reported.
+pub(crate) fn render_path(path: &Path, style: PathStyle, root: &Path) -> String {
+    let absolute = |path: &Path| std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    match style {
+        PathStyle::Absolute => absolute(path).display().to_string(),
+        PathStyle::Relative => {
+            let relative = path.strip_prefix(root).map(Path::to_path_buf).or_else(|_| {
+                absolute(path)
+                    .strip_prefix(absolute(root))
+                    .map(Path::to_path_buf)
+            });
+            match relative {
+                Ok(relative) if relative.as_os_str().is_empty() => ".".to_string(),
+                Ok(relative) => relative.display().to_string(),
+                Err(_) => path.display().to_string(),
+            }
+        }
+    }
+}
+
+/// Render a finding as a line of text, like its `Display` but with the path rendered
+fn render_finding(finding: &Finding, style: PathStyle, root: &Path) -> String {
+    let location = finding
+        .location
+        .as_ref()
+        .map_or_else(String::new, |location| {
+            format!(
+                "{}:{}: ",
+                render_path(&location.path, style, root),
+                location.line
+            )
+        });
+    format!(
+        "{}{} {} [{}] {}",
+        location,
+        finding.severity,
+        finding.rule.code(),
+        finding.rule.name(),
+        finding.message
+    )
+}
+
+/// Render the reasoning behind a finding as lines of text, each starting with `indent`
+fn render_explanation(
+    explanation: &Explanation,
+    indent: &str,
+    style: PathStyle,
+    root: &Path,
+) -> String {
+    let mut output = String::new();
+    if !explanation.import_sites.is_empty() {
+        let sites: Vec<String> = explanation
+            .import_sites
+            .iter()
+            .map(|site| format!("{}:{}", render_path(&site.path, style, root), site.line))
+            .collect();
+        output.push_str(&format!("{}imported at {}\n", indent, sites.join(", ")));
+    }
+    for reason in &explanation.reasons {
+        output.push_str(&format!("{}{}\n", indent, reason));
+    }
+    output
+}
+
+/// Render a finding as text, followed by its explanation when `--explain` is given
+fn render_entry(finding: &Finding, indent: &str, args: &ReportArgs, root: &Path) -> String {
+    let style = args.path_style();
+    let mut line = render_finding(finding, style, root);
+    // Outside the default grouping, the group doesn't tell which file the finding is for
+    if args.group_by != GroupBy::Source
+        && let Some(configuration) = &finding.configuration
+    {
+        line = match finding.location {
+            Some(_) => format!("{} ({})", line, configuration),
+            None => format!("{}: {}", configuration, line),
+        };
+    }
+    let mut output = format!("{}{}\n", indent, line);
+    for location in &finding.related {
+        output.push_str(&format!(
+            "{}  see {}:{}\n",
+            indent,
+            render_path(&location.path, style, root),
+            location.line
+        ));
+    }
+    if let Some(explanation) = finding.explanation.as_ref().filter(|_| args.explain) {
+        output.push_str(&render_explanation(
+            explanation,
+            &format!("{}    ", indent),
+            style,
+            root,
+        ));
+    }
+    output
+}
+
+/// Render a warning about the analysis as a line of text, like its `Display` but with the
+/// path rendered
+fn render_warning(warning: &AnalysisWarning, style: PathStyle, root: &Path) -> String {
+    let path = warning.path().map_or_else(String::new, |path| {
+        format!("{}: ", render_path(path, style, root))
+    });
+    format!("{}warning [{}] {}", path, warning.code(), warning.message())
+}
+
+/// Render the warnings section of the text output, empty if there were no warnings
+fn render_warnings(analysis: &AnalysisReport, style: PathStyle, root: &Path) -> String {
+    if analysis.warnings().is_empty() {
+        return String::new();
+    }
+    let mut output = "Warnings:\n".to_string();
+    for warning in analysis.warnings() {
+        output.push_str(&format!("  {}\n", render_warning(warning, style, root)));
+    }
+    output
+}
+
+/// How long each phase of the analysis took, in the order they run
+fn phases(stats: &Stats, rendering: Duration) -> [(&'static str, Duration); 5] {
+    [
+        ("discovery", stats.discovery),
+        ("parsing", stats.parsing),
+        ("resolving", stats.resolving),
+        ("matching", stats.matching),
+        ("rendering", rendering),
+    ]
+}
+
+/// Render the stats section of the text output
+fn render_stats(stats: &Stats, rendering: Duration) -> String {
+    let mut output = "Stats:\n".to_string();
+    output.push_str(&format!(
+        "  files: {} discovered, {} cached, {} parsed, {} without imports, {} skipped\n",
+        stats.files_discovered,
+        stats.files_cached,
+        stats.files_parsed,
+        stats.files_without_imports,
+        stats.files_skipped
+    ));
+    output.push_str(&format!("  parsed: {} bytes\n", stats.bytes_parsed));
+    let times: Vec<String> = phases(stats, rendering)
+        .into_iter()
+        .map(|(phase, time)| format!("{} {:.1}ms", phase, time.as_secs_f64() * 1000.0))
+        .collect();
+    output.push_str(&format!("  time: {}\n", times.join(", ")));
+    if stats.repodata_downloads + stats.repodata_cache_hits > 0 {
+        output.push_str(&format!(
+            "  repodata: {} downloaded, {} cached\n",
+            stats.repodata_downloads, stats.repodata_cache_hits
+        ));
+    }
+    output
+}
+
+/// Render the table of how much each declared dependency is used, most used first
+pub(crate) fn render_dependency_usage(analysis: &AnalysisReport) -> String {
+    let usage = analysis.dependency_usage();
+    // Dependencies are listed with the extras they're declared with, like `pandas[parquet]`
+    let label = |dependency: &DependencyUsage| match dependency.extras() {
+        [] => dependency.name().to_string(),
+        extras => format!("{}[{}]", dependency.name(), extras.join(",")),
+    };
+    let width = usage
+        .iter()
+        .map(|u| label(u).len())
+        .chain(["DEPENDENCY".len()])
+        .max()
+        .unwrap_or_default();
+    let mut output = format!(
+        "\n{:<width$}  {:>5}  {:>7}  NOTES\n",
+        "DEPENDENCY", "FILES", "IMPORTS"
+    );
+    for dependency in usage {
+        // A dependency exempt from being reported as unused is noted with the reason instead
+        let unused = match dependency.unused_exemption() {
+            Some(exemption) => exemption.to_string(),
+            None => "unused".to_string(),
+        };
+        let notes: Vec<String> = [
+            (dependency.import_sites() == 0, unused),
+            (dependency.is_guarded_only(), "guarded-only".to_string()),
+            (dependency.is_test_only(), "test-only".to_string()),
+            (
+                dependency.is_type_checking_only(),
+                "type-checking-only".to_string(),
+            ),
+            // Imports under `if TYPE_CHECKING:` are type-only too, which is noted already
+            (
+                dependency.is_type_only() && !dependency.is_type_checking_only(),
+                "type-only".to_string(),
+            ),
+            (dependency.is_script_only(), "script-only".to_string()),
+            (
+                dependency.is_main_guard_only(),
+                "main-guard-only".to_string(),
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(applies, note)| applies.then_some(note))
+        .collect();
+        let line = format!(
+            "{:<width$}  {:>5}  {:>7}  {}",
+            label(dependency),
+            dependency.files(),
+            dependency.import_sites(),
+            notes.join(", ")
+        );
+        output.push_str(line.trim_end());
+        output.push('\n');
+    }
+    output
+}
+
+/// Render the findings of a check in the format the options ask for, followed by the
+/// dependency usage and the stats of the analysis if asked for. Paths are rendered relative
+/// to the project root.
+pub(crate) fn render(project: &Project, args: &ReportArgs) -> Result<String, serde_json::Error> {
+    let started = Instant::now();
+    let analysis = project.analysis;
+    let mut output = String::new();
+    if let Some(failure) = project.resolution_failure
+        && args.is_text()
+    {
+        output.push_str(&render_resolution_failure(failure));
+    }
+    output.push_str(&render_findings(project, args, started)?);
+    if args.stats_deps && args.format == OutputFormat::Text {
+        output.push_str(&render_dependency_usage(analysis));
+    }
+    if args.stats && args.format == OutputFormat::Text {
+        output.push_str(&render_stats(analysis.stats(), started.elapsed()));
+    }
+    Ok(output)
+}
+
+/// Say which tool failed to resolve what, so the imports weren't checked. The tool's last
+/// words explain it when nothing in its output was recognized as a finding.
+fn render_resolution_failure(failure: &ResolutionFailure) -> String {
+    let summary = if failure.findings.is_empty() {
+        failure.to_string()
+    } else {
+        format!("{} failed to resolve {}", failure.program, failure.target)
+    };
+    format!("{}, so the imports weren't checked\n", summary)
+}
+
+/// Render everything the tool that failed to resolve the dependencies printed, for
+/// `--verbose` text output
+pub(crate) fn render_resolver_output(failure: &ResolutionFailure) -> String {
+    format!("{} output:\n{}\n", failure.program, failure.output)
+}
+
+/// Render the findings of a project in the format the options ask for. JSON output includes
+/// the stats, with rendering timed from `started`, and the fix of each finding.
+fn render_findings(
+    project: &Project,
+    args: &ReportArgs,
+    started: Instant,
+) -> Result<String, serde_json::Error> {
+    let analysis = project.analysis;
+    let root = project.root;
+    let style = args.path_style();
+    let min_severity = Severity::from(args.min_severity);
+    let shown = |finding: &&Finding| finding.severity >= min_severity;
+    match args.format {
+        OutputFormat::Json => {
+            let report = json_report(project, args, started);
+            Ok(serde_json::to_string_pretty(&report)? + "\n")
+        }
+        OutputFormat::Text if args.summary_only => {
+            let findings: Vec<&Finding> = analysis.findings().iter().filter(shown).collect();
+            let mut output = String::new();
+            for (rule, count) in counts(&findings) {
+                output.push_str(&format!("{}: {}\n", rule, count));
+            }
+            output.push_str(&format!("{}\n", counted(findings.len(), "finding")));
+            if !analysis.warnings().is_empty() {
+                output.push_str(&format!(
+                    "{}\n",
+                    counted(analysis.warnings().len(), "warning")
+                ));
+            }
+            Ok(output)
+        }
+        OutputFormat::Text if args.group_by != GroupBy::Source => {
+            let findings = analysis.findings().iter().filter(shown);
+            let groups: Vec<(String, Vec<&Finding>)> = if args.group_by == GroupBy::Rule {
+                group(findings, |finding| finding.rule)
+                    .into_iter()
+                    .map(|(rule, findings)| {
+                        (format!("{} [{}]", rule.code(), rule.name()), findings)
+                    })
+                    .collect()
+            } else if args.group_by == GroupBy::File {
+                group(findings, |finding| {
+                    finding_file(analysis, finding)
+                        .map(|path| render_path(path, style, root))
+                        .or_else(|| finding.configuration.clone())
+                        .unwrap_or_else(|| render_path(root, style, root))
+                })
+                .into_iter()
+                .collect()
+            } else {
+                // `Flask` and `flask` are the same dependency, and `import yaml` is about
+                // `pyyaml`
+                group(findings, |finding| analysis.distribution_of(finding))
+                    .into_iter()
+                    .collect()
+            };
+            let mut output = String::new();
+            for (group, findings) in groups {
+                output.push_str(&format!("{}:\n", group));
+                // A dependency's section lists every place it's declared and imported, even
+                // without `--explain`
+                if args.group_by == GroupBy::Dependency {
+                    let mut declarations: Vec<String> = Vec::new();
+                    for location in analysis
+                        .configurations()
+                        .iter()
+                        .filter_map(|configuration| configuration.declaration(&group))
+                    {
+                        let location = format!(
+                            "{}:{}",
+                            render_path(&location.path, style, root),
+                            location.line
+                        );
+                        if !declarations.contains(&location) {
+                            declarations.push(location);
+                        }
+                    }
+                    if !declarations.is_empty() {
+                        output.push_str(&format!("  declared at {}\n", declarations.join(", ")));
+                    }
+                    let mut sites: Vec<String> = Vec::new();
+                    for site in findings
+                        .iter()
+                        .filter_map(|finding| finding.explanation.as_ref())
+                        .flat_map(|explanation| &explanation.import_sites)
+                    {
+                        let site =
+                            format!("{}:{}", render_path(&site.path, style, root), site.line);
+                        if !sites.contains(&site) {
+                            sites.push(site);
+                        }
+                    }
+                    if !sites.is_empty() {
+                        output.push_str(&format!("  imported at {}\n", sites.join(", ")));
+                    }
+                }
+                for finding in findings {
+                    output.push_str(&render_entry(finding, "  ", args, root));
+                }
+            }
+            output.push_str(&render_warnings(analysis, style, root));
+            Ok(output)
+        }
+        OutputFormat::Text => {
+            // The findings that aren't specific to a configuration come first, then the
+            // findings of each configuration grouped under its name
+            let mut output = String::new();
+            for finding in analysis
+                .findings()
+                .iter()
+                .filter(shown)
+                .filter(|finding| finding.configuration.is_none())
+            {
+                output.push_str(&render_entry(finding, "", args, root));
+            }
+            for configuration in analysis.configurations() {
+                let findings: Vec<_> = analysis
+                    .findings()
+                    .iter()
+                    .filter(shown)
+                    .filter(|finding| {
+                        finding.configuration.as_deref() == Some(configuration.name())
+                    })
+                    .collect();
+                if findings.is_empty() {
+                    continue;
+                }
+                output.push_str(&format!("{}:\n", configuration.name()));
+                for finding in findings {
+                    output.push_str(&render_entry(finding, "  ", args, root));
+                }
+            }
+            output.push_str(&render_warnings(analysis, style, root));
+            Ok(output)
+        }
+    }
+}
+
+/// The JSON report of a project, with rendering timed from `started` for its stats
+fn json_report<'a>(project: &Project<'a>, args: &ReportArgs, started: Instant) -> JsonReport<'a> {
+    let analysis = project.analysis;
+    let root = project.root;
+    let style = args.path_style();
+    let min_severity = Severity::from(args.min_severity);
+    let shown = |finding: &&Finding| finding.severity >= min_severity;
+    let findings: Vec<(usize, &Finding)> = analysis
+        .findings()
+        .iter()
+        .enumerate()
+        .filter(|(_, finding)| shown(finding))
+        .collect();
+    JsonReport {
+        summary: counts(
+            &findings
+                .iter()
+                .map(|(_, finding)| *finding)
+                .collect::<Vec<_>>(),
+        )
+        .into_iter()
+        .map(|(rule, count)| (rule.code(), count))
+        .collect(),
+        findings: findings
+            .into_iter()
+            .map(|(i, finding)| {
+                let fix = project.fixes.get(i).and_then(Option::as_ref);
+                let diagnostic = Diagnostic::new(finding, style, root).fixed_by(fix, style, root);
+                if args.explain {
+                    diagnostic.explained(finding, style, root)
+                } else {
+                    diagnostic
+                }
+            })
+            .collect(),
+        warnings: analysis
+            .warnings()
+            .iter()
+            .map(|warning| WarningNotification::new(warning, style, root))
+            .collect(),
+        resolution_failure: project.resolution_failure.map(JsonResolutionFailure::from),
+        dependencies: args.stats_deps.then(|| {
+            analysis
+                .dependency_usage()
+                .iter()
+                .map(Usage::from)
+                .collect()
+        }),
+        stats: args
+            .stats
+            .then(|| StatsReport::new(analysis.stats(), started.elapsed())),
+    }
+}
+
+/// Render the reports of several projects checked together as JSON, each under the path it
+/// was checked at
+pub(crate) fn render_projects(
+    projects: &[Project],
+    args: &ReportArgs,
+) -> Result<String, serde_json::Error> {
+    let report = JsonProjects {
+        projects: projects
+            .iter()
+            .map(|project| JsonProject {
+                path: project.path.display().to_string(),
+                report: json_report(project, args, Instant::now()),
+            })
+            .collect(),
+    };
+    Ok(serde_json::to_string_pretty(&report)? + "\n")
+}
+
+/// Render the findings of several projects checked together, merging the ones that are the
+/// same in several of them. Paths are rendered relative to the current directory, while
+/// fingerprints stay relative to the root of the project the finding was first found in, as
+/// they are when it's checked on its own.
+pub(crate) fn render_aggregated(
+    projects: &[Project],
+    args: &ReportArgs,
+) -> Result<String, serde_json::Error> {
+    let root = Path::new(".");
+    let style = args.path_style();
+    let min_severity = Severity::from(args.min_severity);
+    // Each merged finding, with the root of the first project it occurred in and the names
+    // of all of them
+    let mut merged: Vec<(&Finding, &Path, Vec<String>)> = Vec::new();
+    for project in projects {
+        let name = project.path.display().to_string();
+        for finding in project
+            .analysis
+            .findings()
+            .iter()
+            .filter(|finding| finding.severity >= min_severity)
+        {
+            let same = |other: &Finding| {
+                other.rule == finding.rule
+                    && normalize_name(&other.subject) == normalize_name(&finding.subject)
+                    && other.message == finding.message
+            };
+            match merged.iter_mut().find(|(other, _, _)| same(other)) {
+                Some((_, _, names)) if !names.contains(&name) => names.push(name.clone()),
+                Some(_) => {}
+                None => merged.push((finding, project.root, vec![name.clone()])),
+            }
+        }
+    }
+    let warnings: Vec<&AnalysisWarning> = projects
+        .iter()
+        .flat_map(|project| project.analysis.warnings())
+        .collect();
+    let findings: Vec<&Finding> = merged.iter().map(|(finding, _, _)| *finding).collect();
+
+    let mut output = String::new();
+    match args.format {
+        OutputFormat::Json => {
+            let report = JsonReport {
+                summary: counts(&findings)
+                    .into_iter()
+                    .map(|(rule, count)| (rule.code(), count))
+                    .collect(),
+                findings: merged
+                    .iter()
+                    .map(|(finding, project_root, names)| {
+                        let mut diagnostic = Diagnostic::new(finding, style, root);
+                        if args.explain {
+                            diagnostic = diagnostic.explained(finding, style, root);
+                        }
+                        diagnostic.fingerprint = finding.fingerprint(project_root);
+                        diagnostic.projects = Some(names.clone());
+                        diagnostic
+                    })
+                    .collect(),
+                warnings: warnings
+                    .iter()
+                    .map(|warning| WarningNotification::new(warning, style, root))
+                    .collect(),
+                resolution_failure: None,
+                dependencies: None,
+                stats: None,
+            };
+            return Ok(serde_json::to_string_pretty(&report)? + "\n");
+        }
+        OutputFormat::Text if args.summary_only => {
+            for (rule, count) in counts(&findings) {
+                output.push_str(&format!("{}: {}\n", rule, count));
+            }
+            output.push_str(&format!("{}\n", counted(findings.len(), "finding")));
+            if !warnings.is_empty() {
+                output.push_str(&format!("{}\n", counted(warnings.len(), "warning")));
+            }
+        }
+        OutputFormat::Text => {
+            for (finding, _, names) in &merged {
+                output.push_str(&render_entry(finding, "", args, root));
+                output.push_str(&format!("    in {}\n", names.join(", ")));
+            }
+            if !warnings.is_empty() {
+                output.push_str("Warnings:\n");
+                for warning in warnings {
+                    output.push_str(&format!("  {}\n", render_warning(warning, style, root)));
+                }
+            }
+        }
+    }
+    Ok(output)
+}
+
+/// How many findings each rule reported, in rule order
+fn counts(findings: &[&Finding]) -> BTreeMap<Rule, usize> {
+    let mut counts = BTreeMap::new();
+    for finding in findings {
+        *counts.entry(finding.rule).or_default() += 1;
+    }
+    counts
+}
+
+/// Group findings by a key, in key order, keeping the order of the findings in each group
+/// The file a finding is in: where it's located, or for a finding about a dependency without
+/// a location, the file declaring it
+fn finding_file<'a>(analysis: &'a AnalysisReport, finding: &'a Finding) -> Option<&'a Path> {
+    if let Some(location) = &finding.location {
+        return Some(&location.path);
+    }
+    analysis
+        .configurations()
+        .iter()
+        .filter(|configuration| finding.configuration.as_deref() == Some(configuration.name()))
+        .find_map(|configuration| configuration.declaration(&finding.subject))
+        .map(|location| location.path.as_path())
+}
+
+fn group<'a, K: Ord>(
+    findings: impl Iterator<Item = &'a Finding>,
+    key: impl Fn(&Finding) -> K,
+) -> BTreeMap<K, Vec<&'a Finding>> {
+    let mut groups: BTreeMap<K, Vec<&Finding>> = BTreeMap::new();
+    for finding in findings {
+        groups.entry(key(finding)).or_default().push(finding);
+    }
+    groups
+}
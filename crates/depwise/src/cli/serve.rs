@@ -0,0 +1,301 @@
+//! `depwise serve`: answer dependency checks for an editor over JSON-RPC.
+//!
+//! The server speaks JSON-RPC 2.0 on stdin/stdout. Like the Language Server Protocol, every
+//! message is preceded by a `Content-Length: <bytes>` header and a blank line (`\r\n\r\n`).
+//! Bodies over 16 MiB are skipped and answered with an invalid request error. A
+//! `Content-Length` that isn't a number, or too big to be one, is answered with a parse
+//! error; as the end of that message's body isn't known, everything up to the next
+//! `Content-Length` header is discarded.
+//! Paths in requests may be absolute or relative to the project root; paths in responses
+//! are absolute.
+//!
+//! Requests:
+//!
+//! - `initialize`: returns `{"name": "depwise", "version": "<version>"}`.
+//! - `depwise/check`: checks the whole project and returns `{"diagnostics": [...]}`.
+//! - `depwise/diagnostics` with `{"path": "<file>"}`: returns `{"path": "<file>",
+//!   "diagnostics": [...]}` with the diagnostics located in that file. Only that file is
+//!   parsed and matched against the cached dependencies, so project-wide diagnostics like
+//!   unused dependencies are left to `depwise/check`.
+//! - `depwise/dependencyUsage`: checks the whole project and returns `{"dependencies":
+//!   [...]}` with how much each declared dependency is used, most used first.
+//! - `shutdown`: returns `null`; the server keeps reading until `exit`.
+//!
+//! Notifications:
+//!
+//! - `depwise/didChange` with `{"path": "<file>"}`: drops the cached parse of the file. When
+//!   the file is the project's requirements.txt or pyproject.toml the dependencies are read
+//!   again.
+//! - `exit`: stops the server.
+//!
+//! Each diagnostic is an object with `code` (e.g. `DW101`), `rule`, `severity`, `subject`,
+//! `message`, `path` and `line` (both `null` for project-wide findings), `related` (other
+//! places the finding is about as `path:line`, left out when there are none),
+//! `configuration` (`null` unless the finding is specific to one) and `fingerprint` (a hash
+//! of the rule, subject, and file that stays the same when lines move, as in `depwise check
+//! --format json`).
+//!
+//! Each dependency usage is an object with `name`, `files` and `import_sites` (how many
+//! files and import statements import it), and `guarded_only`, `test_only`,
+//! `type_checking_only` and `script_only` (whether every import is guarded, in a test file,
+//! inside `if TYPE_CHECKING:`, or in a script or `if __name__ == "__main__":` block),
+//! `type_only` (whether only type annotations use what every import binds, if it isn't
+//! under `if TYPE_CHECKING:`), and `main_guard_only` (whether every import is inside
+//! `if __name__ == "__main__":` or binds names only used there).
+//! Dependencies nothing imports that aren't reported as unused have an `unused_exemption`:
+//! `no-modules`, `support-package`, or `ignored`.
+
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use depwise_analysis::{AnalysisError, AnalysisOptions, AnalysisSession, Config, Finding};
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use crate::cli::report::{Diagnostic, Usage};
+use crate::cli::{PathStyle, ServeArgs};
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const INTERNAL_ERROR: i64 = -32603;
+
+/// The largest message body that's read, 16 MiB. Requests only name files, so anything
+/// bigger is a broken client rather than a big request.
+const MAX_MESSAGE_BYTES: usize = 16 * 1024 * 1024;
+
+/// An incoming request or notification
+#[derive(Debug, Deserialize)]
+struct Message {
+    id: Option<Value>,
+    method: Option<String>,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct PathParams {
+    path: PathBuf,
+}
+
+/// A JSON-RPC error to send back for a request
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl From<AnalysisError> for RpcError {
+    fn from(error: AnalysisError) -> Self {
+        Self {
+            code: INTERNAL_ERROR,
+            message: error.to_string(),
+        }
+    }
+}
+
+pub fn execute(serve_args: ServeArgs) -> Result<(), Box<dyn std::error::Error>> {
+    // Work with absolute paths so files are identified the same way however they're named
+    let root = std::fs::canonicalize(&serve_args.path)?;
+    // The project's own settings apply as they do to `depwise check`
+    let config = Config::load(&root)?;
+    let mut options = config
+        .apply(AnalysisOptions::builder(&root))
+        .backend(serve_args.backend.into())
+        .check_unused_imports(serve_args.check_unused_imports);
+    if let Some(timeout) = config.backend_timeout {
+        options = options.backend_timeout(timeout);
+    }
+    if let Some(source) = serve_args.environment.into_source() {
+        options = options.source(source);
+    }
+
+    let mut server = Server {
+        root,
+        session: AnalysisSession::new(options.build())?,
+    };
+    server.run(io::stdin().lock(), io::stdout().lock())?;
+    Ok(())
+}
+
+struct Server {
+    root: PathBuf,
+    session: AnalysisSession,
+}
+
+impl Server {
+    /// Handle messages until `exit` or the end of the input
+    fn run(&mut self, mut input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+        while let Some(body) = read_message(&mut input)? {
+            let body = match body {
+                Ok(body) => body,
+                Err(error) => {
+                    write_message(&mut output, &response(Value::Null, Err(error)))?;
+                    continue;
+                }
+            };
+            let message: Message = match serde_json::from_slice(&body) {
+                Ok(message) => message,
+                Err(e) => {
+                    let error = rpc_error(PARSE_ERROR, &e.to_string());
+                    write_message(&mut output, &response(Value::Null, Err(error)))?;
+                    continue;
+                }
+            };
+
+            let Some(method) = message.method.as_deref() else {
+                if let Some(id) = message.id {
+                    let error = rpc_error(INVALID_REQUEST, "Missing method");
+                    write_message(&mut output, &response(id, Err(error)))?;
+                }
+                continue;
+            };
+
+            match message.id {
+                Some(id) => {
+                    let result = self.handle_request(method, message.params);
+                    write_message(&mut output, &response(id, result))?;
+                }
+                None if method == "exit" => break,
+                None => self.handle_notification(method, message.params),
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_request(&mut self, method: &str, params: Value) -> Result<Value, RpcError> {
+        match method {
+            "initialize" => Ok(json!({
+                "name": "depwise",
+                "version": env!("CARGO_PKG_VERSION"),
+            })),
+            "depwise/check" => {
+                let report = self.session.check()?;
+                Ok(json!({ "diagnostics": diagnostics(report.findings(), &self.root) }))
+            }
+            "depwise/diagnostics" => {
+                let path = self.path_param(params)?;
+                let findings = self.session.check_file(&path)?;
+                Ok(json!({ "path": path, "diagnostics": diagnostics(&findings, &self.root) }))
+            }
+            "depwise/dependencyUsage" => {
+                let report = self.session.check()?;
+                let usage: Vec<Usage> = report.dependency_usage().iter().map(Usage::from).collect();
+                Ok(json!({ "dependencies": usage }))
+            }
+            "shutdown" => Ok(Value::Null),
+            _ => Err(rpc_error(
+                METHOD_NOT_FOUND,
+                &format!("Unknown method {}", method),
+            )),
+        }
+    }
+
+    fn handle_notification(&mut self, method: &str, params: Value) {
+        // Unknown notifications are ignored, as JSON-RPC requires
+        if method != "depwise/didChange" {
+            return;
+        }
+        match self.path_param(params) {
+            Ok(path) => {
+                if let Err(e) = self.session.invalidate(&path) {
+                    eprintln!("Failed to reload {}: {}", path.display(), e);
+                }
+            }
+            Err(e) => eprintln!("Ignoring didChange: {}", e.message),
+        }
+    }
+
+    /// Read the `path` parameter and make it absolute
+    fn path_param(&self, params: Value) -> Result<PathBuf, RpcError> {
+        let params: PathParams = serde_json::from_value(params)
+            .map_err(|e| rpc_error(INVALID_PARAMS, &e.to_string()))?;
+        Ok(absolute_path(&self.root, &params.path))
+    }
+}
+
+fn absolute_path(root: &Path, path: &Path) -> PathBuf {
+    let path = root.join(path);
+    std::fs::canonicalize(&path).unwrap_or(path)
+}
+
+fn diagnostics(findings: &[Finding], root: &Path) -> Vec<Diagnostic> {
+    findings
+        .iter()
+        .map(|finding| Diagnostic::new(finding, PathStyle::Absolute, root))
+        .collect()
+}
+
+fn rpc_error(code: i64, message: &str) -> RpcError {
+    RpcError {
+        code,
+        message: message.to_string(),
+    }
+}
+
+fn response(id: Value, result: Result<Value, RpcError>) -> Value {
+    match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(error) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": error.code, "message": error.message },
+        }),
+    }
+}
+
+/// Read the body of the next message, or `None` at the end of the input. A body larger than
+/// [`MAX_MESSAGE_BYTES`] is skipped, and a message whose `Content-Length` can't be read is
+/// left unread, with the error to answer them with.
+///
+/// The header of a message starts wherever `Content-Length:` does, so a message following
+/// one that was left unread is found even though that body didn't end with a newline.
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<Result<Vec<u8>, RpcError>>> {
+    const HEADER: &str = "content-length:";
+    let mut content_length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            if content_length.is_some() {
+                break;
+            }
+            continue;
+        }
+        if let Some(start) = line.to_ascii_lowercase().find(HEADER) {
+            content_length = Some(line[start + HEADER.len()..].trim().parse::<usize>());
+        }
+    }
+
+    let content_length = match content_length {
+        Some(Ok(length)) => length,
+        Some(Err(e)) => {
+            let message = format!("Bad Content-Length: {}", e);
+            return Ok(Some(Err(rpc_error(PARSE_ERROR, &message))));
+        }
+        None => 0,
+    };
+    if content_length > MAX_MESSAGE_BYTES {
+        let mut body = io::Read::take(&mut *input, content_length as u64);
+        if io::copy(&mut body, &mut io::sink())? < content_length as u64 {
+            return Ok(None);
+        }
+        let message = format!(
+            "Message of {} bytes is larger than the limit of {} bytes",
+            content_length, MAX_MESSAGE_BYTES
+        );
+        return Ok(Some(Err(rpc_error(INVALID_REQUEST, &message))));
+    }
+    let mut body = vec![0; content_length];
+    input.read_exact(&mut body)?;
+    Ok(Some(Ok(body)))
+}
+
+fn write_message(output: &mut impl Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
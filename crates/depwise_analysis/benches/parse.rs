@@ -0,0 +1,37 @@
+//! Compares parsing a module without imports, which skips building the AST, against parsing
+//! the same module when a comment mentioning `import` forces the full parse.
+//!
+//! Run with `cargo bench -p depwise-analysis`.
+
+use std::hint::black_box;
+
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use depwise_analysis::python::imports;
+
+/// A data module like a generated lookup table, with no imports
+fn data_module() -> String {
+    let mut source = String::from("\"\"\"Lookup tables\"\"\"\n\n");
+    for i in 0..500 {
+        source.push_str(&format!(
+            "CODE_{i} = {{\"name\": \"code {i}\", \"value\": {i}}}\n"
+        ));
+    }
+    source
+}
+
+fn parse_imports(c: &mut Criterion) {
+    let source = data_module();
+    let mentions_import = format!("{}# import nothing\n", source);
+
+    let mut group = c.benchmark_group("parse_imports");
+    group.throughput(Throughput::Bytes(source.len() as u64));
+    for (label, source) in [("fast path", &source), ("full parse", &mentions_import)] {
+        group.bench_function(label, |b| {
+            b.iter(|| imports(black_box(source)).expect("the benchmark source parses"))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, parse_imports);
+criterion_main!(benches);
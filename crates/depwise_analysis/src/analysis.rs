@@ -0,0 +1,4759 @@
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::thread;
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+
+use crate::cancel::{CancellationToken, Phase};
+use crate::docs;
+use crate::entry_points;
+use crate::env_backend::{self, ResolvedEnvironment};
+use crate::error::AnalysisError;
+use crate::finding::Explanation;
+use crate::finding::{self, Finding, Location, Rule, Severity};
+use crate::import_lines;
+use crate::installed;
+use crate::layout::PackageLayout;
+use crate::matcher::{ModuleIndex, ModuleProvider, UnusedExemption};
+use crate::name_map::{self, NameMap, normalize_name};
+use crate::options::AnalysisOptions;
+use crate::parser::{self, PythonImport, PythonParser, UnusedImport};
+use crate::project::{self, Configuration, Dependency, ParseOptions, PyPIRequirement};
+#[cfg(feature = "backends-exec")]
+use crate::python_version;
+use crate::stats::{ScanCounters, Stats};
+use crate::stdlib::Backport;
+use crate::{
+    AnalysisWarning, EnvironmentBackend, EnvironmentBuilderSource, PythonVersion, stdlib, walker,
+};
+
+/// An imported module that no declared dependency provides
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct MissingImport {
+    module: String,
+    distribution: String,
+    reachable_via: Option<String>,
+    is_optional: bool,
+    is_generated: bool,
+    fallback: Option<String>,
+    extra: Option<String>,
+    environment: Option<String>,
+    location: Location,
+    import_sites: Vec<Location>,
+}
+
+impl MissingImport {
+    /// The module as it was first imported
+    pub fn module(&self) -> &str {
+        &self.module
+    }
+
+    /// The normalized name of the distribution expected to provide the module
+    pub fn distribution(&self) -> &str {
+        &self.distribution
+    }
+
+    /// The extra of a declared dependency that would install the distribution, like
+    /// `pandas[parquet]` for `pyarrow`, if there is one
+    pub fn reachable_via(&self) -> Option<&str> {
+        self.reachable_via.as_deref()
+    }
+
+    /// Whether every import of the distribution is guarded by an `ImportError` handler
+    pub fn is_optional(&self) -> bool {
+        self.is_optional
+    }
+
+    /// Whether every import of the distribution is in a generated file, like a `*_pb2.py`
+    /// module. These are only reported when the options ask for them.
+    pub fn is_generated(&self) -> bool {
+        self.is_generated
+    }
+
+    /// Which of two imports of the same distribution is reported: an unconditional import
+    /// over a guarded one, and either over an import in generated code
+    fn precedence(&self) -> u8 {
+        match (self.is_generated, self.is_optional) {
+            (true, _) => 0,
+            (false, true) => 1,
+            (false, false) => 2,
+        }
+    }
+
+    /// The module the `ImportError` handler of an optional import falls back to, if any
+    pub fn fallback(&self) -> Option<&str> {
+        self.fallback.as_deref()
+    }
+
+    /// The extra whose code imports the module, `None` when the base code does
+    pub fn extra(&self) -> Option<&str> {
+        self.extra.as_deref()
+    }
+
+    /// The hatch environment whose code imports the module, `None` unless it is one
+    pub fn environment(&self) -> Option<&str> {
+        self.environment.as_deref()
+    }
+
+    /// Where the module was first imported
+    pub fn location(&self) -> &Location {
+        &self.location
+    }
+
+    /// Every place the distribution is imported by the code of the configuration
+    pub fn import_sites(&self) -> &[Location] {
+        &self.import_sites
+    }
+}
+
+/// The outcome of checking one configuration of dependencies
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ConfigurationReport {
+    name: String,
+    extra: Option<String>,
+    environment: Option<String>,
+    unused_dependencies: Vec<String>,
+    missing_imports: Vec<String>,
+    declarations: Vec<(String, Location)>,
+}
+
+impl ConfigurationReport {
+    /// The name of the configuration, e.g. `pyproject.toml[docs]`
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The extra the configuration installs, `None` for the base configuration
+    pub fn extra(&self) -> Option<&str> {
+        self.extra.as_deref()
+    }
+
+    /// The hatch environment the configuration installs, if it comes from one
+    pub fn environment(&self) -> Option<&str> {
+        self.environment.as_deref()
+    }
+
+    /// Dependencies the configuration adds that its code never imports
+    pub fn unused_dependencies(&self) -> &[String] {
+        &self.unused_dependencies
+    }
+
+    /// Modules imported by the configuration's code that none of its dependencies provide
+    pub fn missing_imports(&self) -> &[String] {
+        &self.missing_imports
+    }
+
+    /// Where the configuration declares a dependency, if it does
+    pub fn declaration(&self, name: &str) -> Option<&Location> {
+        let name = normalize_name(name);
+        self.declarations
+            .iter()
+            .find(|(declared, _)| *declared == name)
+            .map(|(_, location)| location)
+    }
+}
+
+/// How much a declared dependency is used by the project
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct DependencyUsage {
+    name: String,
+    extras: Vec<String>,
+    files: usize,
+    import_sites: usize,
+    guarded_only: bool,
+    test_only: bool,
+    type_checking_only: bool,
+    type_only: bool,
+    script_only: bool,
+    main_guard_only: bool,
+    unused_exemption: Option<UnusedExemption>,
+}
+
+impl DependencyUsage {
+    /// The name of the dependency as it was first declared
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The extras the dependency is declared with, like `parquet` in `pandas[parquet]`
+    pub fn extras(&self) -> &[String] {
+        &self.extras
+    }
+
+    /// How many files import the dependency
+    pub fn files(&self) -> usize {
+        self.files
+    }
+
+    /// How many import statements import the dependency
+    pub fn import_sites(&self) -> usize {
+        self.import_sites
+    }
+
+    /// Whether every import is guarded by an `ImportError` handler or a platform check
+    pub fn is_guarded_only(&self) -> bool {
+        self.guarded_only
+    }
+
+    /// Whether every import is in a test file
+    pub fn is_test_only(&self) -> bool {
+        self.test_only
+    }
+
+    /// Whether every import is inside `if TYPE_CHECKING:`
+    pub fn is_type_checking_only(&self) -> bool {
+        self.type_checking_only
+    }
+
+    /// Whether only type checkers need the dependency: every import is inside
+    /// `if TYPE_CHECKING:` or binds names only used in type annotations, `cast(...)`, and
+    /// `# type:` comments. Such a dependency could move to a typing extra, once its
+    /// imports are under `if TYPE_CHECKING:`.
+    pub fn is_type_only(&self) -> bool {
+        self.type_only
+    }
+
+    /// Whether every import is in a script or an `if __name__ == "__main__":` block
+    pub fn is_script_only(&self) -> bool {
+        self.script_only
+    }
+
+    /// Whether only `if __name__ == "__main__":` blocks need the dependency: every import is
+    /// inside one, or binds names only used inside one. Such a dependency is only needed to
+    /// run the module as a command line entry point.
+    pub fn is_main_guard_only(&self) -> bool {
+        self.main_guard_only
+    }
+
+    /// Why the dependency isn't reported as unused even though nothing imports it
+    pub fn unused_exemption(&self) -> Option<UnusedExemption> {
+        self.unused_exemption
+    }
+}
+
+/// The imports of each declared dependency found while matching, keyed by normalized name
+#[derive(Debug, Default)]
+struct UsageTally {
+    dependencies: BTreeMap<String, (String, BTreeMap<Location, ImportSite>)>,
+    /// The extras each dependency is declared with, in any configuration
+    extras: BTreeMap<String, Vec<String>>,
+}
+
+/// How a dependency is imported at one place
+#[derive(Debug, Clone, Copy)]
+struct ImportSite {
+    is_guarded: bool,
+    is_test: bool,
+    is_type_checking: bool,
+    is_annotation_only: bool,
+    is_script: bool,
+    is_main_guard_only: bool,
+}
+
+impl UsageTally {
+    fn declare(&mut self, dependency: &Dependency) {
+        if let Some(name) = dependency.name() {
+            self.dependencies
+                .entry(normalize_name(name))
+                .or_insert_with(|| (name.to_string(), BTreeMap::new()));
+            let extras = self.extras.entry(normalize_name(name)).or_default();
+            for extra in dependency.extras() {
+                if !extras.iter().any(|e| e == extra.as_ref()) {
+                    extras.push(extra.to_string());
+                }
+            }
+        }
+    }
+
+    /// Record an import of a dependency. A site seen again while checking another
+    /// configuration is only counted once.
+    fn record(&mut self, dependency: &Dependency, import: &ThirdPartyImport, file: &ScopedFile) {
+        let Some(name) = dependency.name() else {
+            return;
+        };
+        let (_, sites) = self
+            .dependencies
+            .entry(normalize_name(name))
+            .or_insert_with(|| (name.to_string(), BTreeMap::new()));
+        sites.insert(
+            import.location.clone(),
+            ImportSite {
+                is_guarded: import.is_guarded || import.is_platform_guarded,
+                is_test: file.is_test,
+                is_type_checking: import.is_type_checking,
+                is_annotation_only: import.is_annotation_only,
+                is_script: file.is_script || import.main_guard_only,
+                is_main_guard_only: import.main_guard_only,
+            },
+        );
+    }
+
+    /// The usage of every dependency, most imported first
+    fn into_usage(mut self, index: &ModuleIndex) -> Vec<DependencyUsage> {
+        let mut usage: Vec<DependencyUsage> = self
+            .dependencies
+            .into_iter()
+            .map(|(key, (name, sites))| {
+                let files: BTreeSet<&PathBuf> = sites.keys().map(|l| &l.path).collect();
+                let all = |f: fn(&ImportSite) -> bool| !sites.is_empty() && sites.values().all(f);
+                DependencyUsage {
+                    files: files.len(),
+                    import_sites: sites.len(),
+                    guarded_only: all(|site| site.is_guarded),
+                    test_only: all(|site| site.is_test),
+                    type_checking_only: all(|site| site.is_type_checking),
+                    type_only: all(|site| site.is_type_checking || site.is_annotation_only),
+                    script_only: all(|site| site.is_script),
+                    main_guard_only: all(|site| site.is_main_guard_only),
+                    unused_exemption: sites
+                        .is_empty()
+                        .then(|| index.unused_exemption(&name))
+                        .flatten(),
+                    extras: self.extras.remove(&key).unwrap_or_default(),
+                    name,
+                }
+            })
+            .collect();
+        usage.sort_by(|a, b| {
+            (b.import_sites, b.files)
+                .cmp(&(a.import_sites, a.files))
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        usage
+    }
+
+    /// Suggest moving the runtime dependencies that only scripts import to a dev group
+    fn script_only_findings<'a>(
+        &self,
+        configurations: impl Iterator<Item = &'a Configuration>,
+    ) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        for configuration in configurations.filter(|c| c.scope().is_none()) {
+            for dependency in configuration.dependencies() {
+                let Some(name) = dependency.name() else {
+                    continue;
+                };
+                let Some((_, sites)) = self.dependencies.get(&normalize_name(name)) else {
+                    continue;
+                };
+                if sites.is_empty() || !sites.values().all(|site| site.is_script) {
+                    continue;
+                }
+                findings.push(
+                    Finding::new(
+                        Rule::ScriptOnlyDependency,
+                        name,
+                        format!(
+                            "`{}` is only imported by scripts (script-only); consider moving it to a dev or script dependency group",
+                            name
+                        ),
+                        sites.keys().next().cloned(),
+                    )
+                    .with_configuration(configuration.name()),
+                );
+            }
+        }
+        findings
+    }
+}
+
+/// The imports that fall back from a part of the standard library to its backport, or the
+/// other way around, found in the project's files
+#[derive(Debug, Default)]
+struct BackportTally {
+    /// The backport of each pair, where the import tried first is, and whether it's the
+    /// backport's
+    pairs: Vec<(Backport, Location, bool)>,
+    /// The backports imported outside of a pair, which are needed on any Python
+    direct: BTreeSet<&'static str>,
+}
+
+impl BackportTally {
+    /// Record the pairs of a file: an import guarded by an `ImportError` handler, and the
+    /// import of its fallback in the handler
+    fn add(&mut self, file: &ParsedFile) {
+        let mut paired = Vec::new();
+        for (i, import) in file.imports.iter().enumerate() {
+            let (Some(module), Some(fallback)) = (&import.module_name, &import.fallback) else {
+                continue;
+            };
+            let Some((j, handler)) = file
+                .imports
+                .iter()
+                .enumerate()
+                .skip(i + 1)
+                .find(|(_, other)| other.module_name.as_ref() == Some(fallback))
+            else {
+                continue;
+            };
+            let (backport, backport_first) =
+                match stdlib::backport_of(module, &import.imported_names, fallback) {
+                    Some(backport) => (backport, false),
+                    None => match stdlib::backport_of(fallback, &handler.imported_names, module) {
+                        Some(backport) => (backport, true),
+                        None => continue,
+                    },
+                };
+            paired.extend([i, j]);
+            self.pairs.push((
+                backport,
+                Location::new(file.path.clone(), import.line_number),
+                backport_first,
+            ));
+        }
+        for (i, import) in file.imports.iter().enumerate() {
+            if let Some(module) = &import.module_name
+                && !import.is_relative
+                && !paired.contains(&i)
+                && let Some(backport) = stdlib::backport_module(module)
+            {
+                self.direct.insert(backport);
+            }
+        }
+    }
+}
+
+/// The result of analyzing a project
+#[derive(Debug, Default)]
+#[non_exhaustive]
+pub struct AnalysisReport {
+    /// The sources the declared dependencies were read from
+    sources: Vec<EnvironmentBuilderSource>,
+    /// Every module imported by the project. Names imported from a first-party package
+    /// count as imports of the submodule defining them, see [`walker::defining_module`].
+    found_imports: Vec<String>,
+    /// Names of declared dependencies that are never imported
+    unused_dependencies: Vec<String>,
+    /// Imported modules that aren't provided by the standard library, the project itself,
+    /// or a declared dependency, one per expected distribution and configuration
+    missing_imports: Vec<MissingImport>,
+    /// The outcome of each configuration that was checked
+    configurations: Vec<ConfigurationReport>,
+    /// Everything that was found wrong with the project
+    findings: Vec<Finding>,
+    /// The Python version the project was checked against, if one was known
+    python_version: Option<PythonVersion>,
+    /// How much each declared dependency is used
+    dependency_usage: Vec<DependencyUsage>,
+    /// What was skipped or worked around during the analysis
+    warnings: Vec<AnalysisWarning>,
+    /// How much work the analysis did and where its time went
+    stats: Stats,
+    /// The environment the imports were matched against, if the backend resolved one
+    environment: Option<ResolvedEnvironment>,
+    /// The distribution providing the module of each finding about an import
+    import_distributions: BTreeMap<String, String>,
+}
+
+impl AnalysisReport {
+    /// The source fixes are written to, the first of the analyzed sources
+    pub fn source(&self) -> Option<&EnvironmentBuilderSource> {
+        self.sources.first()
+    }
+
+    /// Every source the declared dependencies were read from
+    pub fn sources(&self) -> &[EnvironmentBuilderSource] {
+        &self.sources
+    }
+
+    pub fn found_imports(&self) -> &[String] {
+        &self.found_imports
+    }
+
+    pub fn unused_dependencies(&self) -> &[String] {
+        &self.unused_dependencies
+    }
+
+    pub fn missing_imports(&self) -> &[MissingImport] {
+        &self.missing_imports
+    }
+
+    /// The outcome of each configuration, in the order they were checked
+    pub fn configurations(&self) -> &[ConfigurationReport] {
+        &self.configurations
+    }
+
+    /// Everything that was found wrong with the project, in a stable order: by rule, then
+    /// subject, then location (see [`sort_findings`](crate::finding::sort_findings))
+    pub fn findings(&self) -> &[Finding] {
+        &self.findings
+    }
+
+    /// The Python version standard library modules were classified for. `None` when neither
+    /// the options nor the sources give one, in which case every module that is or was in
+    /// the standard library counts.
+    pub fn python_version(&self) -> Option<PythonVersion> {
+        self.python_version
+    }
+
+    /// How much each declared dependency is used, most imported first. Unused dependencies
+    /// are included with no imports.
+    pub fn dependency_usage(&self) -> &[DependencyUsage] {
+        &self.dependency_usage
+    }
+
+    /// What was skipped or worked around during the analysis, such as files that couldn't
+    /// be parsed, so the findings may be incomplete. Warnings are about the analysis rather
+    /// than the project, and aren't findings.
+    pub fn warnings(&self) -> &[AnalysisWarning] {
+        &self.warnings
+    }
+
+    /// How many files the analysis found, parsed, and reused, and how long each phase took
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    /// The environment the uv, pixi, or current backend inspected, or what the repodata of
+    /// its channels resolves a conda environment file to for the simulated backend. `None`
+    /// when the dependencies were only matched by name, or with a custom backend.
+    pub fn environment(&self) -> Option<&ResolvedEnvironment> {
+        self.environment.as_ref()
+    }
+
+    /// The normalized name of the distribution a finding is about. Findings about an import,
+    /// like a missing dependency or an unused import, are about the distribution the module
+    /// resolves to, so `import yaml` belongs to `pyyaml`. Other findings name it as their
+    /// subject.
+    pub fn distribution_of(&self, finding: &Finding) -> String {
+        let resolved = is_about_import(finding.rule)
+            .then(|| self.import_distributions.get(&finding.subject))
+            .flatten();
+        match resolved {
+            Some(distribution) => distribution.clone(),
+            None => normalize_name(&finding.subject),
+        }
+    }
+}
+
+/// Whether a rule's findings have the imported module as their subject
+fn is_about_import(rule: Rule) -> bool {
+    matches!(
+        rule,
+        Rule::MissingDependency
+            | Rule::ImportedThroughExtra
+            | Rule::RemovedStdlibImport
+            | Rule::UnusedImport
+    )
+}
+
+/// The report of a project whose declared dependencies the backend failed to resolve, so its
+/// imports weren't checked: the problems recognized in the resolver's output, as findings on
+/// the declarations they're about
+#[cfg(feature = "backends-exec")]
+impl From<&env_backend::ResolutionFailure> for AnalysisReport {
+    fn from(failure: &env_backend::ResolutionFailure) -> Self {
+        let mut configurations: Vec<ConfigurationReport> = Vec::new();
+        for finding in &failure.findings {
+            let (Some(name), Some(location)) = (&finding.configuration, &finding.location) else {
+                continue;
+            };
+            let declaration = (normalize_name(&finding.subject), location.clone());
+            match configurations.iter_mut().find(|c| c.name == *name) {
+                Some(configuration) => configuration.declarations.push(declaration),
+                None => configurations.push(ConfigurationReport {
+                    name: name.clone(),
+                    extra: None,
+                    environment: None,
+                    unused_dependencies: Vec::new(),
+                    missing_imports: Vec::new(),
+                    declarations: vec![declaration],
+                }),
+            }
+        }
+        Self {
+            findings: failure.findings.clone(),
+            configurations,
+            ..Self::default()
+        }
+    }
+}
+
+/// Entry point for analyzing a project.
+///
+/// ```
+/// use depwise_analysis::{Analysis, AnalysisOptions, EnvironmentBackend, Rule};
+///
+/// # let dir = tempfile::tempdir().unwrap();
+/// # std::fs::write(dir.path().join("requirements.txt"), "requests\nflask\n").unwrap();
+/// # std::fs::write(dir.path().join("main.py"), "import requests\nimport yaml\n").unwrap();
+/// let options = AnalysisOptions::builder(dir.path())
+///     .backend(EnvironmentBackend::Simulated)
+///     .build();
+/// let report = Analysis::run(&options)?;
+///
+/// assert_eq!(report.unused_dependencies(), &["flask".to_string()]);
+/// assert_eq!(report.missing_imports()[0].distribution(), "pyyaml");
+/// assert!(report
+///     .findings()
+///     .iter()
+///     .any(|finding| finding.rule == Rule::MissingDependency));
+/// # Ok::<(), depwise_analysis::AnalysisError>(())
+/// ```
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct Analysis;
+
+impl Analysis {
+    /// Compare the imports of a project against its declared dependencies.
+    ///
+    /// Every configuration (the required dependencies, each extra on top of them, and each
+    /// hatch environment) is checked on its own. Files under a directory named after an
+    /// extra or environment, such as `docs/` for a `docs` extra, belong to it. Other test
+    /// files belong to the `test` (or `dev`) extra or environment, and are only checked
+    /// against it. All other files belong to the base configuration and are visible to
+    /// every extra.
+    pub fn run(options: &AnalysisOptions) -> Result<AnalysisReport, AnalysisError> {
+        AnalysisSession::new(options.clone())?.check()
+    }
+
+    /// Describe the environment the backend checks against, to save with
+    /// [`ResolvedEnvironment::to_json`] and check against later without the backend's tools
+    /// (see [`env_backend::introspect`]). The pixi backend solves a conda environment file
+    /// with a manifest generated from it, using the channels it lists.
+    pub fn resolve_environment(
+        options: &AnalysisOptions,
+    ) -> Result<ResolvedEnvironment, AnalysisError> {
+        ResolvedProject::introspect(options)
+    }
+}
+
+/// Whether a built-in backend failed because its tool or interpreter can't be run, like a
+/// pyenv shim for a Python version that isn't installed, rather than because the declared
+/// dependencies can't be resolved
+#[cfg(feature = "backends-exec")]
+fn backend_unavailable(error: &AnalysisError, backend: EnvironmentBackend) -> bool {
+    match error {
+        AnalysisError::CommandError(..) => true,
+        AnalysisError::ResolutionFailed(_) => backend == EnvironmentBackend::Current,
+        _ => false,
+    }
+}
+
+/// Whether a configuration is named by its extra or environment, like `dev`, or by its full
+/// name
+fn has_name(configuration: &Configuration, name: &str) -> bool {
+    configuration.name() == name || configuration.scope() == Some(name)
+}
+
+/// Check that every configuration selected by name exists, or say which ones do
+fn check_configuration_names(
+    configurations: &[Configuration],
+    names: &[String],
+) -> Result<(), AnalysisError> {
+    if let Some(unknown) = names
+        .iter()
+        .find(|name| !configurations.iter().any(|c| has_name(c, name)))
+    {
+        let available = configurations
+            .iter()
+            .map(|c| c.scope().unwrap_or(c.name()).to_string())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+        return Err(AnalysisError::UnknownConfiguration(
+            unknown.clone(),
+            available,
+        ));
+    }
+    Ok(())
+}
+
+/// The declared dependencies of a project and what they provide
+#[derive(Debug)]
+struct ResolvedProject {
+    sources: Vec<EnvironmentBuilderSource>,
+    configurations: Vec<Configuration>,
+    index: ModuleIndex,
+    python_version: Option<PythonVersion>,
+    /// Findings about how the dependencies are declared, found while reading the sources
+    declaration_findings: Vec<Finding>,
+    /// Findings about the installed versions, if the backend inspects an environment
+    environment_findings: Vec<Finding>,
+    /// The entry points the project declares, whose modules are used by running them
+    entry_points: Vec<project::EntryPoint>,
+    /// How the build backend is configured to find the project's packages, if it is
+    package_layout: Option<PackageLayout>,
+    /// Whether what generated files import without a declared dependency is reported
+    report_generated: bool,
+    /// What the tox environments and nox sessions install, which test files may import
+    test_runner_dependencies: Vec<Dependency>,
+    /// Groups of extras that are alternatives to each other
+    exclusive_extras: Vec<Vec<String>>,
+    /// Whether unused dependencies are reported, which they aren't when a single script is
+    /// checked against the project around it
+    report_unused: bool,
+    /// The configurations checked, by name, extra, or environment. The others are still
+    /// read for the files that belong to them and the base dependencies of extras. Empty
+    /// checks every configuration.
+    configuration_names: Vec<String>,
+    /// What was skipped or worked around while resolving the project
+    warnings: Vec<AnalysisWarning>,
+    /// The environment the uv, pixi, or current backend inspected, or what the repodata of
+    /// its channels resolves a conda environment file to for the simulated backend
+    environment: Option<ResolvedEnvironment>,
+    /// Why the uv, pixi, or current backend couldn't inspect its environment, when the
+    /// curated mapping was used instead
+    #[cfg(feature = "backends-exec")]
+    backend_error: Option<AnalysisError>,
+    /// How long resolving took and what was downloaded for it, until a check reports it
+    stats: Stats,
+}
+
+impl ResolvedProject {
+    fn resolve(options: &AnalysisOptions) -> Result<Self, AnalysisError> {
+        let started = Instant::now();
+        let path = options.path();
+        let cancel = options.cancellation_token();
+        let mut stats = Stats::default();
+
+        // Without an explicit source we can try to infer it from the path
+        let mut sources = options.sources().to_vec();
+        // A dependency only looks unused when every file is checked
+        let mut report_unused = options.files().is_none();
+        let mut warnings = Vec::new();
+        if sources.is_empty() {
+            match EnvironmentBuilderSource::infer_from_source_path(path) {
+                Ok(inferred_source) => {
+                    // A script without inline metadata falls back to the project it's in,
+                    // whose other dependencies it has no reason to import
+                    report_unused &= !path.is_file() || inferred_source.path() == path;
+                    sources.push(inferred_source);
+                }
+                Err(e) => {
+                    warn!("Error inferring environment builder source: {}", e);
+                    warnings.push(AnalysisWarning::NoDependencySource {
+                        path: path.to_path_buf(),
+                        reason: e.to_string(),
+                    });
+                }
+            }
+        }
+        // A lock file pins the dependencies of dependencies too, which aren't imported
+        report_unused &= !sources
+            .iter()
+            .any(|source| matches!(source, EnvironmentBuilderSource::PoetryLock(_)));
+        if options.test_runners() && path.is_dir() {
+            for source in [
+                EnvironmentBuilderSource::ToxIni(path.join("tox.ini")),
+                EnvironmentBuilderSource::Noxfile(path.join("noxfile.py")),
+            ] {
+                if source.path().is_file() && !sources.contains(&source) {
+                    sources.push(source);
+                }
+            }
+        }
+
+        let mut configurations: Vec<Configuration> = Vec::new();
+        let mut declaration_findings = Vec::new();
+        let mut entry_points = Vec::new();
+        let mut package_layout = None;
+        for source in &sources {
+            declaration_findings.extend(project::declaration_findings(
+                source,
+                options.exclusive_extras(),
+            )?);
+            entry_points.extend(project::entry_points(source)?);
+            if package_layout.is_none() {
+                package_layout = project::package_layout(source)?;
+            }
+            let mut diagnostics = Vec::new();
+            let parse_options = ParseOptions::new(options.offline(), options.max_requirements())
+                .with_download_limits(
+                    options.backend_timeout(),
+                    options.cancellation_token().clone(),
+                );
+            let source_configurations = match options
+                .source_registry()
+                .open(source, parse_options)
+                .and_then(|parser| parser.parse_with_diagnostics(&mut diagnostics))
+            {
+                Ok(configurations) => configurations,
+                // The test runners only add to what tests may import, so the analysis goes
+                // on without them
+                Err(e) if source.is_test_runner() => {
+                    warn!("Skipping {}: {}", source.path().display(), e);
+                    warnings.push(AnalysisWarning::SkippedSource {
+                        path: source.path().to_path_buf(),
+                        reason: e.to_string(),
+                    });
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            if options.strict_parse() && !diagnostics.is_empty() {
+                return Err(AnalysisError::InvalidDeclarations(diagnostics));
+            }
+            declaration_findings.extend(diagnostics.into_iter().map(|diagnostic| {
+                Finding::new(
+                    Rule::InvalidDependency,
+                    &diagnostic.text,
+                    format!(
+                        "`{}` can't be parsed and was skipped: {}",
+                        diagnostic.text, diagnostic.message
+                    ),
+                    Some(diagnostic.location),
+                )
+            }));
+            for configuration in source_configurations {
+                let selected = match (configuration.extra(), options.extras()) {
+                    (Some(extra), Some(extras)) => extras.iter().any(|e| e == extra),
+                    _ => true,
+                } && match (configuration.platform(), options.platforms()) {
+                    (Some(platform), Some(platforms)) => platforms.iter().any(|p| p == platform),
+                    _ => true,
+                };
+                if selected {
+                    debug!(
+                        "dependencies of {}: {:?}",
+                        configuration.name(),
+                        configuration.dependencies()
+                    );
+                    configurations.push(configuration);
+                } else {
+                    debug!("Skipping configuration {}", configuration.name());
+                }
+            }
+        }
+
+        check_configuration_names(&configurations, options.configuration_names())?;
+
+        // The current backend runs the interpreter pyenv would for the project
+        #[cfg(feature = "backends-exec")]
+        let requested_python = match options.backend() {
+            EnvironmentBackend::Current if options.path().is_dir() => {
+                python_version::read_python_version_file(options.path())
+            }
+            EnvironmentBackend::Current => options
+                .path()
+                .parent()
+                .and_then(python_version::read_python_version_file),
+            _ => None,
+        };
+        #[cfg(not(feature = "backends-exec"))]
+        let requested_python: Option<String> = None;
+        let python_version =
+            target_python_version(options, requested_python.as_deref(), &configurations);
+        // Dependencies whose markers rule out the target Python version aren't installed
+        if let Some(version) = python_version {
+            for configuration in &mut configurations {
+                configuration.retain_dependencies(|dependency| {
+                    let applies = dependency.applies_to_python(version);
+                    if !applies {
+                        debug!("{:?} isn't installed for Python {}", dependency, version);
+                    }
+                    applies
+                });
+            }
+        }
+
+        let name_map = match options.name_map() {
+            Some(path) => {
+                let name_map = name_map::load_name_map(path)?;
+                for conflict in name_map.conflicts() {
+                    warnings.push(AnalysisWarning::NameMapConflict {
+                        path: path.to_path_buf(),
+                        reason: conflict.clone(),
+                    });
+                }
+                name_map
+            }
+            None => NameMap::new(),
+        };
+        let mut index = ModuleIndex::with_name_map(name_map);
+        cancel.check(Phase::ResolvingEnvironment)?;
+        // The uv, pixi, and current backends inspect the environment they resolve the
+        // project to, and fall back to the curated mapping when their tool can't be run
+        #[cfg(feature = "backends-exec")]
+        let mut backend_error = None;
+        #[cfg(feature = "backends-exec")]
+        let builtin_environment = match options.custom_backend() {
+            Some(_) => None,
+            None => match env_backend::builtin(
+                options.backend(),
+                path,
+                options.target_environment(),
+                &configurations,
+                options.backend_timeout(),
+                cancel,
+            ) {
+                Ok(environment) => environment,
+                Err(error) if backend_unavailable(&error, options.backend()) => {
+                    let warning = AnalysisWarning::BackendFallback {
+                        backend: options.backend(),
+                        reason: error.to_string(),
+                    };
+                    warn!("{}", warning.message());
+                    warnings.push(warning);
+                    backend_error = Some(error);
+                    None
+                }
+                // The problems the resolver reports are pointed at their declarations
+                Err(AnalysisError::ResolutionFailed(mut failure)) => {
+                    failure.locate(&configurations);
+                    return Err(AnalysisError::ResolutionFailed(failure));
+                }
+                Err(error) => return Err(error),
+            },
+        };
+        #[cfg(not(feature = "backends-exec"))]
+        let builtin_environment: Option<ResolvedEnvironment> = None;
+        // The simulated backend looks conda packages up in the repodata of their channels
+        #[cfg(all(feature = "conda", feature = "backends-net"))]
+        let conda_environment = match options.custom_backend() {
+            None if options.backend() == EnvironmentBackend::Simulated => {
+                repodata_environment(options, &configurations, &mut warnings, &mut stats)?
+            }
+            _ => None,
+        };
+        #[cfg(not(all(feature = "conda", feature = "backends-net")))]
+        let conda_environment: Option<ResolvedEnvironment> = None;
+        let backend = options
+            .custom_backend()
+            .map(|backend| backend.as_ref())
+            .or(builtin_environment
+                .as_ref()
+                .map(|e| e as &dyn env_backend::Backend))
+            .or(conda_environment
+                .as_ref()
+                .map(|e| e as &dyn env_backend::Backend));
+        if let Some(backend) = backend {
+            let dependencies = declared_dependencies(&configurations);
+            backend.populate_index(&dependencies, &mut index, cancel)?;
+        }
+        cancel.check(Phase::ResolvingEnvironment)?;
+
+        // Compare the declared versions against the environment, if the backend has one
+        let distributions = installed::declared_distributions(&configurations);
+        let installed_versions = if distributions.is_empty() {
+            None
+        } else if let Some(backend) = options.custom_backend() {
+            backend.installed_versions(&distributions, cancel)?
+        } else if let Some(environment) = &builtin_environment {
+            env_backend::Backend::installed_versions(environment, &distributions, cancel)?
+        } else {
+            None
+        };
+        #[cfg_attr(not(feature = "backends-net"), allow(unused_mut))]
+        let mut environment_findings = installed_versions
+            .map(|versions| {
+                installed::version_findings(
+                    &configurations,
+                    &versions,
+                    options.extras().is_some(),
+                    options.exclusive_extras(),
+                )
+            })
+            .unwrap_or_default();
+        // The simulated backend installs from PyPI, so the declared versions have to be there
+        let specified = installed::specified_distributions(&configurations);
+        let check_releases = options.backend() == EnvironmentBackend::Simulated
+            && options.custom_backend().is_none()
+            && options.checks(Rule::UnavailableVersion)
+            && !specified.is_empty();
+        if check_releases && options.offline() {
+            let warning = AnalysisWarning::SkippedReleaseCheck;
+            warn!("{}", warning.message());
+            warnings.push(warning);
+        }
+        #[cfg(feature = "backends-net")]
+        if check_releases && !options.offline() {
+            let releases = env_backend::pypi::releases(
+                &specified,
+                options.cache_dir(),
+                options.use_cache(),
+                options.backend_timeout(),
+                cancel,
+            )?;
+            environment_findings.extend(installed::release_findings(&configurations, &releases));
+        }
+
+        let runners: Vec<Configuration> = configurations
+            .iter()
+            .filter(|c| c.source().is_test_runner())
+            .cloned()
+            .collect();
+        let mut test_runner_dependencies = declared_dependencies(&runners);
+        // The noxfile itself is run by nox
+        if runners
+            .iter()
+            .any(|c| matches!(c.source(), EnvironmentBuilderSource::Noxfile(_)))
+        {
+            project::merge_dependencies(
+                &mut test_runner_dependencies,
+                [Dependency::PyPI(
+                    PyPIRequirement::from_str("nox").expect("nox is a valid requirement"),
+                )],
+            );
+        }
+
+        stats.resolving = started.elapsed();
+        Ok(Self {
+            sources,
+            index,
+            python_version,
+            declaration_findings,
+            environment_findings,
+            entry_points,
+            package_layout,
+            report_generated: options.report_generated(),
+            exclusive_extras: options.exclusive_extras().to_vec(),
+            report_unused,
+            configuration_names: options.configuration_names().to_vec(),
+            test_runner_dependencies,
+            configurations,
+            warnings,
+            environment: builtin_environment.or(conda_environment),
+            #[cfg(feature = "backends-exec")]
+            backend_error,
+            stats,
+        })
+    }
+
+    /// Describe the environment the backend of the options checks against, the same one the
+    /// analysis matches imports with. When the backend fails to resolve the dependencies, the
+    /// problems it reports are pointed at their declarations.
+    fn introspect(options: &AnalysisOptions) -> Result<ResolvedEnvironment, AnalysisError> {
+        let resolved = Self::resolve(options)?;
+        #[cfg(feature = "backends-exec")]
+        if let Some(error) = resolved.backend_error {
+            return Err(error);
+        }
+        match options.backend() {
+            EnvironmentBackend::Auto | EnvironmentBackend::Simulated => {
+                let mut environment = env_backend::introspect(
+                    options.backend(),
+                    options.path(),
+                    None,
+                    &declared_dependencies(&resolved.configurations),
+                    None,
+                    options.cancellation_token(),
+                )?;
+                if let Some(conda) = resolved.environment {
+                    environment.python_version = conda.python_version;
+                    environment.distributions.extend(conda.distributions);
+                }
+                Ok(environment)
+            }
+            #[cfg(feature = "backends-exec")]
+            EnvironmentBackend::UV | EnvironmentBackend::Pixi | EnvironmentBackend::Current => {
+                Ok(resolved.environment.unwrap_or_default())
+            }
+        }
+    }
+
+    /// Register what the index needs to know about the project itself: its first-party
+    /// modules, which change as files are added, and the distributions never reported unused
+    fn prepare_index(&mut self, options: &AnalysisOptions) {
+        let first_party = match &self.package_layout {
+            Some(layout) if options.path().is_dir() => layout.first_party_modules(options.path()),
+            _ => walker::discover_first_party_modules(options.path()),
+        };
+        for module in first_party {
+            self.index.add_first_party(&module);
+        }
+        for distribution in options.ignore_unused() {
+            self.index.add_ignored_distribution(distribution);
+        }
+        for module in options.provided_modules() {
+            self.index.add_provided(module);
+        }
+    }
+
+    /// Find the scope a parsed file belongs to and the imports in it that need a dependency
+    fn scoped_file(&self, options: &AnalysisOptions, file: &ParsedFile) -> ScopedFile {
+        // Test runner environments are named after what they run, not after directories
+        let scopes: Vec<&str> = self
+            .configurations
+            .iter()
+            .filter(|c| !c.source().is_test_runner())
+            .filter_map(|c| c.scope())
+            .collect();
+        let relative = relative_path(options.path(), &file.path);
+        let is_doc = walker::is_doc_file(relative);
+        let is_test = !is_doc && walker::is_test_file(relative);
+        ScopedFile {
+            scope: scope_for_file(options.path(), &file.path, &scopes)
+                .or_else(|| is_test.then(|| test_scope(&scopes)))
+                .or_else(|| is_doc.then(|| docs_scope(&scopes))),
+            is_test,
+            is_script: walker::is_script_file(relative, options.script_directories()),
+            is_generated: walker::is_generated_file(relative, options.generated_patterns()),
+            imports: third_party_imports(file, &self.index, self.python_version),
+        }
+    }
+
+    /// The configurations that are checked: every one, or the ones named in the options
+    fn checked_configurations(&self) -> impl Iterator<Item = &Configuration> {
+        self.configurations.iter().filter(|c| {
+            self.configuration_names.is_empty()
+                || self
+                    .configuration_names
+                    .iter()
+                    .any(|name| has_name(c, name))
+        })
+    }
+
+    /// Match the imports of the files against every configuration, adding what's found to
+    /// the report and how each dependency is used to `usage`
+    fn match_files(
+        &self,
+        files: &[ScopedFile],
+        report: &mut AnalysisReport,
+        usage: &mut UsageTally,
+        cancel: &CancellationToken,
+    ) -> Result<(), AnalysisError> {
+        cancel.check(Phase::Matching)?;
+        let configurations = &self.configurations;
+        if configurations.iter().all(|c| c.source().is_test_runner()) {
+            check_configuration(None, &[], &[], files, self, report, usage);
+        }
+        for configuration in self.checked_configurations() {
+            configuration
+                .dependencies()
+                .iter()
+                .for_each(|dependency| usage.declare(dependency));
+        }
+        for configuration in self.checked_configurations() {
+            cancel.check(Phase::Matching)?;
+            let base_dependencies = configurations
+                .iter()
+                .find(|c| c.scope().is_none() && c.source() == configuration.source())
+                .filter(|_| configuration.scope().is_some())
+                .map(|c| c.dependencies())
+                .unwrap_or_default();
+            check_configuration(
+                Some(configuration),
+                configuration.dependencies(),
+                base_dependencies,
+                files,
+                self,
+                report,
+                usage,
+            );
+        }
+        Ok(())
+    }
+
+    /// Treat the modules entry points load as imported by the entry points. A module a
+    /// declared dependency provides uses it; any other module is the project's own, even if
+    /// it wasn't found among the project's files.
+    fn entry_point_imports(&mut self) -> Vec<ThirdPartyImport> {
+        let declared = self.declared_dependencies();
+        let mut imports = Vec::new();
+        for entry in &self.entry_points {
+            let module = &entry.module;
+            let is_stdlib = match self.python_version {
+                Some(version) => stdlib::is_stdlib_in(module, version),
+                None => stdlib::is_stdlib(module),
+            };
+            if is_stdlib || self.index.is_first_party(module) {
+                continue;
+            }
+            if self.index.find_dependency(module, &declared).is_none() {
+                debug!("Entry point module {} is first-party", module);
+                self.index.add_first_party(module);
+                continue;
+            }
+            imports.push(ThirdPartyImport {
+                module: module.clone(),
+                location: entry.location.clone(),
+                is_guarded: false,
+                fallback: None,
+                is_platform_guarded: false,
+                is_lazy: false,
+                is_type_checking: false,
+                is_annotation_only: false,
+                main_guard_only: false,
+                never_used: false,
+            });
+        }
+        imports
+    }
+
+    /// Every dependency the project declares, in any configuration
+    fn declared_dependencies(&self) -> Vec<Dependency> {
+        self.configurations
+            .iter()
+            .flat_map(|c| c.dependencies().iter().cloned())
+            .collect()
+    }
+
+    /// Report the entry points whose module isn't one of the project's or doesn't define
+    /// their attribute. Modules in the standard library, or provided by a declared dependency
+    /// or the runtime, aren't checked. `paths` are the project's Python files, whose modules
+    /// are suggested in place of a missing one.
+    fn entry_point_findings(&self, options: &AnalysisOptions, paths: &[&PathBuf]) -> Vec<Finding> {
+        if !options.path().is_dir() {
+            return Vec::new();
+        }
+        let declared = self.declared_dependencies();
+        let own: Vec<&project::EntryPoint> = self
+            .entry_points
+            .iter()
+            .filter(|entry| {
+                let module = &entry.module;
+                let is_stdlib = match self.python_version {
+                    Some(version) => stdlib::is_stdlib_in(module, version),
+                    None => stdlib::is_stdlib(module),
+                };
+                !is_stdlib
+                    && !self.index.is_provided(module)
+                    && self.index.find_dependency(module, &declared).is_none()
+            })
+            .collect();
+        if own.is_empty() {
+            return Vec::new();
+        }
+        let mut roots = vec![options.path().to_path_buf(), options.path().join("src")];
+        if let Some(layout) = &self.package_layout {
+            roots.extend(layout.source_roots.iter().map(|(root, _)| root.clone()));
+        }
+        let mut modules: Vec<String> = paths
+            .iter()
+            .filter_map(|path| entry_points::module_name(&roots, path))
+            .collect();
+        modules.sort();
+        modules.dedup();
+        entry_points::findings(&own, &roots, self.package_layout.as_ref(), &modules)
+    }
+
+    /// Report the imports of a file that use parts of the standard library the target
+    /// Python version removed, suggesting what replaces them. Imports guarded by an
+    /// `ImportError` handler or only run by type checkers don't break anything, and a
+    /// declared dependency providing the module, like a backport, brings it back.
+    fn removed_stdlib_findings(&self, file: &ParsedFile) -> Vec<Finding> {
+        let Some(version) = self.python_version else {
+            return Vec::new();
+        };
+        let mut findings = Vec::new();
+        for import in &file.imports {
+            if import.is_relative || import.is_likely_exception_guarded || import.is_type_checking {
+                continue;
+            }
+            let Some(module) = &import.module_name else {
+                continue;
+            };
+            let Some(removal) = stdlib::removal(module, &import.imported_names, version) else {
+                continue;
+            };
+            if self.configurations.iter().any(|c| {
+                self.index
+                    .find_dependency(module, c.dependencies())
+                    .is_some()
+            }) {
+                continue;
+            }
+            let mut message = format!(
+                "`{}` was removed from the standard library in Python {}, and the project targets Python {}",
+                removal.name, removal.version, version
+            );
+            if let Some(replacement) = removal.replacement {
+                message.push_str(&format!("; use `{}` instead", replacement));
+            }
+            findings.push(Finding::new(
+                Rule::RemovedStdlibImport,
+                removal.name,
+                message,
+                Some(Location {
+                    path: file.path.clone(),
+                    line: import.line_number,
+                }),
+            ));
+        }
+        findings
+    }
+
+    /// Report the declared backports the Python versions the project supports make
+    /// obsolete, and the ones missing for the oldest of them, going by its `requires-python`.
+    /// A backport is obsolete when every pair falling back to it is about a part of the
+    /// standard library the oldest version has, and nothing imports it directly. Only pairs
+    /// that try the backport first are reported as missing it, since a backport imported in
+    /// the handler is reported as a missing dependency already.
+    fn backport_findings(&self, tally: &BackportTally) -> Vec<Finding> {
+        let Some((minimum, requires_python)) = self.configurations.iter().find_map(|c| {
+            let specifiers = c.requires_python()?;
+            Some((PythonVersion::minimum_of(specifiers)?, specifiers))
+        }) else {
+            return Vec::new();
+        };
+        let mut findings = Vec::new();
+        let mut reported = BTreeSet::new();
+        for (backport, location, backport_first) in &tally.pairs {
+            let declared = self.configurations.iter().any(|c| {
+                self.index
+                    .find_dependency(backport.module, c.dependencies())
+                    .is_some()
+            });
+            let needed = tally.direct.contains(backport.module)
+                || tally.pairs.iter().any(|(other, _, _)| {
+                    other.distribution == backport.distribution && other.added > minimum
+                });
+            let finding = if declared && !needed {
+                if !reported.insert(backport.distribution) {
+                    continue;
+                }
+                Finding::new(
+                    Rule::ObsoleteBackport,
+                    backport.distribution,
+                    format!(
+                        "`{}` backports `{}`, which every supported Python has (requires-python `{}`); the dependency can be dropped",
+                        backport.distribution, backport.name, requires_python
+                    ),
+                    Some(location.clone()),
+                )
+            } else if !declared && *backport_first && backport.added > minimum {
+                Finding::new(
+                    Rule::MissingBackport,
+                    backport.distribution,
+                    format!(
+                        "`{}` was added in Python {}, but the project supports Python {} (requires-python `{}`) without declaring the `{}` backport",
+                        backport.name,
+                        backport.added,
+                        minimum,
+                        requires_python,
+                        backport.distribution
+                    ),
+                    Some(location.clone()),
+                )
+            } else {
+                continue;
+            };
+            findings.push(finding);
+        }
+        findings
+    }
+
+    fn is_source(&self, path: &Path) -> bool {
+        self.sources.iter().any(|source| source.path() == path)
+    }
+}
+
+/// Resolve the first configuration read from a conda environment file with the repodata of
+/// its channels, for the platform of the options or else the running one. The curated
+/// mapping still works without the repodata, so failing to read it is only a warning.
+#[cfg(all(feature = "conda", feature = "backends-net"))]
+fn repodata_environment(
+    options: &AnalysisOptions,
+    configurations: &[Configuration],
+    warnings: &mut Vec<AnalysisWarning>,
+    stats: &mut Stats,
+) -> Result<Option<ResolvedEnvironment>, AnalysisError> {
+    let Some(configuration) = configurations
+        .iter()
+        .find(|c| c.conda_environment().is_some())
+    else {
+        return Ok(None);
+    };
+    let platform = options
+        .platforms()
+        .and_then(|platforms| platforms.first())
+        .map(String::as_str)
+        .or_else(|| env_backend::current_platform());
+    let Some(platform) = platform else {
+        return Ok(None);
+    };
+    let cache = env_backend::repodata::RepodataCache::new(
+        options.cache_dir(),
+        options.offline(),
+        options.use_cache(),
+        options.backend_timeout(),
+    );
+    let environment = env_backend::repodata::conda_environment(
+        configuration,
+        platform,
+        &cache,
+        options.cancellation_token(),
+    );
+    stats.repodata_downloads += cache.downloads();
+    stats.repodata_cache_hits += cache.hits();
+    match environment {
+        Err(e @ AnalysisError::Cancelled(_)) => Err(e),
+        Err(e) => {
+            let warning = AnalysisWarning::RepodataFallback {
+                path: configuration.source().path().to_path_buf(),
+                reason: e.to_string(),
+            };
+            warn!("{}", warning);
+            warnings.push(warning);
+            Ok(None)
+        }
+        result => result,
+    }
+}
+
+/// Every dependency the configurations declare, each package once
+fn declared_dependencies(configurations: &[Configuration]) -> Vec<Dependency> {
+    let mut dependencies: Vec<Dependency> = Vec::new();
+    for configuration in configurations {
+        project::merge_dependencies(
+            &mut dependencies,
+            configuration.dependencies().iter().cloned(),
+        );
+    }
+    dependencies
+}
+
+/// Pick the Python version to check against: the one given in the options, otherwise the
+/// one of the interpreter `requested` by the project's `.python-version` file, otherwise the
+/// first interpreter a source pins
+fn target_python_version(
+    options: &AnalysisOptions,
+    requested: Option<&str>,
+    configurations: &[Configuration],
+) -> Option<PythonVersion> {
+    if let Some(version) = options.python_version() {
+        info!("Targeting Python {} from the options", version);
+        return Some(version);
+    }
+    if let Some(version) = requested.and_then(PythonVersion::from_spec) {
+        info!("Targeting Python {} from .python-version", version);
+        return Some(version);
+    }
+    for configuration in configurations {
+        let Some(spec) = configuration.python_requirement() else {
+            continue;
+        };
+        match PythonVersion::from_spec(spec.version_spec()) {
+            Some(version) => {
+                info!(
+                    "Targeting Python {} pinned by {} ({})",
+                    version,
+                    configuration.source().path().display(),
+                    spec.raw_spec()
+                );
+                return Some(version);
+            }
+            None => debug!("No Python version in {}", spec.raw_spec()),
+        }
+    }
+    None
+}
+
+/// A long-lived analysis of a project.
+///
+/// The session keeps the resolved environment and the parse of every file between checks,
+/// so after a file changes only that file is parsed again before the imports are matched.
+/// Editor integrations should call [`AnalysisSession::invalidate`] whenever a file changes.
+///
+/// ```
+/// use depwise_analysis::{AnalysisOptions, AnalysisSession, EnvironmentBackend};
+///
+/// # let dir = tempfile::tempdir().unwrap();
+/// # std::fs::write(dir.path().join("requirements.txt"), "requests\n").unwrap();
+/// let main = dir.path().join("main.py");
+/// std::fs::write(&main, "import requests\n").unwrap();
+///
+/// let options = AnalysisOptions::builder(dir.path())
+///     .backend(EnvironmentBackend::Simulated)
+///     .build();
+/// let mut session = AnalysisSession::new(options)?;
+/// assert!(session.check()?.findings().is_empty());
+///
+/// std::fs::write(&main, "import requests\nimport yaml\n").unwrap();
+/// session.invalidate(&main)?;
+/// assert_eq!(session.check_file(&main)?.len(), 1);
+/// # Ok::<(), depwise_analysis::AnalysisError>(())
+/// ```
+#[derive(Debug)]
+pub struct AnalysisSession {
+    options: AnalysisOptions,
+    environment: ResolvedProject,
+    /// Parsed files by path, or why they were skipped if they couldn't be read or parsed
+    files: BTreeMap<PathBuf, ParsedOrSkipped>,
+}
+
+impl AnalysisSession {
+    /// Start a session, resolving the project's environment
+    pub fn new(options: AnalysisOptions) -> Result<Self, AnalysisError> {
+        let environment = ResolvedProject::resolve(&options)?;
+        Ok(Self {
+            options,
+            environment,
+            files: BTreeMap::new(),
+        })
+    }
+
+    pub fn options(&self) -> &AnalysisOptions {
+        &self.options
+    }
+
+    /// Forget what is known about a file so the next check reads it again. If the file is
+    /// one of the dependency sources the environment is resolved again.
+    pub fn invalidate(&mut self, path: &Path) -> Result<(), AnalysisError> {
+        if self.environment.is_source(path) {
+            debug!("Dependency source {} changed", path.display());
+            self.environment = ResolvedProject::resolve(&self.options)?;
+        } else {
+            self.files.remove(path);
+        }
+        Ok(())
+    }
+
+    /// Save the parse of every file, to reuse with [`load_state`](Self::load_state) in a
+    /// later run. Files that couldn't be read or parsed aren't saved.
+    pub fn save_state(&self) -> String {
+        let state = SessionState {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            check_unused_imports: self.options.check_unused_imports(),
+            files: self
+                .files
+                .iter()
+                .filter_map(|(path, file)| {
+                    let file = file.as_ref().ok()?;
+                    let saved = SavedFile {
+                        imports: file.imports.clone(),
+                        unused_imports: file.unused_imports.clone(),
+                        degraded: file.degraded.clone(),
+                    };
+                    Some((
+                        relative_path(self.options.path(), path).to_path_buf(),
+                        saved,
+                    ))
+                })
+                .collect(),
+        };
+        serde_json::to_string(&state).expect("an analysis state serializes to JSON")
+    }
+
+    /// Reuse the parses saved by [`save_state`](Self::save_state), except for the `changed`
+    /// files, given relative to the project root, so the next check only parses those and
+    /// files the state doesn't have.
+    ///
+    /// The state is only as accurate as `changed` is complete: a file changed since the
+    /// state was saved but not listed keeps its old imports. Nothing is reused, and `false`
+    /// is returned, when a changed file is a dependency source or named like one, or the
+    /// state was saved by another version of depwise or with other options.
+    pub fn load_state(&mut self, state: &str, changed: &[PathBuf]) -> Result<bool, AnalysisError> {
+        let state: SessionState =
+            serde_json::from_str(state).map_err(|e| AnalysisError::StateError(e.to_string()))?;
+        if state.version != env!("CARGO_PKG_VERSION")
+            || state.check_unused_imports != self.options.check_unused_imports()
+        {
+            debug!("The analysis state was saved by another version or with other options");
+            return Ok(false);
+        }
+        let root = self.options.path();
+        if let Some(manifest) = changed.iter().find(|path| {
+            self.environment.is_source(&root.join(path)) || is_dependency_manifest(path)
+        }) {
+            debug!("Dependency manifest {} changed", manifest.display());
+            return Ok(false);
+        }
+        let changed: HashSet<&Path> = changed.iter().map(PathBuf::as_path).collect();
+        for (path, saved) in state.files {
+            if changed.contains(path.as_path()) {
+                continue;
+            }
+            let path = root.join(path);
+            let file = ParsedFile {
+                path: path.clone(),
+                imports: saved.imports,
+                unused_imports: saved.unused_imports,
+                degraded: saved.degraded,
+            };
+            self.files.insert(path, Ok(file));
+        }
+        Ok(true)
+    }
+
+    /// Check the whole project, parsing only the files that aren't cached
+    pub fn check(&mut self) -> Result<AnalysisReport, AnalysisError> {
+        let options = &self.options;
+        let cancel = options.cancellation_token();
+        // Resolving is only counted by the first check after it
+        let mut stats = std::mem::take(&mut self.environment.stats);
+
+        let started = Instant::now();
+        let discovery = walker::discover_python_files(
+            options.path(),
+            options.excludes(),
+            options.default_excludes(),
+            options.follow_symlinks(),
+        )?;
+        let mut paths = discovery.files;
+        if options.include_docs() {
+            paths.extend(walker::discover_doc_files(
+                options.path(),
+                options.excludes(),
+                options.default_excludes(),
+                options.follow_symlinks(),
+            )?);
+            paths.sort();
+        }
+        if !options.include_tests() {
+            paths.retain(|path| !walker::is_test_file(relative_path(options.path(), path)));
+        }
+        if let Some(files) = options.files() {
+            paths.retain(|path| {
+                let relative = relative_path(options.path(), path);
+                files.iter().any(|file| file == relative)
+            });
+        }
+        let discovered: HashSet<&Path> = paths.iter().map(PathBuf::as_path).collect();
+        self.files
+            .retain(|path, _| discovered.contains(path.as_path()));
+        let uncached: Vec<PathBuf> = paths
+            .iter()
+            .filter(|path| !self.files.contains_key(*path))
+            .cloned()
+            .collect();
+        stats.discovery = started.elapsed();
+        stats.files_discovered = paths.len();
+        stats.files_cached = paths.len() - uncached.len();
+
+        let started = Instant::now();
+        let counters = ScanCounters::default();
+        for (path, file) in parse_files(&uncached, options, &counters)? {
+            self.files.insert(path, file);
+        }
+        counters.add_to(&mut stats);
+        stats.parsing = started.elapsed();
+
+        let started = Instant::now();
+        let environment = &mut self.environment;
+        environment.prepare_index(options);
+        let entry_point_imports = environment.entry_point_imports();
+
+        let mut report = AnalysisReport {
+            sources: environment.sources.clone(),
+            python_version: environment.python_version,
+            warnings: environment.warnings.clone(),
+            environment: environment.environment.clone(),
+            findings: [
+                environment.declaration_findings.as_slice(),
+                environment.environment_findings.as_slice(),
+            ]
+            .concat(),
+            ..Default::default()
+        };
+        if let Some(layout) = &environment.package_layout {
+            report.findings.extend(layout.findings());
+        }
+        let paths: Vec<&PathBuf> = self.files.keys().collect();
+        report
+            .findings
+            .extend(environment.entry_point_findings(options, &paths));
+        report.warnings.extend(
+            discovery
+                .virtual_environments
+                .into_iter()
+                .map(
+                    |(path, marker)| AnalysisWarning::SkippedVirtualEnvironment {
+                        path,
+                        marker: marker.to_string(),
+                    },
+                ),
+        );
+        report
+            .warnings
+            .extend(self.files.values().filter_map(|file| {
+                match file {
+                    Err(warning) => Some(warning.clone()),
+                    Ok(file) => {
+                        file.degraded
+                            .as_ref()
+                            .map(|reason| AnalysisWarning::DegradedParse {
+                                path: file.path.clone(),
+                                reason: reason.clone(),
+                            })
+                    }
+                }
+            }));
+        let star_imported = star_imported_files(options.path(), self.files.values().flatten());
+        let mut files = Vec::new();
+        let mut backports = BackportTally::default();
+        let mut defining_modules = BTreeMap::new();
+        for file in self.files.values().flatten() {
+            let file = &with_star_exports(file, &star_imported);
+            let scoped = environment.scoped_file(options, file);
+            if !scoped.is_generated {
+                report.findings.extend(unused_import_findings(file));
+                report
+                    .findings
+                    .extend(environment.removed_stdlib_findings(file));
+                backports.add(file);
+            }
+            for import in file.imports.iter().filter(|import| !import.is_relative) {
+                let Some(module) = &import.module_name else {
+                    continue;
+                };
+                let imported = first_party_modules(
+                    options.path(),
+                    &environment.index,
+                    import,
+                    &mut defining_modules,
+                );
+                for module in imported.unwrap_or_else(|| vec![module.clone()]) {
+                    if !report.found_imports.contains(&module) {
+                        report.found_imports.push(module);
+                    }
+                }
+            }
+            files.push(scoped);
+        }
+        report
+            .findings
+            .extend(environment.backport_findings(&backports));
+        // The entry points run their modules as part of the installed project
+        files.push(ScopedFile {
+            scope: None,
+            is_test: false,
+            is_script: false,
+            is_generated: false,
+            imports: entry_point_imports,
+        });
+
+        let mut usage = UsageTally::default();
+        environment.match_files(&files, &mut report, &mut usage, cancel)?;
+        if options.report_script_only() && options.files().is_none() {
+            report
+                .findings
+                .extend(usage.script_only_findings(environment.checked_configurations()));
+        }
+        report.dependency_usage = usage.into_usage(&environment.index);
+        report.found_imports.sort();
+        report
+            .findings
+            .retain(|finding| options.checks(finding.rule));
+        finding::sort_findings(&mut report.findings);
+        for finding in &report.findings {
+            if !is_about_import(finding.rule) {
+                continue;
+            }
+            let distribution = environment
+                .index
+                .resolve(&finding.subject)
+                .into_iter()
+                .find_map(|provider| match provider {
+                    ModuleProvider::Distribution(name) => Some(name),
+                    ModuleProvider::FirstParty => None,
+                });
+            if let Some(distribution) = distribution {
+                report
+                    .import_distributions
+                    .insert(finding.subject.clone(), distribution);
+            }
+        }
+        stats.matching = started.elapsed();
+        report.stats = stats;
+        Ok(report)
+    }
+
+    /// Check a single file against the project's cached dependencies and environment,
+    /// parsing only that file if it isn't cached, and return the findings located in it.
+    ///
+    /// The file is matched on its own, so a missing dependency imported by several files is
+    /// reported in each of them rather than only where the project check first finds it.
+    /// Findings about the project as a whole, like unused dependencies, need every file and
+    /// are only reported by [`check`](Self::check). Files the project check skips, because
+    /// they're excluded or are tests that aren't included, have no findings.
+    pub fn check_file(&mut self, path: &Path) -> Result<Vec<Finding>, AnalysisError> {
+        let options = &self.options;
+        let relative = relative_path(options.path(), path);
+        let excluded = options
+            .excludes()
+            .iter()
+            .any(|exclude| relative.starts_with(exclude))
+            || (options.default_excludes() && walker::is_default_excluded(relative));
+        if excluded || (!options.include_tests() && walker::is_test_file(relative)) {
+            return Ok(Vec::new());
+        }
+        if !self.files.contains_key(path) {
+            let counters = ScanCounters::default();
+            for (path, file) in parse_files(&[path.to_path_buf()], options, &counters)? {
+                self.files.insert(path, file);
+            }
+        }
+        let Some(Ok(file)) = self.files.get(path) else {
+            return Ok(Vec::new());
+        };
+        let star_imported = star_imported_files(options.path(), self.files.values().flatten());
+        let file = &with_star_exports(file, &star_imported);
+
+        let environment = &mut self.environment;
+        environment.prepare_index(options);
+        let mut report = AnalysisReport::default();
+        let scoped = environment.scoped_file(options, file);
+        if !scoped.is_generated {
+            report.findings.extend(unused_import_findings(file));
+            report
+                .findings
+                .extend(environment.removed_stdlib_findings(file));
+        }
+        let files = [scoped];
+        environment.match_files(
+            &files,
+            &mut report,
+            &mut UsageTally::default(),
+            options.cancellation_token(),
+        )?;
+        let mut findings: Vec<Finding> = report
+            .findings
+            .into_iter()
+            .filter(|finding| {
+                finding
+                    .location
+                    .as_ref()
+                    .is_some_and(|location| location.path == path)
+                    && options.checks(finding.rule)
+            })
+            .collect();
+        finding::sort_findings(&mut findings);
+        Ok(findings)
+    }
+}
+
+/// An import of a module that has to come from a dependency
+struct ThirdPartyImport {
+    module: String,
+    location: Location,
+    is_guarded: bool,
+    /// The module imported in its place when the import fails, for guarded imports
+    fallback: Option<String>,
+    /// The import is inside an `if` that checks the platform
+    is_platform_guarded: bool,
+    /// The import is inside a function, so it only runs when the function is called
+    is_lazy: bool,
+    /// The import is inside `if TYPE_CHECKING:`
+    is_type_checking: bool,
+    /// The names the import binds are only used in type annotations
+    is_annotation_only: bool,
+    /// The import is inside `if __name__ == "__main__":`, or what it binds is only used there
+    main_guard_only: bool,
+    /// Every name the import binds is unused in its file
+    never_used: bool,
+}
+
+/// The third-party imports of a file and the extra or environment the file belongs to, if
+/// any
+struct ScopedFile {
+    scope: Option<String>,
+    is_test: bool,
+    /// The file is under one of the script directories
+    is_script: bool,
+    /// The file matches one of the generated file patterns
+    is_generated: bool,
+    imports: Vec<ThirdPartyImport>,
+}
+
+/// Collect the imports of a file that aren't relative, from the standard library, or
+/// provided by the project itself
+fn third_party_imports(
+    file: &ParsedFile,
+    index: &ModuleIndex,
+    python_version: Option<PythonVersion>,
+) -> Vec<ThirdPartyImport> {
+    let mut imports = Vec::new();
+    for import in &file.imports {
+        if import.is_relative {
+            continue;
+        }
+        let Some(module) = &import.module_name else {
+            continue;
+        };
+        let is_stdlib = match python_version {
+            Some(version) => stdlib::is_stdlib_in(module, version),
+            None => stdlib::is_stdlib(module),
+        };
+        if is_stdlib || index.is_first_party(module) {
+            continue;
+        }
+
+        // A from-import of a namespace package, like `from google.cloud import storage`,
+        // imports each name from the distribution providing its submodule
+        let is_unused = |name: &str| {
+            file.unused_imports
+                .iter()
+                .any(|unused| unused.line_number == import.line_number && unused.name == name)
+        };
+        let mut modules: Vec<(String, Vec<String>)> = Vec::new();
+        if import.is_from_import {
+            for (name, (bound, _)) in import.imported_names.iter().zip(import.bound_names()) {
+                let imported = index.from_import_module(module, name);
+                match modules.iter_mut().find(|(m, _)| *m == imported) {
+                    Some((_, bound_names)) => bound_names.push(bound),
+                    None => modules.push((imported, vec![bound])),
+                }
+            }
+        }
+        if modules.is_empty() {
+            let bound_names = import.bound_names().into_iter().map(|(bound, _)| bound);
+            modules.push((module.clone(), bound_names.collect()));
+        }
+
+        for (module, bound_names) in modules {
+            // An import that binds nothing used in its file doesn't need the dependency
+            let never_used = !bound_names.is_empty() && bound_names.iter().all(|b| is_unused(b));
+            imports.push(ThirdPartyImport {
+                module,
+                location: Location {
+                    path: file.path.clone(),
+                    line: import.line_number,
+                },
+                is_guarded: import.is_likely_exception_guarded,
+                fallback: import.fallback.clone(),
+                is_platform_guarded: import.is_platform_guarded,
+                is_lazy: import.is_in_function,
+                is_type_checking: import.is_type_checking,
+                is_annotation_only: import.is_annotation_only,
+                main_guard_only: import.main_guard_only,
+                never_used,
+            });
+        }
+    }
+    imports
+}
+
+/// Extras and hatch environments that install what the tests need, most specific first
+const TEST_SCOPES: &[&str] = &["test", "tests", "testing", "dev"];
+
+/// The scope test files belong to: the first test or dev extra or environment. Without one
+/// test files get a scope of their own, so their imports aren't checked against the
+/// runtime dependencies and don't count as using them.
+fn test_scope(scopes: &[&str]) -> String {
+    TEST_SCOPES
+        .iter()
+        .find_map(|name| {
+            scopes
+                .iter()
+                .find(|scope| normalize_name(scope) == *name)
+                .map(|scope| scope.to_string())
+        })
+        .unwrap_or_else(|| TEST_SCOPES[0].to_string())
+}
+
+/// Extras and hatch environments that install what the documentation needs, most specific
+/// first
+const DOCS_SCOPES: &[&str] = &["docs", "doc", "documentation"];
+
+/// The scope the code blocks of documentation belong to: the first docs extra or
+/// environment, or else a scope of their own, like [`test_scope`]
+fn docs_scope(scopes: &[&str]) -> String {
+    DOCS_SCOPES
+        .iter()
+        .find_map(|name| {
+            scopes
+                .iter()
+                .find(|scope| normalize_name(scope) == *name)
+                .map(|scope| scope.to_string())
+        })
+        .unwrap_or_else(|| DOCS_SCOPES[0].to_string())
+}
+
+fn relative_path<'a>(root: &Path, path: &'a Path) -> &'a Path {
+    path.strip_prefix(root).unwrap_or(path)
+}
+
+/// Find the extra or environment a file belongs to from the directories it is in. A
+/// directory belongs to the scope with the same name, ignoring a trailing `s` (so `tests/`
+/// belongs to `test`).
+fn scope_for_file(root: &Path, file: &Path, scopes: &[&str]) -> Option<String> {
+    let directories = relative_path(root, file).parent()?.components();
+    directories
+        .filter_map(|component| component.as_os_str().to_str())
+        .find_map(|directory| {
+            let directory = normalize_name(directory);
+            scopes
+                .iter()
+                .find(|scope| {
+                    let scope = normalize_name(scope);
+                    directory == scope
+                        || directory.strip_suffix('s') == Some(scope.as_str())
+                        || scope.strip_suffix('s') == Some(directory.as_str())
+                })
+                .map(|scope| scope.to_string())
+        })
+}
+
+/// Diff the imports visible to a configuration against its dependencies.
+///
+/// Only problems the configuration introduces are reported: missing imports in files that
+/// belong to it, and unused dependencies it adds on top of `base_dependencies`. Test files
+/// may also import what the tox environments and nox sessions install. Hatch
+/// environments mostly install tools that are run rather than imported, so their unused
+/// dependencies aren't reported, and neither are dependencies the index expects never to be
+/// imported (see [`ModuleIndex::unused_exemption`]).
+fn check_configuration(
+    configuration: Option<&Configuration>,
+    dependencies: &[Dependency],
+    base_dependencies: &[Dependency],
+    files: &[ScopedFile],
+    resolved: &ResolvedProject,
+    report: &mut AnalysisReport,
+    usage: &mut UsageTally,
+) {
+    let index = &resolved.index;
+    let extra = configuration.and_then(|c| c.extra());
+    let environment = configuration.and_then(|c| c.environment());
+    let scope = configuration.and_then(|c| c.scope());
+    // What the extras this one is an alternative to add, which may be installed instead
+    let alternatives: Vec<Dependency> = match (configuration, extra) {
+        (Some(configuration), Some(extra)) => resolved
+            .configurations
+            .iter()
+            .filter(|c| {
+                c.source() == configuration.source()
+                    && c.extra().is_some_and(|other| {
+                        project::are_exclusive(&resolved.exclusive_extras, extra, other)
+                    })
+            })
+            .flat_map(|c| c.dependencies())
+            .filter(|d| !dependencies.iter().any(|own| own.same_package(d)))
+            .cloned()
+            .collect(),
+        _ => Vec::new(),
+    };
+    let mut alternative_installed = false;
+    let tag = |finding: Finding| match configuration {
+        Some(configuration) => finding.with_configuration(configuration.name()),
+        None => finding,
+    };
+
+    let mut findings = Vec::new();
+    let mut missing_imports: Vec<MissingImport> = Vec::new();
+    let mut used_dependencies: Vec<&Dependency> = Vec::new();
+    let mut own_imports: Vec<(&Dependency, &ThirdPartyImport)> = Vec::new();
+    let mut through_extras: Vec<(String, String, &ThirdPartyImport)> = Vec::new();
+    for file in files {
+        let is_own_file = file.scope.as_deref() == scope;
+        if !is_own_file && file.scope.is_some() {
+            continue;
+        }
+
+        for import in &file.imports {
+            match index.find_dependency(&import.module, dependencies) {
+                Some(_) if import.never_used => {
+                    debug!("Ignoring unused import of {}", import.module);
+                }
+                Some(dependency) => {
+                    if is_own_file {
+                        own_imports.push((dependency, import));
+                    }
+                    usage.record(dependency, import, file);
+                    used_dependencies.push(dependency);
+                }
+                None if let Some(alternative) =
+                    index.find_dependency(&import.module, &alternatives) =>
+                {
+                    debug!(
+                        "{} is provided by {:?}, an alternative to extra {:?}",
+                        import.module, alternative, extra
+                    );
+                    alternative_installed = true;
+                }
+                None if index.is_provided(&import.module) => {
+                    debug!("{} is provided by the runtime", import.module);
+                }
+                // Missing imports in the base files are reported by the base configuration
+                None if !is_own_file => {}
+                // Modules the target Python removed from the standard library are reported
+                // where they're imported, with what replaces them
+                None if resolved.python_version.is_some_and(|version| {
+                    stdlib::removal(&import.module, &[], version).is_some()
+                }) => {}
+                None if file.is_test
+                    && let Some(dependency) = index
+                        .find_dependency(&import.module, &resolved.test_runner_dependencies) =>
+                {
+                    debug!(
+                        "{} in a test file is installed by a test runner",
+                        import.module
+                    );
+                    usage.record(dependency, import, file);
+                }
+                // Generated code imports the runtime of its generator, which only needs
+                // declaring if the project ships the generated files
+                None if file.is_generated && !resolved.report_generated => {
+                    debug!("Ignoring import of {} in generated code", import.module);
+                }
+                None => {
+                    let distribution = index
+                        .resolve(&import.module)
+                        .into_iter()
+                        .find_map(|provider| match provider {
+                            ModuleProvider::Distribution(name) => Some(name),
+                            ModuleProvider::FirstParty => None,
+                        })
+                        .unwrap_or_else(|| {
+                            let top_level = import.module.split('.').next();
+                            top_level.unwrap_or(&import.module).to_string()
+                        });
+
+                    // A distribution an extra of a declared dependency installs is there
+                    // when the extra is declared too, but only by accident
+                    let (declared_extra, reachable_via) =
+                        extra_installing(index, &distribution, dependencies);
+                    if let Some(via) = declared_extra {
+                        if !through_extras.iter().any(|(d, _, _)| *d == distribution) {
+                            through_extras.push((distribution, via, import));
+                        }
+                        continue;
+                    }
+
+                    let missing = MissingImport {
+                        module: import.module.clone(),
+                        distribution,
+                        reachable_via,
+                        is_optional: import.is_guarded,
+                        is_generated: file.is_generated,
+                        fallback: import.fallback.clone(),
+                        extra: extra.map(str::to_string),
+                        environment: environment.map(str::to_string),
+                        location: import.location.clone(),
+                        import_sites: vec![import.location.clone()],
+                    };
+
+                    // Guarded imports are optional and are reported with a lower severity,
+                    // unless the same distribution is also imported unconditionally
+                    // somewhere
+                    match missing_imports
+                        .iter_mut()
+                        .find(|m| m.distribution == missing.distribution)
+                    {
+                        Some(existing) => {
+                            let mut import_sites = std::mem::take(&mut existing.import_sites);
+                            import_sites.push(import.location.clone());
+                            if missing.precedence() > existing.precedence() {
+                                *existing = missing;
+                            }
+                            existing.import_sites = import_sites;
+                        }
+                        None => missing_imports.push(missing),
+                    }
+                }
+            }
+        }
+    }
+
+    for (distribution, via, import) in &through_extras {
+        findings.push(tag(Finding::new(
+            Rule::ImportedThroughExtra,
+            &import.module,
+            format!(
+                "`{}` is imported but only installed through `{}`; declare `{}` directly",
+                import.module, via, distribution
+            ),
+            Some(import.location.clone()),
+        )));
+    }
+
+    for missing in &missing_imports {
+        let explanation = missing_explanation(missing, configuration, resolved);
+        findings.push(tag(
+            missing_dependency_finding(missing).with_explanation(explanation)
+        ));
+    }
+
+    if let Some(configuration) = configuration {
+        findings.extend(
+            platform_findings(configuration, &own_imports)
+                .into_iter()
+                .map(tag),
+        );
+    }
+
+    let mut unused_dependencies = Vec::new();
+    let unused_reported = environment.is_none() && resolved.report_unused;
+    for dependency in dependencies.iter().filter(|_| unused_reported) {
+        if used_dependencies.contains(&dependency)
+            || base_dependencies.iter().any(|d| d.same_package(dependency))
+        {
+            continue;
+        }
+        // The imports that would use it are served by the alternative that is installed
+        if alternative_installed {
+            debug!(
+                "Not reporting {:?} as unused, an alternative to extra {:?} is installed",
+                dependency, extra
+            );
+            continue;
+        }
+        if let Some(name) = dependency.name() {
+            if let Some(exemption) = index.unused_exemption(name) {
+                debug!("Not reporting {} as unused ({})", name, exemption);
+                continue;
+            }
+            // An editable install of the project itself, like `-e .`, provides its own modules
+            if matches!(dependency, Dependency::PackagePath { .. })
+                && index
+                    .modules_for(name)
+                    .iter()
+                    .all(|module| index.is_first_party(module))
+            {
+                debug!("Not reporting {} as unused, it installs the project", name);
+                continue;
+            }
+            // A dependency from an included requirements file says where it came from
+            let included = configuration.and_then(|configuration| {
+                let provenance = configuration.provenance(name)?;
+                let base = configuration.source().path().parent()?;
+                (!provenance.included_via.is_empty()).then(|| provenance.describe(base))
+            });
+            let message = match included {
+                Some(declared) => {
+                    format!("`{}` is declared in {} but never imported", name, declared)
+                }
+                None => format!("`{}` is declared but never imported", name),
+            };
+            findings.push(tag(Finding::new(
+                Rule::UnusedDependency,
+                name,
+                message,
+                None,
+            )
+            .with_explanation(unused_explanation(name, configuration, resolved))));
+            if !unused_dependencies.iter().any(|n| n == name) {
+                unused_dependencies.push(name.to_string());
+            }
+        }
+    }
+
+    if let Some(configuration) = configuration {
+        report.configurations.push(ConfigurationReport {
+            name: configuration.name().to_string(),
+            extra: extra.map(str::to_string),
+            environment: environment.map(str::to_string),
+            unused_dependencies: unused_dependencies.clone(),
+            missing_imports: missing_imports
+                .iter()
+                .filter(|m| !m.is_optional && !m.is_generated)
+                .map(|m| m.module.clone())
+                .collect(),
+            declarations: configuration.declarations(),
+        });
+    }
+    for name in unused_dependencies {
+        if !report.unused_dependencies.contains(&name) {
+            report.unused_dependencies.push(name);
+        }
+    }
+    report.missing_imports.extend(missing_imports);
+    report.findings.extend(findings);
+}
+
+/// Compare the platforms each dependency is declared for with the platforms its imports run
+/// on.
+///
+/// A dependency with a platform marker that is imported unconditionally (outside a
+/// try/except, a platform check, or a function) breaks the project on every other platform.
+/// A dependency without a marker whose imports all check the platform first could be
+/// declared with one. Each dependency is reported at most once, at its first import.
+fn platform_findings(
+    configuration: &Configuration,
+    imports: &[(&Dependency, &ThirdPartyImport)],
+) -> Vec<Finding> {
+    let declared_in = configuration.source().path().display();
+    let mut findings = Vec::new();
+    let mut checked: Vec<&Dependency> = Vec::new();
+    for (dependency, _) in imports {
+        if checked.contains(dependency) {
+            continue;
+        }
+        checked.push(dependency);
+        let Some(name) = dependency.name() else {
+            continue;
+        };
+        let mut dependency_imports = imports
+            .iter()
+            .filter(|(d, _)| d == dependency)
+            .map(|(_, import)| *import);
+
+        match dependency.platform_marker() {
+            Some(marker) => {
+                if let Some(import) = dependency_imports
+                    .find(|i| !i.is_guarded && !i.is_platform_guarded && !i.is_lazy)
+                {
+                    let mut finding = Finding::new(
+                        Rule::UnguardedPlatformImport,
+                        name,
+                        format!(
+                            "`{}` is imported on every platform but `{}` is only declared for `{}` in {}",
+                            import.module, name, marker, declared_in
+                        ),
+                        Some(import.location.clone()),
+                    );
+                    if let Some(declaration) = configuration.declaration(name) {
+                        finding = finding.with_related(declaration);
+                    }
+                    findings.push(finding);
+                }
+            }
+            None => {
+                let dependency_imports: Vec<_> = dependency_imports.collect();
+                if dependency_imports.iter().all(|i| i.is_platform_guarded) {
+                    let import = dependency_imports[0];
+                    findings.push(Finding::new(
+                        Rule::MissingPlatformMarker,
+                        name,
+                        format!(
+                            "`{}` is only imported on some platforms; consider declaring `{}` with a platform marker in {}",
+                            import.module, name, declared_in
+                        ),
+                        Some(import.location.clone()),
+                    ));
+                }
+            }
+        }
+    }
+    findings
+}
+
+/// Find an extra of a declared dependency that installs a distribution. Returns the extra
+/// as `name[extra]` if the dependency is declared with it, and otherwise if the dependency
+/// is declared without it.
+fn extra_installing(
+    index: &ModuleIndex,
+    distribution: &str,
+    dependencies: &[Dependency],
+) -> (Option<String>, Option<String>) {
+    let mut reachable_via = None;
+    for (parent, extra) in index.extras_installing(distribution) {
+        let Some(dependency) = dependencies.iter().find(|d| d.satisfies(&parent)) else {
+            continue;
+        };
+        let via = format!("{}[{}]", dependency.name().unwrap_or(&parent), extra);
+        if dependency.has_extra(&extra) {
+            return (Some(via), None);
+        }
+        reachable_via.get_or_insert(via);
+    }
+    (None, reachable_via)
+}
+
+fn missing_dependency_finding(missing: &MissingImport) -> Finding {
+    let importer = if missing.is_generated {
+        "imported by generated code"
+    } else {
+        "imported"
+    };
+    let mut message = format!(
+        "`{}` is {} but no declared dependency provides it (expected `{}`)",
+        missing.module, importer, missing.distribution
+    );
+    if let Some(via) = &missing.reachable_via {
+        message.push_str(&format!(
+            "; `{}` would install it, declare it or the extra directly",
+            via
+        ));
+    }
+    let mut severity = Rule::MissingDependency.severity();
+    if missing.is_generated {
+        // Generated code is only reported when asked for, to list the runtimes it needs
+        severity = Severity::Info;
+    } else if missing.is_optional {
+        // An import guarded by an `ImportError` handler only breaks the code that expects
+        // it, and not even that when the handler imports a replacement
+        match &missing.fallback {
+            Some(fallback) => {
+                message.push_str(&format!(", falling back to `{}`", fallback));
+                severity = Severity::Info;
+            }
+            None => {
+                message.push_str("; the `ImportError` is caught but nothing replaces it");
+                severity = Severity::Warning;
+            }
+        }
+    }
+    let mut finding = Finding::new(
+        Rule::MissingDependency,
+        &missing.module,
+        message,
+        Some(missing.location.clone()),
+    );
+    finding.severity = severity;
+    finding
+}
+
+/// Explain a missing import: where it's imported, that it's neither in the standard library
+/// nor the project, and which distributions were expected to provide it
+fn missing_explanation(
+    missing: &MissingImport,
+    configuration: Option<&Configuration>,
+    environment: &ResolvedProject,
+) -> Explanation {
+    let top_level = missing.module.split('.').next().unwrap_or(&missing.module);
+    let stdlib = match environment.python_version {
+        Some(version) => format!(
+            "`{}` isn't in the standard library of Python {}",
+            top_level, version
+        ),
+        None => format!(
+            "`{}` isn't in the standard library of any Python version",
+            top_level
+        ),
+    };
+    let (providers, resolution) = environment.index.resolve_with_source(&missing.module);
+    let candidates: Vec<String> = providers
+        .iter()
+        .filter_map(|provider| match provider {
+            ModuleProvider::Distribution(name) => Some(format!("`{}`", name)),
+            ModuleProvider::FirstParty => None,
+        })
+        .collect();
+    let declared = match configuration {
+        Some(configuration) => format!("none of them is declared by `{}`", configuration.name()),
+        None => "no dependencies are declared".to_string(),
+    };
+    Explanation::new(
+        missing.import_sites.clone(),
+        vec![
+            stdlib,
+            format!("`{}` isn't one of the project's own modules", top_level),
+            format!(
+                "candidate distributions from {}: {}",
+                resolution,
+                candidates.join(", ")
+            ),
+            declared,
+        ],
+    )
+}
+
+/// Explain an unused dependency: where it's declared, the modules it was expected to be
+/// imported as, and what else was considered before reporting it
+fn unused_explanation(
+    name: &str,
+    configuration: Option<&Configuration>,
+    environment: &ResolvedProject,
+) -> Explanation {
+    let modules: Vec<String> = environment
+        .index
+        .modules_for(name)
+        .iter()
+        .map(|module| format!("`{}`", module))
+        .collect();
+    let mut reasons = Vec::new();
+    if let Some(configuration) = configuration {
+        reasons.push(format!("declared by `{}`", configuration.name()));
+    }
+    reasons.push(format!(
+        "expected to be imported as {}, but no checked file imports it",
+        modules.join(", ")
+    ));
+    reasons.push(match environment.entry_points.len() {
+        0 => "the project declares no entry points".to_string(),
+        count => format!("none of the {} entry points loads it", count),
+    });
+    reasons.push(
+        "it isn't a build or support package, and isn't listed as used without being imported"
+            .to_string(),
+    );
+    Explanation::new(Vec::new(), reasons)
+}
+
+/// The findings for the imported names a file never uses
+fn unused_import_findings(file: &ParsedFile) -> impl Iterator<Item = Finding> + '_ {
+    file.unused_imports.iter().map(|unused| {
+        Finding::new(
+            Rule::UnusedImport,
+            &unused.qualified_name,
+            format!("`{}` is imported but never used", unused.qualified_name),
+            Some(Location {
+                path: file.path.clone(),
+                line: unused.line_number,
+            }),
+        )
+    })
+}
+
+/// The first-party modules that define the names a from-import of a project module binds,
+/// following the re-exports of its package's `__init__.py`: `a.b` for `from a import Thing`
+/// when `a/__init__.py` has `from .b import Thing`. `None` for other imports. Each module
+/// and name is only looked up once, in `defining_modules`.
+fn first_party_modules(
+    root: &Path,
+    index: &ModuleIndex,
+    import: &PythonImport,
+    defining_modules: &mut BTreeMap<(String, String), String>,
+) -> Option<Vec<String>> {
+    let module = import.module_name.as_ref()?;
+    if !import.is_from_import || !index.is_first_party(module) {
+        return None;
+    }
+    let mut modules = Vec::new();
+    for name in &import.imported_names {
+        let defining = match name.as_str() {
+            "*" => module.clone(),
+            _ => defining_modules
+                .entry((module.clone(), name.clone()))
+                .or_insert_with(|| {
+                    walker::defining_module(root, module, name).unwrap_or_else(|| module.clone())
+                })
+                .clone(),
+        };
+        if !modules.contains(&defining) {
+            modules.push(defining);
+        }
+    }
+    Some(modules)
+}
+
+/// The project files that other project files star-import, like `base.py` for
+/// `from .base import *`
+fn star_imported_files<'a>(
+    root: &Path,
+    files: impl Iterator<Item = &'a ParsedFile>,
+) -> BTreeSet<PathBuf> {
+    let mut star_imported = BTreeSet::new();
+    for file in files {
+        for import in file.imports.iter().filter(|i| i.imported_names == ["*"]) {
+            let parts: PathBuf = import
+                .module_name
+                .iter()
+                .flat_map(|module| module.split('.'))
+                .collect();
+            let bases: Vec<PathBuf> = if import.is_relative {
+                let mut base = file.path.parent();
+                for _ in 1..import.relative_level {
+                    base = base.and_then(Path::parent);
+                }
+                base.map(Path::to_path_buf).into_iter().collect()
+            } else {
+                vec![root.to_path_buf(), root.join("src")]
+            };
+            for base in bases {
+                let module = base.join(&parts);
+                star_imported.insert(module.with_extension("py"));
+                star_imported.insert(module.join("__init__.py"));
+            }
+        }
+    }
+    star_imported
+}
+
+/// A file as the files star-importing it see it: its module level imports are exported to
+/// them, so they may be used there even if the file itself doesn't use them
+fn with_star_exports<'a>(
+    file: &'a ParsedFile,
+    star_imported: &BTreeSet<PathBuf>,
+) -> Cow<'a, ParsedFile> {
+    if !star_imported.contains(&file.path) || file.unused_imports.is_empty() {
+        return Cow::Borrowed(file);
+    }
+    debug!(
+        "{} is star-imported, so its module level imports may be used elsewhere",
+        file.path.display()
+    );
+    let mut file = file.clone();
+    file.unused_imports.retain(|unused| !unused.is_module_level);
+    Cow::Owned(file)
+}
+
+/// The parses of a session's files saved between runs, by path relative to the project root
+#[derive(Debug, Serialize, Deserialize)]
+struct SessionState {
+    /// The version of depwise that saved the state, whose parses may differ from another's
+    version: String,
+    check_unused_imports: bool,
+    files: BTreeMap<PathBuf, SavedFile>,
+}
+
+/// A saved [`ParsedFile`]
+#[derive(Debug, Serialize, Deserialize)]
+struct SavedFile {
+    imports: Vec<PythonImport>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    unused_imports: Vec<UnusedImport>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    degraded: Option<String>,
+}
+
+/// Whether a file is named like a dependency source or the depwise settings, which change
+/// what every file is checked against
+fn is_dependency_manifest(path: &Path) -> bool {
+    let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+        return false;
+    };
+    matches!(
+        name,
+        "pyproject.toml"
+            | "pixi.toml"
+            | "setup.py"
+            | "setup.cfg"
+            | "tox.ini"
+            | "noxfile.py"
+            | "depwise.toml"
+    ) || (name.starts_with("requirements") && (name.ends_with(".txt") || name.ends_with(".in")))
+        || (name.starts_with("environment") && (name.ends_with(".yml") || name.ends_with(".yaml")))
+}
+
+/// A parsed file, or why it was skipped
+type ParsedOrSkipped = Result<ParsedFile, AnalysisWarning>;
+
+/// The imports of a single Python file
+#[derive(Debug, Clone)]
+struct ParsedFile {
+    path: PathBuf,
+    imports: Vec<PythonImport>,
+    /// Imported names never used in the file, only collected when requested
+    unused_imports: Vec<UnusedImport>,
+    /// Why the parser couldn't read the file, when its imports were found line by line
+    degraded: Option<String>,
+}
+
+/// Parse the imports of Python files, spread over `options.jobs()` threads, counting what's
+/// parsed in `counters`. Files that can't be read or parsed are returned without a parse.
+fn parse_files(
+    paths: &[PathBuf],
+    options: &AnalysisOptions,
+    counters: &ScanCounters,
+) -> Result<Vec<(PathBuf, ParsedOrSkipped)>, AnalysisError> {
+    type ParsedChunk = Result<Vec<(PathBuf, ParsedOrSkipped)>, AnalysisError>;
+
+    let chunk_size = paths.len().div_ceil(options.jobs()).max(1);
+    let chunks: Vec<ParsedChunk> = thread::scope(|scope| {
+        let handles: Vec<_> = paths
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    let mut files = Vec::new();
+                    for path in chunk {
+                        options.cancellation_token().check(Phase::Scanning)?;
+                        files.push((path.clone(), parse_file(path, options, counters)?));
+                    }
+                    Ok(files)
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("parser thread panicked"))
+            .collect()
+    });
+
+    let mut files = Vec::with_capacity(paths.len());
+    for chunk in chunks {
+        files.extend(chunk?);
+    }
+    Ok(files)
+}
+
+/// Parse a file, or say why it was skipped if it can't be read or parsed
+fn parse_file(
+    file: &Path,
+    options: &AnalysisOptions,
+    counters: &ScanCounters,
+) -> Result<ParsedOrSkipped, AnalysisError> {
+    let skipped = |reason: String| {
+        warn!("Skipping {}: {}", file.display(), reason);
+        counters.skipped();
+        Ok(Err(AnalysisWarning::SkippedFile {
+            path: file.to_path_buf(),
+            reason,
+        }))
+    };
+    // The size is checked before reading, so a huge generated file is never loaded
+    let mut handle = match std::fs::File::open(file) {
+        Ok(handle) => handle,
+        Err(e) => return skipped(e.to_string()),
+    };
+    if let Some(max) = options.max_file_size() {
+        match handle.metadata() {
+            Ok(metadata) if metadata.len() > max => {
+                return skipped(format!(
+                    "{} bytes is over the maximum file size of {} bytes",
+                    metadata.len(),
+                    max
+                ));
+            }
+            Ok(_) => {}
+            Err(e) => return skipped(e.to_string()),
+        }
+    }
+    let mut source = String::new();
+    if let Err(e) = handle.read_to_string(&mut source) {
+        return skipped(e.to_string());
+    }
+    // Documentation is only read for the imports of its code blocks
+    if walker::is_doc_file(file) {
+        if parser::may_contain_imports(&source) {
+            counters.parsed(source.len());
+        } else {
+            counters.without_imports();
+        }
+        return Ok(Ok(ParsedFile {
+            path: file.to_path_buf(),
+            imports: docs::parse_imports(file, &source),
+            unused_imports: Vec::new(),
+            degraded: None,
+        }));
+    }
+    let mut parser = PythonParser::new(&source);
+    // Syntax newer than the parser knows still has plain import statements
+    let (imports, mut unused_imports, degraded) = match parser.parse_imports_with_usage() {
+        Ok((imports, unused_imports)) => (imports, unused_imports, None),
+        Err(e) => {
+            warn!("Reading only the imports of {}: {}", file.display(), e);
+            (import_lines::scan(&source), Vec::new(), Some(e.to_string()))
+        }
+    };
+    if parser::may_contain_imports(&source) {
+        counters.parsed(source.len());
+    } else {
+        counters.without_imports();
+    }
+
+    // Package `__init__.py` files mostly re-export names, so they're never checked
+    let is_package_init = file.file_name().is_some_and(|name| name == "__init__.py");
+    if !options.check_unused_imports() || is_package_init {
+        unused_imports.clear();
+    }
+
+    Ok(Ok(ParsedFile {
+        path: file.to_path_buf(),
+        imports,
+        unused_imports,
+        degraded,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EnvironmentBackend;
+    use crate::cancel::CancellationToken;
+    use crate::finding::Severity;
+    use std::time::Duration;
+    use tracing_subscriber::fmt::format::FmtSpan;
+
+    fn init_tracing() {
+        let _ = tracing_subscriber::fmt()
+            .with_env_filter("debug")
+            .with_span_events(FmtSpan::CLOSE)
+            .try_init();
+    }
+
+    fn simulated(path: &Path) -> AnalysisOptions {
+        AnalysisOptions::builder(path)
+            .backend(EnvironmentBackend::Simulated)
+            .build()
+    }
+
+    #[test]
+    fn test_analyze_flat_project() -> Result<(), AnalysisError> {
+        init_tracing();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("requirements.txt"), "requests\nflask\n").unwrap();
+        std::fs::write(
+            dir.path().join("main.py"),
+            "import os\nimport requests\nimport helpers\nimport yaml\n\
+             import xml.etree.ElementTree\nimport concurrent.futures\nfrom os import path\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("helpers.py"),
+            "try:\n    import ujson\nexcept ImportError:\n    ujson = None\n\
+             try:\n    import orjson as json\nexcept ImportError:\n    import json\n",
+        )
+        .unwrap();
+
+        let report = Analysis::run(&simulated(dir.path()))?;
+
+        assert_eq!(
+            report.source(),
+            Some(&EnvironmentBuilderSource::RequirementsTxt(
+                dir.path().join("requirements.txt")
+            ))
+        );
+        let missing: Vec<(&str, &str, bool)> = report
+            .missing_imports()
+            .iter()
+            .map(|m| (m.module(), m.distribution(), m.is_optional()))
+            .collect();
+        assert_eq!(
+            missing,
+            vec![
+                ("ujson", "ujson", true),
+                ("orjson", "orjson", true),
+                ("yaml", "pyyaml", false)
+            ]
+        );
+        assert_eq!(report.missing_imports()[1].fallback(), Some("json"));
+        // Guarded imports are only errors when nothing guards them
+        let reported: Vec<(&str, Severity)> = report
+            .findings()
+            .iter()
+            .filter(|f| f.rule == Rule::MissingDependency)
+            .map(|f| (f.subject.as_str(), f.severity))
+            .collect();
+        assert_eq!(
+            reported,
+            vec![
+                ("orjson", Severity::Info),
+                ("ujson", Severity::Warning),
+                ("yaml", Severity::Error)
+            ]
+        );
+        assert_eq!(report.unused_dependencies(), &["flask".to_string()]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_file_parses_only_the_file() -> Result<(), AnalysisError> {
+        init_tracing();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("requirements.txt"), "requests\n").unwrap();
+        std::fs::write(dir.path().join("app.py"), "import yaml\nimport requests\n").unwrap();
+        let worker = dir.path().join("worker.py");
+        std::fs::write(&worker, "import os\nimport yaml\n").unwrap();
+        std::fs::write(dir.path().join("broken.py"), "def (:\n").unwrap();
+
+        let mut session = AnalysisSession::new(simulated(dir.path()))?;
+        let findings = session.check_file(&worker)?;
+        assert_eq!(session.files.keys().collect::<Vec<_>>(), [&worker]);
+        // The project check reports yaml where app.py imports it first, the file check
+        // reports the file's own import
+        let found: Vec<(Rule, &str, usize)> = findings
+            .iter()
+            .map(|f| {
+                (
+                    f.rule,
+                    f.subject.as_str(),
+                    f.location.as_ref().unwrap().line,
+                )
+            })
+            .collect();
+        assert_eq!(found, [(Rule::MissingDependency, "yaml", 2)]);
+        assert!(session.check()?.findings().iter().all(|f| {
+            f.location
+                .as_ref()
+                .is_none_or(|location| location.path != worker)
+        }));
+        Ok(())
+    }
+
+    #[test]
+    fn test_unused_imports_roll_up() -> Result<(), AnalysisError> {
+        init_tracing();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("requirements.txt"), "requests\nnumpy\n").unwrap();
+        std::fs::write(
+            dir.path().join("main.py"),
+            "import requests\nimport numpy as np\n\nrequests.get('https://example.com')\n",
+        )
+        .unwrap();
+
+        let report = Analysis::run(&simulated(dir.path()))?;
+        assert!(report.unused_dependencies().is_empty());
+        assert!(report.findings().is_empty());
+
+        let options = AnalysisOptions::builder(dir.path())
+            .backend(EnvironmentBackend::Simulated)
+            .check_unused_imports(true)
+            .build();
+        let report = Analysis::run(&options)?;
+        assert_eq!(report.unused_dependencies(), &["numpy".to_string()]);
+        let rules: Vec<(Rule, &str)> = report
+            .findings()
+            .iter()
+            .map(|f| (f.rule, f.subject.as_str()))
+            .collect();
+        assert_eq!(
+            rules,
+            vec![
+                (Rule::UnusedDependency, "numpy"),
+                (Rule::UnusedImport, "numpy")
+            ]
+        );
+        assert_eq!(
+            report.findings()[1].location,
+            Some(Location {
+                path: dir.path().join("main.py"),
+                line: 2
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_availability_flags() -> Result<(), AnalysisError> {
+        init_tracing();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("requirements.txt"), "numpy\n").unwrap();
+        std::fs::write(
+            dir.path().join("main.py"),
+            "HAS_NUMPY = False\ntry:\n    import numpy\n    HAS_NUMPY = True\nexcept ImportError:\n    pass\n\nif HAS_NUMPY:\n    print('fast path')\n",
+        )
+        .unwrap();
+
+        let options = AnalysisOptions::builder(dir.path())
+            .backend(EnvironmentBackend::Simulated)
+            .check_unused_imports(true)
+            .build();
+        let report = Analysis::run(&options)?;
+        assert!(report.missing_imports().is_empty());
+        assert!(report.unused_dependencies().is_empty());
+        assert!(report.findings().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_imports_of_namespace_packages() -> Result<(), AnalysisError> {
+        init_tracing();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("requirements.txt"),
+            "google-cloud-storage\nazure-identity\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("main.py"),
+            "from google.cloud import storage, bigquery\nfrom azure import identity\n\nprint(storage, bigquery, identity)\n",
+        )
+        .unwrap();
+
+        let options = AnalysisOptions::builder(dir.path())
+            .backend(EnvironmentBackend::Simulated)
+            .build();
+        let report = Analysis::run(&options)?;
+        assert!(report.unused_dependencies().is_empty());
+        let missing: Vec<(&str, &str)> = report
+            .missing_imports()
+            .iter()
+            .map(|m| (m.module(), m.distribution()))
+            .collect();
+        assert_eq!(
+            missing,
+            [("google.cloud.bigquery", "google-cloud-bigquery")]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_star_imported_modules_export_their_imports() -> Result<(), AnalysisError> {
+        init_tracing();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("requirements.txt"), "numpy\nrequests\n").unwrap();
+        std::fs::create_dir(dir.path().join("app")).unwrap();
+        std::fs::write(dir.path().join("app/__init__.py"), "").unwrap();
+        std::fs::write(
+            dir.path().join("app/helpers.py"),
+            "import numpy as np\nimport requests\n\ndef get(url):\n    import json\n    return requests.get(url)\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("app/main.py"),
+            "from .helpers import *\nfrom os import *\n\nprint(np.array([getcwd()]))\n",
+        )
+        .unwrap();
+
+        let options = AnalysisOptions::builder(dir.path())
+            .backend(EnvironmentBackend::Simulated)
+            .check_unused_imports(true)
+            .build();
+        let report = Analysis::run(&options)?;
+        assert!(report.unused_dependencies().is_empty());
+        let rules: Vec<(Rule, &str)> = report
+            .findings()
+            .iter()
+            .map(|f| (f.rule, f.subject.as_str()))
+            .collect();
+        // The function level import isn't exported by the star import
+        assert_eq!(rules, vec![(Rule::UnusedImport, "json")]);
+        Ok(())
+    }
+
+    #[cfg(feature = "backends-exec")]
+    #[test]
+    fn test_warnings() -> Result<(), AnalysisError> {
+        init_tracing();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("requirements.txt"), "requests\n").unwrap();
+        std::fs::write(dir.path().join("main.py"), "import requests\n").unwrap();
+        std::fs::write(dir.path().join("broken.py"), "import os\n\ndef broken(:\n").unwrap();
+        std::fs::write(
+            dir.path().join("tox.ini"),
+            "[testenv]\ndeps = -r missing.txt\n",
+        )
+        .unwrap();
+
+        let options = AnalysisOptions::builder(dir.path())
+            .backend(EnvironmentBackend::UV)
+            .build();
+        let report = Analysis::run(&options)?;
+        assert!(report.findings().is_empty());
+        let warnings: Vec<(&str, Option<&Path>)> = report
+            .warnings()
+            .iter()
+            .map(|warning| (warning.code(), warning.path()))
+            .collect();
+        assert_eq!(
+            warnings,
+            [
+                ("skipped-source", Some(dir.path().join("tox.ini").as_path())),
+                ("backend-fallback", None),
+                (
+                    "degraded-parse",
+                    Some(dir.path().join("broken.py").as_path())
+                ),
+            ]
+        );
+
+        // The simulated backend is meant to match with the built-in mapping
+        assert_eq!(Analysis::run(&simulated(dir.path()))?.warnings().len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_session_state() -> Result<(), AnalysisError> {
+        init_tracing();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("requirements.txt"), "requests\nflask\n").unwrap();
+        std::fs::write(dir.path().join("main.py"), "import requests\n").unwrap();
+        std::fs::write(dir.path().join("app.py"), "import flask\n").unwrap();
+        let mut session = AnalysisSession::new(simulated(dir.path()))?;
+        assert!(session.check()?.findings().is_empty());
+        let state = session.save_state();
+
+        std::fs::write(dir.path().join("app.py"), "import yaml\n").unwrap();
+        std::fs::write(dir.path().join("new.py"), "import click\n").unwrap();
+        let rules = |report: AnalysisReport| -> Vec<(Rule, String)> {
+            report
+                .findings()
+                .iter()
+                .map(|finding| (finding.rule, finding.subject.clone()))
+                .collect()
+        };
+
+        // Only the changed file and the one the state doesn't have are parsed, and the
+        // unchanged file still counts as using `requests`
+        let mut session = AnalysisSession::new(simulated(dir.path()))?;
+        assert!(session.load_state(&state, &[PathBuf::from("app.py")])?);
+        let report = session.check()?;
+        assert_eq!(report.stats().files_cached, 1);
+        assert_eq!(
+            rules(report),
+            [
+                (Rule::MissingDependency, "click".to_string()),
+                (Rule::MissingDependency, "yaml".to_string()),
+                (Rule::UnusedDependency, "flask".to_string()),
+            ]
+        );
+
+        // A file left out of the changes keeps the imports it was saved with
+        let mut session = AnalysisSession::new(simulated(dir.path()))?;
+        assert!(session.load_state(&state, &[])?);
+        assert_eq!(
+            rules(session.check()?),
+            [(Rule::MissingDependency, "click".to_string())]
+        );
+
+        // Nothing is reused when a dependency manifest changed
+        let mut session = AnalysisSession::new(simulated(dir.path()))?;
+        let changed = [PathBuf::from("requirements.txt")];
+        assert!(!session.load_state(&state, &changed)?);
+        assert!(!session.load_state(&state, &[PathBuf::from("sub/pyproject.toml")])?);
+        assert_eq!(session.check()?.stats().files_cached, 0);
+
+        let options = AnalysisOptions::builder(dir.path())
+            .backend(EnvironmentBackend::Simulated)
+            .check_unused_imports(true)
+            .build();
+        assert!(!AnalysisSession::new(options)?.load_state(&state, &[])?);
+        assert!(matches!(
+            session.load_state("{", &[]),
+            Err(AnalysisError::StateError(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_file_size() -> Result<(), AnalysisError> {
+        init_tracing();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("requirements.txt"), "requests\n").unwrap();
+        // `under.py` is exactly the limit and `over.py` a byte over it
+        std::fs::write(
+            dir.path().join("under.py"),
+            "import requests  # padding....\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("over.py"),
+            "import yaml  # padding.........\n",
+        )
+        .unwrap();
+
+        let options = AnalysisOptions::builder(dir.path())
+            .backend(EnvironmentBackend::Simulated)
+            .max_file_size(Some(31))
+            .build();
+        let report = Analysis::run(&options)?;
+        // `over.py` isn't parsed, so `yaml` isn't missing, and `under.py` uses `requests`
+        assert!(report.findings().is_empty(), "{:?}", report.findings());
+        let [AnalysisWarning::SkippedFile { path, reason }] = report.warnings() else {
+            panic!("Expected one skipped file: {:?}", report.warnings());
+        };
+        assert_eq!(path, &dir.path().join("over.py"));
+        assert_eq!(reason, "32 bytes is over the maximum file size of 31 bytes");
+        assert_eq!(report.stats().files_skipped, 1);
+
+        let options = AnalysisOptions::builder(dir.path())
+            .backend(EnvironmentBackend::Simulated)
+            .max_file_size(None)
+            .build();
+        let report = Analysis::run(&options)?;
+        assert!(report.warnings().is_empty());
+        assert_eq!(report.findings()[0].rule, Rule::MissingDependency);
+        Ok(())
+    }
+
+    #[test]
+    fn test_degraded_parse() -> Result<(), AnalysisError> {
+        init_tracing();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("requirements.txt"), "requests\n").unwrap();
+        std::fs::write(
+            dir.path().join("main.py"),
+            "import requests\nimport yaml\ntry:\n    import ujson\nexcept ImportError:\n    ujson = None\n\ndef broken(:\n    pass\n",
+        )
+        .unwrap();
+
+        let report = Analysis::run(&simulated(dir.path()))?;
+        let findings: Vec<(&str, Severity)> = report
+            .findings()
+            .iter()
+            .map(|finding| (finding.subject.as_str(), finding.severity))
+            .collect();
+        // The file is only read line by line, but its imports are still matched
+        assert_eq!(
+            findings,
+            [("ujson", Severity::Warning), ("yaml", Severity::Error)]
+        );
+        let [AnalysisWarning::DegradedParse { path, .. }] = report.warnings() else {
+            panic!("Expected a degraded parse: {:?}", report.warnings());
+        };
+        assert_eq!(path, &dir.path().join("main.py"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_stats() -> Result<(), AnalysisError> {
+        init_tracing();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("requirements.txt"), "requests\n").unwrap();
+        let main = dir.path().join("main.py");
+        std::fs::write(&main, "import requests\n").unwrap();
+        std::fs::write(dir.path().join("constants.py"), "TIMEOUT = 10\n").unwrap();
+        std::fs::write(dir.path().join("broken.py"), b"import os\n\xff\n").unwrap();
+        std::fs::create_dir(dir.path().join(".venv")).unwrap();
+        std::fs::write(dir.path().join(".venv/site.py"), "import sys\n").unwrap();
+
+        let mut session = AnalysisSession::new(simulated(dir.path()))?;
+        let stats = session.check()?.stats().clone();
+        assert_eq!(stats.files_discovered, 3);
+        assert_eq!(stats.files_cached, 0);
+        assert_eq!(stats.files_parsed, 1);
+        assert_eq!(stats.files_without_imports, 1);
+        assert_eq!(stats.files_skipped, 1);
+        assert_eq!(stats.bytes_parsed, "import requests\n".len() as u64);
+        assert!(stats.resolving > Duration::ZERO);
+        assert_eq!(
+            (stats.repodata_downloads, stats.repodata_cache_hits),
+            (0, 0)
+        );
+
+        // The next check reuses every parse and the environment
+        let stats = session.check()?.stats().clone();
+        assert_eq!((stats.files_cached, stats.files_parsed), (3, 0));
+        assert_eq!(stats.resolving, Duration::ZERO);
+
+        std::fs::write(&main, "import requests\nimport json\n").unwrap();
+        session.invalidate(&main)?;
+        let stats = session.check()?.stats().clone();
+        assert_eq!((stats.files_cached, stats.files_parsed), (2, 1));
+        assert_eq!(stats.bytes_parsed, 28);
+        Ok(())
+    }
+
+    #[test]
+    fn test_extras_are_checked_independently() -> Result<(), AnalysisError> {
+        init_tracing();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("pyproject.toml"),
+            r#"[project]
+name = "example"
+dependencies = ["requests"]
+
+[project.optional-dependencies]
+docs = ["sphinx", "furo"]
+"#,
+        )
+        .unwrap();
+        std::fs::create_dir_all(dir.path().join("docs")).unwrap();
+        std::fs::write(dir.path().join("main.py"), "import requests\n").unwrap();
+        std::fs::write(
+            dir.path().join("docs/conf.py"),
+            "import sphinx\nimport requests\nimport myst_parser\n",
+        )
+        .unwrap();
+
+        let report = Analysis::run(&simulated(dir.path()))?;
+
+        let pyproject = dir.path().join("pyproject.toml").display().to_string();
+        let [base, docs] = report.configurations() else {
+            panic!("Expected a base and a docs configuration");
+        };
+        assert_eq!((base.name(), base.extra()), (pyproject.as_str(), None));
+        assert!(base.unused_dependencies().is_empty());
+        assert!(base.missing_imports().is_empty());
+
+        assert_eq!(docs.name(), format!("{}[docs]", pyproject));
+        assert_eq!(docs.extra(), Some("docs"));
+        assert_eq!(docs.unused_dependencies(), &["furo".to_string()]);
+        assert_eq!(docs.missing_imports(), &["myst_parser".to_string()]);
+        assert!(
+            report
+                .findings()
+                .iter()
+                .all(|f| f.configuration == Some(format!("{}[docs]", pyproject)))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_select_configuration() -> Result<(), AnalysisError> {
+        init_tracing();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("pyproject.toml"),
+            r#"[project]
+name = "example"
+dependencies = ["requests", "rich"]
+
+[project.optional-dependencies]
+dev = ["pytest", "mypy"]
+docs = ["sphinx"]
+"#,
+        )
+        .unwrap();
+        std::fs::create_dir_all(dir.path().join("dev")).unwrap();
+        std::fs::write(dir.path().join("main.py"), "import requests\n").unwrap();
+        std::fs::write(
+            dir.path().join("dev/check.py"),
+            "import pytest\nimport hypothesis\n",
+        )
+        .unwrap();
+
+        let options = AnalysisOptions::builder(dir.path())
+            .backend(EnvironmentBackend::Simulated)
+            .configuration("dev")
+            .build();
+        let report = Analysis::run(&options)?;
+        let [dev] = report.configurations() else {
+            panic!("Expected only the dev configuration");
+        };
+        assert_eq!(dev.extra(), Some("dev"));
+        assert_eq!(dev.unused_dependencies(), &["mypy".to_string()]);
+        assert_eq!(dev.missing_imports(), &["hypothesis".to_string()]);
+        // Neither the base configuration's rich nor the docs extra's sphinx are reported
+        let subjects: Vec<&str> = report
+            .findings()
+            .iter()
+            .map(|f| f.subject.as_str())
+            .collect();
+        assert_eq!(subjects, ["hypothesis", "mypy"]);
+
+        let options = AnalysisOptions::builder(dir.path())
+            .backend(EnvironmentBackend::Simulated)
+            .configuration("dev")
+            .configuration("test")
+            .build();
+        let pyproject = dir.path().join("pyproject.toml").display().to_string();
+        match Analysis::run(&options) {
+            Err(AnalysisError::UnknownConfiguration(name, available)) => {
+                assert_eq!(name, "test");
+                assert_eq!(
+                    available,
+                    [pyproject, "dev".to_string(), "docs".to_string()]
+                );
+            }
+            other => panic!("Expected an unknown configuration, got {:?}", other),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_documentation_code_blocks() -> Result<(), AnalysisError> {
+        init_tracing();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("pyproject.toml"),
+            r#"[project]
+name = "example"
+dependencies = ["requests"]
+
+[project.optional-dependencies]
+docs = ["sphinx"]
+"#,
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("main.py"), "import requests\n").unwrap();
+        std::fs::write(
+            dir.path().join("README.md"),
+            "# Example\n\n```python\nimport sphinx\nimport yaml\n```\n\n```python\nsetup(\n```\n",
+        )
+        .unwrap();
+
+        // Without the docs, sphinx looks unused
+        let report = Analysis::run(&simulated(dir.path()))?;
+        assert_eq!(report.unused_dependencies(), &["sphinx".to_string()]);
+
+        let options = AnalysisOptions::builder(dir.path())
+            .backend(EnvironmentBackend::Simulated)
+            .include_docs(true)
+            .build();
+        let report = Analysis::run(&options)?;
+        assert!(report.unused_dependencies().is_empty());
+        assert!(report.warnings().is_empty());
+        // What the docs import is checked against the docs extra, at its line in the file
+        let [missing] = report.missing_imports() else {
+            panic!("Expected yaml to be missing");
+        };
+        assert_eq!(missing.module(), "yaml");
+        assert_eq!(missing.extra(), Some("docs"));
+        assert_eq!(missing.location().path, dir.path().join("README.md"));
+        assert_eq!(missing.location().line, 5);
+        Ok(())
+    }
+
+    #[test]
+    fn test_hatch_environment_scopes_tests() -> Result<(), AnalysisError> {
+        init_tracing();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("pyproject.toml"),
+            r#"[project]
+name = "example"
+dependencies = ["requests"]
+
+[tool.hatch.envs.test]
+dependencies = ["pytest", "pytest-cov"]
+"#,
+        )
+        .unwrap();
+        std::fs::create_dir_all(dir.path().join("tests")).unwrap();
+        std::fs::write(
+            dir.path().join("main.py"),
+            "import requests
+",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("tests/test_main.py"),
+            "import pytest
+import yaml
+",
+        )
+        .unwrap();
+
+        let report = Analysis::run(&simulated(dir.path()))?;
+
+        let pyproject = dir.path().join("pyproject.toml").display().to_string();
+        let [base, test] = report.configurations() else {
+            panic!("Expected a base and a test configuration");
+        };
+        assert!(base.missing_imports().is_empty());
+        assert_eq!(test.name(), format!("{} (hatch env test)", pyproject));
+        assert_eq!(test.environment(), Some("test"));
+        // pytest-cov is a plugin that's never imported, which is fine for an environment
+        assert!(test.unused_dependencies().is_empty());
+        assert_eq!(test.missing_imports(), &["yaml".to_string()]);
+        assert_eq!(report.findings().len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_test_files_are_checked_separately() -> Result<(), AnalysisError> {
+        init_tracing();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("requirements.txt"), "requests\n").unwrap();
+        std::fs::create_dir_all(dir.path().join("tests")).unwrap();
+        std::fs::write(dir.path().join("main.py"), "import requests\n").unwrap();
+        std::fs::write(
+            dir.path().join("tests/test_x.py"),
+            "import pytest\nimport requests\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("conftest.py"), "import hypothesis\n").unwrap();
+
+        // Without a test extra the test imports aren't held against the runtime dependencies
+        let report = Analysis::run(&simulated(dir.path()))?;
+        assert!(report.findings().is_empty());
+        assert!(report.missing_imports().is_empty());
+
+        // With one they're checked against it, and test-only installs don't satisfy app code
+        std::fs::remove_file(dir.path().join("requirements.txt")).unwrap();
+        std::fs::write(
+            dir.path().join("pyproject.toml"),
+            r#"[project]
+name = "example"
+dependencies = ["requests"]
+
+[project.optional-dependencies]
+dev = ["pytest", "pyyaml"]
+"#,
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("main.py"), "import requests\nimport yaml\n").unwrap();
+        let report = Analysis::run(&simulated(dir.path()))?;
+        let missing: Vec<(&str, Option<&str>)> = report
+            .missing_imports()
+            .iter()
+            .map(|m| (m.module(), m.extra()))
+            .collect();
+        assert_eq!(missing, vec![("yaml", None), ("hypothesis", Some("dev"))]);
+
+        let options = AnalysisOptions::builder(dir.path())
+            .backend(EnvironmentBackend::Simulated)
+            .include_tests(false)
+            .build();
+        let report = Analysis::run(&options)?;
+        let missing: Vec<&str> = report
+            .missing_imports()
+            .iter()
+            .map(|m| m.module())
+            .collect();
+        assert_eq!(missing, vec!["yaml"]);
+        assert!(!report.found_imports().contains(&"pytest".to_string()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_first_party_reexports_are_attributed() -> Result<(), AnalysisError> {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("requirements.txt"), "requests\n").unwrap();
+        std::fs::create_dir(dir.path().join("a")).unwrap();
+        std::fs::write(
+            dir.path().join("a/__init__.py"),
+            "from .b import Thing\nfrom requests import get\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("a/b.py"), "class Thing:\n    pass\n").unwrap();
+        std::fs::write(
+            dir.path().join("main.py"),
+            "from a import Thing, get\nimport requests\n",
+        )
+        .unwrap();
+
+        let report = Analysis::run(&simulated(dir.path()))?;
+        // `from a import Thing` imports the module defining `Thing`, and `get` stays with
+        // the package re-exporting it from another distribution
+        assert_eq!(report.found_imports(), ["a", "a.b", "requests"]);
+        assert!(report.findings().is_empty(), "{:?}", report.findings());
+        Ok(())
+    }
+
+    #[test]
+    fn test_platform_markers_match_import_guards() -> Result<(), AnalysisError> {
+        init_tracing();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("requirements.txt"),
+            "pywin32; sys_platform == \"win32\"\nuvloop\ncolorama; os_name == \"nt\"\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("main.py"),
+            r#"import sys
+import win32api
+
+if sys.platform != "win32":
+    import uvloop
+
+if sys.platform == "win32":
+    import colorama
+
+def lazy():
+    import colorama
+"#,
+        )
+        .unwrap();
+
+        let report = Analysis::run(&simulated(dir.path()))?;
+
+        let findings: Vec<(Rule, &str, usize)> = report
+            .findings()
+            .iter()
+            .map(|f| {
+                (
+                    f.rule,
+                    f.subject.as_str(),
+                    f.location.as_ref().unwrap().line,
+                )
+            })
+            .collect();
+        assert_eq!(
+            findings,
+            vec![
+                (Rule::UnguardedPlatformImport, "pywin32", 2),
+                (Rule::MissingPlatformMarker, "uvloop", 5),
+            ]
+        );
+        // The unguarded import points at the import and at the declaration
+        let unguarded = &report.findings()[0];
+        assert_eq!(unguarded.severity, Severity::Error);
+        assert!(unguarded.message.contains("requirements.txt"));
+        assert_eq!(
+            unguarded.location,
+            Some(Location::new(dir.path().join("main.py"), 2))
+        );
+        assert_eq!(
+            unguarded.related,
+            [Location::new(dir.path().join("requirements.txt"), 1)]
+        );
+        Ok(())
+    }
+
+    /// A backend that never finishes on its own, like a solve that hangs
+    #[derive(Debug)]
+    struct BlockingBackend;
+
+    impl env_backend::Backend for BlockingBackend {
+        fn populate_index(
+            &self,
+            _dependencies: &[Dependency],
+            _index: &mut ModuleIndex,
+            cancel: &CancellationToken,
+        ) -> Result<(), AnalysisError> {
+            while !cancel.is_cancelled() {
+                thread::sleep(std::time::Duration::from_millis(5));
+            }
+            Err(AnalysisError::Cancelled(Phase::ResolvingEnvironment))
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "conda")]
+    fn test_python_version_pinned_by_conda_environment() -> Result<(), AnalysisError> {
+        init_tracing();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("environment.yml"),
+            "dependencies:\n  - python=3.10\n  - numpy\n  - pip:\n    - requests\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("main.py"),
+            "import numpy\nimport requests\nimport tomllib\n",
+        )
+        .unwrap();
+
+        // tomllib was added in Python 3.11, so it has to come from a dependency on 3.10
+        let options = AnalysisOptions::builder(dir.path())
+            .backend(EnvironmentBackend::Simulated)
+            .cache_dir(dir.path().join("cache"))
+            .offline(true)
+            .build();
+        let report = Analysis::run(&options)?;
+        assert_eq!(report.python_version(), Some(PythonVersion::new(3, 10)));
+        let missing: Vec<&str> = report
+            .missing_imports()
+            .iter()
+            .map(|m| m.module())
+            .collect();
+        assert_eq!(missing, vec!["tomllib"]);
+
+        // An explicit version wins over the pinned one
+        let options = AnalysisOptions::builder(dir.path())
+            .backend(EnvironmentBackend::Simulated)
+            .cache_dir(dir.path().join("cache"))
+            .offline(true)
+            .python_version(PythonVersion::new(3, 12))
+            .build();
+        let report = Analysis::run(&options)?;
+        assert_eq!(report.python_version(), Some(PythonVersion::new(3, 12)));
+        assert!(report.missing_imports().is_empty());
+        Ok(())
+    }
+
+    #[cfg(feature = "backends-exec")]
+    #[test]
+    fn test_python_version_file() -> Result<(), AnalysisError> {
+        init_tracing();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("requirements.txt"), "").unwrap();
+        std::fs::write(
+            dir.path().join("main.py"),
+            "import tomllib\nimport distutils\n",
+        )
+        .unwrap();
+        let run = || {
+            let options = AnalysisOptions::builder(dir.path())
+                .backend(EnvironmentBackend::Current)
+                .build();
+            Analysis::run(&options)
+        };
+
+        // pyenv runs the first version listed, which has tomllib and still has distutils
+        std::fs::write(dir.path().join(".python-version"), "3.11\n3.10\n").unwrap();
+        let report = run()?;
+        assert_eq!(report.python_version(), Some(PythonVersion::new(3, 11)));
+        assert!(report.findings().is_empty(), "{:?}", report.findings());
+
+        std::fs::write(dir.path().join(".python-version"), "3.10\n").unwrap();
+        let report = run()?;
+        assert_eq!(report.python_version(), Some(PythonVersion::new(3, 10)));
+        let missing: Vec<&str> = report
+            .missing_imports()
+            .iter()
+            .map(|m| m.module())
+            .collect();
+        assert_eq!(missing, vec!["tomllib"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_backports() -> Result<(), AnalysisError> {
+        init_tracing();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("compat.py"),
+            r#"try:
+    from functools import cached_property
+except ImportError:
+    from cached_property import cached_property
+
+try:
+    import tomli as tomllib
+except ImportError:
+    import tomllib
+
+try:
+    from typing import Self
+except ImportError:
+    from typing_extensions import Self
+"#,
+        )
+        .unwrap();
+        let run = |requires_python: &str| {
+            std::fs::write(
+                dir.path().join("pyproject.toml"),
+                format!(
+                    "[project]\nname = \"example\"\n{}dependencies = [\"cached-property\", \"typing-extensions\"]\n",
+                    requires_python
+                ),
+            )
+            .unwrap();
+            let options = AnalysisOptions::builder(dir.path())
+                .backend(EnvironmentBackend::Simulated)
+                .build();
+            let report = Analysis::run(&options)?;
+            Ok::<_, AnalysisError>(
+                report
+                    .findings()
+                    .iter()
+                    .filter(|f| matches!(f.rule, Rule::ObsoleteBackport | Rule::MissingBackport))
+                    .map(|f| {
+                        (
+                            f.rule,
+                            f.subject.clone(),
+                            f.location.as_ref().map_or(0, |l| l.line),
+                        )
+                    })
+                    .collect::<Vec<_>>(),
+            )
+        };
+
+        // Python 3.9 has cached_property but not tomllib or typing.Self
+        assert_eq!(
+            run("requires-python = \">=3.9\"\n")?,
+            vec![
+                (Rule::ObsoleteBackport, "cached-property".to_string(), 2),
+                (Rule::MissingBackport, "tomli".to_string(), 7),
+            ]
+        );
+        assert_eq!(
+            run("requires-python = \">=3.11\"\n")?,
+            vec![
+                (Rule::ObsoleteBackport, "cached-property".to_string(), 2),
+                (Rule::ObsoleteBackport, "typing-extensions".to_string(), 12),
+            ]
+        );
+        // Without requires-python, the oldest supported Python isn't known
+        assert_eq!(run("")?, vec![]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_removed_stdlib_imports() -> Result<(), AnalysisError> {
+        init_tracing();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("requirements.txt"), "requests\n").unwrap();
+        std::fs::write(
+            dir.path().join("setup_helpers.py"),
+            "import requests\nfrom distutils.core import setup\nfrom asyncio import coroutine\n\ntry:\n    import imp\nexcept ImportError:\n    imp = None\n",
+        )
+        .unwrap();
+
+        let run = |version| {
+            let options = AnalysisOptions::builder(dir.path())
+                .backend(EnvironmentBackend::Simulated)
+                .python_version(version)
+                .build();
+            Analysis::run(&options)
+        };
+        let report = run(PythonVersion::new(3, 12))?;
+        let found: Vec<(Rule, &str, &str)> = report
+            .findings()
+            .iter()
+            .map(|f| (f.rule, f.subject.as_str(), f.message.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (
+                    Rule::RemovedStdlibImport,
+                    "asyncio.coroutine",
+                    "`asyncio.coroutine` was removed from the standard library in Python 3.11, and the project targets Python 3.12; use `async def` instead"
+                ),
+                (
+                    Rule::RemovedStdlibImport,
+                    "distutils",
+                    "`distutils` was removed from the standard library in Python 3.12, and the project targets Python 3.12; use `setuptools` instead"
+                ),
+            ]
+        );
+
+        // Python 3.10 still has both
+        let report = run(PythonVersion::new(3, 10))?;
+        assert!(report.findings().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(all(feature = "conda", feature = "backends-net"))]
+    fn test_simulated_conda_repodata() -> Result<(), AnalysisError> {
+        init_tracing();
+        let dir = tempfile::tempdir().unwrap();
+        let channel = dir.path().join("channel");
+        let record = |name: &str, version: &str, depends: &[&str]| serde_json::json!({"name": name, "version": version, "depends": depends});
+        let subdirs = [
+            (
+                "linux-64",
+                serde_json::json!({"packages": {
+                    "python-3.10.14-0.tar.bz2": record("python", "3.10.14", &["openssl >=3"]),
+                    "openssl-3.3.1-0.tar.bz2": record("openssl", "3.3.1", &["ca-certificates"]),
+                    "pyyaml-6.0.1-0.tar.bz2":
+                        record("pyyaml", "6.0.1", &["python >=3.10,<3.11.0a0"]),
+                }}),
+            ),
+            (
+                "noarch",
+                serde_json::json!({"packages": {
+                    "ca-certificates-2024.7.4-0.tar.bz2":
+                        record("ca-certificates", "2024.7.4", &[]),
+                }}),
+            ),
+        ];
+        for (subdir, repodata) in subdirs {
+            std::fs::create_dir_all(channel.join(subdir)).unwrap();
+            std::fs::write(
+                channel.join(subdir).join("repodata.json"),
+                repodata.to_string(),
+            )
+            .unwrap();
+        }
+        std::fs::write(
+            dir.path().join("environment.yml"),
+            format!(
+                "channels:\n  - file://{}\ndependencies:\n  - python=3.10\n  - pyyaml\n  - openssl\n",
+                channel.display()
+            ),
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("main.py"), "import yaml\n").unwrap();
+
+        let options = AnalysisOptions::builder(dir.path())
+            .backend(EnvironmentBackend::Simulated)
+            .platform("linux-64")
+            .cache_dir(dir.path().join("cache"))
+            .build();
+        let report = Analysis::run(&options)?;
+        assert!(report.warnings().is_empty(), "{:?}", report.warnings());
+        assert!(report.missing_imports().is_empty());
+        // openssl provides no modules, so it isn't reported as unused
+        assert!(report.unused_dependencies().is_empty());
+
+        // What the packages depend on is part of the environment
+        let environment = Analysis::resolve_environment(&options)?;
+        assert_eq!(environment.python_version.as_deref(), Some("3.10.14"));
+        let certificates = environment.distribution("ca-certificates").unwrap();
+        assert_eq!(certificates.version.as_deref(), Some("2024.7.4"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_requirements_markers_and_extras() -> Result<(), AnalysisError> {
+        init_tracing();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("requirements.txt"),
+            "requests\ntomli; python_version < \"3.11\"\npytest; extra == \"test\"\n",
+        )
+        .unwrap();
+        std::fs::create_dir_all(dir.path().join("tests")).unwrap();
+        std::fs::write(dir.path().join("main.py"), "import requests\n").unwrap();
+        std::fs::write(dir.path().join("tests/test_main.py"), "import pytest\n").unwrap();
+        let check = |version: PythonVersion| {
+            let options = AnalysisOptions::builder(dir.path())
+                .backend(EnvironmentBackend::Simulated)
+                .python_version(version)
+                .include_tests(true)
+                .build();
+            Analysis::run(&options)
+        };
+
+        // tomli isn't installed on Python 3.12, so it isn't unused there
+        let report = check(PythonVersion::new(3, 12))?;
+        // The pytest line belongs to the test extra rather than the base requirements
+        assert_eq!(report.configurations().len(), 2);
+        assert_eq!(report.configurations()[1].extra(), Some("test"));
+        assert!(report.findings().is_empty(), "{:?}", report.findings());
+
+        let report = check(PythonVersion::new(3, 10))?;
+        assert_eq!(report.unused_dependencies(), &["tomli".to_string()]);
+        Ok(())
+    }
+
+    #[cfg(feature = "backends-net")]
+    #[test]
+    fn test_unavailable_versions() -> Result<(), AnalysisError> {
+        init_tracing();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("requirements.txt"),
+            "flask==3.0.0\nrequests==2.99.0\nurllib3==2.0.0\nrich>=99\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("main.py"),
+            "import flask\nimport requests\nimport urllib3\nimport rich\n",
+        )
+        .unwrap();
+        // Releases cached while fresh aren't looked up again
+        let cache = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(cache.path().join("pypi")).unwrap();
+        for (name, releases) in [
+            ("flask", r#"[{"version": "3.0.0", "yanked": false}]"#),
+            (
+                "requests",
+                r#"[{"version": "2.31.0", "yanked": false}, {"version": "2.32.3", "yanked": false}]"#,
+            ),
+            (
+                "urllib3",
+                r#"[{"version": "1.26.18", "yanked": false}, {"version": "2.0.0", "yanked": true}, {"version": "2.0.1", "yanked": false}]"#,
+            ),
+            ("rich", r#"[{"version": "13.7.1", "yanked": false}]"#),
+        ] {
+            std::fs::write(
+                cache.path().join("pypi").join(format!("{}.json", name)),
+                releases,
+            )
+            .unwrap();
+        }
+        let options = || {
+            AnalysisOptions::builder(dir.path())
+                .backend(EnvironmentBackend::Simulated)
+                .cache_dir(cache.path())
+        };
+
+        let report = Analysis::run(&options().build())?;
+        let findings: Vec<(&str, &str, usize)> = report
+            .findings()
+            .iter()
+            .map(|f| {
+                assert_eq!(f.rule, Rule::UnavailableVersion);
+                (
+                    f.subject.as_str(),
+                    f.message.as_str(),
+                    f.location.as_ref().map_or(0, |l| l.line),
+                )
+            })
+            .collect();
+        assert_eq!(
+            findings,
+            vec![
+                (
+                    "requests",
+                    "`requests==2.99.0` is declared but requests 2.99.0 isn't published; the \
+                     nearest published versions are 2.32.3",
+                    2
+                ),
+                (
+                    "rich",
+                    "`rich>=99` is declared but no release of rich that isn't yanked matches \
+                     it; the nearest published versions are 13.7.1",
+                    4
+                ),
+                (
+                    "urllib3",
+                    "`urllib3==2.0.0` is declared but urllib3 2.0.0 is yanked; the nearest \
+                     published versions are 1.26.18 and 2.0.1",
+                    3
+                ),
+            ]
+        );
+
+        // The check can be skipped by its rule, and is skipped offline with a warning
+        let report = Analysis::run(&options().skip_rule(Rule::UnavailableVersion).build())?;
+        assert!(report.findings().is_empty(), "{:?}", report.findings());
+        assert!(report.warnings().is_empty());
+        let report = Analysis::run(&options().offline(true).build())?;
+        assert!(report.findings().is_empty(), "{:?}", report.findings());
+        assert_eq!(report.warnings(), &[AnalysisWarning::SkippedReleaseCheck]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_generated_files() -> Result<(), AnalysisError> {
+        init_tracing();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("shop/migrations")).unwrap();
+        std::fs::write(dir.path().join("requirements.txt"), "django\n").unwrap();
+        std::fs::write(
+            dir.path().join("foo_pb2.py"),
+            "from google.protobuf import descriptor as _descriptor\nimport grpc\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("shop/migrations/0001_initial.py"),
+            "from django.db import migrations\nimport django_extensions.db.fields\n",
+        )
+        .unwrap();
+
+        let report = Analysis::run(&simulated(dir.path()))?;
+        assert!(report.findings().is_empty(), "{:?}", report.findings());
+        assert!(report.missing_imports().is_empty());
+
+        let options = AnalysisOptions::builder(dir.path())
+            .backend(EnvironmentBackend::Simulated)
+            .report_generated(true)
+            .build();
+        let report = Analysis::run(&options)?;
+        let findings: Vec<(Severity, &str)> = report
+            .findings()
+            .iter()
+            .map(|finding| (finding.severity, finding.message.as_str()))
+            .collect();
+        assert_eq!(
+            findings,
+            [
+                (
+                    Severity::Info,
+                    "`django_extensions.db.fields` is imported by generated code but no declared dependency provides it (expected `django-extensions`)"
+                ),
+                (
+                    Severity::Info,
+                    "`google.protobuf` is imported by generated code but no declared dependency provides it (expected `protobuf`)"
+                ),
+                (
+                    Severity::Info,
+                    "`grpc` is imported by generated code but no declared dependency provides it (expected `grpcio`)"
+                ),
+            ]
+        );
+        assert!(report.missing_imports().iter().all(|m| m.is_generated()));
+
+        // Other patterns replace the defaults
+        let options = AnalysisOptions::builder(dir.path())
+            .backend(EnvironmentBackend::Simulated)
+            .generated_patterns(["migrations/*.py".to_string()])
+            .build();
+        let report = Analysis::run(&options)?;
+        let missing: Vec<&str> = report
+            .missing_imports()
+            .iter()
+            .map(|m| m.module())
+            .collect();
+        assert_eq!(missing, ["google.protobuf", "grpc"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_test_runners_satisfy_test_imports() -> Result<(), AnalysisError> {
+        init_tracing();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("requirements.txt"),
+            "requests\npytest; extra == \"test\"\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("tox.ini"),
+            "[testenv]\ndeps =\n    hypothesis\n\n[testenv:lint]\ndeps = ruff\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("noxfile.py"),
+            "import nox\n\n@nox.session\ndef tests(session):\n    session.install(\"pyyaml\")\n",
+        )
+        .unwrap();
+        std::fs::create_dir_all(dir.path().join("tests")).unwrap();
+        std::fs::write(
+            dir.path().join("main.py"),
+            "import requests\nimport hypothesis\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("tests/test_main.py"),
+            "import pytest\nimport hypothesis\nimport yaml\n",
+        )
+        .unwrap();
+        let check = |test_runners: bool| {
+            let options = AnalysisOptions::builder(dir.path())
+                .backend(EnvironmentBackend::Simulated)
+                .test_runners(test_runners)
+                .build();
+            Analysis::run(&options)
+        };
+        let missing = |report: &AnalysisReport| -> Vec<(String, String)> {
+            report
+                .missing_imports()
+                .iter()
+                .map(|m| {
+                    let file = m.location().path.file_name().unwrap();
+                    (m.module().to_string(), file.to_string_lossy().to_string())
+                })
+                .collect()
+        };
+
+        // What tox and nox install only satisfies the test files, and a nox session named
+        // `tests` doesn't claim the tests/ directory
+        let report = check(true)?;
+        let names: Vec<&str> = report.configurations().iter().map(|c| c.name()).collect();
+        assert_eq!(names.len(), 5, "{:?}", names);
+        assert!(names[2].ends_with("tox.ini (testenv)"), "{:?}", names);
+        assert!(
+            names[4].ends_with("noxfile.py (session tests)"),
+            "{:?}",
+            names
+        );
+        assert_eq!(
+            missing(&report),
+            [("hypothesis".to_string(), "main.py".to_string())]
+        );
+
+        let report = check(false)?;
+        assert_eq!(report.configurations().len(), 2);
+        let mut found = missing(&report);
+        found.sort();
+        assert_eq!(
+            found,
+            [
+                ("hypothesis".to_string(), "main.py".to_string()),
+                ("hypothesis".to_string(), "test_main.py".to_string()),
+                ("nox".to_string(), "noxfile.py".to_string()),
+                ("yaml".to_string(), "test_main.py".to_string()),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_environment_manifest() -> Result<(), AnalysisError> {
+        init_tracing();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("requirements.txt"), "acme-sdk==1.0\n").unwrap();
+        std::fs::write(dir.path().join("main.py"), "import acmeclient\n").unwrap();
+
+        // Without the environment, the module name can't be tied to the distribution
+        let report = Analysis::run(&simulated(dir.path()))?;
+        assert_eq!(report.missing_imports().len(), 1);
+
+        let dumped = Analysis::resolve_environment(&simulated(dir.path()))?;
+        assert!(dumped.distribution("acme-sdk").is_some());
+
+        let manifest = ResolvedEnvironment::from_json(
+            r#"{
+                "python_version": "3.11.9",
+                "distributions": {
+                    "acme-sdk": {"version": "1.1", "top_level_modules": ["acmeclient"]}
+                }
+            }"#,
+        )?;
+        let manifest = ResolvedEnvironment::from_json(&manifest.to_json())?;
+        let options = AnalysisOptions::builder(dir.path())
+            .custom_backend(std::sync::Arc::new(manifest))
+            .build();
+        let report = Analysis::run(&options)?;
+        assert!(report.missing_imports().is_empty());
+        let messages: Vec<&str> = report
+            .findings()
+            .iter()
+            .map(|finding| finding.message.as_str())
+            .collect();
+        assert_eq!(
+            messages,
+            ["`acme-sdk==1.0` is declared but acme-sdk 1.1 is installed"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_exclusive_extras() -> Result<(), AnalysisError> {
+        init_tracing();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("pyproject.toml"),
+            r#"[project]
+name = "example"
+dependencies = ["numpy"]
+
+[project.optional-dependencies]
+cpu = ["onnxruntime==1.17.0"]
+gpu = ["onnxruntime-gpu==1.17.0"]
+"#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("main.py"),
+            "import numpy\n\ntry:\n    import onnxruntime\nexcept ImportError:\n    onnxruntime = None\n",
+        )
+        .unwrap();
+        // The GPU build is installed, and provides the same module as the CPU one
+        let manifest = ResolvedEnvironment::from_json(
+            r#"{
+                "distributions": {
+                    "numpy": {"version": "1.26.4", "top_level_modules": ["numpy"]},
+                    "onnxruntime-gpu": {"version": "1.17.0", "top_level_modules": ["onnxruntime"]}
+                }
+            }"#,
+        )?;
+        let check = |exclusive: bool| {
+            let mut builder = AnalysisOptions::builder(dir.path())
+                .custom_backend(std::sync::Arc::new(manifest.clone()));
+            if exclusive {
+                builder = builder.exclusive_extras(["cpu", "gpu"]);
+            }
+            Analysis::run(&builder.build())
+        };
+        let unused = |report: &AnalysisReport| -> Vec<String> {
+            report
+                .findings()
+                .iter()
+                .filter(|f| f.rule == Rule::UnusedDependency)
+                .map(|f| f.subject.clone())
+                .collect()
+        };
+
+        let report = check(false)?;
+        assert_eq!(unused(&report), ["onnxruntime"]);
+
+        // Neither alternative is unused against the base
+        let report = check(true)?;
+        assert!(unused(&report).is_empty(), "{:?}", report.findings());
+        assert!(
+            report
+                .configurations()
+                .iter()
+                .all(|c| c.unused_dependencies().is_empty())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_imports_installed_through_extras() -> Result<(), AnalysisError> {
+        init_tracing();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("requirements.txt"),
+            "pandas[parquet]\nhttpx\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("main.py"),
+            "import pandas\nimport pyarrow.parquet\nimport h2\nimport httpx\n",
+        )
+        .unwrap();
+
+        let report = Analysis::run(&simulated(dir.path()))?;
+
+        let messages: Vec<(Rule, &str)> = report
+            .findings()
+            .iter()
+            .map(|finding| (finding.rule, finding.message.as_str()))
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                (
+                    Rule::MissingDependency,
+                    "`h2` is imported but no declared dependency provides it (expected `h2`); `httpx[http2]` would install it, declare it or the extra directly"
+                ),
+                (
+                    Rule::ImportedThroughExtra,
+                    "`pyarrow.parquet` is imported but only installed through `pandas[parquet]`; declare `pyarrow` directly"
+                ),
+            ]
+        );
+        assert_eq!(
+            report.missing_imports()[0].reachable_via(),
+            Some("httpx[http2]")
+        );
+        let pandas = report
+            .dependency_usage()
+            .iter()
+            .find(|usage| usage.name() == "pandas")
+            .unwrap();
+        assert_eq!(pandas.extras(), ["parquet"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_findings_explain_missing_and_unused() -> Result<(), AnalysisError> {
+        init_tracing();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("requirements.txt"), "flask\n").unwrap();
+        std::fs::write(dir.path().join("main.py"), "from PIL import Image\n").unwrap();
+        std::fs::write(dir.path().join("thumbnails.py"), "\nimport PIL\n").unwrap();
+
+        let report = Analysis::run(&simulated(dir.path()))?;
+
+        let explanation = |rule: Rule| {
+            report
+                .findings()
+                .iter()
+                .find(|finding| finding.rule == rule)
+                .and_then(|finding| finding.explanation.clone())
+                .unwrap()
+        };
+        let missing = explanation(Rule::MissingDependency);
+        assert_eq!(
+            missing.import_sites,
+            [
+                Location::new(dir.path().join("main.py"), 1),
+                Location::new(dir.path().join("thumbnails.py"), 2),
+            ]
+        );
+        assert!(
+            missing.reasons.contains(
+                &"candidate distributions from the built-in name map: `pillow`".to_string()
+            ),
+            "{:?}",
+            missing.reasons
+        );
+        assert!(
+            missing
+                .reasons
+                .contains(&"`PIL` isn't one of the project's own modules".to_string())
+        );
+
+        let unused = explanation(Rule::UnusedDependency);
+        assert!(unused.import_sites.is_empty());
+        assert!(unused.reasons.contains(
+            &"expected to be imported as `flask`, but no checked file imports it".to_string()
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_dependency_usage() -> Result<(), AnalysisError> {
+        init_tracing();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("pyproject.toml"),
+            r#"[project]
+name = "example"
+dependencies = ["requests", "numpy", "flask", "ujson"]
+
+[project.optional-dependencies]
+test = ["pytest"]
+"#,
+        )
+        .unwrap();
+        std::fs::create_dir_all(dir.path().join("tests")).unwrap();
+        std::fs::write(
+            dir.path().join("main.py"),
+            "import requests\nfrom typing import TYPE_CHECKING\nif TYPE_CHECKING:\n    import numpy\ntry:\n    import ujson\nexcept ImportError:\n    ujson = None\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("client.py"),
+            "import requests\nfrom requests import Session\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("tests/test_main.py"),
+            "import pytest\nimport requests\n",
+        )
+        .unwrap();
+
+        let report = Analysis::run(&simulated(dir.path()))?;
+        let usage: Vec<(&str, usize, usize, bool, bool, bool)> = report
+            .dependency_usage()
+            .iter()
+            .map(|u| {
+                (
+                    u.name(),
+                    u.files(),
+                    u.import_sites(),
+                    u.is_guarded_only(),
+                    u.is_test_only(),
+                    u.is_type_checking_only(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            usage,
+            vec![
+                ("requests", 3, 4, false, false, false),
+                ("numpy", 1, 1, false, false, true),
+                ("pytest", 1, 1, false, true, false),
+                ("ujson", 1, 1, true, false, false),
+                ("flask", 0, 0, false, false, false),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_type_only_dependencies() -> Result<(), AnalysisError> {
+        init_tracing();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("requirements.txt"),
+            "numpy\npandas\nrequests\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("main.py"),
+            r#"import numpy.typing
+import pandas as pd
+import requests
+from typing import cast
+
+def load(path: str) -> numpy.typing.NDArray:
+    frame = cast("pd.DataFrame", read(path))
+    return frame.to_numpy()
+
+def fetch(session: requests.Session) -> None:
+    requests.get("https://example.com")
+"#,
+        )
+        .unwrap();
+
+        let report = Analysis::run(&simulated(dir.path()))?;
+        let mut type_only: Vec<(&str, bool)> = report
+            .dependency_usage()
+            .iter()
+            .map(|u| (u.name(), u.is_type_only()))
+            .collect();
+        type_only.sort();
+        assert_eq!(
+            type_only,
+            [("numpy", true), ("pandas", true), ("requests", false)]
+        );
+        assert!(
+            report
+                .dependency_usage()
+                .iter()
+                .all(|u| !u.is_type_checking_only())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_entry_points_use_their_modules() -> Result<(), AnalysisError> {
+        init_tracing();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("pyproject.toml"),
+            r#"[project]
+name = "example"
+dependencies = ["click", "black", "rich"]
+
+[project.scripts]
+example = "example.cli:main"
+fmt = "black:patched_main"
+
+[project.gui-scripts]
+legacy = "legacy_tool:run"
+"#,
+        )
+        .unwrap();
+        std::fs::create_dir_all(dir.path().join("example")).unwrap();
+        std::fs::write(dir.path().join("example/__init__.py"), "").unwrap();
+        std::fs::write(dir.path().join("example/cli.py"), "import click\n").unwrap();
+
+        let report = Analysis::run(&simulated(dir.path()))?;
+        assert_eq!(report.unused_dependencies(), &["rich".to_string()]);
+        assert!(report.missing_imports().is_empty());
+        let black = report
+            .dependency_usage()
+            .iter()
+            .find(|u| u.name() == "black")
+            .unwrap();
+        assert_eq!((black.files(), black.import_sites()), (1, 1));
+
+        // Neither `legacy_tool` nor `example.cli:main` exist
+        let mut broken: Vec<(&str, usize)> = report
+            .findings()
+            .iter()
+            .filter(|f| f.rule == Rule::BrokenEntryPoint)
+            .map(|f| (f.subject.as_str(), f.location.as_ref().unwrap().line))
+            .collect();
+        broken.sort();
+        assert_eq!(broken, [("example.cli", 6), ("legacy_tool", 10)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_unused_exemptions() -> Result<(), AnalysisError> {
+        init_tracing();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("requirements.txt"),
+            "requests\nsetuptools-scm\ngunicorn\nflask\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("main.py"), "import requests\n").unwrap();
+
+        let options = AnalysisOptions::builder(dir.path())
+            .backend(EnvironmentBackend::Simulated)
+            .ignore_unused("gunicorn")
+            .build();
+        let report = Analysis::run(&options)?;
+        assert_eq!(report.unused_dependencies(), &["flask".to_string()]);
+        let exemptions: Vec<(&str, Option<UnusedExemption>)> = report
+            .dependency_usage()
+            .iter()
+            .map(|u| (u.name(), u.unused_exemption()))
+            .collect();
+        assert_eq!(
+            exemptions,
+            vec![
+                ("requests", None),
+                ("flask", None),
+                ("gunicorn", Some(UnusedExemption::Ignored)),
+                ("setuptools-scm", Some(UnusedExemption::SupportPackage)),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_provided_modules() -> Result<(), AnalysisError> {
+        init_tracing();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("requirements.txt"), "requests\n").unwrap();
+        std::fs::write(
+            dir.path().join("main.py"),
+            "import requests\nimport pyspark.sql\nfrom delta.tables import DeltaTable\nimport awsglue\n",
+        )
+        .unwrap();
+
+        let mut options = AnalysisOptions::builder(dir.path())
+            .backend(EnvironmentBackend::Simulated)
+            .provided_module("delta");
+        for module in crate::name_map::profile_modules("glue").unwrap() {
+            options = options.provided_module(*module);
+        }
+        let report = Analysis::run(&options.build())?;
+        assert!(report.missing_imports().is_empty());
+        assert!(report.unused_dependencies().is_empty());
+
+        let report = Analysis::run(&simulated(dir.path()))?;
+        let missing: Vec<&str> = report
+            .missing_imports()
+            .iter()
+            .map(|m| m.module())
+            .collect();
+        assert_eq!(missing, ["pyspark.sql", "delta.tables", "awsglue"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_script_only_dependencies() -> Result<(), AnalysisError> {
+        init_tracing();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("requirements.txt"),
+            "requests\nclick\nrich\ntyper\n",
+        )
+        .unwrap();
+        std::fs::create_dir_all(dir.path().join("scripts")).unwrap();
+        std::fs::write(
+            dir.path().join("main.py"),
+            "import requests\nimport typer\n\nif __name__ == \"__main__\":\n    import click\n    typer.run(click.echo)\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("scripts/release.py"),
+            "import requests\nimport rich\n",
+        )
+        .unwrap();
+
+        let report = Analysis::run(&simulated(dir.path()))?;
+        let findings: Vec<(&str, String)> = report
+            .findings()
+            .iter()
+            .filter(|f| f.rule == Rule::ScriptOnlyDependency)
+            .map(|f| (f.subject.as_str(), f.location.as_ref().unwrap().to_string()))
+            .collect();
+        let main = dir.path().join("main.py");
+        let release = dir.path().join("scripts/release.py");
+        assert_eq!(
+            findings,
+            vec![
+                ("click", format!("{}:5", main.display())),
+                ("rich", format!("{}:2", release.display())),
+                ("typer", format!("{}:2", main.display())),
+            ]
+        );
+        let script_only: Vec<&str> = report
+            .dependency_usage()
+            .iter()
+            .filter(|u| u.is_script_only())
+            .map(|u| u.name())
+            .collect();
+        assert_eq!(script_only, vec!["click", "rich", "typer"]);
+        // Only the dependencies `if __name__ == "__main__":` needs, not the scripts
+        let main_guard_only: Vec<&str> = report
+            .dependency_usage()
+            .iter()
+            .filter(|u| u.is_main_guard_only())
+            .map(|u| u.name())
+            .collect();
+        assert_eq!(main_guard_only, vec!["click", "typer"]);
+
+        let options = AnalysisOptions::builder(dir.path())
+            .backend(EnvironmentBackend::Simulated)
+            .report_script_only(false)
+            .build();
+        let report = Analysis::run(&options)?;
+        assert!(
+            report
+                .findings()
+                .iter()
+                .all(|f| f.rule != Rule::ScriptOnlyDependency)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_cancel_blocked_backend() {
+        init_tracing();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("requirements.txt"), "requests\n").unwrap();
+        std::fs::write(dir.path().join("main.py"), "import requests\n").unwrap();
+
+        let cancel = CancellationToken::new();
+        let options = AnalysisOptions::builder(dir.path())
+            .custom_backend(std::sync::Arc::new(BlockingBackend))
+            .cancellation_token(cancel.clone())
+            .build();
+        let timer = thread::spawn(move || {
+            thread::sleep(std::time::Duration::from_millis(50));
+            cancel.cancel();
+        });
+
+        let result = Analysis::run(&options);
+        timer.join().unwrap();
+        assert!(matches!(
+            result,
+            Err(AnalysisError::Cancelled(Phase::ResolvingEnvironment))
+        ));
+    }
+
+    #[test]
+    fn test_cancelled_before_scanning() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("main.py"), "import requests\n").unwrap();
+
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        let options = AnalysisOptions::builder(dir.path())
+            .cancellation_token(cancel)
+            .build();
+        let error = Analysis::run(&options).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Analysis was cancelled while resolving the environment"
+        );
+    }
+
+    #[test]
+    fn test_excludes_and_selected_extras() -> Result<(), AnalysisError> {
+        init_tracing();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("pyproject.toml"),
+            r#"[project]
+name = "example"
+dependencies = ["requests"]
+
+[project.optional-dependencies]
+docs = ["sphinx"]
+test = ["pytest"]
+"#,
+        )
+        .unwrap();
+        std::fs::create_dir_all(dir.path().join("tests/fixtures")).unwrap();
+        std::fs::write(dir.path().join("main.py"), "import requests\n").unwrap();
+        std::fs::write(dir.path().join("tests/fixtures/broken.py"), "import yaml\n").unwrap();
+
+        let options = AnalysisOptions::builder(dir.path())
+            .backend(EnvironmentBackend::Simulated)
+            .exclude("tests/fixtures")
+            .extra("docs")
+            .jobs(2)
+            .build();
+        let report = Analysis::run(&options)?;
+
+        assert!(report.missing_imports().is_empty());
+        assert_eq!(report.unused_dependencies(), &["sphinx".to_string()]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_only_selected_files() -> Result<(), AnalysisError> {
+        init_tracing();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("pyproject.toml"),
+            r#"[project]
+name = "example"
+dependencies = ["requests", "rich"]
+"#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("helpers.py"),
+            "import yaml
+import rich
+",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("main.py"),
+            "import requests
+import helpers
+import toml
+",
+        )
+        .unwrap();
+
+        let options = AnalysisOptions::builder(dir.path())
+            .backend(EnvironmentBackend::Simulated)
+            .files([PathBuf::from("main.py")])
+            .build();
+        let report = Analysis::run(&options)?;
+
+        // helpers.py is still the project's own module, but its imports aren't checked
+        let missing: Vec<&str> = report
+            .missing_imports()
+            .iter()
+            .map(|missing| missing.module.as_str())
+            .collect();
+        assert_eq!(missing, vec!["toml"]);
+        // rich is only imported by a file that wasn't checked
+        assert!(report.unused_dependencies().is_empty());
+        Ok(())
+    }
+
+    /// A backend whose environment has fixed versions installed
+    #[derive(Debug)]
+    struct SimulatedEnvironment(Vec<(&'static str, &'static str)>);
+
+    impl env_backend::Backend for SimulatedEnvironment {
+        fn populate_index(
+            &self,
+            _dependencies: &[Dependency],
+            _index: &mut ModuleIndex,
+            _cancel: &CancellationToken,
+        ) -> Result<(), AnalysisError> {
+            Ok(())
+        }
+
+        fn installed_versions(
+            &self,
+            distributions: &[String],
+            _cancel: &CancellationToken,
+        ) -> Result<Option<std::collections::HashMap<String, String>>, AnalysisError> {
+            Ok(Some(
+                self.0
+                    .iter()
+                    .filter(|(name, _)| distributions.iter().any(|d| d == name))
+                    .map(|(name, version)| (name.to_string(), version.to_string()))
+                    .collect(),
+            ))
+        }
+    }
+
+    #[test]
+    fn test_installed_versions_are_checked() -> Result<(), AnalysisError> {
+        init_tracing();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("pyproject.toml"),
+            r#"[project]
+name = "example"
+dependencies = ["numpy<1.20", "requests>=2", "rich", "pywin32; sys_platform == 'win32'"]
+
+[project.optional-dependencies]
+test = ["pytest>=8"]
+"#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("main.py"),
+            "import numpy\nimport requests\nimport rich\n",
+        )
+        .unwrap();
+        let backend = std::sync::Arc::new(SimulatedEnvironment(vec![
+            ("numpy", "1.26.4"),
+            ("requests", "2.32.3"),
+        ]));
+
+        let options = AnalysisOptions::builder(dir.path())
+            .custom_backend(backend.clone())
+            .build();
+        let report = Analysis::run(&options)?;
+        let findings: Vec<(Rule, &str)> = report
+            .findings()
+            .iter()
+            .filter(|f| f.rule.code().starts_with("DW4"))
+            .map(|f| (f.rule, f.subject.as_str()))
+            .collect();
+        // pywin32 may be left out by its marker, and pytest by not installing the extra
+        assert_eq!(
+            findings,
+            vec![
+                (Rule::InstalledVersionMismatch, "numpy"),
+                (Rule::DependencyNotInstalled, "rich"),
+            ]
+        );
+        assert!(
+            report
+                .findings()
+                .iter()
+                .any(|f| { f.message == "`numpy<1.20` is declared but numpy 1.26.4 is installed" })
+        );
+
+        // Selecting the extra says it was installed too
+        let options = AnalysisOptions::builder(dir.path())
+            .custom_backend(backend)
+            .extra("test")
+            .build();
+        let report = Analysis::run(&options)?;
+        assert!(report.findings().iter().any(|f| {
+            f.rule == Rule::DependencyNotInstalled
+                && f.subject == "pytest"
+                && f.configuration
+                    .as_deref()
+                    .is_some_and(|c| c.ends_with("[test]"))
+        }));
+        Ok(())
+    }
+}
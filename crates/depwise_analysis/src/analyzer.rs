@@ -0,0 +1,70 @@
+use std::path::{Path, PathBuf};
+
+use crate::error::AnalysisError;
+use crate::options::{AnalysisOptions, AnalysisOptionsBuilder};
+use crate::{Analysis, AnalysisReport};
+
+/// Reusable settings for checking projects, for tools that embed depwise.
+///
+/// An analyzer is configured once, with the same [`AnalysisOptionsBuilder`] as an
+/// [`Analysis`], and can then check any number of projects. Each call to
+/// [`Analyzer::analyze`] reads the project's sources and files from scratch; use an
+/// [`AnalysisSession`](crate::AnalysisSession) to check the same project repeatedly.
+///
+/// ```
+/// use depwise_analysis::{Analyzer, AnalysisReport, EnvironmentBackend, PythonVersion, Rule};
+///
+/// # let dir = tempfile::tempdir().unwrap();
+/// # std::fs::write(dir.path().join("requirements.txt"), "requests\nflask\n").unwrap();
+/// # std::fs::write(dir.path().join("main.py"), "import requests\nimport yaml\n").unwrap();
+/// let analyzer = Analyzer::new(
+///     Analyzer::builder()
+///         .backend(EnvironmentBackend::Simulated)
+///         .python_version(PythonVersion::new(3, 12))
+///         .exclude("build")
+///         .build(),
+/// );
+///
+/// let report: AnalysisReport = analyzer.analyze(dir.path())?;
+/// assert_eq!(report.python_version(), Some(PythonVersion::new(3, 12)));
+/// assert_eq!(report.unused_dependencies(), &["flask".to_string()]);
+/// for finding in report.findings() {
+///     assert!(matches!(finding.rule, Rule::MissingDependency | Rule::UnusedDependency));
+/// }
+/// # Ok::<(), depwise_analysis::AnalysisError>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct Analyzer {
+    /// Every setting except the project root, which is given to `analyze`
+    options: AnalysisOptions,
+}
+
+impl Analyzer {
+    /// Start configuring the options of an analyzer. The project root is left empty, since
+    /// each call to `analyze` gives it.
+    pub fn builder() -> AnalysisOptionsBuilder {
+        AnalysisOptions::builder(PathBuf::new())
+    }
+
+    /// An analyzer checking projects with `options`, whatever project root they have
+    pub fn new(options: AnalysisOptions) -> Self {
+        Self { options }
+    }
+
+    /// Check the project rooted at `path` (or a single file)
+    pub fn analyze(&self, path: impl AsRef<Path>) -> Result<AnalysisReport, AnalysisError> {
+        Analysis::run(&self.options.with_path(path.as_ref()))
+    }
+}
+
+impl From<AnalysisOptions> for Analyzer {
+    fn from(options: AnalysisOptions) -> Self {
+        Self::new(options)
+    }
+}
+
+impl Default for Analyzer {
+    fn default() -> Self {
+        Self::new(Self::builder().build())
+    }
+}
@@ -0,0 +1,61 @@
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::error::AnalysisError;
+
+/// The stage an analysis is in, reported when it is cancelled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Phase {
+    /// Finding and parsing the project's Python files
+    Scanning,
+    /// Asking the backend what the declared dependencies provide
+    ResolvingEnvironment,
+    /// Comparing imports against the declared dependencies
+    Matching,
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Phase::Scanning => write!(f, "scanning files"),
+            Phase::ResolvingEnvironment => write!(f, "resolving the environment"),
+            Phase::Matching => write!(f, "matching imports to dependencies"),
+        }
+    }
+}
+
+/// A flag shared between an analysis and whoever started it to request that it stops.
+///
+/// Clones share the same flag, so a token can be handed to another thread (or a timer) and
+/// cancelled from there. The analysis checks the token between files and around backend
+/// work, and backends kill any process they started when it is cancelled.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Request that the analysis stops
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Return a `Cancelled` error for the phase if cancellation was requested
+    pub fn check(&self, phase: Phase) -> Result<(), AnalysisError> {
+        if self.is_cancelled() {
+            Err(AnalysisError::Cancelled(phase))
+        } else {
+            Ok(())
+        }
+    }
+}
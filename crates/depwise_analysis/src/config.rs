@@ -0,0 +1,341 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use toml::Value;
+
+use crate::error::AnalysisError;
+use crate::finding::Rule;
+use crate::options::AnalysisOptionsBuilder;
+
+/// Settings read from a project's `depwise.toml`, or the `[tool.depwise]` table of its
+/// `pyproject.toml` when there is no `depwise.toml`.
+///
+/// ```toml
+/// [tool.depwise]
+/// backend-timeout = 120
+/// script-directories = ["scripts", "tools"]
+/// report-script-only = false
+/// generated-patterns = ["*_pb2.py", "*_pb2_grpc.py", "migrations/*.py"]
+/// report-generated = true
+/// test-runners = false
+/// exclusive-extras = [["torch-cpu", "torch-gpu"]]
+/// ignore-unused = ["sphinx-rtd-theme"]
+/// provided-modules = ["pyspark", "dbutils"]
+/// skip-rules = ["DW404"]
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Config {
+    /// How long a command run by the environment backend may take before it's stopped
+    pub backend_timeout: Option<Duration>,
+    /// The directories, relative to the project root, whose files are scripts
+    pub script_directories: Option<Vec<PathBuf>>,
+    /// Whether runtime dependencies only imported by scripts are reported
+    pub report_script_only: Option<bool>,
+    /// The patterns of generated files, replacing the default ones
+    pub generated_patterns: Option<Vec<String>>,
+    /// Whether what generated files import without a declared dependency is reported
+    pub report_generated: Option<bool>,
+    /// Whether the packages tox and nox install satisfy what test files import
+    pub test_runners: Option<bool>,
+    /// Groups of extras that are alternatives to each other
+    pub exclusive_extras: Vec<Vec<String>>,
+    /// Distributions never reported as unused, on top of the built-in exemptions
+    pub ignore_unused: Vec<String>,
+    /// Modules the runtime provides, which never need a declared dependency
+    pub provided_modules: Vec<String>,
+    /// Rules whose findings aren't reported
+    pub skip_rules: Vec<Rule>,
+}
+
+impl Config {
+    /// Load the settings of the project at `path`, a directory or a file in it. A project
+    /// without settings gets the defaults.
+    pub fn load(path: &Path) -> Result<Self, AnalysisError> {
+        let root = if path.is_dir() {
+            path
+        } else {
+            path.parent().unwrap_or(Path::new("."))
+        };
+
+        let depwise_toml = root.join("depwise.toml");
+        if depwise_toml.is_file() {
+            let document = read_toml(&depwise_toml)?;
+            return match document.get("depwise") {
+                Some(table) => Self::from_table(&depwise_toml, table),
+                None => Ok(Self::default()),
+            };
+        }
+        let pyproject_toml = root.join("pyproject.toml");
+        if pyproject_toml.is_file() {
+            let document = read_toml(&pyproject_toml)?;
+            if let Some(table) = document.get("tool").and_then(|tool| tool.get("depwise")) {
+                return Self::from_table(&pyproject_toml, table);
+            }
+        }
+        Ok(Self::default())
+    }
+
+    /// Apply the settings that change the analysis to options, keeping the defaults for
+    /// the settings that aren't given
+    pub fn apply(&self, mut options: AnalysisOptionsBuilder) -> AnalysisOptionsBuilder {
+        if let Some(timeout) = self.backend_timeout {
+            options = options.backend_timeout(timeout);
+        }
+        if let Some(directories) = &self.script_directories {
+            options = options.script_directories(directories.iter().cloned());
+        }
+        if let Some(report) = self.report_script_only {
+            options = options.report_script_only(report);
+        }
+        if let Some(patterns) = &self.generated_patterns {
+            options = options.generated_patterns(patterns.iter().cloned());
+        }
+        if let Some(report) = self.report_generated {
+            options = options.report_generated(report);
+        }
+        if let Some(read) = self.test_runners {
+            options = options.test_runners(read);
+        }
+        for group in &self.exclusive_extras {
+            options = options.exclusive_extras(group.iter().cloned());
+        }
+        for distribution in &self.ignore_unused {
+            options = options.ignore_unused(distribution.clone());
+        }
+        for module in &self.provided_modules {
+            options = options.provided_module(module.clone());
+        }
+        for rule in &self.skip_rules {
+            options = options.skip_rule(*rule);
+        }
+        options
+    }
+
+    fn from_table(file_path: &Path, table: &Value) -> Result<Self, AnalysisError> {
+        let invalid =
+            |message: String| AnalysisError::ConfigError(file_path.display().to_string(), message);
+        let table = table
+            .as_table()
+            .ok_or_else(|| invalid("depwise settings must be a table".to_string()))?;
+
+        // Settings that aren't read yet, like `project` tables, are left alone
+        let mut config = Self::default();
+        if let Some(value) = table.get("backend-timeout") {
+            let secs = value
+                .as_integer()
+                .and_then(|secs| u64::try_from(secs).ok())
+                .ok_or_else(|| {
+                    invalid(format!(
+                        "`backend-timeout` must be a number of seconds, not {}",
+                        value
+                    ))
+                })?;
+            config.backend_timeout = Some(Duration::from_secs(secs));
+        }
+        if let Some(value) = table.get("script-directories") {
+            let directories = value
+                .as_array()
+                .and_then(|directories| {
+                    directories
+                        .iter()
+                        .map(|directory| directory.as_str().map(PathBuf::from))
+                        .collect::<Option<Vec<_>>>()
+                })
+                .ok_or_else(|| {
+                    invalid(format!(
+                        "`script-directories` must be a list of paths, not {}",
+                        value
+                    ))
+                })?;
+            config.script_directories = Some(directories);
+        }
+        if let Some(value) = table.get("report-script-only") {
+            let report = value.as_bool().ok_or_else(|| {
+                invalid(format!(
+                    "`report-script-only` must be true or false, not {}",
+                    value
+                ))
+            })?;
+            config.report_script_only = Some(report);
+        }
+        if let Some(value) = table.get("generated-patterns") {
+            let patterns = value
+                .as_array()
+                .and_then(|patterns| {
+                    patterns
+                        .iter()
+                        .map(|pattern| pattern.as_str().map(str::to_string))
+                        .collect::<Option<Vec<_>>>()
+                })
+                .ok_or_else(|| {
+                    invalid(format!(
+                        "`generated-patterns` must be a list of patterns, not {}",
+                        value
+                    ))
+                })?;
+            config.generated_patterns = Some(patterns);
+        }
+        if let Some(value) = table.get("report-generated") {
+            let report = value.as_bool().ok_or_else(|| {
+                invalid(format!(
+                    "`report-generated` must be true or false, not {}",
+                    value
+                ))
+            })?;
+            config.report_generated = Some(report);
+        }
+        if let Some(value) = table.get("test-runners") {
+            let read = value.as_bool().ok_or_else(|| {
+                invalid(format!(
+                    "`test-runners` must be true or false, not {}",
+                    value
+                ))
+            })?;
+            config.test_runners = Some(read);
+        }
+        if let Some(value) = table.get("exclusive-extras") {
+            config.exclusive_extras = value
+                .as_array()
+                .and_then(|groups| {
+                    groups
+                        .iter()
+                        .map(|group| {
+                            group
+                                .as_array()?
+                                .iter()
+                                .map(|extra| extra.as_str().map(str::to_string))
+                                .collect()
+                        })
+                        .collect::<Option<Vec<Vec<String>>>>()
+                })
+                .ok_or_else(|| {
+                    invalid(format!(
+                        "`exclusive-extras` must be a list of lists of extras, not {}",
+                        value
+                    ))
+                })?;
+        }
+        if let Some(value) = table.get("ignore-unused") {
+            config.ignore_unused = value
+                .as_array()
+                .and_then(|names| {
+                    names
+                        .iter()
+                        .map(|name| name.as_str().map(str::to_string))
+                        .collect::<Option<Vec<_>>>()
+                })
+                .ok_or_else(|| {
+                    invalid(format!(
+                        "`ignore-unused` must be a list of distribution names, not {}",
+                        value
+                    ))
+                })?;
+        }
+        if let Some(value) = table.get("provided-modules") {
+            config.provided_modules = value
+                .as_array()
+                .and_then(|names| {
+                    names
+                        .iter()
+                        .map(|name| name.as_str().map(str::to_string))
+                        .collect::<Option<Vec<_>>>()
+                })
+                .ok_or_else(|| {
+                    invalid(format!(
+                        "`provided-modules` must be a list of module names, not {}",
+                        value
+                    ))
+                })?;
+        }
+        if let Some(value) = table.get("skip-rules") {
+            let rules = value.as_array().ok_or_else(|| {
+                invalid(format!(
+                    "`skip-rules` must be a list of rule codes or names, not {}",
+                    value
+                ))
+            })?;
+            config.skip_rules = rules
+                .iter()
+                .map(|rule| match rule.as_str() {
+                    Some(rule) => rule
+                        .parse()
+                        .map_err(|e: AnalysisError| invalid(e.to_string())),
+                    None => Err(invalid(format!(
+                        "`skip-rules` must be a list of rule codes or names, not {}",
+                        value
+                    ))),
+                })
+                .collect::<Result<_, _>>()?;
+        }
+        Ok(config)
+    }
+}
+
+fn read_toml(file_path: &Path) -> Result<Value, AnalysisError> {
+    let content = fs::read_to_string(file_path).map_err(|e| {
+        AnalysisError::FileReadError(file_path.to_string_lossy().to_string(), e.to_string())
+    })?;
+    toml::from_str(&content)
+        .map_err(|e| AnalysisError::ConfigError(file_path.display().to_string(), e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AnalysisOptions;
+
+    #[test]
+    fn test_load_config() -> Result<(), AnalysisError> {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(Config::load(dir.path())?, Config::default());
+
+        fs::write(
+            dir.path().join("pyproject.toml"),
+            "[project]\nname = \"example\"\n\n[tool.depwise]\nbackend-timeout = 30\n",
+        )
+        .unwrap();
+        assert_eq!(
+            Config::load(&dir.path().join("main.py"))?.backend_timeout,
+            Some(Duration::from_secs(30))
+        );
+
+        // depwise.toml takes precedence over pyproject.toml
+        fs::write(
+            dir.path().join("depwise.toml"),
+            "[depwise]\nbackend-timeout = 5\nscript-directories = [\"tools\"]\nreport-script-only = false\nignore-unused = [\"gunicorn\"]\nprovided-modules = [\"pyspark\"]\ngenerated-patterns = [\"gen/*.py\"]\nreport-generated = true\ntest-runners = false\nexclusive-extras = [[\"cpu\", \"gpu\"]]\nskip-rules = [\"DW404\", \"unused-import\"]\n",
+        )
+        .unwrap();
+        let config = Config::load(dir.path())?;
+        assert_eq!(config.backend_timeout, Some(Duration::from_secs(5)));
+        let options = config.apply(AnalysisOptions::builder(dir.path())).build();
+        assert_eq!(options.backend_timeout(), Some(Duration::from_secs(5)));
+        assert_eq!(options.script_directories(), &[PathBuf::from("tools")]);
+        assert!(!options.report_script_only());
+        assert_eq!(options.ignore_unused(), &["gunicorn".to_string()]);
+        assert_eq!(options.provided_modules(), &["pyspark".to_string()]);
+        assert_eq!(options.generated_patterns(), &["gen/*.py".to_string()]);
+        assert!(options.report_generated());
+        assert!(!options.test_runners());
+        assert_eq!(
+            options.exclusive_extras(),
+            &[vec!["cpu".to_string(), "gpu".to_string()]]
+        );
+        assert_eq!(
+            options.skip_rules(),
+            &[Rule::UnavailableVersion, Rule::UnusedImport]
+        );
+
+        fs::write(
+            dir.path().join("depwise.toml"),
+            "[depwise]\nbackend-timeout = \"soon\"\n",
+        )
+        .unwrap();
+        assert!(matches!(
+            Config::load(dir.path()),
+            Err(AnalysisError::ConfigError(..))
+        ));
+        Ok(())
+    }
+}
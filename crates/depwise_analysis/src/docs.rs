@@ -0,0 +1,292 @@
+//! Python code blocks embedded in documentation.
+//!
+//! Markdown fences (```` ``` ```` or `~~~`) tagged `python`, `py`, `python3`, or `pycon`, and
+//! reStructuredText `code-block`, `code`, and `sourcecode` directives with the same
+//! languages, are extracted with the line they start on. Interactive sessions (`pycon`)
+//! keep only the code after their `>>>` and `...` prompts.
+
+use std::path::Path;
+
+use tracing::debug;
+
+use crate::parser::{PythonImport, PythonParser};
+
+/// The languages of code blocks that hold Python
+const PYTHON_LANGUAGES: &[&str] = &["python", "py", "python3", "py3", "pycon"];
+
+/// A block of Python code in a documentation file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct CodeBlock {
+    /// The line of the file the code starts on, starting from 1
+    pub(crate) line: usize,
+    pub(crate) code: String,
+}
+
+/// Whether the language of a code block is Python, and whether it's an interactive session
+fn python_language(language: &str) -> Option<bool> {
+    let language = language
+        .trim()
+        .trim_start_matches('{')
+        .trim_end_matches('}');
+    let language = language.split_whitespace().next()?.to_ascii_lowercase();
+    PYTHON_LANGUAGES
+        .contains(&language.as_str())
+        .then_some(language == "pycon")
+}
+
+/// Extract the Python code blocks of a Markdown or reStructuredText file, telling which one
+/// it is by its extension
+pub(crate) fn python_blocks(path: &Path, source: &str) -> Vec<CodeBlock> {
+    if path.extension().is_some_and(|ext| ext == "rst") {
+        rst_blocks(source)
+    } else {
+        markdown_blocks(source)
+    }
+}
+
+/// The Python fenced code blocks of a Markdown file. An unclosed fence runs to the end of
+/// the file, as in CommonMark.
+fn markdown_blocks(source: &str) -> Vec<CodeBlock> {
+    let mut blocks = Vec::new();
+    // The fence of the block being read, and whether it's Python and a session
+    let mut open: Option<((char, usize), Option<bool>, CodeBlock)> = None;
+    for (i, line) in source.lines().enumerate() {
+        let trimmed = line.trim_start();
+        match &mut open {
+            // A fence is closed by at least as many of its characters and nothing else
+            Some(((fence, length), _, _))
+                if trimmed.trim_end().len() >= *length
+                    && trimmed.trim_end().chars().all(|c| c == *fence) =>
+            {
+                let (_, language, block) = open.take().expect("a block is open");
+                if let Some(session) = language {
+                    blocks.push(finish(block, session));
+                }
+            }
+            Some((_, _, block)) => {
+                block.code.push_str(line);
+                block.code.push('\n');
+            }
+            None => {
+                let fence_char = match trimmed.chars().next() {
+                    Some(c @ ('`' | '~')) => c,
+                    _ => continue,
+                };
+                let length = trimmed.chars().take_while(|c| *c == fence_char).count();
+                if length < 3 {
+                    continue;
+                }
+                let block = CodeBlock {
+                    line: i + 2,
+                    code: String::new(),
+                };
+                let language = python_language(&trimmed[length..]);
+                open = Some(((fence_char, length), language, block));
+            }
+        }
+    }
+    if let Some((_, Some(session), block)) = open {
+        blocks.push(finish(block, session));
+    }
+    blocks
+}
+
+/// The Python code directives of a reStructuredText file: the indented lines after the
+/// directive and its options
+fn rst_blocks(source: &str) -> Vec<CodeBlock> {
+    let lines: Vec<&str> = source.lines().collect();
+    let mut blocks = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        let indent = line.len() - line.trim_start().len();
+        let directive = line.trim_start().strip_prefix("..").and_then(|rest| {
+            let (name, language) = rest.split_once("::")?;
+            matches!(name.trim(), "code-block" | "code" | "sourcecode")
+                .then(|| python_language(language))
+                .flatten()
+        });
+        i += 1;
+        let Some(session) = directive else {
+            continue;
+        };
+        // Options like `:linenos:` come right after the directive
+        while i < lines.len() && lines[i].trim_start().starts_with(':') {
+            i += 1;
+        }
+        let start = i;
+        while i < lines.len() {
+            let line = lines[i];
+            let is_indented = line.len() - line.trim_start().len() > indent;
+            if !line.trim().is_empty() && !is_indented {
+                break;
+            }
+            i += 1;
+        }
+        let body = &lines[start..i];
+        let Some(first) = body.iter().position(|line| !line.trim().is_empty()) else {
+            continue;
+        };
+        let margin = body
+            .iter()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| line.len() - line.trim_start().len())
+            .min()
+            .unwrap_or(0);
+        let mut block = CodeBlock {
+            line: start + first + 1,
+            code: String::new(),
+        };
+        for line in &body[first..] {
+            block.code.push_str(line.get(margin..).unwrap_or(""));
+            block.code.push('\n');
+        }
+        blocks.push(finish(block, session));
+    }
+    blocks
+}
+
+/// Keep only the code of an interactive session, in place so line numbers still match.
+/// Output lines become blank.
+fn finish(mut block: CodeBlock, session: bool) -> CodeBlock {
+    if session {
+        block.code = block
+            .code
+            .lines()
+            .map(|line| {
+                let trimmed = line.trim_start();
+                ["... ", ">>> "]
+                    .iter()
+                    .find_map(|prompt| trimmed.strip_prefix(prompt))
+                    .or_else(|| (matches!(trimmed, ">>>" | "...")).then_some(""))
+                    .unwrap_or("")
+            })
+            .fold(String::new(), |mut code, line| {
+                code.push_str(line);
+                code.push('\n');
+                code
+            });
+    }
+    block
+}
+
+/// Parse the imports of the Python code blocks of a documentation file, with the lines they
+/// are on in the file. Snippets are often incomplete, so blocks that don't parse are
+/// skipped.
+pub(crate) fn parse_imports(path: &Path, source: &str) -> Vec<PythonImport> {
+    let mut imports = Vec::new();
+    for block in python_blocks(path, source) {
+        match PythonParser::new(&block.code).parse_imports() {
+            Ok(block_imports) => {
+                imports.extend(block_imports.into_iter().map(|mut import| {
+                    import.line_number += block.line - 1;
+                    import
+                }));
+            }
+            Err(e) => debug!(
+                "Skipping the code block on line {} of {}: {}",
+                block.line,
+                path.display(),
+                e
+            ),
+        }
+    }
+    imports
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_markdown_blocks() {
+        let source = "# Usage
+
+```python
+import sphinx
+from docutils import nodes
+```
+
+```bash
+pip install example
+```
+
+~~~ py
+import requests
+~~~
+
+```pycon
+>>> import numpy as np
+>>> np.zeros(
+...     3)
+array([0., 0., 0.])
+```
+";
+        let path = Path::new("README.md");
+        let blocks = python_blocks(path, source);
+        let lines: Vec<usize> = blocks.iter().map(|block| block.line).collect();
+        assert_eq!(lines, [4, 13, 17]);
+        assert_eq!(blocks[2].code, "import numpy as np\nnp.zeros(\n    3)\n\n");
+
+        let imports: Vec<(String, usize)> = parse_imports(path, source)
+            .into_iter()
+            .map(|import| (import.module_name.unwrap(), import.line_number))
+            .collect();
+        assert_eq!(
+            imports,
+            [
+                ("sphinx".to_string(), 4),
+                ("docutils".to_string(), 5),
+                ("requests".to_string(), 13),
+                ("numpy".to_string(), 17),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rst_blocks() {
+        let source = "Usage
+=====
+
+.. code-block:: python
+   :linenos:
+
+   import sphinx
+
+   def setup(app):
+       pass
+
+Then run it.
+
+.. code:: bash
+
+   make html
+";
+        let blocks = python_blocks(Path::new("docs/usage.rst"), source);
+        assert_eq!(
+            blocks,
+            [CodeBlock {
+                line: 7,
+                code: "import sphinx\n\ndef setup(app):\n    pass\n\n".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_incomplete_snippets_are_skipped() {
+        let source = "```python
+import yaml
+config = yaml.safe_load(
+```
+
+```python
+import toml
+```
+";
+        let modules: Vec<String> = parse_imports(Path::new("README.md"), source)
+            .into_iter()
+            .filter_map(|import| import.module_name)
+            .collect();
+        assert_eq!(modules, ["toml"]);
+    }
+}
@@ -0,0 +1,296 @@
+//! Checking that the entry points a project declares refer to modules and attributes it has.
+//!
+//! An entry point like `mytool = "mypkg.cli:main"` is only loaded when the installed tool
+//! runs, so renaming `mypkg/cli.py` breaks it without any import failing in the project
+//! itself. The modules are looked up on disk under the project's source roots and the
+//! packages its build backend is configured with; the attributes are looked up among the
+//! names the module binds at module level.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::finding::{Finding, Rule};
+use crate::layout::PackageLayout;
+use crate::name_map;
+use crate::parser;
+use crate::project::EntryPoint;
+
+/// What was found for a module under the project's source roots
+#[derive(Debug, PartialEq, Eq)]
+enum ModuleSource {
+    /// A Python source, whose names can be read
+    File(PathBuf),
+    /// Something importable whose names can't be read, like a namespace package or a
+    /// compiled extension module
+    Opaque,
+    Missing,
+}
+
+/// Report the entry points whose module isn't in the project, suggesting the closest one of
+/// `modules` that is, and the ones whose module doesn't define their attribute. Modules are
+/// looked up under `roots` and the packages `layout` declares.
+pub(crate) fn findings(
+    entry_points: &[&EntryPoint],
+    roots: &[PathBuf],
+    layout: Option<&PackageLayout>,
+    modules: &[String],
+) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    for entry in entry_points {
+        let table = match entry.group.as_str() {
+            "scripts" | "gui-scripts" => format!("[project.{}]", entry.group),
+            group if group.contains('.') => format!("[project.entry-points.\"{}\"]", group),
+            group => format!("[project.entry-points.{}]", group),
+        };
+        match locate(roots, layout, &entry.module) {
+            ModuleSource::Missing => {
+                let suggestion = match closest(&entry.module, modules) {
+                    Some(module) => format!("; did you mean `{}`?", module),
+                    None => String::new(),
+                };
+                findings.push(Finding::new(
+                    Rule::BrokenEntryPoint,
+                    &entry.module,
+                    format!(
+                        "`{}` in `{}` refers to `{}`, which isn't a module of the project{}",
+                        entry.name, table, entry.module, suggestion
+                    ),
+                    Some(entry.location.clone()),
+                ));
+            }
+            ModuleSource::File(path) => {
+                let Some(attribute) = &entry.attribute else {
+                    continue;
+                };
+                // Only the first part of a dotted attribute is bound by the module
+                let name = attribute.split('.').next().unwrap_or(attribute).trim();
+                let names = fs::read_to_string(&path)
+                    .ok()
+                    .and_then(|source| parser::module_names(&source).ok().flatten());
+                if names.is_some_and(|names| !names.iter().any(|n| n == name)) {
+                    findings.push(Finding::new(
+                        Rule::BrokenEntryPoint,
+                        &entry.module,
+                        format!(
+                            "`{}` in `{}` refers to `{}:{}`, but `{}` doesn't define `{}`",
+                            entry.name, table, entry.module, attribute, entry.module, name
+                        ),
+                        Some(entry.location.clone()),
+                    ));
+                }
+            }
+            ModuleSource::Opaque => {}
+        }
+    }
+    findings
+}
+
+/// Find a module under the source roots, or in a package the layout declares
+fn locate(roots: &[PathBuf], layout: Option<&PackageLayout>, module: &str) -> ModuleSource {
+    let parts: Vec<&str> = module.split('.').collect();
+    let mut candidates: Vec<PathBuf> = roots
+        .iter()
+        .map(|root| {
+            parts
+                .iter()
+                .fold(root.clone(), |path, part| path.join(part))
+        })
+        .collect();
+    for package in layout.into_iter().flat_map(|layout| &layout.packages) {
+        if !name_map::is_module_prefix(&package.name, module) {
+            continue;
+        }
+        let Some(path) = &package.path else {
+            // Built from another language, like a maturin extension module
+            return ModuleSource::Opaque;
+        };
+        if package.name == module && path.is_file() {
+            return ModuleSource::File(path.clone());
+        }
+        let depth = package.name.split('.').count();
+        candidates.push(
+            parts[depth..]
+                .iter()
+                .fold(path.clone(), |path, part| path.join(part)),
+        );
+    }
+
+    let mut found = ModuleSource::Missing;
+    for candidate in candidates {
+        if candidate.with_extension("py").is_file() {
+            return ModuleSource::File(candidate.with_extension("py"));
+        }
+        if candidate.join("__init__.py").is_file() {
+            return ModuleSource::File(candidate.join("__init__.py"));
+        }
+        if candidate.is_dir() || is_extension_module(&candidate) {
+            found = ModuleSource::Opaque;
+        }
+    }
+    found
+}
+
+/// Whether a compiled extension module or a stub is next to where a module's source would be,
+/// like `_native.cpython-312-x86_64-linux-gnu.so` for `_native`
+fn is_extension_module(candidate: &Path) -> bool {
+    let (Some(parent), Some(stem)) = (candidate.parent(), candidate.file_name()) else {
+        return false;
+    };
+    let prefix = format!("{}.", stem.to_string_lossy());
+    fs::read_dir(parent).is_ok_and(|entries| {
+        entries.flatten().any(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            name.starts_with(&prefix)
+                && [".so", ".pyd", ".pyi"]
+                    .iter()
+                    .any(|extension| name.ends_with(extension))
+        })
+    })
+}
+
+/// The module of `modules` an entry point most likely meant: one with the same last part,
+/// as when a module moved to another package, or else the one with the fewest edits away,
+/// if it's close enough
+fn closest<'a>(module: &str, modules: &'a [String]) -> Option<&'a str> {
+    let last = module.rsplit('.').next().unwrap_or(module);
+    let moved = modules
+        .iter()
+        .filter(|candidate| candidate.rsplit('.').next() == Some(last))
+        .min_by_key(|candidate| edit_distance(module, candidate));
+    if let Some(moved) = moved {
+        return Some(moved);
+    }
+    let limit = (module.len() / 3).max(2);
+    modules
+        .iter()
+        .map(|candidate| (edit_distance(module, candidate), candidate))
+        .filter(|(distance, _)| *distance <= limit)
+        .min()
+        .map(|(_, candidate)| candidate.as_str())
+}
+
+/// The Levenshtein distance between two strings, counted in characters
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != *b);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// The dotted name of the module a Python file under one of the source roots is, if it can
+/// be imported by that name. The deepest root containing the file wins, so `src/` layouts
+/// aren't named after `src`.
+pub(crate) fn module_name(roots: &[PathBuf], path: &Path) -> Option<String> {
+    let relative = roots
+        .iter()
+        .filter_map(|root| path.strip_prefix(root).ok())
+        .min_by_key(|relative| relative.components().count())?;
+    if relative
+        .extension()
+        .is_none_or(|extension| extension != "py")
+    {
+        return None;
+    }
+    let mut parts: Vec<String> = relative
+        .with_extension("")
+        .components()
+        .map(|part| part.as_os_str().to_string_lossy().to_string())
+        .collect();
+    if parts.last().is_some_and(|last| last == "__init__") {
+        parts.pop();
+    }
+    let is_identifier = |part: &String| {
+        part.chars()
+            .next()
+            .is_some_and(|c| c.is_alphabetic() || c == '_')
+            && part.chars().all(|c| c.is_alphanumeric() || c == '_')
+    };
+    (!parts.is_empty() && parts.iter().all(is_identifier)).then(|| parts.join("."))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::finding::Location;
+
+    fn entry_point(name: &str, module: &str, attribute: Option<&str>) -> EntryPoint {
+        EntryPoint {
+            group: "scripts".to_string(),
+            name: name.to_string(),
+            module: module.to_string(),
+            attribute: attribute.map(str::to_string),
+            location: Location::new(PathBuf::from("pyproject.toml"), 1),
+        }
+    }
+
+    #[test]
+    fn test_entry_point_findings() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().to_path_buf();
+        fs::create_dir_all(root.join("src/mypkg/commands")).unwrap();
+        fs::write(root.join("src/mypkg/__init__.py"), "").unwrap();
+        fs::write(root.join("src/mypkg/commands/__init__.py"), "").unwrap();
+        fs::write(
+            root.join("src/mypkg/commands/cli.py"),
+            "import click\n\n@click.command()\ndef main():\n    pass\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("src/mypkg/lazy.py"),
+            "def __getattr__(name):\n    pass\n",
+        )
+        .unwrap();
+        fs::write(root.join("src/mypkg/_native.abi3.so"), "").unwrap();
+
+        let roots = [root.clone(), root.join("src")];
+        let modules: Vec<String> = ["src/mypkg/__init__.py", "src/mypkg/commands/cli.py"]
+            .iter()
+            .filter_map(|path| module_name(&roots, &root.join(path)))
+            .collect();
+        assert_eq!(modules, ["mypkg", "mypkg.commands.cli"]);
+
+        let entry_points = [
+            entry_point("ok", "mypkg.commands.cli", Some("main")),
+            entry_point("moved", "mypkg.cli", Some("main")),
+            entry_point("typo", "mypkg.commands.cly", Some("main")),
+            entry_point("renamed", "mypkg.commands.cli", Some("run")),
+            entry_point("method", "mypkg.commands.cli", Some("main.callback")),
+            entry_point("lazy", "mypkg.lazy", Some("anything")),
+            entry_point("native", "mypkg._native", Some("run")),
+            entry_point("namespace", "mypkg.commands", None),
+            entry_point("unknown", "other", None),
+        ];
+        let messages: Vec<String> = findings(
+            &entry_points.iter().collect::<Vec<_>>(),
+            &roots,
+            None,
+            &modules,
+        )
+        .into_iter()
+        .map(|finding| finding.message)
+        .collect();
+        assert_eq!(
+            messages,
+            [
+                "`moved` in `[project.scripts]` refers to `mypkg.cli`, which isn't a module of the project; did you mean `mypkg.commands.cli`?",
+                "`typo` in `[project.scripts]` refers to `mypkg.commands.cly`, which isn't a module of the project; did you mean `mypkg.commands.cli`?",
+                "`renamed` in `[project.scripts]` refers to `mypkg.commands.cli:run`, but `mypkg.commands.cli` doesn't define `run`",
+                "`unknown` in `[project.scripts]` refers to `other`, which isn't a module of the project",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("cli", "cli"), 0);
+    }
+}
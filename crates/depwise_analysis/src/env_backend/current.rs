@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+use tracing::debug;
+
+use super::run_command;
+use crate::PythonVersion;
+use crate::cancel::{CancellationToken, Phase};
+use crate::error::AnalysisError;
+
+/// Prints `name==version` for every distribution given on the command line that is installed
+const INSTALLED_VERSIONS_SCRIPT: &str = r#"
+import sys
+from importlib import metadata
+
+for name in sys.argv[1:]:
+    try:
+        print(name + "==" + metadata.version(name))
+    except metadata.PackageNotFoundError:
+        pass
+"#;
+
+/// The interpreter of the active environment: the one of the virtual environment in
+/// `$VIRTUAL_ENV` or the conda environment in `$CONDA_PREFIX`, or else the one `requested` by
+/// the project's `.python-version` file, or else `python3` on the $PATH.
+///
+/// This doesn't look at the project's directories, so an environment the project walker
+/// skips, like a `.venv` inside the project, is still used once it's activated.
+pub(super) fn interpreter(requested: Option<&str>) -> PathBuf {
+    ["VIRTUAL_ENV", "CONDA_PREFIX"]
+        .into_iter()
+        .filter_map(env::var_os)
+        .filter(|prefix| !prefix.is_empty())
+        .find_map(|prefix| environment_interpreter(Path::new(&prefix)))
+        .or_else(|| requested.and_then(requested_interpreter))
+        .unwrap_or_else(|| PathBuf::from("python3"))
+}
+
+/// The interpreter a `.python-version` entry like `3.11.4` names: the pyenv installation of
+/// that version, or else `python3.11` on the $PATH, or `None` when neither is installed
+fn requested_interpreter(entry: &str) -> Option<PathBuf> {
+    let pyenv_root = env::var_os("PYENV_ROOT")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".pyenv")));
+    if let Some(found) =
+        pyenv_root.and_then(|root| environment_interpreter(&root.join("versions").join(entry)))
+    {
+        return Some(found);
+    }
+    let found = PythonVersion::from_spec(entry).and_then(|version| {
+        let name = format!("python{}", version);
+        env::split_paths(&env::var_os("PATH")?)
+            .flat_map(|dir| [dir.join(&name), dir.join(&name).with_extension("exe")])
+            .find(|path| path.is_file())
+    });
+    if found.is_none() {
+        debug!(
+            "Python {} from .python-version isn't installed, using python3",
+            entry
+        );
+    }
+    found
+}
+
+/// The interpreter of an environment, which is in `Scripts` for venvs on Windows and at the
+/// root of conda environments on Windows
+fn environment_interpreter(prefix: &Path) -> Option<PathBuf> {
+    [
+        "bin/python3",
+        "bin/python",
+        "Scripts/python.exe",
+        "python.exe",
+    ]
+    .into_iter()
+    .map(|path| prefix.join(path))
+    .find(|path| path.is_file())
+}
+
+/// Look up the installed versions of distributions using the interpreter of the active
+/// environment (see `interpreter`).
+///
+/// Distributions that aren't installed are left out of the result. If no interpreter can be
+/// found an empty map is returned.
+pub(crate) fn installed_versions(
+    distributions: &[String],
+    timeout: Option<Duration>,
+    cancel: &CancellationToken,
+) -> Result<HashMap<String, String>, AnalysisError> {
+    Ok(environment_versions(distributions, None, timeout, cancel)?.unwrap_or_default())
+}
+
+/// Like `installed_versions`, but returns `None` if there is no interpreter to ask, so that
+/// distributions missing from the environment can be told apart from an unknown environment.
+/// The interpreter a project's `.python-version` file names is preferred when no environment
+/// is active.
+pub(crate) fn environment_versions(
+    distributions: &[String],
+    requested: Option<&str>,
+    timeout: Option<Duration>,
+    cancel: &CancellationToken,
+) -> Result<Option<HashMap<String, String>>, AnalysisError> {
+    let mut command = Command::new(interpreter(requested));
+    command
+        .arg("-c")
+        .arg(INSTALLED_VERSIONS_SCRIPT)
+        .args(distributions);
+    let output = match run_command(&mut command, timeout, cancel, Phase::ResolvingEnvironment) {
+        Ok(output) if output.status.success() => output,
+        Ok(output) => {
+            debug!("Python failed to look up installed versions: {:?}", output);
+            return Ok(None);
+        }
+        Err(AnalysisError::CommandError(program, e)) => {
+            debug!("Unable to run {}: {}", program, e);
+            return Ok(None);
+        }
+        Err(e) => return Err(e),
+    };
+
+    Ok(Some(
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|line| line.split_once("=="))
+            .map(|(name, version)| (name.to_string(), version.trim().to_string()))
+            .collect(),
+    ))
+}
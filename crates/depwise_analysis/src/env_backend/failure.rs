@@ -0,0 +1,374 @@
+use std::fmt;
+#[cfg(feature = "python-parsing")]
+use std::fs;
+
+#[cfg(feature = "python-parsing")]
+use crate::finding::Location;
+use crate::finding::{Explanation, Finding, Rule};
+use crate::name_map::normalize_name;
+#[cfg(feature = "python-parsing")]
+use crate::project::{Configuration, declaration_line};
+
+/// A backend command that failed to resolve or install the declared dependencies, like
+/// `uv run` on a requirement with no matching distribution
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ResolutionFailure {
+    /// The tool that failed, like `uv` or `pixi`
+    pub program: String,
+    /// What was being resolved, like `environment.yml` or the project at a path
+    pub target: String,
+    /// The problems recognized in the tool's output, reported against the declared
+    /// dependencies they are about (DW403)
+    pub findings: Vec<Finding>,
+    /// Everything the tool printed, its stderr followed by its stdout
+    pub output: String,
+}
+
+impl ResolutionFailure {
+    pub(crate) fn new(program: &str, target: &str, stderr: &[u8], stdout: &[u8]) -> Self {
+        let output = [stderr, stdout]
+            .iter()
+            .map(|output| String::from_utf8_lossy(output).trim().to_string())
+            .filter(|output| !output.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n");
+        Self {
+            program: program.to_string(),
+            target: target.to_string(),
+            findings: parse_resolver_output(&output),
+            output,
+        }
+    }
+
+    /// Point the findings at the declarations of the dependencies they are about, in the
+    /// first configuration declaring each one
+    #[cfg(feature = "python-parsing")]
+    pub(crate) fn locate(&mut self, configurations: &[Configuration]) {
+        for finding in &mut self.findings {
+            let name = normalize_name(&finding.subject);
+            let Some(configuration) = configurations.iter().find(|configuration| {
+                configuration
+                    .dependencies()
+                    .iter()
+                    .any(|dependency| dependency.name().map(normalize_name) == Some(name.clone()))
+            }) else {
+                continue;
+            };
+            let path = configuration.source().path();
+            let line = fs::read_to_string(path)
+                .map_or(0, |content| declaration_line(&content, &finding.subject));
+            finding.location = Some(Location::new(path.to_path_buf(), line));
+            finding.configuration = Some(configuration.name().to_string());
+        }
+    }
+}
+
+impl fmt::Display for ResolutionFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} failed to resolve {}", self.program, self.target)?;
+        if self.findings.is_empty() {
+            // Nothing was recognized, so the tool's own words are the best explanation
+            return match self
+                .output
+                .lines()
+                .rev()
+                .find(|line| !line.trim().is_empty())
+            {
+                Some(line) => write!(f, ": {}", line.trim()),
+                None => Ok(()),
+            };
+        }
+        for finding in &self.findings {
+            write!(f, "\n  {}", finding)?;
+        }
+        Ok(())
+    }
+}
+
+/// The kinds of resolver failures that are recognized
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FailureKind {
+    /// No version of the package satisfies the requirement, or the package doesn't exist
+    NoMatchingDistribution,
+    /// The requirements on the package can't all be satisfied at once
+    VersionConflict,
+    /// A downloaded file doesn't match the hash the requirement pins
+    HashMismatch,
+}
+
+impl FailureKind {
+    fn message(self, package: &str) -> String {
+        match self {
+            FailureKind::NoMatchingDistribution => {
+                format!("no distribution of `{}` matches the requirement", package)
+            }
+            FailureKind::VersionConflict => {
+                format!("the requirements on `{}` conflict with each other", package)
+            }
+            FailureKind::HashMismatch => {
+                format!("the downloaded `{}` doesn't match its pinned hash", package)
+            }
+        }
+    }
+}
+
+/// Recognize the common failures in the output of uv, pip, and pixi: no matching
+/// distribution, version conflicts, and hash mismatches. Each package is reported once per
+/// kind of failure, with the lines of output that mention it.
+pub(crate) fn parse_resolver_output(output: &str) -> Vec<Finding> {
+    let mut problems: Vec<(FailureKind, String, Vec<String>)> = Vec::new();
+    let mut in_hash_listing = false;
+    for line in output.lines() {
+        let text = strip_decoration(line);
+        if text.is_empty() {
+            continue;
+        }
+        // pip lists the files whose hashes don't match after a heading, one per
+        // `<package> from <url>:` line
+        if text.contains("DO NOT MATCH THE HASHES") {
+            in_hash_listing = true;
+            continue;
+        }
+        let recognized = if in_hash_listing
+            && text.ends_with(':')
+            && let Some((package, _)) = text.split_once(" from ")
+        {
+            vec![(FailureKind::HashMismatch, package)]
+        } else {
+            recognize(text)
+        };
+        for (kind, requirement) in recognized {
+            let Some(package) = package_name(requirement) else {
+                continue;
+            };
+            match problems
+                .iter_mut()
+                .find(|(k, p, _)| *k == kind && normalize_name(p) == normalize_name(package))
+            {
+                Some((_, _, lines)) => lines.push(text.to_string()),
+                None => problems.push((kind, package.to_string(), vec![text.to_string()])),
+            }
+        }
+    }
+    problems
+        .into_iter()
+        .map(|(kind, package, lines)| {
+            Finding::new(
+                Rule::UnresolvableDependency,
+                &package,
+                kind.message(&package),
+                None,
+            )
+            .with_explanation(Explanation::new(Vec::new(), lines))
+        })
+        .collect()
+}
+
+/// The failures a line of output describes, with the requirements they are about
+fn recognize(text: &str) -> Vec<(FailureKind, &str)> {
+    use FailureKind::*;
+
+    // pip
+    if let Some(requirement) = text.strip_prefix("No matching distribution found for ") {
+        return vec![(NoMatchingDistribution, requirement)];
+    }
+    if let Some(rest) =
+        text.strip_prefix("Could not find a version that satisfies the requirement ")
+    {
+        return vec![(NoMatchingDistribution, rest)];
+    }
+    if let Some(rest) = text.strip_prefix("Cannot install ")
+        && let Some((requirements, _)) = rest.split_once(" because ")
+    {
+        return requirements
+            .split(", ")
+            .flat_map(|requirement| requirement.split(" and "))
+            .map(|requirement| (VersionConflict, requirement))
+            .collect();
+    }
+
+    // uv, which pixi uses for PyPI dependencies too
+    if let Some(rest) = text.strip_prefix("Hash mismatch for ") {
+        return vec![(HashMismatch, rest.trim_matches('`'))];
+    }
+    if let Some(rest) = text.strip_prefix("Because ") {
+        let requirement = rest
+            .strip_prefix("there is no version of ")
+            .or_else(|| rest.strip_prefix("there are no versions of "))
+            .or_else(|| rest.strip_prefix("only "))
+            .or_else(|| {
+                rest.split_once(" was not found in the package registry")
+                    .map(|(package, _)| package)
+            });
+        if let Some(requirement) = requirement {
+            return vec![(NoMatchingDistribution, requirement)];
+        }
+        if let Some((_, required)) = rest.split_once("you require ") {
+            return vec![(VersionConflict, required)];
+        }
+    }
+
+    // pixi's conda solver
+    if let Some((_, rest)) = text.split_once("No candidates were found for ") {
+        return vec![(NoMatchingDistribution, rest)];
+    }
+    if let Some((requirement, _)) =
+        text.split_once(" cannot be installed because there are no viable options")
+    {
+        return vec![(VersionConflict, requirement)];
+    }
+    Vec::new()
+}
+
+/// Remove what tools decorate their error lines with, like `ERROR: ` or uv's `╰─▶ `
+fn strip_decoration(line: &str) -> &str {
+    let mut text = line.trim_start_matches(|c: char| {
+        c.is_whitespace() || matches!(c, '×' | '╰' | '─' | '▶' | '├' | '│' | '└')
+    });
+    for prefix in ["ERROR: ", "error: ", "Error: "] {
+        text = text.strip_prefix(prefix).unwrap_or(text);
+    }
+    text.trim_end()
+}
+
+/// The package name a requirement starts with, like `numpy` in `numpy==9.9 (from versions:
+/// none)` or in `` `numpy==1.0` ``
+fn package_name(requirement: &str) -> Option<&str> {
+    let requirement = requirement.trim_start_matches(['`', '\'', '"']);
+    let end = requirement
+        .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')))
+        .unwrap_or(requirement.len());
+    let name = requirement[..end].trim_end_matches('.');
+    (!name.is_empty()).then_some(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "python-parsing")]
+    use crate::EnvironmentBuilderSource;
+    #[cfg(feature = "python-parsing")]
+    use crate::project::extract_configurations;
+
+    fn recognized(output: &str) -> Vec<(String, String)> {
+        parse_resolver_output(output)
+            .into_iter()
+            .map(|finding| (finding.subject, finding.message))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_resolver_output() {
+        // uv
+        assert_eq!(
+            recognized(
+                "  × No solution found when resolving dependencies:
+  ╰─▶ Because there is no version of numpyy==1.26 and you require numpyy==1.26, we can conclude that your requirements are unsatisfiable."
+            ),
+            [(
+                "numpyy".to_string(),
+                "no distribution of `numpyy` matches the requirement".to_string()
+            )]
+        );
+        assert_eq!(
+            recognized(
+                "  ╰─▶ Because flask==3.0.0 depends on werkzeug>=3.0.0 and you require werkzeug<3, we can conclude that your requirements are unsatisfiable."
+            ),
+            [(
+                "werkzeug".to_string(),
+                "the requirements on `werkzeug` conflict with each other".to_string()
+            )]
+        );
+        assert_eq!(
+            recognized("error: Hash mismatch for `requests==2.32.3`"),
+            [(
+                "requests".to_string(),
+                "the downloaded `requests` doesn't match its pinned hash".to_string()
+            )]
+        );
+
+        // pip, where both lines name the same requirement
+        let findings = parse_resolver_output(
+            "ERROR: Could not find a version that satisfies the requirement rich==99 (from versions: 13.0.0)
+ERROR: No matching distribution found for rich==99",
+        );
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].subject, "rich");
+        assert_eq!(findings[0].explanation.as_ref().unwrap().reasons.len(), 2);
+        assert_eq!(
+            recognized(
+                "ERROR: Cannot install flask==3.0.0 and werkzeug==2.0 because these package versions have conflicting dependencies."
+            )
+            .len(),
+            2
+        );
+        assert_eq!(
+            recognized(
+                "ERROR: THESE PACKAGES DO NOT MATCH THE HASHES FROM THE REQUIREMENTS FILE.
+    idna from https://files.pythonhosted.org/packages/idna-3.7-py3-none-any.whl:
+        Expected sha256 0000
+             Got        1111"
+            ),
+            [(
+                "idna".to_string(),
+                "the downloaded `idna` doesn't match its pinned hash".to_string()
+            )]
+        );
+
+        // pixi
+        assert_eq!(
+            recognized(
+                "  × failed to solve the conda requirements of 'default' 'linux-64'
+  ╰─▶ Cannot solve the request because of: No candidates were found for samtoolz *."
+            ),
+            [(
+                "samtoolz".to_string(),
+                "no distribution of `samtoolz` matches the requirement".to_string()
+            )]
+        );
+
+        assert!(recognized("error: Failed to spawn: `python`").is_empty());
+    }
+
+    #[cfg(feature = "python-parsing")]
+    #[test]
+    fn test_locate_failures() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("requirements.txt");
+        fs::write(&path, "# numpyy is misspelled\nrequests\nnumpyy==1.26\n").unwrap();
+        let configurations = extract_configurations(
+            EnvironmentBuilderSource::RequirementsTxt(path.clone()),
+            true,
+        )
+        .unwrap();
+
+        let mut failure = ResolutionFailure::new(
+            "uv",
+            "the project",
+            b"  \xe2\x95\xb0\xe2\x94\x80\xe2\x96\xb6 Because there is no version of numpyy==1.26 and you require numpyy==1.26, we can conclude that your requirements are unsatisfiable.\n",
+            b"",
+        );
+        failure.locate(&configurations);
+        let finding = &failure.findings[0];
+        assert_eq!(finding.location, Some(Location::new(path, 3)));
+        assert_eq!(
+            finding.configuration.as_deref(),
+            Some(configurations[0].name())
+        );
+        assert!(
+            failure
+                .to_string()
+                .starts_with("uv failed to resolve the project\n"),
+            "{}",
+            failure
+        );
+
+        let failure = ResolutionFailure::new("uv", "the project", b"error: No interpreter\n", b"");
+        assert!(failure.findings.is_empty());
+        assert_eq!(
+            failure.to_string(),
+            "uv failed to resolve the project: error: No interpreter"
+        );
+    }
+}
@@ -0,0 +1,259 @@
+//! Downloading files over HTTP, for everything that fetches remote data: requirements files
+//! included by URL, PyPI's JSON API, and conda repodata.
+//!
+//! Downloads run `curl` through `run_command`, so they're killed once the analysis is
+//! cancelled or they run longer than the backend timeout. Every response is kept under the
+//! user's cache directory with its `ETag`, and downloading the same URL again asks the server
+//! whether it changed first, reusing the cached body when it didn't. What callers cache of
+//! the responses, and for how long, is up to them; this cache only saves transferring a body
+//! that hasn't changed.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+use tracing::debug;
+
+use super::{default_cache_dir, run_command};
+use crate::cancel::{CancellationToken, Phase};
+use crate::error::AnalysisError;
+use crate::hash::fnv1a;
+
+/// The most a response may be, as sent. The biggest are whole zstd-compressed repodata,
+/// which for conda-forge's biggest subdir is about half of this.
+const MAX_DOWNLOAD_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Download a file, killing the download once `cancel` is cancelled or after `timeout`
+pub(crate) fn get(
+    url: &str,
+    timeout: Option<Duration>,
+    cancel: &CancellationToken,
+) -> Result<Vec<u8>, AnalysisError> {
+    get_in(&cache_dir(), url, timeout, cancel)
+}
+
+fn cache_dir() -> PathBuf {
+    default_cache_dir().join("http")
+}
+
+/// Where the response of a URL is cached: its body, and the `ETag` it was served with
+struct Entry {
+    body: PathBuf,
+    etag: PathBuf,
+}
+
+impl Entry {
+    fn new(directory: &Path, url: &str) -> Self {
+        // Readable, but shortened to fit a file name, and kept apart from the URLs that
+        // read the same, like `a-b.txt` and `a_b.txt`, by their hash
+        let readable: String = url
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .take(111)
+            .collect();
+        let name = format!("{}_{:016x}", readable, fnv1a(url.bytes()));
+        Self {
+            body: directory.join(&name),
+            etag: directory.join(format!("{}.etag", name)),
+        }
+    }
+}
+
+/// Download a file, caching the response under `directory`
+fn get_in(
+    directory: &Path,
+    url: &str,
+    timeout: Option<Duration>,
+    cancel: &CancellationToken,
+) -> Result<Vec<u8>, AnalysisError> {
+    cancel.check(Phase::ResolvingEnvironment)?;
+    let fetch_error =
+        |e: &dyn std::fmt::Display| AnalysisError::FetchError(url.to_string(), e.to_string());
+    fs::create_dir_all(directory).map_err(|e| fetch_error(&e))?;
+    let entry = Entry::new(directory, url);
+    let partial = entry.body.with_extension("partial");
+    let partial_etag = entry.etag.with_extension("etag.partial");
+
+    let mut command = Command::new("curl");
+    command
+        .args([
+            "--fail",
+            "--silent",
+            "--show-error",
+            "--location",
+            "--compressed",
+        ])
+        .arg("--max-filesize")
+        .arg(MAX_DOWNLOAD_BYTES.to_string())
+        .args(["--write-out", "%{response_code}"])
+        .arg("--output")
+        .arg(&partial)
+        .arg("--etag-save")
+        .arg(&partial_etag);
+    // The server only sends the body again if it changed since it was cached
+    if entry.body.exists() && fs::metadata(&entry.etag).is_ok_and(|etag| etag.len() > 0) {
+        command.arg("--etag-compare").arg(&entry.etag);
+    }
+    let output = run_command(
+        command.arg(url),
+        timeout,
+        cancel,
+        Phase::ResolvingEnvironment,
+    );
+    let output = match output {
+        Ok(output) => output,
+        Err(e) => {
+            let _ = fs::remove_file(&partial);
+            let _ = fs::remove_file(&partial_etag);
+            return Err(e);
+        }
+    };
+    if !output.status.success() {
+        let _ = fs::remove_file(&partial);
+        let _ = fs::remove_file(&partial_etag);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(fetch_error(&stderr.trim()));
+    }
+
+    if String::from_utf8_lossy(&output.stdout).trim() == "304" {
+        debug!("{} hasn't changed since it was cached", url);
+        let _ = fs::remove_file(&partial);
+        let _ = fs::remove_file(&partial_etag);
+        return fs::read(&entry.body).map_err(|e| fetch_error(&e));
+    }
+    let body = fs::read(&partial).unwrap_or_default();
+    // The body is only replaced once it's all written, and the ETag along with it
+    if let Err(e) = fs::rename(&partial, &entry.body) {
+        debug!("Failed to cache the response of {}: {}", url, e);
+    }
+    if fs::metadata(&partial_etag).is_ok_and(|etag| etag.len() > 0) {
+        let _ = fs::rename(&partial_etag, &entry.etag);
+    } else {
+        let _ = fs::remove_file(&partial_etag);
+        let _ = fs::remove_file(&entry.etag);
+    }
+    Ok(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Instant;
+
+    use super::*;
+
+    /// Serve canned responses on a local port, one per connection, sending the request
+    /// headers of each back on the channel
+    fn serve(responses: Vec<&'static str>) -> (String, mpsc::Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/file.txt", listener.local_addr().unwrap());
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            for response in responses {
+                let Ok((mut stream, _)) = listener.accept() else {
+                    return;
+                };
+                let mut request = String::new();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                while reader.read_line(&mut request).is_ok_and(|read| read > 2) {}
+                let _ = sender.send(request);
+                if response.is_empty() {
+                    // Never answer, like a server that hangs
+                    thread::sleep(Duration::from_secs(30));
+                }
+                let _ = stream.write_all(response.as_bytes());
+            }
+        });
+        (url, receiver)
+    }
+
+    #[test]
+    fn test_get_revalidates_the_cached_response() -> Result<(), AnalysisError> {
+        let (url, requests) = serve(vec![
+            "HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello",
+            "HTTP/1.1 304 Not Modified\r\nETag: \"v1\"\r\nConnection: close\r\n\r\n",
+        ]);
+        let dir = tempfile::tempdir().unwrap();
+        let cancel = CancellationToken::new();
+
+        assert_eq!(get_in(dir.path(), &url, None, &cancel)?, b"hello");
+        assert!(!requests.recv().unwrap().contains("If-None-Match"));
+        // The second time the server says nothing changed, and the cached body is returned
+        assert_eq!(get_in(dir.path(), &url, None, &cancel)?, b"hello");
+        assert!(requests.recv().unwrap().contains("If-None-Match: \"v1\""));
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_reads_files() -> Result<(), AnalysisError> {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("requirements.txt");
+        fs::write(&file, "requests\n").unwrap();
+        let url = format!("file://{}", file.display());
+
+        let cache = dir.path().join("cache");
+        let body = get_in(&cache, &url, None, &CancellationToken::new())?;
+        assert_eq!(body, b"requests\n");
+        assert_eq!(fs::read(Entry::new(&cache, &url).body).unwrap(), body);
+
+        fs::remove_file(&file).unwrap();
+        assert!(matches!(
+            get_in(&cache, &url, None, &CancellationToken::new()),
+            Err(AnalysisError::FetchError(_, _))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_times_out_and_is_cancelled() {
+        let (url, _requests) = serve(vec![""]);
+        let dir = tempfile::tempdir().unwrap();
+        let started = Instant::now();
+        let result = get_in(
+            dir.path(),
+            &url,
+            Some(Duration::from_millis(200)),
+            &CancellationToken::new(),
+        );
+        assert!(
+            matches!(&result, Err(AnalysisError::CommandTimeout(program, _)) if program == "curl"),
+            "{:?}",
+            result
+        );
+        assert!(started.elapsed() < Duration::from_secs(10));
+
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        assert!(matches!(
+            get_in(dir.path(), &url, None, &cancel),
+            Err(AnalysisError::Cancelled(Phase::ResolvingEnvironment))
+        ));
+    }
+
+    #[test]
+    fn test_long_urls_are_shortened() {
+        let long = format!("https://example.com/{}", "a".repeat(300));
+        let entry = Entry::new(Path::new("cache"), &long);
+        let name = entry
+            .body
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .to_string();
+        assert_eq!(name.len(), 128);
+        let other = Entry::new(Path::new("cache"), &format!("{}b", long));
+        assert_ne!(entry.body, other.body);
+    }
+
+    #[test]
+    fn test_urls_reading_the_same_are_kept_apart() {
+        let entry = Entry::new(Path::new("cache"), "https://example.com/a-b.txt");
+        let other = Entry::new(Path::new("cache"), "https://example.com/a_b.txt");
+        assert_ne!(entry.body, other.body);
+        assert_ne!(entry.etag, other.etag);
+    }
+}
@@ -0,0 +1,440 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+#[cfg(feature = "backends-exec")]
+use std::process::Command;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "backends-exec")]
+use tracing::debug;
+
+use super::Backend;
+#[cfg(feature = "backends-exec")]
+use super::{ResolutionFailure, current, run_command};
+use crate::EnvironmentBackend;
+use crate::cancel::CancellationToken;
+#[cfg(feature = "backends-exec")]
+use crate::cancel::Phase;
+use crate::error::AnalysisError;
+use crate::matcher::ModuleIndex;
+use crate::name_map;
+use crate::project::Dependency;
+#[cfg(feature = "backends-exec")]
+use crate::python_version;
+
+/// Prints the interpreter version, `sys.path`, and the installed distributions with the
+/// top-level modules each provides, what each requires, and the hashes of the archives they
+/// were installed from, as an environment manifest. The packages of a conda environment
+/// are read from its `conda-meta` records, and the Python distribution each installs is
+/// found from the files it lists.
+#[cfg(feature = "backends-exec")]
+const INTROSPECT_SCRIPT: &str = r#"
+import glob
+import json
+import os
+import re
+import sys
+from importlib import metadata
+
+def normalize(name):
+    return re.sub(r"[-_.]+", "-", name).lower()
+
+def top_level_modules(dist):
+    top_level = dist.read_text("top_level.txt")
+    if top_level:
+        return sorted({line.strip() for line in top_level.splitlines() if line.strip()})
+    modules = set()
+    for path in dist.files or []:
+        head = path.parts[0]
+        if head in ("..", "__pycache__") or head.endswith((".dist-info", ".egg-info", ".data")):
+            continue
+        if len(path.parts) > 1:
+            modules.add(head)
+        elif head.endswith((".py", ".so", ".pyd")):
+            modules.add(head.split(".")[0])
+    return sorted(modules)
+
+def required_name(requirement):
+    # What extras require isn't installed with the distribution
+    if re.search(r"\bextra\s*==", requirement):
+        return None
+    match = re.match(r"\s*([A-Za-z0-9][A-Za-z0-9._-]*)", requirement)
+    return match.group(1) if match else None
+
+def archive_hashes(dist):
+    try:
+        direct_url = json.loads(dist.read_text("direct_url.json") or "{}")
+    except ValueError:
+        return {}
+    archive = direct_url.get("archive_info", {})
+    hashes = dict(archive.get("hashes", {}))
+    if "=" in archive.get("hash", ""):
+        algorithm, digest = archive["hash"].split("=", 1)
+        hashes.setdefault(algorithm, digest)
+    return hashes
+
+distributions = {}
+for dist in metadata.distributions():
+    name = dist.metadata["Name"]
+    if name and name not in distributions:
+        distributions[name] = {
+            "version": dist.version,
+            "top_level_modules": top_level_modules(dist),
+            "requires": sorted({n for n in map(required_name, dist.requires or []) if n}),
+            "hashes": archive_hashes(dist),
+        }
+by_normalized_name = {normalize(name): name for name in distributions}
+for distribution in distributions.values():
+    distribution["requires"] = sorted(
+        {by_normalized_name[normalize(n)] for n in distribution["requires"] if normalize(n) in by_normalized_name}
+    )
+
+records = []
+for path in sorted(glob.glob(os.path.join(sys.prefix, "conda-meta", "*.json"))):
+    try:
+        with open(path) as f:
+            records.append(json.load(f))
+    except (OSError, ValueError):
+        pass
+# The distribution each conda package installs, or the package itself when it has none
+installs = {}
+for record in records:
+    installs[record["name"]] = record["name"]
+    for file in record.get("files", []):
+        parts = file.split("/")
+        if len(parts) > 1 and parts[-1] == "METADATA" and parts[-2].endswith(".dist-info"):
+            name = normalize(parts[-2][: -len(".dist-info")].rsplit("-", 1)[0])
+            installs[record["name"]] = by_normalized_name.get(name, record["name"])
+            break
+for record in records:
+    distribution = distributions.setdefault(
+        installs[record["name"]], {"version": record["version"], "top_level_modules": []}
+    )
+    distribution["conda_package"] = record["name"]
+    distribution["hashes"] = {a: record[a] for a in ("md5", "sha256") if record.get(a)}
+    depends = {installs.get(spec.split()[0]) for spec in record.get("depends", [])}
+    distribution["requires"] = sorted(depends - {None})
+
+print(json.dumps({
+    "python_version": "%d.%d.%d" % sys.version_info[:3],
+    "sys_path": [path for path in sys.path if path],
+    "distributions": distributions,
+}))
+"#;
+
+/// A snapshot of what a Python environment provides: the interpreter, where it imports
+/// from, and the installed distributions.
+///
+/// Every backend can produce one (see [`introspect`]), and it can be saved as JSON and used
+/// as the backend of a later analysis, which then doesn't need the tools that created the
+/// environment.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResolvedEnvironment {
+    /// The interpreter version, like `3.11.9`, if known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub python_version: Option<String>,
+    /// The interpreter's `sys.path`
+    #[serde(default)]
+    pub sys_path: Vec<PathBuf>,
+    /// The installed distributions, by name
+    #[serde(default)]
+    pub distributions: BTreeMap<String, InstalledDistribution>,
+}
+
+/// A distribution in a [`ResolvedEnvironment`]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InstalledDistribution {
+    /// The installed version, or `None` when the environment is simulated
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// The top-level modules (or namespace module prefixes) the distribution provides
+    #[serde(default)]
+    pub top_level_modules: Vec<String>,
+    /// The conda package it was installed from, like `yaml` for `pyyaml`, or `None` when it
+    /// was installed from PyPI
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conda_package: Option<String>,
+    /// The other distributions of the environment it requires, by their names in it
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub requires: Vec<String>,
+    /// The digests of the archive it was installed from, in hex, by algorithm like `sha256`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub hashes: BTreeMap<String, String>,
+}
+
+impl ResolvedEnvironment {
+    /// Read a manifest written by [`to_json`](Self::to_json)
+    pub fn from_json(json: &str) -> Result<Self, AnalysisError> {
+        serde_json::from_str(json)
+            .map_err(|e| AnalysisError::EnvironmentManifestError(e.to_string()))
+    }
+
+    /// Write the manifest as pretty-printed JSON
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("an environment manifest serializes to JSON")
+    }
+
+    /// Find an installed distribution, comparing normalized names
+    pub fn distribution(&self, name: &str) -> Option<&InstalledDistribution> {
+        let name = name_map::normalize_name(name);
+        self.distributions
+            .iter()
+            .find(|(installed, _)| name_map::normalize_name(installed) == name)
+            .map(|(_, distribution)| distribution)
+    }
+}
+
+impl Backend for ResolvedEnvironment {
+    fn populate_index(
+        &self,
+        _dependencies: &[Dependency],
+        index: &mut ModuleIndex,
+        _cancel: &CancellationToken,
+    ) -> Result<(), AnalysisError> {
+        for (name, distribution) in &self.distributions {
+            index.add_distribution_top_level(name, &distribution.top_level_modules.join("\n"));
+        }
+        Ok(())
+    }
+
+    fn installed_versions(
+        &self,
+        distributions: &[String],
+        _cancel: &CancellationToken,
+    ) -> Result<Option<HashMap<String, String>>, AnalysisError> {
+        Ok(Some(
+            distributions
+                .iter()
+                .filter_map(|name| {
+                    let version = self.distribution(name)?.version.clone()?;
+                    Some((name.clone(), version))
+                })
+                .collect(),
+        ))
+    }
+}
+
+/// Describe the environment a backend checks against.
+///
+/// The current environment is inspected with the interpreter the current backend checks
+/// against: the one of the active environment, or else the one the project's
+/// `.python-version` file names, or else `python3` on the $PATH. uv and pixi environments are
+/// inspected with the interpreter `uv run` or `pixi run` starts in the project at `path` (in
+/// the pixi environment named `environment`, if given). The automatic and simulated backends
+/// have no environment to inspect, so like the analysis they describe the `dependencies` with
+/// the modules the curated mapping expects them to provide, without versions.
+#[cfg_attr(not(feature = "backends-exec"), allow(unused_variables))]
+pub fn introspect(
+    backend: EnvironmentBackend,
+    path: &Path,
+    environment: Option<&str>,
+    dependencies: &[Dependency],
+    timeout: Option<Duration>,
+    cancel: &CancellationToken,
+) -> Result<ResolvedEnvironment, AnalysisError> {
+    let directory = if path.is_file() {
+        path.parent().unwrap_or(Path::new("."))
+    } else {
+        path
+    };
+    match backend {
+        EnvironmentBackend::Auto | EnvironmentBackend::Simulated => Ok(simulate(dependencies)),
+        #[cfg(feature = "backends-exec")]
+        EnvironmentBackend::Current => {
+            let requested = python_version::read_python_version_file(directory);
+            run_introspection(
+                Command::new(current::interpreter(requested.as_deref())),
+                "the current environment",
+                timeout,
+                cancel,
+            )
+        }
+        #[cfg(feature = "backends-exec")]
+        EnvironmentBackend::UV => {
+            let mut command = Command::new("uv");
+            command
+                .arg("run")
+                .arg("--directory")
+                .arg(directory)
+                .arg("python");
+            run_introspection(command, &target(directory, environment), timeout, cancel)
+        }
+        #[cfg(feature = "backends-exec")]
+        EnvironmentBackend::Pixi => {
+            let mut command = Command::new("pixi");
+            command.arg("run").arg("--manifest-path").arg(directory);
+            if let Some(environment) = environment {
+                command.arg("--environment").arg(environment);
+            }
+            command.arg("python");
+            let target = target(directory, environment);
+            run_introspection(command, &target, timeout, cancel)
+        }
+    }
+}
+
+/// What a backend resolves in a project directory, for errors
+#[cfg(feature = "backends-exec")]
+fn target(directory: &Path, environment: Option<&str>) -> String {
+    match environment {
+        Some(environment) => format!(
+            "the `{}` environment of {}",
+            environment,
+            directory.display()
+        ),
+        None => format!("the project at {}", directory.display()),
+    }
+}
+
+/// Run the introspection script with a Python interpreter command, like `pixi run python`,
+/// and read the manifest it prints. `target` describes what the command resolves, like
+/// `environment.yml`, for the errors when it fails or times out.
+#[cfg(feature = "backends-exec")]
+pub(super) fn run_introspection(
+    mut command: Command,
+    target: &str,
+    timeout: Option<Duration>,
+    cancel: &CancellationToken,
+) -> Result<ResolvedEnvironment, AnalysisError> {
+    command.arg("-c").arg(INTROSPECT_SCRIPT);
+    let program = command.get_program().to_string_lossy().to_string();
+    let output = run_command(&mut command, timeout, cancel, Phase::ResolvingEnvironment).map_err(
+        |error| match error {
+            AnalysisError::CommandTimeout(program, seconds) => {
+                AnalysisError::ResolutionTimeout(program, target.to_string(), seconds)
+            }
+            error => error,
+        },
+    )?;
+    if !output.status.success() {
+        debug!(
+            "{} failed to describe the environment: {:?}",
+            program, output
+        );
+        return Err(AnalysisError::ResolutionFailed(Box::new(
+            ResolutionFailure::new(&program, target, &output.stderr, &output.stdout),
+        )));
+    }
+    ResolvedEnvironment::from_json(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Describe the environment the dependencies would install, as far as the curated mapping
+/// knows
+fn simulate(dependencies: &[Dependency]) -> ResolvedEnvironment {
+    let index = ModuleIndex::new();
+    let mut environment = ResolvedEnvironment::default();
+    for dependency in dependencies {
+        let name = match dependency {
+            Dependency::Conda(spec) => name_map::conda_to_pypi(spec.name())
+                .map(str::to_string)
+                .unwrap_or_else(|| name_map::normalize_name(spec.name())),
+            _ => match dependency.name() {
+                Some(name) => name_map::normalize_name(name),
+                None => continue,
+            },
+        };
+        let top_level_modules = index.modules_for(&name);
+        environment
+            .distributions
+            .entry(name)
+            .or_insert(InstalledDistribution {
+                top_level_modules,
+                ..InstalledDistribution::default()
+            });
+    }
+    environment
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::matcher::ModuleProvider;
+    use crate::project::PyPIRequirement;
+
+    fn manifest() -> ResolvedEnvironment {
+        let mut environment = ResolvedEnvironment {
+            python_version: Some("3.11.9".to_string()),
+            sys_path: vec![PathBuf::from("/venv/lib/python3.11/site-packages")],
+            ..Default::default()
+        };
+        environment.distributions.insert(
+            "PyYAML".to_string(),
+            InstalledDistribution {
+                version: Some("6.0.1".to_string()),
+                top_level_modules: vec!["_yaml".to_string(), "yaml".to_string()],
+                ..InstalledDistribution::default()
+            },
+        );
+        environment
+    }
+
+    #[test]
+    fn test_manifest_round_trip() -> Result<(), AnalysisError> {
+        let environment = manifest();
+        let json = environment.to_json();
+        assert!(json.contains("\"top_level_modules\""), "{}", json);
+        assert_eq!(ResolvedEnvironment::from_json(&json)?, environment);
+
+        // Everything but the distributions can be left out
+        let minimal = ResolvedEnvironment::from_json(r#"{"distributions": {"rich": {}}}"#)?;
+        assert_eq!(minimal.python_version, None);
+        assert_eq!(
+            minimal.distribution("rich"),
+            Some(&InstalledDistribution::default())
+        );
+
+        assert!(matches!(
+            ResolvedEnvironment::from_json(r#"{"distributions": ["rich"]}"#),
+            Err(AnalysisError::EnvironmentManifestError(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_manifest_backend() -> Result<(), AnalysisError> {
+        let environment = manifest();
+        let cancel = CancellationToken::new();
+        let mut index = ModuleIndex::new();
+        environment.populate_index(&[], &mut index, &cancel)?;
+        assert_eq!(
+            index.resolve("_yaml"),
+            [ModuleProvider::Distribution("pyyaml".to_string())]
+        );
+
+        let versions = environment
+            .installed_versions(&["pyyaml".to_string(), "rich".to_string()], &cancel)?
+            .unwrap();
+        assert_eq!(versions.get("pyyaml").map(String::as_str), Some("6.0.1"));
+        assert!(!versions.contains_key("rich"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_simulated_manifest() -> Result<(), AnalysisError> {
+        let dependencies = [
+            Dependency::PyPI(PyPIRequirement::from_str("PyYAML>=6").unwrap()),
+            Dependency::PyPI(PyPIRequirement::from_str("typing-extensions").unwrap()),
+        ];
+        let environment = introspect(
+            EnvironmentBackend::Simulated,
+            Path::new("."),
+            None,
+            &dependencies,
+            None,
+            &CancellationToken::new(),
+        )?;
+        let modules = |name: &str| {
+            environment
+                .distribution(name)
+                .map(|distribution| distribution.top_level_modules.clone())
+        };
+        assert_eq!(modules("pyyaml"), Some(vec!["yaml".to_string()]));
+        assert_eq!(
+            modules("typing-extensions"),
+            Some(vec!["typing_extensions".to_string()])
+        );
+        Ok(())
+    }
+}
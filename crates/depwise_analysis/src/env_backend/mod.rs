@@ -1,3 +1,399 @@
+#[cfg(feature = "backends-exec")]
+mod current;
+#[cfg(feature = "backends-exec")]
+mod failure;
+#[cfg(feature = "backends-net")]
+pub(crate) mod http;
+mod manifest;
+#[cfg(all(feature = "backends-exec", feature = "python-parsing"))]
 mod pixi;
+#[cfg(all(feature = "backends-net", feature = "python-parsing"))]
+pub(crate) mod pypi;
+#[cfg(all(
+    feature = "conda",
+    feature = "backends-net",
+    feature = "python-parsing"
+))]
+pub(crate) mod repodata;
 mod synthetic;
 mod uv;
+
+use std::collections::HashMap;
+use std::fmt;
+#[cfg(any(feature = "backends-exec", feature = "backends-net"))]
+use std::io::Read;
+#[cfg(all(feature = "backends-exec", feature = "python-parsing"))]
+use std::path::Path;
+#[cfg(feature = "backends-net")]
+use std::path::PathBuf;
+#[cfg(any(feature = "backends-exec", feature = "backends-net"))]
+use std::process::{Child, Command, Output, Stdio};
+#[cfg(any(feature = "backends-exec", feature = "backends-net"))]
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+#[cfg(any(feature = "backends-exec", feature = "backends-net"))]
+use std::thread;
+use std::time::Duration;
+#[cfg(any(feature = "backends-exec", feature = "backends-net"))]
+use std::time::Instant;
+
+#[cfg(any(feature = "backends-exec", feature = "backends-net"))]
+use tracing::debug;
+#[cfg(any(feature = "backends-exec", feature = "backends-net"))]
+use wait_timeout::ChildExt;
+
+use crate::EnvironmentBackend;
+use crate::cancel::CancellationToken;
+#[cfg(any(feature = "backends-exec", feature = "backends-net"))]
+use crate::cancel::Phase;
+use crate::error::AnalysisError;
+use crate::matcher::ModuleIndex;
+#[cfg(all(feature = "backends-exec", feature = "python-parsing"))]
+use crate::project::Configuration;
+use crate::project::Dependency;
+
+#[cfg(feature = "backends-exec")]
+pub use failure::ResolutionFailure;
+pub use manifest::{InstalledDistribution, ResolvedEnvironment, introspect};
+#[cfg(all(feature = "backends-exec", feature = "python-parsing"))]
+pub(crate) use pixi::introspect_conda_environment;
+
+/// The channel conda calls `defaults`, which pixi and the repodata server only know by its URL
+#[cfg(all(
+    feature = "python-parsing",
+    any(
+        all(feature = "conda", feature = "backends-net"),
+        feature = "backends-exec"
+    )
+))]
+pub(crate) const DEFAULTS_CHANNEL: &str = "https://repo.anaconda.com/pkgs/main";
+
+/// The conda platform of the running machine, like `linux-64`
+#[cfg(all(
+    feature = "python-parsing",
+    any(
+        all(feature = "conda", feature = "backends-net"),
+        feature = "backends-exec"
+    )
+))]
+pub(crate) fn current_platform() -> Option<&'static str> {
+    match (std::env::consts::OS, std::env::consts::ARCH) {
+        ("linux", "x86_64") => Some("linux-64"),
+        ("linux", "aarch64") => Some("linux-aarch64"),
+        ("macos", "x86_64") => Some("osx-64"),
+        ("macos", "aarch64") => Some("osx-arm64"),
+        ("windows", "x86_64") => Some("win-64"),
+        _ => None,
+    }
+}
+
+/// How long a running command is waited for before checking whether it was cancelled
+#[cfg(any(feature = "backends-exec", feature = "backends-net"))]
+const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Describes what the declared dependencies of a project install.
+///
+/// Backends that do slow work, like solving or creating an environment, should check the
+/// cancellation token regularly and stop any process they started once it is cancelled
+/// (see `run_command`).
+pub trait Backend: fmt::Debug + Send + Sync {
+    /// Register the modules provided by the dependencies on the index
+    fn populate_index(
+        &self,
+        dependencies: &[Dependency],
+        index: &mut ModuleIndex,
+        cancel: &CancellationToken,
+    ) -> Result<(), AnalysisError>;
+
+    /// Look up the installed versions of distributions in the backend's environment, keyed by
+    /// the names given. Distributions that aren't installed are left out of the map.
+    ///
+    /// Backends that don't inspect an environment return `None`, and the declared versions
+    /// aren't checked against it.
+    fn installed_versions(
+        &self,
+        _distributions: &[String],
+        _cancel: &CancellationToken,
+    ) -> Result<Option<HashMap<String, String>>, AnalysisError> {
+        Ok(None)
+    }
+}
+
+/// Get the environment a built-in backend checks against: the one the uv, pixi, and current
+/// backends resolve the project at `path` to, described by [`introspect`]. The pixi backend
+/// solves the first of the `configurations` read from a conda environment file, if there is
+/// one.
+///
+/// The automatic and simulated backends have no environment to inspect and return `None`, so
+/// matching falls back to the curated mapping in `name_map`.
+#[cfg(all(feature = "backends-exec", feature = "python-parsing"))]
+pub(crate) fn builtin(
+    backend: EnvironmentBackend,
+    path: &Path,
+    environment: Option<&str>,
+    configurations: &[Configuration],
+    timeout: Option<Duration>,
+    cancel: &CancellationToken,
+) -> Result<Option<ResolvedEnvironment>, AnalysisError> {
+    if matches!(
+        backend,
+        EnvironmentBackend::Auto | EnvironmentBackend::Simulated
+    ) {
+        return Ok(None);
+    }
+    if backend == EnvironmentBackend::Pixi
+        && let Some(configuration) = configurations
+            .iter()
+            .find(|c| c.conda_environment().is_some())
+    {
+        return introspect_conda_environment(configuration, timeout, cancel).map(Some);
+    }
+    introspect(backend, path, environment, &[], timeout, cancel).map(Some)
+}
+
+/// Look up the installed versions of distributions in the backend's environment.
+///
+/// Only the current environment can be queried today; other backends return an empty map.
+/// Commands the backend runs are killed after `timeout`.
+#[cfg_attr(not(feature = "backends-exec"), allow(unused_variables))]
+pub fn installed_versions(
+    backend: &EnvironmentBackend,
+    distributions: &[String],
+    timeout: Option<Duration>,
+    cancel: &CancellationToken,
+) -> Result<HashMap<String, String>, AnalysisError> {
+    #[cfg(feature = "backends-exec")]
+    if *backend == EnvironmentBackend::Current {
+        return current::installed_versions(distributions, timeout, cancel);
+    }
+    Ok(HashMap::new())
+}
+
+/// Look up the versions of distributions the backend resolves them to: the installed ones for
+/// the current environment, and the latest ones on PyPI for the simulated backend, unless
+/// `offline` is set (or without the `backends-net` feature). Distributions whose version
+/// isn't known are left out.
+#[cfg(feature = "python-parsing")]
+#[cfg_attr(not(feature = "backends-net"), allow(unused_variables))]
+pub(crate) fn resolved_versions(
+    backend: &EnvironmentBackend,
+    distributions: &[String],
+    offline: bool,
+    timeout: Option<Duration>,
+    cancel: &CancellationToken,
+) -> Result<HashMap<String, String>, AnalysisError> {
+    #[cfg(feature = "backends-net")]
+    if *backend == EnvironmentBackend::Simulated && !offline {
+        return pypi::latest_versions(distributions, timeout, cancel);
+    }
+    installed_versions(backend, distributions, timeout, cancel)
+}
+
+/// The user's cache directory for depwise
+#[cfg(feature = "backends-net")]
+pub(crate) fn default_cache_dir() -> PathBuf {
+    let home = std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache"));
+    std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("LOCALAPPDATA").map(PathBuf::from))
+        .or(home)
+        .unwrap_or_else(std::env::temp_dir)
+        .join("depwise")
+}
+
+/// Run a command to completion and collect its output, killing it if the analysis is
+/// cancelled while it runs or it runs longer than `timeout`. A process the command leaves
+/// behind holding its output open, like a daemon it started in a new session, counts
+/// against the same timeout.
+#[cfg(any(feature = "backends-exec", feature = "backends-net"))]
+pub(crate) fn run_command(
+    command: &mut Command,
+    timeout: Option<Duration>,
+    cancel: &CancellationToken,
+    phase: Phase,
+) -> Result<Output, AnalysisError> {
+    let program = command.get_program().to_string_lossy().to_string();
+    let command_error =
+        |e: std::io::Error| AnalysisError::CommandError(program.clone(), e.to_string());
+
+    // The child leads its own process group, so the processes it starts, like the resolver
+    // `uv run` or `pixi run` starts, are stopped along with it
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(command, 0);
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(command_error)?;
+
+    // Drain the pipes while waiting so a chatty child can't block on a full pipe
+    let stdout = child.stdout.take().map(drain);
+    let stderr = child.stderr.take().map(drain);
+
+    let started = Instant::now();
+    let stopped = || {
+        if cancel.is_cancelled() {
+            Some(AnalysisError::Cancelled(phase))
+        } else {
+            timeout
+                .filter(|timeout| started.elapsed() >= *timeout)
+                .map(|timeout| {
+                    AnalysisError::CommandTimeout(program.clone(), timeout.as_secs_f64())
+                })
+        }
+    };
+    let status = loop {
+        if let Some(error) = stopped() {
+            kill_process_group(&child);
+            // Reap the child so it doesn't linger as a zombie
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(error);
+        }
+        if let Some(status) = child
+            .wait_timeout(CANCEL_POLL_INTERVAL)
+            .map_err(command_error)?
+        {
+            break status;
+        }
+    };
+
+    // The pipes only close once every process holding them has exited, which a process
+    // that left the group may never do. Its readers are left behind when time runs out.
+    let collect = |pipe: Option<Receiver<Vec<u8>>>| {
+        let Some(pipe) = pipe else {
+            return Ok(Vec::new());
+        };
+        loop {
+            if let Some(error) = stopped() {
+                kill_process_group(&child);
+                return Err(error);
+            }
+            match pipe.recv_timeout(CANCEL_POLL_INTERVAL) {
+                Ok(output) => return Ok(output),
+                Err(RecvTimeoutError::Disconnected) => return Ok(Vec::new()),
+                Err(RecvTimeoutError::Timeout) => {}
+            }
+        }
+    };
+    Ok(Output {
+        status,
+        stdout: collect(stdout)?,
+        stderr: collect(stderr)?,
+    })
+}
+
+/// Kill every process in the group a child started by `run_command` leads
+#[cfg(all(unix, any(feature = "backends-exec", feature = "backends-net")))]
+#[allow(unsafe_code)]
+fn kill_process_group(child: &Child) {
+    let Ok(group) = libc::pid_t::try_from(child.id()) else {
+        return;
+    };
+    // SAFETY: killpg only sends a signal, it doesn't touch this process's memory
+    if unsafe { libc::killpg(group, libc::SIGKILL) } != 0 {
+        debug!(
+            "Failed to kill process group {}: {}",
+            group,
+            std::io::Error::last_os_error()
+        );
+    }
+}
+
+#[cfg(all(not(unix), any(feature = "backends-exec", feature = "backends-net")))]
+fn kill_process_group(_child: &Child) {}
+
+#[cfg(any(feature = "backends-exec", feature = "backends-net"))]
+fn drain(mut pipe: impl Read + Send + 'static) -> Receiver<Vec<u8>> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut buffer = Vec::new();
+        let _ = pipe.read_to_end(&mut buffer);
+        let _ = sender.send(buffer);
+    });
+    receiver
+}
+
+#[cfg(all(test, feature = "backends-exec"))]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    #[test]
+    fn test_run_command_kills_child_on_cancel() {
+        let cancel = CancellationToken::new();
+        let timer = {
+            let cancel = cancel.clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(50));
+                cancel.cancel();
+            })
+        };
+
+        let start = Instant::now();
+        let result = run_command(
+            Command::new("sleep").arg("30"),
+            None,
+            &cancel,
+            Phase::ResolvingEnvironment,
+        );
+        timer.join().unwrap();
+
+        assert!(matches!(
+            result,
+            Err(AnalysisError::Cancelled(Phase::ResolvingEnvironment))
+        ));
+        assert!(start.elapsed() < Duration::from_secs(10));
+    }
+
+    #[test]
+    fn test_run_command_times_out() {
+        let start = Instant::now();
+        let result = run_command(
+            Command::new("sleep").arg("30"),
+            Some(Duration::from_millis(50)),
+            &CancellationToken::new(),
+            Phase::ResolvingEnvironment,
+        );
+
+        assert!(
+            matches!(&result, Err(AnalysisError::CommandTimeout(program, _)) if program == "sleep"),
+            "{:?}",
+            result
+        );
+        assert!(start.elapsed() < Duration::from_secs(10));
+    }
+
+    #[test]
+    fn test_run_command_times_out_on_escaped_processes() {
+        // The background process starts a new session, so killing the command's process
+        // group doesn't stop it, and it keeps the output pipes open after `sh` exits
+        let start = Instant::now();
+        let result = run_command(
+            Command::new("sh").args(["-c", "setsid sleep 30 &"]),
+            Some(Duration::from_millis(200)),
+            &CancellationToken::new(),
+            Phase::ResolvingEnvironment,
+        );
+
+        assert!(
+            matches!(&result, Err(AnalysisError::CommandTimeout(program, _)) if program == "sh"),
+            "{:?}",
+            result
+        );
+        assert!(start.elapsed() < Duration::from_secs(10));
+    }
+
+    #[test]
+    fn test_run_command_collects_output() -> Result<(), AnalysisError> {
+        let output = run_command(
+            Command::new("echo").arg("hello"),
+            Some(Duration::from_secs(30)),
+            &CancellationToken::new(),
+            Phase::ResolvingEnvironment,
+        )?;
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"hello\n");
+        Ok(())
+    }
+}
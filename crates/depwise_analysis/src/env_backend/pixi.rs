@@ -0,0 +1,257 @@
+use std::fs;
+use std::process::Command;
+use std::time::Duration;
+
+use pep508_rs::VersionOrUrl;
+use toml_edit::{Array, DocumentMut, InlineTable, Table, value};
+use tracing::debug;
+
+use super::manifest::{ResolvedEnvironment, run_introspection};
+use super::{DEFAULTS_CHANNEL, current_platform};
+use crate::cancel::CancellationToken;
+use crate::error::AnalysisError;
+use crate::project::{Configuration, Dependency};
+
+/// A pixi manifest installing the packages of a configuration read from a conda environment
+/// file on `platform`, solved with the channels the file lists
+pub(crate) fn conda_manifest(configuration: &Configuration, platform: &str) -> String {
+    let metadata = configuration
+        .conda_environment()
+        .cloned()
+        .unwrap_or_default();
+    let channels: Array = metadata
+        .solver_channels()
+        .iter()
+        .map(|channel| match channel.as_str() {
+            "defaults" => DEFAULTS_CHANNEL,
+            channel => channel,
+        })
+        .collect();
+
+    let mut document = DocumentMut::new();
+    let mut workspace = Table::new();
+    workspace["name"] = value(metadata.name.as_deref().unwrap_or("depwise-check"));
+    workspace["channels"] = value(channels);
+    workspace["platforms"] = value(Array::from_iter([platform]));
+    document["workspace"] = toml_edit::Item::Table(workspace);
+
+    // The interpreter is needed to inspect the environment, even if the file doesn't pin it
+    let mut conda = Table::new();
+    let python = configuration
+        .python_requirement()
+        .and_then(|spec| spec.version())
+        .map_or_else(|| "*".to_string(), ToString::to_string);
+    conda["python"] = value(python);
+    let mut pypi = Table::new();
+    for dependency in configuration.dependencies() {
+        // A requirement parsed with its direct reference, like `foo @ https://...`, is
+        // installed from the reference
+        let direct;
+        let dependency = match dependency {
+            Dependency::PyPI(requirement)
+                if matches!(requirement.version_or_url, Some(VersionOrUrl::Url(_))) =>
+            {
+                direct = Dependency::from_requirement(requirement.clone());
+                &direct
+            }
+            dependency => dependency,
+        };
+        match dependency {
+            Dependency::Conda(spec) => {
+                let version = spec
+                    .version()
+                    .map_or_else(|| "*".to_string(), ToString::to_string);
+                conda[spec.name()] = match spec.channel() {
+                    Some(channel) => {
+                        let mut table = InlineTable::new();
+                        table.insert("version", version.into());
+                        table.insert("channel", channel.into());
+                        value(table)
+                    }
+                    None => value(version),
+                };
+            }
+            Dependency::PyPI(requirement) => {
+                let version = match &requirement.version_or_url {
+                    Some(VersionOrUrl::VersionSpecifier(specifiers)) => specifiers.to_string(),
+                    // Handled above
+                    Some(VersionOrUrl::Url(_)) | None => String::new(),
+                };
+                let version = if version.is_empty() { "*" } else { &version };
+                pypi[requirement.name.as_ref()] = if requirement.extras.is_empty() {
+                    value(version)
+                } else {
+                    let mut table = InlineTable::new();
+                    table.insert("version", version.into());
+                    let extras: Array = requirement.extras.iter().map(|e| e.as_ref()).collect();
+                    table.insert("extras", extras.into());
+                    value(table)
+                };
+            }
+            dependency => match (dependency.name(), direct_reference(dependency)) {
+                (Some(name), Some(reference)) => pypi[name] = value(reference),
+                _ => debug!("Leaving {:?} out of the pixi manifest", dependency),
+            },
+        }
+    }
+    document["dependencies"] = toml_edit::Item::Table(conda);
+    if !pypi.is_empty() {
+        document["pypi-dependencies"] = toml_edit::Item::Table(pypi);
+    }
+    document.to_string()
+}
+
+/// The pixi `[pypi-dependencies]` entry installing a URL, path or git dependency, like
+/// `{ git = "https://github.com/org/repo.git", rev = "v1" }`. Only git repositories can be
+/// installed from version control.
+fn direct_reference(dependency: &Dependency) -> Option<InlineTable> {
+    let mut table = InlineTable::new();
+    match dependency {
+        Dependency::PackageUrl { url, .. } => {
+            table.insert("url", url.as_str().into());
+        }
+        Dependency::PackagePath { path, .. } => {
+            table.insert("path", path.display().to_string().into());
+        }
+        Dependency::PackageVcs { url, .. } => {
+            let repository = url.strip_prefix("git+")?;
+            let (repository, fragment) = repository.split_once('#').unwrap_or((repository, ""));
+            // The revision follows an `@` in the last path segment, as the user info of
+            // `ssh://git@github.com/...` also uses one
+            let (base, last) = repository.rsplit_once('/')?;
+            let (repository, revision) = match last.split_once('@') {
+                Some((last, revision)) => (format!("{}/{}", base, last), Some(revision)),
+                None => (repository.to_string(), None),
+            };
+            table.insert("git", repository.into());
+            if let Some(revision) = revision {
+                table.insert("rev", revision.into());
+            }
+            if let Some(subdirectory) = fragment
+                .split('&')
+                .find_map(|parameter| parameter.strip_prefix("subdirectory="))
+            {
+                table.insert("subdirectory", subdirectory.into());
+            }
+        }
+        Dependency::PyPI(_) | Dependency::Conda(_) => return None,
+    }
+    Some(table)
+}
+
+/// Describe the environment pixi solves for a configuration read from a conda environment
+/// file, by generating a manifest for it in a temporary directory and running the
+/// introspection script in its environment
+pub(crate) fn introspect_conda_environment(
+    configuration: &Configuration,
+    timeout: Option<Duration>,
+    cancel: &CancellationToken,
+) -> Result<ResolvedEnvironment, AnalysisError> {
+    let platform = current_platform().ok_or_else(|| {
+        AnalysisError::CommandError(
+            "pixi".to_string(),
+            "this platform has no conda packages".to_string(),
+        )
+    })?;
+    let directory = std::env::temp_dir().join(format!("depwise-pixi-{}", std::process::id()));
+    let manifest_path = directory.join("pixi.toml");
+    let write_error = |e: std::io::Error| {
+        AnalysisError::FileWriteError(manifest_path.display().to_string(), e.to_string())
+    };
+    fs::create_dir_all(&directory).map_err(write_error)?;
+    fs::write(&manifest_path, conda_manifest(configuration, platform)).map_err(write_error)?;
+
+    let mut command = Command::new("pixi");
+    command
+        .arg("run")
+        .arg("--manifest-path")
+        .arg(&manifest_path)
+        .arg("python");
+    let environment = run_introspection(command, configuration.name(), timeout, cancel);
+    if let Err(e) = fs::remove_dir_all(&directory) {
+        debug!("Failed to remove {}: {}", directory.display(), e);
+    }
+    environment
+}
+
+#[cfg(all(test, feature = "conda"))]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::EnvironmentBuilderSource;
+    use crate::project::extract_configurations;
+
+    #[test]
+    fn test_conda_manifest() -> Result<(), AnalysisError> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("environment.yml");
+        fs::write(
+            &path,
+            "name: align
+channels:
+  - bioconda
+  - defaults
+  - nodefaults
+dependencies:
+  - python=3.11
+  - samtools>=1.19
+  - conda-forge::numpy
+  - pip:
+    - rich[jupyter]>=13
+    - requests
+    - mytool @ git+https://github.com/org/mytool.git@v1#subdirectory=python
+    - wheelpkg @ https://example.com/wheelpkg-1.0-py3-none-any.whl
+    - -e git+ssh://git@github.com/org/vendored.git#egg=vendored
+",
+        )
+        .unwrap();
+        let configurations =
+            extract_configurations(EnvironmentBuilderSource::CondaEnvironmentYml(path), true)?;
+
+        let manifest: toml::Table =
+            toml::from_str(&conda_manifest(&configurations[0], "linux-64")).unwrap();
+        let workspace = &manifest["workspace"];
+        assert_eq!(workspace["name"].as_str(), Some("align"));
+        // nodefaults drops the defaults channel
+        assert_eq!(workspace["channels"], toml::Value::from(vec!["bioconda"]));
+        assert_eq!(workspace["platforms"], toml::Value::from(vec!["linux-64"]));
+        let conda = &manifest["dependencies"];
+        assert_eq!(conda["python"].as_str(), Some("3.11.*"));
+        assert_eq!(conda["samtools"].as_str(), Some(">=1.19"));
+        assert_eq!(conda["numpy"]["channel"].as_str(), Some("conda-forge"));
+        let pypi = &manifest["pypi-dependencies"];
+        assert_eq!(pypi["rich"]["version"].as_str(), Some(">=13"));
+        assert_eq!(pypi["requests"].as_str(), Some("*"));
+        // Direct references are installed from where they point
+        assert_eq!(
+            pypi["mytool"]["git"].as_str(),
+            Some("https://github.com/org/mytool.git")
+        );
+        assert_eq!(pypi["mytool"]["rev"].as_str(), Some("v1"));
+        assert_eq!(pypi["mytool"]["subdirectory"].as_str(), Some("python"));
+        assert_eq!(
+            pypi["wheelpkg"]["url"].as_str(),
+            Some("https://example.com/wheelpkg-1.0-py3-none-any.whl")
+        );
+        assert_eq!(
+            pypi["vendored"]["git"].as_str(),
+            Some("ssh://git@github.com/org/vendored.git")
+        );
+        assert!(pypi["vendored"].get("rev").is_none());
+
+        // Without channels, the packages come from conda-forge
+        let configuration = Configuration::new(
+            Vec::new(),
+            "environment.yml".to_string(),
+            EnvironmentBuilderSource::CondaEnvironmentYml(Path::new("environment.yml").into()),
+        );
+        let manifest: toml::Table =
+            toml::from_str(&conda_manifest(&configuration, "osx-arm64")).unwrap();
+        assert_eq!(
+            manifest["workspace"]["channels"],
+            toml::Value::from(vec!["conda-forge"])
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,244 @@
+//! Looking up the versions of distributions on PyPI, for backends that don't install them.
+//!
+//! The JSON API's `info.version` is the latest release PyPI would install, which is only a
+//! pre-release when the distribution has nothing else. Its `releases` list the files of every
+//! version, each marked when it's yanked.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use pep508_rs::pep440_rs::Version;
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use super::{default_cache_dir, http};
+use crate::cancel::{CancellationToken, Phase};
+use crate::error::AnalysisError;
+use crate::name_map::normalize_name;
+
+const PYPI_URL: &str = "https://pypi.org/pypi";
+
+/// How long the releases of a distribution are cached before they're looked up again
+const MAX_CACHE_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// A version of a distribution published on PyPI
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct Release {
+    pub(crate) version: String,
+    /// Every file of the version is yanked, so installers skip it unless it's pinned
+    pub(crate) yanked: bool,
+}
+
+/// The latest version of each distribution on PyPI. Distributions that can't be looked up,
+/// because they aren't on PyPI or it can't be reached, are left out.
+pub(crate) fn latest_versions(
+    distributions: &[String],
+    timeout: Option<Duration>,
+    cancel: &CancellationToken,
+) -> Result<HashMap<String, String>, AnalysisError> {
+    let mut versions = HashMap::new();
+    for distribution in distributions {
+        cancel.check(Phase::ResolvingEnvironment)?;
+        if versions.contains_key(distribution) {
+            continue;
+        }
+        let url = format!("{}/{}/json", PYPI_URL, distribution);
+        let body = match http::get(&url, timeout, cancel) {
+            Ok(body) => body,
+            Err(e @ AnalysisError::Cancelled(_)) => return Err(e),
+            Err(e) => {
+                debug!(
+                    "Unable to look up the latest version of {}: {}",
+                    distribution, e
+                );
+                continue;
+            }
+        };
+        if let Some(version) = latest_version(&body) {
+            versions.insert(distribution.clone(), version);
+        }
+    }
+    Ok(versions)
+}
+
+/// The version of the JSON API's response for a distribution, if it's a valid one
+fn latest_version(body: &[u8]) -> Option<String> {
+    let response: serde_json::Value = serde_json::from_slice(body).ok()?;
+    let version = response.get("info")?.get("version")?.as_str()?;
+    version.parse::<Version>().ok()?;
+    Some(version.to_string())
+}
+
+/// The releases of each distribution on PyPI, read from the cache under `cache_dir` (or the
+/// user's cache directory) while they're fresh and `reuse` is set. Releases that can't be
+/// downloaded fall back to stale cached ones, and distributions with neither, because they
+/// aren't on PyPI or it can't be reached, are left out.
+pub(crate) fn releases(
+    distributions: &[String],
+    cache_dir: Option<&Path>,
+    reuse: bool,
+    timeout: Option<Duration>,
+    cancel: &CancellationToken,
+) -> Result<HashMap<String, Vec<Release>>, AnalysisError> {
+    let directory = cache_dir
+        .map(Path::to_path_buf)
+        .unwrap_or_else(default_cache_dir)
+        .join("pypi");
+    let mut releases = HashMap::new();
+    for distribution in distributions {
+        cancel.check(Phase::ResolvingEnvironment)?;
+        if releases.contains_key(distribution) {
+            continue;
+        }
+        if let Some(found) = cached_releases(&directory, distribution, reuse, timeout, cancel)? {
+            releases.insert(distribution.clone(), found);
+        }
+    }
+    Ok(releases)
+}
+
+/// The releases of a distribution, cached or downloaded
+fn cached_releases(
+    directory: &Path,
+    distribution: &str,
+    reuse: bool,
+    timeout: Option<Duration>,
+    cancel: &CancellationToken,
+) -> Result<Option<Vec<Release>>, AnalysisError> {
+    let path = directory.join(format!("{}.json", normalize_name(distribution)));
+    let age = fs::metadata(&path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .map(|modified| modified.elapsed().unwrap_or_default());
+    let read = || -> Option<Vec<Release>> {
+        let json = fs::read(&path).ok()?;
+        serde_json::from_slice(&json).ok()
+    };
+    if reuse && age.is_some_and(|age| age < MAX_CACHE_AGE) {
+        return Ok(read());
+    }
+
+    let url = format!("{}/{}/json", PYPI_URL, distribution);
+    let found = match http::get(&url, timeout, cancel) {
+        Ok(body) => parse_releases(&body),
+        Err(e @ AnalysisError::Cancelled(_)) => return Err(e),
+        Err(e) => {
+            debug!("Unable to look up the releases of {}: {}", distribution, e);
+            return Ok(read());
+        }
+    };
+    let Some(found) = found else {
+        return Ok(None);
+    };
+    if let Err(e) = store(&path, &found) {
+        debug!("Failed to cache the releases of {}: {}", distribution, e);
+    }
+    Ok(Some(found))
+}
+
+/// Write the releases of a distribution to the cache, replacing what's there only once
+/// they're all written
+fn store(path: &Path, releases: &[Release]) -> std::io::Result<()> {
+    if let Some(directory) = path.parent() {
+        fs::create_dir_all(directory)?;
+    }
+    let partial = path.with_extension("json.partial");
+    fs::write(&partial, serde_json::to_vec(releases)?)?;
+    fs::rename(&partial, path)
+}
+
+/// The releases in the JSON API's response for a distribution, oldest first. Versions
+/// without files, which nothing can install, and invalid versions are left out.
+fn parse_releases(body: &[u8]) -> Option<Vec<Release>> {
+    let response: serde_json::Value = serde_json::from_slice(body).ok()?;
+    let mut releases: Vec<(Version, Release)> = response
+        .get("releases")?
+        .as_object()?
+        .iter()
+        .filter_map(|(version, files)| {
+            let files = files.as_array()?;
+            if files.is_empty() {
+                return None;
+            }
+            let yanked = files.iter().all(|file| {
+                file.get("yanked")
+                    .and_then(serde_json::Value::as_bool)
+                    .unwrap_or(false)
+            });
+            let parsed = version.parse::<Version>().ok()?;
+            Some((
+                parsed,
+                Release {
+                    version: version.clone(),
+                    yanked,
+                },
+            ))
+        })
+        .collect();
+    releases.sort_by(|(a, _), (b, _)| a.cmp(b));
+    Some(releases.into_iter().map(|(_, release)| release).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latest_version() {
+        let body = br#"{"info": {"name": "requests", "version": "2.32.3"}, "releases": {}}"#;
+        assert_eq!(latest_version(body).as_deref(), Some("2.32.3"));
+        assert_eq!(
+            latest_version(br#"{"info": {"version": "not a version"}}"#),
+            None
+        );
+        assert_eq!(latest_version(br#"{"message": "Not Found"}"#), None);
+    }
+
+    #[test]
+    fn test_parse_releases() {
+        let body = br#"{
+            "info": {"version": "2.0.0"},
+            "releases": {
+                "2.0.0": [{"yanked": false}, {"yanked": false}],
+                "1.10.0": [{"yanked": true}, {"yanked": false}],
+                "1.2.0": [{"yanked": true}],
+                "1.0.0": [],
+                "not a version": [{"yanked": false}]
+            }
+        }"#;
+        let release = |version: &str, yanked| Release {
+            version: version.to_string(),
+            yanked,
+        };
+        assert_eq!(
+            parse_releases(body),
+            Some(vec![
+                release("1.2.0", true),
+                release("1.10.0", false),
+                release("2.0.0", false),
+            ])
+        );
+        assert_eq!(parse_releases(br#"{"message": "Not Found"}"#), None);
+    }
+
+    #[test]
+    fn test_releases_are_read_from_the_cache() -> Result<(), AnalysisError> {
+        let dir = tempfile::tempdir().unwrap();
+        let cached = vec![Release {
+            version: "1.0".to_string(),
+            yanked: true,
+        }];
+        store(&dir.path().join("pypi").join("my-package.json"), &cached).unwrap();
+        let releases = releases(
+            &["My_Package".to_string()],
+            Some(dir.path()),
+            true,
+            None,
+            &CancellationToken::new(),
+        )?;
+        assert_eq!(releases.get("My_Package"), Some(&cached));
+        Ok(())
+    }
+}
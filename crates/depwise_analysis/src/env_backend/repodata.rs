@@ -0,0 +1,960 @@
+use std::cell::Cell;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::fs;
+use std::io::{self, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use pep508_rs::pep440_rs::Version;
+use ruzstd::decoding::StreamingDecoder;
+use serde::{Deserialize, Serialize};
+use serde_bytes::ByteBuf;
+use tracing::debug;
+
+use super::manifest::{InstalledDistribution, ResolvedEnvironment};
+use super::{DEFAULTS_CHANNEL, default_cache_dir, http};
+use crate::cancel::{CancellationToken, Phase};
+use crate::error::AnalysisError;
+use crate::matcher::ModuleIndex;
+use crate::name_map;
+use crate::project::{CondaMatchSpec, Configuration, Dependency};
+
+/// Where channels given by name are served from
+const CHANNEL_HOST: &str = "https://conda.anaconda.org";
+
+/// How long cached repodata is used before it's downloaded again
+const MAX_CACHE_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// The most compressed repodata may unpack to. It's parsed as it's unpacked, keeping only
+/// what [`PackageRecord`] holds, so this bounds the work rather than the memory.
+const MAX_UNPACKED_BYTES: u64 = 512 * 1024 * 1024;
+
+/// A package build in a channel's repodata, with only what's needed to resolve dependencies
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct PackageRecord {
+    pub(crate) name: String,
+    pub(crate) version: String,
+    #[serde(default)]
+    pub(crate) build_number: u64,
+    #[serde(default)]
+    pub(crate) depends: Vec<String>,
+    /// The digests of the package archive, in hex
+    #[serde(
+        default,
+        deserialize_with = "digest",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub(crate) sha256: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "digest",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub(crate) md5: Option<String>,
+}
+
+/// Read a digest, which `repodata.json` gives in hex and shards give as bytes
+fn digest<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Digest {
+        Hex(String),
+        Bytes(ByteBuf),
+    }
+    Ok(match Option::<Digest>::deserialize(deserializer)? {
+        Some(Digest::Hex(hex)) => Some(hex),
+        Some(Digest::Bytes(bytes)) => {
+            Some(bytes.iter().map(|byte| format!("{:02x}", byte)).collect())
+        }
+        None => None,
+    })
+}
+
+impl PackageRecord {
+    /// Order builds by version, then build number
+    fn precedence(&self, other: &Self) -> Ordering {
+        let version = match (
+            Version::from_str(&self.version),
+            Version::from_str(&other.version),
+        ) {
+            (Ok(version), Ok(other)) => version.cmp(&other),
+            _ => self.version.cmp(&other.version),
+        };
+        version.then(self.build_number.cmp(&other.build_number))
+    }
+
+    fn matches(&self, spec: &CondaMatchSpec) -> bool {
+        self.name == spec.name()
+            && spec
+                .version()
+                .is_none_or(|version| version.contains(&self.version))
+    }
+}
+
+/// `repodata.json` as served, keyed by file name. Downloads are cached in this form with
+/// everything but the fields of [`PackageRecord`] left out.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct RawRepodata {
+    #[serde(default)]
+    packages: BTreeMap<String, PackageRecord>,
+    #[serde(default, rename = "packages.conda")]
+    conda_packages: BTreeMap<String, PackageRecord>,
+}
+
+/// The packages in a subdir of a channel, by name
+#[derive(Debug, Default)]
+pub(crate) struct Repodata {
+    packages: HashMap<String, Vec<PackageRecord>>,
+    /// The shards of a sharded subdir, which packages are read from as they're looked up
+    shards: Option<Shards>,
+}
+
+/// Where the packages of a sharded subdir are, one shard per package name
+#[derive(Debug)]
+struct Shards {
+    /// The URL shard file names are appended to
+    base_url: String,
+    /// The SHA-256 of each package's shard, in hex, by package name
+    hashes: HashMap<String, String>,
+    /// The packages whose shards were read, or that have none
+    loaded: HashSet<String>,
+}
+
+/// `repodata_shards.msgpack.zst` as served, with only what's needed to find the shards
+#[derive(Debug, Deserialize)]
+struct ShardIndex {
+    #[serde(default)]
+    info: ShardIndexInfo,
+    shards: HashMap<String, ByteBuf>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ShardIndexInfo {
+    /// Where the shards are, relative to the subdir unless it's a URL
+    #[serde(default)]
+    shards_base_url: String,
+}
+
+impl Repodata {
+    /// Read the `repodata.json` of a subdir, downloaded from `url`
+    pub(crate) fn from_json(url: &str, json: &[u8]) -> Result<Self, AnalysisError> {
+        let raw: RawRepodata = serde_json::from_slice(json)
+            .map_err(|e| AnalysisError::RepodataError(url.to_string(), e.to_string()))?;
+        Ok(Self::from(raw))
+    }
+
+    /// Read the compressed shard index of the subdir at `url`. None of its packages are
+    /// read until they're looked up with `load_shard`.
+    fn from_shard_index(url: &str, index: &[u8]) -> Result<Self, AnalysisError> {
+        let index_url = format!("{}/repodata_shards.msgpack.zst", url);
+        let index: ShardIndex = rmp_serde::from_read(unzstd(&index_url, index)?)
+            .map_err(|e| AnalysisError::RepodataError(index_url.clone(), e.to_string()))?;
+        let mut base_url = match index.info.shards_base_url.as_str() {
+            "" => format!("{}/", url),
+            base_url if base_url.contains("://") => base_url.to_string(),
+            base_url => format!("{}/{}", url, base_url.trim_start_matches("./")),
+        };
+        if !base_url.ends_with('/') {
+            base_url.push('/');
+        }
+        let hashes = index
+            .shards
+            .into_iter()
+            .map(|(name, hash)| {
+                let hash = hash.iter().map(|byte| format!("{:02x}", byte)).collect();
+                (name, hash)
+            })
+            .collect();
+        Ok(Self {
+            packages: HashMap::new(),
+            shards: Some(Shards {
+                base_url,
+                hashes,
+                loaded: HashSet::new(),
+            }),
+        })
+    }
+
+    /// Read the builds of a package from its shard, once, if the subdir is sharded. Shards
+    /// are named after their hash, so a cached one is always up to date; offline, a shard
+    /// that isn't cached leaves the package out.
+    fn load_shard(
+        &mut self,
+        name: &str,
+        cache: &RepodataCache,
+        cancel: &CancellationToken,
+    ) -> Result<(), AnalysisError> {
+        let Some(shards) = &mut self.shards else {
+            return Ok(());
+        };
+        if !shards.loaded.insert(name.to_string()) {
+            return Ok(());
+        }
+        let Some(hash) = shards.hashes.get(name) else {
+            return Ok(());
+        };
+        let url = format!("{}{}.msgpack.zst", shards.base_url, hash);
+        let path = cache
+            .directory
+            .join("shards")
+            .join(format!("{}.msgpack.zst", hash));
+        let (shard, downloaded) = match fs::read(&path) {
+            Ok(shard) => (shard, false),
+            Err(_) if cache.offline => {
+                debug!("The shard of {} at {} isn't cached", name, url);
+                return Ok(());
+            }
+            Err(_) => (http::get(&url, cache.timeout, cancel)?, true),
+        };
+        let raw: RawRepodata = rmp_serde::from_read(unzstd(&url, &shard)?)
+            .map_err(|e| AnalysisError::RepodataError(url.clone(), e.to_string()))?;
+        // Only shards that could be read are cached
+        if downloaded && let Err(e) = store(&path, &shard) {
+            debug!("Failed to cache the shard at {}: {}", url, e);
+        }
+        self.add(raw);
+        Ok(())
+    }
+
+    fn add(&mut self, raw: RawRepodata) {
+        for record in raw
+            .packages
+            .into_values()
+            .chain(raw.conda_packages.into_values())
+        {
+            let builds = self.packages.entry(record.name.clone()).or_default();
+            if !builds.contains(&record) {
+                builds.push(record);
+            }
+        }
+    }
+
+    /// The newest build matching a spec, of the ones `accept` accepts
+    fn resolve(
+        &self,
+        spec: &CondaMatchSpec,
+        accept: impl Fn(&PackageRecord) -> bool,
+    ) -> Option<&PackageRecord> {
+        self.packages
+            .get(spec.name())?
+            .iter()
+            .filter(|record| record.matches(spec) && accept(record))
+            .max_by(|a, b| a.precedence(b))
+    }
+}
+
+impl From<RawRepodata> for Repodata {
+    fn from(raw: RawRepodata) -> Self {
+        let mut repodata = Self::default();
+        repodata.add(raw);
+        repodata
+    }
+}
+
+/// A reader that fails once more than a number of bytes were read from it
+struct Bounded<R> {
+    inner: R,
+    remaining: u64,
+}
+
+impl<R: Read> Read for Bounded<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.remaining = self.remaining.checked_sub(read as u64).ok_or_else(|| {
+            io::Error::other(format!(
+                "unpacks to more than {} MiB",
+                MAX_UNPACKED_BYTES / (1024 * 1024)
+            ))
+        })?;
+        Ok(read)
+    }
+}
+
+/// Unpack zstd-compressed repodata as it's read, up to `MAX_UNPACKED_BYTES`
+fn unzstd<'a>(url: &str, data: &'a [u8]) -> Result<impl Read + 'a, AnalysisError> {
+    let decoder = StreamingDecoder::new(data)
+        .map_err(|e| AnalysisError::RepodataError(url.to_string(), e.to_string()))?;
+    Ok(BufReader::new(Bounded {
+        inner: decoder,
+        remaining: MAX_UNPACKED_BYTES,
+    }))
+}
+
+/// Repodata as downloaded
+enum Download {
+    /// The index of a sharded subdir, still compressed
+    ShardIndex(Vec<u8>),
+    Repodata(RawRepodata),
+}
+
+/// Where downloaded repodata is kept between runs
+#[derive(Debug, Clone)]
+pub(crate) struct RepodataCache {
+    directory: PathBuf,
+    /// Only read what's cached, however old
+    offline: bool,
+    /// Reuse what's cached while it's fresh
+    reuse: bool,
+    timeout: Option<Duration>,
+    /// How many subdirs were downloaded, and how many read from the cache instead
+    downloads: Cell<usize>,
+    hits: Cell<usize>,
+}
+
+impl RepodataCache {
+    /// A cache under `directory`, or the user's cache directory
+    pub(crate) fn new(
+        directory: Option<&Path>,
+        offline: bool,
+        reuse: bool,
+        timeout: Option<Duration>,
+    ) -> Self {
+        let directory = match directory {
+            Some(directory) => directory.to_path_buf(),
+            None => default_cache_dir(),
+        };
+        Self {
+            directory: directory.join("repodata"),
+            offline,
+            reuse,
+            timeout,
+            downloads: Cell::new(0),
+            hits: Cell::new(0),
+        }
+    }
+
+    /// How many subdirs were downloaded so far
+    pub(crate) fn downloads(&self) -> usize {
+        self.downloads.get()
+    }
+
+    /// How many subdirs were read from the cache so far, stale ones included
+    pub(crate) fn hits(&self) -> usize {
+        self.hits.get()
+    }
+
+    fn path(&self, url: &str, extension: &str) -> PathBuf {
+        let name: String = url
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        self.directory.join(name).with_extension(extension)
+    }
+
+    /// The packages in a subdir of a channel, like `noarch` of `conda-forge`: the cached
+    /// ones while they're fresh, otherwise downloaded ones, falling back to stale cached ones
+    /// if the download fails. Offline, whatever is cached is used, and `None` is returned
+    /// when nothing is.
+    ///
+    /// A sharded subdir is cached as its shard index, and the shards of the packages looked
+    /// up in it are cached as they're downloaded.
+    pub(crate) fn load(
+        &self,
+        channel: &str,
+        subdir: &str,
+        cancel: &CancellationToken,
+    ) -> Result<Option<Repodata>, AnalysisError> {
+        let url = format!("{}/{}", channel_url(channel), subdir);
+        let json_path = self.path(&url, "json");
+        let index_path = self.path(&url, "shards.msgpack.zst");
+        // Only the form the subdir was last downloaded in is cached
+        let cached = [&index_path, &json_path].into_iter().find_map(|path| {
+            let modified = fs::metadata(path).and_then(|metadata| metadata.modified());
+            Some((path, modified.ok()?.elapsed().unwrap_or_default()))
+        });
+        let read = |path: &Path| -> Result<Option<Repodata>, AnalysisError> {
+            self.hits.set(self.hits.get() + 1);
+            let data = fs::read(path).map_err(|e| {
+                AnalysisError::FileReadError(path.display().to_string(), e.to_string())
+            })?;
+            if path == index_path {
+                Repodata::from_shard_index(&url, &data).map(Some)
+            } else {
+                Repodata::from_json(&url, &data).map(Some)
+            }
+        };
+        match cached {
+            Some((path, _)) if self.offline => return read(path),
+            Some((path, age)) if self.reuse && age < MAX_CACHE_AGE => return read(path),
+            None if self.offline => {
+                debug!(
+                    "No repodata of {} is cached, and the analysis is offline",
+                    url
+                );
+                return Ok(None);
+            }
+            _ => {}
+        }
+
+        let download = match fetch(&url, self.timeout, cancel) {
+            Ok(download) => download,
+            Err(e @ AnalysisError::Cancelled(_)) => return Err(e),
+            Err(e) if let Some((path, _)) = cached => {
+                debug!("Using the cached repodata of {}: {}", url, e);
+                return read(path);
+            }
+            Err(e) => return Err(e),
+        };
+        self.downloads.set(self.downloads.get() + 1);
+        let (repodata, stored) = match download {
+            Download::ShardIndex(index) => {
+                let repodata = Repodata::from_shard_index(&url, &index)?;
+                (repodata, self.replace(&index_path, &json_path, &index))
+            }
+            Download::Repodata(raw) => {
+                let stored = serde_json::to_vec(&raw)
+                    .map_err(io::Error::from)
+                    .and_then(|json| self.replace(&json_path, &index_path, &json));
+                (Repodata::from(raw), stored)
+            }
+        };
+        if let Err(e) = stored {
+            debug!("Failed to cache the repodata of {}: {}", url, e);
+        }
+        Ok(Some(repodata))
+    }
+
+    /// Cache the repodata of a subdir at `path`, removing the form it was cached in before
+    fn replace(&self, path: &Path, previous: &Path, data: &[u8]) -> io::Result<()> {
+        store(path, data)?;
+        match fs::remove_file(previous) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Write a file to the cache, replacing what's there only once it's all written
+fn store(path: &Path, data: &[u8]) -> io::Result<()> {
+    if let Some(directory) = path.parent() {
+        fs::create_dir_all(directory)?;
+    }
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".partial");
+    fs::write(&partial, data)?;
+    fs::rename(&partial, path)
+}
+
+/// The URL of a channel given by name, like `conda-forge`, or by URL
+fn channel_url(channel: &str) -> String {
+    match channel {
+        "defaults" => DEFAULTS_CHANNEL.to_string(),
+        channel if channel.contains("://") => channel.trim_end_matches('/').to_string(),
+        channel => format!("{}/{}", CHANNEL_HOST, channel.trim_matches('/')),
+    }
+}
+
+/// Download the repodata of the subdir at `url`: its shard index when it's sharded,
+/// otherwise `repodata.json`, zstd-compressed when the channel has it
+fn fetch(
+    url: &str,
+    timeout: Option<Duration>,
+    cancel: &CancellationToken,
+) -> Result<Download, AnalysisError> {
+    let index_url = format!("{}/repodata_shards.msgpack.zst", url);
+    match http::get(&index_url, timeout, cancel) {
+        Ok(index) => return Ok(Download::ShardIndex(index)),
+        Err(e @ AnalysisError::Cancelled(_)) => return Err(e),
+        Err(e) => debug!("Downloading the whole repodata of {}: {}", url, e),
+    }
+
+    let compressed = format!("{}/repodata.json.zst", url);
+    let raw = http::get(&compressed, timeout, cancel).and_then(|data| {
+        serde_json::from_reader(unzstd(&compressed, &data)?)
+            .map_err(|e| AnalysisError::RepodataError(compressed.clone(), e.to_string()))
+    });
+    match raw {
+        Ok(raw) => return Ok(Download::Repodata(raw)),
+        Err(e @ AnalysisError::Cancelled(_)) => return Err(e),
+        Err(e) => debug!("Downloading the uncompressed repodata instead: {}", e),
+    }
+
+    let json_url = format!("{}/repodata.json", url);
+    let json = http::get(&json_url, timeout, cancel)?;
+    serde_json::from_slice(&json)
+        .map(Download::Repodata)
+        .map_err(|e| AnalysisError::RepodataError(json_url, e.to_string()))
+}
+
+/// Describe the environment a configuration read from a conda environment file installs on
+/// `platform`, as the repodata of its channels tells: each conda package at the newest build
+/// matching its spec, and everything those depend on. A package comes from the first
+/// channel that has a build of it, like with conda's strict channel priority, and builds
+/// that depend on a version of an already picked package other than the picked one are
+/// passed over. This is no solver, so it can pick builds a solver wouldn't.
+///
+/// Packages that depend on `python` provide the modules the curated mapping expects, and
+/// the others, like compilers and C libraries, none. Returns `None` when the analysis is
+/// offline and the repodata isn't cached.
+pub(crate) fn conda_environment(
+    configuration: &Configuration,
+    platform: &str,
+    cache: &RepodataCache,
+    cancel: &CancellationToken,
+) -> Result<Option<ResolvedEnvironment>, AnalysisError> {
+    let Some(metadata) = configuration.conda_environment() else {
+        return Ok(None);
+    };
+    // The interpreter goes first, so the packages picked are built for it
+    let specs: Vec<&CondaMatchSpec> = configuration
+        .python_requirement()
+        .into_iter()
+        .chain(
+            configuration
+                .dependencies()
+                .iter()
+                .filter_map(|dependency| match dependency {
+                    Dependency::Conda(spec) => Some(spec),
+                    _ => None,
+                }),
+        )
+        .collect();
+    let mut channels = metadata.solver_channels();
+    for channel in specs.iter().filter_map(|spec| spec.channel()) {
+        if !channels.iter().any(|c| c == channel) {
+            channels.push(channel.to_string());
+        }
+    }
+
+    let mut repodata: Vec<(String, Vec<Repodata>)> = Vec::new();
+    for channel in channels {
+        let mut subdirs = Vec::new();
+        for subdir in [platform, "noarch"] {
+            cancel.check(Phase::ResolvingEnvironment)?;
+            match cache.load(&channel, subdir, cancel)? {
+                Some(packages) => subdirs.push(packages),
+                None => return Ok(None),
+            }
+        }
+        repodata.push((channel, subdirs));
+    }
+
+    let index = ModuleIndex::new();
+    let mut environment = ResolvedEnvironment::default();
+    // The name each conda package is given in the environment
+    let mut names: HashMap<String, String> = HashMap::new();
+    // The versions picked, by conda package name
+    let mut picked: HashMap<String, String> = HashMap::new();
+    let mut queue: VecDeque<CondaMatchSpec> = specs.into_iter().cloned().collect();
+    while let Some(spec) = queue.pop_front() {
+        // Virtual packages like `__glibc` describe the machine, not a package
+        if spec.is_virtual() || picked.contains_key(spec.name()) {
+            continue;
+        }
+        for (_, subdirs) in repodata
+            .iter_mut()
+            .filter(|(channel, _)| spec.channel().is_none_or(|c| c == channel))
+        {
+            for packages in subdirs {
+                packages.load_shard(spec.name(), cache, cancel)?;
+            }
+        }
+        let compatible = |record: &PackageRecord| {
+            record.depends.iter().all(|depends| {
+                let depends = CondaMatchSpec::new(depends);
+                match (picked.get(depends.name()), depends.version()) {
+                    (Some(version), Some(spec)) => spec.contains(version),
+                    _ => true,
+                }
+            })
+        };
+        let record = repodata
+            .iter()
+            .filter(|(channel, _)| spec.channel().is_none_or(|c| c == channel))
+            .find_map(|(_, subdirs)| {
+                subdirs
+                    .iter()
+                    .filter_map(|packages| packages.resolve(&spec, compatible))
+                    .max_by(|a, b| a.precedence(b))
+            });
+        let Some(record) = record else {
+            debug!("No package in the channels matches `{}`", spec.raw_spec());
+            continue;
+        };
+
+        debug!(
+            "Picked {} {} for `{}`",
+            record.name,
+            record.version,
+            spec.raw_spec()
+        );
+        picked.insert(record.name.clone(), record.version.clone());
+        if record.name == "python" {
+            environment.python_version = Some(record.version.clone());
+        }
+        let name = name_map::conda_to_pypi(&record.name)
+            .map(str::to_string)
+            .unwrap_or_else(|| name_map::normalize_name(&record.name));
+        let is_python_package = record
+            .depends
+            .iter()
+            .any(|depends| CondaMatchSpec::new(depends).name() == "python");
+        let top_level_modules = if is_python_package {
+            index.modules_for(&name)
+        } else {
+            Vec::new()
+        };
+        names.insert(record.name.clone(), name.clone());
+        let hashes = [("sha256", &record.sha256), ("md5", &record.md5)]
+            .into_iter()
+            .filter_map(|(algorithm, digest)| Some((algorithm.to_string(), digest.clone()?)))
+            .collect();
+        environment.distributions.insert(
+            name,
+            InstalledDistribution {
+                version: Some(record.version.clone()),
+                top_level_modules,
+                conda_package: Some(record.name.clone()),
+                // Conda package names for now, renamed once every package is picked
+                requires: record
+                    .depends
+                    .iter()
+                    .map(|depends| CondaMatchSpec::new(depends))
+                    .filter(|depends| !depends.is_virtual())
+                    .map(|depends| depends.name().to_string())
+                    .collect(),
+                hashes,
+            },
+        );
+        queue.extend(
+            record
+                .depends
+                .iter()
+                .map(|depends| CondaMatchSpec::new(depends)),
+        );
+    }
+    for distribution in environment.distributions.values_mut() {
+        distribution.requires = distribution
+            .requires
+            .iter()
+            .filter_map(|package| names.get(package).cloned())
+            .collect();
+        distribution.requires.sort();
+        distribution.requires.dedup();
+    }
+    Ok(Some(environment))
+}
+
+#[cfg(test)]
+mod tests {
+    use ruzstd::encoding::{CompressionLevel, compress_to_vec};
+
+    use super::*;
+    use crate::EnvironmentBuilderSource;
+    use crate::project::extract_configurations;
+
+    const LINUX_64: &str = r#"{
+  "info": {"subdir": "linux-64"},
+  "packages": {
+    "python-3.10.14-hd12c33a_0.tar.bz2": {
+      "name": "python", "version": "3.10.14", "build": "hd12c33a_0", "build_number": 0,
+      "depends": ["libgcc-ng >=12"], "size": 25000000
+    },
+    "libgcc-ng-13.2.0-h807b86a_5.tar.bz2": {
+      "name": "libgcc-ng", "version": "13.2.0", "build": "h807b86a_5", "build_number": 5,
+      "depends": ["__glibc >=2.17"]
+    }
+  },
+  "packages.conda": {
+    "python-3.12.3-hab00c5b_0.conda": {
+      "name": "python", "version": "3.12.3", "build": "hab00c5b_0", "build_number": 0,
+      "depends": ["libgcc-ng >=12"]
+    },
+    "numpy-1.26.4-py310hb13e2d6_0.conda": {
+      "name": "numpy", "version": "1.26.4", "build": "py310hb13e2d6_0", "build_number": 0,
+      "depends": ["libgcc-ng >=12", "python >=3.10,<3.11.0a0"]
+    },
+    "numpy-2.0.0-py312h22e1c76_0.conda": {
+      "name": "numpy", "version": "2.0.0", "build": "py312h22e1c76_0", "build_number": 0,
+      "depends": ["libgcc-ng >=12", "python >=3.12,<3.13.0a0"]
+    },
+    "pyyaml-6.0.1-py310h2372a71_1.conda": {
+      "name": "pyyaml", "version": "6.0.1", "build": "py310h2372a71_1", "build_number": 1,
+      "depends": ["python >=3.10,<3.11.0a0", "yaml >=0.2.5,<0.3.0a0"],
+      "md5": "d2d8a5bd4d7b7e6f4b1f4d6a9c3e2b10",
+      "sha256": "bd49c8a5ec2c2f3a3e3a8a3b1d3c1d1e6f8a1b2c3d4e5f60718293a4b5c6d7e8"
+    },
+    "yaml-0.2.5-h7f98852_2.tar.bz2": {
+      "name": "yaml", "version": "0.2.5", "build": "h7f98852_2", "build_number": 2,
+      "depends": ["libgcc-ng >=9.4.0"]
+    }
+  }
+}"#;
+
+    const NOARCH: &str = r#"{
+  "info": {"subdir": "noarch"},
+  "packages": {
+    "requests-2.32.3-pyhd8ed1ab_0.conda": {
+      "name": "requests", "version": "2.32.3", "build": "pyhd8ed1ab_0", "build_number": 0,
+      "depends": ["charset-normalizer >=2,<4", "python >=3.8", "urllib3 >=1.21.1,<3"]
+    },
+    "urllib3-2.2.2-pyhd8ed1ab_1.conda": {
+      "name": "urllib3", "version": "2.2.2", "build": "pyhd8ed1ab_1", "build_number": 1,
+      "depends": ["python >=3.8"]
+    },
+    "charset-normalizer-3.3.2-pyhd8ed1ab_0.conda": {
+      "name": "charset-normalizer", "version": "3.3.2", "build": "pyhd8ed1ab_0",
+      "build_number": 0, "depends": ["python >=3.7"]
+    }
+  }
+}"#;
+
+    /// A channel in a directory, with the fixture repodata
+    fn channel(dir: &Path) -> String {
+        let channel = dir.join("channel");
+        for (subdir, repodata) in [("linux-64", LINUX_64), ("noarch", NOARCH)] {
+            fs::create_dir_all(channel.join(subdir)).unwrap();
+            fs::write(channel.join(subdir).join("repodata.json"), repodata).unwrap();
+        }
+        format!("file://{}", channel.display())
+    }
+
+    /// A package record as shards encode it, with its digests as bytes
+    #[derive(Serialize)]
+    struct ShardRecord {
+        name: String,
+        version: String,
+        build_number: u64,
+        depends: Vec<String>,
+        sha256: Option<ByteBuf>,
+        md5: Option<ByteBuf>,
+    }
+
+    impl From<PackageRecord> for ShardRecord {
+        fn from(record: PackageRecord) -> Self {
+            let bytes = |hex: Option<String>| {
+                hex.map(|hex| {
+                    let bytes = (0..hex.len())
+                        .step_by(2)
+                        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap());
+                    ByteBuf::from(bytes.collect::<Vec<u8>>())
+                })
+            };
+            Self {
+                name: record.name,
+                version: record.version,
+                build_number: record.build_number,
+                depends: record.depends,
+                sha256: bytes(record.sha256),
+                md5: bytes(record.md5),
+            }
+        }
+    }
+
+    #[derive(Default, Serialize)]
+    struct Shard {
+        packages: BTreeMap<String, ShardRecord>,
+        #[serde(rename = "packages.conda")]
+        conda_packages: BTreeMap<String, ShardRecord>,
+    }
+
+    /// The fixture channel in a directory, sharded: each package in a shard named after a
+    /// made-up hash, and an index of them. Its index also lists a shard that isn't there.
+    fn sharded_channel(dir: &Path) -> String {
+        let channel = dir.join("sharded");
+        let mut hashes = 0..;
+        for (subdir, repodata) in [("linux-64", LINUX_64), ("noarch", NOARCH)] {
+            let raw: RawRepodata = serde_json::from_str(repodata).unwrap();
+            let mut shards: BTreeMap<String, Shard> = BTreeMap::new();
+            for (file, record) in raw.packages {
+                let shard = shards.entry(record.name.clone()).or_default();
+                shard.packages.insert(file, record.into());
+            }
+            for (file, record) in raw.conda_packages {
+                let shard = shards.entry(record.name.clone()).or_default();
+                shard.conda_packages.insert(file, record.into());
+            }
+            let directory = channel.join(subdir).join("shards");
+            fs::create_dir_all(&directory).unwrap();
+            let mut index = BTreeMap::new();
+            for ((name, shard), i) in shards.iter().zip(&mut hashes) {
+                let hash = vec![i; 32];
+                let hex: String = hash.iter().map(|byte| format!("{:02x}", byte)).collect();
+                let shard = rmp_serde::to_vec_named(shard).unwrap();
+                fs::write(
+                    directory.join(format!("{}.msgpack.zst", hex)),
+                    compress_to_vec(shard.as_slice(), CompressionLevel::Fastest),
+                )
+                .unwrap();
+                index.insert(name.clone(), ByteBuf::from(hash));
+            }
+            index.insert("scipy".to_string(), ByteBuf::from(vec![0xff; 32]));
+            #[derive(Serialize)]
+            struct Index {
+                info: BTreeMap<&'static str, &'static str>,
+                shards: BTreeMap<String, ByteBuf>,
+            }
+            let index = Index {
+                info: BTreeMap::from([("subdir", subdir), ("shards_base_url", "./shards/")]),
+                shards: index,
+            };
+            let index = rmp_serde::to_vec_named(&index).unwrap();
+            fs::write(
+                channel.join(subdir).join("repodata_shards.msgpack.zst"),
+                compress_to_vec(index.as_slice(), CompressionLevel::Fastest),
+            )
+            .unwrap();
+        }
+        format!("file://{}", channel.display())
+    }
+
+    fn configuration(dir: &Path, channel: &str) -> Result<Configuration, AnalysisError> {
+        let path = dir.join("environment.yml");
+        fs::write(
+            &path,
+            format!(
+                "channels:
+  - {}
+dependencies:
+  - python=3.10
+  - numpy
+  - pyyaml
+  - requests
+  - pip:
+    - rich
+",
+                channel
+            ),
+        )
+        .unwrap();
+        let mut configurations =
+            extract_configurations(EnvironmentBuilderSource::CondaEnvironmentYml(path), true)?;
+        Ok(configurations.remove(0))
+    }
+
+    #[test]
+    fn test_repodata() -> Result<(), AnalysisError> {
+        let repodata = Repodata::from_json("linux-64", LINUX_64.as_bytes())?;
+        let resolve = |spec: &str| {
+            repodata
+                .resolve(&CondaMatchSpec::new(spec), |_| true)
+                .map(|record| record.version.as_str())
+        };
+        assert_eq!(resolve("numpy"), Some("2.0.0"));
+        assert_eq!(resolve("numpy <2"), Some("1.26.4"));
+        assert_eq!(resolve("python=3.10"), Some("3.10.14"));
+        assert_eq!(resolve("scipy"), None);
+
+        assert!(matches!(
+            Repodata::from_json("linux-64", b"{\"packages\": []}"),
+            Err(AnalysisError::RepodataError(_, _))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_conda_environment() -> Result<(), AnalysisError> {
+        let dir = tempfile::tempdir().unwrap();
+        let channel = channel(dir.path());
+        let configuration = configuration(dir.path(), &channel)?;
+        let cancel = CancellationToken::new();
+        let cache_dir = dir.path().join("cache");
+        let cache = RepodataCache::new(Some(&cache_dir), false, true, None);
+
+        let environment = conda_environment(&configuration, "linux-64", &cache, &cancel)?.unwrap();
+        assert_eq!((cache.downloads(), cache.hits()), (2, 0));
+        assert_eq!(environment.python_version.as_deref(), Some("3.10.14"));
+        let distribution = |name: &str| environment.distribution(name).cloned().unwrap();
+        // The newest numpy is built for another Python
+        assert_eq!(distribution("numpy").version.as_deref(), Some("1.26.4"));
+        assert_eq!(distribution("numpy").top_level_modules, vec!["numpy"]);
+        // The conda package of PyYAML is named after the PyPI one
+        assert_eq!(distribution("pyyaml").top_level_modules, vec!["yaml"]);
+        assert_eq!(
+            distribution("pyyaml").conda_package.as_deref(),
+            Some("pyyaml")
+        );
+        assert_eq!(
+            distribution("pyyaml").hashes.get("md5").map(String::as_str),
+            Some("d2d8a5bd4d7b7e6f4b1f4d6a9c3e2b10")
+        );
+        // What each package depends on is given by its name in the environment
+        assert_eq!(distribution("pyyaml").requires, vec!["python", "yaml"]);
+        assert_eq!(
+            distribution("requests").requires,
+            vec!["charset-normalizer", "python", "urllib3"]
+        );
+        assert_eq!(distribution("libgcc-ng").requires, Vec::<String>::new());
+        // What the packages depend on is installed too, and C libraries provide no modules
+        assert_eq!(distribution("urllib3").version.as_deref(), Some("2.2.2"));
+        assert!(distribution("yaml").top_level_modules.is_empty());
+        assert!(distribution("libgcc-ng").top_level_modules.is_empty());
+        assert!(environment.distribution("rich").is_none());
+
+        // Offline, the cached repodata is used even once the channel is gone
+        fs::remove_dir_all(dir.path().join("channel")).unwrap();
+        let offline = RepodataCache::new(Some(&cache_dir), true, true, None);
+        let cached = conda_environment(&configuration, "linux-64", &offline, &cancel)?;
+        assert_eq!(cached, Some(environment));
+        assert_eq!((offline.downloads(), offline.hits()), (0, 2));
+
+        // And nothing is resolved without it
+        let empty = RepodataCache::new(Some(&dir.path().join("empty")), true, true, None);
+        assert_eq!(
+            conda_environment(&configuration, "linux-64", &empty, &cancel)?,
+            None
+        );
+        let online = RepodataCache::new(Some(&dir.path().join("empty")), false, true, None);
+        assert!(matches!(
+            conda_environment(&configuration, "linux-64", &online, &cancel),
+            Err(AnalysisError::FetchError(_, _))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_sharded_repodata() -> Result<(), AnalysisError> {
+        let dir = tempfile::tempdir().unwrap();
+        let expected = {
+            let configuration = configuration(dir.path(), &channel(dir.path()))?;
+            let cache = RepodataCache::new(Some(&dir.path().join("plain")), false, true, None);
+            conda_environment(
+                &configuration,
+                "linux-64",
+                &cache,
+                &CancellationToken::new(),
+            )?
+        };
+
+        let configuration = configuration(dir.path(), &sharded_channel(dir.path()))?;
+        let cancel = CancellationToken::new();
+        let cache_dir = dir.path().join("cache");
+        let cache = RepodataCache::new(Some(&cache_dir), false, true, None);
+        let environment = conda_environment(&configuration, "linux-64", &cache, &cancel)?;
+        assert!(environment.is_some());
+        // Including the digests, which shards give as bytes
+        assert_eq!(environment, expected);
+        assert_eq!((cache.downloads(), cache.hits()), (2, 0));
+        // Only the shards of the packages looked up were downloaded, not the one for scipy
+        let shards = fs::read_dir(cache_dir.join("repodata").join("shards")).unwrap();
+        assert_eq!(shards.count(), 8);
+
+        // Offline, the cached index and shards are used once the channel is gone
+        fs::remove_dir_all(dir.path().join("sharded")).unwrap();
+        let offline = RepodataCache::new(Some(&cache_dir), true, true, None);
+        let cached = conda_environment(&configuration, "linux-64", &offline, &cancel)?;
+        assert_eq!(cached, expected);
+        assert_eq!((offline.downloads(), offline.hits()), (0, 2));
+        Ok(())
+    }
+
+    #[test]
+    fn test_unpacking_is_bounded() {
+        let json = compress_to_vec(LINUX_64.as_bytes(), CompressionLevel::Fastest);
+        let mut unpacked = Vec::new();
+        unzstd("linux-64", &json)
+            .unwrap()
+            .read_to_end(&mut unpacked)
+            .unwrap();
+        assert_eq!(unpacked, LINUX_64.as_bytes());
+
+        let mut reader = Bounded {
+            inner: LINUX_64.as_bytes(),
+            remaining: 100,
+        };
+        let error = reader.read_to_end(&mut Vec::new()).unwrap_err();
+        assert!(error.to_string().starts_with("unpacks to more than"));
+    }
+}
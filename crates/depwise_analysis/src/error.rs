@@ -1,20 +1,112 @@
+use std::fmt;
+use std::path::Path;
+
 use pep508_rs::Pep508Error;
 use thiserror::Error;
 
+use crate::cancel::Phase;
+#[cfg(feature = "backends-exec")]
+use crate::env_backend::ResolutionFailure;
+use crate::finding::Location;
+
+/// A dependency declaration that couldn't be parsed. The rest of the file is still parsed,
+/// without it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ParseDiagnostic {
+    pub location: Location,
+    /// The declaration as written
+    pub text: String,
+    pub message: String,
+}
+
+impl ParseDiagnostic {
+    pub fn new(path: &Path, line: usize, text: &str, message: String) -> Self {
+        Self {
+            location: Location::new(path.to_path_buf(), line),
+            text: text.to_string(),
+            message,
+        }
+    }
+}
+
+impl fmt::Display for ParseDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: `{}`: {}", self.location, self.text, self.message)
+    }
+}
+
+fn display_diagnostics(diagnostics: &[ParseDiagnostic]) -> String {
+    diagnostics
+        .iter()
+        .map(ParseDiagnostic::to_string)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
 #[derive(Error, Debug)]
 pub enum AnalysisError {
     #[error("Failed to parse file: {0}. Error reading line {1} column {2}")]
     ParseFileError(String, String, String),
     #[error("Failed to parse pyproject.toml: {0}")]
     PyProjectTomlError(String),
+    #[error("Invalid depwise settings in {0}: {1}")]
+    ConfigError(String, String),
     #[error("Unsupported project format: {0}")]
     UnsupportedProjectFormat(String),
+    #[error("{0} needs depwise-analysis to be built with the `{1}` feature")]
+    FeatureDisabled(String, &'static str),
     #[error("Failed to read file {0}: {1}")]
     FileReadError(String, String),
+    #[error("Failed to write file {0}: {1}")]
+    FileWriteError(String, String),
     #[error("Failed to parse dependency {0}")]
     DependencyParseError(String),
     #[error("No project or requirements file could be automatically discovered in {0}")]
     NoProjectOrRequirementsFile(String),
+    #[error("Analysis was cancelled while {0}")]
+    Cancelled(Phase),
+    #[error("Failed to run {0}: {1}")]
+    CommandError(String, String),
+    #[error("{0} did not finish within {1}s and was stopped")]
+    CommandTimeout(String, f64),
+    #[error("{0} did not finish resolving {1} within {2}s and was stopped")]
+    ResolutionTimeout(String, String, f64),
+    #[cfg(feature = "backends-exec")]
+    #[error("{0}")]
+    ResolutionFailed(Box<ResolutionFailure>),
+    #[error("Failed to fetch {0}: {1}")]
+    FetchError(String, String),
+    #[error("Invalid repodata from {0}: {1}")]
+    RepodataError(String, String),
+    #[error("Failed to parse conda environment {0}: {1}")]
+    CondaEnvironmentError(String, String),
+    #[error("Failed to parse pixi.toml: {0}")]
+    PixiTomlError(String),
+    #[error("Failed to parse poetry lock file {0}: {1}")]
+    PoetryLockError(String, String),
+    #[error("Failed to parse the inline script metadata of {0}: {1}")]
+    InlineScriptError(String, String),
+    #[error("Failed to parse package metadata {0}: {1}")]
+    CoreMetadataError(String, String),
+    #[error("Invalid environment manifest: {0}")]
+    EnvironmentManifestError(String),
+    #[error("Invalid analysis state: {0}")]
+    StateError(String),
+    #[error("Invalid name map {0}: {1}")]
+    NameMapError(String, String),
+    #[error("No configuration named {name}, the available ones are: {available}", name = .0, available = .1.join(", "))]
+    UnknownConfiguration(String, Vec<String>),
+    #[error("Invalid Python version {0}, expected a version like 3.11")]
+    InvalidPythonVersion(String),
+    #[error("Unknown rule {0}, expected a code like DW404 or a name like unavailable-version")]
+    UnknownRule(String),
+    #[error("Failed to parse dependency declarations:\n{}", display_diagnostics(.0))]
+    InvalidDeclarations(Vec<ParseDiagnostic>),
+    #[error(
+        "{0} declares more than {1} requirements with the files it includes; check its `-r` includes or raise the limit"
+    )]
+    TooManyRequirements(String, usize),
 }
 
 impl From<Pep508Error> for AnalysisError {
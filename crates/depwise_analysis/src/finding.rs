@@ -0,0 +1,371 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::error::AnalysisError;
+use crate::hash::fnv1a;
+use crate::name_map::normalize_name;
+
+/// How serious a finding is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Info => write!(f, "info"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// The check that produced a finding.
+///
+/// Rule codes are grouped by what they look at: `DW1xx` rules compare imports against the
+/// declared dependencies, `DW2xx` rules look at how imports are used within a file, and
+/// `DW3xx` rules look at how the dependencies and packages are declared, and `DW4xx` rules
+/// compare the declared dependencies against the environment they are installed in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub enum Rule {
+    /// An import isn't provided by the standard library, the project, or a declared dependency
+    MissingDependency,
+    /// A declared dependency is never imported
+    UnusedDependency,
+    /// A dependency only installed on some platforms is imported unconditionally
+    UnguardedPlatformImport,
+    /// A dependency imported only on some platforms is declared without a platform marker
+    MissingPlatformMarker,
+    /// A runtime dependency is only imported by scripts and `__main__` blocks
+    ScriptOnlyDependency,
+    /// An import is only installed through an extra of another declared dependency
+    ImportedThroughExtra,
+    /// An import uses a part of the standard library the target Python version removed
+    RemovedStdlibImport,
+    /// A backport is declared for a part of the standard library every supported Python has
+    ObsoleteBackport,
+    /// A part of the standard library some supported Pythons don't have is imported with a
+    /// fallback to a backport that isn't declared
+    MissingBackport,
+    /// An imported name is never used in the file that imports it
+    UnusedImport,
+    /// A required dependency is declared again in an extra
+    DuplicateDependency,
+    /// Two extras declare the same package with different specifiers
+    ConflictingExtraSpecifiers,
+    /// Every dependency of an extra is also in another, bigger extra
+    RedundantExtra,
+    /// A dependency declaration can't be parsed, so it was skipped
+    InvalidDependency,
+    /// The packages the build backend is configured with don't match the packages on disk
+    PackageLayoutMismatch,
+    /// A package is declared both as a conda package and as a PyPI package of one environment
+    DuplicateProvision,
+    /// An entry point refers to a module or attribute the project doesn't have
+    BrokenEntryPoint,
+    /// An optional dependency or a feature is declared, but no extra or environment installs it
+    UnreachableDependency,
+    /// The installed version of a dependency doesn't satisfy its declared specifier
+    InstalledVersionMismatch,
+    /// A declared dependency isn't installed in the environment
+    DependencyNotInstalled,
+    /// The backend's resolver can't install a declared dependency
+    UnresolvableDependency,
+    /// A pinned version of a dependency isn't on the index or is yanked, or no release
+    /// matches its specifier
+    UnavailableVersion,
+}
+
+impl Rule {
+    /// Every rule, in the order of their codes
+    pub const ALL: [Rule; 22] = [
+        Rule::MissingDependency,
+        Rule::UnusedDependency,
+        Rule::UnguardedPlatformImport,
+        Rule::MissingPlatformMarker,
+        Rule::ScriptOnlyDependency,
+        Rule::ImportedThroughExtra,
+        Rule::RemovedStdlibImport,
+        Rule::ObsoleteBackport,
+        Rule::MissingBackport,
+        Rule::UnusedImport,
+        Rule::DuplicateDependency,
+        Rule::ConflictingExtraSpecifiers,
+        Rule::RedundantExtra,
+        Rule::InvalidDependency,
+        Rule::PackageLayoutMismatch,
+        Rule::DuplicateProvision,
+        Rule::BrokenEntryPoint,
+        Rule::UnreachableDependency,
+        Rule::InstalledVersionMismatch,
+        Rule::DependencyNotInstalled,
+        Rule::UnresolvableDependency,
+        Rule::UnavailableVersion,
+    ];
+
+    /// The stable code for the rule
+    pub fn code(&self) -> &'static str {
+        match self {
+            Rule::MissingDependency => "DW101",
+            Rule::UnusedDependency => "DW102",
+            Rule::UnguardedPlatformImport => "DW103",
+            Rule::MissingPlatformMarker => "DW104",
+            Rule::ScriptOnlyDependency => "DW105",
+            Rule::ImportedThroughExtra => "DW106",
+            Rule::RemovedStdlibImport => "DW107",
+            Rule::ObsoleteBackport => "DW108",
+            Rule::MissingBackport => "DW109",
+            Rule::UnusedImport => "DW201",
+            Rule::DuplicateDependency => "DW301",
+            Rule::ConflictingExtraSpecifiers => "DW302",
+            Rule::RedundantExtra => "DW303",
+            Rule::InvalidDependency => "DW304",
+            Rule::PackageLayoutMismatch => "DW305",
+            Rule::DuplicateProvision => "DW306",
+            Rule::BrokenEntryPoint => "DW307",
+            Rule::UnreachableDependency => "DW308",
+            Rule::InstalledVersionMismatch => "DW401",
+            Rule::DependencyNotInstalled => "DW402",
+            Rule::UnresolvableDependency => "DW403",
+            Rule::UnavailableVersion => "DW404",
+        }
+    }
+
+    /// The human readable name of the rule
+    pub fn name(&self) -> &'static str {
+        match self {
+            Rule::MissingDependency => "missing-dependency",
+            Rule::UnusedDependency => "unused-dependency",
+            Rule::UnguardedPlatformImport => "unguarded-platform-import",
+            Rule::MissingPlatformMarker => "missing-platform-marker",
+            Rule::ScriptOnlyDependency => "script-only-dependency",
+            Rule::ImportedThroughExtra => "imported-through-extra",
+            Rule::RemovedStdlibImport => "removed-stdlib-import",
+            Rule::ObsoleteBackport => "obsolete-backport",
+            Rule::MissingBackport => "missing-backport",
+            Rule::UnusedImport => "unused-import",
+            Rule::DuplicateDependency => "duplicate-dependency",
+            Rule::ConflictingExtraSpecifiers => "conflicting-extra-specifiers",
+            Rule::RedundantExtra => "redundant-extra",
+            Rule::InvalidDependency => "invalid-dependency",
+            Rule::PackageLayoutMismatch => "package-layout-mismatch",
+            Rule::DuplicateProvision => "duplicate-provision",
+            Rule::BrokenEntryPoint => "broken-entry-point",
+            Rule::UnreachableDependency => "unreachable-dependency",
+            Rule::InstalledVersionMismatch => "installed-version-mismatch",
+            Rule::DependencyNotInstalled => "dependency-not-installed",
+            Rule::UnresolvableDependency => "unresolvable-dependency",
+            Rule::UnavailableVersion => "unavailable-version",
+        }
+    }
+
+    /// The severity findings for the rule are reported with
+    pub fn severity(&self) -> Severity {
+        match self {
+            Rule::MissingDependency => Severity::Error,
+            Rule::UnusedDependency => Severity::Warning,
+            Rule::UnguardedPlatformImport => Severity::Error,
+            Rule::MissingPlatformMarker => Severity::Info,
+            Rule::ScriptOnlyDependency => Severity::Info,
+            Rule::ImportedThroughExtra => Severity::Warning,
+            Rule::RemovedStdlibImport => Severity::Error,
+            Rule::ObsoleteBackport => Severity::Info,
+            Rule::MissingBackport => Severity::Warning,
+            Rule::UnusedImport => Severity::Warning,
+            Rule::DuplicateDependency => Severity::Warning,
+            Rule::ConflictingExtraSpecifiers => Severity::Warning,
+            Rule::RedundantExtra => Severity::Info,
+            Rule::InvalidDependency => Severity::Warning,
+            Rule::PackageLayoutMismatch => Severity::Info,
+            Rule::DuplicateProvision => Severity::Warning,
+            Rule::BrokenEntryPoint => Severity::Error,
+            Rule::UnreachableDependency => Severity::Warning,
+            Rule::InstalledVersionMismatch => Severity::Warning,
+            Rule::DependencyNotInstalled => Severity::Warning,
+            Rule::UnresolvableDependency => Severity::Error,
+            Rule::UnavailableVersion => Severity::Warning,
+        }
+    }
+}
+
+impl FromStr for Rule {
+    type Err = AnalysisError;
+
+    /// Parse a rule from its code, like `DW404`, or its name, like `unavailable-version`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        Rule::ALL
+            .into_iter()
+            .find(|rule| rule.code().eq_ignore_ascii_case(s) || rule.name() == s)
+            .ok_or_else(|| AnalysisError::UnknownRule(s.to_string()))
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.code(), self.name())
+    }
+}
+
+/// A position in a file
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub struct Location {
+    pub path: PathBuf,
+    pub line: usize,
+}
+
+impl Location {
+    pub fn new(path: PathBuf, line: usize) -> Self {
+        Self { path, line }
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.path.display(), self.line)
+    }
+}
+
+/// A problem found while analyzing a project
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Finding {
+    pub rule: Rule,
+    pub severity: Severity,
+    /// The module or package the finding is about
+    pub subject: String,
+    pub message: String,
+    pub location: Option<Location>,
+    /// Other places the finding is about, like the declaration of a dependency that is
+    /// imported where it isn't installed
+    pub related: Vec<Location>,
+    /// The name of the configuration the finding was reported for, if it is specific to one
+    pub configuration: Option<String>,
+    /// Why the finding was reported, for the rules that explain themselves
+    pub explanation: Option<Explanation>,
+}
+
+/// The reasoning behind a finding: where its subject is imported and what was checked
+/// before reporting it
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Explanation {
+    /// Every place the subject is imported, empty when nothing imports it
+    pub import_sites: Vec<Location>,
+    /// What was checked, one statement per entry
+    pub reasons: Vec<String>,
+}
+
+impl Explanation {
+    pub fn new(import_sites: Vec<Location>, reasons: Vec<String>) -> Self {
+        Self {
+            import_sites,
+            reasons,
+        }
+    }
+}
+
+impl Finding {
+    /// Create a finding with the rule's default severity
+    pub fn new(rule: Rule, subject: &str, message: String, location: Option<Location>) -> Self {
+        Self {
+            rule,
+            severity: rule.severity(),
+            subject: subject.to_string(),
+            message,
+            location,
+            related: Vec::new(),
+            configuration: None,
+            explanation: None,
+        }
+    }
+
+    /// Attribute the finding to a configuration of dependencies
+    pub fn with_configuration(mut self, configuration: &str) -> Self {
+        self.configuration = Some(configuration.to_string());
+        self
+    }
+
+    /// Point the finding at another place it's about as well
+    pub fn with_related(mut self, location: Location) -> Self {
+        self.related.push(location);
+        self
+    }
+
+    /// Attach the reasoning behind the finding
+    pub fn with_explanation(mut self, explanation: Explanation) -> Self {
+        self.explanation = Some(explanation);
+        self
+    }
+
+    /// A stable identifier for the finding, to recognize it across runs: a hash of its rule,
+    /// the normalized name of its subject, and its path relative to the project `root`, or
+    /// the configuration it was reported for when it has no location. Line numbers are left
+    /// out, so the fingerprint survives edits elsewhere in the file.
+    pub fn fingerprint(&self, root: &Path) -> String {
+        let absolute = |path: &Path| std::path::absolute(path).unwrap_or_else(|_| path.into());
+        let path = match &self.location {
+            Some(location) => location
+                .path
+                .strip_prefix(root)
+                .map(Path::to_path_buf)
+                .or_else(|_| {
+                    absolute(&location.path)
+                        .strip_prefix(absolute(root))
+                        .map(Path::to_path_buf)
+                })
+                .unwrap_or_else(|_| location.path.clone())
+                .to_string_lossy()
+                .replace('\\', "/"),
+            None => self.configuration.clone().unwrap_or_default(),
+        };
+        let parts = [self.rule.code(), &normalize_name(&self.subject), &path];
+        let hash = fnv1a(parts.iter().flat_map(|part| part.bytes().chain([0])));
+        format!("{:016x}", hash)
+    }
+
+    /// What findings are ordered by, see [`sort_findings`]
+    fn sort_key(&self) -> (Rule, String, Option<&Path>, usize, Option<&str>, &str) {
+        (
+            self.rule,
+            normalize_name(&self.subject),
+            self.location
+                .as_ref()
+                .map(|location| location.path.as_path()),
+            self.location.as_ref().map_or(0, |location| location.line),
+            self.configuration.as_deref(),
+            &self.message,
+        )
+    }
+}
+
+/// Sort findings into the order they're reported in: by rule code, then by the normalized
+/// name of their subject, then by path and line, findings without a location first. The
+/// configuration and the message break any remaining ties, so the order never depends on
+/// the order files were found or parsed in.
+pub fn sort_findings(findings: &mut [Finding]) {
+    findings.sort_by(|a, b| a.sort_key().cmp(&b.sort_key()));
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(location) = &self.location {
+            write!(f, "{}: ", location)?;
+        }
+        write!(
+            f,
+            "{} {} [{}] {}",
+            self.severity,
+            self.rule.code(),
+            self.rule.name(),
+            self.message
+        )
+    }
+}
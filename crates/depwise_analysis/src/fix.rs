@@ -0,0 +1,572 @@
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use pep508_rs::pep440_rs::Version;
+
+use crate::cancel::CancellationToken;
+use crate::error::AnalysisError;
+use crate::finding::Rule;
+use crate::name_map::normalize_name;
+use crate::project;
+use crate::{
+    AnalysisReport, EnvironmentBackend, EnvironmentBuilderSource, MissingImport, env_backend,
+};
+
+/// The dependency group runtime dependencies only scripts import are moved to
+const SCRIPT_GROUP: &str = "dev";
+
+/// The changes made (or that would be made in a dry run) to a dependency file
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct FileFix {
+    /// The file that was edited
+    pub path: PathBuf,
+    /// The declarations that were removed, as written in the file
+    pub removed: Vec<String>,
+    /// The requirements that were added
+    pub added: Vec<String>,
+    /// Packages that were requested to be removed but couldn't be confidently edited
+    pub skipped: Vec<String>,
+    /// The new content of the file
+    pub content: String,
+}
+
+/// A replacement of a byte range of a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct TextEdit {
+    pub path: PathBuf,
+    /// The bytes of the file as it was analyzed that are replaced, empty for an insertion
+    pub range: Range<usize>,
+    pub replacement: String,
+}
+
+/// The edits that fix a finding, for tools that apply fixes themselves
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct SuggestedFix {
+    /// What the fix does, like "Remove `flask` from requirements.txt"
+    pub description: String,
+    /// Edits to the file as it was analyzed, which never overlap
+    pub edits: Vec<TextEdit>,
+}
+
+/// How a requirement added for a missing import is pinned to the version in use
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum PinStyle {
+    /// The version itself, `==1.2.3`
+    Exact,
+    /// Releases compatible with its minor version, `~=1.2`
+    Compatible,
+    /// The version or any later one, `>=1.2.3`
+    #[default]
+    Minimum,
+}
+
+impl PinStyle {
+    /// The specifier pinning a requirement to `version` in this style, if it's a valid
+    /// version. Its local segment, like `+cu121`, is dropped since an index never serves it,
+    /// and a pre-release or development segment is only kept when the version has one, so
+    /// a stable version never lets a pre-release in.
+    pub fn specifier(self, version: &str) -> Option<String> {
+        let version = version.trim().parse::<Version>().ok()?.without_local();
+        Some(match self {
+            PinStyle::Exact => format!("=={}", version),
+            PinStyle::Minimum => format!(">={}", version),
+            PinStyle::Compatible => {
+                // `~=` needs at least two release numbers
+                let release = version.release();
+                let minor = [
+                    release.first().copied().unwrap_or(0),
+                    release.get(1).copied().unwrap_or(0),
+                ];
+                format!("~={}", version.clone().with_release(minor).with_post(None))
+            }
+        })
+    }
+}
+
+/// Remove unused dependencies from the file they were declared in.
+///
+/// Only `requirements.txt` and `pyproject.toml` sources are supported. Declarations that
+/// can't be edited confidently (URL and path dependencies, entries in included files, or
+/// anything that isn't a plain requirement string) are left untouched and reported as
+/// skipped. When `dry_run` is set the file is not written.
+pub fn remove_unused_dependencies(
+    source: &EnvironmentBuilderSource,
+    unused: &[String],
+    dry_run: bool,
+) -> Result<FileFix, AnalysisError> {
+    let (path, content) = read_editable_source(source)?;
+
+    let (new_content, removed) = match source {
+        EnvironmentBuilderSource::PyProjectToml(_) => {
+            project::pyprojecttoml::remove_dependencies(&content, unused)?
+        }
+        _ => project::requirementstxt::remove_dependencies(&content, unused),
+    };
+
+    let skipped = unused
+        .iter()
+        .filter(|name| {
+            let name = normalize_name(name);
+            !removed
+                .iter()
+                .any(|(removed_name, _)| *removed_name == name)
+        })
+        .cloned()
+        .collect();
+
+    if !dry_run && !removed.is_empty() {
+        write_source(path, &new_content)?;
+    }
+
+    Ok(FileFix {
+        path: path.clone(),
+        removed: removed
+            .into_iter()
+            .map(|(_, declaration)| declaration)
+            .collect(),
+        added: Vec::new(),
+        skipped,
+        content: new_content,
+    })
+}
+
+/// Build the requirements that would declare the missing imports of an analysis.
+///
+/// Each requirement names the distribution expected to provide the import. With a `pin`
+/// style, the requirement is pinned to the version the backend resolves the distribution
+/// to: the installed one, or for the simulated backend the latest one on PyPI, which isn't
+/// looked up when `offline` is set. Without one, an installed version the backend can
+/// report is used as a lower bound (`name>=version`). Optional imports, those guarded by an `ImportError` handler,
+/// are only included when `include_optional` is set. Imports only made by an extra's code
+/// belong in that extra, and imports made by a hatch environment's code belong in the
+/// environment, so both are left out. The requirements are in the order the findings for
+/// the imports are listed, by module. Commands the backend runs are killed after `timeout`.
+pub fn missing_requirements(
+    analysis: &AnalysisReport,
+    backend: &EnvironmentBackend,
+    include_optional: bool,
+    pin: Option<PinStyle>,
+    offline: bool,
+    timeout: Option<Duration>,
+    cancel: &CancellationToken,
+) -> Result<Vec<String>, AnalysisError> {
+    Ok(requirements_for_missing(
+        analysis,
+        backend,
+        include_optional,
+        pin,
+        offline,
+        timeout,
+        cancel,
+    )?
+    .into_iter()
+    .map(|(_, requirement)| requirement)
+    .collect())
+}
+
+/// The requirements `missing_requirements` builds, with the missing import each declares
+fn requirements_for_missing<'a>(
+    analysis: &'a AnalysisReport,
+    backend: &EnvironmentBackend,
+    include_optional: bool,
+    pin: Option<PinStyle>,
+    offline: bool,
+    timeout: Option<Duration>,
+    cancel: &CancellationToken,
+) -> Result<Vec<(&'a MissingImport, String)>, AnalysisError> {
+    let mut missing: Vec<&MissingImport> = analysis
+        .missing_imports()
+        .iter()
+        .filter(|missing| missing.extra().is_none() && missing.environment().is_none())
+        .filter(|missing| include_optional || !missing.is_optional())
+        .collect();
+    missing.sort_by(|a, b| (a.module(), a.location()).cmp(&(b.module(), b.location())));
+    let distributions: Vec<String> = missing
+        .iter()
+        .map(|missing| missing.distribution().to_string())
+        .collect();
+    let versions = match pin {
+        Some(_) => {
+            env_backend::resolved_versions(backend, &distributions, offline, timeout, cancel)?
+        }
+        None => env_backend::installed_versions(backend, &distributions, timeout, cancel)?,
+    };
+    let style = pin.unwrap_or_default();
+
+    Ok(missing
+        .into_iter()
+        .zip(distributions)
+        .map(|(missing, distribution)| {
+            let specifier = versions
+                .get(&distribution)
+                .and_then(|version| style.specifier(version));
+            let requirement = match specifier {
+                Some(specifier) => format!("{}{}", distribution, specifier),
+                None => distribution,
+            };
+            (missing, requirement)
+        })
+        .collect())
+}
+
+/// Add requirements to the file the project's dependencies are declared in.
+///
+/// Only `requirements.txt` and `pyproject.toml` sources are supported. Requirements are
+/// appended to the end of a requirements file, or to the `[project] dependencies` array of
+/// a `pyproject.toml`. When `dry_run` is set the file is not written.
+pub fn add_missing_dependencies(
+    source: &EnvironmentBuilderSource,
+    requirements: &[String],
+    dry_run: bool,
+) -> Result<FileFix, AnalysisError> {
+    let (path, content) = read_editable_source(source)?;
+
+    let new_content = match source {
+        EnvironmentBuilderSource::PyProjectToml(_) => {
+            project::pyprojecttoml::add_dependencies(&content, requirements)?
+        }
+        _ => project::requirementstxt::add_dependencies(&content, requirements),
+    };
+
+    if !dry_run && !requirements.is_empty() {
+        write_source(path, &new_content)?;
+    }
+
+    Ok(FileFix {
+        path: path.clone(),
+        removed: Vec::new(),
+        added: requirements.to_vec(),
+        skipped: Vec::new(),
+        content: new_content,
+    })
+}
+
+/// Suggest the edits that fix each finding of an analysis, in the order of
+/// [`AnalysisReport::findings`].
+///
+/// Unused dependencies are removed and missing ones added the way
+/// [`remove_unused_dependencies`] and [`add_missing_dependencies`] would, with the
+/// requirements [`missing_requirements`] builds from the same arguments. Applying the edits
+/// of every unused dependency, or of every missing one, reproduces the file those functions
+/// write. When the edits of single findings wouldn't combine into it, the first finding
+/// gets one edit making the whole change and the others get none. Runtime dependencies
+/// only scripts import are moved to the `dev` group of a pyproject.toml. Findings nothing
+/// can fix, and every finding of a source that can't be edited, get `None`.
+pub fn suggested_fixes(
+    analysis: &AnalysisReport,
+    backend: &EnvironmentBackend,
+    include_optional: bool,
+    pin: Option<PinStyle>,
+    offline: bool,
+    timeout: Option<Duration>,
+    cancel: &CancellationToken,
+) -> Result<Vec<Option<SuggestedFix>>, AnalysisError> {
+    let findings = analysis.findings();
+    let mut fixes = vec![None; findings.len()];
+    let Some(source) = analysis.source() else {
+        return Ok(fixes);
+    };
+    let (path, content) = match read_editable_source(source) {
+        Ok(editable) => editable,
+        Err(AnalysisError::UnsupportedProjectFormat(_)) => return Ok(fixes),
+        Err(e) => return Err(e),
+    };
+    let is_pyproject = matches!(source, EnvironmentBuilderSource::PyProjectToml(_));
+    let file_name = path
+        .file_name()
+        .unwrap_or(path.as_os_str())
+        .to_string_lossy();
+    let remove = |names: &[String]| match source {
+        EnvironmentBuilderSource::PyProjectToml(_) => {
+            project::pyprojecttoml::remove_dependencies(&content, names)
+        }
+        _ => Ok(project::requirementstxt::remove_dependencies(
+            &content, names,
+        )),
+    };
+    let add = |requirements: &[String]| match source {
+        EnvironmentBuilderSource::PyProjectToml(_) => {
+            project::pyprojecttoml::add_dependencies(&content, requirements)
+        }
+        _ => Ok(project::requirementstxt::add_dependencies(
+            &content,
+            requirements,
+        )),
+    };
+
+    // Unused dependencies, on the first unused finding for each
+    let (removed_content, removed) = remove(analysis.unused_dependencies())?;
+    let mut removals = Vec::new();
+    for (name, declaration) in &removed {
+        let finding = findings.iter().enumerate().position(|(i, finding)| {
+            finding.rule == Rule::UnusedDependency
+                && normalize_name(&finding.subject) == *name
+                && !removals.iter().any(|(j, _, _)| *j == i)
+        });
+        let (single, _) = remove(std::slice::from_ref(name))?;
+        removals.push((
+            finding.unwrap_or(usize::MAX),
+            edit_between(path, &content, &single),
+            declaration.clone(),
+        ));
+    }
+    attach(
+        &mut fixes,
+        removals,
+        &content,
+        &removed_content,
+        path,
+        |declarations| format!("Remove {} from {}", declarations, file_name),
+    );
+
+    // Missing dependencies, on the finding for each missing import
+    let requirements = requirements_for_missing(
+        analysis,
+        backend,
+        include_optional,
+        pin,
+        offline,
+        timeout,
+        cancel,
+    )?;
+    let all: Vec<String> = requirements
+        .iter()
+        .map(|(_, requirement)| requirement.clone())
+        .collect();
+    let added_content = add(&all)?;
+    let mut additions = Vec::new();
+    for (missing, requirement) in &requirements {
+        let finding = findings.iter().enumerate().position(|(i, finding)| {
+            finding.rule == Rule::MissingDependency
+                && finding.subject == missing.module()
+                && finding.location.as_ref() == Some(missing.location())
+                && !additions.iter().any(|(j, _, _)| *j == i)
+        });
+        let single = add(std::slice::from_ref(requirement))?;
+        additions.push((
+            finding.unwrap_or(usize::MAX),
+            edit_between(path, &content, &single),
+            requirement.clone(),
+        ));
+    }
+    attach(
+        &mut fixes,
+        additions,
+        &content,
+        &added_content,
+        path,
+        |requirements| format!("Add {} to {}", requirements, file_name),
+    );
+
+    // Script-only dependencies move from the runtime dependencies to a group
+    for (i, finding) in findings.iter().enumerate() {
+        if !is_pyproject || finding.rule != Rule::ScriptOnlyDependency || fixes[i].is_some() {
+            continue;
+        }
+        let (without, removed) = remove(std::slice::from_ref(&finding.subject))?;
+        let [(_, declaration)] = removed.as_slice() else {
+            continue;
+        };
+        let grouped = project::pyprojecttoml::add_to_group(
+            &content,
+            SCRIPT_GROUP,
+            std::slice::from_ref(declaration),
+        )?;
+        let (Some(removal), Some(insertion)) = (
+            edit_between(path, &content, &without),
+            edit_between(path, &content, &grouped),
+        ) else {
+            continue;
+        };
+        let edits = vec![removal, insertion];
+        if apply_edits(&content, &edits).is_some() {
+            fixes[i] = Some(SuggestedFix {
+                description: format!(
+                    "Move `{}` to the `{}` dependency group of {}",
+                    declaration, SCRIPT_GROUP, file_name
+                ),
+                edits,
+            });
+        }
+    }
+    Ok(fixes)
+}
+
+/// Attach the edit of each change to the finding it fixes, as long as applying them all
+/// gives `expected`. Otherwise the first finding gets one edit making the whole change.
+fn attach(
+    fixes: &mut [Option<SuggestedFix>],
+    mut changes: Vec<(usize, Option<TextEdit>, String)>,
+    content: &str,
+    expected: &str,
+    path: &Path,
+    describe: impl Fn(&str) -> String,
+) {
+    // Insertions at the same place are applied in the order their findings are listed
+    changes.sort_by_key(|(i, _, _)| *i);
+    let Some(first) = changes.first().map(|(i, _, _)| *i) else {
+        return;
+    };
+    let combines = changes
+        .iter()
+        .all(|(i, edit, _)| *i < fixes.len() && edit.is_some());
+    let edits: Vec<TextEdit> = changes
+        .iter()
+        .filter_map(|(_, edit, _)| edit.clone())
+        .collect();
+    if combines && apply_edits(content, &edits).as_deref() == Some(expected) {
+        for (i, edit, text) in changes {
+            fixes[i] = Some(SuggestedFix {
+                description: describe(&format!("`{}`", text)),
+                edits: edit.into_iter().collect(),
+            });
+        }
+        return;
+    }
+    if first < fixes.len()
+        && let Some(edit) = edit_between(path, content, expected)
+    {
+        let texts: Vec<String> = changes
+            .iter()
+            .map(|(_, _, text)| format!("`{}`", text))
+            .collect();
+        fixes[first] = Some(SuggestedFix {
+            description: describe(&texts.join(", ")),
+            edits: vec![edit],
+        });
+    }
+}
+
+/// Apply edits to the content they were made for, in the order of their ranges, and
+/// insertions at the same place in the order they're given. Returns `None` if two edits
+/// overlap or one is out of bounds.
+pub fn apply_edits(content: &str, edits: &[TextEdit]) -> Option<String> {
+    let mut sorted: Vec<&TextEdit> = edits.iter().collect();
+    sorted.sort_by_key(|edit| edit.range.start);
+    let mut result = String::with_capacity(content.len());
+    let mut position = 0;
+    for edit in sorted {
+        if edit.range.start < position || edit.range.end > content.len() {
+            return None;
+        }
+        result.push_str(content.get(position..edit.range.start)?);
+        result.push_str(&edit.replacement);
+        position = edit.range.end;
+    }
+    result.push_str(&content[position..]);
+    Some(result)
+}
+
+/// The single edit turning `old` into `new`: the bytes between what they start and end
+/// with in common. An insertion or deletion is moved as far back as it can go and still
+/// make the same change, so the edits of neighboring declarations don't overlap.
+fn edit_between(path: &Path, old: &str, new: &str) -> Option<TextEdit> {
+    if old == new {
+        return None;
+    }
+    let mut prefix = old
+        .bytes()
+        .zip(new.bytes())
+        .take_while(|(a, b)| a == b)
+        .count();
+    while !old.is_char_boundary(prefix) || !new.is_char_boundary(prefix) {
+        prefix -= 1;
+    }
+    let mut suffix = old
+        .bytes()
+        .rev()
+        .zip(new.bytes().rev())
+        .take(old.len().min(new.len()) - prefix)
+        .take_while(|(a, b)| a == b)
+        .count();
+    while !old.is_char_boundary(old.len() - suffix) || !new.is_char_boundary(new.len() - suffix) {
+        suffix -= 1;
+    }
+    let mut range = prefix..old.len() - suffix;
+    let mut replacement = new[prefix..new.len() - suffix].to_string();
+    if range.is_empty() || replacement.is_empty() {
+        loop {
+            let moved = if range.is_empty() {
+                &replacement
+            } else {
+                &old[range.clone()]
+            };
+            let (Some(before), Some(last)) =
+                (old[..range.start].chars().last(), moved.chars().last())
+            else {
+                break;
+            };
+            if before != last {
+                break;
+            }
+            let width = last.len_utf8();
+            if range.is_empty() {
+                replacement = format!("{}{}", before, &replacement[..replacement.len() - width]);
+                range = range.start - width..range.start - width;
+            } else {
+                range = range.start - width..range.end - width;
+            }
+        }
+    }
+    Some(TextEdit {
+        path: path.to_path_buf(),
+        range,
+        replacement,
+    })
+}
+
+/// Get the path and content of a source that can be edited
+fn read_editable_source(
+    source: &EnvironmentBuilderSource,
+) -> Result<(&PathBuf, String), AnalysisError> {
+    let path = match source {
+        EnvironmentBuilderSource::RequirementsTxt(path)
+        | EnvironmentBuilderSource::PyProjectToml(path) => path,
+        _ => {
+            return Err(AnalysisError::UnsupportedProjectFormat(format!(
+                "{:?}",
+                source
+            )));
+        }
+    };
+
+    let content = fs::read_to_string(path).map_err(|e| {
+        AnalysisError::FileReadError(path.to_string_lossy().to_string(), e.to_string())
+    })?;
+    Ok((path, content))
+}
+
+fn write_source(path: &PathBuf, content: &str) -> Result<(), AnalysisError> {
+    fs::write(path, content).map_err(|e| {
+        AnalysisError::FileWriteError(path.to_string_lossy().to_string(), e.to_string())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pin_specifiers() {
+        let pins = |version: &str| {
+            [PinStyle::Exact, PinStyle::Compatible, PinStyle::Minimum]
+                .map(|style| style.specifier(version).unwrap_or_default())
+        };
+        assert_eq!(pins("1.2.3"), ["==1.2.3", "~=1.2", ">=1.2.3"]);
+        assert_eq!(pins("2"), ["==2", "~=2.0", ">=2"]);
+        assert_eq!(pins("2.1.0+cu121"), ["==2.1.0", "~=2.1", ">=2.1.0"]);
+        assert_eq!(
+            pins("1.4.2.post1"),
+            ["==1.4.2.post1", "~=1.4", ">=1.4.2.post1"]
+        );
+        assert_eq!(pins("2.0.0rc1"), ["==2.0.0rc1", "~=2.0rc1", ">=2.0.0rc1"]);
+        assert_eq!(pins("1!3.1"), ["==1!3.1", "~=1!3.1", ">=1!3.1"]);
+        assert_eq!(PinStyle::Exact.specifier("latest"), None);
+    }
+}
@@ -0,0 +1,8 @@
+/// The 64-bit FNV-1a hash of some bytes. Unlike the standard library's hasher, it's the
+/// same on every platform, build, and release, so it can name cached files and identify
+/// findings across runs.
+pub(crate) fn fnv1a(bytes: impl IntoIterator<Item = u8>) -> u64 {
+    bytes.into_iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
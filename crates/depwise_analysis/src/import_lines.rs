@@ -0,0 +1,391 @@
+//! The imports of Python sources the parser can't read, found line by line.
+//!
+//! The parser only knows the grammar of the Python versions it was built for, so a file
+//! using newer syntax, like a PEP 695 `type` alias, fails to parse as a whole. Its import
+//! statements are still plain: `import` or `from ... import` at the start of a logical line,
+//! possibly after a `;`. Those are read here, joining lines continued by open parentheses or
+//! a backslash, skipping comments and triple-quoted strings, and following indentation to
+//! tell which blocks an import is in.
+//!
+//! Only `try`, `def`, `class`, and `if` blocks are told apart, by their first line. An import
+//! in a `try` body is taken to be guarded whatever the handlers catch, since they can't be
+//! read reliably, so a degraded scan errs on the side of not reporting imports as missing.
+
+use crate::parser::PythonImport;
+
+/// The kinds of blocks an import can be in that change how it's reported
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Block {
+    Try,
+    Function,
+    Class,
+    TypeChecking,
+    Main,
+    Platform,
+    Other,
+}
+
+impl Block {
+    /// The kind of block a statement ending with `:` opens
+    fn opened_by(statement: &str) -> Self {
+        let keyword = statement
+            .split(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+            .next()
+            .unwrap_or("");
+        match keyword {
+            "try" => Block::Try,
+            "def" | "async" if statement.contains("def ") => Block::Function,
+            "class" => Block::Class,
+            "if" | "elif" if statement.contains("TYPE_CHECKING") => Block::TypeChecking,
+            "if" if statement.contains("__name__") && statement.contains("__main__") => Block::Main,
+            "if" | "elif"
+                if ["sys.platform", "os.name", "platform.system()"]
+                    .iter()
+                    .any(|check| statement.contains(check)) =>
+            {
+                Block::Platform
+            }
+            _ => Block::Other,
+        }
+    }
+}
+
+/// Find the import statements of a source without parsing it
+pub(crate) fn scan(source: &str) -> Vec<PythonImport> {
+    let lines: Vec<&str> = source.lines().collect();
+    let mut imports = Vec::new();
+    // The blocks the current line is in, with the indentation of the line opening them
+    let mut blocks: Vec<(usize, Block)> = Vec::new();
+    // The quotes of the triple-quoted string the current line is in
+    let mut in_string: Option<&str> = None;
+    let mut i = 0;
+    while i < lines.len() {
+        let number = i + 1;
+        let first = lines[i];
+        i += 1;
+        if let Some(quotes) = in_string {
+            if first.matches(quotes).count() % 2 == 1 {
+                in_string = None;
+            }
+            continue;
+        }
+
+        // A logical line goes on while parentheses are open or a line ends with `\`
+        let mut logical = String::new();
+        let mut line = first;
+        loop {
+            let code = strip_comment(line);
+            let depth = logical_depth(&logical) + logical_depth(code);
+            logical.push_str(code.strip_suffix('\\').unwrap_or(code));
+            logical.push(' ');
+            if (depth > 0 || code.ends_with('\\')) && i < lines.len() {
+                line = lines[i];
+                i += 1;
+            } else {
+                break;
+            }
+        }
+        in_string = ["\"\"\"", "'''"]
+            .into_iter()
+            .find(|quotes| logical.matches(quotes).count() % 2 == 1);
+
+        let trimmed = logical.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+        let indent = first.len() - first.trim_start().len();
+        while blocks.last().is_some_and(|(opened, _)| *opened >= indent) {
+            blocks.pop();
+        }
+        let in_block = |kind: Block| blocks.iter().any(|(_, block)| *block == kind);
+        for statement in split_statements(trimmed) {
+            let Some(mut import) = parse_statement(statement) else {
+                continue;
+            };
+            for import in &mut import {
+                import.line_number = number;
+                import.is_top_level_import = blocks.is_empty();
+                import.is_likely_exception_guarded = in_block(Block::Try);
+                import.is_in_function = in_block(Block::Function);
+                import.is_type_checking = in_block(Block::TypeChecking);
+                import.is_main_block = in_block(Block::Main);
+                import.main_guard_only = import.is_main_block;
+                import.is_platform_guarded = in_block(Block::Platform);
+            }
+            imports.extend(import);
+        }
+        // A block opened on its own line holds the indented lines after it
+        if let Some(statement) = trimmed.strip_suffix(':') {
+            blocks.push((indent, Block::opened_by(statement)));
+        }
+    }
+    imports
+}
+
+/// The line without its comment, if it has one outside a string
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            _ if escaped => escaped = false,
+            (Some(_), '\\') => escaped = true,
+            (Some(q), c) if c == q => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '#') => return line[..i].trim_end(),
+            _ => {}
+        }
+    }
+    line.trim_end()
+}
+
+/// How many more brackets the code opens than it closes, outside strings
+fn logical_depth(code: &str) -> i32 {
+    let mut depth = 0;
+    let mut quote = None;
+    for c in code.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '(' | '[' | '{') => depth += 1,
+            (None, ')' | ']' | '}') => depth -= 1,
+            _ => {}
+        }
+    }
+    depth
+}
+
+/// The statements of a logical line, separated by `;` outside strings
+fn split_statements(line: &str) -> Vec<&str> {
+    let mut statements = Vec::new();
+    let mut quote = None;
+    let mut start = 0;
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, ';') => {
+                statements.push(line[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    statements.push(line[start..].trim());
+    statements
+}
+
+/// The imports of an `import` or `from ... import` statement, with the flags that depend on
+/// where it is left unset
+fn parse_statement(statement: &str) -> Option<Vec<PythonImport>> {
+    let base = PythonImport {
+        module_name: None,
+        imported_names: Vec::new(),
+        imported_name_aliases: Vec::new(),
+        is_from_import: false,
+        is_relative: false,
+        alias: None,
+        line_number: 0,
+        relative_level: 0,
+        is_top_level_import: true,
+        is_likely_exception_guarded: false,
+        fallback: None,
+        is_platform_guarded: false,
+        is_in_function: false,
+        is_type_checking: false,
+        is_main_block: false,
+        is_annotation_only: false,
+        main_guard_only: false,
+    };
+    if let Some(names) = statement.strip_prefix("import ") {
+        let mut imports = Vec::new();
+        for (name, alias) in names.split(',').map(split_alias) {
+            if !is_dotted_name(name) {
+                return None;
+            }
+            imports.push(PythonImport {
+                module_name: Some(name.to_string()),
+                alias: alias.map(str::to_string),
+                ..base.clone()
+            });
+        }
+        return Some(imports);
+    }
+
+    let rest = statement.strip_prefix("from ")?;
+    let (module, names) = rest.split_once(" import ")?;
+    let module = module.trim();
+    let level = module.chars().take_while(|c| *c == '.').count();
+    let module = &module[level..];
+    if !(is_dotted_name(module) || (module.is_empty() && level > 0)) {
+        return None;
+    }
+    let names = names
+        .trim()
+        .trim_start_matches('(')
+        .trim_end_matches(')')
+        .trim();
+    let mut import = PythonImport {
+        module_name: (!module.is_empty()).then(|| module.to_string()),
+        is_from_import: true,
+        is_relative: level > 0,
+        relative_level: level,
+        ..base
+    };
+    for (name, alias) in names.split(',').map(split_alias) {
+        // A trailing comma leaves an empty name
+        if name.is_empty() {
+            continue;
+        }
+        if name != "*" && !is_dotted_name(name) {
+            return None;
+        }
+        import.imported_names.push(name.to_string());
+        import.imported_name_aliases.push(alias.map(str::to_string));
+    }
+    (!import.imported_names.is_empty()).then(|| vec![import])
+}
+
+/// Split `name as alias`
+fn split_alias(item: &str) -> (&str, Option<&str>) {
+    let item = item.trim();
+    match item.split_once(" as ") {
+        Some((name, alias)) => (name.trim(), Some(alias.trim())),
+        None => (item, None),
+    }
+}
+
+fn is_dotted_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.split('.').all(|part| {
+            part.chars()
+                .next()
+                .is_some_and(|c| c.is_alphabetic() || c == '_')
+                && part.chars().all(|c| c.is_alphanumeric() || c == '_')
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The module, names, and line of each import
+    fn found(source: &str) -> Vec<(Option<String>, Vec<String>, usize)> {
+        scan(source)
+            .into_iter()
+            .map(|import| {
+                (
+                    import.module_name,
+                    import.imported_names,
+                    import.line_number,
+                )
+            })
+            .collect()
+    }
+
+    fn module(name: &str, line: usize) -> (Option<String>, Vec<String>, usize) {
+        (Some(name.to_string()), Vec::new(), line)
+    }
+
+    #[test]
+    fn test_parenthesized_imports() {
+        let source = "from typing import (
+    Any,  # comment
+    Callable as Fn,
+)
+from . import (sibling)
+import os, \\
+    sys
+type Alias[T] = list[T]
+";
+        let imports = scan(source);
+        assert_eq!(imports.len(), 4);
+        assert_eq!(imports[0].imported_names, ["Any", "Callable"]);
+        assert_eq!(imports[0].imported_name_aliases, [None, Some("Fn".into())]);
+        assert_eq!(imports[1].relative_level, 1);
+        assert_eq!(imports[1].module_name, None);
+        assert_eq!(imports[1].imported_names, ["sibling"]);
+        assert_eq!(found(source)[2..], [module("os", 6), module("sys", 6)]);
+    }
+
+    #[test]
+    fn test_semicolons_and_comments() {
+        let source = "import json; import yaml as y  # import toml
+x = 1; from collections import abc
+# import requests
+s = 'import rich; import click'
+\"\"\"
+import numpy
+\"\"\"
+";
+        assert_eq!(
+            found(source),
+            [
+                module("json", 1),
+                module("yaml", 1),
+                (Some("collections".into()), vec!["abc".into()], 2),
+            ]
+        );
+        assert_eq!(scan(source)[1].alias.as_deref(), Some("y"));
+    }
+
+    #[test]
+    fn test_blocks() {
+        let source = "import os
+
+try:
+    import ujson as json
+except ImportError:
+    import json
+
+def main():
+    import click
+
+if TYPE_CHECKING:
+    from pandas import DataFrame
+
+class Config:
+    match x:
+        case 1:
+            import tomllib
+import sys
+";
+        let imports = scan(source);
+        let flags: Vec<(&str, bool, bool, bool, bool)> = imports
+            .iter()
+            .map(|import| {
+                (
+                    import.module_name.as_deref().unwrap(),
+                    import.is_top_level_import,
+                    import.is_likely_exception_guarded,
+                    import.is_in_function,
+                    import.is_type_checking,
+                )
+            })
+            .collect();
+        assert_eq!(
+            flags,
+            [
+                ("os", true, false, false, false),
+                ("ujson", false, true, false, false),
+                ("json", false, false, false, false),
+                ("click", false, false, true, false),
+                ("pandas", false, false, false, true),
+                ("tomllib", false, false, false, false),
+                ("sys", true, false, false, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_not_imports() {
+        let source = "importer = 1
+from_ = 2
+print(f'{x} from y import z')
+import
+";
+        assert!(scan(source).is_empty());
+    }
+}
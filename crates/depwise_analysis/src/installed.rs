@@ -0,0 +1,256 @@
+use std::collections::{HashMap, HashSet};
+#[cfg(feature = "backends-net")]
+use std::fs;
+use std::str::FromStr;
+
+use pep508_rs::VersionOrUrl;
+#[cfg(feature = "backends-net")]
+use pep508_rs::pep440_rs::Operator;
+use pep508_rs::pep440_rs::Version;
+use tracing::debug;
+
+#[cfg(feature = "backends-net")]
+use crate::env_backend::pypi::Release;
+#[cfg(feature = "backends-net")]
+use crate::finding::Location;
+use crate::finding::{Finding, Rule};
+#[cfg(feature = "backends-net")]
+use crate::project::declaration_line;
+use crate::project::{Configuration, Dependency, PyPIRequirement, are_exclusive};
+
+/// The names of the PyPI distributions the configurations declare, to look up in the
+/// environment
+pub(crate) fn declared_distributions(configurations: &[Configuration]) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for configuration in configurations {
+        for dependency in configuration.dependencies() {
+            if let Dependency::PyPI(requirement) = dependency {
+                let name = requirement.name.to_string();
+                if !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+        }
+    }
+    names
+}
+
+/// The names of the PyPI distributions the configurations declare with a version
+/// specifier, to look up on the index
+pub(crate) fn specified_distributions(configurations: &[Configuration]) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for configuration in configurations {
+        for dependency in configuration.dependencies() {
+            if let Dependency::PyPI(requirement) = dependency
+                && let Some(VersionOrUrl::VersionSpecifier(specifiers)) =
+                    &requirement.version_or_url
+                && !specifiers.is_empty()
+            {
+                let name = requirement.name.to_string();
+                if !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+        }
+    }
+    names
+}
+
+/// Compare the declared PyPI dependencies against the versions installed in the environment.
+///
+/// An installed version outside the declared specifier is reported for every configuration.
+/// A dependency that isn't installed at all is only reported for the base configurations,
+/// and for extras when `extras_selected` says the environment was installed with them;
+/// dependencies with an environment marker may be left out on purpose, so they're skipped.
+/// Only one of the extras in a group of `exclusive_extras` is installed, so their
+/// dependencies are never expected to be, and a version one of them accepts is fine.
+pub(crate) fn version_findings(
+    configurations: &[Configuration],
+    installed: &HashMap<String, String>,
+    extras_selected: bool,
+    exclusive_extras: &[Vec<String>],
+) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let mut checked = HashSet::new();
+    for configuration in configurations {
+        let alternatives: Vec<&Configuration> = match configuration.extra() {
+            Some(extra) => configurations
+                .iter()
+                .filter(|c| {
+                    c.extra()
+                        .is_some_and(|other| are_exclusive(exclusive_extras, extra, other))
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+        let expects_installed = configuration.environment().is_none()
+            && (configuration.extra().is_none() || extras_selected)
+            && alternatives.is_empty();
+        for dependency in configuration.dependencies() {
+            let Dependency::PyPI(requirement) = dependency else {
+                continue;
+            };
+            let name = requirement.name.to_string();
+            let finding = match installed.get(&name) {
+                Some(installed_version) => {
+                    let Some(VersionOrUrl::VersionSpecifier(specifiers)) =
+                        &requirement.version_or_url
+                    else {
+                        continue;
+                    };
+                    let Ok(version) = Version::from_str(installed_version) else {
+                        debug!(
+                            "Unable to parse installed version {} of {}",
+                            installed_version, name
+                        );
+                        continue;
+                    };
+                    if specifiers.contains(&version)
+                        || alternatives
+                            .iter()
+                            .any(|alternative| accepts(alternative, requirement, &version))
+                    {
+                        continue;
+                    }
+                    Finding::new(
+                        Rule::InstalledVersionMismatch,
+                        &name,
+                        format!(
+                            "`{}` is declared but {} {} is installed",
+                            requirement, name, installed_version
+                        ),
+                        None,
+                    )
+                }
+                None if expects_installed && requirement.marker.is_true() => Finding::new(
+                    Rule::DependencyNotInstalled,
+                    &name,
+                    format!("`{}` is declared but isn't installed", requirement),
+                    None,
+                ),
+                None => continue,
+            };
+            // A package repeated in an extra is only reported once
+            if checked.insert(name) {
+                findings.push(finding.with_configuration(configuration.name()));
+            }
+        }
+    }
+    findings
+}
+
+/// Whether a configuration declares a package with a specifier that accepts a version
+fn accepts(
+    configuration: &Configuration,
+    requirement: &PyPIRequirement,
+    version: &Version,
+) -> bool {
+    configuration.dependencies().iter().any(|dependency| {
+        let Dependency::PyPI(declared) = dependency else {
+            return false;
+        };
+        declared.name == requirement.name
+            && match &declared.version_or_url {
+                Some(VersionOrUrl::VersionSpecifier(specifiers)) => specifiers.contains(version),
+                _ => true,
+            }
+    })
+}
+
+/// Compare the declared PyPI dependencies against the releases published on the index.
+///
+/// A version pinned with `==` has to be published and not yanked, and any other specifier
+/// has to match a release that isn't yanked. Each finding names the published versions
+/// nearest to the declared one. Distributions whose releases aren't known are skipped.
+#[cfg(feature = "backends-net")]
+pub(crate) fn release_findings(
+    configurations: &[Configuration],
+    releases: &HashMap<String, Vec<Release>>,
+) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let mut checked = HashSet::new();
+    for configuration in configurations {
+        for dependency in configuration.dependencies() {
+            let Dependency::PyPI(requirement) = dependency else {
+                continue;
+            };
+            let Some(VersionOrUrl::VersionSpecifier(specifiers)) = &requirement.version_or_url
+            else {
+                continue;
+            };
+            let name = requirement.name.to_string();
+            let (Some(first), Some(published)) = (specifiers.iter().next(), releases.get(&name))
+            else {
+                continue;
+            };
+            let published: Vec<(Version, bool)> = published
+                .iter()
+                .filter_map(|release| Some((release.version.parse().ok()?, release.yanked)))
+                .collect();
+            let declared = first.version();
+            let problem = match &specifiers[..] {
+                [pin] if *pin.operator() == Operator::Equal => {
+                    match published.iter().find(|(version, _)| version == declared) {
+                        Some((_, false)) => continue,
+                        Some((_, true)) => format!("{} {} is yanked", name, declared),
+                        None => format!("{} {} isn't published", name, declared),
+                    }
+                }
+                _ if published
+                    .iter()
+                    .any(|(version, yanked)| !yanked && specifiers.contains(version)) =>
+                {
+                    continue;
+                }
+                _ => format!("no release of {} that isn't yanked matches it", name),
+            };
+            // The same declaration repeated in an extra is only reported once
+            if !checked.insert(requirement.to_string()) {
+                continue;
+            }
+            let mut message = format!("`{}` is declared but {}", requirement, problem);
+            let nearest = nearest_releases(&published, declared);
+            if !nearest.is_empty() {
+                message.push_str(&format!(
+                    "; the nearest published versions are {}",
+                    nearest.join(" and ")
+                ));
+            }
+            let location = declaration(configuration, &name);
+            findings.push(
+                Finding::new(Rule::UnavailableVersion, &name, message, Some(location))
+                    .with_configuration(configuration.name()),
+            );
+        }
+    }
+    findings
+}
+
+/// The releases that aren't yanked closest below and above a version
+#[cfg(feature = "backends-net")]
+fn nearest_releases(published: &[(Version, bool)], version: &Version) -> Vec<String> {
+    let available = || published.iter().filter(|(_, yanked)| !yanked);
+    let below = available()
+        .filter(|(release, _)| release < version)
+        .max_by(|(a, _), (b, _)| a.cmp(b));
+    let above = available()
+        .filter(|(release, _)| release > version)
+        .min_by(|(a, _), (b, _)| a.cmp(b));
+    [below, above]
+        .into_iter()
+        .flatten()
+        .map(|(release, _)| release.to_string())
+        .collect()
+}
+
+/// Where a configuration declares a dependency: the line of its requirements file, or the
+/// first line of its source mentioning it
+#[cfg(feature = "backends-net")]
+fn declaration(configuration: &Configuration, name: &str) -> Location {
+    if let Some(provenance) = configuration.provenance(name) {
+        return provenance.declared.clone();
+    }
+    let path = configuration.source().path();
+    let line = fs::read_to_string(path).map_or(0, |content| declaration_line(&content, name));
+    Location::new(path.to_path_buf(), line)
+}
@@ -0,0 +1,255 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::finding::{Finding, Location, Rule};
+use crate::walker;
+
+/// How a project's packages are laid out, as its build backend is configured to find them.
+///
+/// When a project configures its layout, the first-party modules are the ones the
+/// configuration names or finds instead of whatever looks like a package in the project
+/// root and `src/`. Modules directly in the project root stay first-party, since they can be
+/// imported from the project directory whether or not they're packaged.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct PackageLayout {
+    /// The directory of the pyproject.toml the layout is configured in
+    pub(crate) root: PathBuf,
+    /// Directories the build backend looks for packages in, with where they're configured
+    pub(crate) source_roots: Vec<(PathBuf, Location)>,
+    /// The patterns of a setuptools find directive, if there is one
+    pub(crate) find: Option<PackageFind>,
+    /// Packages and modules listed explicitly
+    pub(crate) packages: Vec<DeclaredPackage>,
+    /// Whether subpackages have to be listed too, like setuptools' `packages` requires
+    pub(crate) lists_subpackages: bool,
+}
+
+/// The `include` and `exclude` patterns of a `packages.find` directive
+#[derive(Debug, Clone)]
+pub(crate) struct PackageFind {
+    pub(crate) include: Vec<String>,
+    pub(crate) exclude: Vec<String>,
+    pub(crate) location: Location,
+}
+
+/// A package or module the build backend is told to install
+#[derive(Debug, Clone)]
+pub(crate) struct DeclaredPackage {
+    pub(crate) name: String,
+    /// The directory or file it's expected in, `None` for compiled extension modules
+    pub(crate) path: Option<PathBuf>,
+    pub(crate) location: Location,
+}
+
+impl PackageFind {
+    fn matches(&self, module: &str) -> bool {
+        let include = self.include.is_empty()
+            || self
+                .include
+                .iter()
+                .any(|pattern| walker::wildcard_match(pattern, module));
+        include
+            && !self
+                .exclude
+                .iter()
+                .any(|pattern| walker::wildcard_match(pattern, module))
+    }
+}
+
+impl PackageLayout {
+    /// The modules that belong to the project: the declared packages, the packages found in
+    /// the configured source roots, and the modules directly in `project_root`
+    pub(crate) fn first_party_modules(&self, project_root: &Path) -> Vec<String> {
+        let mut modules = walker::discover_modules(&[project_root.to_path_buf()]);
+        modules.extend(self.found_packages());
+        modules.extend(self.packages.iter().map(|package| package.name.clone()));
+        modules.sort();
+        modules.dedup();
+        modules
+    }
+
+    /// The packages in the source roots, filtered by the find directive's patterns
+    fn found_packages(&self) -> Vec<String> {
+        let roots: Vec<PathBuf> = self
+            .source_roots
+            .iter()
+            .map(|(root, _)| root.clone())
+            .collect();
+        walker::discover_modules(&roots)
+            .into_iter()
+            .filter(|module| self.find.as_ref().is_none_or(|find| find.matches(module)))
+            .collect()
+    }
+
+    /// Compare the configured layout against what's on disk: declared packages and source
+    /// roots that don't exist, find directives that don't find anything, and subpackages
+    /// that aren't listed when they have to be
+    pub(crate) fn findings(&self) -> Vec<Finding> {
+        let display = |path: &Path| {
+            path.strip_prefix(&self.root)
+                .unwrap_or(path)
+                .display()
+                .to_string()
+        };
+        let mut findings = Vec::new();
+        for package in &self.packages {
+            let Some(path) = &package.path else {
+                continue;
+            };
+            if !path.exists() {
+                findings.push(Finding::new(
+                    Rule::PackageLayoutMismatch,
+                    &package.name,
+                    format!(
+                        "`{}` is declared as a package but `{}` doesn't exist",
+                        package.name,
+                        display(path)
+                    ),
+                    Some(package.location.clone()),
+                ));
+                continue;
+            }
+            if !self.lists_subpackages {
+                continue;
+            }
+            for subpackage in subpackages(path) {
+                let Some(directory) = subpackage.file_name() else {
+                    continue;
+                };
+                let name = format!("{}.{}", package.name, directory.to_string_lossy());
+                if self.packages.iter().all(|package| package.name != name) {
+                    findings.push(Finding::new(
+                        Rule::PackageLayoutMismatch,
+                        &name,
+                        format!(
+                            "`{}` is in `{}` but isn't listed in `packages`, so it isn't installed",
+                            name,
+                            display(&subpackage)
+                        ),
+                        Some(package.location.clone()),
+                    ));
+                }
+            }
+        }
+
+        let mut missing_root = false;
+        for (root, location) in &self.source_roots {
+            if !root.is_dir() {
+                missing_root = true;
+                let directory = display(root);
+                findings.push(Finding::new(
+                    Rule::PackageLayoutMismatch,
+                    &directory,
+                    format!(
+                        "`{}` is configured as a package directory but doesn't exist",
+                        directory
+                    ),
+                    Some(location.clone()),
+                ));
+            }
+        }
+        if let Some(find) = &self.find
+            && !missing_root
+            && self.found_packages().is_empty()
+        {
+            let roots: Vec<String> = self
+                .source_roots
+                .iter()
+                .map(|(root, _)| format!("`{}`", display(root)))
+                .collect();
+            findings.push(Finding::new(
+                Rule::PackageLayoutMismatch,
+                "packages.find",
+                format!(
+                    "The package find directive doesn't match any package in {}",
+                    roots.join(", ")
+                ),
+                Some(find.location.clone()),
+            ));
+        }
+        findings
+    }
+}
+
+/// The directories in a package that are regular packages themselves
+fn subpackages(path: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(path) else {
+        return Vec::new();
+    };
+    let mut subpackages: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.join("__init__.py").is_file())
+        .collect();
+    subpackages.sort();
+    subpackages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{File, create_dir_all};
+
+    #[test]
+    fn test_layout_findings() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        create_dir_all(root.join("src/acme/tools")).unwrap();
+        create_dir_all(root.join("src/acme/billing")).unwrap();
+        File::create(root.join("src/acme/__init__.py")).unwrap();
+        File::create(root.join("src/acme/tools/__init__.py")).unwrap();
+        File::create(root.join("src/acme/billing/__init__.py")).unwrap();
+
+        let location = Location::new(root.join("pyproject.toml"), 1);
+        let declared = |name: &str, path: &str| DeclaredPackage {
+            name: name.to_string(),
+            path: Some(root.join(path)),
+            location: location.clone(),
+        };
+        let layout = PackageLayout {
+            root: root.to_path_buf(),
+            source_roots: Vec::new(),
+            find: None,
+            packages: vec![
+                declared("acme", "src/acme"),
+                declared("acme.tools", "src/acme/tools"),
+                declared("acme.reports", "src/acme/reports"),
+            ],
+            lists_subpackages: true,
+        };
+        let messages: Vec<String> = layout
+            .findings()
+            .into_iter()
+            .map(|finding| finding.message)
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "`acme.billing` is in `src/acme/billing` but isn't listed in `packages`, so it isn't installed",
+                "`acme.reports` is declared as a package but `src/acme/reports` doesn't exist",
+            ]
+        );
+        assert_eq!(
+            layout.first_party_modules(root),
+            ["acme", "acme.reports", "acme.tools"]
+        );
+
+        let find = PackageLayout {
+            root: root.to_path_buf(),
+            source_roots: vec![(root.join("src"), location.clone())],
+            find: Some(PackageFind {
+                include: vec!["widgets*".to_string()],
+                exclude: Vec::new(),
+                location: location.clone(),
+            }),
+            packages: Vec::new(),
+            lists_subpackages: false,
+        };
+        let findings = find.findings();
+        assert_eq!(findings.len(), 1);
+        assert_eq!(
+            findings[0].message,
+            "The package find directive doesn't match any package in `src`"
+        );
+    }
+}
@@ -1,42 +1,190 @@
+#[cfg(feature = "python-parsing")]
+mod analysis;
+#[cfg(feature = "python-parsing")]
+mod analyzer;
+pub mod cancel;
+mod config;
+#[cfg(feature = "python-parsing")]
+mod docs;
+#[cfg(feature = "python-parsing")]
+mod entry_points;
 pub mod env_backend;
 pub mod error;
-pub mod parser;
+pub mod finding;
+#[cfg(feature = "python-parsing")]
+pub mod fix;
+mod hash;
+#[cfg(feature = "python-parsing")]
+mod import_lines;
+#[cfg(feature = "python-parsing")]
+mod installed;
+#[cfg(feature = "python-parsing")]
+mod layout;
+pub mod matcher;
+pub mod name_map;
+mod options;
+#[cfg(feature = "python-parsing")]
+pub(crate) mod parser;
 pub mod project;
+#[cfg(feature = "python-parsing")]
+pub mod python;
+mod python_version;
+pub mod sbom;
+mod stats;
+pub mod stdlib;
+#[cfg(feature = "python-parsing")]
+pub(crate) mod walker;
+mod warning;
 
-pub use error::AnalysisError;
-use std::path::Path;
+#[cfg(feature = "python-parsing")]
+pub use analysis::{
+    Analysis, AnalysisReport, AnalysisSession, ConfigurationReport, DependencyUsage, MissingImport,
+};
+#[cfg(feature = "python-parsing")]
+pub use analyzer::Analyzer;
+pub use cancel::{CancellationToken, Phase};
+pub use config::Config;
+pub use env_backend::{Backend, ResolvedEnvironment};
+pub use error::{AnalysisError, ParseDiagnostic};
+pub use finding::{Explanation, Finding, Location, Rule, Severity};
+#[cfg(feature = "python-parsing")]
+pub use fix::FileFix;
+pub use options::{
+    AnalysisOptions, AnalysisOptionsBuilder, DEFAULT_MAX_FILE_SIZE, DEFAULT_MAX_REQUIREMENTS,
+};
+pub use project::{Configuration, Dependency};
+pub use python_version::PythonVersion;
+pub use stats::Stats;
 use std::path::PathBuf;
+use std::path::{Component, Path};
 use toml::Value;
+use tracing::info;
+pub use warning::AnalysisWarning;
 /// A file that can be used to extract dependencies from to build up an environment.
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum EnvironmentBuilderSource {
+    #[cfg(feature = "conda")]
     CondaEnvironmentYml(PathBuf),
+    /// A standalone script declaring its dependencies in PEP 723 `# /// script` metadata
+    InlineScript(PathBuf),
+    /// The core metadata of a distribution: a wheel's or installed package's `METADATA`, or a
+    /// source distribution's `PKG-INFO`
+    PackageMetadata(PathBuf),
     PixiToml(PathBuf),
+    /// The packages a `poetry.lock` pins, the whole resolved set of a poetry project
+    PoetryLock(PathBuf),
     PyProjectToml(PathBuf),
     RequirementsTxt(PathBuf),
+    /// The `deps` of the test environments in a `tox.ini`
+    ToxIni(PathBuf),
+    /// The packages the sessions of a `noxfile.py` install
+    #[cfg(feature = "python-parsing")]
+    Noxfile(PathBuf),
+    /// A dependency file of a kind that isn't built in, parsed by the parser registered for
+    /// `kind` on a [`project::SourceRegistry`]
+    Custom {
+        kind: String,
+        path: PathBuf,
+    },
 }
 
 impl EnvironmentBuilderSource {
+    /// The file the dependencies are read from
+    pub fn path(&self) -> &Path {
+        match self {
+            #[cfg(feature = "conda")]
+            Self::CondaEnvironmentYml(path) => path,
+            #[cfg(feature = "python-parsing")]
+            Self::Noxfile(path) => path,
+            Self::InlineScript(path)
+            | Self::PackageMetadata(path)
+            | Self::PixiToml(path)
+            | Self::PoetryLock(path)
+            | Self::PyProjectToml(path)
+            | Self::RequirementsTxt(path)
+            | Self::ToxIni(path)
+            | Self::Custom { path, .. } => path,
+        }
+    }
+
+    /// The name of the kind of the source, which its parser is registered under on a
+    /// [`project::SourceRegistry`], like `pyproject.toml`
+    pub fn kind(&self) -> &str {
+        match self {
+            #[cfg(feature = "conda")]
+            Self::CondaEnvironmentYml(_) => "environment.yml",
+            Self::InlineScript(_) => "script",
+            Self::PackageMetadata(_) => "METADATA",
+            Self::PixiToml(_) => "pixi.toml",
+            Self::PoetryLock(_) => "poetry.lock",
+            Self::PyProjectToml(_) => "pyproject.toml",
+            Self::RequirementsTxt(_) => "requirements.txt",
+            Self::ToxIni(_) => "tox.ini",
+            #[cfg(feature = "python-parsing")]
+            Self::Noxfile(_) => "noxfile.py",
+            Self::Custom { kind, .. } => kind,
+        }
+    }
+
+    /// Whether the source describes the environments of a test runner (tox or nox) rather
+    /// than the project's own dependencies
+    pub fn is_test_runner(&self) -> bool {
+        match self {
+            #[cfg(feature = "python-parsing")]
+            Self::Noxfile(_) => true,
+            _ => matches!(self, Self::ToxIni(_)),
+        }
+    }
+
     pub fn infer_from_source_path(path: &Path) -> Result<Self, AnalysisError> {
         if path.is_dir() {
             let pyproject_toml = path.join("pyproject.toml");
             if pyproject_toml.exists() {
                 // Check if the pyproject.toml is a poetry project or has a [project] section
-                if let Ok(pyproject_toml_content) = std::fs::read_to_string(&pyproject_toml) {
-                    if let Ok(toml_value) = pyproject_toml_content.parse::<Value>() {
-                        if toml_value.get("project").is_some_and(|v| v.is_table()) {
-                            return Ok(Self::PyProjectToml(pyproject_toml));
-                        }
-                    }
+                if let Ok(pyproject_toml_content) = std::fs::read_to_string(&pyproject_toml)
+                    && let Ok(toml_value) = pyproject_toml_content.parse::<Value>()
+                    && (toml_value.get("project").is_some_and(|v| v.is_table())
+                        || toml_value
+                            .get("tool")
+                            .and_then(|tool| tool.get("poetry"))
+                            .is_some_and(|v| v.is_table()))
+                {
+                    return Ok(Self::PyProjectToml(pyproject_toml));
                 }
             }
             let requirements_txt = path.join("requirements.txt");
             if requirements_txt.exists() {
                 return Ok(Self::RequirementsTxt(requirements_txt));
             }
-            let conda_environment_yml = path.join("environment.yml");
-            if conda_environment_yml.exists() {
-                return Ok(Self::CondaEnvironmentYml(conda_environment_yml));
+            #[cfg(feature = "conda")]
+            {
+                let conda_environment_yml = path.join("environment.yml");
+                if conda_environment_yml.exists() {
+                    return Ok(Self::CondaEnvironmentYml(conda_environment_yml));
+                }
+            }
+            let pixi_toml = path.join("pixi.toml");
+            if pixi_toml.exists() {
+                return Ok(Self::PixiToml(pixi_toml));
+            }
+            let poetry_lock = path.join("poetry.lock");
+            if poetry_lock.exists() {
+                return Ok(Self::PoetryLock(poetry_lock));
+            }
+        } else if path
+            .file_name()
+            .is_some_and(|name| name == "METADATA" || name == "PKG-INFO")
+        {
+            return Ok(Self::PackageMetadata(path.to_path_buf()));
+        } else if path.file_name().is_some_and(|name| name == "poetry.lock") {
+            return Ok(Self::PoetryLock(path.to_path_buf()));
+        } else if project::inlinescript::has_metadata(path) {
+            return Ok(Self::InlineScript(path.to_path_buf()));
+        } else if path.is_file() {
+            // A script without inline metadata is checked against the project containing it
+            if let Some(source) = Self::infer_from_enclosing_directory(path) {
+                return Ok(source);
             }
         }
 
@@ -44,73 +192,72 @@ impl EnvironmentBuilderSource {
             path.to_string_lossy().to_string(),
         ))
     }
+
+    /// Find the project of the nearest directory above a file. The directories are given
+    /// relative to the current one while they are below it, like the file.
+    fn infer_from_enclosing_directory(file: &Path) -> Option<Self> {
+        let within_current = file.is_relative()
+            && file
+                .components()
+                .all(|component| matches!(component, Component::Normal(_)));
+        let mut directories: Vec<PathBuf> = Vec::new();
+        if within_current {
+            directories.extend(file.ancestors().skip(1).map(|directory| {
+                match directory.as_os_str().is_empty() {
+                    true => PathBuf::from("."),
+                    false => directory.to_path_buf(),
+                }
+            }));
+        }
+        let start = match within_current {
+            true => std::path::absolute(".")
+                .ok()
+                .and_then(|cwd| cwd.parent().map(Path::to_path_buf)),
+            false => std::path::absolute(file)
+                .ok()
+                .and_then(|file| file.parent().map(Path::to_path_buf)),
+        };
+        directories.extend(
+            start
+                .iter()
+                .flat_map(|start| start.ancestors().map(Path::to_path_buf)),
+        );
+        directories.iter().find_map(|directory| {
+            let source = Self::infer_from_source_path(directory).ok()?;
+            info!(
+                "{} has no inline script metadata, using {}",
+                file.display(),
+                source.path().display()
+            );
+            Some(source)
+        })
+    }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum EnvironmentBackend {
     Auto,
     Simulated,
+    #[cfg(feature = "backends-exec")]
     UV,
+    #[cfg(feature = "backends-exec")]
     Pixi,
+    #[cfg(feature = "backends-exec")]
     Current,
 }
 
-pub struct Analysis {
-    found_imports: Vec<String>,
-    unused_imports: Vec<String>,
-    missing_imports: Vec<String>,
-}
-
-impl Default for Analysis {
-    fn default() -> Self {
-        Self {
-            found_imports: vec![],
-            unused_imports: vec![],
-            missing_imports: vec![],
-        }
-    }
-}
-
+/// Analyze a project with the given source and backend.
+#[deprecated(note = "use `Analysis::run` with `AnalysisOptions::builder` instead")]
+#[cfg(feature = "python-parsing")]
 pub fn analyze_project(
-    mut environment_builder_source: Option<EnvironmentBuilderSource>,
+    environment_builder_source: Option<EnvironmentBuilderSource>,
     backend: EnvironmentBackend,
     path: &Path,
-) -> Result<Analysis, AnalysisError> {
-    // If the environment_builder_source is None we can try to infer it from the path
-    if environment_builder_source.is_none() {
-        match EnvironmentBuilderSource::infer_from_source_path(path) {
-            Ok(inferred_source) => {
-                environment_builder_source = Some(inferred_source);
-            }
-            Err(e) => {
-                println!("Error inferring environment builder source: {:?}", e);
-            }
-        }
-    }
-
-    if let Some(environment) = environment_builder_source {
-        let dependencies = project::extract_configurations(environment)?;
-        println!("dependencies: {:?}", dependencies);
-    }
-
-    let analysis = Analysis::default();
-    Ok(analysis)
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use tracing_subscriber::fmt::format::FmtSpan;
-
-    fn init_tracing() {
-        let _ = tracing_subscriber::fmt()
-            .with_env_filter("debug")
-            .with_span_events(FmtSpan::CLOSE)
-            .try_init();
-    }
-
-    #[test]
-    fn test_parse_relative_imports() -> Result<(), AnalysisError> {
-        init_tracing();
-        Ok(())
+) -> Result<AnalysisReport, AnalysisError> {
+    let mut builder = AnalysisOptions::builder(path).backend(backend);
+    if let Some(source) = environment_builder_source {
+        builder = builder.source(source);
     }
+    Analysis::run(&builder.build())
 }
@@ -0,0 +1,627 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fmt;
+
+use tracing::trace;
+
+use crate::name_map::{self, NameMap};
+use crate::project::Dependency;
+
+/// What provides a module
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ModuleProvider {
+    /// The module is part of the project being analyzed
+    FirstParty,
+    /// The module is provided by the distribution with the given PyPI name
+    Distribution(String),
+}
+
+/// Why a declared dependency that is never imported isn't reported as unused
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum UnusedExemption {
+    /// The distribution's metadata lists no importable modules, only data files
+    NoModules,
+    /// The distribution is a well-known build or runtime support package
+    SupportPackage,
+    /// The distribution was listed as used without being imported
+    Ignored,
+}
+
+impl fmt::Display for UnusedExemption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnusedExemption::NoModules => write!(f, "no-modules"),
+            UnusedExemption::SupportPackage => write!(f, "support-package"),
+            UnusedExemption::Ignored => write!(f, "ignored"),
+        }
+    }
+}
+
+/// Where the providers of a module were looked up
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Resolution {
+    /// Metadata or first-party modules registered on the index
+    Index,
+    /// The curated mapping in `name_map`
+    NameMap,
+    /// An entry of the name map given by the user
+    CustomNameMap,
+    /// Nothing matched, so the top-level module name was taken as the distribution name
+    ModuleName,
+}
+
+impl fmt::Display for Resolution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Resolution::Index => write!(f, "the installed distribution metadata"),
+            Resolution::NameMap => write!(f, "the built-in name map"),
+            Resolution::CustomNameMap => write!(f, "the custom name map"),
+            Resolution::ModuleName => write!(f, "the module name"),
+        }
+    }
+}
+
+/// Index of module prefixes to what provides them.
+///
+/// Module prefixes can span several segments so distributions that share a namespace
+/// package (PEP 420) such as `google` or `azure` can be told apart. Lookups always use the
+/// longest prefix that matches an import.
+#[derive(Debug, Clone, Default)]
+pub struct ModuleIndex {
+    providers: BTreeMap<String, Vec<ModuleProvider>>,
+    /// Distributions whose installed metadata was registered, by normalized name
+    described: BTreeSet<String>,
+    /// Distributions used without being imported, by normalized name
+    ignored: BTreeSet<String>,
+    /// Module prefixes the runtime provides, which never need a dependency
+    provided: BTreeSet<String>,
+    /// The extras that install a distribution, by its normalized name, as pairs of the
+    /// normalized name of the distribution the extra belongs to and the extra
+    extras: BTreeMap<String, Vec<(String, String)>>,
+    /// The curated mapping, along with the entries given by the user
+    name_map: NameMap,
+}
+
+impl ModuleIndex {
+    /// Create an empty index. Lookups fall back to the curated mapping in `name_map`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create an empty index whose lookups fall back to the custom entries of a name map
+    /// before the curated ones
+    pub fn with_name_map(name_map: NameMap) -> Self {
+        Self {
+            name_map,
+            ..Self::default()
+        }
+    }
+
+    fn add(&mut self, module: &str, provider: ModuleProvider) {
+        let providers = self.providers.entry(module.to_string()).or_default();
+        if !providers.contains(&provider) {
+            providers.push(provider);
+        }
+    }
+
+    /// Register a module (or module prefix) that belongs to the project being analyzed
+    pub fn add_first_party(&mut self, module: &str) {
+        self.add(module, ModuleProvider::FirstParty);
+    }
+
+    /// Register a module (or module prefix) provided by a distribution
+    pub fn add_distribution_module(&mut self, distribution: &str, module: &str) {
+        self.add(
+            module,
+            ModuleProvider::Distribution(name_map::normalize_name(distribution)),
+        );
+    }
+
+    /// Register the modules listed in a distribution's `top_level.txt`.
+    ///
+    /// `top_level.txt` only lists the first segment of each package, so it can't describe
+    /// namespace participation. Prefer `add_distribution_record` when a RECORD is available.
+    pub fn add_distribution_top_level(&mut self, distribution: &str, top_level: &str) {
+        self.described
+            .insert(name_map::normalize_name(distribution));
+        for line in top_level.lines() {
+            let module = line.trim();
+            if !module.is_empty() {
+                self.add_distribution_module(distribution, module);
+            }
+        }
+    }
+
+    /// Register the modules provided by a distribution from the contents of its RECORD file.
+    ///
+    /// Directories without an `__init__.py` are treated as namespace packages, so a
+    /// distribution installing `google/cloud/storage/__init__.py` provides
+    /// `google.cloud.storage` rather than all of `google`.
+    pub fn add_distribution_record(&mut self, distribution: &str, record: &str) {
+        self.described
+            .insert(name_map::normalize_name(distribution));
+        for module in modules_from_record(record) {
+            trace!("{} provides {}", distribution, module);
+            self.add_distribution_module(distribution, &module);
+        }
+    }
+
+    /// Register that an extra of a distribution installs another distribution, like the
+    /// `Requires-Dist: pyarrow; extra == "parquet"` of `pandas`
+    pub fn add_distribution_extra(&mut self, distribution: &str, extra: &str, installs: &str) {
+        let entry = (
+            name_map::normalize_name(distribution),
+            name_map::normalize_name(extra),
+        );
+        let extras = self
+            .extras
+            .entry(name_map::normalize_name(installs))
+            .or_default();
+        if !extras.contains(&entry) {
+            extras.push(entry);
+        }
+    }
+
+    /// Find the extras that install a distribution, as pairs of the distribution the extra
+    /// belongs to and the extra. Extras registered on the index come first, followed by the
+    /// ones in the curated mapping in `name_map`.
+    pub fn extras_installing(&self, distribution: &str) -> Vec<(String, String)> {
+        let name = name_map::normalize_name(distribution);
+        let mut extras = self.extras.get(&name).cloned().unwrap_or_default();
+        for (parent, extra) in name_map::static_extras_installing(&name) {
+            let entry = (name_map::normalize_name(parent), extra.to_string());
+            if !extras.contains(&entry) {
+                extras.push(entry);
+            }
+        }
+        extras
+    }
+
+    /// Register a distribution that is used without being imported, so it's never reported
+    /// as unused
+    pub fn add_ignored_distribution(&mut self, distribution: &str) {
+        self.ignored.insert(name_map::normalize_name(distribution));
+    }
+
+    /// Register a module (or module prefix) the runtime the project runs in provides
+    pub fn add_provided(&mut self, module: &str) {
+        self.provided.insert(module.to_string());
+    }
+
+    /// Check if a module is provided by the runtime, so importing it needs no dependency
+    pub fn is_provided(&self, module: &str) -> bool {
+        self.provided
+            .iter()
+            .any(|prefix| name_map::is_module_prefix(prefix, module))
+    }
+
+    /// Check whether a distribution is expected never to be imported: its registered
+    /// metadata lists no modules, it's a well-known build or support package, or it was
+    /// registered with `add_ignored_distribution`.
+    pub fn unused_exemption(&self, distribution: &str) -> Option<UnusedExemption> {
+        let name = name_map::normalize_name(distribution);
+        let provider = ModuleProvider::Distribution(name.clone());
+        if self.ignored.contains(&name) {
+            Some(UnusedExemption::Ignored)
+        } else if self.described.contains(&name)
+            && !self.providers.values().any(|p| p.contains(&provider))
+        {
+            Some(UnusedExemption::NoModules)
+        } else if name_map::is_support_distribution(&name) {
+            Some(UnusedExemption::SupportPackage)
+        } else {
+            None
+        }
+    }
+
+    /// Find what provides a module using the longest matching prefix.
+    ///
+    /// Backend metadata and first-party modules registered on the index are combined with
+    /// the curated static mapping; when both match at the same depth the index wins. If
+    /// nothing matches, the top-level module name is assumed to be the distribution name.
+    pub fn resolve(&self, module: &str) -> Vec<ModuleProvider> {
+        self.resolve_with_source(module).0
+    }
+
+    /// Find what provides a module like [`resolve`](Self::resolve), along with where the
+    /// providers were found
+    pub(crate) fn resolve_with_source(&self, module: &str) -> (Vec<ModuleProvider>, Resolution) {
+        let indexed = self
+            .providers
+            .iter()
+            .filter(|(prefix, _)| name_map::is_module_prefix(prefix, module))
+            .max_by_key(|(prefix, _)| prefix.len());
+        let curated = self.name_map.distributions_for_module(module);
+
+        match (indexed, curated) {
+            (Some((prefix, providers)), Some((curated_prefix, _)))
+                if prefix.len() >= curated_prefix.len() =>
+            {
+                (providers.clone(), Resolution::Index)
+            }
+            (_, Some((prefix, distributions))) => (
+                distributions
+                    .iter()
+                    .map(|d| ModuleProvider::Distribution(name_map::normalize_name(d)))
+                    .collect(),
+                if self.name_map.is_custom(&prefix) {
+                    Resolution::CustomNameMap
+                } else {
+                    Resolution::NameMap
+                },
+            ),
+            (Some((_, providers)), None) => (providers.clone(), Resolution::Index),
+            (None, None) => {
+                let top_level = module.split('.').next().unwrap_or(module);
+                (
+                    vec![ModuleProvider::Distribution(name_map::normalize_name(
+                        top_level,
+                    ))],
+                    Resolution::ModuleName,
+                )
+            }
+        }
+    }
+
+    /// The module prefixes a distribution is expected to provide: the ones registered on the
+    /// index, otherwise the ones in the curated mapping, otherwise its name as a module
+    pub(crate) fn modules_for(&self, distribution: &str) -> Vec<String> {
+        let provider = ModuleProvider::Distribution(name_map::normalize_name(distribution));
+        let indexed: Vec<String> = self
+            .providers
+            .iter()
+            .filter(|(_, providers)| providers.contains(&provider))
+            .map(|(module, _)| module.clone())
+            .collect();
+        if !indexed.is_empty() {
+            return indexed;
+        }
+        let curated = self.name_map.modules_for_distribution(distribution);
+        if !curated.is_empty() {
+            return curated;
+        }
+        vec![name_map::normalize_name(distribution).replace('-', "_")]
+    }
+
+    /// The module a `from module import name` needs a distribution for: `module.name` when
+    /// that resolves to other providers than `module`, as for `from google.cloud import
+    /// storage` where `google.cloud` is a namespace package shared by many distributions,
+    /// and `module` otherwise, including when `name` is only an attribute of it.
+    pub fn from_import_module(&self, module: &str, name: &str) -> String {
+        let qualified = format!("{}.{}", module, name);
+        let (providers, resolution) = self.resolve_with_source(&qualified);
+        if resolution != Resolution::ModuleName && providers != self.resolve(module) {
+            qualified
+        } else {
+            module.to_string()
+        }
+    }
+
+    /// Check if a module is part of the project being analyzed
+    pub fn is_first_party(&self, module: &str) -> bool {
+        self.resolve(module).contains(&ModuleProvider::FirstParty)
+    }
+
+    /// Find the declared dependency that provides a module, if any
+    pub fn find_dependency<'a>(
+        &self,
+        module: &str,
+        dependencies: &'a [Dependency],
+    ) -> Option<&'a Dependency> {
+        self.resolve(module)
+            .iter()
+            .filter_map(|provider| match provider {
+                ModuleProvider::Distribution(name) => Some(name),
+                ModuleProvider::FirstParty => None,
+            })
+            .find_map(|name| dependencies.iter().find(|dep| dep.satisfies(name)))
+    }
+}
+
+/// Compute the importable module prefixes described by a RECORD file
+fn modules_from_record(record: &str) -> Vec<String> {
+    let files: Vec<&str> = record
+        .lines()
+        .filter_map(|line| line.split(',').next())
+        .map(str::trim)
+        .filter(|path| {
+            !path.is_empty()
+                && !path.starts_with("..")
+                && !path.contains("__pycache__")
+                && !path
+                    .split('/')
+                    .next()
+                    .is_some_and(|root| root.ends_with(".dist-info") || root.ends_with(".data"))
+        })
+        .collect();
+
+    let package_dirs: HashSet<&str> = files
+        .iter()
+        .filter_map(|path| path.rsplit_once('/'))
+        .filter(|(_, file)| file.starts_with("__init__."))
+        .map(|(dir, _)| dir)
+        .collect();
+
+    let mut modules = Vec::new();
+    for path in files {
+        let is_module = path.ends_with(".py")
+            || path.ends_with(".so")
+            || path.ends_with(".pyd")
+            || path.ends_with(".pyi");
+        if !is_module {
+            continue;
+        }
+
+        let segments: Vec<&str> = path.split('/').collect();
+        let (dirs, file) = segments.split_at(segments.len() - 1);
+
+        // The module is the first directory (from the root) that is a regular package,
+        // everything above it is a namespace package shared with other distributions
+        let package_depth =
+            (1..=dirs.len()).find(|depth| package_dirs.contains(dirs[..*depth].join("/").as_str()));
+
+        let module = match package_depth {
+            Some(depth) => dirs[..depth].join("."),
+            None => {
+                let stem = file[0].split('.').next().unwrap_or(file[0]);
+                if stem == "__init__" || stem.is_empty() {
+                    continue;
+                }
+                let mut parts = dirs.to_vec();
+                parts.push(stem);
+                parts.join(".")
+            }
+        };
+
+        if !modules.contains(&module) {
+            modules.push(module);
+        }
+    }
+    modules
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::project::PyPIRequirement;
+    use std::str::FromStr;
+
+    const GOOGLE_CLOUD_STORAGE_RECORD: &str = "\
+google/cloud/storage/__init__.py,sha256=abc,100
+google/cloud/storage/blob.py,sha256=abc,100
+google_cloud_storage-2.16.0.dist-info/RECORD,,
+google_cloud_storage-2.16.0.dist-info/top_level.txt,sha256=abc,7
+";
+
+    const PROTOBUF_RECORD: &str = "\
+google/protobuf/__init__.py,sha256=abc,100
+google/protobuf/message.py,sha256=abc,100
+google/_upb/_message.abi3.so,sha256=abc,100
+protobuf-5.26.1.dist-info/RECORD,,
+";
+
+    fn dist(name: &str) -> ModuleProvider {
+        ModuleProvider::Distribution(name.to_string())
+    }
+
+    #[test]
+    fn test_modules_from_record() {
+        assert_eq!(
+            modules_from_record(GOOGLE_CLOUD_STORAGE_RECORD),
+            vec!["google.cloud.storage".to_string()]
+        );
+        assert_eq!(
+            modules_from_record(PROTOBUF_RECORD),
+            vec![
+                "google.protobuf".to_string(),
+                "google._upb._message".to_string()
+            ]
+        );
+        assert_eq!(
+            modules_from_record("six.py,sha256=abc,100\nsix-1.16.0.dist-info/RECORD,,\n"),
+            vec!["six".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_google_namespace_from_record() {
+        let mut index = ModuleIndex::new();
+        index.add_distribution_record("google-cloud-storage", GOOGLE_CLOUD_STORAGE_RECORD);
+        index.add_distribution_record("protobuf", PROTOBUF_RECORD);
+
+        assert_eq!(
+            index.resolve("google.cloud.storage.blob"),
+            vec![dist("google-cloud-storage")]
+        );
+        assert_eq!(
+            index.resolve("google.protobuf.message"),
+            vec![dist("protobuf")]
+        );
+    }
+
+    #[test]
+    fn test_longest_prefix_beats_top_level() {
+        let mut index = ModuleIndex::new();
+        index.add_distribution_top_level("google-cloud-storage", "google\n");
+        index.add_distribution_top_level("google-auth", "google\n");
+        index.add_distribution_record("google-cloud-storage", GOOGLE_CLOUD_STORAGE_RECORD);
+
+        assert_eq!(
+            index.resolve("google.cloud.storage"),
+            vec![dist("google-cloud-storage")]
+        );
+        // Only top_level.txt knows about this one, so every `google` distribution matches
+        assert_eq!(
+            index.resolve("google.oauth2"),
+            vec![dist("google-cloud-storage"), dist("google-auth")]
+        );
+    }
+
+    #[test]
+    fn test_simulated_fallback_to_static_mapping() {
+        let index = ModuleIndex::new();
+
+        assert_eq!(
+            index.resolve("google.cloud.bigquery.client"),
+            vec![dist("google-cloud-bigquery")]
+        );
+        assert_eq!(
+            index.resolve("azure.storage.blob"),
+            vec![dist("azure-storage-blob")]
+        );
+        assert_eq!(
+            index.resolve("azure.identity.aio"),
+            vec![dist("azure-identity")]
+        );
+        assert_eq!(index.resolve("yaml"), vec![dist("pyyaml")]);
+        // Unknown modules assume the distribution is named after the top-level package
+        assert_eq!(index.resolve("requests.adapters"), vec![dist("requests")]);
+    }
+
+    #[test]
+    fn test_azure_namespace_from_record() {
+        let mut index = ModuleIndex::new();
+        index.add_distribution_record(
+            "azure-storage-blob",
+            "azure/storage/blob/__init__.py,sha256=abc,100\n",
+        );
+        index.add_distribution_record("azure-core", "azure/core/__init__.py,sha256=abc,100\n");
+
+        assert_eq!(
+            index.resolve("azure.storage.blob.aio"),
+            vec![dist("azure-storage-blob")]
+        );
+        assert_eq!(
+            index.resolve("azure.core.exceptions"),
+            vec![dist("azure-core")]
+        );
+    }
+
+    #[test]
+    fn test_from_import_of_namespace_package() {
+        let mut index = ModuleIndex::new();
+        index.add_distribution_record("google-cloud-storage", GOOGLE_CLOUD_STORAGE_RECORD);
+
+        // `from google.cloud import storage` imports a submodule of a namespace package
+        assert_eq!(
+            index.from_import_module("google.cloud", "storage"),
+            "google.cloud.storage"
+        );
+        assert_eq!(
+            index.from_import_module("azure", "identity"),
+            "azure.identity"
+        );
+        // Names that are attributes of a module, or submodules of the same distribution,
+        // are attributed to the module
+        assert_eq!(
+            index.from_import_module("google.cloud.storage", "Client"),
+            "google.cloud.storage"
+        );
+        assert_eq!(index.from_import_module("requests", "get"), "requests");
+        assert_eq!(index.from_import_module("yaml", "safe_load"), "yaml");
+
+        let dependencies = vec![
+            Dependency::PyPI(PyPIRequirement::from_str("google-cloud-storage").unwrap()),
+            Dependency::PyPI(PyPIRequirement::from_str("azure-identity").unwrap()),
+        ];
+        assert_eq!(
+            index.find_dependency(
+                &index.from_import_module("google.cloud", "storage"),
+                &dependencies
+            ),
+            Some(&dependencies[0])
+        );
+        assert_eq!(
+            index.find_dependency(
+                &index.from_import_module("azure", "identity"),
+                &dependencies
+            ),
+            Some(&dependencies[1])
+        );
+    }
+
+    #[test]
+    fn test_first_party_namespace_package() {
+        let mut index = ModuleIndex::new();
+        index.add_first_party("acme.tools");
+        index.add_distribution_record("acme-billing", "acme/billing/__init__.py,sha256=abc,100\n");
+
+        assert!(index.is_first_party("acme.tools.cli"));
+        assert!(!index.is_first_party("acme.billing"));
+        assert_eq!(
+            index.resolve("acme.billing.invoice"),
+            vec![dist("acme-billing")]
+        );
+    }
+
+    #[test]
+    fn test_find_dependency() {
+        let index = ModuleIndex::new();
+        let dependencies = vec![
+            Dependency::PyPI(PyPIRequirement::from_str("google-cloud-storage>=2").unwrap()),
+            Dependency::PyPI(PyPIRequirement::from_str("PyYAML").unwrap()),
+        ];
+
+        assert_eq!(
+            index.find_dependency("google.cloud.storage", &dependencies),
+            Some(&dependencies[0])
+        );
+        assert_eq!(
+            index.find_dependency("yaml", &dependencies),
+            Some(&dependencies[1])
+        );
+        assert_eq!(
+            index.find_dependency("google.cloud.bigquery", &dependencies),
+            None
+        );
+    }
+
+    #[test]
+    fn test_unused_exemption() {
+        let mut index = ModuleIndex::new();
+        index.add_distribution_record(
+            "fonts-roboto",
+            "fonts_roboto-1.0.dist-info/RECORD,,\nshare/fonts/Roboto-Regular.ttf,sha256=abc,100\n",
+        );
+        index.add_distribution_top_level("requests", "requests\n");
+        index.add_ignored_distribution("Sphinx_RTD_Theme");
+
+        assert_eq!(
+            index.unused_exemption("fonts-roboto"),
+            Some(UnusedExemption::NoModules)
+        );
+        assert_eq!(
+            index.unused_exemption("setuptools_scm"),
+            Some(UnusedExemption::SupportPackage)
+        );
+        assert_eq!(
+            index.unused_exemption("sphinx-rtd-theme"),
+            Some(UnusedExemption::Ignored)
+        );
+        assert_eq!(index.unused_exemption("requests"), None);
+        // Without metadata nothing is known about what a distribution provides
+        assert_eq!(index.unused_exemption("flask"), None);
+    }
+
+    #[test]
+    fn test_extras_installing() {
+        let mut index = ModuleIndex::new();
+        index.add_distribution_extra("Pandas", "Parquet", "PyArrow");
+        index.add_distribution_extra("polars", "pyarrow", "pyarrow");
+
+        // Registered extras come first, and the curated ones that duplicate them are skipped
+        assert_eq!(
+            index.extras_installing("pyarrow"),
+            vec![
+                ("pandas".to_string(), "parquet".to_string()),
+                ("polars".to_string(), "pyarrow".to_string()),
+                ("pandas".to_string(), "feather".to_string()),
+            ]
+        );
+        assert_eq!(
+            index.extras_installing("PySocks"),
+            vec![("requests".to_string(), "socks".to_string())]
+        );
+        assert!(index.extras_installing("requests").is_empty());
+    }
+}
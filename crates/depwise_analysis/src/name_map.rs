@@ -0,0 +1,585 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::error::AnalysisError;
+
+/// Curated list of packages that are published under different names on conda and PyPI.
+///
+/// Each entry is `(conda name, PyPI name)`. A PyPI project can be repackaged under
+/// several conda names (e.g. `opencv` and `py-opencv`); when mapping back from PyPI the
+/// first entry wins, so the canonical conda name should be listed first.
+const CONDA_PYPI_ALIASES: &[(&str, &str)] = &[
+    ("pytorch", "torch"),
+    ("pytorch-cpu", "torch"),
+    ("pytorch-gpu", "torch"),
+    ("opencv", "opencv-python"),
+    ("py-opencv", "opencv-python"),
+    ("py-xgboost", "xgboost"),
+    ("py-lief", "lief"),
+    ("pyqt", "pyqt5"),
+    ("pytables", "tables"),
+    ("matplotlib-base", "matplotlib"),
+    ("msgpack-python", "msgpack"),
+    ("python-graphviz", "graphviz"),
+    ("python-kaleido", "kaleido"),
+    ("python-duckdb", "duckdb"),
+    ("python-lmdb", "lmdb"),
+    ("python-flatbuffers", "flatbuffers"),
+    ("python-xxhash", "xxhash"),
+];
+
+/// Curated list of import names that are provided by a distribution with a different name.
+///
+/// Each entry is `(module prefix, PyPI name)`. Module prefixes may span several segments
+/// for distributions that share a namespace package (e.g. `google.cloud.storage`), in
+/// which case the longest matching prefix is used.
+const IMPORT_DISTRIBUTIONS: &[(&str, &str)] = &[
+    ("PIL", "pillow"),
+    ("attr", "attrs"),
+    ("azure.core", "azure-core"),
+    ("azure.identity", "azure-identity"),
+    ("azure.keyvault.secrets", "azure-keyvault-secrets"),
+    ("azure.storage.blob", "azure-storage-blob"),
+    ("azure.storage.queue", "azure-storage-queue"),
+    ("bs4", "beautifulsoup4"),
+    ("cv2", "opencv-python"),
+    ("dateutil", "python-dateutil"),
+    ("dotenv", "python-dotenv"),
+    ("git", "gitpython"),
+    ("google.api_core", "google-api-core"),
+    ("google.auth", "google-auth"),
+    ("google.cloud.bigquery", "google-cloud-bigquery"),
+    ("google.cloud.pubsub", "google-cloud-pubsub"),
+    ("google.cloud.pubsub_v1", "google-cloud-pubsub"),
+    ("google.cloud.storage", "google-cloud-storage"),
+    ("google.protobuf", "protobuf"),
+    ("grpc", "grpcio"),
+    ("jwt", "pyjwt"),
+    ("magic", "python-magic"),
+    ("pythoncom", "pywin32"),
+    ("pywintypes", "pywin32"),
+    ("serial", "pyserial"),
+    ("skimage", "scikit-image"),
+    ("sklearn", "scikit-learn"),
+    ("win32api", "pywin32"),
+    ("win32com", "pywin32"),
+    ("win32con", "pywin32"),
+    ("win32gui", "pywin32"),
+    ("yaml", "pyyaml"),
+    ("zmq", "pyzmq"),
+];
+
+/// Curated list of distributions that are installed by an extra of another distribution.
+///
+/// Each entry is `(distribution, extra, PyPI name of what the extra installs)`.
+const EXTRA_DISTRIBUTIONS: &[(&str, &str, &str)] = &[
+    ("aiohttp", "speedups", "aiodns"),
+    ("aiohttp", "speedups", "brotli"),
+    ("boto3", "crt", "awscrt"),
+    ("celery", "redis", "redis"),
+    ("dask", "array", "numpy"),
+    ("dask", "dataframe", "pandas"),
+    ("dask", "distributed", "distributed"),
+    ("fastapi", "standard", "email-validator"),
+    ("fastapi", "standard", "httpx"),
+    ("fastapi", "standard", "jinja2"),
+    ("fastapi", "standard", "python-multipart"),
+    ("fastapi", "standard", "uvicorn"),
+    ("httpx", "brotli", "brotli"),
+    ("httpx", "cli", "click"),
+    ("httpx", "cli", "rich"),
+    ("httpx", "http2", "h2"),
+    ("httpx", "socks", "socksio"),
+    ("pandas", "excel", "openpyxl"),
+    ("pandas", "feather", "pyarrow"),
+    ("pandas", "parquet", "pyarrow"),
+    ("pandas", "performance", "bottleneck"),
+    ("pandas", "performance", "numexpr"),
+    ("pandas", "plot", "matplotlib"),
+    ("pandas", "sql-other", "sqlalchemy"),
+    ("pandas", "xml", "lxml"),
+    ("pydantic", "email", "email-validator"),
+    ("requests", "socks", "pysocks"),
+    ("requests", "use-chardet-on-py3", "chardet"),
+    ("sqlalchemy", "asyncio", "greenlet"),
+    ("sqlalchemy", "postgresql", "psycopg2"),
+    ("uvicorn", "standard", "httptools"),
+    ("uvicorn", "standard", "uvloop"),
+    ("uvicorn", "standard", "watchfiles"),
+    ("uvicorn", "standard", "websockets"),
+];
+
+/// Distributions a project depends on without importing them: build backends and tools, and
+/// packages that support other packages at runtime, like CA bundles and time zone data.
+const SUPPORT_DISTRIBUTIONS: &[&str] = &[
+    "build",
+    "certifi",
+    "cython",
+    "flit-core",
+    "hatch-vcs",
+    "hatchling",
+    "maturin",
+    "meson-python",
+    "pdm-backend",
+    "pip",
+    "poetry-core",
+    "pybind11",
+    "scikit-build-core",
+    "setuptools",
+    "setuptools-scm",
+    "tzdata",
+    "wheel",
+];
+
+/// The runtimes whose modules are known to be provided, with those modules
+const PROFILES: &[(&str, &[&str])] = &[
+    (
+        "databricks",
+        &["databricks", "dbruntime", "dbutils", "delta", "pyspark"],
+    ),
+    ("glue", &["awsglue", "pyspark"]),
+];
+
+/// Normalize a distribution name according to PEP 503 (lowercase, runs of `-`, `_` and `.`
+/// collapsed to a single `-`).
+pub fn normalize_name(name: &str) -> String {
+    let mut normalized = String::with_capacity(name.len());
+    let mut last_was_separator = false;
+    for c in name.trim().chars() {
+        if c == '-' || c == '_' || c == '.' {
+            if !last_was_separator {
+                normalized.push('-');
+            }
+            last_was_separator = true;
+        } else {
+            normalized.push(c.to_ascii_lowercase());
+            last_was_separator = false;
+        }
+    }
+    normalized
+}
+
+/// Get the PyPI name of a package known on conda under a different name.
+///
+/// Returns `None` if the package is not in the alias table, in which case the conda
+/// and PyPI names are assumed to be the same.
+pub fn conda_to_pypi(name: &str) -> Option<&'static str> {
+    let name = normalize_name(name);
+    CONDA_PYPI_ALIASES
+        .iter()
+        .find(|(conda, _)| normalize_name(conda) == name)
+        .map(|(_, pypi)| *pypi)
+}
+
+/// Get the conda name of a package known on PyPI under a different name.
+///
+/// Returns `None` if the package is not in the alias table, in which case the conda
+/// and PyPI names are assumed to be the same.
+pub fn pypi_to_conda(name: &str) -> Option<&'static str> {
+    let name = normalize_name(name);
+    CONDA_PYPI_ALIASES
+        .iter()
+        .find(|(_, pypi)| normalize_name(pypi) == name)
+        .map(|(conda, _)| *conda)
+}
+
+/// Check if a conda package name and a PyPI distribution name refer to the same
+/// logical package.
+pub fn conda_matches_pypi(conda_name: &str, pypi_name: &str) -> bool {
+    let pypi_name = normalize_name(pypi_name);
+    let conda_as_pypi = conda_to_pypi(conda_name)
+        .map(normalize_name)
+        .unwrap_or_else(|| normalize_name(conda_name));
+    conda_as_pypi == pypi_name
+}
+
+/// Get the distributions known to provide a module from the curated mapping, paired with
+/// the module prefix they were matched on.
+///
+/// Only the longest matching prefix is considered, so `google.cloud.storage.blob` matches
+/// `google-cloud-storage` and not every distribution in the `google` namespace.
+pub fn static_distributions_for_module(module: &str) -> Option<(&'static str, Vec<&'static str>)> {
+    let mut best: Option<(&'static str, Vec<&'static str>)> = None;
+    for (prefix, distribution) in IMPORT_DISTRIBUTIONS {
+        if !is_module_prefix(prefix, module) {
+            continue;
+        }
+        match &mut best {
+            Some((best_prefix, distributions)) if best_prefix.len() == prefix.len() => {
+                distributions.push(distribution);
+            }
+            Some((best_prefix, _)) if best_prefix.len() > prefix.len() => {}
+            _ => best = Some((prefix, vec![distribution])),
+        }
+    }
+    best
+}
+
+/// Get the module prefixes the curated mapping says a distribution provides
+pub fn static_modules_for_distribution(distribution: &str) -> Vec<&'static str> {
+    let name = normalize_name(distribution);
+    IMPORT_DISTRIBUTIONS
+        .iter()
+        .filter(|(_, provider)| normalize_name(provider) == name)
+        .map(|(prefix, _)| *prefix)
+        .collect()
+}
+
+/// Get the extras that install a distribution from the curated mapping, as pairs of the
+/// distribution the extra belongs to and the name of the extra
+pub fn static_extras_installing(distribution: &str) -> Vec<(&'static str, &'static str)> {
+    let name = normalize_name(distribution);
+    EXTRA_DISTRIBUTIONS
+        .iter()
+        .filter(|(_, _, installed)| normalize_name(installed) == name)
+        .map(|(parent, extra, _)| (*parent, *extra))
+        .collect()
+}
+
+/// Check if a distribution is a well-known build or runtime support package, which is used
+/// without ever being imported
+pub fn is_support_distribution(name: &str) -> bool {
+    SUPPORT_DISTRIBUTIONS.contains(&normalize_name(name).as_str())
+}
+
+/// The names of the runtimes [`profile_modules`] knows
+pub fn profiles() -> impl Iterator<Item = &'static str> {
+    PROFILES.iter().map(|(name, _)| *name)
+}
+
+/// Get the modules a runtime like Databricks or AWS Glue provides to the code it runs
+pub fn profile_modules(profile: &str) -> Option<&'static [&'static str]> {
+    PROFILES
+        .iter()
+        .find(|(name, _)| *name == profile)
+        .map(|(_, modules)| *modules)
+}
+
+/// Check if `prefix` is `module` or one of its parent packages.
+pub fn is_module_prefix(prefix: &str, module: &str) -> bool {
+    module == prefix
+        || (module.starts_with(prefix) && module.as_bytes().get(prefix.len()) == Some(&b'.'))
+}
+
+/// Mappings between modules and the distributions that provide them, given by the user on
+/// top of the curated ones, like the modules of a team's internal packages.
+///
+/// Custom entries win over the curated mapping when they match a module at the same depth.
+/// Every entry works both ways: it tells which distribution provides an import, and which
+/// modules a declared distribution is expected to be imported as.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NameMap {
+    /// Module prefixes and the normalized names of the distributions that provide them
+    modules: BTreeMap<String, Vec<String>>,
+    /// Entries of the file the map was loaded from that disagree with each other
+    conflicts: Vec<String>,
+}
+
+/// A name map file: `distributions` lists the modules each distribution provides, and
+/// `modules` the distributions that provide each module. Either side takes one name or a list.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NameMapFile {
+    #[serde(default)]
+    distributions: BTreeMap<String, Names>,
+    #[serde(default)]
+    modules: BTreeMap<String, Names>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Names {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Names {
+    fn into_vec(self) -> Vec<String> {
+        match self {
+            Names::One(name) => vec![name],
+            Names::Many(names) => names,
+        }
+    }
+}
+
+impl NameMap {
+    /// A map with only the curated entries
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Map a module prefix, like `acme` or `acme.sdk`, to a distribution that provides it
+    pub fn insert(&mut self, module: &str, distribution: &str) {
+        let distributions = self.modules.entry(module.to_string()).or_default();
+        let distribution = normalize_name(distribution);
+        if !distributions.contains(&distribution) {
+            distributions.push(distribution);
+        }
+    }
+
+    /// Whether there are no custom entries
+    pub fn is_empty(&self) -> bool {
+        self.modules.is_empty()
+    }
+
+    /// Whether a module prefix has a custom entry
+    pub fn is_custom(&self, prefix: &str) -> bool {
+        self.modules.contains_key(prefix)
+    }
+
+    /// Entries of the loaded file that disagree with each other, like a module the
+    /// `modules` table maps to other distributions than the one `distributions` says provides
+    /// it. Both are kept.
+    pub fn conflicts(&self) -> &[String] {
+        &self.conflicts
+    }
+
+    /// Get the distributions known to provide a module, paired with the module prefix they
+    /// were matched on, like [`static_distributions_for_module`] with the custom entries
+    pub fn distributions_for_module(&self, module: &str) -> Option<(String, Vec<String>)> {
+        let custom = self
+            .modules
+            .iter()
+            .filter(|(prefix, _)| is_module_prefix(prefix, module))
+            .max_by_key(|(prefix, _)| prefix.len());
+        let curated = static_distributions_for_module(module);
+        match (custom, curated) {
+            (Some((prefix, distributions)), Some((curated_prefix, _)))
+                if prefix.len() >= curated_prefix.len() =>
+            {
+                Some((prefix.clone(), distributions.clone()))
+            }
+            (_, Some((prefix, distributions))) => Some((
+                prefix.to_string(),
+                distributions.into_iter().map(str::to_string).collect(),
+            )),
+            (Some((prefix, distributions)), None) => Some((prefix.clone(), distributions.clone())),
+            (None, None) => None,
+        }
+    }
+
+    /// Get the module prefixes a distribution is expected to provide: its custom entries,
+    /// otherwise its curated ones
+    pub fn modules_for_distribution(&self, distribution: &str) -> Vec<String> {
+        let name = normalize_name(distribution);
+        let custom: Vec<String> = self
+            .modules
+            .iter()
+            .filter(|(_, distributions)| distributions.contains(&name))
+            .map(|(module, _)| module.clone())
+            .collect();
+        if !custom.is_empty() {
+            return custom;
+        }
+        static_modules_for_distribution(distribution)
+            .into_iter()
+            .map(str::to_string)
+            .collect()
+    }
+}
+
+/// Load a name map from a TOML or JSON file, told apart by its extension.
+///
+/// ```toml
+/// # The modules each distribution provides
+/// [distributions]
+/// acme-sdk = ["acme", "acme_cli"]
+/// pyyaml-fork = "yaml"
+///
+/// # The distributions that provide each module, for modules several can provide
+/// [modules]
+/// "cv2" = ["opencv-python", "opencv-python-headless"]
+/// ```
+///
+/// JSON files have the same shape: `{"distributions": {"acme-sdk": ["acme"]}}`.
+pub fn load_name_map(path: &Path) -> Result<NameMap, AnalysisError> {
+    let invalid =
+        |message: String| AnalysisError::NameMapError(path.display().to_string(), message);
+    let content = fs::read_to_string(path).map_err(|e| {
+        AnalysisError::FileReadError(path.to_string_lossy().to_string(), e.to_string())
+    })?;
+    let file: NameMapFile = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("toml") => toml::from_str(&content).map_err(|e| invalid(e.to_string()))?,
+        Some("json") => serde_json::from_str(&content).map_err(|e| invalid(e.to_string()))?,
+        _ => return Err(invalid("expected a .toml or .json file".to_string())),
+    };
+
+    let mut map = NameMap::new();
+    for (module, distributions) in file.modules {
+        for distribution in distributions.into_vec() {
+            map.insert(&module, &distribution);
+        }
+    }
+    // `modules` lists every distribution that provides a module, so a distribution that
+    // says it provides one without being listed there disagrees with it
+    let listed = map.modules.clone();
+    for (distribution, modules) in file.distributions {
+        for module in modules.into_vec() {
+            if let Some(listed) = listed.get(&module)
+                && !listed.contains(&normalize_name(&distribution))
+            {
+                map.conflicts.push(format!(
+                    "`{}` provides `{}` under `distributions`, but `modules` maps it to {}",
+                    distribution,
+                    module,
+                    listed
+                        .iter()
+                        .map(|name| format!("`{}`", name))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ));
+            }
+            map.insert(&module, &distribution);
+        }
+    }
+    Ok(map)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::project::{CondaMatchSpec, Dependency, PyPIRequirement};
+    use std::str::FromStr;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_normalize_name() {
+        assert_eq!(normalize_name("Friendly_Bard"), "friendly-bard");
+        assert_eq!(normalize_name("ruamel.yaml"), "ruamel-yaml");
+        assert_eq!(normalize_name("a-_.b"), "a-b");
+    }
+
+    #[test]
+    fn test_pytorch_torch_aliases() {
+        assert_eq!(conda_to_pypi("pytorch"), Some("torch"));
+        assert_eq!(pypi_to_conda("torch"), Some("pytorch"));
+        assert!(conda_matches_pypi("pytorch", "torch"));
+        assert!(!conda_matches_pypi("pytorch", "pytorch-lightning"));
+    }
+
+    #[test]
+    fn test_opencv_aliases() {
+        assert_eq!(conda_to_pypi("opencv"), Some("opencv-python"));
+        assert_eq!(conda_to_pypi("py-opencv"), Some("opencv-python"));
+        assert_eq!(pypi_to_conda("opencv-python"), Some("opencv"));
+        assert_eq!(pypi_to_conda("opencv_python"), Some("opencv"));
+    }
+
+    #[test]
+    fn test_unaliased_names() {
+        assert_eq!(conda_to_pypi("numpy"), None);
+        assert_eq!(pypi_to_conda("numpy"), None);
+        assert!(conda_matches_pypi("numpy", "NumPy"));
+    }
+
+    #[test]
+    fn test_dependency_satisfies_across_ecosystems() {
+        let conda = Dependency::Conda(CondaMatchSpec::new("conda-forge::pytorch>=2.0"));
+        assert!(conda.satisfies("torch"));
+        assert!(!conda.satisfies("pytorch-lightning"));
+
+        let pypi = Dependency::PyPI(PyPIRequirement::from_str("opencv-python>=4").unwrap());
+        assert!(pypi.satisfies("opencv-python"));
+        assert!(pypi.satisfies_conda("opencv"));
+        assert!(pypi.satisfies_conda("py-opencv"));
+        assert!(!pypi.satisfies_conda("pytorch"));
+    }
+
+    #[test]
+    fn test_load_name_map() -> Result<(), AnalysisError> {
+        let dir = tempdir().unwrap();
+        let toml_path = dir.path().join("names.toml");
+        fs::write(
+            &toml_path,
+            r#"[distributions]
+acme-sdk = ["acme", "acme_cli"]
+Acme_Yaml = "yaml"
+
+[modules]
+"acme.plugins" = ["acme-plugins", "acme-plugins-lite"]
+"#,
+        )
+        .unwrap();
+        let json_path = dir.path().join("names.json");
+        fs::write(
+            &json_path,
+            r#"{
+  "distributions": {"acme-sdk": ["acme", "acme_cli"], "Acme_Yaml": "yaml"},
+  "modules": {"acme.plugins": ["acme-plugins", "acme-plugins-lite"]}
+}"#,
+        )
+        .unwrap();
+
+        let map = load_name_map(&toml_path)?;
+        assert_eq!(map, load_name_map(&json_path)?);
+        assert!(map.conflicts().is_empty());
+
+        // Custom entries are looked up alongside the curated ones, by the longest prefix
+        assert_eq!(
+            map.distributions_for_module("acme.client"),
+            Some(("acme".to_string(), vec!["acme-sdk".to_string()]))
+        );
+        assert_eq!(
+            map.distributions_for_module("acme.plugins.csv"),
+            Some((
+                "acme.plugins".to_string(),
+                vec!["acme-plugins".to_string(), "acme-plugins-lite".to_string()]
+            ))
+        );
+        assert_eq!(
+            map.distributions_for_module("yaml"),
+            Some(("yaml".to_string(), vec!["acme-yaml".to_string()]))
+        );
+        assert_eq!(
+            map.distributions_for_module("sklearn.linear_model"),
+            Some(("sklearn".to_string(), vec!["scikit-learn".to_string()]))
+        );
+        // And the other way around
+        assert_eq!(
+            map.modules_for_distribution("acme-sdk"),
+            ["acme", "acme_cli"]
+        );
+        assert_eq!(map.modules_for_distribution("pyyaml"), ["yaml"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_name_map_conflicts() -> Result<(), AnalysisError> {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("names.toml");
+        fs::write(
+            &path,
+            "[distributions]\nacme-sdk = [\"acme\"]\n\n[modules]\nacme = \"acme-core\"\n",
+        )
+        .unwrap();
+        let map = load_name_map(&path)?;
+        assert_eq!(
+            map.conflicts(),
+            [
+                "`acme-sdk` provides `acme` under `distributions`, but `modules` maps it to `acme-core`"
+            ]
+        );
+        assert_eq!(
+            map.distributions_for_module("acme"),
+            Some((
+                "acme".to_string(),
+                vec!["acme-core".to_string(), "acme-sdk".to_string()]
+            ))
+        );
+
+        fs::write(dir.path().join("names.yaml"), "").unwrap();
+        assert!(matches!(
+            load_name_map(&dir.path().join("names.yaml")),
+            Err(AnalysisError::NameMapError(..))
+        ));
+        fs::write(&path, "[distribution]\nacme-sdk = \"acme\"\n").unwrap();
+        assert!(matches!(
+            load_name_map(&path),
+            Err(AnalysisError::NameMapError(..))
+        ));
+        Ok(())
+    }
+}
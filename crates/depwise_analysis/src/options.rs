@@ -0,0 +1,630 @@
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::cancel::CancellationToken;
+use crate::env_backend::Backend;
+use crate::finding::Rule;
+use crate::project::SourceRegistry;
+use crate::{EnvironmentBackend, EnvironmentBuilderSource, PythonVersion};
+
+/// The size above which Python files are skipped by default, 5 MiB. Files this large are
+/// generated, and parsing them would only slow the analysis down.
+pub const DEFAULT_MAX_FILE_SIZE: u64 = 5 * 1024 * 1024;
+
+/// How many requirements a requirements file may declare by default, along with the files
+/// it includes with `-r`. Real projects stay far below it; a tree of includes fanning out
+/// by accident doesn't.
+pub const DEFAULT_MAX_REQUIREMENTS: usize = 100_000;
+
+/// The patterns of files generated by code generators and migration tools: protobuf and
+/// gRPC modules, Django migrations, and Alembic revisions
+pub(crate) const DEFAULT_GENERATED_PATTERNS: &[&str] = &[
+    "*_pb2.py",
+    "*_pb2.pyi",
+    "*_pb2_grpc.py",
+    "migrations/*.py",
+    "alembic/versions/*.py",
+];
+
+/// Settings for analyzing a project.
+///
+/// Options are created with [`AnalysisOptions::builder`]; only the project root is required.
+///
+/// ```
+/// use depwise_analysis::{AnalysisOptions, EnvironmentBackend, EnvironmentBuilderSource};
+///
+/// let options = AnalysisOptions::builder("my-project")
+///     .source(EnvironmentBuilderSource::PyProjectToml(
+///         "my-project/pyproject.toml".into(),
+///     ))
+///     .backend(EnvironmentBackend::Simulated)
+///     .exclude("tests/fixtures")
+///     .extra("docs")
+///     .jobs(4)
+///     .build();
+///
+/// assert_eq!(options.extras(), Some(&["docs".to_string()][..]));
+/// assert_eq!(options.jobs(), 4);
+/// ```
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct AnalysisOptions {
+    path: PathBuf,
+    sources: Vec<EnvironmentBuilderSource>,
+    source_registry: Arc<SourceRegistry>,
+    backend: EnvironmentBackend,
+    custom_backend: Option<Arc<dyn Backend>>,
+    target_environment: Option<String>,
+    excludes: Vec<PathBuf>,
+    default_excludes: bool,
+    follow_symlinks: bool,
+    files: Option<Vec<PathBuf>>,
+    extras: Option<Vec<String>>,
+    configuration_names: Vec<String>,
+    exclusive_extras: Vec<Vec<String>>,
+    platforms: Option<Vec<String>>,
+    jobs: Option<NonZeroUsize>,
+    cache_dir: Option<PathBuf>,
+    use_cache: bool,
+    backend_timeout: Option<Duration>,
+    check_unused_imports: bool,
+    include_tests: bool,
+    include_docs: bool,
+    script_directories: Vec<PathBuf>,
+    report_script_only: bool,
+    generated_patterns: Vec<String>,
+    report_generated: bool,
+    test_runners: bool,
+    ignore_unused: Vec<String>,
+    provided_modules: Vec<String>,
+    name_map: Option<PathBuf>,
+    offline: bool,
+    strict_parse: bool,
+    max_file_size: Option<u64>,
+    max_requirements: Option<usize>,
+    python_version: Option<PythonVersion>,
+    skip_rules: Vec<Rule>,
+    cancellation_token: CancellationToken,
+}
+
+impl AnalysisOptions {
+    /// Start building options for the project rooted at `path`
+    pub fn builder(path: impl Into<PathBuf>) -> AnalysisOptionsBuilder {
+        AnalysisOptionsBuilder {
+            options: AnalysisOptions {
+                path: path.into(),
+                sources: Vec::new(),
+                source_registry: Arc::new(SourceRegistry::default()),
+                backend: EnvironmentBackend::Auto,
+                custom_backend: None,
+                target_environment: None,
+                excludes: Vec::new(),
+                default_excludes: true,
+                follow_symlinks: false,
+                files: None,
+                extras: None,
+                configuration_names: Vec::new(),
+                exclusive_extras: Vec::new(),
+                platforms: None,
+                jobs: None,
+                cache_dir: None,
+                use_cache: true,
+                backend_timeout: None,
+                check_unused_imports: false,
+                include_tests: true,
+                include_docs: false,
+                script_directories: vec![PathBuf::from("scripts")],
+                report_script_only: true,
+                generated_patterns: DEFAULT_GENERATED_PATTERNS
+                    .iter()
+                    .map(|pattern| pattern.to_string())
+                    .collect(),
+                report_generated: false,
+                test_runners: true,
+                ignore_unused: Vec::new(),
+                provided_modules: Vec::new(),
+                name_map: None,
+                offline: false,
+                strict_parse: false,
+                max_file_size: Some(DEFAULT_MAX_FILE_SIZE),
+                max_requirements: Some(DEFAULT_MAX_REQUIREMENTS),
+                python_version: None,
+                skip_rules: Vec::new(),
+                cancellation_token: CancellationToken::new(),
+            },
+        }
+    }
+
+    /// The project root (or single file) that is analyzed
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The files dependencies are declared in. When empty the source is inferred from the
+    /// project root.
+    pub fn sources(&self) -> &[EnvironmentBuilderSource] {
+        &self.sources
+    }
+
+    /// The parsers of the kinds of sources that can be checked against
+    pub fn source_registry(&self) -> &SourceRegistry {
+        &self.source_registry
+    }
+
+    /// The backend used to resolve what the declared dependencies provide
+    pub fn backend(&self) -> EnvironmentBackend {
+        self.backend
+    }
+
+    /// A backend implementation used instead of the built-in `backend`
+    pub fn custom_backend(&self) -> Option<&Arc<dyn Backend>> {
+        self.custom_backend.as_ref()
+    }
+
+    /// The environment the backend should check against, e.g. a pixi environment name or the
+    /// path to a virtual environment
+    pub fn target_environment(&self) -> Option<&str> {
+        self.target_environment.as_deref()
+    }
+
+    /// Paths, relative to the project root, whose Python files aren't analyzed
+    pub fn excludes(&self) -> &[PathBuf] {
+        &self.excludes
+    }
+
+    /// Whether the directories that never hold project sources, like `.venv` and
+    /// `node_modules`, are left out along with `excludes`. Defaults to true.
+    pub fn default_excludes(&self) -> bool {
+        self.default_excludes
+    }
+
+    /// Whether symlinked directories in the project are walked. A directory reached twice is
+    /// only analyzed once either way. Defaults to false.
+    pub fn follow_symlinks(&self) -> bool {
+        self.follow_symlinks
+    }
+
+    /// The Python files, relative to the project root, whose imports are checked, or `None`
+    /// to check every file. The rest of the project is still read to find its own modules.
+    pub fn files(&self) -> Option<&[PathBuf]> {
+        self.files.as_deref()
+    }
+
+    /// The extras whose dependencies are checked along with the required ones. `None`
+    /// checks every extra.
+    pub fn extras(&self) -> Option<&[String]> {
+        self.extras.as_deref()
+    }
+
+    /// The configurations the analysis is restricted to, by name, extra, or environment.
+    /// Empty checks every configuration.
+    pub fn configuration_names(&self) -> &[String] {
+        &self.configuration_names
+    }
+
+    /// Groups of extras that are alternatives to each other, like `torch-cpu` and
+    /// `torch-gpu`, and are never installed together
+    pub fn exclusive_extras(&self) -> &[Vec<String>] {
+        &self.exclusive_extras
+    }
+
+    /// The platforms, like `linux-64`, whose configurations are checked when a source
+    /// installs different dependencies on each platform. `None` checks every platform.
+    pub fn platforms(&self) -> Option<&[String]> {
+        self.platforms.as_deref()
+    }
+
+    /// How many files are parsed at once. Defaults to the available parallelism.
+    pub fn jobs(&self) -> usize {
+        self.jobs
+            .or_else(|| std::thread::available_parallelism().ok())
+            .map_or(1, NonZeroUsize::get)
+    }
+
+    /// Where backends keep the environments they create. `None` uses the backend default.
+    pub fn cache_dir(&self) -> Option<&Path> {
+        self.cache_dir.as_deref()
+    }
+
+    /// Whether backends may reuse environments from previous runs
+    pub fn use_cache(&self) -> bool {
+        self.use_cache
+    }
+
+    /// How long a command run by a built-in backend may take. `None` lets it run until it
+    /// finishes.
+    pub fn backend_timeout(&self) -> Option<Duration> {
+        self.backend_timeout
+    }
+
+    /// Whether imported names never used in their own file are reported
+    pub fn check_unused_imports(&self) -> bool {
+        self.check_unused_imports
+    }
+
+    /// Whether test files are analyzed. Defaults to true.
+    pub fn include_tests(&self) -> bool {
+        self.include_tests
+    }
+
+    /// Whether the Python code blocks of Markdown and reStructuredText files are analyzed.
+    /// Defaults to false.
+    pub fn include_docs(&self) -> bool {
+        self.include_docs
+    }
+
+    /// The directories, relative to the project root, that hold scripts rather than code the
+    /// project runs. Defaults to `scripts`.
+    pub fn script_directories(&self) -> &[PathBuf] {
+        &self.script_directories
+    }
+
+    /// Whether runtime dependencies only imported by scripts are reported. Defaults to true.
+    pub fn report_script_only(&self) -> bool {
+        self.report_script_only
+    }
+
+    /// The patterns of generated files, like `*_pb2.py`, whose imports aren't reported as
+    /// missing. Defaults to [`DEFAULT_GENERATED_PATTERNS`].
+    pub fn generated_patterns(&self) -> &[String] {
+        &self.generated_patterns
+    }
+
+    /// Whether what generated files import without a declared dependency is reported, as
+    /// info. Defaults to false.
+    pub fn report_generated(&self) -> bool {
+        self.report_generated
+    }
+
+    /// Whether the packages the `tox.ini` and `noxfile.py` in the project root install
+    /// satisfy what test files import. Defaults to true.
+    pub fn test_runners(&self) -> bool {
+        self.test_runners
+    }
+
+    /// Distributions used without being imported, which are never reported as unused
+    pub fn ignore_unused(&self) -> &[String] {
+        &self.ignore_unused
+    }
+
+    /// Modules the runtime the project runs in provides, which are never reported missing
+    pub fn provided_modules(&self) -> &[String] {
+        &self.provided_modules
+    }
+
+    /// The TOML or JSON file mapping modules to the distributions that provide them on top
+    /// of the built-in mapping, if one was given
+    pub fn name_map(&self) -> Option<&Path> {
+        self.name_map.as_deref()
+    }
+
+    /// Whether files that would have to be downloaded, like requirements files included by
+    /// URL, are an error instead. Always set without the `backends-net` feature, which
+    /// downloads them.
+    pub fn offline(&self) -> bool {
+        self.offline || cfg!(not(feature = "backends-net"))
+    }
+
+    /// Whether a dependency declaration that can't be parsed fails the analysis, instead of
+    /// being skipped and reported as a finding
+    pub fn strict_parse(&self) -> bool {
+        self.strict_parse
+    }
+
+    /// The size in bytes above which a Python file is skipped instead of parsed. Defaults to
+    /// [`DEFAULT_MAX_FILE_SIZE`]; `None` parses files of any size.
+    pub fn max_file_size(&self) -> Option<u64> {
+        self.max_file_size
+    }
+
+    /// How many requirements a requirements file and the files it includes may declare
+    /// before the analysis fails. Defaults to [`DEFAULT_MAX_REQUIREMENTS`]; `None` allows
+    /// any number.
+    pub fn max_requirements(&self) -> Option<usize> {
+        self.max_requirements
+    }
+
+    /// The Python version the project targets. `None` uses the version the dependency
+    /// sources pin, if any.
+    pub fn python_version(&self) -> Option<PythonVersion> {
+        self.python_version
+    }
+
+    /// The rules whose findings aren't reported, and whose checks don't run when they're
+    /// the only ones needing them
+    pub fn skip_rules(&self) -> &[Rule] {
+        &self.skip_rules
+    }
+
+    /// Whether findings for a rule are reported
+    pub fn checks(&self, rule: Rule) -> bool {
+        !self.skip_rules.contains(&rule)
+    }
+
+    /// The token that stops the analysis when cancelled
+    pub fn cancellation_token(&self) -> &CancellationToken {
+        &self.cancellation_token
+    }
+
+    /// The same options for another project root
+    #[cfg(feature = "python-parsing")]
+    pub(crate) fn with_path(&self, path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            ..self.clone()
+        }
+    }
+}
+
+/// Builder for [`AnalysisOptions`]
+#[derive(Debug, Clone)]
+pub struct AnalysisOptionsBuilder {
+    options: AnalysisOptions,
+}
+
+impl AnalysisOptionsBuilder {
+    /// Read declared dependencies from a source. Can be given several times, in which case
+    /// the dependencies of every source are combined and fixes are written to the first.
+    pub fn source(mut self, source: EnvironmentBuilderSource) -> Self {
+        self.options.sources.push(source);
+        self
+    }
+
+    /// Parse sources with the parsers of a registry instead of the built-in ones, so
+    /// [`EnvironmentBuilderSource::Custom`] sources of the kinds it has can be checked against
+    pub fn source_registry(mut self, registry: Arc<SourceRegistry>) -> Self {
+        self.options.source_registry = registry;
+        self
+    }
+
+    /// Set the backend used to resolve what the declared dependencies provide
+    pub fn backend(mut self, backend: EnvironmentBackend) -> Self {
+        self.options.backend = backend;
+        self
+    }
+
+    /// Use a backend implementation instead of one of the built-in backends
+    pub fn custom_backend(mut self, backend: Arc<dyn Backend>) -> Self {
+        self.options.custom_backend = Some(backend);
+        self
+    }
+
+    /// Set the environment the backend should check against
+    pub fn target_environment(mut self, environment: impl Into<String>) -> Self {
+        self.options.target_environment = Some(environment.into());
+        self
+    }
+
+    /// Skip the Python files under a path, relative to the project root
+    pub fn exclude(mut self, path: impl Into<PathBuf>) -> Self {
+        self.options.excludes.push(path.into());
+        self
+    }
+
+    /// Set whether the directories that never hold project sources, like `.venv` and
+    /// `node_modules`, are skipped. The paths given to `exclude` are skipped either way.
+    pub fn default_excludes(mut self, skip: bool) -> Self {
+        self.options.default_excludes = skip;
+        self
+    }
+
+    /// Set whether symlinked directories, like links to sibling packages, are walked
+    pub fn follow_symlinks(mut self, follow: bool) -> Self {
+        self.options.follow_symlinks = follow;
+        self
+    }
+
+    /// Only check the imports of these Python files, relative to the project root, like the
+    /// files changed on a branch. Unused dependencies and dependencies only scripts import
+    /// need every file, so they aren't reported.
+    pub fn files(mut self, files: impl IntoIterator<Item = PathBuf>) -> Self {
+        self.options.files = Some(files.into_iter().collect());
+        self
+    }
+
+    /// Check an extra's dependencies along with the required ones. Once an extra is
+    /// selected the extras that weren't selected are ignored.
+    pub fn extra(mut self, extra: impl Into<String>) -> Self {
+        self.options
+            .extras
+            .get_or_insert_with(Vec::new)
+            .push(extra.into());
+        self
+    }
+
+    /// Only check a configuration, given by its extra or environment, like `dev`, or by its
+    /// full name. Can be given several times. Naming a configuration the project doesn't
+    /// have is an error.
+    pub fn configuration(mut self, name: impl Into<String>) -> Self {
+        self.options.configuration_names.push(name.into());
+        self
+    }
+
+    /// Declare extras as alternatives to each other, like backend selectors. They're never
+    /// compared with each other, and an extra's dependencies aren't reported as unused
+    /// when another extra of the group provides what the project imports instead.
+    pub fn exclusive_extras(mut self, extras: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.options
+            .exclusive_extras
+            .push(extras.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Check the configurations of a platform, like `linux-64`, when a source installs
+    /// different dependencies on each platform. Once a platform is selected the others are
+    /// ignored.
+    pub fn platform(mut self, platform: impl Into<String>) -> Self {
+        self.options
+            .platforms
+            .get_or_insert_with(Vec::new)
+            .push(platform.into());
+        self
+    }
+
+    /// Set how many files are parsed at once
+    pub fn jobs(mut self, jobs: usize) -> Self {
+        self.options.jobs = NonZeroUsize::new(jobs);
+        self
+    }
+
+    /// Set where backends keep the environments they create
+    pub fn cache_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.options.cache_dir = Some(dir.into());
+        self
+    }
+
+    /// Set whether backends may reuse environments from previous runs
+    pub fn use_cache(mut self, use_cache: bool) -> Self {
+        self.options.use_cache = use_cache;
+        self
+    }
+
+    /// Stop commands run by a built-in backend, like looking up installed versions, after
+    /// `timeout`
+    pub fn backend_timeout(mut self, timeout: Duration) -> Self {
+        self.options.backend_timeout = Some(timeout);
+        self
+    }
+
+    /// Report imported names that are never used in their own file. Imports found to be
+    /// unused don't count as a use of the dependency that provides them.
+    pub fn check_unused_imports(mut self, check: bool) -> Self {
+        self.options.check_unused_imports = check;
+        self
+    }
+
+    /// Set whether test files (see [`walker::is_test_file`](crate::walker::is_test_file)) are
+    /// analyzed. Their imports are checked against a test or dev extra (or hatch
+    /// environment) instead of the required dependencies; when excluded they're ignored.
+    pub fn include_tests(mut self, include: bool) -> Self {
+        self.options.include_tests = include;
+        self
+    }
+
+    /// Set whether the `python` code blocks of documentation (see
+    /// [`walker::is_doc_file`](crate::walker::is_doc_file)) are analyzed. Their imports are
+    /// checked against a docs extra (or hatch environment), like test files are against a
+    /// test extra.
+    pub fn include_docs(mut self, include: bool) -> Self {
+        self.options.include_docs = include;
+        self
+    }
+
+    /// Replace the directories, relative to the project root, whose files are scripts (see
+    /// [`walker::is_script_file`](crate::walker::is_script_file))
+    pub fn script_directories(mut self, directories: impl IntoIterator<Item = PathBuf>) -> Self {
+        self.options.script_directories = directories.into_iter().collect();
+        self
+    }
+
+    /// Set whether runtime dependencies imported only by scripts and `if __name__ ==
+    /// "__main__":` blocks are reported, with a suggestion to move them to a dev group.
+    /// Projects that ship a command line tool usually want this off.
+    pub fn report_script_only(mut self, report: bool) -> Self {
+        self.options.report_script_only = report;
+        self
+    }
+
+    /// Replace the patterns of generated files (see
+    /// [`walker::is_generated_file`](crate::walker::is_generated_file))
+    pub fn generated_patterns(mut self, patterns: impl IntoIterator<Item = String>) -> Self {
+        self.options.generated_patterns = patterns.into_iter().collect();
+        self
+    }
+
+    /// Set whether the distributions generated files import without a declared dependency,
+    /// like the `protobuf` runtime of `*_pb2.py` files, are reported as info findings
+    /// instead of being left out
+    pub fn report_generated(mut self, report: bool) -> Self {
+        self.options.report_generated = report;
+        self
+    }
+
+    /// Set whether the test environments of a `tox.ini` and the sessions of a `noxfile.py`
+    /// in the project root are read, so the tools they install satisfy what test files
+    /// import instead of being reported as missing
+    pub fn test_runners(mut self, read: bool) -> Self {
+        self.options.test_runners = read;
+        self
+    }
+
+    /// Never report a distribution as unused, because the project uses it without
+    /// importing it (like a plugin that registers itself). Well-known build and support
+    /// packages, and distributions without importable modules, are already left out.
+    pub fn ignore_unused(mut self, distribution: impl Into<String>) -> Self {
+        self.options.ignore_unused.push(distribution.into());
+        self
+    }
+
+    /// Treat a module, and the modules under it, as provided by the runtime the project runs
+    /// in, like `pyspark` on Databricks, so importing it never needs a declared dependency.
+    /// See [`profile_modules`](crate::name_map::profile_modules) for the modules of
+    /// well-known runtimes.
+    pub fn provided_module(mut self, module: impl Into<String>) -> Self {
+        self.options.provided_modules.push(module.into());
+        self
+    }
+
+    /// Map modules to the distributions that provide them with a TOML or JSON file (see
+    /// [`load_name_map`](crate::name_map::load_name_map)), on top of the built-in mapping
+    pub fn name_map(mut self, path: impl Into<PathBuf>) -> Self {
+        self.options.name_map = Some(path.into());
+        self
+    }
+
+    /// Fail instead of downloading files, like requirements files included by URL
+    pub fn offline(mut self, offline: bool) -> Self {
+        self.options.offline = offline;
+        self
+    }
+
+    /// Fail with `AnalysisError::InvalidDeclarations` when a dependency declaration can't be
+    /// parsed, instead of skipping it
+    pub fn strict_parse(mut self, strict: bool) -> Self {
+        self.options.strict_parse = strict;
+        self
+    }
+
+    /// Skip Python files larger than this many bytes, with a warning, instead of parsing
+    /// them. `None` parses files of any size.
+    pub fn max_file_size(mut self, bytes: Option<u64>) -> Self {
+        self.options.max_file_size = bytes;
+        self
+    }
+
+    /// Fail the analysis when a requirements file declares more than this many requirements
+    /// with the files it includes, instead of reading on. `None` allows any number.
+    pub fn max_requirements(mut self, count: Option<usize>) -> Self {
+        self.options.max_requirements = count;
+        self
+    }
+
+    /// Set the Python version the project targets, which decides what is in the standard
+    /// library. Overrides the interpreter a conda environment or pixi manifest pins.
+    pub fn python_version(mut self, version: PythonVersion) -> Self {
+        self.options.python_version = Some(version);
+        self
+    }
+
+    /// Don't report findings for a rule, like
+    /// [`UnavailableVersion`](Rule::UnavailableVersion) to skip looking the declared
+    /// versions up on PyPI
+    pub fn skip_rule(mut self, rule: Rule) -> Self {
+        if !self.options.skip_rules.contains(&rule) {
+            self.options.skip_rules.push(rule);
+        }
+        self
+    }
+
+    /// Stop the analysis, with `AnalysisError::Cancelled`, once the token is cancelled
+    pub fn cancellation_token(mut self, token: CancellationToken) -> Self {
+        self.options.cancellation_token = token;
+        self
+    }
+
+    pub fn build(self) -> AnalysisOptions {
+        self.options
+    }
+}
@@ -1,13 +1,18 @@
 use crate::error::AnalysisError;
+use crate::python::Diagnostic;
+use rustpython_ast::{Ranged, Visitor};
 use rustpython_parser::{Parse, ast};
+use serde::{Deserialize, Serialize};
 use tracing::{debug, instrument, trace};
 
 /// Represents a Python import statement
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct PythonImport {
     // The module name is None for relative imports
     pub module_name: Option<String>,
     pub imported_names: Vec<String>,
+    /// The alias each of the `imported_names` is bound to, if any
+    pub imported_name_aliases: Vec<Option<String>>,
     pub is_from_import: bool,
     pub is_relative: bool,
     pub alias: Option<String>,
@@ -17,14 +22,85 @@ pub struct PythonImport {
     pub is_top_level_import: bool,
     /// Whether this import is likely guarded by a try/except block that catches ImportError
     pub is_likely_exception_guarded: bool,
+    /// The module the `ImportError` handler guarding this import imports in its place, like
+    /// `json` for `try: import ujson as json` / `except ImportError: import json`
+    pub fallback: Option<String>,
+    /// Whether this import only runs on some platforms, inside an `if` that checks
+    /// `sys.platform`, `os.name`, or `platform.system()`
+    pub is_platform_guarded: bool,
+    /// Whether this import only runs when a function is called
+    pub is_in_function: bool,
+    /// Whether this import only runs for type checkers, inside `if TYPE_CHECKING:`
+    pub is_type_checking: bool,
+    /// Whether this import only runs when the file is executed as a script, inside
+    /// `if __name__ == "__main__":`
+    pub is_main_block: bool,
+    /// Whether the names this import binds are only used in type annotations, so only type
+    /// checkers need the module
+    #[serde(default)]
+    pub is_annotation_only: bool,
+    /// Whether this import is only needed when the file runs as a script: it's inside
+    /// `if __name__ == "__main__":`, or the names it binds are only used there
+    #[serde(default)]
+    pub main_guard_only: bool,
+}
+
+impl PythonImport {
+    /// The names this import binds in the importing scope, paired with the fully qualified
+    /// name they refer to.
+    ///
+    /// Star imports bind names that can't be known statically, so they return nothing.
+    pub fn bound_names(&self) -> Vec<(String, String)> {
+        let module = self.module_name.clone().unwrap_or_default();
+        if !self.is_from_import {
+            let bound = match &self.alias {
+                Some(alias) => alias.clone(),
+                None => module.split('.').next().unwrap_or(&module).to_string(),
+            };
+            return vec![(bound, module)];
+        }
+
+        self.imported_names
+            .iter()
+            .zip(self.imported_name_aliases.iter())
+            .filter(|(name, _)| name.as_str() != "*")
+            .map(|(name, alias)| {
+                let bound = alias.clone().unwrap_or_else(|| name.clone());
+                let dots = ".".repeat(self.relative_level);
+                let qualified = if module.is_empty() {
+                    format!("{}{}", dots, name)
+                } else {
+                    format!("{}{}.{}", dots, module, name)
+                };
+                (bound, qualified)
+            })
+            .collect()
+    }
+}
+
+/// An imported name that is never referenced in the file that imports it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UnusedImport {
+    /// The name bound by the import
+    pub name: String,
+    /// The fully qualified name that was imported
+    pub qualified_name: String,
+    pub line_number: usize,
+    /// Whether the import is made at module level, where it's also exported to the files
+    /// that star-import the module
+    pub is_module_level: bool,
 }
 
 /// Parser for Python source code
 pub struct PythonParser {
     source: String,
     nesting_level: usize,
-    in_try_block: bool,
-    has_import_error_handler: bool,
+    /// The `try` statements whose body is being processed, innermost last
+    try_guards: Vec<TryGuard>,
+    in_platform_check: bool,
+    in_type_checking: bool,
+    in_main_block: bool,
+    function_depth: usize,
 }
 
 impl PythonParser {
@@ -37,11 +113,32 @@ impl PythonParser {
         Self {
             source: source.to_string(),
             nesting_level: 0,
-            in_try_block: false,
-            has_import_error_handler: false,
+            try_guards: Vec::new(),
+            in_platform_check: false,
+            in_type_checking: false,
+            in_main_block: false,
+            function_depth: 0,
         }
     }
 
+    /// Whether an `ImportError` raised here is caught by one of the enclosing `try`
+    /// statements
+    fn is_exception_guarded(&self) -> bool {
+        self.try_guards
+            .iter()
+            .any(|guard| guard.catches_import_error)
+    }
+
+    /// The module imported in place of a failed import here: the fallback of the innermost
+    /// `try` statement that catches the `ImportError`
+    fn import_fallback(&self) -> Option<String> {
+        self.try_guards
+            .iter()
+            .rev()
+            .find(|guard| guard.catches_import_error)
+            .and_then(|guard| guard.fallback.clone())
+    }
+
     /// Calculate line number from source position
     fn get_line_number(&self, pos: usize) -> usize {
         self.source[..pos].chars().filter(|&c| c == '\n').count() + 1
@@ -55,14 +152,21 @@ impl PythonParser {
                     imports.push(PythonImport {
                         module_name: Some(name.name.to_string()),
                         imported_names: vec![],
+                        imported_name_aliases: vec![],
                         is_from_import: false,
                         is_relative: false,
                         alias: name.asname.as_ref().map(|n| n.to_string()),
                         line_number: self.get_line_number(import.range.start().into()),
                         relative_level: 0,
                         is_top_level_import: self.nesting_level == 0,
-                        is_likely_exception_guarded: self.in_try_block
-                            && self.has_import_error_handler,
+                        is_likely_exception_guarded: self.is_exception_guarded(),
+                        fallback: self.import_fallback(),
+                        is_platform_guarded: self.in_platform_check,
+                        is_in_function: self.function_depth > 0,
+                        is_type_checking: self.in_type_checking,
+                        is_main_block: self.in_main_block,
+                        is_annotation_only: false,
+                        main_guard_only: self.in_main_block,
                     });
                 }
             }
@@ -70,46 +174,56 @@ impl PythonParser {
                 let level = import_from.level.unwrap_or(ast::Int::new(0)).to_u32() as usize;
                 let is_relative = level > 0;
 
-                let module_name = if let Some(module) = &import_from.module {
-                    Some(module.to_string())
-                } else {
-                    None
-                };
+                let module_name = import_from.module.as_ref().map(|module| module.to_string());
 
                 let mut imported_names = Vec::new();
+                let mut imported_name_aliases = Vec::new();
                 for name in &import_from.names {
                     if name.name.to_string() == "*" {
                         imported_names.push("*".to_string());
                     } else {
                         imported_names.push(name.name.to_string());
                     }
+                    imported_name_aliases.push(name.asname.as_ref().map(|n| n.to_string()));
                 }
 
                 imports.push(PythonImport {
                     module_name,
                     imported_names,
+                    imported_name_aliases,
                     is_from_import: true,
                     is_relative,
                     alias: None, // Aliases are handled per imported name
                     line_number: self.get_line_number(import_from.range.start().into()),
                     relative_level: level,
                     is_top_level_import: self.nesting_level == 0,
-                    is_likely_exception_guarded: self.in_try_block && self.has_import_error_handler,
+                    is_likely_exception_guarded: self.is_exception_guarded(),
+                    fallback: self.import_fallback(),
+                    is_platform_guarded: self.in_platform_check,
+                    is_in_function: self.function_depth > 0,
+                    is_type_checking: self.in_type_checking,
+                    is_main_block: self.in_main_block,
+                    is_annotation_only: false,
+                    main_guard_only: self.in_main_block,
                 });
             }
             // Recursively process statements in other contexts
             ast::Stmt::FunctionDef(func) => {
                 self.nesting_level += 1;
+                self.function_depth += 1;
                 for stmt in &func.body {
                     self.process_statement(stmt, imports);
                 }
+                self.function_depth -= 1;
                 self.nesting_level -= 1;
             }
             ast::Stmt::AsyncFunctionDef(func) => {
                 self.nesting_level += 1;
+                self.function_depth += 1;
                 for stmt in &func.body {
                     self.process_statement(stmt, imports);
                 }
+                self.function_depth -= 1;
                 self.nesting_level -= 1;
             }
             ast::Stmt::ClassDef(class) => {
@@ -120,14 +234,27 @@ impl PythonParser {
                 self.nesting_level -= 1;
             }
             ast::Stmt::If(if_stmt) => {
+                // Both branches of a platform check only run on some platforms, and so does
+                // everything in an `elif` chain that follows one
+                let was_platform_check = self.in_platform_check;
+                self.in_platform_check |= is_platform_check(&if_stmt.test);
                 self.nesting_level += 1;
+                // Only the body of `if TYPE_CHECKING:` is skipped at runtime, and only the
+                // body of `if __name__ == "__main__":` is limited to running as a script
+                let was_type_checking = self.in_type_checking;
+                let was_main_block = self.in_main_block;
+                self.in_type_checking |= is_type_checking(&if_stmt.test);
+                self.in_main_block |= is_main_check(&if_stmt.test);
                 for stmt in &if_stmt.body {
                     self.process_statement(stmt, imports);
                 }
+                self.in_type_checking = was_type_checking;
+                self.in_main_block = was_main_block;
                 for stmt in &if_stmt.orelse {
                     self.process_statement(stmt, imports);
                 }
                 self.nesting_level -= 1;
+                self.in_platform_check = was_platform_check;
             }
             ast::Stmt::While(while_stmt) => {
                 self.nesting_level += 1;
@@ -151,39 +278,44 @@ impl PythonParser {
                 self.nesting_level -= 1;
             }
             ast::Stmt::Try(try_stmt) => {
-                self.in_try_block = true;
-                self.has_import_error_handler = false;
-
-                // Check for ImportError handlers before processing the try block
+                // Find the handler that catches ImportError, if any
+                let mut guard = TryGuard {
+                    catches_import_error: false,
+                    fallback: None,
+                };
                 for handler in &try_stmt.handlers {
                     if let Some(except_handler) = handler.as_except_handler() {
                         // Check if this handler catches ImportError or is a catch-all
-                        if let Some(exception_type) = &except_handler.type_ {
-                            if let ast::Expr::Name(name) = exception_type.as_ref() {
-                                let exception_name = name.id.to_string();
-                                if exception_name == "ImportError"
-                                    || exception_name == "Exception"
-                                    || exception_name == "BaseException"
-                                    || exception_name == "ModuleNotFoundError"
-                                {
-                                    self.has_import_error_handler = true;
-                                    break;
+                        let catches_import_error = match except_handler.type_.as_deref() {
+                            Some(type_) => catches_import_error(type_),
+                            // No exception type specified means it's a catch-all
+                            None => true,
+                        };
+                        if catches_import_error {
+                            // A handler that raises again doesn't make the import optional
+                            match except_action(&except_handler.body) {
+                                ExceptAction::ImportsAlternative(module) => {
+                                    guard.catches_import_error = true;
+                                    guard.fallback = Some(module);
+                                }
+                                ExceptAction::Reraises => {}
+                                ExceptAction::AssignsNone | ExceptAction::Passes => {
+                                    guard.catches_import_error = true;
                                 }
                             }
-                        } else {
-                            // No exception type specified means it's a catch-all
-                            self.has_import_error_handler = true;
                             break;
                         }
                     }
                 }
 
-                // Now process the try block body with the correct handler state
+                // Only the body is guarded by the handlers; the handlers, `else`, and
+                // `finally` blocks are guarded by whatever the `try` statement is in
+                self.try_guards.push(guard);
                 for stmt in &try_stmt.body {
                     self.process_statement(stmt, imports);
                 }
+                self.try_guards.pop();
 
-                // Process the handlers
                 for handler in &try_stmt.handlers {
                     if let Some(except_handler) = handler.as_except_handler() {
                         for stmt in &except_handler.body {
@@ -199,9 +331,6 @@ impl PythonParser {
                 for stmt in &try_stmt.finalbody {
                     self.process_statement(stmt, imports);
                 }
-
-                self.in_try_block = false;
-                self.has_import_error_handler = false;
             }
             _ => {
                 trace!("Skipping statement: {:?}", stmt);
@@ -209,9 +338,16 @@ impl PythonParser {
         }
     }
 
-    /// Parse all import statements in the source code
+    /// Parse all import statements in the source code.
+    ///
+    /// Sources that can't contain an import (see `may_contain_imports`) aren't parsed at
+    /// all, so a syntax error in one of them isn't reported.
     #[instrument(skip(self), level = "debug")]
     pub fn parse_imports(&mut self) -> Result<Vec<PythonImport>, AnalysisError> {
+        if !may_contain_imports(&self.source) {
+            debug!("No import keyword in the source, skipping the parse");
+            return Ok(Vec::new());
+        }
         let mut imports = Vec::new();
 
         // Parse the Python source into an AST
@@ -234,6 +370,836 @@ impl PythonParser {
         );
         Ok(imports)
     }
+
+    /// Parse the imports of the source and whether it starts with a docstring. Unlike
+    /// `parse_imports`, every source is parsed, and a syntax error says where it is.
+    pub(crate) fn scan_module(&mut self) -> Result<(Vec<PythonImport>, bool), Diagnostic> {
+        let suite = ast::Suite::parse(&self.source, "<string>").map_err(|e| {
+            let offset: usize = e.offset.into();
+            let line = self.get_line_number(offset);
+            let line_start = self.source[..offset].rfind('\n').map_or(0, |i| i + 1);
+            Diagnostic {
+                line,
+                column: self.source[line_start..offset].chars().count() + 1,
+                message: e.error.to_string(),
+            }
+        })?;
+        let has_docstring = suite.first().is_some_and(|stmt| {
+            matches!(stmt, ast::Stmt::Expr(expr) if matches!(expr.value.as_ref(),
+                ast::Expr::Constant(constant) if matches!(constant.value, ast::Constant::Str(_))))
+        });
+        let mut imports = Vec::new();
+        for stmt in &suite {
+            self.process_statement(stmt, &mut imports);
+        }
+        Ok((imports, has_docstring))
+    }
+
+    /// Find the imports that bind names never referenced in the source.
+    ///
+    /// An import is used if its bound name is loaded anywhere in the scope (function, lambda,
+    /// class, or module) the import was made in, including nested scopes. Names listed in
+    /// `__all__` count as used, as do the submodules and attributes a package exports lazily
+    /// with `lazy_loader.attach(...)`, imports on a line with a `# noqa` comment, and imports
+    /// in a `try` body that sets an availability flag like `HAS_NUMPY = True`.
+    /// `__future__` imports and star imports are never reported. When a lazy loader is given
+    /// names that can't be read statically, no module level import is reported.
+    ///
+    /// A star import binds names that can't be known without importing the module, so it
+    /// always counts as used, and any name it might rebind is taken to still refer to the
+    /// explicit import of it. Which names a star import brings in is never guessed, so an
+    /// import shadowed by one isn't reported either.
+    #[instrument(skip(self, imports), level = "debug")]
+    pub fn find_unused_imports(
+        &self,
+        imports: &[PythonImport],
+    ) -> Result<Vec<UnusedImport>, AnalysisError> {
+        if imports.is_empty() {
+            return Ok(Vec::new());
+        }
+        let suite = ast::Suite::parse(&self.source, "<string>").map_err(|e| {
+            AnalysisError::ParseFileError(
+                format!("Failed to parse Python source: {}", e),
+                "".to_string(),
+                "".to_string(),
+            )
+        })?;
+        Ok(self.collect_usage(suite).unused_imports(imports))
+    }
+
+    /// Parse the imports of the source along with how the names they bind are used: the
+    /// imports whose names are only used in type annotations are marked
+    /// `is_annotation_only`, the ones whose names are only used inside
+    /// `if __name__ == "__main__":` are marked `main_guard_only`, and the names never used
+    /// are returned as by `find_unused_imports`. The source is only parsed once for both.
+    pub(crate) fn parse_imports_with_usage(
+        &mut self,
+    ) -> Result<(Vec<PythonImport>, Vec<UnusedImport>), AnalysisError> {
+        if !may_contain_imports(&self.source) {
+            return Ok((Vec::new(), Vec::new()));
+        }
+        let suite = ast::Suite::parse(&self.source, "<string>").map_err(|e| {
+            AnalysisError::ParseFileError(
+                format!("Failed to parse Python source: {}", e),
+                "".to_string(),
+                "".to_string(),
+            )
+        })?;
+        let mut imports = Vec::new();
+        for stmt in &suite {
+            self.process_statement(stmt, &mut imports);
+        }
+        let collector = self.collect_usage(suite);
+        for import in &mut imports {
+            import.is_annotation_only = collector.is_annotation_only(import);
+            import.main_guard_only |= collector.is_main_guard_only(import);
+        }
+        let unused = collector.unused_imports(&imports);
+        Ok((imports, unused))
+    }
+
+    /// Collect the names the statements load, along with the names of `# type:` comments
+    fn collect_usage(&self, suite: ast::Suite) -> UsageCollector {
+        let mut collector = UsageCollector::new(&self.source);
+        for stmt in suite {
+            collector.visit_stmt(stmt);
+        }
+        collector.collect_type_comments(&self.source);
+        collector
+    }
+}
+
+/// The names a module binds at module level: its functions, classes, assigned variables,
+/// and imports, including the ones bound under `if`, `try`, `with`, and loops. Returns `None`
+/// when the names can't be known statically, because the module has a star import or
+/// defines a module-level `__getattr__`.
+pub(crate) fn module_names(source: &str) -> Result<Option<Vec<String>>, AnalysisError> {
+    let suite = ast::Suite::parse(source, "<string>").map_err(|e| {
+        AnalysisError::ParseFileError(
+            format!("Failed to parse Python source: {}", e),
+            "".to_string(),
+            "".to_string(),
+        )
+    })?;
+    let mut names = Vec::new();
+    if !collect_module_names(&suite, &mut names) || names.iter().any(|n| n == "__getattr__") {
+        return Ok(None);
+    }
+    names.sort();
+    names.dedup();
+    Ok(Some(names))
+}
+
+/// Add the names a block of module-level statements binds, returning false if one of them
+/// is a star import
+fn collect_module_names(body: &[ast::Stmt], names: &mut Vec<String>) -> bool {
+    fn targets(expr: &ast::Expr, names: &mut Vec<String>) {
+        match expr {
+            ast::Expr::Name(name) => names.push(name.id.to_string()),
+            ast::Expr::Tuple(tuple) => tuple.elts.iter().for_each(|e| targets(e, names)),
+            ast::Expr::List(list) => list.elts.iter().for_each(|e| targets(e, names)),
+            ast::Expr::Starred(starred) => targets(&starred.value, names),
+            _ => {}
+        }
+    }
+
+    for stmt in body {
+        let known = match stmt {
+            ast::Stmt::FunctionDef(func) => {
+                names.push(func.name.to_string());
+                true
+            }
+            ast::Stmt::AsyncFunctionDef(func) => {
+                names.push(func.name.to_string());
+                true
+            }
+            ast::Stmt::ClassDef(class) => {
+                names.push(class.name.to_string());
+                true
+            }
+            ast::Stmt::Assign(assign) => {
+                assign.targets.iter().for_each(|t| targets(t, names));
+                true
+            }
+            ast::Stmt::AnnAssign(assign) => {
+                targets(&assign.target, names);
+                true
+            }
+            ast::Stmt::AugAssign(assign) => {
+                targets(&assign.target, names);
+                true
+            }
+            ast::Stmt::Import(import) => {
+                for alias in &import.names {
+                    let name = alias.name.as_str();
+                    names.push(match &alias.asname {
+                        Some(asname) => asname.to_string(),
+                        None => name.split('.').next().unwrap_or(name).to_string(),
+                    });
+                }
+                true
+            }
+            ast::Stmt::ImportFrom(import) => {
+                for alias in &import.names {
+                    let name = alias.asname.as_ref().unwrap_or(&alias.name);
+                    names.push(name.to_string());
+                }
+                import.names.iter().all(|alias| alias.name.as_str() != "*")
+            }
+            ast::Stmt::If(if_stmt) => {
+                collect_module_names(&if_stmt.body, names)
+                    & collect_module_names(&if_stmt.orelse, names)
+            }
+            ast::Stmt::With(with) => collect_module_names(&with.body, names),
+            ast::Stmt::AsyncWith(with) => collect_module_names(&with.body, names),
+            ast::Stmt::For(for_stmt) => {
+                targets(&for_stmt.target, names);
+                collect_module_names(&for_stmt.body, names)
+                    & collect_module_names(&for_stmt.orelse, names)
+            }
+            ast::Stmt::While(while_stmt) => {
+                collect_module_names(&while_stmt.body, names)
+                    & collect_module_names(&while_stmt.orelse, names)
+            }
+            ast::Stmt::Try(try_stmt) => {
+                let handlers = try_stmt.handlers.iter().all(|handler| {
+                    handler
+                        .as_except_handler()
+                        .is_none_or(|handler| collect_module_names(&handler.body, names))
+                });
+                collect_module_names(&try_stmt.body, names)
+                    & handlers
+                    & collect_module_names(&try_stmt.orelse, names)
+                    & collect_module_names(&try_stmt.finalbody, names)
+            }
+            _ => true,
+        };
+        if !known {
+            return false;
+        }
+    }
+    true
+}
+
+/// Whether a source could contain an import statement. Every `import` and `from ... import`
+/// statement spells out the `import` keyword, so a source without the word anywhere has
+/// none. The word is looked for anywhere rather than at the start of lines, because
+/// statements like `if TYPE_CHECKING: import numpy` and `x = 1; import os` put it in the
+/// middle of one.
+pub(crate) fn may_contain_imports(source: &str) -> bool {
+    source.contains("import")
+}
+
+/// Check if the exception type of an `except` clause catches `ImportError`: the class itself,
+/// a subclass or base class of it, a dotted name ending in one (like
+/// `builtins.ImportError`), or a tuple containing one
+fn catches_import_error(type_: &ast::Expr) -> bool {
+    let name = match type_ {
+        ast::Expr::Name(name) => name.id.as_str(),
+        ast::Expr::Attribute(attribute) => attribute.attr.as_str(),
+        ast::Expr::Tuple(tuple) => return tuple.elts.iter().any(catches_import_error),
+        _ => return false,
+    };
+    matches!(
+        name,
+        "ImportError" | "Exception" | "BaseException" | "ModuleNotFoundError"
+    )
+}
+
+/// A `try` statement around the statements being processed
+#[derive(Debug)]
+struct TryGuard {
+    /// Whether one of its handlers catches `ImportError` without raising again
+    catches_import_error: bool,
+    /// The module that handler imports instead, if it does
+    fallback: Option<String>,
+}
+
+/// What an `except ImportError:` handler does when the guarded import fails
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ExceptAction {
+    /// Imports another module in its place
+    ImportsAlternative(String),
+    /// Binds a name to `None` for the code to check later
+    AssignsNone,
+    /// Raises again, so the import is effectively required
+    Reraises,
+    /// Carries on without the module, with `pass` or anything else that doesn't raise
+    Passes,
+}
+
+/// Work out what an exception handler's body does, looking into `try` statements in it for
+/// a chain of alternatives like `try: import simplejson as json` / `except: import json`
+fn except_action(body: &[ast::Stmt]) -> ExceptAction {
+    let mut action = ExceptAction::Passes;
+    for stmt in body {
+        match stmt {
+            ast::Stmt::Import(import) => {
+                if let Some(name) = import.names.first() {
+                    return ExceptAction::ImportsAlternative(name.name.to_string());
+                }
+            }
+            ast::Stmt::ImportFrom(import_from)
+                if import_from.level.is_none_or(|l| l.to_u32() == 0) =>
+            {
+                if let Some(module) = &import_from.module {
+                    return ExceptAction::ImportsAlternative(module.to_string());
+                }
+            }
+            ast::Stmt::Try(try_stmt) => {
+                if let ExceptAction::ImportsAlternative(module) = except_action(&try_stmt.body) {
+                    return ExceptAction::ImportsAlternative(module);
+                }
+            }
+            ast::Stmt::Raise(_) => action = ExceptAction::Reraises,
+            ast::Stmt::Assign(assign)
+                if matches!(
+                    assign.value.as_ref(),
+                    ast::Expr::Constant(constant) if constant.value.is_none()
+                ) && action == ExceptAction::Passes =>
+            {
+                action = ExceptAction::AssignsNone;
+            }
+            _ => {}
+        }
+    }
+    action
+}
+
+/// Check whether a condition depends on the platform, e.g. `sys.platform == "win32"`,
+/// `os.name == "nt"`, or `platform.system() == "Darwin"`
+fn is_platform_check(expr: &ast::Expr) -> bool {
+    match expr {
+        ast::Expr::Attribute(attribute) => {
+            let is_platform_attribute = match attribute.value.as_ref() {
+                ast::Expr::Name(name) => matches!(
+                    (name.id.as_str(), attribute.attr.as_str()),
+                    ("sys", "platform")
+                        | ("os", "name")
+                        | ("platform", "system" | "machine" | "platform")
+                ),
+                _ => false,
+            };
+            is_platform_attribute || is_platform_check(&attribute.value)
+        }
+        ast::Expr::Call(call) => {
+            is_platform_check(&call.func) || call.args.iter().any(is_platform_check)
+        }
+        ast::Expr::Compare(compare) => {
+            is_platform_check(&compare.left) || compare.comparators.iter().any(is_platform_check)
+        }
+        ast::Expr::BoolOp(bool_op) => bool_op.values.iter().any(is_platform_check),
+        ast::Expr::UnaryOp(unary_op) => is_platform_check(&unary_op.operand),
+        _ => false,
+    }
+}
+
+/// Check whether a condition is `TYPE_CHECKING` or `typing.TYPE_CHECKING`
+fn is_type_checking(expr: &ast::Expr) -> bool {
+    match expr {
+        ast::Expr::Name(name) => name.id.as_str() == "TYPE_CHECKING",
+        ast::Expr::Attribute(attribute) => attribute.attr.as_str() == "TYPE_CHECKING",
+        _ => false,
+    }
+}
+
+/// Check whether a condition is `__name__ == "__main__"`, in either order
+fn is_main_check(expr: &ast::Expr) -> bool {
+    let ast::Expr::Compare(compare) = expr else {
+        return false;
+    };
+    let ([ast::CmpOp::Eq], [right]) = (compare.ops.as_slice(), compare.comparators.as_slice())
+    else {
+        return false;
+    };
+    let is_name =
+        |expr: &ast::Expr| matches!(expr, ast::Expr::Name(name) if name.id.as_str() == "__name__");
+    let is_main = |expr: &ast::Expr| {
+        matches!(expr, ast::Expr::Constant(constant)
+            if matches!(&constant.value, ast::Constant::Str(value) if value == "__main__"))
+    };
+    (is_name(&compare.left) && is_main(right)) || (is_main(&compare.left) && is_name(right))
+}
+
+/// Collects the names loaded by Python source, along with the scopes they're loaded in
+struct UsageCollector {
+    /// Byte offset of the start of each line
+    line_starts: Vec<usize>,
+    /// Names loaded, the line they were loaded on, and whether they were loaded in a type
+    /// annotation
+    usages: Vec<(String, usize, bool)>,
+    /// How many annotations the expression being visited is in
+    annotation_depth: usize,
+    /// The lines with a `# noqa` comment
+    noqa_lines: Vec<usize>,
+    /// First and last line of every function, lambda, and class
+    scopes: Vec<(usize, usize)>,
+    /// Names listed in `__all__` or exported through a lazy loader
+    exported: Vec<String>,
+    /// Whether a lazy loader exports names that couldn't be read statically, so any module
+    /// level import may be exported
+    exports_unknown: bool,
+    /// Lines of the imports made to set an availability flag
+    flag_imports: Vec<usize>,
+    /// First and last line of the body of every `if __name__ == "__main__":`
+    main_blocks: Vec<(usize, usize)>,
+}
+
+impl UsageCollector {
+    fn new(source: &str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self {
+            line_starts,
+            usages: Vec::new(),
+            annotation_depth: 0,
+            noqa_lines: source
+                .lines()
+                .enumerate()
+                .filter(|(_, line)| line.contains("# noqa"))
+                .map(|(i, _)| i + 1)
+                .collect(),
+            scopes: Vec::new(),
+            exported: Vec::new(),
+            exports_unknown: false,
+            flag_imports: Vec::new(),
+            main_blocks: Vec::new(),
+        }
+    }
+
+    fn line_number(&self, pos: usize) -> usize {
+        match self.line_starts.binary_search(&pos) {
+            Ok(line) => line + 1,
+            Err(line) => line,
+        }
+    }
+
+    fn add_scope(&mut self, range: ast::text_size::TextRange) {
+        let start = self.line_number(range.start().into());
+        let end = self.line_number(range.end().into());
+        self.scopes.push((start, end));
+    }
+
+    /// The innermost scope containing a line, or the whole module
+    fn scope_of(&self, line: usize) -> (usize, usize) {
+        self.scopes
+            .iter()
+            .filter(|(start, end)| *start < line && line <= *end)
+            .min_by_key(|(start, end)| end - start)
+            .copied()
+            .unwrap_or((1, usize::MAX))
+    }
+
+    /// The uses of a name in the scope an import on `line` binds it in, with whether each
+    /// one is in a type annotation
+    fn uses_in_scope<'a>(&'a self, name: &'a str, line: usize) -> impl Iterator<Item = bool> + 'a {
+        let (scope_start, scope_end) = self.scope_of(line);
+        self.usages
+            .iter()
+            .filter(move |(used_name, used_line, _)| {
+                used_name == name && *used_line >= scope_start && *used_line <= scope_end
+            })
+            .map(|(_, _, in_annotation)| *in_annotation)
+    }
+
+    /// The names imports bind that are never used, see `PythonParser::find_unused_imports`
+    fn unused_imports(&self, imports: &[PythonImport]) -> Vec<UnusedImport> {
+        let mut unused = Vec::new();
+        for import in imports {
+            if import.module_name.as_deref() == Some("__future__") {
+                continue;
+            }
+            let suppressed = self.noqa_lines.contains(&import.line_number);
+            if suppressed || self.flag_imports.contains(&import.line_number) {
+                continue;
+            }
+
+            let (_, scope_end) = self.scope_of(import.line_number);
+            if self.exports_unknown && scope_end == usize::MAX {
+                continue;
+            }
+            for (name, qualified_name) in import.bound_names() {
+                if self.exported.contains(&name) {
+                    continue;
+                }
+                let used = self
+                    .uses_in_scope(&name, import.line_number)
+                    .next()
+                    .is_some();
+                if !used {
+                    unused.push(UnusedImport {
+                        name,
+                        qualified_name,
+                        line_number: import.line_number,
+                        is_module_level: scope_end == usize::MAX,
+                    });
+                }
+            }
+        }
+
+        unused
+    }
+
+    /// Whether the names an import binds are used, and only in type annotations. Exported
+    /// names are used at runtime by whatever imports them.
+    fn is_annotation_only(&self, import: &PythonImport) -> bool {
+        let mut used = false;
+        for (name, _) in import.bound_names() {
+            if self.exported.contains(&name) {
+                return false;
+            }
+            for in_annotation in self.uses_in_scope(&name, import.line_number) {
+                if !in_annotation {
+                    return false;
+                }
+                used = true;
+            }
+        }
+        used && !(self.exports_unknown && self.scope_of(import.line_number).1 == usize::MAX)
+    }
+
+    /// Whether the names an import binds are used, and only inside
+    /// `if __name__ == "__main__":` blocks. Exported names are used by whatever imports them.
+    fn is_main_guard_only(&self, import: &PythonImport) -> bool {
+        let mut used = false;
+        for (name, _) in import.bound_names() {
+            if self.exported.contains(&name) {
+                return false;
+            }
+            let (scope_start, scope_end) = self.scope_of(import.line_number);
+            for (used_name, line, _) in &self.usages {
+                if *used_name != name || *line < scope_start || *line > scope_end {
+                    continue;
+                }
+                let in_main_block = self
+                    .main_blocks
+                    .iter()
+                    .any(|(start, end)| start <= line && line <= end);
+                if !in_main_block {
+                    return false;
+                }
+                used = true;
+            }
+        }
+        used && !(self.exports_unknown && self.scope_of(import.line_number).1 == usize::MAX)
+    }
+
+    /// Visit an expression that is a type annotation
+    fn visit_annotation(&mut self, annotation: ast::Expr) {
+        self.annotation_depth += 1;
+        self.visit_expr(annotation);
+        self.annotation_depth -= 1;
+    }
+
+    /// Collect the names of an annotation written as a string, like `"np.ndarray"`, as if
+    /// they were in the annotation at `offset`
+    fn visit_string_annotation(&mut self, annotation: &str, offset: ast::text_size::TextSize) {
+        if let Ok(expr) = ast::Expr::parse_starts_at(annotation.trim(), "<annotation>", offset) {
+            self.visit_annotation(expr);
+        }
+    }
+
+    /// Collect the names of `# type:` comments, like `x = []  # type: list[np.ndarray]` or a
+    /// function's `# type: (int) -> np.ndarray`. Type checkers read them as annotations;
+    /// `# type: ignore` isn't one.
+    fn collect_type_comments(&mut self, source: &str) {
+        let mut offset = 0;
+        for line in source.split_inclusive('\n') {
+            if let Some(start) = line.find("# type:") {
+                let comment = &line[start + "# type:".len()..];
+                if !comment.trim_start().starts_with("ignore") {
+                    let at = ast::text_size::TextSize::from((offset + start) as u32);
+                    for part in comment.split("->") {
+                        self.visit_string_annotation(part, at);
+                    }
+                }
+            }
+            offset += line.len();
+        }
+    }
+
+    fn collect_exports(&mut self, value: &ast::Expr) {
+        let elts = match value {
+            ast::Expr::List(list) => &list.elts,
+            ast::Expr::Tuple(tuple) => &tuple.elts,
+            _ => return,
+        };
+        for elt in elts {
+            if let ast::Expr::Constant(constant) = elt
+                && let ast::Constant::Str(name) = &constant.value
+            {
+                self.exported.push(name.clone());
+            }
+        }
+    }
+
+    /// Collect the names exported by a `lazy.attach(__name__, submodules, submod_attrs)` or
+    /// `lazy.attach_stub(__name__, __file__)` call
+    fn collect_lazy_exports(&mut self, call: &ast::ExprCall) {
+        let ast::Expr::Attribute(attribute) = call.func.as_ref() else {
+            return;
+        };
+        let is_lazy_loader = matches!(
+            attribute.value.as_ref(),
+            ast::Expr::Name(name) if matches!(name.id.as_str(), "lazy" | "lazy_loader")
+        );
+        if !is_lazy_loader {
+            return;
+        }
+        match attribute.attr.as_str() {
+            "attach" => {}
+            // The exports are read from a `.pyi` stub at runtime
+            "attach_stub" => {
+                self.exports_unknown = true;
+                return;
+            }
+            _ => return,
+        }
+
+        let argument = |position: usize, keyword: &str| {
+            call.keywords
+                .iter()
+                .find(|k| k.arg.as_ref().is_some_and(|arg| arg.as_str() == keyword))
+                .map(|k| &k.value)
+                .or_else(|| call.args.get(position))
+        };
+        for value in [argument(1, "submodules"), argument(2, "submod_attrs")]
+            .into_iter()
+            .flatten()
+        {
+            if !self.collect_names(value) {
+                self.exports_unknown = true;
+            }
+        }
+    }
+
+    /// Collect the string literals of a list, tuple, set, or dict (keys and values). Returns
+    /// false if the expression isn't made up of literals.
+    fn collect_names(&mut self, value: &ast::Expr) -> bool {
+        let elts = match value {
+            ast::Expr::Constant(constant) => match &constant.value {
+                ast::Constant::Str(name) => {
+                    self.exported.push(name.clone());
+                    return true;
+                }
+                ast::Constant::None => return true,
+                _ => return false,
+            },
+            ast::Expr::List(list) => &list.elts,
+            ast::Expr::Tuple(tuple) => &tuple.elts,
+            ast::Expr::Set(set) => &set.elts,
+            ast::Expr::Dict(dict) => {
+                let keys_are_literal = dict
+                    .keys
+                    .iter()
+                    .all(|key| key.as_ref().is_some_and(|key| self.collect_names(key)));
+                return keys_are_literal && dict.values.iter().all(|v| self.collect_names(v));
+            }
+            _ => return false,
+        };
+        elts.iter().all(|elt| self.collect_names(elt))
+    }
+
+    /// Collect the imports of a `try` body that sets an availability flag when they succeed,
+    /// like `try: import numpy; HAS_NUMPY = True` / `except ImportError: pass`. Setting the
+    /// flag is what they're imported for, so they count as used.
+    fn collect_flag_imports(&mut self, body: &[ast::Stmt], handlers: &[ast::ExceptHandler]) {
+        let catches_import_error = handlers
+            .iter()
+            .filter_map(|handler| handler.as_except_handler())
+            .any(|handler| handler.type_.as_deref().is_none_or(catches_import_error));
+        if !catches_import_error || !body.iter().any(Self::sets_flag) {
+            return;
+        }
+        for stmt in body {
+            let range = match stmt {
+                ast::Stmt::Import(import) => import.range,
+                ast::Stmt::ImportFrom(import) => import.range,
+                _ => continue,
+            };
+            let line = self.line_number(range.start().into());
+            self.flag_imports.push(line);
+        }
+    }
+
+    /// Whether a statement sets a flag, like `HAS_NUMPY = True`
+    fn sets_flag(stmt: &ast::Stmt) -> bool {
+        let (targets, value): (&[ast::Expr], _) = match stmt {
+            ast::Stmt::Assign(assign) => (&assign.targets, Some(&assign.value)),
+            ast::Stmt::AnnAssign(assign) => {
+                (std::slice::from_ref(&*assign.target), assign.value.as_ref())
+            }
+            _ => return false,
+        };
+        let is_true = value.is_some_and(|value| {
+            matches!(value.as_ref(), ast::Expr::Constant(constant)
+                if matches!(constant.value, ast::Constant::Bool(true)))
+        });
+        is_true
+            && targets
+                .iter()
+                .all(|target| matches!(target, ast::Expr::Name(_)))
+    }
+
+    fn is_all_target(target: &ast::Expr) -> bool {
+        matches!(target, ast::Expr::Name(name) if name.id.as_str() == "__all__")
+    }
+}
+
+impl Visitor for UsageCollector {
+    fn visit_stmt_function_def(&mut self, mut node: ast::StmtFunctionDef) {
+        self.add_scope(node.range);
+        if let Some(returns) = node.returns.take() {
+            self.visit_annotation(*returns);
+        }
+        self.generic_visit_stmt_function_def(node);
+    }
+
+    fn visit_stmt_async_function_def(&mut self, mut node: ast::StmtAsyncFunctionDef) {
+        self.add_scope(node.range);
+        if let Some(returns) = node.returns.take() {
+            self.visit_annotation(*returns);
+        }
+        self.generic_visit_stmt_async_function_def(node);
+    }
+
+    fn visit_arguments(&mut self, node: ast::Arguments) {
+        for arg in node
+            .posonlyargs
+            .into_iter()
+            .chain(node.args)
+            .chain(node.kwonlyargs)
+        {
+            self.visit_arg(arg.def);
+            if let Some(default) = arg.default {
+                self.visit_expr(*default);
+            }
+        }
+        for arg in node.vararg.into_iter().chain(node.kwarg) {
+            self.visit_arg(*arg);
+        }
+    }
+
+    fn visit_arg(&mut self, node: ast::Arg) {
+        if let Some(annotation) = node.annotation {
+            self.visit_annotation(*annotation);
+        }
+    }
+
+    fn visit_stmt_ann_assign(&mut self, node: ast::StmtAnnAssign) {
+        self.visit_expr(*node.target);
+        self.visit_annotation(*node.annotation);
+        if let Some(value) = node.value {
+            self.visit_expr(*value);
+        }
+    }
+
+    fn visit_keyword(&mut self, node: ast::Keyword) {
+        self.visit_expr(node.value);
+    }
+
+    fn visit_comprehension(&mut self, node: ast::Comprehension) {
+        self.visit_expr(node.target);
+        self.visit_expr(node.iter);
+        for condition in node.ifs {
+            self.visit_expr(condition);
+        }
+    }
+
+    fn visit_withitem(&mut self, node: ast::WithItem) {
+        self.visit_expr(node.context_expr);
+        if let Some(vars) = node.optional_vars {
+            self.visit_expr(*vars);
+        }
+    }
+
+    fn visit_match_case(&mut self, node: ast::MatchCase) {
+        self.visit_pattern(node.pattern);
+        if let Some(guard) = node.guard {
+            self.visit_expr(*guard);
+        }
+        for stmt in node.body {
+            self.visit_stmt(stmt);
+        }
+    }
+
+    fn visit_stmt_class_def(&mut self, node: ast::StmtClassDef) {
+        self.add_scope(node.range);
+        self.generic_visit_stmt_class_def(node);
+    }
+
+    fn visit_expr_lambda(&mut self, node: ast::ExprLambda) {
+        self.add_scope(node.range);
+        self.generic_visit_expr_lambda(node);
+    }
+
+    fn visit_stmt_assign(&mut self, node: ast::StmtAssign) {
+        if node.targets.iter().any(Self::is_all_target) {
+            self.collect_exports(&node.value);
+        }
+        self.generic_visit_stmt_assign(node);
+    }
+
+    fn visit_stmt_aug_assign(&mut self, node: ast::StmtAugAssign) {
+        if Self::is_all_target(&node.target) {
+            self.collect_exports(&node.value);
+        }
+        self.generic_visit_stmt_aug_assign(node);
+    }
+
+    fn visit_stmt_if(&mut self, node: ast::StmtIf) {
+        if is_main_check(&node.test)
+            && let (Some(first), Some(last)) = (node.body.first(), node.body.last())
+        {
+            let start = self.line_number(first.range().start().into());
+            let end = self.line_number(last.range().end().into());
+            self.main_blocks.push((start, end));
+        }
+        self.generic_visit_stmt_if(node);
+    }
+
+    fn visit_stmt_try(&mut self, node: ast::StmtTry) {
+        self.collect_flag_imports(&node.body, &node.handlers);
+        self.generic_visit_stmt_try(node);
+    }
+
+    fn visit_expr_call(&mut self, mut node: ast::ExprCall) {
+        self.collect_lazy_exports(&node);
+        // The type `cast(T, value)` casts to is only read by type checkers
+        let is_cast = match node.func.as_ref() {
+            ast::Expr::Name(name) => name.id.as_str() == "cast",
+            ast::Expr::Attribute(attribute) => {
+                attribute.attr.as_str() == "cast"
+                    && matches!(attribute.value.as_ref(),
+                        ast::Expr::Name(name) if name.id.as_str() == "typing")
+            }
+            _ => false,
+        };
+        if is_cast && !node.args.is_empty() {
+            let type_ = node.args.remove(0);
+            self.visit_annotation(type_);
+        }
+        self.generic_visit_expr_call(node);
+    }
+
+    fn visit_expr_constant(&mut self, node: ast::ExprConstant) {
+        if self.annotation_depth > 0
+            && let ast::Constant::Str(annotation) = &node.value
+        {
+            // Past the opening quote
+            let offset = node.range.start() + ast::text_size::TextSize::from(1);
+            self.visit_string_annotation(annotation, offset);
+        }
+    }
+
+    fn visit_expr_name(&mut self, node: ast::ExprName) {
+        if node.ctx == ast::ExprContext::Load {
+            let line = self.line_number(node.range.start().into());
+            self.usages
+                .push((node.id.to_string(), line, self.annotation_depth > 0));
+        }
+    }
 }
 
 #[cfg(test)]
@@ -660,4 +1626,624 @@ def function():
 
         Ok(())
     }
+
+    #[test]
+    fn test_parse_import_error_handler_types() -> Result<(), AnalysisError> {
+        init_tracing();
+        let source = r#"
+try:
+    import in_tuple
+except (ImportError, OSError):
+    pass
+
+try:
+    import dotted
+except builtins.ImportError:
+    pass
+
+try:
+    import dotted_in_tuple
+except (OSError, importlib.ModuleNotFoundError):
+    pass
+
+try:
+    import bound_name
+except ImportError as e:
+    pass
+
+try:
+    import bound_dotted
+except builtins.ImportError as e:
+    pass
+
+try:
+    import other_tuple
+except (OSError, ValueError):
+    pass
+
+try:
+    import other_dotted
+except socket.timeout:
+    pass
+"#;
+
+        let mut parser = PythonParser::new(source);
+        let imports = parser.parse_imports()?;
+        let guarded: Vec<(&str, bool)> = imports
+            .iter()
+            .map(|import| {
+                (
+                    import.module_name.as_deref().unwrap_or_default(),
+                    import.is_likely_exception_guarded,
+                )
+            })
+            .collect();
+        assert_eq!(
+            guarded,
+            [
+                ("in_tuple", true),
+                ("dotted", true),
+                ("dotted_in_tuple", true),
+                ("bound_name", true),
+                ("bound_dotted", true),
+                ("other_tuple", false),
+                ("other_dotted", false),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_nested_try_guards() -> Result<(), AnalysisError> {
+        init_tracing();
+        let source = r#"
+try:
+    try:
+        import inner_guarded
+    except ValueError:
+        pass
+    import after_inner_try
+except ImportError:
+    pass
+
+try:
+    import plain_body
+except ImportError:
+    try:
+        import handler_try
+    except ImportError:
+        pass
+    import handler_body
+else:
+    import else_body
+finally:
+    import finally_body
+
+import top_level
+"#;
+
+        let mut parser = PythonParser::new(source);
+        let imports = parser.parse_imports()?;
+        let guarded: Vec<(&str, bool)> = imports
+            .iter()
+            .map(|import| {
+                (
+                    import.module_name.as_deref().unwrap_or_default(),
+                    import.is_likely_exception_guarded,
+                )
+            })
+            .collect();
+        assert_eq!(
+            guarded,
+            [
+                ("inner_guarded", true),
+                ("after_inner_try", true),
+                ("plain_body", true),
+                ("handler_try", true),
+                ("handler_body", false),
+                ("else_body", false),
+                ("finally_body", false),
+                ("top_level", false),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_sources_without_imports_skip_the_parse() -> Result<(), AnalysisError> {
+        init_tracing();
+        // A syntax error would fail the full parse, so an empty list means it was skipped
+        let source = "\"\"\"Lookup tables\"\"\"\n\nCODES = {\"a\": 1,\n";
+        assert!(!may_contain_imports(source));
+        assert!(PythonParser::new(source).parse_imports()?.is_empty());
+        assert!(PythonParser::new("CODES = {").parse_imports()?.is_empty());
+
+        // The keyword doesn't have to start a line
+        for source in [
+            "if TYPE_CHECKING: import numpy\n",
+            "x = 1; import os\n",
+            "try: from yaml import safe_load\nexcept ImportError: pass\n",
+        ] {
+            assert!(may_contain_imports(source), "{}", source);
+            assert_eq!(
+                PythonParser::new(source).parse_imports()?.len(),
+                1,
+                "{}",
+                source
+            );
+        }
+        assert!(
+            PythonParser::new("# import nothing\nCODES = {")
+                .parse_imports()
+                .is_err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_import_fallbacks() -> Result<(), AnalysisError> {
+        init_tracing();
+        let source = r#"
+try:
+    import ujson as json
+except ImportError:
+    import json
+
+try:
+    from lxml import etree
+except ImportError:
+    try:
+        import defusedxml.ElementTree as etree
+    except ImportError:
+        from xml.etree import ElementTree as etree
+
+try:
+    import torch
+except ImportError:
+    torch = None
+
+try:
+    import numpy
+except ImportError:
+    raise RuntimeError("numpy is required")
+"#;
+
+        let mut parser = PythonParser::new(source);
+        let imports = parser.parse_imports()?;
+        let guarded: Vec<(&str, bool, Option<&str>)> = imports
+            .iter()
+            .map(|import| {
+                (
+                    import.module_name.as_deref().unwrap_or_default(),
+                    import.is_likely_exception_guarded,
+                    import.fallback.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            guarded,
+            [
+                ("ujson", true, Some("json")),
+                ("json", false, None),
+                ("lxml", true, Some("defusedxml.ElementTree")),
+                ("defusedxml.ElementTree", true, Some("xml.etree")),
+                ("xml.etree", false, None),
+                ("torch", true, None),
+                // Raising again makes the import required
+                ("numpy", false, None),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_platform_guarded_imports() -> Result<(), AnalysisError> {
+        init_tracing();
+        let source = r#"
+import sys
+
+if sys.platform == "win32":
+    import winreg
+elif platform.system() == "Darwin":
+    import objc
+else:
+    import fcntl
+
+if not sys.platform.startswith("linux") and DEBUG:
+    import pywintypes
+
+if DEBUG:
+    import pdb
+
+def lazy():
+    import yaml
+
+if typing.TYPE_CHECKING:
+    import numpy
+else:
+    import json
+"#;
+
+        let mut parser = PythonParser::new(source);
+        let imports = parser.parse_imports()?;
+
+        let guards: Vec<(&str, bool, bool, bool)> = imports
+            .iter()
+            .map(|import| {
+                (
+                    import.module_name.as_deref().unwrap(),
+                    import.is_platform_guarded,
+                    import.is_in_function,
+                    import.is_type_checking,
+                )
+            })
+            .collect();
+        assert_eq!(
+            guards,
+            vec![
+                ("sys", false, false, false),
+                ("winreg", true, false, false),
+                ("objc", true, false, false),
+                ("fcntl", true, false, false),
+                ("pywintypes", true, false, false),
+                ("pdb", false, false, false),
+                ("yaml", false, true, false),
+                ("numpy", false, false, true),
+                ("json", false, false, false),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_main_block_imports() -> Result<(), AnalysisError> {
+        init_tracing();
+        let source = r#"
+import requests
+
+def main():
+    import click
+
+if __name__ == "__main__":
+    import rich
+    if VERBOSE:
+        import tqdm
+else:
+    import yaml
+
+if "__main__" == __name__:
+    import typer
+
+if __name__ != "__main__":
+    import toml
+"#;
+
+        let mut parser = PythonParser::new(source);
+        let imports = parser.parse_imports()?;
+
+        let main_block: Vec<(&str, bool)> = imports
+            .iter()
+            .map(|import| (import.module_name.as_deref().unwrap(), import.is_main_block))
+            .collect();
+        assert_eq!(
+            main_block,
+            vec![
+                ("requests", false),
+                ("click", false),
+                ("rich", true),
+                ("tqdm", true),
+                ("yaml", false),
+                ("typer", true),
+                ("toml", false),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_main_guard_only_imports() -> Result<(), AnalysisError> {
+        let source = r#"
+import argparse
+import json
+import logging
+import sys
+
+log = logging.getLogger(__name__)
+
+def main(argv):
+    return json.loads(argv[0])
+
+if __name__ == "__main__":
+    import rich
+    parser = argparse.ArgumentParser()
+    log.info(parser.parse_args())
+    rich.print(main(sys.argv))
+"#;
+        let mut parser = PythonParser::new(source);
+        let (imports, _) = parser.parse_imports_with_usage()?;
+        let main_guard_only: Vec<(Option<&str>, bool)> = imports
+            .iter()
+            .map(|import| (import.module_name.as_deref(), import.main_guard_only))
+            .collect();
+        assert_eq!(
+            main_guard_only,
+            [
+                (Some("argparse"), true),
+                (Some("json"), false),
+                (Some("logging"), false),
+                (Some("sys"), true),
+                (Some("rich"), true),
+            ]
+        );
+        // Only `rich` is imported inside the block
+        assert!(imports[0].is_top_level_import && !imports[0].is_main_block);
+        Ok(())
+    }
+
+    #[test]
+    fn test_annotation_only_imports() -> Result<(), AnalysisError> {
+        let source = r#"
+import numpy.typing
+import pandas as pd
+import attrs
+import polars
+import scipy
+from typing import cast, TYPE_CHECKING
+from decimal import Decimal
+
+def load(path: "os.PathLike", *args: attrs.Attribute, **kwargs: "pd.Series") -> numpy.typing.NDArray:
+    frames = {}  # type: dict[str, polars.DataFrame]
+    value = cast(Decimal, 1)  # type: ignore
+    return [scipy.stats.zscore(x) for x in frames.values() if x]
+
+class Model:
+    size: "Decimal"
+"#;
+        let mut parser = PythonParser::new(source);
+        let (imports, unused) = parser.parse_imports_with_usage()?;
+        let annotation_only: Vec<(Option<&str>, bool)> = imports
+            .iter()
+            .map(|import| (import.module_name.as_deref(), import.is_annotation_only))
+            .collect();
+        assert_eq!(
+            annotation_only,
+            [
+                (Some("numpy.typing"), true),
+                (Some("pandas"), true),
+                (Some("attrs"), true),
+                (Some("polars"), true),
+                (Some("scipy"), false),
+                (Some("typing"), false),
+                (Some("decimal"), true),
+            ]
+        );
+        // `TYPE_CHECKING` is never used
+        let names: Vec<&str> = unused.iter().map(|u| u.name.as_str()).collect();
+        assert_eq!(names, ["TYPE_CHECKING"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_unused_imports() -> Result<(), AnalysisError> {
+        init_tracing();
+        let source = r#"
+from __future__ import annotations
+import os
+import sys as system
+import xml.etree.ElementTree
+import json  # noqa: F401
+from typing import List, Dict as Dictionary
+from collections import *
+from .helpers import exported
+
+__all__ = ["exported"]
+
+def main() -> List[int]:
+    import re
+    import shutil
+    print(system.argv)
+    return [re.compile("x")]
+
+def other():
+    return xml.etree.ElementTree.parse("f")
+
+print(shutil)
+"#;
+
+        let mut parser = PythonParser::new(source);
+        let imports = parser.parse_imports()?;
+        let unused = parser.find_unused_imports(&imports)?;
+
+        let unused: Vec<(&str, &str, usize)> = unused
+            .iter()
+            .map(|u| (u.name.as_str(), u.qualified_name.as_str(), u.line_number))
+            .collect();
+        assert_eq!(
+            unused,
+            vec![
+                ("os", "os", 3),
+                ("Dictionary", "typing.Dict", 7),
+                ("shutil", "shutil", 15),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_availability_flag_imports_are_used() -> Result<(), AnalysisError> {
+        init_tracing();
+        let source = r#"
+HAS_NUMPY = False
+try:
+    import numpy
+    HAS_NUMPY = True
+except ImportError:
+    pass
+
+try:
+    from yaml import CLoader
+    HAS_YAML: bool = True
+except (ImportError, AttributeError):
+    HAS_YAML = False
+
+try:
+    import toml
+    LOADED = "toml"
+except ImportError:
+    LOADED = None
+
+try:
+    import rich
+    HAS_RICH = True
+finally:
+    pass
+
+def zeros():
+    if HAS_NUMPY:
+        return [0]
+"#;
+
+        let mut parser = PythonParser::new(source);
+        let imports = parser.parse_imports()?;
+        assert!(
+            imports
+                .iter()
+                .all(|import| import.is_likely_exception_guarded
+                    || import.module_name.as_deref() == Some("rich"))
+        );
+        let unused: Vec<String> = parser
+            .find_unused_imports(&imports)?
+            .into_iter()
+            .map(|u| u.name)
+            .collect();
+        // Only a flag set to `True` when the import can fail makes it a use
+        assert_eq!(unused, vec!["toml".to_string(), "rich".to_string()]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_star_imports_are_conservative() -> Result<(), AnalysisError> {
+        init_tracing();
+        let source = r#"
+import path
+from os import *
+import json
+
+print(getcwd(), path.join("a", "b"))
+
+def main():
+    import re
+"#;
+
+        let mut parser = PythonParser::new(source);
+        let imports = parser.parse_imports()?;
+        let unused = parser.find_unused_imports(&imports)?;
+
+        // `path` may be `os.path` by the time it's used, but that isn't guessed, and the
+        // star import binds `getcwd` so it counts as used
+        let unused: Vec<(&str, usize, bool)> = unused
+            .iter()
+            .map(|u| (u.name.as_str(), u.line_number, u.is_module_level))
+            .collect();
+        assert_eq!(unused, vec![("json", 4, true), ("re", 9, false)]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lazy_loader_exports_are_used() -> Result<(), AnalysisError> {
+        init_tracing();
+        let source = r#"
+import lazy_loader as lazy
+from typing import TYPE_CHECKING
+
+if TYPE_CHECKING:
+    from . import fft, io, linalg
+    from ._funcs import helper
+
+__getattr__, __dir__, __all__ = lazy.attach(
+    __name__, submodules=["io", "fft"], submod_attrs={"_funcs": ["helper"]}
+)
+"#;
+
+        let mut parser = PythonParser::new(source);
+        let imports = parser.parse_imports()?;
+        let unused: Vec<String> = parser
+            .find_unused_imports(&imports)?
+            .into_iter()
+            .map(|u| u.name)
+            .collect();
+        assert_eq!(unused, vec!["linalg".to_string()]);
+
+        // Exports that can't be read statically might include any module level import
+        let source = r#"
+import lazy_loader as lazy
+from . import linalg
+
+def main():
+    import os
+
+__getattr__, __dir__, __all__ = lazy.attach(__name__, submodules=SUBMODULES)
+"#;
+        let mut parser = PythonParser::new(source);
+        let imports = parser.parse_imports()?;
+        let unused: Vec<String> = parser
+            .find_unused_imports(&imports)?
+            .into_iter()
+            .map(|u| u.name)
+            .collect();
+        assert_eq!(unused, vec!["os".to_string()]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_module_names() -> Result<(), AnalysisError> {
+        let source = r#"
+import os.path
+from typing import Any as A
+VERSION: str = "1"
+a, (b, *c) = 1, (2, 3)
+
+def main():
+    inner = 1
+
+class App:
+    attribute = 1
+
+try:
+    import ujson as json
+except ImportError:
+    json = None
+
+if True:
+    async def serve():
+        pass
+"#;
+        assert_eq!(
+            module_names(source)?.unwrap(),
+            [
+                "A", "App", "VERSION", "a", "b", "c", "json", "main", "os", "serve"
+            ]
+        );
+        assert_eq!(
+            module_names(
+                "from os import *
+"
+            )?,
+            None
+        );
+        assert_eq!(
+            module_names(
+                "def __getattr__(name):
+    pass
+"
+            )?,
+            None
+        );
+        Ok(())
+    }
 }
@@ -0,0 +1,110 @@
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use flate2::read::GzDecoder;
+use tracing::debug;
+
+use crate::error::AnalysisError;
+
+/// The most of an archive's metadata file that's read
+const MAX_METADATA_BYTES: u64 = 16 * 1024 * 1024;
+
+/// Unpack a wheel, or a `.tar.gz` or `.zip` source distribution, into `destination`, and
+/// return the directory of the package: `destination` for a wheel, and the
+/// `{name}-{version}` directory of a source distribution. Entries that would be written
+/// outside `destination` are skipped.
+pub fn unpack_archive(archive: &Path, destination: &Path) -> Result<PathBuf, AnalysisError> {
+    let filename = archive
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default();
+    let read_error = |e: &dyn std::fmt::Display| {
+        AnalysisError::FileReadError(archive.display().to_string(), e.to_string())
+    };
+    if filename.ends_with(".whl") || filename.ends_with(".zip") {
+        let file = fs::File::open(archive).map_err(|e| read_error(&e))?;
+        let mut zip = zip::ZipArchive::new(file).map_err(|e| read_error(&e))?;
+        for i in 0..zip.len() {
+            let mut entry = zip.by_index(i).map_err(|e| read_error(&e))?;
+            let Some(path) = entry.enclosed_name().map(|name| destination.join(name)) else {
+                debug!("Skipping {} in {}", entry.name(), archive.display());
+                continue;
+            };
+            if entry.is_dir() {
+                fs::create_dir_all(&path).map_err(|e| read_error(&e))?;
+                continue;
+            }
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).map_err(|e| read_error(&e))?;
+            }
+            let mut file = fs::File::create(&path).map_err(|e| read_error(&e))?;
+            std::io::copy(&mut entry, &mut file).map_err(|e| read_error(&e))?;
+        }
+    } else if filename.ends_with(".tar.gz") || filename.ends_with(".tgz") {
+        let file = fs::File::open(archive).map_err(|e| read_error(&e))?;
+        tar::Archive::new(GzDecoder::new(file))
+            .unpack(destination)
+            .map_err(|e| read_error(&e))?;
+    } else {
+        return Err(AnalysisError::UnsupportedProjectFormat(
+            archive.display().to_string(),
+        ));
+    }
+    if filename.ends_with(".whl") {
+        return Ok(destination.to_path_buf());
+    }
+    // A source distribution has everything under one directory
+    let mut directories = fs::read_dir(destination)
+        .map_err(|e| read_error(&e))?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir());
+    match (directories.next(), directories.next()) {
+        (Some(directory), None) => Ok(directory),
+        _ => Ok(destination.to_path_buf()),
+    }
+}
+
+/// Whether a path in a source distribution is its `{name}-{version}/PKG-INFO`
+pub(crate) fn is_sdist_metadata(name: &str) -> bool {
+    name.split_once('/')
+        .is_some_and(|(_, file)| file == "PKG-INFO")
+}
+
+/// The first file of a zip archive whose name `matches`
+pub(crate) fn zip_metadata(
+    path: &Path,
+    matches: impl Fn(&str) -> bool,
+) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    let mut archive = zip::ZipArchive::new(fs::File::open(path)?)?;
+    let Some(name) = archive
+        .file_names()
+        .find(|name| matches(name))
+        .map(str::to_string)
+    else {
+        return Ok(None);
+    };
+    let mut content = String::new();
+    archive
+        .by_name(&name)?
+        .take(MAX_METADATA_BYTES)
+        .read_to_string(&mut content)?;
+    Ok(Some(content))
+}
+
+/// The `PKG-INFO` of a gzipped tar source distribution
+pub(crate) fn tar_metadata(path: &Path) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    let mut archive = tar::Archive::new(GzDecoder::new(fs::File::open(path)?));
+    for entry in archive.entries()? {
+        let entry = entry?;
+        if is_sdist_metadata(&entry.path()?.to_string_lossy()) {
+            let mut content = String::new();
+            entry
+                .take(MAX_METADATA_BYTES)
+                .read_to_string(&mut content)?;
+            return Ok(Some(content));
+        }
+    }
+    Ok(None)
+}
@@ -0,0 +1,288 @@
+use std::fmt;
+use std::str::FromStr;
+
+use pep508_rs::pep440_rs::Version;
+
+/// The version constraint of a conda match spec, like `>=1.20,<1.25` or `1.26.*`.
+///
+/// Conda's syntax differs from PEP 440: `,` joins constraints that all have to hold, `|`
+/// joins alternatives and binds looser than `,`, a version ending in `*` or following a
+/// single `=` matches every version it is a prefix of, and a bare version is an exact
+/// match. See https://docs.conda.io/projects/conda-build/en/stable/resources/package-spec.html
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CondaVersionSpec {
+    /// Any version, `*`
+    Any,
+    /// A single constraint
+    Constraint(CondaConstraint),
+    /// Every spec has to hold, joined with `,`
+    All(Vec<CondaVersionSpec>),
+    /// One of the specs has to hold, joined with `|`
+    Either(Vec<CondaVersionSpec>),
+}
+
+/// How a constraint compares a version
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CondaOperator {
+    /// `==1.26.0` or a bare `1.26.0`
+    Equal,
+    /// `!=1.26.0`
+    NotEqual,
+    /// `1.26.*`, `1.26*`, or `=1.26`
+    StartsWith,
+    /// `!=1.26.*`
+    NotStartsWith,
+    /// `>1.20`
+    Greater,
+    /// `>=1.20`
+    GreaterEqual,
+    /// `<1.25`
+    Less,
+    /// `<=1.25`
+    LessEqual,
+    /// `~=1.20`
+    Compatible,
+}
+
+/// An operator and the version it compares against, without any `.*` suffix
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CondaConstraint {
+    pub operator: CondaOperator,
+    pub version: String,
+}
+
+impl FromStr for CondaVersionSpec {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let spec: String = spec.chars().filter(|c| !c.is_whitespace()).collect();
+        if spec.is_empty() {
+            return Err("empty version spec".to_string());
+        }
+        let alternatives = spec
+            .split('|')
+            .map(|alternative| {
+                let constraints = alternative
+                    .split(',')
+                    .map(parse_constraint)
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(flatten(constraints, CondaVersionSpec::All))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(flatten(alternatives, CondaVersionSpec::Either))
+    }
+}
+
+/// A single spec stays as it is, more are joined with `join`
+fn flatten(
+    mut specs: Vec<CondaVersionSpec>,
+    join: fn(Vec<CondaVersionSpec>) -> CondaVersionSpec,
+) -> CondaVersionSpec {
+    if specs.len() == 1 {
+        specs.remove(0)
+    } else {
+        join(specs)
+    }
+}
+
+fn parse_constraint(constraint: &str) -> Result<CondaVersionSpec, String> {
+    if constraint == "*" {
+        return Ok(CondaVersionSpec::Any);
+    }
+    let (operator, version) = [
+        ("==", CondaOperator::Equal),
+        ("!=", CondaOperator::NotEqual),
+        (">=", CondaOperator::GreaterEqual),
+        ("<=", CondaOperator::LessEqual),
+        ("~=", CondaOperator::Compatible),
+        (">", CondaOperator::Greater),
+        ("<", CondaOperator::Less),
+        ("=", CondaOperator::StartsWith),
+    ]
+    .into_iter()
+    .find_map(|(prefix, operator)| {
+        constraint
+            .strip_prefix(prefix)
+            .map(|version| (operator, version))
+    })
+    .unwrap_or((CondaOperator::Equal, constraint));
+
+    // A trailing `*` turns an equality into a prefix match
+    let (operator, version) = match version.strip_suffix('*') {
+        Some(prefix) => {
+            let operator = match operator {
+                CondaOperator::Equal | CondaOperator::StartsWith => CondaOperator::StartsWith,
+                CondaOperator::NotEqual => CondaOperator::NotStartsWith,
+                _ => return Err(format!("`{}` can't end with a wildcard", constraint)),
+            };
+            (operator, prefix.strip_suffix('.').unwrap_or(prefix))
+        }
+        None => (operator, version),
+    };
+    let is_version = |c: char| c.is_ascii_alphanumeric() || "._+!-".contains(c);
+    if version.is_empty() || !version.chars().all(is_version) {
+        return Err(format!("`{}` isn't a valid version constraint", constraint));
+    }
+    Ok(CondaVersionSpec::Constraint(CondaConstraint {
+        operator,
+        version: version.to_string(),
+    }))
+}
+
+impl CondaVersionSpec {
+    /// Check whether a version satisfies the spec. Versions are compared as PEP 440
+    /// versions, which most conda packages of Python distributions use; a constraint on a
+    /// version that isn't one only matches the exact same version string.
+    pub fn contains(&self, version: &str) -> bool {
+        match self {
+            CondaVersionSpec::Any => true,
+            CondaVersionSpec::Constraint(constraint) => constraint.contains(version),
+            CondaVersionSpec::All(specs) => specs.iter().all(|spec| spec.contains(version)),
+            CondaVersionSpec::Either(specs) => specs.iter().any(|spec| spec.contains(version)),
+        }
+    }
+}
+
+impl CondaConstraint {
+    fn contains(&self, version: &str) -> bool {
+        let (Ok(expected), Ok(actual)) =
+            (Version::from_str(&self.version), Version::from_str(version))
+        else {
+            return match self.operator {
+                CondaOperator::NotEqual | CondaOperator::NotStartsWith => self.version != version,
+                _ => self.version == version,
+            };
+        };
+        let starts_with = || {
+            actual.epoch() == expected.epoch() && actual.release().starts_with(expected.release())
+        };
+        match self.operator {
+            CondaOperator::Equal => actual == expected,
+            CondaOperator::NotEqual => actual != expected,
+            CondaOperator::StartsWith => starts_with(),
+            CondaOperator::NotStartsWith => !starts_with(),
+            CondaOperator::Greater => actual > expected,
+            CondaOperator::GreaterEqual => actual >= expected,
+            CondaOperator::Less => actual < expected,
+            CondaOperator::LessEqual => actual <= expected,
+            CondaOperator::Compatible => {
+                // `~=1.20.3` is `>=1.20.3,1.20.*`
+                let release = expected.release();
+                let prefix = &release[..release.len().saturating_sub(1).max(1)];
+                actual >= expected && actual.release().starts_with(prefix)
+            }
+        }
+    }
+}
+
+impl fmt::Display for CondaVersionSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let join = |f: &mut fmt::Formatter<'_>, specs: &[CondaVersionSpec], separator| {
+            for (i, spec) in specs.iter().enumerate() {
+                if i > 0 {
+                    write!(f, "{}", separator)?;
+                }
+                write!(f, "{}", spec)?;
+            }
+            Ok(())
+        };
+        match self {
+            CondaVersionSpec::Any => write!(f, "*"),
+            CondaVersionSpec::Constraint(constraint) => write!(f, "{}", constraint),
+            CondaVersionSpec::All(specs) => join(f, specs, ","),
+            CondaVersionSpec::Either(specs) => join(f, specs, "|"),
+        }
+    }
+}
+
+impl fmt::Display for CondaConstraint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let version = &self.version;
+        match self.operator {
+            CondaOperator::Equal => write!(f, "=={}", version),
+            CondaOperator::NotEqual => write!(f, "!={}", version),
+            CondaOperator::StartsWith => write!(f, "{}.*", version),
+            CondaOperator::NotStartsWith => write!(f, "!={}.*", version),
+            CondaOperator::Greater => write!(f, ">{}", version),
+            CondaOperator::GreaterEqual => write!(f, ">={}", version),
+            CondaOperator::Less => write!(f, "<{}", version),
+            CondaOperator::LessEqual => write!(f, "<={}", version),
+            CondaOperator::Compatible => write!(f, "~={}", version),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn constraint(operator: CondaOperator, version: &str) -> CondaVersionSpec {
+        CondaVersionSpec::Constraint(CondaConstraint {
+            operator,
+            version: version.to_string(),
+        })
+    }
+
+    #[test]
+    fn test_parse_wildcard() {
+        let spec: CondaVersionSpec = "1.26.*".parse().unwrap();
+        assert_eq!(spec, constraint(CondaOperator::StartsWith, "1.26"));
+        assert_eq!("=1.26".parse(), Ok(spec.clone()));
+        assert!(spec.contains("1.26.4"));
+        assert!(!spec.contains("1.27.0"));
+        assert!(!spec.contains("1.2"));
+
+        assert_eq!("*".parse(), Ok(CondaVersionSpec::Any));
+        assert_eq!(
+            "==1.26.0".parse(),
+            Ok(constraint(CondaOperator::Equal, "1.26.0"))
+        );
+        assert_eq!(
+            "1.26.0".parse(),
+            Ok(constraint(CondaOperator::Equal, "1.26.0"))
+        );
+    }
+
+    #[test]
+    fn test_parse_and() {
+        let spec: CondaVersionSpec = ">=1.20,<1.25".parse().unwrap();
+        assert_eq!(
+            spec,
+            CondaVersionSpec::All(vec![
+                constraint(CondaOperator::GreaterEqual, "1.20"),
+                constraint(CondaOperator::Less, "1.25"),
+            ])
+        );
+        assert!(spec.contains("1.24.3"));
+        assert!(!spec.contains("1.25.0"));
+        assert!(!spec.contains("1.19"));
+    }
+
+    #[test]
+    fn test_parse_or() {
+        let spec: CondaVersionSpec = ">=1.20|<1.25".parse().unwrap();
+        assert_eq!(
+            spec,
+            CondaVersionSpec::Either(vec![
+                constraint(CondaOperator::GreaterEqual, "1.20"),
+                constraint(CondaOperator::Less, "1.25"),
+            ])
+        );
+        assert!(spec.contains("2.0"));
+        assert!(spec.contains("1.0"));
+
+        // `,` binds tighter than `|`
+        let spec: CondaVersionSpec = ">=1.20,<1.25|2.*".parse().unwrap();
+        assert_eq!(spec.to_string(), ">=1.20,<1.25|2.*");
+        assert!(spec.contains("2.1"));
+        assert!(!spec.contains("1.26"));
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert!("".parse::<CondaVersionSpec>().is_err());
+        assert!(">=".parse::<CondaVersionSpec>().is_err());
+        assert!(">=1.2*".parse::<CondaVersionSpec>().is_err());
+        assert!("1.2,".parse::<CondaVersionSpec>().is_err());
+    }
+}
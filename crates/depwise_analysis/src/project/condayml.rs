@@ -0,0 +1,578 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde_yaml::Value;
+
+use crate::error::{AnalysisError, ParseDiagnostic};
+use crate::finding::Finding;
+use crate::name_map::normalize_name;
+use crate::project::{
+    CondaMatchSpec, DeclaredPackage, Dependency, ParseOptions, PyPIRequirement,
+    duplicate_provision_findings, line_of_entry, requirementstxt,
+};
+
+/// The dependencies declared in a conda `environment.yml`
+#[derive(Debug, Default)]
+pub(crate) struct CondaEnvironment {
+    /// Conda packages, and the PyPI packages listed under `pip:`
+    pub(crate) dependencies: Vec<Dependency>,
+    /// The `python` package spec, which pins the interpreter rather than providing modules
+    pub(crate) python: Option<CondaMatchSpec>,
+    /// The virtual packages, like `__cuda>=11`, which the machine provides rather than conda
+    pub(crate) virtual_packages: Vec<CondaMatchSpec>,
+    /// The `name:` of the environment
+    pub(crate) name: Option<String>,
+    /// The `channels:` to solve the environment with, highest priority first
+    pub(crate) channels: Vec<String>,
+    /// The entries that couldn't be parsed and were skipped
+    pub(crate) diagnostics: Vec<ParseDiagnostic>,
+}
+
+impl CondaEnvironment {
+    /// Merge another environment into this one, as if its file was solved after this one.
+    /// A package both declare, by conda or PyPI name, takes the other's spec, and the other's
+    /// channels come first.
+    fn merge(&mut self, other: CondaEnvironment) {
+        let mut channels = other.channels;
+        for channel in self.channels.drain(..) {
+            if !channels.contains(&channel) {
+                channels.push(channel);
+            }
+        }
+        self.channels = channels;
+        if other.name.is_some() {
+            self.name = other.name;
+        }
+        for dependency in other.dependencies {
+            match self
+                .dependencies
+                .iter_mut()
+                .find(|existing| same_name(existing, &dependency))
+            {
+                Some(existing) => *existing = dependency,
+                None => self.dependencies.push(dependency),
+            }
+        }
+        if other.python.is_some() {
+            self.python = other.python;
+        }
+        for spec in other.virtual_packages {
+            self.virtual_packages
+                .retain(|existing| existing.name() != spec.name());
+            self.virtual_packages.push(spec);
+        }
+        self.diagnostics.extend(other.diagnostics);
+    }
+}
+
+/// Whether two dependencies are the same package, whatever channel a conda package is from
+fn same_name(a: &Dependency, b: &Dependency) -> bool {
+    match (a, b) {
+        (Dependency::Conda(a), Dependency::Conda(b)) => {
+            normalize_name(a.name()) == normalize_name(b.name())
+        }
+        _ => a.same_package(b),
+    }
+}
+
+/// Parse a conda environment file.
+///
+/// The files listed under a `merge:` key, relative to the file, are merged into it first,
+/// recursively, so an environment can be split across files the way
+/// `conda env update -f base.yml -f dev.yml` combines them.
+pub(crate) fn parse(
+    file_path: &Path,
+    options: &ParseOptions,
+) -> Result<CondaEnvironment, AnalysisError> {
+    parse_merged(file_path, options, &mut Vec::new())
+}
+
+/// Parse a conda environment file, with `merging` holding the files whose merges are being
+/// parsed; merging one of them again is a cycle
+fn parse_merged(
+    file_path: &Path,
+    options: &ParseOptions,
+    merging: &mut Vec<PathBuf>,
+) -> Result<CondaEnvironment, AnalysisError> {
+    let key = fs::canonicalize(file_path).unwrap_or_else(|_| file_path.to_path_buf());
+    if merging.contains(&key) {
+        return Err(AnalysisError::CondaEnvironmentError(
+            file_path.display().to_string(),
+            "the environment merges itself".to_string(),
+        ));
+    }
+    let content = fs::read_to_string(file_path).map_err(|e| {
+        AnalysisError::FileReadError(file_path.to_string_lossy().to_string(), e.to_string())
+    })?;
+    merging.push(key);
+    let environment = parse_contents(&content, file_path, options, merging);
+    merging.pop();
+    environment
+}
+
+fn parse_contents(
+    content: &str,
+    file_path: &Path,
+    options: &ParseOptions,
+    merging: &mut Vec<PathBuf>,
+) -> Result<CondaEnvironment, AnalysisError> {
+    let invalid = |message: String| {
+        AnalysisError::CondaEnvironmentError(file_path.display().to_string(), message)
+    };
+    let document: Value = serde_yaml::from_str(content).map_err(|e| invalid(e.to_string()))?;
+
+    let mut merged = CondaEnvironment::default();
+    let merge_files = match document.get("merge") {
+        Some(Value::Sequence(files)) => files.as_slice(),
+        Some(Value::Null) | None => &[],
+        Some(_) => return Err(invalid("`merge` is not a list of files".to_string())),
+    };
+    for file in merge_files {
+        let Some(file) = file.as_str() else {
+            return Err(invalid(format!("Unsupported merge file {:?}", file)));
+        };
+        let file = file_path.parent().unwrap_or(Path::new(".")).join(file);
+        merged.merge(parse_merged(&file, options, merging)?);
+    }
+
+    let mut environment = CondaEnvironment {
+        name: document
+            .get("name")
+            .and_then(Value::as_str)
+            .map(str::to_string),
+        ..Default::default()
+    };
+    match document.get("channels") {
+        Some(Value::Sequence(channels)) => {
+            for channel in channels {
+                match channel.as_str() {
+                    Some(channel) => environment.channels.push(channel.trim().to_string()),
+                    None => return Err(invalid(format!("Unsupported channel {:?}", channel))),
+                }
+            }
+        }
+        Some(Value::Null) | None => {}
+        Some(_) => return Err(invalid("`channels` is not a list".to_string())),
+    }
+    let entries = match document.get("dependencies") {
+        Some(Value::Sequence(entries)) => entries.as_slice(),
+        Some(Value::Null) | None => &[],
+        Some(_) => return Err(invalid("`dependencies` is not a list".to_string())),
+    };
+    let unsupported = |entry: &Value| {
+        let text = serde_yaml::to_string(entry).unwrap_or_default();
+        let text = text.lines().next().unwrap_or_default().trim();
+        ParseDiagnostic::new(
+            file_path,
+            line_of_entry(content, "dependencies:", text),
+            text,
+            "Unsupported dependency".to_string(),
+        )
+    };
+    for entry in entries {
+        match entry {
+            Value::String(spec) => {
+                let spec = CondaMatchSpec::new(spec);
+                match spec.name() {
+                    "python" => environment.python = Some(spec),
+                    // pip only installs the packages listed under it
+                    "pip" => {}
+                    _ if spec.is_virtual() => environment.virtual_packages.push(spec),
+                    _ => environment.dependencies.push(Dependency::Conda(spec)),
+                }
+            }
+            Value::Mapping(mapping) => {
+                let Some(Value::Sequence(requirements)) = mapping.get("pip") else {
+                    environment.diagnostics.push(unsupported(entry));
+                    continue;
+                };
+                // The pip section takes the same lines as a requirements file next to it
+                let lines: Vec<&str> = requirements.iter().filter_map(Value::as_str).collect();
+                let mut diagnostics = Vec::new();
+                environment
+                    .dependencies
+                    .extend(requirementstxt::parse_lines(
+                        &lines.join("\n"),
+                        file_path,
+                        options,
+                        &mut diagnostics,
+                    )?);
+                // The lines are numbered within the pip section, not the environment file
+                for mut diagnostic in diagnostics {
+                    if diagnostic.location.path == file_path {
+                        diagnostic.location.line =
+                            line_of_entry(content, "- pip:", &diagnostic.text);
+                    }
+                    environment.diagnostics.push(diagnostic);
+                }
+            }
+            _ => environment.diagnostics.push(unsupported(entry)),
+        }
+    }
+    merged.merge(environment);
+    Ok(merged)
+}
+
+/// Find the packages the environment file installs both as conda packages and under `pip:`.
+/// Only the file's own entries are compared, not those of the files it merges or the
+/// requirements files its `pip:` section includes.
+pub(crate) fn declaration_findings(file_path: &Path) -> Result<Vec<Finding>, AnalysisError> {
+    let content = fs::read_to_string(file_path).map_err(|e| {
+        AnalysisError::FileReadError(file_path.to_string_lossy().to_string(), e.to_string())
+    })?;
+    duplicate_provisions(&content, file_path)
+}
+
+fn duplicate_provisions(content: &str, file_path: &Path) -> Result<Vec<Finding>, AnalysisError> {
+    let document: Value = serde_yaml::from_str(content).map_err(|e| {
+        AnalysisError::CondaEnvironmentError(file_path.display().to_string(), e.to_string())
+    })?;
+    let entries = document
+        .get("dependencies")
+        .and_then(Value::as_sequence)
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    let mut conda = Vec::new();
+    let mut pypi = Vec::new();
+    for entry in entries {
+        match entry {
+            Value::String(spec) => {
+                let parsed = CondaMatchSpec::new(spec);
+                let name = parsed.name().to_string();
+                if !matches!(name.as_str(), "python" | "pip") && !parsed.is_virtual() {
+                    conda.push(DeclaredPackage {
+                        line: line_of_entry(content, "dependencies:", spec),
+                        text: spec.trim().to_string(),
+                        name,
+                    });
+                }
+            }
+            Value::Mapping(mapping) => {
+                let Some(Value::Sequence(requirements)) = mapping.get("pip") else {
+                    continue;
+                };
+                for line in requirements.iter().filter_map(Value::as_str) {
+                    let text = line.split(" #").next().unwrap_or_default().trim();
+                    // Options like `-r` and `-e` name files rather than packages
+                    if text.starts_with('-') {
+                        continue;
+                    }
+                    let Ok(requirement) = text.parse::<PyPIRequirement>() else {
+                        continue;
+                    };
+                    pypi.push(DeclaredPackage {
+                        name: requirement.name.to_string(),
+                        text: text.to_string(),
+                        line: line_of_entry(content, "- pip:", text),
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(duplicate_provision_findings(file_path, &conda, &pypi))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_environment_yml() -> Result<(), AnalysisError> {
+        let content = r#"
+name: example
+channels:
+  - conda-forge
+dependencies:
+  - python=3.10
+  - numpy>=1.26
+  - conda-forge::pandas
+  - pip
+  - pip:
+    - requests==2.31.0
+    - rich
+"#;
+        let environment = parse_contents(
+            content,
+            Path::new("environment.yml"),
+            &ParseOptions::new(true, None),
+            &mut Vec::new(),
+        )?;
+
+        let names: Vec<_> = environment
+            .dependencies
+            .iter()
+            .filter_map(|dep| dep.name())
+            .collect();
+        assert_eq!(names, ["numpy", "pandas", "requests", "rich"]);
+        assert!(matches!(environment.dependencies[2], Dependency::PyPI(_)));
+        assert_eq!(
+            environment.python.as_ref().map(CondaMatchSpec::raw_spec),
+            Some("python=3.10")
+        );
+        assert_eq!(environment.name.as_deref(), Some("example"));
+        assert_eq!(environment.channels, ["conda-forge"]);
+        assert!(environment.diagnostics.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_virtual_packages() -> Result<(), AnalysisError> {
+        let content = r#"
+dependencies:
+  - __cuda>=11
+  - __glibc
+  - pytorch
+"#;
+        let environment = parse_contents(
+            content,
+            Path::new("environment.yml"),
+            &ParseOptions::new(true, None),
+            &mut Vec::new(),
+        )?;
+
+        let names: Vec<_> = environment
+            .dependencies
+            .iter()
+            .filter_map(|dep| dep.name())
+            .collect();
+        assert_eq!(names, ["pytorch"]);
+        let virtual_packages: Vec<_> = environment
+            .virtual_packages
+            .iter()
+            .map(CondaMatchSpec::raw_spec)
+            .collect();
+        assert_eq!(virtual_packages, ["__cuda>=11", "__glibc"]);
+        assert_eq!(
+            environment.virtual_packages[0]
+                .version()
+                .map(ToString::to_string),
+            Some(">=11".to_string())
+        );
+        assert!(environment.diagnostics.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_duplicate_provisions() -> Result<(), AnalysisError> {
+        let content = r#"
+dependencies:
+  - python=3.11
+  - numpy=1.26
+  - pytorch
+  - pip
+  - pip:
+    - -r requirements.txt
+    - numpy>=1.26 # for the wheels
+    - torch
+    - rich
+"#;
+        let findings = duplicate_provisions(content, Path::new("environment.yml"))?;
+        let found: Vec<(&str, usize)> = findings
+            .iter()
+            .map(|finding| {
+                (
+                    finding.subject.as_str(),
+                    finding.location.as_ref().unwrap().line,
+                )
+            })
+            .collect();
+        assert_eq!(found, [("numpy", 9), ("torch", 10)]);
+        assert_eq!(
+            findings[0].message,
+            "`numpy` is installed both from conda (`numpy=1.26` on line 4) and from PyPI (`numpy>=1.26` on line 9); the PyPI package overwrites the conda one, so declare it only once"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_collects_invalid_entries() -> Result<(), AnalysisError> {
+        let content = r#"dependencies:
+  - numpy
+  - channel: conda-forge
+  - pip:
+    - requests
+    - flask>=>2
+  - 42
+"#;
+        let environment = parse_contents(
+            content,
+            Path::new("environment.yml"),
+            &ParseOptions::new(true, None),
+            &mut Vec::new(),
+        )?;
+
+        let names: Vec<_> = environment
+            .dependencies
+            .iter()
+            .filter_map(|dep| dep.name())
+            .collect();
+        assert_eq!(names, ["numpy", "requests"]);
+        let found: Vec<_> = environment
+            .diagnostics
+            .iter()
+            .map(|d| (d.location.line, d.text.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            [(3, "channel: conda-forge"), (6, "flask>=>2"), (7, "42")]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_merged_environments() -> Result<(), AnalysisError> {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("envs")).unwrap();
+        fs::write(
+            dir.path().join("envs/base.yml"),
+            "name: base\nchannels: [bioconda, conda-forge]\ndependencies:\n  - python=3.10\n  - conda-forge::numpy=1.24\n  - pandas\n  - pip:\n    - requests==2.28\n",
+        )
+        .unwrap();
+        let file_path = dir.path().join("environment.yml");
+        fs::write(
+            &file_path,
+            "merge:\n  - envs/base.yml\nchannels:\n  - conda-forge\n  - nodefaults\ndependencies:\n  - numpy=1.26\n  - scipy\n  - pip:\n    - requests>=2.31\n",
+        )
+        .unwrap();
+
+        let environment = parse(&file_path, &ParseOptions::new(true, None))?;
+        let specs: Vec<String> = environment
+            .dependencies
+            .iter()
+            .map(|dep| match dep {
+                Dependency::Conda(spec) => spec.raw_spec().to_string(),
+                Dependency::PyPI(requirement) => requirement.to_string(),
+                other => panic!("unexpected dependency {:?}", other),
+            })
+            .collect();
+        // The merging file's specs win, in the place the base declared the package
+        assert_eq!(specs, ["numpy=1.26", "pandas", "requests>=2.31", "scipy"]);
+        assert_eq!(
+            environment.python.as_ref().map(CondaMatchSpec::raw_spec),
+            Some("python=3.10")
+        );
+        // The merging file's channels take priority, and its name would replace the base's
+        assert_eq!(
+            environment.channels,
+            ["conda-forge", "nodefaults", "bioconda"]
+        );
+        assert_eq!(environment.name.as_deref(), Some("base"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_pip_requirements_file() -> Result<(), AnalysisError> {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("envs")).unwrap();
+        fs::write(dir.path().join("envs/requirements.txt"), "flask\n").unwrap();
+        let file_path = dir.path().join("envs/environment.yml");
+        fs::write(
+            &file_path,
+            "dependencies:\n  - numpy\n  - pip:\n    - -r requirements.txt\n    - rich\n",
+        )
+        .unwrap();
+
+        let environment = parse(&file_path, &ParseOptions::new(true, None))?;
+        let names: Vec<_> = environment
+            .dependencies
+            .iter()
+            .filter_map(|dep| dep.name())
+            .collect();
+        assert_eq!(names, ["numpy", "flask", "rich"]);
+        assert!(matches!(environment.dependencies[1], Dependency::PyPI(_)));
+
+        // A requirements file including the environment back is a cycle
+        fs::write(
+            dir.path().join("envs/requirements.txt"),
+            "flask\n-r environment.yml\n",
+        )
+        .unwrap();
+        assert!(matches!(
+            parse(&file_path, &ParseOptions::new(true, None)),
+            Err(AnalysisError::DependencyParseError(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_pip_max_requirements() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("requirements.txt"), "flask\n").unwrap();
+        let file_path = dir.path().join("environment.yml");
+        fs::write(
+            &file_path,
+            "dependencies:\n  - numpy\n  - pip:\n    - -r requirements.txt\n    - rich\n",
+        )
+        .unwrap();
+
+        // The limit covers the pip section and the files it includes
+        assert!(parse(&file_path, &ParseOptions::new(true, Some(2))).is_ok());
+        assert!(matches!(
+            parse(&file_path, &ParseOptions::new(true, Some(1))),
+            Err(AnalysisError::TooManyRequirements(_, 1))
+        ));
+    }
+
+    #[test]
+    fn test_parse_pip_editable_installs() -> Result<(), AnalysisError> {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("subpkg")).unwrap();
+        fs::write(
+            dir.path().join("pyproject.toml"),
+            "[project]\nname = \"example-app\"\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("subpkg/pyproject.toml"),
+            "[project]\nname = \"example-plugin\"\n",
+        )
+        .unwrap();
+        let file_path = dir.path().join("environment.yml");
+        fs::write(
+            &file_path,
+            "dependencies:\n  - numpy\n  - pip:\n    - -e .\n    - --editable ./subpkg\n",
+        )
+        .unwrap();
+
+        let environment = parse(&file_path, &ParseOptions::new(true, None))?;
+        let paths: Vec<(&Path, Option<&str>)> = environment
+            .dependencies
+            .iter()
+            .filter_map(|dep| match dep {
+                Dependency::PackagePath { path, .. } => Some((path.as_path(), dep.name())),
+                _ => None,
+            })
+            .collect();
+        // The paths are resolved against the environment file's directory
+        assert_eq!(
+            paths,
+            [
+                (Path::new("."), Some("example-app")),
+                (Path::new("./subpkg"), Some("example-plugin"))
+            ]
+        );
+        assert!(environment.diagnostics.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_self_referential_merge() {
+        let dir = tempfile::tempdir().unwrap();
+        let file_path = dir.path().join("environment.yml");
+        fs::write(&file_path, "merge: [dev.yml]\ndependencies: [numpy]\n").unwrap();
+        fs::write(
+            dir.path().join("dev.yml"),
+            "merge: [./environment.yml]\ndependencies: [pytest]\n",
+        )
+        .unwrap();
+
+        let error = parse(&file_path, &ParseOptions::new(true, None)).unwrap_err();
+        assert!(
+            matches!(error, AnalysisError::CondaEnvironmentError(ref path, _) if path.ends_with("environment.yml"))
+        );
+
+        fs::write(&file_path, "merge: [environment.yml]\n").unwrap();
+        assert!(parse(&file_path, &ParseOptions::new(true, None)).is_err());
+    }
+}
@@ -0,0 +1,157 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use pep508_rs::ExtraName;
+
+use crate::error::AnalysisError;
+use crate::project::{Dependency, PyPIRequirement};
+
+/// The requirements in a distribution's core metadata: the `METADATA` file of a wheel or an
+/// installed package, or the `PKG-INFO` file of a source distribution
+#[derive(Debug, Default)]
+pub(crate) struct CoreMetadata {
+    /// The distribution name, from the `Name` header
+    pub(crate) name: Option<String>,
+    /// The distribution version, from the `Version` header
+    pub(crate) version: Option<String>,
+    /// The extras listed with `Provides-Extra`, in order
+    pub(crate) extras: Vec<String>,
+    requires_dist: Vec<PyPIRequirement>,
+}
+
+impl CoreMetadata {
+    /// The requirements installed without any extra
+    pub(crate) fn required_dependencies(&self) -> Vec<Dependency> {
+        self.requires_dist
+            .iter()
+            .filter(|requirement| requirement.marker.evaluate_extras(&[]))
+            .cloned()
+            .map(Dependency::PyPI)
+            .collect()
+    }
+
+    /// The requirements an extra adds to the required ones. The `extra == "..."` clause is
+    /// removed from their markers, so only the clauses about the environment are left.
+    pub(crate) fn extra_dependencies(&self, extra: &str) -> Vec<Dependency> {
+        let Ok(extra) = ExtraName::from_str(extra) else {
+            return Vec::new();
+        };
+        let selected = [extra];
+        self.requires_dist
+            .iter()
+            .filter(|requirement| {
+                !requirement.marker.evaluate_extras(&[])
+                    && requirement.marker.evaluate_extras(&selected)
+            })
+            .map(|requirement| {
+                let mut requirement = requirement.clone();
+                requirement.marker = requirement.marker.simplify_extras(&selected);
+                Dependency::PyPI(requirement)
+            })
+            .collect()
+    }
+}
+
+pub(crate) fn parse(file_path: &Path) -> Result<CoreMetadata, AnalysisError> {
+    let content = fs::read_to_string(file_path).map_err(|e| {
+        AnalysisError::FileReadError(file_path.to_string_lossy().to_string(), e.to_string())
+    })?;
+    from_contents(&file_path.display().to_string(), &content)
+}
+
+/// Parse core metadata that was read from `source`, like a file in an archive
+pub(crate) fn from_contents(source: &str, content: &str) -> Result<CoreMetadata, AnalysisError> {
+    parse_contents(content)
+        .map_err(|message| AnalysisError::CoreMetadataError(source.to_string(), message))
+}
+
+fn parse_contents(content: &str) -> Result<CoreMetadata, String> {
+    let mut metadata = CoreMetadata::default();
+    let mut extras = HashSet::new();
+    for (name, value) in headers(content) {
+        match name.to_ascii_lowercase().as_str() {
+            "name" => metadata.name = Some(value),
+            "version" => metadata.version = Some(value),
+            "provides-extra" if extras.insert(value.clone()) => metadata.extras.push(value),
+            "requires-dist" => {
+                let requirement = PyPIRequirement::from_str(&value)
+                    .map_err(|e| format!("Invalid Requires-Dist `{}`: {}", value, e))?;
+                metadata.requires_dist.push(requirement);
+            }
+            _ => {}
+        }
+    }
+    Ok(metadata)
+}
+
+/// The `Name: value` headers before the description, with continuation lines joined
+fn headers(content: &str) -> Vec<(String, String)> {
+    let mut headers: Vec<(String, String)> = Vec::new();
+    for line in content.lines() {
+        if line.trim().is_empty() {
+            break;
+        }
+        if line.starts_with([' ', '\t']) {
+            if let Some((_, value)) = headers.last_mut() {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+            continue;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+    headers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn requirements(dependencies: &[Dependency]) -> Vec<String> {
+        dependencies
+            .iter()
+            .map(|dependency| match dependency {
+                Dependency::PyPI(requirement) => requirement.to_string(),
+                other => panic!("unexpected dependency {:?}", other),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_core_metadata() -> Result<(), String> {
+        let content = r#"Metadata-Version: 2.1
+Name: example
+Version: 1.0.0
+Requires-Dist: requests>=2
+Requires-Dist: tomli; python_version < "3.11"
+Provides-Extra: test
+Requires-Dist: pytest; extra == "test"
+Requires-Dist: exceptiongroup; extra == "test" and python_version < "3.11"
+Provides-Extra: socks
+Requires-Dist: pysocks!=1.5.7,>=1.5.6; extra == "socks"
+
+Requires-Dist: not-a-header
+"#;
+        let metadata = parse_contents(content)?;
+
+        assert_eq!(metadata.extras, ["test", "socks"]);
+        assert_eq!(
+            requirements(&metadata.required_dependencies()),
+            ["requests>=2", "tomli ; python_full_version < '3.11'"]
+        );
+        assert_eq!(
+            requirements(&metadata.extra_dependencies("test")),
+            ["pytest", "exceptiongroup ; python_full_version < '3.11'"]
+        );
+        assert_eq!(
+            requirements(&metadata.extra_dependencies("socks")),
+            ["pysocks>=1.5.6,!=1.5.7"]
+        );
+        assert!(metadata.extra_dependencies("docs").is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,118 @@
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use toml::Value;
+
+use crate::error::AnalysisError;
+use crate::project::{Dependency, PyPIRequirement};
+
+/// Check whether a Python file embeds PEP 723 `script` metadata
+pub(crate) fn has_metadata(file_path: &Path) -> bool {
+    fs::read_to_string(file_path).is_ok_and(|content| {
+        content
+            .lines()
+            .any(|line| line.trim_end() == "# /// script")
+    })
+}
+
+/// Read the dependencies a script declares in its PEP 723 metadata block
+pub(crate) fn parse(file_path: &Path) -> Result<Vec<Dependency>, AnalysisError> {
+    let content = fs::read_to_string(file_path).map_err(|e| {
+        AnalysisError::FileReadError(file_path.to_string_lossy().to_string(), e.to_string())
+    })?;
+    parse_contents(&content).map_err(|message| {
+        AnalysisError::InlineScriptError(file_path.display().to_string(), message)
+    })
+}
+
+fn parse_contents(content: &str) -> Result<Vec<Dependency>, String> {
+    let Some(metadata) = metadata_block(content)? else {
+        return Ok(Vec::new());
+    };
+    let document: Value = toml::from_str(&metadata).map_err(|e| e.to_string())?;
+    let Some(dependencies) = document.get("dependencies") else {
+        return Ok(Vec::new());
+    };
+    dependencies
+        .as_array()
+        .ok_or_else(|| "`dependencies` is not a list".to_string())?
+        .iter()
+        .map(|dependency| {
+            let dependency = dependency
+                .as_str()
+                .ok_or_else(|| format!("Unsupported dependency {}", dependency))?;
+            PyPIRequirement::from_str(dependency)
+                .map(Dependency::PyPI)
+                .map_err(|e| e.to_string())
+        })
+        .collect()
+}
+
+/// Extract the TOML of the `script` metadata block.
+///
+/// The block starts with a `# /// script` line and ends at the last `# ///` line of the
+/// comment lines that follow it; every line in between is `#` or starts with `# `.
+fn metadata_block(content: &str) -> Result<Option<String>, String> {
+    let mut lines = content.lines();
+    let mut block = None;
+    while let Some(line) = lines.next() {
+        if line.trim_end() != "# /// script" {
+            continue;
+        }
+        if block.is_some() {
+            return Err("Only one `script` metadata block is allowed".to_string());
+        }
+        let comments: Vec<&str> = lines
+            .by_ref()
+            .take_while(|line| *line == "#" || line.starts_with("# "))
+            .collect();
+        let end = comments
+            .iter()
+            .rposition(|line| line.trim_end() == "# ///")
+            .ok_or_else(|| "The `script` metadata block is never closed".to_string())?;
+        let toml: Vec<&str> = comments[..end]
+            .iter()
+            .map(|line| line.strip_prefix("# ").unwrap_or_default())
+            .collect();
+        block = Some(toml.join("\n"));
+    }
+    Ok(block)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_inline_script_metadata() -> Result<(), String> {
+        let content = r#"#!/usr/bin/env python3
+# /// script
+# requires-python = ">=3.11"
+# dependencies = [
+#   "requests<3",
+#   "rich",
+# ]
+#
+# [tool.example]
+# key = "value"
+# ///
+
+import requests
+"#;
+        let names: Vec<String> = parse_contents(content)?
+            .iter()
+            .filter_map(|dep| dep.name().map(str::to_string))
+            .collect();
+        assert_eq!(names, ["requests", "rich"]);
+
+        assert!(parse_contents("import requests\n")?.is_empty());
+        assert!(parse_contents("# /// script\n# dependencies = []\n").is_err());
+        assert!(
+            parse_contents("# /// script\n# ///\nimport os\n# /// script\n# ///\n")
+                .unwrap_err()
+                .contains("Only one")
+        );
+        Ok(())
+    }
+}
@@ -1,14 +1,133 @@
+#[cfg(feature = "archives")]
+mod archive;
+pub mod condaversion;
+#[cfg(feature = "conda")]
 mod condayml;
+mod coremetadata;
+pub(crate) mod inlinescript;
+#[cfg(feature = "python-parsing")]
+mod noxfile;
+mod packagefile;
 mod pixitoml;
-mod pyprojecttoml;
-mod requirementstxt;
+mod poetrylock;
+pub(crate) mod pyprojecttoml;
+pub(crate) mod requirementstxt;
+mod source;
+mod toxini;
 
-use crate::error::AnalysisError;
+use crate::error::{AnalysisError, ParseDiagnostic};
+use crate::finding::{Finding, Location, Rule};
+#[cfg(feature = "python-parsing")]
+use crate::layout::PackageLayout;
+use crate::name_map;
+use crate::options::DEFAULT_MAX_REQUIREMENTS;
+#[cfg(feature = "archives")]
+pub use archive::unpack_archive;
+pub use packagefile::PackageFile;
 pub use pep508_rs::Requirement as PyPIRequirement;
+pub use requirementstxt::{IncludeTree, Provenance};
+pub use source::{DependencySource, ParseOptions, SourceFactory, SourceRegistry};
 
-use crate::EnvironmentBuilderSource;
+use crate::{EnvironmentBuilderSource, PythonVersion};
+use condaversion::CondaVersionSpec;
+
+use std::path::{Component, Path, PathBuf};
+
+use pep508_rs::pep440_rs::{Version, VersionSpecifiers};
+use pep508_rs::{
+    ExtraName, MarkerTree, MarkerTreeKind, MarkerValueExtra, MarkerValueVersion, VersionOrUrl,
+};
+use tracing::{debug, info};
+
+/// Collect the extras a marker compares `extra` against, in the order they're found
+fn marker_extras(marker: &MarkerTree, extras: &mut Vec<ExtraName>) {
+    let children: Vec<MarkerTree> = match marker.kind() {
+        MarkerTreeKind::True | MarkerTreeKind::False => Vec::new(),
+        MarkerTreeKind::Version(marker) => marker.edges().map(|(_, tree)| tree).collect(),
+        MarkerTreeKind::String(marker) => marker.children().map(|(_, tree)| tree).collect(),
+        MarkerTreeKind::In(marker) => marker.children().map(|(_, tree)| tree).collect(),
+        MarkerTreeKind::Contains(marker) => marker.children().map(|(_, tree)| tree).collect(),
+        MarkerTreeKind::Extra(marker) => {
+            if let MarkerValueExtra::Extra(name) = marker.name()
+                && !extras.contains(name)
+            {
+                extras.push(name.clone());
+            }
+            marker.children().map(|(_, tree)| tree).collect()
+        }
+    };
+    for child in &children {
+        marker_extras(child, extras);
+    }
+}
+
+/// Split dependencies selected by an `extra == "..."` marker, as in requirements generated
+/// from a distribution's metadata, from the ones installed without any extra. Returns the
+/// dependencies installed without an extra, and the dependencies each extra adds in the
+/// order the extras first appear, with the `extra` clause removed from their markers.
+fn split_extras(
+    dependencies: Vec<Dependency>,
+) -> (Vec<Dependency>, Vec<(ExtraName, Vec<Dependency>)>) {
+    let mut extras = Vec::new();
+    for dependency in &dependencies {
+        if let Dependency::PyPI(requirement) = dependency {
+            marker_extras(&requirement.marker, &mut extras);
+        }
+    }
+    let mut base = Vec::new();
+    let mut selected: Vec<(ExtraName, Vec<Dependency>)> = extras
+        .into_iter()
+        .map(|extra| (extra, Vec::new()))
+        .collect();
+    for dependency in dependencies {
+        let Dependency::PyPI(requirement) = &dependency else {
+            base.push(dependency);
+            continue;
+        };
+        if requirement.marker.evaluate_extras(&[]) {
+            base.push(dependency);
+            continue;
+        }
+        for (extra, dependencies) in &mut selected {
+            let names = [extra.clone()];
+            if requirement.marker.evaluate_extras(&names) {
+                let mut requirement = requirement.clone();
+                requirement.marker = requirement.marker.simplify_extras(&names);
+                dependencies.push(Dependency::PyPI(requirement));
+            }
+        }
+    }
+    (base, selected)
+}
+/// Check whether a marker can hold for a Python version, whatever the other clauses are
+fn marker_allows_python(marker: &MarkerTree, version: &Version) -> bool {
+    match marker.kind() {
+        MarkerTreeKind::True => true,
+        MarkerTreeKind::False => false,
+        MarkerTreeKind::Version(marker) => {
+            let is_python = matches!(
+                marker.key(),
+                MarkerValueVersion::PythonVersion | MarkerValueVersion::PythonFullVersion
+            );
+            marker.edges().any(|(range, tree)| {
+                (!is_python || range.contains(version)) && marker_allows_python(&tree, version)
+            })
+        }
+        MarkerTreeKind::String(marker) => marker
+            .children()
+            .any(|(_, tree)| marker_allows_python(&tree, version)),
+        MarkerTreeKind::In(marker) => marker
+            .children()
+            .any(|(_, tree)| marker_allows_python(&tree, version)),
+        MarkerTreeKind::Contains(marker) => marker
+            .children()
+            .any(|(_, tree)| marker_allows_python(&tree, version)),
+        MarkerTreeKind::Extra(marker) => marker
+            .children()
+            .any(|(_, tree)| marker_allows_python(&tree, version)),
+    }
+}
 
-use std::path::PathBuf;
 /// Implements a match spec for Conda packages. Follows the rules in https://github.com/conda/conda/blob/main/conda/models/match_spec.py#L569
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct CondaMatchSpec {
@@ -16,6 +135,8 @@ pub struct CondaMatchSpec {
     name: String,
     /// The raw spec string that was parsed
     raw_spec: String,
+    /// The version constraint, `None` if there is none or it can't be parsed
+    version: Option<CondaVersionSpec>,
 }
 
 impl CondaMatchSpec {
@@ -27,11 +148,55 @@ impl CondaMatchSpec {
         // - package=1.0
         // - package>=1.0
         // - package[build=*]
+        // - package 1.0.*
         // - channel::package
         let raw_spec = spec.trim().to_string();
         let name = Self::extract_name(&raw_spec);
 
-        Self { name, raw_spec }
+        let mut spec = Self {
+            name,
+            raw_spec,
+            version: None,
+        };
+        spec.version = spec
+            .version_constraint()
+            .and_then(|constraint| match constraint.parse() {
+                Ok(version) => Some(version),
+                Err(e) => {
+                    debug!("Ignoring the version of `{}`: {}", spec.raw_spec, e);
+                    None
+                }
+            });
+        spec
+    }
+
+    /// Find the version constraint in the spec: the text following the name up to the build
+    /// string, like `>=1.20, <1.25` in `numpy >=1.20, <1.25 py311*`, or the `version` key in
+    /// brackets, like `numpy[version='>=1.20']`
+    fn version_constraint(&self) -> Option<String> {
+        let spec = self.version_spec();
+        let (spec, brackets) = match spec.split_once('[') {
+            Some((spec, brackets)) => (spec, Some(brackets.trim_end_matches(']'))),
+            None => (spec, None),
+        };
+        // Whitespace after an operator, or around `,` and `|`, is part of the version
+        let mut version = String::new();
+        for token in spec.split_whitespace() {
+            let continues = version.is_empty()
+                || version.ends_with([',', '|', '=', '<', '>', '!', '~'])
+                || token.starts_with([',', '|']);
+            if !continues {
+                break;
+            }
+            version.push_str(token);
+        }
+        if !version.is_empty() {
+            return Some(version);
+        }
+        brackets?.split(',').find_map(|entry| {
+            let (key, value) = entry.split_once('=')?;
+            (key.trim() == "version").then(|| value.trim().trim_matches(['\'', '"']).to_string())
+        })
     }
 
     /// Extract the package name from a conda spec string
@@ -40,14 +205,12 @@ impl CondaMatchSpec {
         let without_channel = spec.split("::").last().unwrap_or(spec);
 
         // Remove any version constraints and build specs
-        let name_part = without_channel
-            .split(|c: char| c == '=' || c == '>' || c == '<' || c == '~' || c == '[')
+        without_channel
+            .split(|c: char| ['=', '>', '<', '~', '!', '['].contains(&c) || c.is_whitespace())
             .next()
             .unwrap_or(without_channel)
             .trim()
-            .to_string();
-
-        name_part
+            .to_string()
     }
 
     /// Get the package name
@@ -55,10 +218,39 @@ impl CondaMatchSpec {
         &self.name
     }
 
+    /// Whether the spec is a virtual package, like `__cuda>=11` or `__glibc`, which describes
+    /// the machine rather than a package to install
+    pub fn is_virtual(&self) -> bool {
+        self.name.starts_with("__")
+    }
+
     /// Get the original raw spec string
     pub fn raw_spec(&self) -> &str {
         &self.raw_spec
     }
+
+    /// Get the channel the spec asks for, e.g. `conda-forge` for `conda-forge::numpy`
+    pub fn channel(&self) -> Option<&str> {
+        self.raw_spec
+            .rsplit_once("::")
+            .map(|(channel, _)| channel.trim())
+            .filter(|channel| !channel.is_empty())
+    }
+
+    /// Get the parsed version constraint, e.g. `1.26.*` for `numpy=1.26`
+    pub fn version(&self) -> Option<&CondaVersionSpec> {
+        self.version.as_ref()
+    }
+
+    /// Get the constraints following the package name, e.g. `=3.10` for `python=3.10`
+    pub fn version_spec(&self) -> &str {
+        let without_channel = self.raw_spec.split("::").last().unwrap_or(&self.raw_spec);
+        without_channel
+            .trim_start()
+            .strip_prefix(self.name.as_str())
+            .unwrap_or(without_channel)
+            .trim()
+    }
 }
 
 /// Represents a Python package dependency with its version requirements
@@ -68,10 +260,368 @@ pub enum Dependency {
     PyPI(PyPIRequirement),
     /// A dependency on a Conda package
     Conda(CondaMatchSpec),
-    /// Explict URL dependency where we know for sure what the package name is (e.g. https://example.com/package-1.0.0-py3-none-any.whl)
-    PackageUrl(String),
-    /// Explict path dependency where we don't know for sure what the package name is (e.g. /path/to/package-1.0.0-py3-none-any.whl)
-    PackagePath(PathBuf),
+    /// Explict URL dependency (e.g. https://example.com/package-1.0.0-py3-none-any.whl)
+    PackageUrl {
+        url: String,
+        /// The package it installs, if the URL tells
+        package: Option<PackageFile>,
+    },
+    /// Explict path dependency (e.g. /path/to/package-1.0.0-py3-none-any.whl)
+    PackagePath {
+        path: PathBuf,
+        /// The package it installs, if the file name or the package's metadata tells
+        package: Option<PackageFile>,
+    },
+    /// Version control dependency (e.g. git+https://github.com/org/repo.git@v1.0)
+    PackageVcs {
+        url: String,
+        /// The package it installs, if the declaration or the `#egg=` fragment tells
+        package: Option<PackageFile>,
+    },
+}
+
+/// The version control systems pip installs from, as URL scheme prefixes like `git+https`
+const VCS_SCHEMES: [&str; 4] = ["git", "hg", "svn", "bzr"];
+
+impl Dependency {
+    /// The dependency a PEP 508 requirement declares. A direct reference, like
+    /// `foo @ https://...`, `foo @ git+https://...` or `foo @ file:///...`, is a URL, version
+    /// control or path dependency named after the requirement, whose version comes from the
+    /// file it points to.
+    pub fn from_requirement(requirement: PyPIRequirement) -> Self {
+        let Some(VersionOrUrl::Url(url)) = &requirement.version_or_url else {
+            return Dependency::PyPI(requirement);
+        };
+        let name = requirement.name.as_ref();
+        if url.scheme() == "file"
+            && let Ok(path) = url.to_file_path()
+        {
+            // File URLs are absolute, so there's nothing to resolve the path against
+            let found = PackageFile::from_path(&path, Path::new(""));
+            return Dependency::PackagePath {
+                path,
+                package: PackageFile::declared_as(name, found),
+            };
+        }
+        let given = url.given().unwrap_or(url.as_str());
+        let package = PackageFile::declared_as(name, PackageFile::from_url(given));
+        if is_vcs_url(given) {
+            return Dependency::PackageVcs {
+                url: given.to_string(),
+                package,
+            };
+        }
+        Dependency::PackageUrl {
+            url: given.to_string(),
+            package,
+        }
+    }
+
+    /// A git dependency declared as a repository URL and an optional revision, like the
+    /// `git` and `rev` keys of a poetry or pixi entry, named after its key
+    pub(crate) fn git(name: &str, repository: &str, revision: Option<&str>) -> Self {
+        let mut url = if is_vcs_url(repository) {
+            repository.to_string()
+        } else {
+            format!("git+{}", repository)
+        };
+        if let Some(revision) = revision {
+            url.push('@');
+            url.push_str(revision);
+        }
+        Dependency::PackageVcs {
+            package: PackageFile::declared_as(name, None),
+            url,
+        }
+    }
+
+    /// A URL dependency, named after the `#egg=` fragment or the file name the URL ends with.
+    /// URLs like `git+https://...` are version control dependencies.
+    pub fn package_url(url: &str) -> Self {
+        let package = PackageFile::from_url(url);
+        if package.is_none() {
+            debug!(
+                "Can't tell which package {} installs, it won't match any import",
+                url
+            );
+        }
+        if is_vcs_url(url) {
+            return Dependency::PackageVcs {
+                url: url.to_string(),
+                package,
+            };
+        }
+        Dependency::PackageUrl {
+            url: url.to_string(),
+            package,
+        }
+    }
+
+    /// A path dependency, named after the package's metadata or file name. Relative paths
+    /// are resolved against `base`, the directory of the file declaring the dependency.
+    pub fn package_path(path: &Path, base: &Path) -> Self {
+        let package = PackageFile::from_path(path, base);
+        if package.is_none() {
+            debug!(
+                "Can't tell which package {} installs, it won't match any import",
+                path.display()
+            );
+        }
+        Dependency::PackagePath {
+            path: path.to_path_buf(),
+            package,
+        }
+    }
+
+    /// Get the package name, if the dependency declares one
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            Dependency::PyPI(requirement) => Some(requirement.name.as_ref()),
+            Dependency::Conda(spec) => Some(spec.name()),
+            Dependency::PackageUrl { package, .. }
+            | Dependency::PackagePath { package, .. }
+            | Dependency::PackageVcs { package, .. } => {
+                package.as_ref().map(|package| package.name.as_str())
+            }
+        }
+    }
+
+    /// The extras the dependency asks for, like `parquet` in `pandas[parquet]`. Only PyPI
+    /// requirements have extras.
+    pub fn extras(&self) -> &[ExtraName] {
+        match self {
+            Dependency::PyPI(requirement) => &requirement.extras,
+            _ => &[],
+        }
+    }
+
+    /// Ask for the extras of another declaration of the same package too, keeping the extras
+    /// sorted once any are added. Only PyPI requirements have extras.
+    fn add_extras(&mut self, extras: &[ExtraName]) {
+        let Dependency::PyPI(requirement) = self else {
+            return;
+        };
+        let missing: Vec<&ExtraName> = extras
+            .iter()
+            .filter(|extra| !requirement.extras.contains(extra))
+            .collect();
+        if missing.is_empty() {
+            return;
+        }
+        requirement.extras.extend(missing.into_iter().cloned());
+        requirement.extras.sort();
+    }
+
+    /// Check whether the dependency asks for an extra, comparing normalized names
+    pub fn has_extra(&self, extra: &str) -> bool {
+        let extra = name_map::normalize_name(extra);
+        self.extras()
+            .iter()
+            .any(|name| name_map::normalize_name(name.as_ref()) == extra)
+    }
+
+    /// The environment marker of the dependency, if it restricts the platforms the
+    /// dependency is installed on (e.g. `sys_platform == 'win32'`)
+    pub fn platform_marker(&self) -> Option<String> {
+        const PLATFORM_MARKERS: [&str; 5] = [
+            "sys_platform",
+            "platform_system",
+            "os_name",
+            "platform_machine",
+            "platform_release",
+        ];
+        let Dependency::PyPI(requirement) = self else {
+            return None;
+        };
+        requirement
+            .marker
+            .try_to_string()
+            .filter(|marker| PLATFORM_MARKERS.iter().any(|name| marker.contains(name)))
+    }
+
+    /// Check whether the dependency is installed for a Python version, judging by the
+    /// `python_version` clauses of its marker. Clauses about extras are assumed to hold.
+    pub fn applies_to_python(&self, version: PythonVersion) -> bool {
+        let Dependency::PyPI(requirement) = self else {
+            return true;
+        };
+        let version = Version::new([u64::from(version.major), u64::from(version.minor)]);
+        marker_allows_python(&requirement.marker, &version)
+    }
+
+    /// Check if two dependencies declare the same package, whatever versions, extras, or
+    /// markers they ask for and however they're formatted.
+    ///
+    /// PyPI names are compared normalized, so `Flask_Login>=0.6` and `flask-login` are the
+    /// same package. Conda names are compared normalized too, and when both specs name a
+    /// channel it has to match. Paths are compared without `.` components, and URLs and
+    /// version control URLs without a trailing slash. A PyPI and a conda dependency are never
+    /// the same package.
+    pub fn same_package(&self, other: &Dependency) -> bool {
+        match (self, other) {
+            (Dependency::PyPI(a), Dependency::PyPI(b)) => {
+                name_map::normalize_name(a.name.as_ref())
+                    == name_map::normalize_name(b.name.as_ref())
+            }
+            (Dependency::Conda(a), Dependency::Conda(b)) => {
+                let same_channel = match (a.channel(), b.channel()) {
+                    (Some(a), Some(b)) => a == b,
+                    _ => true,
+                };
+                same_channel
+                    && name_map::normalize_name(a.name()) == name_map::normalize_name(b.name())
+            }
+            (Dependency::PackageUrl { url: a, .. }, Dependency::PackageUrl { url: b, .. }) => {
+                a.trim().trim_end_matches('/') == b.trim().trim_end_matches('/')
+            }
+            (Dependency::PackagePath { path: a, .. }, Dependency::PackagePath { path: b, .. }) => {
+                normalize_path(a) == normalize_path(b)
+            }
+            (Dependency::PackageVcs { url: a, .. }, Dependency::PackageVcs { url: b, .. }) => {
+                a.trim().trim_end_matches('/') == b.trim().trim_end_matches('/')
+            }
+            _ => false,
+        }
+    }
+
+    /// Check if this dependency provides the given PyPI distribution.
+    ///
+    /// Conda dependencies are translated through the conda to PyPI alias table, so a
+    /// conda `pytorch` dependency satisfies an import that resolves to the `torch`
+    /// distribution.
+    pub fn satisfies(&self, pypi_name: &str) -> bool {
+        match self {
+            Dependency::PyPI(requirement) => {
+                name_map::normalize_name(requirement.name.as_ref())
+                    == name_map::normalize_name(pypi_name)
+            }
+            Dependency::Conda(spec) => name_map::conda_matches_pypi(spec.name(), pypi_name),
+            Dependency::PackageUrl { .. }
+            | Dependency::PackagePath { .. }
+            | Dependency::PackageVcs { .. } => self
+                .name()
+                .is_some_and(|name| name == name_map::normalize_name(pypi_name)),
+        }
+    }
+
+    /// Check if this dependency provides the given conda package.
+    ///
+    /// PyPI dependencies are translated through the conda to PyPI alias table, so a
+    /// PyPI `opencv-python` dependency satisfies a conda `opencv` package.
+    pub fn satisfies_conda(&self, conda_name: &str) -> bool {
+        match self {
+            Dependency::PyPI(requirement) => {
+                name_map::conda_matches_pypi(conda_name, requirement.name.as_ref())
+            }
+            Dependency::Conda(spec) => {
+                name_map::normalize_name(spec.name()) == name_map::normalize_name(conda_name)
+            }
+            Dependency::PackageUrl { .. }
+            | Dependency::PackagePath { .. }
+            | Dependency::PackageVcs { .. } => false,
+        }
+    }
+}
+
+/// Whether a URL installs from version control, like `git+https://github.com/org/repo`
+pub(crate) fn is_vcs_url(url: &str) -> bool {
+    url.split_once('+')
+        .is_some_and(|(scheme, _)| VCS_SCHEMES.contains(&scheme))
+}
+
+/// Drop `.` components and trailing separators so equivalent spellings of a path compare equal
+/// A path without its `.` components. Windows paths are compared the way Windows does
+/// whatever the platform, using either separator and ignoring case.
+fn normalize_path(path: &Path) -> PathBuf {
+    let text = path.to_string_lossy();
+    let path = if is_windows_path(&text) {
+        PathBuf::from(text.replace('\\', "/").to_lowercase())
+    } else {
+        path.to_path_buf()
+    };
+    path.components()
+        .filter(|component| *component != Component::CurDir)
+        .collect()
+}
+
+/// Whether a path starts with a drive letter, like `C:\wheels` or `C:/wheels`, or is a UNC
+/// path like `\\server\share`
+pub(crate) fn is_windows_path(text: &str) -> bool {
+    let has_drive = match text.as_bytes() {
+        [drive, b':', separator, ..] => {
+            drive.is_ascii_alphabetic() && matches!(separator, b'\\' | b'/')
+        }
+        _ => false,
+    };
+    has_drive || text.starts_with(r"\\")
+}
+
+/// Add dependencies to a list, skipping those that declare a package already in it (see
+/// [`Dependency::same_package`]). The first declaration of a package wins, asking for the
+/// extras of both, so `celery[redis]` and `celery[auth]` merge into `celery[auth,redis]`.
+pub fn merge_dependencies(
+    dependencies: &mut Vec<Dependency>,
+    other: impl IntoIterator<Item = Dependency>,
+) {
+    for dependency in other {
+        match dependencies
+            .iter_mut()
+            .find(|d| d.same_package(&dependency))
+        {
+            Some(existing) => existing.add_extras(dependency.extras()),
+            None => dependencies.push(dependency),
+        }
+    }
+}
+
+/// Whether two extras are in the same group of alternatives, comparing normalized names
+pub(crate) fn are_exclusive(exclusive_extras: &[Vec<String>], first: &str, second: &str) -> bool {
+    let (first, second) = (
+        name_map::normalize_name(first),
+        name_map::normalize_name(second),
+    );
+    first != second
+        && exclusive_extras.iter().any(|group| {
+            let contains = |extra: &str| group.iter().any(|e| name_map::normalize_name(e) == extra);
+            contains(&first) && contains(&second)
+        })
+}
+
+/// What a conda environment file declares besides its packages
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct CondaEnvironmentMetadata {
+    /// The `name:` of the environment
+    pub name: Option<String>,
+    /// The `channels:` as listed, highest priority first
+    pub channels: Vec<String>,
+}
+
+impl CondaEnvironmentMetadata {
+    /// The channels to solve the environment with. `nodefaults` isn't a channel but drops
+    /// `defaults`, which conda would otherwise add. Without any channel listed, packages are
+    /// solved from conda-forge.
+    pub fn solver_channels(&self) -> Vec<String> {
+        let nodefaults = self.channels.iter().any(|channel| channel == "nodefaults");
+        let channels: Vec<String> = self
+            .channels
+            .iter()
+            .filter(|channel| match channel.as_str() {
+                "nodefaults" => false,
+                "defaults" => !nodefaults,
+                _ => true,
+            })
+            .cloned()
+            .collect();
+        if channels.is_empty() {
+            info!(
+                "{} lists no channels, solving it with conda-forge",
+                self.name.as_deref().unwrap_or("The conda environment")
+            );
+            return vec!["conda-forge".to_string()];
+        }
+        channels
+    }
 }
 
 /// Represents a configuration of dependencies from the project
@@ -83,8 +633,37 @@ pub struct Configuration {
     /// The name of the configuration
     name: String,
 
+    /// The extra the configuration installs, if it isn't the base configuration
+    extra: Option<String>,
+
+    /// The hatch environment, pixi environment, or pixi feature the configuration installs, if
+    /// it comes from one
+    environment: Option<String>,
+
+    /// The platform the configuration is installed on, when its source installs different
+    /// dependencies on each of the platforms it supports
+    platform: Option<String>,
+
+    /// The Python interpreter the source pins, like `python=3.10` in a conda environment
+    python_requirement: Option<CondaMatchSpec>,
+
+    /// The virtual packages the source requires of the machine, like `__cuda>=11`
+    virtual_packages: Vec<CondaMatchSpec>,
+
+    /// The Python versions the project supports, like `>=3.9` from `requires-python`
+    requires_python: Option<VersionSpecifiers>,
+
+    /// The name and channels of the conda environment, if the source is an environment file
+    conda_environment: Option<CondaEnvironmentMetadata>,
+
     /// The source of the configuration
     source: EnvironmentBuilderSource,
+
+    /// Where each dependency of a requirements file is declared, by normalized name
+    provenance: Vec<(String, Provenance)>,
+
+    /// The files a requirements file includes
+    includes: Option<IncludeTree>,
 }
 
 impl Configuration {
@@ -96,47 +675,584 @@ impl Configuration {
         Self {
             dependencies,
             name,
+            extra: None,
+            environment: None,
+            python_requirement: None,
+            virtual_packages: Vec::new(),
+            requires_python: None,
+            conda_environment: None,
+            platform: None,
             source,
+            provenance: Vec::new(),
+            includes: None,
         }
     }
+
+    pub fn dependencies(&self) -> &[Dependency] {
+        &self.dependencies
+    }
+
+    /// Keep only the dependencies that match a predicate
+    #[cfg(feature = "python-parsing")]
+    pub(crate) fn retain_dependencies(&mut self, f: impl FnMut(&Dependency) -> bool) {
+        self.dependencies.retain(f);
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn extra(&self) -> Option<&str> {
+        self.extra.as_deref()
+    }
+
+    pub fn environment(&self) -> Option<&str> {
+        self.environment.as_deref()
+    }
+
+    /// The platform the configuration is installed on, like `linux-64`, if its source
+    /// installs different dependencies on each platform
+    pub fn platform(&self) -> Option<&str> {
+        self.platform.as_deref()
+    }
+
+    /// The Python interpreter the source pins, if it declares one
+    pub fn python_requirement(&self) -> Option<&CondaMatchSpec> {
+        self.python_requirement.as_ref()
+    }
+
+    /// The conda virtual packages the source requires, like `__glibc>=2.17`. They aren't
+    /// installed, so they're neither dependencies nor provide modules
+    pub fn virtual_packages(&self) -> &[CondaMatchSpec] {
+        &self.virtual_packages
+    }
+
+    /// The Python versions the project supports, if its source says, like the
+    /// `requires-python` of a pyproject.toml
+    pub fn requires_python(&self) -> Option<&VersionSpecifiers> {
+        self.requires_python.as_ref()
+    }
+
+    /// The name and channels a conda environment file declares, if the configuration comes
+    /// from one
+    pub fn conda_environment(&self) -> Option<&CondaEnvironmentMetadata> {
+        self.conda_environment.as_ref()
+    }
+
+    /// The extra or environment the configuration installs on top of the base one. Code
+    /// under a directory with the same name belongs to the configuration.
+    pub fn scope(&self) -> Option<&str> {
+        self.extra().or(self.environment())
+    }
+
+    pub fn source(&self) -> &EnvironmentBuilderSource {
+        &self.source
+    }
+
+    /// Where a dependency is declared, and which `-r` includes lead to its file, if the
+    /// configuration comes from a requirements file
+    pub fn provenance(&self, name: &str) -> Option<&Provenance> {
+        let name = name_map::normalize_name(name);
+        self.provenance
+            .iter()
+            .find(|(declared, _)| *declared == name)
+            .map(|(_, provenance)| provenance)
+    }
+
+    /// Where each dependency of the configuration is declared, by normalized name: the line
+    /// of a requirements file it's on, even in an included file, or the first line of the
+    /// source that mentions it. Dependencies of sources that aren't read line by line, like
+    /// a directory, are left out.
+    pub fn declarations(&self) -> Vec<(String, Location)> {
+        let content = std::fs::read_to_string(self.source.path()).ok();
+        let mut declarations: Vec<(String, Location)> = Vec::new();
+        for name in self.dependencies.iter().filter_map(Dependency::name) {
+            let name = name_map::normalize_name(name);
+            if declarations.iter().any(|(declared, _)| *declared == name) {
+                continue;
+            }
+            let location = match self.provenance(&name) {
+                Some(provenance) => provenance.declared.clone(),
+                None => match content
+                    .as_deref()
+                    .map(|content| declaration_line(content, &name))
+                {
+                    Some(0) | None => continue,
+                    Some(line) => Location::new(self.source.path().to_path_buf(), line),
+                },
+            };
+            declarations.push((name, location));
+        }
+        declarations
+    }
+
+    /// Where the configuration declares a dependency, as in [`declarations`](Self::declarations)
+    pub fn declaration(&self, name: &str) -> Option<Location> {
+        let name = name_map::normalize_name(name);
+        self.declarations()
+            .into_iter()
+            .find(|(declared, _)| *declared == name)
+            .map(|(_, location)| location)
+    }
+
+    /// The tree of files a requirements file includes with `-r` and `-c`, if the
+    /// configuration comes from one
+    pub fn includes(&self) -> Option<&IncludeTree> {
+        self.includes.as_ref()
+    }
 }
 
-/// Extract the the different configurations of dependencies from the project
+/// Check how the dependencies in a source are declared, reporting declarations that are
+/// redundant or contradict each other. pyproject.toml files are checked for packages
+/// declared again in extras, conda environment files and pixi manifests for packages
+/// installed both from conda and from PyPI. Extras in the same group of `exclusive_extras`
+/// are alternatives, so they aren't compared.
+pub fn declaration_findings(
+    source: &EnvironmentBuilderSource,
+    exclusive_extras: &[Vec<String>],
+) -> Result<Vec<Finding>, AnalysisError> {
+    match source {
+        EnvironmentBuilderSource::PyProjectToml(path) => {
+            pyprojecttoml::declaration_findings(path, exclusive_extras)
+        }
+        #[cfg(feature = "conda")]
+        EnvironmentBuilderSource::CondaEnvironmentYml(path) => condayml::declaration_findings(path),
+        EnvironmentBuilderSource::PixiToml(path) => pixitoml::declaration_findings(path),
+        _ => Ok(Vec::new()),
+    }
+}
+
+/// A package as declared in a dependency file, and the line it's declared on
+#[derive(Clone)]
+pub(crate) struct DeclaredPackage {
+    pub(crate) name: String,
+    pub(crate) text: String,
+    pub(crate) line: usize,
+}
+
+/// Report the PyPI packages of an environment that it also installs from conda, through the
+/// conda to PyPI name mapping. pip installs over the conda package without the conda solver
+/// knowing, which breaks the packages built against it, so each should be declared once.
+pub(crate) fn duplicate_provision_findings(
+    file_path: &Path,
+    conda: &[DeclaredPackage],
+    pypi: &[DeclaredPackage],
+) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    for package in pypi {
+        let Some(conda_package) = conda
+            .iter()
+            .find(|conda_package| name_map::conda_matches_pypi(&conda_package.name, &package.name))
+        else {
+            continue;
+        };
+        findings.push(Finding::new(
+            Rule::DuplicateProvision,
+            &package.name,
+            format!(
+                "`{}` is installed both from conda (`{}` on line {}) and from PyPI (`{}` on line {}); the PyPI package overwrites the conda one, so declare it only once",
+                package.name, conda_package.text, conda_package.line, package.text, package.line
+            ),
+            Some(Location::new(file_path.to_path_buf(), package.line)),
+        ));
+    }
+    findings
+}
+
+/// An entry point a project declares, like a console script: a module and optionally an
+/// attribute of it, loaded when the entry point is used
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct EntryPoint {
+    /// The group it's declared in, like `scripts`, `gui-scripts`, or a plugin group
+    pub group: String,
+    pub name: String,
+    pub module: String,
+    /// The object in the module, like `main` in `mypkg.cli:main`, which may be dotted
+    pub attribute: Option<String>,
+    pub location: Location,
+}
+
+/// Find the entry points a source declares, whose modules are used even when nothing
+/// imports them. Only pyproject.toml files declare entry points.
+pub fn entry_points(source: &EnvironmentBuilderSource) -> Result<Vec<EntryPoint>, AnalysisError> {
+    match source {
+        EnvironmentBuilderSource::PyProjectToml(path) => pyprojecttoml::entry_points(path),
+        _ => Ok(Vec::new()),
+    }
+}
+
+/// Find how the project's packages are laid out, if the source configures its build backend.
+/// Only pyproject.toml files configure build backends.
+#[cfg(feature = "python-parsing")]
+pub(crate) fn package_layout(
+    source: &EnvironmentBuilderSource,
+) -> Result<Option<PackageLayout>, AnalysisError> {
+    match source {
+        EnvironmentBuilderSource::PyProjectToml(path) => pyprojecttoml::package_layout(path),
+        _ => Ok(None),
+    }
+}
+
+/// Find the line of an entry in a document that was parsed without positions: the first line
+/// containing `text` after the `section` header, or after the start of the document if there
+/// is no such header. Returns 0 if the entry can't be found.
+pub(crate) fn line_of_entry(content: &str, section: &str, text: &str) -> usize {
+    let start = content
+        .lines()
+        .position(|line| line.trim() == section)
+        .unwrap_or(0);
+    content
+        .lines()
+        .enumerate()
+        .skip(start)
+        .find(|(_, line)| line.contains(text))
+        .map_or(0, |(i, _)| i + 1)
+}
+
+/// The line a dependency is declared on: the first line mentioning it that isn't a
+/// comment, or 0 if there is none
+pub(crate) fn declaration_line(content: &str, package: &str) -> usize {
+    let package = name_map::normalize_name(package);
+    content
+        .lines()
+        .position(|line| {
+            let line = line.split('#').next().unwrap_or_default();
+            line.split(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')))
+                .any(|word| name_map::normalize_name(word) == package)
+        })
+        .map_or(0, |i| i + 1)
+}
+
+/// Extract the the different configurations of dependencies from the project.
+///
+/// Requirements files, and the `pip:` section of conda environments, may include remote
+/// requirements files by URL, which are downloaded unless `offline` is set, in which case
+/// they're an error. So is any dependency that can't be parsed; use
+/// `extract_configurations_with_diagnostics` to skip those instead. A requirements file
+/// declaring more than [`DEFAULT_MAX_REQUIREMENTS`] requirements with the files it includes
+/// is an error too.
 pub fn extract_configurations(
     source: EnvironmentBuilderSource,
+    offline: bool,
 ) -> Result<Vec<Configuration>, AnalysisError> {
-    // If the file is a pyproject.toml, use the PyProjectTomlParser
-    match &source {
-        EnvironmentBuilderSource::PyProjectToml(path) => {
-            let pyproject = pyprojecttoml::parse(&path)?;
-            let mut configurations = Vec::new();
+    let mut diagnostics = Vec::new();
+    let configurations = extract_configurations_with_diagnostics(
+        source,
+        offline,
+        Some(DEFAULT_MAX_REQUIREMENTS),
+        &mut diagnostics,
+    )?;
+    if !diagnostics.is_empty() {
+        return Err(AnalysisError::InvalidDeclarations(diagnostics));
+    }
+    Ok(configurations)
+}
 
-            let configuration = Configuration::new(
-                pyproject.required_dependencies().clone(),
-                format!("{}", path.display().to_string()),
-                source.clone(),
-            );
-            configurations.push(configuration);
-
-            // Add all optional configurations
-            for configuration in pyproject.optional_configurations() {
-                let dependencies = pyproject.get_dependencies_for_configuration(&[configuration]);
-                configurations.push(Configuration::new(
-                    dependencies,
-                    format!("{}[{}]", path.display().to_string(), configuration),
-                    source.clone(),
-                ));
-            }
-            Ok(configurations)
-        }
-        EnvironmentBuilderSource::RequirementsTxt(path) => {
-            let dependencies = requirementstxt::parse(&path)?;
-            let configuration =
-                Configuration::new(dependencies, path.display().to_string(), source.clone());
-            Ok(vec![configuration])
+/// Extract the configurations of dependencies from the project like
+/// `extract_configurations`, skipping the dependencies of requirements files, pyproject.toml,
+/// conda environments, and pixi manifests that can't be parsed and adding them to
+/// `diagnostics`. A requirements file declaring more than `max_requirements` requirements
+/// with the files it includes is an error.
+///
+/// Only the built-in kinds of sources are parsed; [`SourceRegistry::open`] parses the kinds
+/// registered on a registry.
+pub fn extract_configurations_with_diagnostics(
+    source: EnvironmentBuilderSource,
+    offline: bool,
+    max_requirements: Option<usize>,
+    diagnostics: &mut Vec<ParseDiagnostic>,
+) -> Result<Vec<Configuration>, AnalysisError> {
+    SourceRegistry::default()
+        .open(&source, ParseOptions::new(offline, max_requirements))?
+        .parse_with_diagnostics(diagnostics)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn pypi(requirement: &str) -> Dependency {
+        Dependency::PyPI(PyPIRequirement::from_str(requirement).unwrap())
+    }
+
+    #[test]
+    fn test_conda_match_spec_version() {
+        let version = |spec: &str| CondaMatchSpec::new(spec).version().map(|v| v.to_string());
+        assert_eq!(version("numpy=1.26"), Some("1.26.*".to_string()));
+        assert_eq!(version("numpy 1.26.* py311*"), Some("1.26.*".to_string()));
+        assert_eq!(
+            version("conda-forge::numpy >=1.20, <1.25"),
+            Some(">=1.20,<1.25".to_string())
+        );
+        assert_eq!(
+            version("numpy>=1.20|<1.25"),
+            Some(">=1.20|<1.25".to_string())
+        );
+        assert_eq!(version("numpy==1.26.0"), Some("==1.26.0".to_string()));
+        assert_eq!(
+            version("numpy[version='>=1.20']"),
+            Some(">=1.20".to_string())
+        );
+        assert_eq!(version("numpy"), None);
+        assert_eq!(version("numpy >=oops<"), None);
+    }
+
+    #[test]
+    fn test_applies_to_python() {
+        let py310 = PythonVersion::new(3, 10);
+        let py312 = PythonVersion::new(3, 12);
+        let tomli = pypi("tomli; python_version < '3.11'");
+        assert!(tomli.applies_to_python(py310));
+        assert!(!tomli.applies_to_python(py312));
+
+        let compound = pypi("exceptiongroup; extra == 'test' and python_version < '3.11'");
+        assert!(compound.applies_to_python(py310));
+        assert!(!compound.applies_to_python(py312));
+
+        let windows = pypi("pywin32; sys_platform == 'win32'");
+        assert!(windows.applies_to_python(py312));
+    }
+
+    #[test]
+    fn test_same_package() {
+        let requests = pypi("requests");
+        for other in [
+            "requests>=2.0",
+            "requests >= 2.0",
+            "Requests[socks]==2.31.0",
+            "requests; python_version >= '3.8'",
+        ] {
+            assert!(requests.same_package(&pypi(other)), "{}", other);
         }
-        //EnvironmentBuilderSource::CondaEnvironmentYml => condayml::parse_dependencies_file(file_path),
-        //EnvironmentBuilderSource::PixiToml => pixitoml::parse_dependencies_file(file_path),
-        _ => Err(AnalysisError::UnsupportedProjectFormat(todo!())),
+        assert!(pypi("Flask_Login").same_package(&pypi("flask-login>=0.6")));
+        assert!(!requests.same_package(&pypi("requests-oauthlib")));
+        assert!(!requests.same_package(&Dependency::Conda(CondaMatchSpec::new("requests"))));
+
+        let numpy = Dependency::Conda(CondaMatchSpec::new("conda-forge::numpy>=1.26"));
+        assert!(numpy.same_package(&Dependency::Conda(CondaMatchSpec::new("numpy 1.26.*"))));
+        assert!(numpy.same_package(&Dependency::Conda(CondaMatchSpec::new(
+            "conda-forge::numpy"
+        ))));
+        assert!(!numpy.same_package(&Dependency::Conda(CondaMatchSpec::new("defaults::numpy"))));
+
+        assert!(
+            Dependency::package_path(Path::new("./vendor/pkg/"), Path::new(".")).same_package(
+                &Dependency::package_path(Path::new("vendor/pkg"), Path::new("."))
+            )
+        );
+    }
+
+    #[test]
+    fn test_package_file_dependencies_satisfy_their_name() {
+        let wheel = Dependency::package_url(
+            "https://download.pytorch.org/whl/torch-2.6.0-cp311-cp311-linux_x86_64.whl",
+        );
+        assert_eq!(wheel.name(), Some("torch"));
+        assert!(wheel.satisfies("Torch"));
+        assert!(!wheel.satisfies("torchvision"));
+
+        let sdist = Dependency::package_path(Path::new("vendor/mypkg-1.0.tar.gz"), Path::new("."));
+        assert!(sdist.satisfies("mypkg"));
+
+        let repository = Dependency::package_url("git+https://github.com/org/repo.git#egg=My_Pkg");
+        assert!(matches!(repository, Dependency::PackageVcs { .. }));
+        assert!(repository.satisfies("my-pkg"));
+
+        // Unparseable names are kept, but can't match anything
+        let unknown = Dependency::package_path(Path::new("vendor/blob.bin"), Path::new("."));
+        assert_eq!(unknown.name(), None);
+        assert!(!unknown.satisfies("blob"));
+    }
+
+    #[test]
+    fn test_pixi_configurations_per_platform() -> Result<(), AnalysisError> {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest = dir.path().join("pixi.toml");
+        std::fs::write(
+            &manifest,
+            r#"
+[project]
+platforms = ["linux-64", "win-64"]
+
+[dependencies]
+numpy = "*"
+
+[target.win-64.pypi-dependencies]
+pywin32 = "*"
+
+[feature.cuda.target.linux-64.dependencies]
+cupy = "*"
+"#,
+        )
+        .unwrap();
+        let configurations =
+            extract_configurations(EnvironmentBuilderSource::PixiToml(manifest.clone()), true)?;
+
+        let found: Vec<(String, Option<&str>, Vec<&str>)> = configurations
+            .iter()
+            .map(|configuration| {
+                (
+                    configuration
+                        .name()
+                        .replace(&manifest.display().to_string(), "pixi.toml"),
+                    configuration.platform(),
+                    configuration
+                        .dependencies()
+                        .iter()
+                        .filter_map(Dependency::name)
+                        .collect(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (
+                    "pixi.toml (linux-64)".to_string(),
+                    Some("linux-64"),
+                    vec!["numpy"]
+                ),
+                (
+                    "pixi.toml (feature cuda, linux-64)".to_string(),
+                    Some("linux-64"),
+                    vec!["numpy", "cupy"]
+                ),
+                (
+                    "pixi.toml (win-64)".to_string(),
+                    Some("win-64"),
+                    vec!["numpy", "pywin32"]
+                ),
+                (
+                    "pixi.toml (feature cuda, win-64)".to_string(),
+                    Some("win-64"),
+                    vec!["numpy", "pywin32"]
+                ),
+            ]
+        );
+        assert_eq!(configurations[1].environment(), Some("cuda"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_pixi_configurations_per_environment() -> Result<(), AnalysisError> {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest = dir.path().join("pixi.toml");
+        std::fs::write(
+            &manifest,
+            r#"
+[dependencies]
+numpy = "*"
+
+[feature.test.dependencies]
+pytest = "*"
+
+[feature.cuda.dependencies]
+cupy = "*"
+
+[feature.lint.dependencies]
+ruff = "*"
+
+[environments]
+test = ["test"]
+gpu = { features = ["cuda", "test"] }
+lint = { features = ["lint"], no-default-feature = true }
+"#,
+        )
+        .unwrap();
+        let configurations =
+            extract_configurations(EnvironmentBuilderSource::PixiToml(manifest.clone()), true)?;
+
+        let found: Vec<(String, Option<&str>, Vec<&str>)> = configurations
+            .iter()
+            .map(|configuration| {
+                (
+                    configuration
+                        .name()
+                        .replace(&manifest.display().to_string(), "pixi.toml"),
+                    configuration.environment(),
+                    configuration
+                        .dependencies()
+                        .iter()
+                        .filter_map(Dependency::name)
+                        .collect(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("pixi.toml".to_string(), None, vec!["numpy"]),
+                (
+                    "pixi.toml (environment gpu)".to_string(),
+                    Some("gpu"),
+                    vec!["numpy", "cupy", "pytest"]
+                ),
+                (
+                    "pixi.toml (environment lint)".to_string(),
+                    Some("lint"),
+                    vec!["ruff"]
+                ),
+                (
+                    "pixi.toml (environment test)".to_string(),
+                    Some("test"),
+                    vec!["numpy", "pytest"]
+                ),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_conda_solver_channels() {
+        let metadata = |channels: &[&str]| CondaEnvironmentMetadata {
+            name: Some("example".to_string()),
+            channels: channels.iter().map(|channel| channel.to_string()).collect(),
+        };
+        assert_eq!(
+            metadata(&["bioconda", "conda-forge", "defaults"]).solver_channels(),
+            ["bioconda", "conda-forge", "defaults"]
+        );
+        assert_eq!(
+            metadata(&["bioconda", "defaults", "nodefaults"]).solver_channels(),
+            ["bioconda"]
+        );
+        assert_eq!(metadata(&[]).solver_channels(), ["conda-forge"]);
+        assert_eq!(metadata(&["nodefaults"]).solver_channels(), ["conda-forge"]);
+    }
+
+    #[test]
+    fn test_merge_dependencies() {
+        let mut dependencies = vec![pypi("requests>=2.0"), pypi("rich")];
+        merge_dependencies(
+            &mut dependencies,
+            [pypi("requests >= 2.31"), pypi("Rich"), pypi("click")],
+        );
+        let requirements: Vec<String> = dependencies
+            .iter()
+            .map(|d| match d {
+                Dependency::PyPI(requirement) => requirement.to_string(),
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(requirements, ["requests>=2.0", "rich", "click"]);
+    }
+
+    #[test]
+    fn test_merge_dependencies_unions_extras() {
+        let mut dependencies = vec![pypi("celery[redis]>=5")];
+        merge_dependencies(
+            &mut dependencies,
+            [pypi("celery[auth]"), pypi("Celery[redis]")],
+        );
+        assert_eq!(dependencies, [pypi("celery[auth,redis]>=5")]);
+        assert!(dependencies[0].has_extra("auth"));
+
+        // Extras already asked for keep their order
+        let mut dependencies = vec![pypi("celery[redis,auth]")];
+        merge_dependencies(&mut dependencies, [pypi("celery[auth]")]);
+        assert_eq!(dependencies, [pypi("celery[redis,auth]")]);
     }
 }
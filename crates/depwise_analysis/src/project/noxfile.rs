@@ -0,0 +1,257 @@
+use std::fs;
+use std::path::Path;
+
+use rustpython_ast::Visitor;
+use rustpython_parser::{Parse, ast};
+use tracing::debug;
+
+use crate::error::{AnalysisError, ParseDiagnostic};
+use crate::project::{CondaMatchSpec, Dependency, ParseOptions, requirementstxt, toxini};
+
+/// `pip install` options whose value is the next argument and that don't name requirements
+const OPTIONS_WITH_VALUES: [&str; 6] = [
+    "-c",
+    "--constraint",
+    "-e",
+    "--editable",
+    "-i",
+    "--index-url",
+];
+
+/// The packages each session of a `noxfile.py` installs with `session.install()` or
+/// `session.conda_install()`, in order, by session name. Only string literal arguments are
+/// read; anything computed at runtime is skipped.
+pub(crate) fn parse(
+    file_path: &Path,
+    options: &ParseOptions,
+    diagnostics: &mut Vec<ParseDiagnostic>,
+) -> Result<Vec<(String, Vec<Dependency>)>, AnalysisError> {
+    let content = fs::read_to_string(file_path).map_err(|e| {
+        AnalysisError::FileReadError(file_path.to_string_lossy().to_string(), e.to_string())
+    })?;
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(content.match_indices('\n').map(|(i, _)| i + 1))
+        .collect();
+    let suite = ast::Suite::parse(&content, &file_path.to_string_lossy()).map_err(|e| {
+        let offset: usize = e.offset.into();
+        let line = line_number(&line_starts, offset);
+        AnalysisError::ParseFileError(
+            file_path.display().to_string(),
+            line.to_string(),
+            (offset - line_starts[line - 1] + 1).to_string(),
+        )
+    })?;
+
+    let mut sessions = Vec::new();
+    for statement in suite {
+        let ast::Stmt::FunctionDef(function) = statement else {
+            continue;
+        };
+        let Some(name) = session_name(&function) else {
+            continue;
+        };
+        let mut collector = InstallCollector {
+            line_starts: &line_starts,
+            installs: Vec::new(),
+        };
+        for statement in function.body {
+            collector.visit_stmt(statement);
+        }
+
+        let mut dependencies = Vec::new();
+        for install in collector.installs {
+            if install.conda {
+                dependencies.extend(
+                    install
+                        .arguments
+                        .iter()
+                        .filter(|argument| !argument.starts_with('-'))
+                        .map(|argument| Dependency::Conda(CondaMatchSpec::new(argument))),
+                );
+                continue;
+            }
+            let mut arguments = install.arguments.iter();
+            while let Some(argument) = arguments.next() {
+                let argument = match argument.as_str() {
+                    "-r" | "--requirement" => match arguments.next() {
+                        Some(file) => format!("-r {}", file),
+                        None => continue,
+                    },
+                    option if OPTIONS_WITH_VALUES.contains(&option) => {
+                        arguments.next();
+                        continue;
+                    }
+                    argument => argument.to_string(),
+                };
+                let Some(line) = toxini::pip_argument(&argument) else {
+                    continue;
+                };
+                // Padded to the call's line, so diagnostics point at it
+                let content = format!("{}{}", "\n".repeat(install.line - 1), line);
+                dependencies.extend(requirementstxt::parse_lines(
+                    &content,
+                    file_path,
+                    options,
+                    diagnostics,
+                )?);
+            }
+        }
+        sessions.push((name, dependencies));
+    }
+    Ok(sessions)
+}
+
+/// The name of the session a function defines: the function's name, or the `name=` given
+/// to the `@nox.session` decorator. Functions without the decorator aren't sessions.
+fn session_name(function: &ast::StmtFunctionDef) -> Option<String> {
+    let is_session = |expression: &ast::Expr| match expression {
+        ast::Expr::Name(name) => name.id.as_str() == "session",
+        ast::Expr::Attribute(attribute) => attribute.attr.as_str() == "session",
+        _ => false,
+    };
+    for decorator in &function.decorator_list {
+        match decorator {
+            ast::Expr::Call(call) if is_session(&call.func) => {
+                let name = call
+                    .keywords
+                    .iter()
+                    .find(|keyword| {
+                        keyword
+                            .arg
+                            .as_ref()
+                            .is_some_and(|arg| arg.as_str() == "name")
+                    })
+                    .and_then(|keyword| string_literal(&keyword.value));
+                return Some(name.unwrap_or_else(|| function.name.to_string()));
+            }
+            decorator if is_session(decorator) => return Some(function.name.to_string()),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// The 1-based line of a byte offset
+fn line_number(line_starts: &[usize], offset: usize) -> usize {
+    match line_starts.binary_search(&offset) {
+        Ok(line) => line + 1,
+        Err(line) => line,
+    }
+}
+
+fn string_literal(expression: &ast::Expr) -> Option<String> {
+    match expression {
+        ast::Expr::Constant(ast::ExprConstant {
+            value: ast::Constant::Str(value),
+            ..
+        }) => Some(value.clone()),
+        _ => None,
+    }
+}
+
+/// A `session.install()` or `session.conda_install()` call
+struct Install {
+    conda: bool,
+    line: usize,
+    /// The string literal arguments
+    arguments: Vec<String>,
+}
+
+struct InstallCollector<'a> {
+    line_starts: &'a [usize],
+    installs: Vec<Install>,
+}
+
+impl Visitor for InstallCollector<'_> {
+    fn visit_expr_call(&mut self, node: ast::ExprCall) {
+        if let ast::Expr::Attribute(attribute) = node.func.as_ref()
+            && matches!(attribute.attr.as_str(), "install" | "conda_install")
+        {
+            let line = line_number(self.line_starts, node.range.start().into());
+            let mut arguments = Vec::new();
+            for argument in &node.args {
+                match string_literal(argument) {
+                    Some(argument) => arguments.push(argument),
+                    None => debug!(
+                        "Skipping an argument to {} on line {} that isn't a string literal",
+                        attribute.attr, line
+                    ),
+                }
+            }
+            self.installs.push(Install {
+                conda: attribute.attr.as_str() == "conda_install",
+                line,
+                arguments,
+            });
+        }
+        self.generic_visit_expr_call(node);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_noxfile() -> Result<(), AnalysisError> {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("requirements-test.txt"), "hypothesis\n").unwrap();
+        let noxfile = dir.path().join("noxfile.py");
+        fs::write(
+            &noxfile,
+            r#"import nox
+
+LINTERS = ["ruff"]
+
+
+@nox.session(python=["3.11", "3.12"])
+def tests(session):
+    session.install("-e", ".", "pytest>=7", "-r", "requirements-test.txt")
+    session.run("pytest")
+
+
+@nox.session(name="lint-all")
+def lint(session):
+    session.install(*LINTERS)
+    session.install("mypy", "types-requests >=> 2")
+
+
+@nox.session(venv_backend="conda")
+def docs(session):
+    session.conda_install("sphinx", "pandoc", channel="conda-forge")
+
+
+def helper(session):
+    session.install("black")
+"#,
+        )
+        .unwrap();
+
+        let mut diagnostics = Vec::new();
+        let sessions = parse(&noxfile, &ParseOptions::new(true, None), &mut diagnostics)?;
+        let found: Vec<(&str, Vec<&str>)> = sessions
+            .iter()
+            .map(|(name, dependencies)| {
+                (
+                    name.as_str(),
+                    dependencies.iter().filter_map(Dependency::name).collect(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("tests", vec!["pytest", "hypothesis"]),
+                ("lint-all", vec!["mypy"]),
+                ("docs", vec!["sphinx", "pandoc"]),
+            ]
+        );
+        assert!(matches!(sessions[2].1[0], Dependency::Conda(_)));
+        let invalid: Vec<(usize, &str)> = diagnostics
+            .iter()
+            .map(|diagnostic| (diagnostic.location.line, diagnostic.text.as_str()))
+            .collect();
+        assert_eq!(invalid, [(15, "types-requests >=> 2")]);
+        Ok(())
+    }
+}
@@ -0,0 +1,370 @@
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use pep508_rs::PackageName;
+use pep508_rs::pep440_rs::Version;
+use toml::Value;
+use tracing::debug;
+
+#[cfg(feature = "archives")]
+use crate::project::archive;
+use crate::project::{Dependency, coremetadata, pyprojecttoml};
+
+/// Archive extensions of source distributions
+const SDIST_EXTENSIONS: [&str; 6] = [".tar.gz", ".tar.bz2", ".tar.xz", ".tgz", ".zip", ".tar"];
+
+/// The package a URL or path dependency installs, as far as the file name or the package's
+/// own metadata tells
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackageFile {
+    /// The normalized distribution name
+    pub name: String,
+    /// The version, if the file name or the metadata has one
+    pub version: Option<String>,
+    /// The requirements listed in the package's metadata, when a local package was read
+    pub requirements: Vec<Dependency>,
+}
+
+impl PackageFile {
+    fn new(name: &str, version: Option<&str>) -> Option<Self> {
+        let name = PackageName::from_str(name).ok()?;
+        Some(Self {
+            name: name.to_string(),
+            version: version.map(str::to_string),
+            requirements: Vec::new(),
+        })
+    }
+
+    /// The package a manifest declares under `name`, keeping the version and requirements
+    /// found in the file itself
+    pub(crate) fn declared_as(name: &str, found: Option<Self>) -> Option<Self> {
+        let mut package = Self::new(name, None)?;
+        if let Some(found) = found {
+            package.version = found.version;
+            package.requirements = found.requirements;
+        }
+        Some(package)
+    }
+
+    /// Parse the name and version out of a wheel file name
+    /// (`torch-2.6.0-cp311-cp311-linux_x86_64.whl`, see PEP 427), an egg
+    /// (`mypkg-1.0-py3.11.egg`), or a source distribution (`mypkg-1.0.tar.gz`)
+    pub fn from_filename(filename: &str) -> Option<Self> {
+        if let Some(stem) = filename.strip_suffix(".whl") {
+            // name-version(-build)?-python-abi-platform, with `-` in the name escaped as `_`
+            let parts: Vec<&str> = stem.split('-').collect();
+            if !matches!(parts.len(), 5 | 6) || Version::from_str(parts[1]).is_err() {
+                return None;
+            }
+            return Self::new(parts[0], Some(parts[1]));
+        }
+        if let Some(stem) = filename.strip_suffix(".egg") {
+            let mut parts = stem.split('-');
+            let (name, version) = (parts.next()?, parts.next()?);
+            Version::from_str(version).ok()?;
+            return Self::new(name, Some(version));
+        }
+        let stem = SDIST_EXTENSIONS
+            .iter()
+            .find_map(|extension| filename.strip_suffix(extension))?;
+        // The name can contain `-`, the version can't
+        let (name, version) = stem.rsplit_once('-')?;
+        Version::from_str(version).ok()?;
+        Self::new(name, Some(version))
+    }
+
+    /// Find the package a URL installs: the `#egg=name` fragment if there is one, otherwise
+    /// the file name the URL ends with
+    pub fn from_url(url: &str) -> Option<Self> {
+        let (location, fragment) = url.split_once('#').unwrap_or((url, ""));
+        let egg = fragment
+            .split('&')
+            .find_map(|parameter| parameter.strip_prefix("egg="));
+        if let Some(egg) = egg {
+            return Self::new(egg, None);
+        }
+        let location = location.split_once('?').map_or(location, |(path, _)| path);
+        Self::from_filename(location.trim_end_matches('/').rsplit('/').next()?)
+    }
+
+    /// Find the package at a path, relative to `base`. A directory is identified by its
+    /// metadata: the `PKG-INFO` of an unpacked source distribution, the `METADATA` in a
+    /// `.dist-info` directory, or the `[project]` table of a `pyproject.toml`. So is an
+    /// archive that's there, with the `archives` feature: the `METADATA` of a wheel, or the
+    /// `PKG-INFO` of a `.tar.gz` or `.zip` source distribution. Other archives are
+    /// identified by their file name.
+    pub(crate) fn from_path(path: &Path, base: &Path) -> Option<Self> {
+        let resolved = base.join(path);
+        if resolved.is_dir() {
+            return from_directory(&resolved);
+        }
+        let filename = path.file_name()?.to_str()?;
+        #[cfg(feature = "archives")]
+        if resolved.is_file()
+            && let Some(package) = from_archive(&resolved, filename)
+        {
+            return Some(package);
+        }
+        Self::from_filename(filename)
+    }
+}
+
+/// Read the package out of the metadata in an archive
+#[cfg(feature = "archives")]
+fn from_archive(path: &Path, filename: &str) -> Option<PackageFile> {
+    let metadata = if filename.ends_with(".whl") {
+        // The wheel's `{name}-{version}.dist-info/METADATA`
+        archive::zip_metadata(path, |name| {
+            name.split_once('/').is_some_and(|(directory, file)| {
+                directory.ends_with(".dist-info") && file == "METADATA"
+            })
+        })
+    } else if filename.ends_with(".zip") {
+        archive::zip_metadata(path, archive::is_sdist_metadata)
+    } else if filename.ends_with(".tar.gz") || filename.ends_with(".tgz") {
+        archive::tar_metadata(path)
+    } else {
+        return None;
+    };
+    let content = match metadata {
+        Ok(Some(content)) => content,
+        Ok(None) => {
+            debug!("{} has no metadata", path.display());
+            return None;
+        }
+        Err(e) => {
+            debug!("Skipping the metadata of {}: {}", path.display(), e);
+            return None;
+        }
+    };
+    match coremetadata::from_contents(&path.display().to_string(), &content) {
+        Ok(metadata) => {
+            let mut package =
+                PackageFile::new(metadata.name.as_deref()?, metadata.version.as_deref())?;
+            package.requirements = metadata.required_dependencies();
+            Some(package)
+        }
+        Err(e) => {
+            debug!("Skipping {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+fn from_directory(directory: &Path) -> Option<PackageFile> {
+    let mut candidates = vec![directory.join("PKG-INFO")];
+    if let Ok(entries) = fs::read_dir(directory) {
+        let mut entries: Vec<_> = entries.flatten().map(|entry| entry.path()).collect();
+        entries.sort();
+        for entry in entries {
+            match entry.extension().and_then(|extension| extension.to_str()) {
+                Some("dist-info") => candidates.push(entry.join("METADATA")),
+                Some("egg-info") => candidates.push(entry.join("PKG-INFO")),
+                _ => {}
+            }
+        }
+    }
+    for candidate in candidates.iter().filter(|candidate| candidate.is_file()) {
+        match coremetadata::parse(candidate) {
+            Ok(metadata) => {
+                let mut package =
+                    PackageFile::new(metadata.name.as_deref()?, metadata.version.as_deref())?;
+                package.requirements = metadata.required_dependencies();
+                return Some(package);
+            }
+            Err(e) => debug!("Skipping {}: {}", candidate.display(), e),
+        }
+    }
+
+    let pyproject = directory.join("pyproject.toml");
+    let content = fs::read_to_string(&pyproject).ok()?;
+    let project = content.parse::<Value>().ok()?.get("project")?.clone();
+    let version = project.get("version").and_then(Value::as_str);
+    let mut package = PackageFile::new(project.get("name")?.as_str()?, version)?;
+    match pyprojecttoml::parse(&pyproject) {
+        Ok(pyproject) => package.requirements = pyproject.required_dependencies().clone(),
+        Err(e) => debug!(
+            "Skipping the requirements of {}: {}",
+            pyproject.display(),
+            e
+        ),
+    }
+    Some(package)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn name_and_version(package: Option<PackageFile>) -> Option<(String, Option<String>)> {
+        package.map(|package| (package.name, package.version))
+    }
+
+    #[test]
+    fn test_from_filename() {
+        let cases = [
+            ("torch-2.6.0-cp311-cp311-linux_x86_64.whl", "torch", "2.6.0"),
+            (
+                "Flask_Login-0.6.3-1-py3-none-any.whl",
+                "flask-login",
+                "0.6.3",
+            ),
+            ("mypkg-1.0.tar.gz", "mypkg", "1.0"),
+            ("my-pkg-2.0rc1.zip", "my-pkg", "2.0rc1"),
+            ("simplejson-3.19.2-py3.11.egg", "simplejson", "3.19.2"),
+        ];
+        for (filename, name, version) in cases {
+            assert_eq!(
+                name_and_version(PackageFile::from_filename(filename)),
+                Some((name.to_string(), Some(version.to_string()))),
+                "{}",
+                filename
+            );
+        }
+
+        for filename in ["torch.whl", "torch-2.6.0.whl", "mypkg.tar.gz", "notes.txt"] {
+            assert_eq!(PackageFile::from_filename(filename), None, "{}", filename);
+        }
+    }
+
+    #[test]
+    fn test_from_url() {
+        assert_eq!(
+            name_and_version(PackageFile::from_url(
+                "https://example.com/wheels/torch-2.6.0-cp311-cp311-linux_x86_64.whl?token=1"
+            )),
+            Some(("torch".to_string(), Some("2.6.0".to_string())))
+        );
+        assert_eq!(
+            name_and_version(PackageFile::from_url(
+                "git+https://github.com/org/repo.git@v1#egg=My_Pkg"
+            )),
+            Some(("my-pkg".to_string(), None))
+        );
+        assert_eq!(
+            PackageFile::from_url("git+https://github.com/org/repo.git"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_from_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let sdist = dir.path().join("mypkg-1.0");
+        fs::create_dir(&sdist).unwrap();
+        fs::write(
+            sdist.join("PKG-INFO"),
+            "Metadata-Version: 2.1\nName: MyPkg\nVersion: 1.0\nRequires-Dist: requests\n",
+        )
+        .unwrap();
+        let package = PackageFile::from_path(Path::new("mypkg-1.0"), dir.path()).unwrap();
+        assert_eq!(
+            (package.name.as_str(), package.version.as_deref()),
+            ("mypkg", Some("1.0"))
+        );
+        assert_eq!(package.requirements.len(), 1);
+        assert_eq!(package.requirements[0].name(), Some("requests"));
+
+        let tree = dir.path().join("local");
+        fs::create_dir(&tree).unwrap();
+        fs::write(
+            tree.join("pyproject.toml"),
+            "[project]\nname = \"local-tool\"\ndependencies = [\"click\"]\n",
+        )
+        .unwrap();
+        let package = PackageFile::from_path(Path::new("./local"), dir.path()).unwrap();
+        assert_eq!(
+            (package.name.as_str(), package.version),
+            ("local-tool", None)
+        );
+        assert_eq!(package.requirements[0].name(), Some("click"));
+
+        let wheel =
+            PackageFile::from_path(Path::new("dist/mypkg-1.0-py3-none-any.whl"), dir.path());
+        assert_eq!(wheel.map(|package| package.name), Some("mypkg".to_string()));
+        assert_eq!(
+            PackageFile::from_path(Path::new("./missing"), dir.path()),
+            None
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "archives")]
+    fn test_from_archive() {
+        use std::io::Write;
+
+        let dir = tempfile::tempdir().unwrap();
+        let metadata = "Metadata-Version: 2.1\nName: Real_Pkg\nVersion: 2.0\n\
+                        Requires-Dist: numpy\nRequires-Dist: rich; extra == \"cli\"\n\n\
+                        A description\n";
+
+        // Named after another package, which the metadata corrects
+        let wheel = dir.path().join("renamed-1.0-py3-none-any.whl");
+        let mut writer = zip::ZipWriter::new(fs::File::create(&wheel).unwrap());
+        let options = zip::write::SimpleFileOptions::default();
+        writer.start_file("real_pkg/__init__.py", options).unwrap();
+        writer
+            .start_file("real_pkg-2.0.dist-info/METADATA", options)
+            .unwrap();
+        writer.write_all(metadata.as_bytes()).unwrap();
+        writer.finish().unwrap();
+
+        let sdist = dir.path().join("real_pkg-2.0.tar.gz");
+        let encoder = flate2::write::GzEncoder::new(
+            fs::File::create(&sdist).unwrap(),
+            flate2::Compression::fast(),
+        );
+        let mut builder = tar::Builder::new(encoder);
+        for (name, content) in [
+            ("real_pkg-2.0/src/real_pkg/PKG-INFO", "Name: nested\n"),
+            ("real_pkg-2.0/PKG-INFO", metadata),
+        ] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, name, content.as_bytes())
+                .unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap();
+
+        for archive in ["renamed-1.0-py3-none-any.whl", "real_pkg-2.0.tar.gz"] {
+            let package = PackageFile::from_path(Path::new(archive), dir.path()).unwrap();
+            assert_eq!(
+                (package.name.as_str(), package.version.as_deref()),
+                ("real-pkg", Some("2.0")),
+                "{}",
+                archive
+            );
+            let requirements: Vec<_> = package
+                .requirements
+                .iter()
+                .map(|requirement| requirement.name())
+                .collect();
+            assert_eq!(requirements, vec![Some("numpy")], "{}", archive);
+        }
+
+        // Unpacked, the package is found where its metadata is
+        let unpacked = tempfile::tempdir().unwrap();
+        let root = archive::unpack_archive(&wheel, unpacked.path()).unwrap();
+        assert_eq!(root, unpacked.path());
+        assert!(root.join("real_pkg-2.0.dist-info/METADATA").is_file());
+        let unpacked = tempfile::tempdir().unwrap();
+        let root = archive::unpack_archive(&sdist, unpacked.path()).unwrap();
+        assert_eq!(root, unpacked.path().join("real_pkg-2.0"));
+        assert_eq!(
+            name_and_version(PackageFile::from_path(&root, Path::new("."))),
+            Some(("real-pkg".to_string(), Some("2.0".to_string())))
+        );
+
+        // An archive that can't be read is identified by its file name
+        fs::write(dir.path().join("broken-1.0.tar.gz"), "not an archive").unwrap();
+        let broken = PackageFile::from_path(Path::new("broken-1.0.tar.gz"), dir.path());
+        assert_eq!(
+            name_and_version(broken),
+            Some(("broken".to_string(), Some("1.0".to_string())))
+        );
+    }
+}
@@ -0,0 +1,633 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use pep508_rs::Requirement;
+use toml::Value;
+
+use crate::error::{AnalysisError, ParseDiagnostic};
+use crate::finding::{Finding, Location, Rule};
+use crate::project::{
+    CondaMatchSpec, DeclaredPackage, Dependency, PackageFile, duplicate_provision_findings,
+    line_of_entry,
+};
+
+/// The dependencies declared in a `pixi.toml` manifest
+#[derive(Debug, Default)]
+pub(crate) struct PixiToml {
+    /// Conda packages from `[dependencies]` and PyPI packages from `[pypi-dependencies]`
+    pub(crate) dependencies: Vec<Dependency>,
+    /// The dependencies of `[target.<selector>.*]` tables, with their platform selector
+    pub(crate) targets: Vec<(String, Dependency)>,
+    /// The features declared under `[feature.<name>]`, in order
+    pub(crate) features: Vec<PixiFeature>,
+    /// The environments declared under `[environments]`, in order
+    pub(crate) environments: Vec<PixiEnvironment>,
+    /// The platforms the project supports, from `platforms` in `[project]` or `[workspace]`
+    pub(crate) platforms: Vec<String>,
+    /// The `python` dependency, which pins the interpreter rather than providing modules
+    pub(crate) python: Option<CondaMatchSpec>,
+    /// The entries that couldn't be parsed and were skipped
+    pub(crate) diagnostics: Vec<ParseDiagnostic>,
+}
+
+/// The dependencies a `[feature.<name>]` adds to an environment
+#[derive(Debug, Default)]
+pub(crate) struct PixiFeature {
+    pub(crate) name: String,
+    pub(crate) dependencies: Vec<Dependency>,
+    /// The dependencies of the feature's `target.<selector>` tables, with their selector
+    pub(crate) targets: Vec<(String, Dependency)>,
+}
+
+/// An environment of `[environments]`, which installs the features it lists along with the
+/// default feature, the top-level tables, unless `no-default-feature` is set
+#[derive(Debug, Default)]
+pub(crate) struct PixiEnvironment {
+    pub(crate) name: String,
+    pub(crate) features: Vec<String>,
+    pub(crate) no_default_feature: bool,
+}
+
+impl PixiToml {
+    /// Whether any dependency is only installed on some platforms
+    pub(crate) fn has_targets(&self) -> bool {
+        !self.targets.is_empty()
+            || self
+                .features
+                .iter()
+                .any(|feature| !feature.targets.is_empty())
+    }
+}
+
+/// The dependencies installed on a platform: the ones for every platform, and the ones of
+/// the targets whose selector matches it. Without a platform every target is included.
+pub(crate) fn for_platform(
+    dependencies: &[Dependency],
+    targets: &[(String, Dependency)],
+    platform: Option<&str>,
+) -> Vec<Dependency> {
+    let mut selected = dependencies.to_vec();
+    for (selector, dependency) in targets {
+        if platform.is_none_or(|platform| selector_matches(selector, platform)) {
+            selected.push(dependency.clone());
+        }
+    }
+    selected
+}
+
+/// Check whether a target selector applies to a platform. Selectors are a platform like
+/// `linux-64`, an operating system like `linux`, `osx`, or `win`, or `unix` for both
+/// linux and osx.
+fn selector_matches(selector: &str, platform: &str) -> bool {
+    let os = platform.split('-').next().unwrap_or(platform);
+    selector == platform || selector == os || (selector == "unix" && matches!(os, "linux" | "osx"))
+}
+
+pub(crate) fn parse(file_path: &Path) -> Result<PixiToml, AnalysisError> {
+    let content = fs::read_to_string(file_path).map_err(|e| {
+        AnalysisError::FileReadError(file_path.to_string_lossy().to_string(), e.to_string())
+    })?;
+    parse_contents(&content, file_path)
+}
+
+fn parse_contents(content: &str, file_path: &Path) -> Result<PixiToml, AnalysisError> {
+    let document: Value =
+        toml::from_str(content).map_err(|e| AnalysisError::PixiTomlError(e.to_string()))?;
+
+    let mut pixi = PixiToml::default();
+    let base = file_path.parent().unwrap_or(Path::new("."));
+    let invalid = |section: &str, name: &str, spec: &Value, message: String| {
+        ParseDiagnostic::new(
+            file_path,
+            line_of_entry(content, section, name),
+            &format!("{} = {}", name, spec),
+            message,
+        )
+    };
+    let mut read_tables = |table: &Value, prefix: &str, dependencies: &mut Vec<Dependency>| {
+        if let Some(entries) = table.get("dependencies").and_then(Value::as_table) {
+            let section = format!("[{}dependencies]", prefix);
+            for (name, spec) in entries {
+                match conda_spec(name, spec) {
+                    Ok(spec) if spec.name() == "python" => {
+                        // Only the interpreter every environment gets is the one targeted
+                        if prefix.is_empty() {
+                            pixi.python = Some(spec);
+                        }
+                    }
+                    Ok(spec) => dependencies.push(Dependency::Conda(spec)),
+                    Err(message) => {
+                        let diagnostic = invalid(&section, name, spec, message);
+                        pixi.diagnostics.push(diagnostic);
+                    }
+                }
+            }
+        }
+        if let Some(entries) = table.get("pypi-dependencies").and_then(Value::as_table) {
+            let section = format!("[{}pypi-dependencies]", prefix);
+            for (name, spec) in entries {
+                match pypi_dependency(name, spec, base) {
+                    Ok(dependency) => dependencies.push(dependency),
+                    Err(message) => {
+                        let diagnostic = invalid(&section, name, spec, message);
+                        pixi.diagnostics.push(diagnostic);
+                    }
+                }
+            }
+        }
+    };
+    let mut dependencies = Vec::new();
+    read_tables(&document, "", &mut dependencies);
+    let targets = read_targets(&document, "", &mut read_tables);
+    let mut features = Vec::new();
+    for (name, table) in document
+        .get("feature")
+        .and_then(Value::as_table)
+        .into_iter()
+        .flatten()
+    {
+        let prefix = format!("feature.{}.", name);
+        let mut feature = PixiFeature {
+            name: name.clone(),
+            ..Default::default()
+        };
+        read_tables(table, &prefix, &mut feature.dependencies);
+        feature.targets = read_targets(table, &prefix, &mut read_tables);
+        features.push(feature);
+    }
+    pixi.dependencies = dependencies;
+    pixi.targets = targets;
+    pixi.features = features;
+    pixi.environments = environments(&document);
+    pixi.platforms = ["workspace", "project"]
+        .iter()
+        .filter_map(|key| document.get(*key)?.get("platforms")?.as_array())
+        .flatten()
+        .filter_map(Value::as_str)
+        .map(str::to_string)
+        .collect();
+    Ok(pixi)
+}
+
+/// Read `[environments]`, where an environment is either the list of its features or a table
+/// with `features` and `no-default-feature`
+fn environments(document: &Value) -> Vec<PixiEnvironment> {
+    let strings = |value: Option<&Value>| -> Vec<String> {
+        value
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+            .map(str::to_string)
+            .collect()
+    };
+    let mut environments = Vec::new();
+    let tables = document.get("environments").and_then(Value::as_table);
+    for (name, environment) in tables.into_iter().flatten() {
+        let (features, no_default_feature) = match environment {
+            Value::Table(table) => (
+                strings(table.get("features")),
+                table.get("no-default-feature").and_then(Value::as_bool) == Some(true),
+            ),
+            list => (strings(Some(list)), false),
+        };
+        environments.push(PixiEnvironment {
+            name: name.clone(),
+            features,
+            no_default_feature,
+        });
+    }
+    environments
+}
+
+/// Read the dependencies of each `target.<selector>` table under `table` with `read_tables`,
+/// pairing them with their selector
+fn read_targets(
+    table: &Value,
+    prefix: &str,
+    read_tables: &mut impl FnMut(&Value, &str, &mut Vec<Dependency>),
+) -> Vec<(String, Dependency)> {
+    let mut targets = Vec::new();
+    let tables = table.get("target").and_then(Value::as_table);
+    for (selector, target) in tables.into_iter().flatten() {
+        let mut dependencies = Vec::new();
+        read_tables(
+            target,
+            &format!("{}target.{}.", prefix, selector),
+            &mut dependencies,
+        );
+        targets.extend(
+            dependencies
+                .into_iter()
+                .map(|dependency| (selector.clone(), dependency)),
+        );
+    }
+    targets
+}
+
+/// Build a match spec from a `name = "spec"` or `name = { version = "spec" }` entry
+fn conda_spec(name: &str, spec: &Value) -> Result<CondaMatchSpec, String> {
+    let version = match spec {
+        Value::String(version) => version.as_str(),
+        Value::Table(table) => table.get("version").and_then(Value::as_str).unwrap_or("*"),
+        _ => return Err("Unsupported dependency".to_string()),
+    };
+    Ok(match version.trim() {
+        "" | "*" => CondaMatchSpec::new(name),
+        version => CondaMatchSpec::new(&format!("{} {}", name, version)),
+    })
+}
+
+/// Build a PyPI dependency from a `[pypi-dependencies]` entry. Path, URL and git dependencies
+/// are named after their key, paths being relative to `base`, the directory of the manifest.
+fn pypi_dependency(name: &str, spec: &Value, base: &Path) -> Result<Dependency, String> {
+    let (version, extras) = match spec {
+        Value::String(version) => (version.as_str(), Vec::new()),
+        Value::Table(table) => {
+            if let Some(path) = table.get("path").and_then(Value::as_str) {
+                let path = PathBuf::from(path);
+                let found = PackageFile::from_path(&path, base);
+                return Ok(Dependency::PackagePath {
+                    path,
+                    package: PackageFile::declared_as(name, found),
+                });
+            }
+            if let Some(repository) = table.get("git").and_then(Value::as_str) {
+                let revision = ["rev", "tag", "branch"]
+                    .iter()
+                    .find_map(|key| table.get(*key).and_then(Value::as_str));
+                return Ok(Dependency::git(name, repository, revision));
+            }
+            if let Some(url) = table.get("url").and_then(Value::as_str) {
+                return Ok(Dependency::PackageUrl {
+                    url: url.to_string(),
+                    package: PackageFile::declared_as(name, PackageFile::from_url(url)),
+                });
+            }
+            let extras = table
+                .get("extras")
+                .and_then(Value::as_array)
+                .map(|extras| extras.iter().filter_map(Value::as_str).collect())
+                .unwrap_or_default();
+            let version = table.get("version").and_then(Value::as_str).unwrap_or("*");
+            (version, extras)
+        }
+        _ => return Err("Unsupported dependency".to_string()),
+    };
+
+    let mut requirement = name.to_string();
+    if !extras.is_empty() {
+        requirement.push_str(&format!("[{}]", extras.join(",")));
+    }
+    match version.trim() {
+        "" | "*" => {}
+        // A bare version pins it exactly, like it does for conda packages
+        version if version.starts_with(|c: char| c.is_ascii_digit()) => {
+            requirement.push_str(&format!("=={}", version));
+        }
+        version => requirement.push_str(version),
+    }
+    let requirement = Requirement::from_str(&requirement).map_err(|e| e.message.to_string())?;
+    Ok(Dependency::PyPI(requirement))
+}
+
+/// Find the packages an environment of the manifest installs both from `[dependencies]` and
+/// `[pypi-dependencies]`. Environments get the default tables along with their features, so
+/// the tables of each feature are compared with the default ones too.
+pub(crate) fn declaration_findings(file_path: &Path) -> Result<Vec<Finding>, AnalysisError> {
+    let content = fs::read_to_string(file_path).map_err(|e| {
+        AnalysisError::FileReadError(file_path.to_string_lossy().to_string(), e.to_string())
+    })?;
+    let mut findings = duplicate_provisions(&content, file_path)?;
+    findings.extend(unreachable_features(&content, file_path)?);
+    Ok(findings)
+}
+
+/// Report the dependencies of features that no environment of `[environments]` lists, which
+/// pixi never installs. Without `[environments]` every feature is checked on its own, so
+/// none are unreachable.
+fn unreachable_features(content: &str, file_path: &Path) -> Result<Vec<Finding>, AnalysisError> {
+    let document: Value =
+        toml::from_str(content).map_err(|e| AnalysisError::PixiTomlError(e.to_string()))?;
+    let mut findings = Vec::new();
+    if document.get("environments").is_none() {
+        return Ok(findings);
+    }
+    let environments = environments(&document);
+    for (feature, table) in document
+        .get("feature")
+        .and_then(Value::as_table)
+        .into_iter()
+        .flatten()
+    {
+        if environments
+            .iter()
+            .any(|environment| environment.features.contains(feature))
+        {
+            continue;
+        }
+        for key in ["dependencies", "pypi-dependencies"] {
+            let section = format!("[feature.{}.{}]", feature, key);
+            let entries = table.get(key).and_then(Value::as_table);
+            for name in entries.into_iter().flatten().map(|(name, _)| name) {
+                findings.push(Finding::new(
+                    Rule::UnreachableDependency,
+                    name,
+                    format!(
+                        "`{}` is in feature `{}`, but no environment in `[environments]` includes the feature, so it's never installed; add the feature to an environment or remove it",
+                        name, feature
+                    ),
+                    Some(Location::new(
+                        file_path.to_path_buf(),
+                        line_of_entry(content, &section, name),
+                    )),
+                ));
+            }
+        }
+    }
+    Ok(findings)
+}
+
+fn duplicate_provisions(content: &str, file_path: &Path) -> Result<Vec<Finding>, AnalysisError> {
+    let document: Value =
+        toml::from_str(content).map_err(|e| AnalysisError::PixiTomlError(e.to_string()))?;
+    let base = file_path.parent().unwrap_or(Path::new("."));
+    let declared = |table: &Value, key: &str, prefix: &str| {
+        let section = format!("[{}{}]", prefix, key);
+        let mut packages = Vec::new();
+        for (name, spec) in table
+            .get(key)
+            .and_then(Value::as_table)
+            .into_iter()
+            .flatten()
+        {
+            let name = if key == "dependencies" {
+                match conda_spec(name, spec) {
+                    Ok(conda) if conda.name() != "python" => conda.name().to_string(),
+                    _ => continue,
+                }
+            } else {
+                match pypi_dependency(name, spec, base) {
+                    Ok(dependency) => dependency.name().unwrap_or(name).to_string(),
+                    Err(_) => continue,
+                }
+            };
+            packages.push(DeclaredPackage {
+                text: format!("{} = {}", name, spec),
+                line: line_of_entry(content, &section, &name),
+                name,
+            });
+        }
+        packages
+    };
+
+    let conda = declared(&document, "dependencies", "");
+    let pypi = declared(&document, "pypi-dependencies", "");
+    let mut findings = duplicate_provision_findings(file_path, &conda, &pypi);
+    for (name, table) in document
+        .get("feature")
+        .and_then(Value::as_table)
+        .into_iter()
+        .flatten()
+    {
+        let prefix = format!("feature.{}.", name);
+        let feature_conda = declared(table, "dependencies", &prefix);
+        let feature_pypi = declared(table, "pypi-dependencies", &prefix);
+        let mut environment_conda = conda.clone();
+        environment_conda.extend(feature_conda.iter().cloned());
+        findings.extend(duplicate_provision_findings(
+            file_path,
+            &environment_conda,
+            &feature_pypi,
+        ));
+        findings.extend(duplicate_provision_findings(
+            file_path,
+            &feature_conda,
+            &pypi,
+        ));
+    }
+    Ok(findings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_pixi_toml() -> Result<(), AnalysisError> {
+        let content = r#"
+[project]
+name = "example"
+channels = ["conda-forge"]
+platforms = ["linux-64"]
+
+[dependencies]
+python = "3.11.*"
+numpy = ">=1.26"
+pandas = { version = "2.*", channel = "conda-forge" }
+ffmpeg = "*"
+
+[pypi-dependencies]
+requests = ">=2.31"
+rich = { version = "13.7.0", extras = ["jupyter"] }
+local = { path = "./local", editable = true }
+"#;
+        let pixi = parse_contents(content, Path::new("pixi.toml"))?;
+
+        let names: Vec<_> = pixi
+            .dependencies
+            .iter()
+            .filter_map(|dep| dep.name())
+            .collect();
+        assert_eq!(
+            names,
+            ["ffmpeg", "numpy", "pandas", "local", "requests", "rich"]
+        );
+        assert_eq!(
+            pixi.python.as_ref().map(CondaMatchSpec::raw_spec),
+            Some("python 3.11.*")
+        );
+        assert!(matches!(
+            pixi.dependencies[3],
+            Dependency::PackagePath { .. }
+        ));
+        let Dependency::PyPI(rich) = &pixi.dependencies[5] else {
+            panic!("expected a PyPI dependency");
+        };
+        assert_eq!(rich.to_string(), "rich[jupyter]==13.7.0");
+        assert!(pixi.diagnostics.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_collects_invalid_entries() -> Result<(), AnalysisError> {
+        let content = r#"[dependencies]
+numpy = ">=1.26"
+pandas = 2
+
+[pypi-dependencies]
+requests = ">=2.31"
+flask = ">=>2"
+"#;
+        let pixi = parse_contents(content, Path::new("pixi.toml"))?;
+
+        let names: Vec<_> = pixi
+            .dependencies
+            .iter()
+            .filter_map(|dep| dep.name())
+            .collect();
+        assert_eq!(names, ["numpy", "requests"]);
+        let found: Vec<_> = pixi
+            .diagnostics
+            .iter()
+            .map(|d| (d.location.line, d.text.as_str()))
+            .collect();
+        assert_eq!(found, [(3, "pandas = 2"), (7, "flask = \">=>2\"")]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_targets_and_features() -> Result<(), AnalysisError> {
+        let content = r#"
+[workspace]
+channels = ["conda-forge"]
+platforms = ["linux-64", "osx-arm64", "win-64"]
+
+[dependencies]
+numpy = "*"
+
+[target.linux-64.dependencies]
+jemalloc = "*"
+
+[target.unix.pypi-dependencies]
+uvloop = "*"
+
+[target.win.pypi-dependencies]
+pywin32 = "*"
+
+[feature.cuda.dependencies]
+cupy = "*"
+
+[feature.cuda.target.win-64.pypi-dependencies]
+nvidia-ml-py = "*"
+flask = ">=>2"
+"#;
+        let pixi = parse_contents(content, Path::new("pixi.toml"))?;
+        assert_eq!(pixi.platforms, ["linux-64", "osx-arm64", "win-64"]);
+        assert!(pixi.has_targets());
+
+        let names = |dependencies: Vec<Dependency>| -> Vec<String> {
+            dependencies
+                .iter()
+                .filter_map(|dependency| dependency.name().map(str::to_string))
+                .collect()
+        };
+        let base = |platform| for_platform(&pixi.dependencies, &pixi.targets, platform);
+        assert_eq!(
+            names(base(Some("linux-64"))),
+            ["numpy", "jemalloc", "uvloop"]
+        );
+        assert_eq!(names(base(Some("osx-arm64"))), ["numpy", "uvloop"]);
+        assert_eq!(names(base(Some("win-64"))), ["numpy", "pywin32"]);
+        assert_eq!(
+            names(base(None)),
+            ["numpy", "jemalloc", "uvloop", "pywin32"]
+        );
+
+        let [cuda] = &pixi.features[..] else {
+            panic!("expected one feature: {:?}", pixi.features);
+        };
+        assert_eq!(cuda.name, "cuda");
+        let cuda_on = |platform| names(for_platform(&cuda.dependencies, &cuda.targets, platform));
+        assert_eq!(cuda_on(Some("win-64")), ["cupy", "nvidia-ml-py"]);
+        assert_eq!(cuda_on(Some("linux-64")), ["cupy"]);
+
+        let found: Vec<_> = pixi
+            .diagnostics
+            .iter()
+            .map(|diagnostic| (diagnostic.location.line, diagnostic.text.as_str()))
+            .collect();
+        assert_eq!(found, [(23, "flask = \">=>2\"")]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_unreachable_features() -> Result<(), AnalysisError> {
+        let content = r#"
+[dependencies]
+numpy = "*"
+
+[feature.test.dependencies]
+pytest = "*"
+
+[feature.docs.dependencies]
+sphinx = "*"
+
+[feature.docs.pypi-dependencies]
+furo = "*"
+
+[environments]
+test = { features = ["test"], solve-group = "default" }
+"#;
+        let findings = unreachable_features(content, Path::new("pixi.toml"))?;
+        let found: Vec<(Rule, &str, usize)> = findings
+            .iter()
+            .map(|f| {
+                (
+                    f.rule,
+                    f.subject.as_str(),
+                    f.location.as_ref().unwrap().line,
+                )
+            })
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (Rule::UnreachableDependency, "sphinx", 9),
+                (Rule::UnreachableDependency, "furo", 12),
+            ]
+        );
+
+        // Without `[environments]`, each feature is an environment of its own
+        let content = content.split("[environments]").next().unwrap();
+        assert!(unreachable_features(content, Path::new("pixi.toml"))?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_duplicate_provisions() -> Result<(), AnalysisError> {
+        let content = r#"
+[dependencies]
+numpy = ">=1.26"
+pytorch = "*"
+
+[pypi-dependencies]
+numpy = ">=1.26"
+
+[feature.gpu.dependencies]
+cupy = "*"
+
+[feature.gpu.pypi-dependencies]
+torch = { version = ">=2", extras = ["cuda"] }
+cupy = "*"
+"#;
+        let findings = duplicate_provisions(content, Path::new("pixi.toml"))?;
+        let found: Vec<(&str, usize)> = findings
+            .iter()
+            .map(|finding| {
+                (
+                    finding.subject.as_str(),
+                    finding.location.as_ref().unwrap().line,
+                )
+            })
+            .collect();
+        assert_eq!(found, [("numpy", 7), ("cupy", 14), ("torch", 13)]);
+        assert!(
+            findings[0]
+                .message
+                .contains("(`numpy = \">=1.26\"` on line 3)")
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,237 @@
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use toml::Value;
+
+use crate::error::AnalysisError;
+use crate::name_map::normalize_name;
+use crate::project::{Dependency, PyPIRequirement};
+
+/// The group of the packages a poetry project installs without `--with` or `--only`
+const MAIN_GROUP: &str = "main";
+
+/// A package pinned by a `[[package]]` entry of a `poetry.lock`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct LockedPackage {
+    pub(crate) name: String,
+    pub(crate) version: String,
+    /// The dependency groups it's installed for, from `groups`, or from `category` in lock
+    /// files written before Poetry 1.5
+    pub(crate) groups: Vec<String>,
+    /// Whether it's only installed with an extra
+    pub(crate) optional: bool,
+    /// The environments it's installed in for the main group, like `sys_platform == "win32"`
+    marker: Option<String>,
+}
+
+impl LockedPackage {
+    /// The requirement pinning the package to its locked version
+    fn dependency(&self) -> Option<Dependency> {
+        let requirement = match &self.marker {
+            Some(marker) => format!("{}=={} ; {}", self.name, self.version, marker),
+            None => format!("{}=={}", self.name, self.version),
+        };
+        PyPIRequirement::from_str(&requirement)
+            .or_else(|_| PyPIRequirement::from_str(&format!("{}=={}", self.name, self.version)))
+            .ok()
+            .map(Dependency::PyPI)
+    }
+}
+
+/// The packages a `poetry.lock` pins, which are the whole resolved set of a poetry project,
+/// dependencies of dependencies included
+#[derive(Debug, Default)]
+pub(crate) struct PoetryLock {
+    pub(crate) packages: Vec<LockedPackage>,
+    /// The optional packages each extra of `[extras]` installs, in order
+    pub(crate) extras: Vec<(String, Vec<String>)>,
+}
+
+impl PoetryLock {
+    /// The packages of the main group that are installed without any extra
+    pub(crate) fn required_dependencies(&self) -> Vec<Dependency> {
+        self.packages
+            .iter()
+            .filter(|package| !package.optional && package.groups.iter().any(|g| g == MAIN_GROUP))
+            .filter_map(LockedPackage::dependency)
+            .collect()
+    }
+
+    /// The optional packages an extra adds to the required ones
+    pub(crate) fn extra_dependencies(&self, extra: &str) -> Vec<Dependency> {
+        let Some((_, names)) = self.extras.iter().find(|(name, _)| name == extra) else {
+            return Vec::new();
+        };
+        let names: Vec<String> = names.iter().map(|name| normalize_name(name)).collect();
+        self.packages
+            .iter()
+            .filter(|package| package.optional && names.contains(&normalize_name(&package.name)))
+            .filter_map(LockedPackage::dependency)
+            .collect()
+    }
+
+    /// The dependency groups other than the main one, sorted
+    pub(crate) fn groups(&self) -> Vec<&str> {
+        let mut groups: Vec<&str> = self
+            .packages
+            .iter()
+            .flat_map(|package| package.groups.iter().map(String::as_str))
+            .filter(|group| *group != MAIN_GROUP)
+            .collect();
+        groups.sort_unstable();
+        groups.dedup();
+        groups
+    }
+
+    /// The packages a dependency group installs
+    pub(crate) fn group_dependencies(&self, group: &str) -> Vec<Dependency> {
+        self.packages
+            .iter()
+            .filter(|package| package.groups.iter().any(|g| g == group))
+            .filter_map(LockedPackage::dependency)
+            .collect()
+    }
+}
+
+/// Read the `[[package]]` entries and `[extras]` of a `poetry.lock`
+pub(crate) fn parse(path: &Path) -> Result<PoetryLock, AnalysisError> {
+    let content = fs::read_to_string(path).map_err(|e| {
+        AnalysisError::FileReadError(path.to_string_lossy().to_string(), e.to_string())
+    })?;
+    parse_content(&content)
+        .map_err(|e| AnalysisError::PoetryLockError(path.display().to_string(), e))
+}
+
+fn parse_content(content: &str) -> Result<PoetryLock, String> {
+    let document: Value = toml::from_str(content).map_err(|e| e.to_string())?;
+    let mut lock = PoetryLock::default();
+    let packages = match document.get("package") {
+        Some(Value::Array(packages)) => packages.as_slice(),
+        Some(_) => return Err("`package` is not an array of tables".to_string()),
+        None => &[],
+    };
+    for package in packages {
+        let field = |key: &str| package.get(key).and_then(Value::as_str);
+        let (Some(name), Some(version)) = (field("name"), field("version")) else {
+            return Err("a `[[package]]` entry has no `name` or `version`".to_string());
+        };
+        let groups = match (package.get("groups"), field("category")) {
+            (Some(Value::Array(groups)), _) => groups
+                .iter()
+                .filter_map(Value::as_str)
+                .map(str::to_string)
+                .collect(),
+            (_, Some(category)) => vec![category.to_string()],
+            _ => vec![MAIN_GROUP.to_string()],
+        };
+        // Poetry 2 writes the markers of each group in a table
+        let marker = match package.get("markers") {
+            Some(Value::String(marker)) => Some(marker.clone()),
+            Some(Value::Table(markers)) => markers
+                .get(MAIN_GROUP)
+                .and_then(Value::as_str)
+                .map(str::to_string),
+            _ => None,
+        };
+        lock.packages.push(LockedPackage {
+            name: name.to_string(),
+            version: version.to_string(),
+            groups,
+            optional: package
+                .get("optional")
+                .and_then(Value::as_bool)
+                .unwrap_or(false),
+            marker,
+        });
+    }
+    if let Some(Value::Table(extras)) = document.get("extras") {
+        for (extra, names) in extras {
+            let names = names
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str)
+                // Entries may carry extras or constraints, like `pyyaml (>=5.1)`
+                .filter_map(|name| name.split([' ', '[', '(']).next())
+                .map(str::to_string)
+                .collect();
+            lock.extras.push((extra.clone(), names));
+        }
+    }
+    Ok(lock)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pins(dependencies: &[Dependency]) -> Vec<String> {
+        dependencies
+            .iter()
+            .map(|dependency| match dependency {
+                Dependency::PyPI(requirement) => requirement.to_string(),
+                _ => panic!("Expected a PyPI dependency"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_poetry_lock() {
+        let content = r#"
+[[package]]
+name = "requests"
+version = "2.31.0"
+description = "Python HTTP for Humans."
+optional = false
+python-versions = ">=3.7"
+groups = ["main"]
+markers = {main = "python_version >= \"3.8\""}
+
+[package.dependencies]
+certifi = ">=2017.4.17"
+
+[[package]]
+name = "PyYAML"
+version = "6.0.1"
+optional = true
+python-versions = ">=3.6"
+groups = ["main"]
+
+[[package]]
+name = "pytest"
+version = "8.0.0"
+optional = false
+python-versions = ">=3.8"
+category = "dev"
+
+[extras]
+yaml = ["pyyaml"]
+
+[metadata]
+lock-version = "2.1"
+"#;
+        let lock = parse_content(content).unwrap();
+        let packages: Vec<(&str, &str)> = lock
+            .packages
+            .iter()
+            .map(|package| (package.name.as_str(), package.version.as_str()))
+            .collect();
+        assert_eq!(
+            packages,
+            [
+                ("requests", "2.31.0"),
+                ("PyYAML", "6.0.1"),
+                ("pytest", "8.0.0")
+            ]
+        );
+
+        assert_eq!(
+            pins(&lock.required_dependencies()),
+            ["requests==2.31.0 ; python_full_version >= '3.8'"]
+        );
+        assert_eq!(pins(&lock.extra_dependencies("yaml")), ["pyyaml==6.0.1"]);
+        assert_eq!(lock.groups(), ["dev"]);
+        assert_eq!(pins(&lock.group_dependencies("dev")), ["pytest==8.0.0"]);
+    }
+}
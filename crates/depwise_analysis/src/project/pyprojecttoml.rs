@@ -1,19 +1,29 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use toml::Value;
+use tracing::warn;
 
-use crate::error::AnalysisError;
+use crate::error::{AnalysisError, ParseDiagnostic};
+use crate::finding::{Finding, Location, Rule};
+#[cfg(feature = "python-parsing")]
+use crate::layout::{DeclaredPackage, PackageFind, PackageLayout};
+use crate::name_map::normalize_name;
 
 use pep508_rs::Requirement;
+use pep508_rs::pep440_rs::VersionSpecifiers;
+#[cfg(feature = "python-parsing")]
+use pep508_rs::{VerbatimUrl, VersionOrUrl};
 
-use crate::project::Dependency;
+use crate::project::{
+    Dependency, EntryPoint, PackageFile, are_exclusive, line_of_entry, merge_dependencies,
+};
 
 fn parse_dependency_string(dep_str: &str) -> Result<Dependency, AnalysisError> {
     let requirement = Requirement::from_str(dep_str)
         .map_err(|e| AnalysisError::PyProjectTomlError(e.to_string()))?;
-    Ok(Dependency::PyPI(requirement))
+    Ok(Dependency::from_requirement(requirement))
 }
 
 pub struct PyProjectToml {
@@ -23,6 +33,12 @@ pub struct PyProjectToml {
     required_dependencies: Vec<Dependency>,
     /// Optional dependencies grouped by extra name
     optional_dependencies: HashMap<String, Vec<Dependency>>,
+    /// Dependencies of each environment declared under `[tool.hatch.envs]`
+    hatch_environments: BTreeMap<String, Vec<Dependency>>,
+    /// The Python versions `requires-python` says the project supports
+    requires_python: Option<VersionSpecifiers>,
+    /// The dependencies that couldn't be parsed and were skipped
+    diagnostics: Vec<ParseDiagnostic>,
 }
 
 impl PyProjectToml {
@@ -31,13 +47,12 @@ impl PyProjectToml {
             all_dependencies: Vec::new(),
             required_dependencies: Vec::new(),
             optional_dependencies: HashMap::new(),
+            hatch_environments: BTreeMap::new(),
+            requires_python: None,
+            diagnostics: Vec::new(),
         }
     }
 
-    pub fn all_dependencies(&self) -> Vec<Dependency> {
-        self.all_dependencies.clone()
-    }
-
     pub fn required_dependencies(&self) -> &Vec<Dependency> {
         &self.required_dependencies
     }
@@ -53,25 +68,68 @@ impl PyProjectToml {
         // extend all each optional dependency with the required dependencies
         let mut dependencies = self.required_dependencies.clone();
         for configuration in configurations {
-            if let Some(deps) = self.optional_dependencies.get(&configuration.to_string()) {
-                dependencies.extend(deps.clone());
+            if let Some(deps) = self.optional_dependencies.get(*configuration) {
+                merge_dependencies(&mut dependencies, deps.iter().cloned());
             }
         }
         dependencies
     }
+
+    pub fn hatch_environments(&self) -> &BTreeMap<String, Vec<Dependency>> {
+        &self.hatch_environments
+    }
+
+    /// The Python versions the project supports, if `requires-python` says
+    pub fn requires_python(&self) -> Option<&VersionSpecifiers> {
+        self.requires_python.as_ref()
+    }
+
+    pub fn diagnostics(&self) -> &[ParseDiagnostic] {
+        &self.diagnostics
+    }
 }
 
-fn parse_table(table: &Value) -> Result<PyProjectToml, AnalysisError> {
+fn parse_table(
+    table: &Value,
+    contents: &str,
+    file_path: &Path,
+) -> Result<PyProjectToml, AnalysisError> {
     let mut pyprojecttoml = PyProjectToml::new();
+    // A dependency that can't be parsed is skipped, so the rest are still checked
+    let mut diagnostics = Vec::new();
+    let mut parse = |section: &str, dep_str: &str| match Requirement::from_str(dep_str) {
+        Ok(requirement) => Some(Dependency::from_requirement(requirement)),
+        Err(e) => {
+            diagnostics.push(ParseDiagnostic::new(
+                file_path,
+                line_of_entry(contents, section, dep_str),
+                dep_str,
+                e.message.to_string(),
+            ));
+            None
+        }
+    };
 
     if let Some(project_table) = table.get("project") {
+        if let Some(requires_python) = project_table.get("requires-python").and_then(Value::as_str)
+        {
+            match VersionSpecifiers::from_str(requires_python) {
+                Ok(specifiers) => pyprojecttoml.requires_python = Some(specifiers),
+                Err(e) => warn!(
+                    "Ignoring the requires-python of {}: {}",
+                    file_path.display(),
+                    e
+                ),
+            }
+        }
         // Handle dependencies section
         if let Some(deps) = project_table.get("dependencies") {
             match deps {
                 Value::Array(dep_array) => {
                     for dep in dep_array {
-                        if let Value::String(dep_str) = dep {
-                            let dep = parse_dependency_string(dep_str)?;
+                        if let Value::String(dep_str) = dep
+                            && let Some(dep) = parse("[project]", dep_str)
+                        {
                             pyprojecttoml.all_dependencies.push(dep.clone());
                             pyprojecttoml.required_dependencies.push(dep);
                         }
@@ -81,7 +139,9 @@ fn parse_table(table: &Value) -> Result<PyProjectToml, AnalysisError> {
                     for (name, version) in dep_table {
                         if let Value::String(version_str) = version {
                             let dep_str = format!("{} {}", name, version_str);
-                            let dep = parse_dependency_string(&dep_str)?;
+                            let Some(dep) = parse("[project]", &dep_str) else {
+                                continue;
+                            };
                             pyprojecttoml.all_dependencies.push(dep.clone());
                             pyprojecttoml.required_dependencies.push(dep);
                         }
@@ -90,27 +150,25 @@ fn parse_table(table: &Value) -> Result<PyProjectToml, AnalysisError> {
                 _ => {
                     return Err(AnalysisError::PyProjectTomlError(
                         "Invalid dependencies format".to_string(),
-                    )
-                    .into());
+                    ));
                 }
             }
         }
 
         // Handle optional-dependencies section
-        if let Some(optional_deps) = project_table.get("optional-dependencies") {
-            if let Value::Table(optional_table) = optional_deps {
-                for (group, deps) in optional_table {
-                    if let Value::Array(dep_array) = deps {
-                        for dep in dep_array {
-                            if let Value::String(dep_str) = dep {
-                                let dep = parse_dependency_string(dep_str)?;
-                                pyprojecttoml
-                                    .optional_dependencies
-                                    .entry(group.clone())
-                                    .or_insert(Vec::new())
-                                    .push(dep.clone());
-                                pyprojecttoml.all_dependencies.push(dep);
-                            }
+        if let Some(Value::Table(optional_table)) = project_table.get("optional-dependencies") {
+            for (group, deps) in optional_table {
+                if let Value::Array(dep_array) = deps {
+                    for dep in dep_array {
+                        if let Value::String(dep_str) = dep
+                            && let Some(dep) = parse("[project.optional-dependencies]", dep_str)
+                        {
+                            pyprojecttoml
+                                .optional_dependencies
+                                .entry(group.clone())
+                                .or_default()
+                                .push(dep.clone());
+                            pyprojecttoml.all_dependencies.push(dep);
                         }
                     }
                 }
@@ -118,20 +176,917 @@ fn parse_table(table: &Value) -> Result<PyProjectToml, AnalysisError> {
         }
     }
 
+    if let Some(poetry) = poetry_table(table) {
+        let base = file_path.parent().unwrap_or(Path::new("."));
+        let mut optional = Vec::new();
+        for (name, spec) in poetry
+            .get("dependencies")
+            .and_then(Value::as_table)
+            .into_iter()
+            .flatten()
+        {
+            // `python` is the interpreter the project supports, not a package
+            if name == "python" {
+                continue;
+            }
+            match poetry_dependency(name, spec, base) {
+                Ok((dependencies, true)) => optional.extend(dependencies),
+                Ok((dependencies, false)) => {
+                    pyprojecttoml
+                        .all_dependencies
+                        .extend(dependencies.iter().cloned());
+                    pyprojecttoml.required_dependencies.extend(dependencies);
+                }
+                Err(message) => diagnostics.push(ParseDiagnostic::new(
+                    file_path,
+                    line_of_entry(contents, "[tool.poetry.dependencies]", name),
+                    &format!("{} = {}", name, spec),
+                    message,
+                )),
+            }
+        }
+        let extras = poetry_extras(poetry.get("extras"));
+        pyprojecttoml.optional_dependencies = resolve_extras(&optional, &extras);
+        pyprojecttoml.all_dependencies.extend(optional);
+    }
+
+    if let Some(Value::Table(envs)) = table
+        .get("tool")
+        .and_then(|tool| tool.get("hatch"))
+        .and_then(|hatch| hatch.get("envs"))
+    {
+        pyprojecttoml.hatch_environments = parse_hatch_environments(envs, &pyprojecttoml);
+    }
+
+    pyprojecttoml.diagnostics = diagnostics;
     Ok(pyprojecttoml)
 }
 
+/// The `[tool.poetry]` table, if the project declares its dependencies there. Poetry only
+/// reads `[tool.poetry.dependencies]` for the packages to install when `[project]` doesn't
+/// list any; otherwise it just adds constraints and sources to them.
+fn poetry_table(table: &Value) -> Option<&Value> {
+    let declares_dependencies = table
+        .get("project")
+        .is_some_and(|project| project.get("dependencies").is_some());
+    table
+        .get("tool")
+        .and_then(|tool| tool.get("poetry"))
+        .filter(|_| !declares_dependencies)
+}
+
+/// Build the requirements of a `[tool.poetry.dependencies]` entry, and whether it's
+/// `optional`. An entry with a list of constraints, each for some Python versions or
+/// platforms, gives one requirement per constraint. Path, URL and git dependencies are
+/// named after their key, paths being relative to `base`, the directory of the
+/// pyproject.toml.
+fn poetry_dependency(
+    name: &str,
+    spec: &Value,
+    base: &Path,
+) -> Result<(Vec<Dependency>, bool), String> {
+    let constraints = match spec {
+        Value::Array(constraints) => constraints.iter().collect(),
+        spec => vec![spec],
+    };
+    let mut dependencies = Vec::new();
+    let mut optional = false;
+    for constraint in constraints {
+        let table = match constraint {
+            Value::String(version) => {
+                let requirement = format!("{} {}", name, poetry_specifier(version)?);
+                let requirement =
+                    Requirement::from_str(&requirement).map_err(|e| e.message.to_string())?;
+                dependencies.push(Dependency::PyPI(requirement));
+                continue;
+            }
+            Value::Table(table) => table,
+            _ => return Err("Unsupported dependency".to_string()),
+        };
+        optional |= table.get("optional").and_then(Value::as_bool) == Some(true);
+        if let Some(path) = table.get("path").and_then(Value::as_str) {
+            let path = PathBuf::from(path);
+            let found = PackageFile::from_path(&path, base);
+            dependencies.push(Dependency::PackagePath {
+                path,
+                package: PackageFile::declared_as(name, found),
+            });
+            continue;
+        }
+        if let Some(repository) = table.get("git").and_then(Value::as_str) {
+            let revision = ["rev", "tag", "branch"]
+                .iter()
+                .find_map(|key| table.get(*key).and_then(Value::as_str));
+            dependencies.push(Dependency::git(name, repository, revision));
+            continue;
+        }
+        if let Some(url) = table.get("url").and_then(Value::as_str) {
+            dependencies.push(Dependency::PackageUrl {
+                url: url.to_string(),
+                package: PackageFile::declared_as(name, PackageFile::from_url(url)),
+            });
+            continue;
+        }
+
+        let mut requirement = name.to_string();
+        let extras: Vec<&str> = table
+            .get("extras")
+            .and_then(Value::as_array)
+            .map(|extras| extras.iter().filter_map(Value::as_str).collect())
+            .unwrap_or_default();
+        if !extras.is_empty() {
+            requirement.push_str(&format!("[{}]", extras.join(",")));
+        }
+        let version = table.get("version").and_then(Value::as_str).unwrap_or("*");
+        requirement.push_str(&format!(" {}", poetry_specifier(version)?));
+        let mut markers = Vec::new();
+        if let Some(marker) = table.get("markers").and_then(Value::as_str) {
+            markers.push(format!("({})", marker));
+        }
+        if let Some(python) = table.get("python").and_then(Value::as_str) {
+            for clause in poetry_specifier(python)?
+                .split(',')
+                .filter(|c| !c.is_empty())
+            {
+                let (operator, version) =
+                    clause.split_at(clause.find(|c: char| c.is_ascii_digit()).unwrap_or(0));
+                markers.push(format!("python_version {} \"{}\"", operator, version));
+            }
+        }
+        if !markers.is_empty() {
+            requirement.push_str(&format!(" ; {}", markers.join(" and ")));
+        }
+        let requirement = Requirement::from_str(&requirement).map_err(|e| e.message.to_string())?;
+        dependencies.push(Dependency::PyPI(requirement));
+    }
+    Ok((dependencies, optional))
+}
+
+/// Translate a poetry version constraint to a PEP 440 specifier: `^1.2` allows the versions
+/// up to the next change of the first non-zero part (`>=1.2,<2.0`), `~1.2.3` the ones up to
+/// the next minor version (`>=1.2.3,<1.3.0`), and a bare version pins it exactly.
+fn poetry_specifier(constraint: &str) -> Result<String, String> {
+    if constraint.contains("||") {
+        return Err(format!(
+            "`{}` can't be written as a single PEP 440 specifier",
+            constraint
+        ));
+    }
+    let mut clauses = Vec::new();
+    for clause in constraint.split(',').map(str::trim) {
+        // The part of the version that's bumped for the upper bound
+        let (version, bumped) = if let Some(version) = clause.strip_prefix('^') {
+            let parts: Vec<&str> = version.trim().split('.').collect();
+            let bumped = parts
+                .iter()
+                .position(|part| *part != "0")
+                .unwrap_or(parts.len() - 1);
+            (version.trim(), bumped)
+        } else if let Some(version) = clause.strip_prefix('~').filter(|v| !v.starts_with('=')) {
+            let bumped = usize::from(version.contains('.'));
+            (version.trim(), bumped)
+        } else {
+            match clause {
+                "" | "*" => {}
+                clause if clause.starts_with(|c: char| c.is_ascii_digit()) => {
+                    clauses.push(format!("=={}", clause));
+                }
+                clause if clause.starts_with('=') && !clause.starts_with("==") => {
+                    clauses.push(format!("={}", clause.replace(' ', "")));
+                }
+                clause => clauses.push(clause.replace(' ', "")),
+            }
+            continue;
+        };
+        let parts: Vec<&str> = version.split('.').collect();
+        let mut upper = Vec::new();
+        for (i, part) in parts.iter().enumerate() {
+            upper.push(match i.cmp(&bumped) {
+                std::cmp::Ordering::Less => part.to_string(),
+                std::cmp::Ordering::Equal => part
+                    .parse::<u64>()
+                    .map_err(|_| format!("`{}` isn't a valid version constraint", clause))?
+                    .saturating_add(1)
+                    .to_string(),
+                std::cmp::Ordering::Greater => "0".to_string(),
+            });
+        }
+        clauses.push(format!(">={},<{}", version, upper.join(".")));
+    }
+    Ok(clauses.join(","))
+}
+
+/// Read `[tool.poetry.extras]`, the names of the optional dependencies each extra installs
+fn poetry_extras(extras: Option<&Value>) -> BTreeMap<String, Vec<String>> {
+    extras
+        .and_then(Value::as_table)
+        .into_iter()
+        .flatten()
+        .map(|(extra, names)| {
+            let names = hatch_strings(Some(names))
+                .into_iter()
+                .map(extra_package_name)
+                .collect();
+            (extra.clone(), names)
+        })
+        .collect()
+}
+
+/// The normalized name of the package an entry of a poetry extra refers to. Entries are
+/// package names, though some add a constraint or extras the way a requirement does.
+fn extra_package_name(entry: &str) -> String {
+    let end = entry
+        .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')))
+        .unwrap_or(entry.len());
+    normalize_name(&entry[..end])
+}
+
+/// Resolve the extras of a poetry project to their dependencies. Poetry only installs an
+/// optional dependency through the extras that list it, so each extra gets exactly the
+/// optional dependencies it names, a dependency listed by several extras going into each
+/// of them, and one no extra lists isn't in any.
+fn resolve_extras(
+    optional: &[Dependency],
+    extras: &BTreeMap<String, Vec<String>>,
+) -> HashMap<String, Vec<Dependency>> {
+    extras
+        .iter()
+        .map(|(extra, names)| {
+            let dependencies = optional
+                .iter()
+                .filter(|dependency| {
+                    dependency
+                        .name()
+                        .is_some_and(|name| names.contains(&normalize_name(name)))
+                })
+                .cloned()
+                .collect();
+            (extra.clone(), dependencies)
+        })
+        .collect()
+}
+
+/// Read the dependencies of the environments declared under `[tool.hatch.envs]`.
+///
+/// An environment inherits the options it doesn't set from its `template` (the `default`
+/// environment unless it is `detached`) and installs the project, with its `features`,
+/// unless `skip-install` is set. Overrides only apply on some platforms or matrix variables,
+/// so they're ignored and every variant of a matrix gets the environment's dependencies.
+fn parse_hatch_environments(
+    envs: &toml::Table,
+    pyprojecttoml: &PyProjectToml,
+) -> BTreeMap<String, Vec<Dependency>> {
+    let mut environments = BTreeMap::new();
+    for (name, env) in envs {
+        if env.get("overrides").is_some() {
+            warn!("Ignoring the overrides of hatch environment {}", name);
+        }
+
+        let mut dependencies = Vec::new();
+        let detached = env.get("detached").and_then(Value::as_bool) == Some(true);
+        let skip_install = hatch_option(envs, name, "skip-install").and_then(Value::as_bool);
+        if !detached && skip_install != Some(true) {
+            let features = hatch_strings(hatch_option(envs, name, "features"));
+            dependencies.extend(pyprojecttoml.get_dependencies_for_configuration(&features));
+        }
+        for key in ["dependencies", "extra-dependencies"] {
+            for dep_str in hatch_strings(hatch_option(envs, name, key)) {
+                // Hatch expands context fields like `{root}`, which aren't valid PEP 508
+                match parse_dependency_string(dep_str) {
+                    Ok(dep) => dependencies.push(dep),
+                    Err(e) => warn!("Skipping dependency of hatch environment {}: {}", name, e),
+                }
+            }
+        }
+        environments.insert(name.clone(), dependencies);
+    }
+    environments
+}
+
+/// Look up an option of a hatch environment, falling back to its templates
+fn hatch_option<'a>(envs: &'a toml::Table, name: &'a str, key: &str) -> Option<&'a Value> {
+    let mut seen = Vec::new();
+    let mut name = name;
+    while let Some(env) = envs.get(name) {
+        if let Some(value) = env.get(key) {
+            return Some(value);
+        }
+        if env.get("detached").and_then(Value::as_bool) == Some(true) {
+            return None;
+        }
+        seen.push(name);
+        name = env
+            .get("template")
+            .and_then(Value::as_str)
+            .unwrap_or("default");
+        // An environment that is its own template inherits nothing
+        if seen.contains(&name) {
+            return None;
+        }
+    }
+    None
+}
+
+fn hatch_strings(value: Option<&Value>) -> Vec<&str> {
+    value
+        .and_then(Value::as_array)
+        .map(|values| values.iter().filter_map(Value::as_str).collect())
+        .unwrap_or_default()
+}
+
 pub(crate) fn parse(file_path: &Path) -> Result<PyProjectToml, AnalysisError> {
     let content = fs::read_to_string(file_path)
         .map_err(|e| AnalysisError::PyProjectTomlError(e.to_string()))?;
-    parse_contents(&content)
+    parse_contents(&content, file_path)
 }
 
-pub(crate) fn parse_contents(contents: &str) -> Result<PyProjectToml, AnalysisError> {
+pub(crate) fn parse_contents(
+    contents: &str,
+    file_path: &Path,
+) -> Result<PyProjectToml, AnalysisError> {
     let toml_value: Value = contents
         .parse()
         .map_err(|_| AnalysisError::PyProjectTomlError("Invalid TOML".to_string()))?;
-    parse_table(&toml_value)
+    parse_table(&toml_value, contents, file_path)
+}
+
+/// Remove the entries for any of the given packages from the `[project] dependencies` array.
+///
+/// The document is edited in place with `toml_edit` so comments and formatting are
+/// preserved. Direct URL references and entries that fail to parse are left alone. Returns
+/// the new content and the normalized name and text of each removed entry.
+#[cfg(feature = "python-parsing")]
+pub(crate) fn remove_dependencies(
+    contents: &str,
+    names: &[String],
+) -> Result<(String, Vec<(String, String)>), AnalysisError> {
+    let names: Vec<String> = names.iter().map(|name| normalize_name(name)).collect();
+    let mut document: toml_edit::DocumentMut = contents
+        .parse()
+        .map_err(|_| AnalysisError::PyProjectTomlError("Invalid TOML".to_string()))?;
+
+    let mut removed = Vec::new();
+    if let Some(dependencies) = document
+        .get_mut("project")
+        .and_then(|project| project.get_mut("dependencies"))
+        .and_then(|dependencies| dependencies.as_array_mut())
+    {
+        dependencies.retain(|value| {
+            let Some(dep_str) = value.as_str() else {
+                return true;
+            };
+            let Ok(requirement) = Requirement::<VerbatimUrl>::from_str(dep_str) else {
+                return true;
+            };
+            let name = normalize_name(requirement.name.as_ref());
+            if matches!(requirement.version_or_url, Some(VersionOrUrl::Url(_)))
+                || !names.contains(&name)
+            {
+                return true;
+            }
+            removed.push((name, dep_str.to_string()));
+            false
+        });
+    }
+
+    Ok((document.to_string(), removed))
+}
+
+/// Append requirements to the `[project] dependencies` array, creating it if needed.
+///
+/// New entries follow the layout of the existing ones, so a multi-line array stays one
+/// requirement per line.
+#[cfg(feature = "python-parsing")]
+pub(crate) fn add_dependencies(
+    contents: &str,
+    requirements: &[String],
+) -> Result<String, AnalysisError> {
+    let mut document: toml_edit::DocumentMut = contents
+        .parse()
+        .map_err(|_| AnalysisError::PyProjectTomlError("Invalid TOML".to_string()))?;
+
+    let project = document
+        .get_mut("project")
+        .and_then(|project| project.as_table_like_mut())
+        .ok_or_else(|| AnalysisError::PyProjectTomlError("No [project] table".to_string()))?;
+    let dependencies = project
+        .entry("dependencies")
+        .or_insert_with(|| toml_edit::value(toml_edit::Array::new()))
+        .as_array_mut()
+        .ok_or_else(|| {
+            AnalysisError::PyProjectTomlError("`dependencies` is not an array".to_string())
+        })?;
+
+    let prefix = dependencies
+        .iter()
+        .last()
+        .and_then(|value| value.decor().prefix())
+        .and_then(|prefix| prefix.as_str())
+        .map(str::to_string);
+
+    // A comment after the last entry is part of the array's trailing whitespace, keep it on
+    // the entry it belongs to rather than letting it move below the new ones
+    let mut trailing_comment = String::new();
+    if prefix.is_some()
+        && let Some(trailing) = dependencies.trailing().as_str().map(str::to_string)
+        && let Some((comment, rest)) = trailing.rsplit_once('\n')
+        && !comment.trim().is_empty()
+    {
+        trailing_comment = comment.to_string();
+        dependencies.set_trailing(format!("\n{}", rest));
+    }
+
+    for requirement in requirements {
+        dependencies.push(requirement.as_str());
+        if let Some(prefix) = &prefix
+            && let Some(value) = dependencies.get_mut(dependencies.len() - 1)
+        {
+            let prefix = format!("{}{}", std::mem::take(&mut trailing_comment), prefix);
+            value.decor_mut().set_prefix(prefix);
+        }
+    }
+
+    Ok(document.to_string())
+}
+
+/// Append requirements to a `[dependency-groups]` group (PEP 735), creating the table and
+/// the group if needed
+#[cfg(feature = "python-parsing")]
+pub(crate) fn add_to_group(
+    contents: &str,
+    group: &str,
+    requirements: &[String],
+) -> Result<String, AnalysisError> {
+    let mut document: toml_edit::DocumentMut = contents
+        .parse()
+        .map_err(|_| AnalysisError::PyProjectTomlError("Invalid TOML".to_string()))?;
+
+    let groups = document
+        .entry("dependency-groups")
+        .or_insert_with(toml_edit::table)
+        .as_table_like_mut()
+        .ok_or_else(|| {
+            AnalysisError::PyProjectTomlError("`dependency-groups` is not a table".to_string())
+        })?;
+    let dependencies = groups
+        .entry(group)
+        .or_insert_with(|| toml_edit::value(toml_edit::Array::new()))
+        .as_array_mut()
+        .ok_or_else(|| {
+            AnalysisError::PyProjectTomlError(format!("The `{}` group is not an array", group))
+        })?;
+    for requirement in requirements {
+        dependencies.push(requirement.as_str());
+    }
+    Ok(document.to_string())
+}
+
+/// A dependency as written in pyproject.toml and the line it's declared on
+struct Declaration {
+    dependency: Dependency,
+    text: String,
+    line: usize,
+}
+
+/// Find declarations in a pyproject.toml that are redundant or disagree with each other:
+/// packages both required and in an extra, packages pinned differently by two extras, and
+/// extras whose dependencies are all in a bigger extra. Extras that are alternatives to each
+/// other aren't compared. In a poetry project, the optional dependencies no extra lists are
+/// reported too, since nothing installs them.
+pub(crate) fn declaration_findings(
+    file_path: &Path,
+    exclusive_extras: &[Vec<String>],
+) -> Result<Vec<Finding>, AnalysisError> {
+    let contents = fs::read_to_string(file_path)
+        .map_err(|e| AnalysisError::PyProjectTomlError(e.to_string()))?;
+    let document = toml_edit::ImDocument::parse(contents.as_str())
+        .map_err(|_| AnalysisError::PyProjectTomlError("Invalid TOML".to_string()))?;
+    let line_of = |span: Option<std::ops::Range<usize>>| {
+        span.map_or(0, |span| contents[..span.start].matches('\n').count() + 1)
+    };
+    let declarations = |item: Option<&toml_edit::Item>| -> Vec<Declaration> {
+        item.and_then(toml_edit::Item::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(|value| {
+                let text = value.as_str()?;
+                Some(Declaration {
+                    dependency: parse_dependency_string(text).ok()?,
+                    text: text.to_string(),
+                    line: line_of(value.span()),
+                })
+            })
+            .collect()
+    };
+
+    let location = |line: usize| Some(Location::new(file_path.to_path_buf(), line));
+    let mut findings = Vec::new();
+    let poetry = document
+        .get("tool")
+        .and_then(|tool| tool.get("poetry"))
+        .filter(|_| {
+            document
+                .get("project")
+                .is_none_or(|project| project.get("dependencies").is_none())
+        });
+    if let Some(dependencies) = poetry
+        .and_then(|poetry| poetry.get("dependencies"))
+        .and_then(toml_edit::Item::as_table_like)
+    {
+        let extras = poetry.and_then(|poetry| poetry.get("extras"));
+        let listed: Vec<String> = extras
+            .and_then(toml_edit::Item::as_table_like)
+            .into_iter()
+            .flat_map(|extras| extras.iter())
+            .filter_map(|(_, names)| names.as_array())
+            .flatten()
+            .filter_map(|name| name.as_str())
+            .map(extra_package_name)
+            .collect();
+        for (name, item) in dependencies.iter() {
+            let is_optional = |value: &toml_edit::Value| {
+                value
+                    .as_inline_table()
+                    .and_then(|table| table.get("optional"))
+                    .and_then(toml_edit::Value::as_bool)
+                    == Some(true)
+            };
+            let optional = match item {
+                toml_edit::Item::Value(toml_edit::Value::Array(constraints)) => {
+                    constraints.iter().any(is_optional)
+                }
+                toml_edit::Item::Value(value) => is_optional(value),
+                toml_edit::Item::Table(table) => {
+                    table.get("optional").and_then(toml_edit::Item::as_bool) == Some(true)
+                }
+                _ => false,
+            };
+            if !optional || listed.contains(&normalize_name(name)) {
+                continue;
+            }
+            findings.push(Finding::new(
+                Rule::UnreachableDependency,
+                name,
+                format!(
+                    "`{}` is an optional dependency, but no extra in `[tool.poetry.extras]` lists it, so it's never installed; add it to an extra or make it required",
+                    name
+                ),
+                location(line_of(dependencies.key(name).and_then(toml_edit::Key::span))),
+            ));
+        }
+    }
+
+    let Some(project) = document
+        .get("project")
+        .and_then(toml_edit::Item::as_table_like)
+    else {
+        return Ok(findings);
+    };
+    let project_name = project
+        .get("name")
+        .and_then(toml_edit::Item::as_str)
+        .unwrap_or("<project>");
+    let required = declarations(project.get("dependencies"));
+    let mut extras: BTreeMap<&str, (usize, Vec<Declaration>)> = BTreeMap::new();
+    if let Some(optional) = project
+        .get("optional-dependencies")
+        .and_then(toml_edit::Item::as_table_like)
+    {
+        for (extra, item) in optional.iter() {
+            let line = line_of(optional.key(extra).and_then(toml_edit::Key::span));
+            extras.insert(extra, (line, declarations(Some(item))));
+        }
+    }
+
+    for (extra, (_, declared)) in &extras {
+        for declaration in declared {
+            let Some(existing) = required
+                .iter()
+                .find(|r| r.dependency.same_package(&declaration.dependency))
+            else {
+                continue;
+            };
+            let name = declaration.dependency.name().unwrap_or(&declaration.text);
+            let cleanup = if same_specifier(&existing.dependency, &declaration.dependency) {
+                "remove it from the extra".to_string()
+            } else {
+                format!(
+                    "remove it from the extra, moving `{}` to the required declaration if the constraint matters",
+                    declaration.text
+                )
+            };
+            findings.push(Finding::new(
+                Rule::DuplicateDependency,
+                name,
+                format!(
+                    "`{}` in extra `{}` is already a required dependency (`{}` on line {}); {}",
+                    name, extra, existing.text, existing.line, cleanup
+                ),
+                location(declaration.line),
+            ));
+        }
+    }
+
+    for (i, (first, (_, first_declared))) in extras.iter().enumerate() {
+        for (second, (_, second_declared)) in extras.iter().skip(i + 1) {
+            if are_exclusive(exclusive_extras, first, second) {
+                continue;
+            }
+            for declaration in second_declared {
+                let Some(other) = first_declared
+                    .iter()
+                    .find(|d| d.dependency.same_package(&declaration.dependency))
+                else {
+                    continue;
+                };
+                if same_specifier(&other.dependency, &declaration.dependency) {
+                    continue;
+                }
+                let name = declaration.dependency.name().unwrap_or(&declaration.text);
+                findings.push(Finding::new(
+                    Rule::ConflictingExtraSpecifiers,
+                    name,
+                    format!(
+                        "`{}` is `{}` in extra `{}` (line {}) but `{}` in extra `{}`; declare it the same way in both",
+                        name, other.text, first, other.line, declaration.text, second
+                    ),
+                    location(declaration.line),
+                ));
+            }
+        }
+    }
+
+    for (extra, (line, declared)) in &extras {
+        let covered_by = |other: &[Declaration]| {
+            declared.iter().all(|declaration| {
+                other.iter().any(|d| {
+                    d.dependency.same_package(&declaration.dependency)
+                        && same_specifier(&d.dependency, &declaration.dependency)
+                })
+            })
+        };
+        let Some(superset) = extras.iter().find(|(other, (_, other_declared))| {
+            other != &extra
+                && !are_exclusive(exclusive_extras, extra, other)
+                && !declared.is_empty()
+                && other_declared.len() > declared.len()
+                && covered_by(other_declared)
+        }) else {
+            continue;
+        };
+        findings.push(Finding::new(
+            Rule::RedundantExtra,
+            extra,
+            format!(
+                "Every dependency of extra `{}` is also in extra `{}`; remove `{}` or replace them in `{}` with `{}[{}]`",
+                extra, superset.0, extra, superset.0, project_name, extra
+            ),
+            location(*line),
+        ));
+    }
+
+    Ok(findings)
+}
+
+/// Read the entry points of `[project.scripts]`, `[project.gui-scripts]`, and the groups of
+/// `[project.entry-points]`, like `mypkg.cli` and `main` for `mytool = "mypkg.cli:main"`,
+/// with where each one is declared
+pub(crate) fn entry_points(file_path: &Path) -> Result<Vec<EntryPoint>, AnalysisError> {
+    let contents = fs::read_to_string(file_path)
+        .map_err(|e| AnalysisError::PyProjectTomlError(e.to_string()))?;
+    let document = toml_edit::ImDocument::parse(contents.as_str())
+        .map_err(|_| AnalysisError::PyProjectTomlError("Invalid TOML".to_string()))?;
+
+    let mut entry_points = Vec::new();
+    let Some(project) = document
+        .get("project")
+        .and_then(toml_edit::Item::as_table_like)
+    else {
+        return Ok(entry_points);
+    };
+    let mut groups: Vec<(String, &dyn toml_edit::TableLike)> = Vec::new();
+    for key in ["scripts", "gui-scripts"] {
+        if let Some(scripts) = project.get(key).and_then(toml_edit::Item::as_table_like) {
+            groups.push((key.to_string(), scripts));
+        }
+    }
+    if let Some(tables) = project
+        .get("entry-points")
+        .and_then(toml_edit::Item::as_table_like)
+    {
+        for (group, item) in tables.iter() {
+            if let Some(table) = item.as_table_like() {
+                groups.push((group.to_string(), table));
+            }
+        }
+    }
+    for (group, table) in groups {
+        for (name, item) in table.iter() {
+            let Some(target) = item.as_str() else {
+                continue;
+            };
+            // The object reference is `module:attr`, optionally followed by `[extras]`
+            let reference = target.split('[').next().unwrap_or(target);
+            let (module, attribute) = match reference.split_once(':') {
+                Some((module, attribute)) => (module.trim(), Some(attribute.trim())),
+                None => (reference.trim(), None),
+            };
+            if module.is_empty() {
+                continue;
+            }
+            let line = item
+                .span()
+                .map_or(0, |span| contents[..span.start].matches('\n').count() + 1);
+            entry_points.push(EntryPoint {
+                group: group.clone(),
+                name: name.to_string(),
+                module: module.to_string(),
+                attribute: attribute
+                    .filter(|attribute| !attribute.is_empty())
+                    .map(str::to_string),
+                location: Location::new(file_path.to_path_buf(), line),
+            });
+        }
+    }
+    Ok(entry_points)
+}
+
+/// Read how the build backend is configured to find the project's packages: `packages`,
+/// `py-modules`, `package-dir`, and `packages.find` under `[tool.setuptools]`, `packages`
+/// under `[tool.hatch.build.targets.wheel]`, and `module-name` and `python-source` under
+/// `[tool.maturin]`. Returns `None` if none of them are configured.
+#[cfg(feature = "python-parsing")]
+pub(crate) fn package_layout(file_path: &Path) -> Result<Option<PackageLayout>, AnalysisError> {
+    let contents = fs::read_to_string(file_path)
+        .map_err(|e| AnalysisError::PyProjectTomlError(e.to_string()))?;
+    let document = toml_edit::ImDocument::parse(contents.as_str())
+        .map_err(|_| AnalysisError::PyProjectTomlError("Invalid TOML".to_string()))?;
+    let root = file_path.parent().unwrap_or(Path::new("")).to_path_buf();
+    let location = |span: Option<std::ops::Range<usize>>| {
+        let line = span.map_or(0, |span| contents[..span.start].matches('\n').count() + 1);
+        Location::new(file_path.to_path_buf(), line)
+    };
+    let strings = |item: &toml_edit::Item| -> Vec<(String, Location)> {
+        item.as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|value| Some((value.as_str()?.to_string(), location(value.span()))))
+            .collect()
+    };
+    let tool = |keys: &[&str]| {
+        keys.iter()
+            .try_fold(document.get("tool")?.as_table_like()?, |table, key| {
+                table.get(key)?.as_table_like()
+            })
+    };
+
+    let mut layout = PackageLayout {
+        root: root.clone(),
+        ..Default::default()
+    };
+    if let Some(setuptools) = tool(&["setuptools"]) {
+        // `package-dir` maps package names to their directories, `""` being the directory
+        // top-level packages are in
+        let package_dirs: Vec<(&str, PathBuf, Location)> = setuptools
+            .get("package-dir")
+            .and_then(toml_edit::Item::as_table_like)
+            .into_iter()
+            .flat_map(|dirs| dirs.iter())
+            .filter_map(|(package, dir)| {
+                Some((package, root.join(dir.as_str()?), location(dir.span())))
+            })
+            .collect();
+        let base = package_dirs.iter().find(|(package, ..)| package.is_empty());
+        let path_of = |module: &str| {
+            let (mut path, rest) = package_dirs
+                .iter()
+                .filter(|(package, ..)| {
+                    !package.is_empty()
+                        && (module == *package || module.starts_with(&format!("{}.", package)))
+                })
+                .max_by_key(|(package, ..)| package.len())
+                .map_or_else(
+                    || {
+                        (
+                            base.map_or_else(|| root.clone(), |(_, dir, _)| dir.clone()),
+                            module,
+                        )
+                    },
+                    |(package, dir, _)| (dir.clone(), &module[package.len()..]),
+                );
+            path.extend(rest.split('.').filter(|part| !part.is_empty()));
+            path
+        };
+
+        match setuptools.get("packages") {
+            Some(packages) if packages.is_array() => {
+                for (name, location) in strings(packages) {
+                    let path = path_of(&name);
+                    layout.packages.push(DeclaredPackage {
+                        name,
+                        path: Some(path),
+                        location,
+                    });
+                }
+                layout.lists_subpackages = true;
+            }
+            Some(packages) => {
+                if let Some(find) = packages.as_table_like().and_then(|p| p.get("find")) {
+                    let find_location = location(find.span());
+                    let patterns = |key: &str| -> Vec<String> {
+                        find.get(key)
+                            .map(|item| strings(item).into_iter().map(|(p, _)| p).collect())
+                            .unwrap_or_default()
+                    };
+                    let wheres = find.get("where").map(strings).unwrap_or_default();
+                    if wheres.is_empty() {
+                        layout
+                            .source_roots
+                            .push((root.clone(), find_location.clone()));
+                    }
+                    for (directory, location) in wheres {
+                        layout.source_roots.push((root.join(directory), location));
+                    }
+                    layout.find = Some(PackageFind {
+                        include: patterns("include"),
+                        exclude: patterns("exclude"),
+                        location: find_location,
+                    });
+                }
+            }
+            None => {
+                if let Some((_, dir, location)) = base {
+                    layout.source_roots.push((dir.clone(), location.clone()));
+                }
+            }
+        }
+        if let Some(modules) = setuptools.get("py-modules") {
+            for (name, location) in strings(modules) {
+                let path = path_of(&name).with_extension("py");
+                layout.packages.push(DeclaredPackage {
+                    name,
+                    path: Some(path),
+                    location,
+                });
+            }
+        }
+    }
+
+    // Hatch lists the directories of the packages to ship
+    if let Some(packages) =
+        tool(&["hatch", "build", "targets", "wheel"]).and_then(|wheel| wheel.get("packages"))
+    {
+        for (directory, location) in strings(packages) {
+            let path = root.join(&directory);
+            let Some(name) = path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+            else {
+                continue;
+            };
+            layout.packages.push(DeclaredPackage {
+                name,
+                path: Some(path),
+                location,
+            });
+        }
+    }
+
+    // Maturin builds `module-name` from Rust, so it isn't on disk; the Python half of a mixed
+    // project is in `python-source`
+    if let Some(maturin) = tool(&["maturin"]) {
+        if let Some(item) = maturin.get("python-source")
+            && let Some(source) = item.as_str()
+        {
+            layout
+                .source_roots
+                .push((root.join(source), location(item.span())));
+        }
+        if let Some(item) = maturin.get("module-name")
+            && let Some(module) = item.as_str()
+        {
+            layout.packages.push(DeclaredPackage {
+                name: module.to_string(),
+                path: None,
+                location: location(item.span()),
+            });
+        }
+    }
+
+    Ok((!layout.packages.is_empty() || !layout.source_roots.is_empty()).then_some(layout))
+}
+
+fn same_specifier(a: &Dependency, b: &Dependency) -> bool {
+    match (a, b) {
+        (Dependency::PyPI(a), Dependency::PyPI(b)) => {
+            let mut a_extras = a.extras.clone();
+            let mut b_extras = b.extras.clone();
+            a_extras.sort();
+            b_extras.sort();
+            a.version_or_url == b.version_or_url && a.marker == b.marker && a_extras == b_extras
+        }
+        _ => a == b,
+    }
 }
 
 #[cfg(test)]
@@ -147,7 +1102,7 @@ dependencies = [
     "flask == 1.0.0",
 ]
 "#;
-        let deps = parse_contents(content)?;
+        let deps = parse_contents(content, Path::new("pyproject.toml"))?;
 
         assert_eq!(deps.all_dependencies.len(), 2);
         match &deps.all_dependencies[0] {
@@ -161,6 +1116,105 @@ dependencies = [
         Ok(())
     }
 
+    #[test]
+    fn test_parse_collects_invalid_dependencies() -> Result<(), AnalysisError> {
+        let content = r#"[project]
+name = "example"
+dependencies = [
+    "requests >= 2.8.1",
+    "flask >=> 2",
+    "rich",
+]
+
+[project.optional-dependencies]
+test = ["pytest", "pytest-cov ==="]
+"#;
+        let pyproject = parse_contents(content, Path::new("pyproject.toml"))?;
+
+        let names: Vec<_> = pyproject
+            .all_dependencies
+            .iter()
+            .filter_map(|dep| dep.name())
+            .collect();
+        assert_eq!(names, ["requests", "rich", "pytest"]);
+        let found: Vec<_> = pyproject
+            .diagnostics()
+            .iter()
+            .map(|d| (d.location.line, d.text.as_str()))
+            .collect();
+        assert_eq!(found, [(5, "flask >=> 2"), (10, "pytest-cov ===")]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_direct_references() -> Result<(), AnalysisError> {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("pyproject.toml"),
+            "[project]\nname = \"bar\"\nversion = \"2.0\"\n",
+        )
+        .unwrap();
+        let content = format!(
+            r#"[project]
+dependencies = [
+    "foo @ https://example.com/foo-1.0-py3-none-any.whl",
+    "Baz_Pkg @ https://example.com/archive/main.zip",
+    "bar @ file://{}",
+]
+
+[project.optional-dependencies]
+dev = ["Lint_Tool[fast] @ git+https://github.com/org/lint.git@v2#subdirectory=python"]
+"#,
+            dir.path().display()
+        );
+        let deps = parse_contents(&content, Path::new("pyproject.toml"))?;
+
+        let [foo, baz, bar, lint] = deps.all_dependencies.as_slice() else {
+            panic!("Expected 4 dependencies: {:?}", deps.all_dependencies);
+        };
+        // The version comes from the file name, the name from the requirement
+        let Dependency::PackageUrl {
+            url,
+            package: Some(package),
+        } = foo
+        else {
+            panic!("Expected a URL dependency: {:?}", foo);
+        };
+        assert_eq!(url, "https://example.com/foo-1.0-py3-none-any.whl");
+        assert_eq!(
+            (package.name.as_str(), package.version.as_deref()),
+            ("foo", Some("1.0"))
+        );
+        assert_eq!(baz.name(), Some("baz-pkg"));
+        // A local package is read for its version
+        let Dependency::PackagePath {
+            path,
+            package: Some(package),
+        } = bar
+        else {
+            panic!("Expected a path dependency: {:?}", bar);
+        };
+        assert_eq!(path, dir.path());
+        assert_eq!(
+            (package.name.as_str(), package.version.as_deref()),
+            ("bar", Some("2.0"))
+        );
+        // A repository is matched by the name it's declared as
+        let Dependency::PackageVcs { url, .. } = lint else {
+            panic!("Expected a version control dependency: {:?}", lint);
+        };
+        assert_eq!(
+            url,
+            "git+https://github.com/org/lint.git@v2#subdirectory=python"
+        );
+        assert!(lint.satisfies("lint-tool"));
+        assert_eq!(
+            deps.optional_dependencies["dev"].as_slice(),
+            std::slice::from_ref(lint)
+        );
+        Ok(())
+    }
+
     #[test]
     fn test_parse_dependencies_with_extras() -> Result<(), Box<dyn std::error::Error>> {
         let content = r#"
@@ -172,7 +1226,7 @@ dependencies = [
 [project.optional-dependencies]
 dev = ["pytest >= 6.0.0"]
 "#;
-        let deps = parse_contents(content)?;
+        let deps = parse_contents(content, Path::new("pyproject.toml"))?;
 
         assert_eq!(deps.all_dependencies.len(), 2);
         match &deps.all_dependencies[0] {
@@ -184,4 +1238,463 @@ dev = ["pytest >= 6.0.0"]
 
         Ok(())
     }
+
+    #[test]
+    fn test_parse_hatch_environments() -> Result<(), AnalysisError> {
+        let content = r#"
+[project]
+dependencies = ["requests"]
+
+[project.optional-dependencies]
+cli = ["click"]
+
+[tool.hatch.envs.default]
+dependencies = ["coverage"]
+
+[tool.hatch.envs.test]
+features = ["cli"]
+extra-dependencies = ["pytest"]
+
+[[tool.hatch.envs.test.matrix]]
+python = ["3.11", "3.12"]
+
+[tool.hatch.envs.test.overrides]
+platform.windows.extra-dependencies = ["pywin32"]
+
+[tool.hatch.envs.lint]
+detached = true
+dependencies = ["ruff", "{root}/tools"]
+"#;
+        let pyproject = parse_contents(content, Path::new("pyproject.toml"))?;
+
+        let names = |name: &str| -> Vec<String> {
+            pyproject.hatch_environments()[name]
+                .iter()
+                .filter_map(|dep| dep.name().map(str::to_string))
+                .collect()
+        };
+        assert_eq!(names("default"), ["requests", "coverage"]);
+        assert_eq!(names("test"), ["requests", "click", "coverage", "pytest"]);
+        assert_eq!(names("lint"), ["ruff"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_poetry_extras() -> Result<(), AnalysisError> {
+        let content = r#"
+[tool.poetry]
+name = "example"
+
+[tool.poetry.dependencies]
+python = "^3.9"
+requests = "^2.31"
+click = { version = "~8.1.3", extras = ["colorama"] }
+numpy = [
+    { version = "^1.24", python = "<3.12" },
+    { version = "^1.26", python = ">=3.12" },
+]
+psycopg = { version = "^3.1", optional = true }
+sqlalchemy = { version = "2.0.*", optional = true }
+pandas = { version = "^0.25", optional = true }
+tomli = { version = "^2.0", markers = "python_version < '3.11'" }
+broken = "^1.0 || ^2.0"
+
+[tool.poetry.extras]
+postgres = ["psycopg", "SQLAlchemy"]
+sqlite = ["sqlalchemy"]
+"#;
+        let pyproject = parse_contents(content, Path::new("pyproject.toml"))?;
+
+        let requirements = |dependencies: &[Dependency]| -> Vec<String> {
+            dependencies
+                .iter()
+                .map(|dependency| match dependency {
+                    Dependency::PyPI(requirement) => requirement.to_string(),
+                    dependency => panic!("expected a PyPI dependency: {:?}", dependency),
+                })
+                .collect()
+        };
+        assert_eq!(
+            requirements(pyproject.required_dependencies()),
+            [
+                "click[colorama]>=8.1.3,<8.2.0",
+                "numpy>=1.24,<2.0 ; python_full_version < '3.12'",
+                "numpy>=1.26,<2.0 ; python_full_version >= '3.12'",
+                "requests>=2.31,<3.0",
+                "tomli>=2.0,<3.0 ; python_full_version < '3.11'",
+            ]
+        );
+
+        // A dependency listed by several extras is in each of them, and one no extra
+        // lists isn't in any
+        let mut extras = pyproject.optional_configurations();
+        extras.sort();
+        assert_eq!(extras, ["postgres", "sqlite"]);
+        let extra = |name: &str| -> Vec<String> {
+            pyproject.optional_dependencies[name]
+                .iter()
+                .filter_map(|dependency| dependency.name().map(str::to_string))
+                .collect()
+        };
+        assert_eq!(extra("postgres"), ["psycopg", "sqlalchemy"]);
+        assert_eq!(extra("sqlite"), ["sqlalchemy"]);
+        assert_eq!(pyproject.all_dependencies.len(), 8);
+
+        let found: Vec<_> = pyproject
+            .diagnostics()
+            .iter()
+            .map(|d| (d.location.line, d.text.as_str()))
+            .collect();
+        assert_eq!(found, [(17, "broken = \"^1.0 || ^2.0\"")]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_poetry_specifier() {
+        let cases = [
+            ("^1.2.3", ">=1.2.3,<2.0.0"),
+            ("^0.2.3", ">=0.2.3,<0.3.0"),
+            ("^0.0.3", ">=0.0.3,<0.0.4"),
+            ("^0", ">=0,<1"),
+            ("~1.2.3", ">=1.2.3,<1.3.0"),
+            ("~1", ">=1,<2"),
+            ("~=1.2", "~=1.2"),
+            ("1.2.*", "==1.2.*"),
+            ("=1.0", "==1.0"),
+            (">= 1.0, < 3", ">=1.0,<3"),
+            ("*", ""),
+        ];
+        for (constraint, specifier) in cases {
+            assert_eq!(poetry_specifier(constraint).unwrap(), specifier);
+        }
+        assert!(poetry_specifier("^1 || ^2").is_err());
+    }
+
+    #[cfg(feature = "python-parsing")]
+    #[test]
+    fn test_remove_dependencies() -> Result<(), AnalysisError> {
+        let content = r#"[project]
+name = "example"
+dependencies = [
+    # HTTP client
+    "requests >= 2.8.1",
+    "flask == 1.0.0",
+    "local @ file:///wheels/local-1.0-py3-none-any.whl",
+    "numpy",
+]
+
+[project.optional-dependencies]
+dev = ["requests"]
+"#;
+        let unused = vec![
+            "requests".to_string(),
+            "local".to_string(),
+            "NumPy".to_string(),
+        ];
+        let (new_content, removed) = remove_dependencies(content, &unused)?;
+
+        assert_eq!(
+            removed,
+            vec![
+                ("requests".to_string(), "requests >= 2.8.1".to_string()),
+                ("numpy".to_string(), "numpy".to_string()),
+            ]
+        );
+        assert_eq!(
+            new_content,
+            r#"[project]
+name = "example"
+dependencies = [
+    "flask == 1.0.0",
+    "local @ file:///wheels/local-1.0-py3-none-any.whl",
+]
+
+[project.optional-dependencies]
+dev = ["requests"]
+"#
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "python-parsing")]
+    #[test]
+    fn test_add_dependencies() -> Result<(), AnalysisError> {
+        let requirements = vec!["requests".to_string(), "pyyaml>=6.0.1".to_string()];
+
+        let content = r#"[project]
+name = "example"
+dependencies = [
+    "flask == 1.0.0",  # web
+]
+"#;
+        assert_eq!(
+            add_dependencies(content, &requirements)?,
+            r#"[project]
+name = "example"
+dependencies = [
+    "flask == 1.0.0",  # web
+    "requests",
+    "pyyaml>=6.0.1",
+]
+"#
+        );
+
+        let content = "[project]\nname = \"example\"\n";
+        assert_eq!(
+            add_dependencies(content, &requirements)?,
+            "[project]\nname = \"example\"\ndependencies = [\"requests\", \"pyyaml>=6.0.1\"]\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_declaration_findings() -> Result<(), AnalysisError> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("pyproject.toml");
+        fs::write(
+            &path,
+            r#"[project]
+name = "example"
+dependencies = [
+    "requests>=2.0",
+    "click",
+]
+
+[project.optional-dependencies]
+cli = ["Click"]
+docs = ["sphinx>=7", "requests >= 2.0"]
+lint = ["ruff==0.4.0"]
+dev = ["ruff==0.5.0", "sphinx>=7", "pytest"]
+all = ["sphinx>=7", "pytest", "ruff==0.5.0", "mypy"]
+"#,
+        )
+        .unwrap();
+
+        let findings = declaration_findings(&path, &[])?;
+        let found: Vec<(Rule, &str, usize)> = findings
+            .iter()
+            .map(|f| {
+                (
+                    f.rule,
+                    f.subject.as_str(),
+                    f.location.as_ref().unwrap().line,
+                )
+            })
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (Rule::DuplicateDependency, "click", 9),
+                (Rule::DuplicateDependency, "requests", 10),
+                (Rule::ConflictingExtraSpecifiers, "ruff", 11),
+                (Rule::ConflictingExtraSpecifiers, "ruff", 11),
+                (Rule::RedundantExtra, "dev", 12),
+            ]
+        );
+        assert!(findings[0].message.contains("remove it from the extra"));
+        assert!(findings[4].message.contains("example[dev]"));
+
+        // Alternatives aren't compared with each other
+        let exclusive = ["lint", "dev", "all"].map(str::to_string).to_vec();
+        let findings = declaration_findings(&path, &[exclusive])?;
+        let rules: Vec<Rule> = findings.iter().map(|f| f.rule).collect();
+        assert_eq!(
+            rules,
+            [Rule::DuplicateDependency, Rule::DuplicateDependency]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_unreachable_optional_dependencies() -> Result<(), AnalysisError> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("pyproject.toml");
+        fs::write(
+            &path,
+            r#"[tool.poetry]
+name = "example"
+
+[tool.poetry.dependencies]
+python = "^3.9"
+requests = "^2.31"
+psycopg = { version = "^3.1", optional = true }
+sqlalchemy = { version = "^2.0", optional = true }
+pandas = { version = "^2.0", optional = true }
+
+[tool.poetry.dependencies.boto3]
+version = "^1.34"
+optional = true
+
+[tool.poetry.extras]
+postgres = ["psycopg", "sqlalchemy"]
+sqlite = ["sqlalchemy"]
+"#,
+        )
+        .unwrap();
+
+        let findings = declaration_findings(&path, &[])?;
+        let found: Vec<(Rule, &str, usize)> = findings
+            .iter()
+            .map(|f| {
+                (
+                    f.rule,
+                    f.subject.as_str(),
+                    f.location.as_ref().unwrap().line,
+                )
+            })
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (Rule::UnreachableDependency, "pandas", 9),
+                (Rule::UnreachableDependency, "boto3", 11),
+            ]
+        );
+        assert!(findings[0].message.contains("[tool.poetry.extras]"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_entry_points() -> Result<(), AnalysisError> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("pyproject.toml");
+        fs::write(
+            &path,
+            r#"[project]
+name = "example"
+
+[project.scripts]
+example = "example.cli:main"
+fmt = "black:patched_main"
+
+[project.gui-scripts]
+viewer = "example.gui.app:run [gui]"
+
+[project.entry-points."pytest11"]
+example = "example.plugin"
+"#,
+        )
+        .unwrap();
+
+        let entry_points: Vec<(String, String, Option<String>, usize)> = entry_points(&path)?
+            .into_iter()
+            .map(|entry| {
+                (
+                    entry.group,
+                    entry.module,
+                    entry.attribute,
+                    entry.location.line,
+                )
+            })
+            .collect();
+        assert_eq!(
+            entry_points,
+            [
+                (
+                    "scripts".to_string(),
+                    "example.cli".to_string(),
+                    Some("main".to_string()),
+                    5
+                ),
+                (
+                    "scripts".to_string(),
+                    "black".to_string(),
+                    Some("patched_main".to_string()),
+                    6
+                ),
+                (
+                    "gui-scripts".to_string(),
+                    "example.gui.app".to_string(),
+                    Some("run".to_string()),
+                    9
+                ),
+                (
+                    "pytest11".to_string(),
+                    "example.plugin".to_string(),
+                    None,
+                    12
+                ),
+            ]
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "python-parsing")]
+    #[test]
+    fn test_package_layout() -> Result<(), AnalysisError> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("pyproject.toml");
+        let layout = |contents: &str| -> Result<Option<PackageLayout>, AnalysisError> {
+            fs::write(&path, contents).unwrap();
+            package_layout(&path)
+        };
+
+        assert!(
+            layout("[project]\nname = \"example\"\n\n[tool.setuptools]\nzip-safe = false\n")?
+                .is_none()
+        );
+
+        let setuptools = layout(
+            r#"[tool.setuptools]
+package-dir = {"" = "src", "example.vendored" = "third_party"}
+packages = ["example", "example.vendored.six"]
+py-modules = ["helpers"]
+"#,
+        )?
+        .unwrap();
+        let packages: Vec<(&str, PathBuf, usize)> = setuptools
+            .packages
+            .iter()
+            .map(|p| {
+                let path = p.path.as_ref().unwrap().strip_prefix(dir.path()).unwrap();
+                (p.name.as_str(), path.to_path_buf(), p.location.line)
+            })
+            .collect();
+        assert_eq!(
+            packages,
+            [
+                ("example", PathBuf::from("src/example"), 3),
+                ("example.vendored.six", PathBuf::from("third_party/six"), 3),
+                ("helpers", PathBuf::from("src/helpers.py"), 4),
+            ]
+        );
+        assert!(setuptools.lists_subpackages);
+        assert!(setuptools.source_roots.is_empty());
+
+        let find = layout(
+            r#"[tool.setuptools.packages.find]
+where = ["lib"]
+include = ["example*"]
+exclude = ["example.tests*"]
+"#,
+        )?
+        .unwrap();
+        assert_eq!(find.source_roots.len(), 1);
+        assert_eq!(find.source_roots[0].0, dir.path().join("lib"));
+        assert_eq!(find.source_roots[0].1.line, 2);
+        let patterns = find.find.unwrap();
+        assert_eq!(patterns.include, ["example*"]);
+        assert_eq!(patterns.exclude, ["example.tests*"]);
+        assert_eq!(patterns.location.line, 1);
+
+        let mixed = layout(
+            r#"[tool.hatch.build.targets.wheel]
+packages = ["src/example"]
+
+[tool.maturin]
+python-source = "python"
+module-name = "example._native"
+"#,
+        )?
+        .unwrap();
+        assert_eq!(mixed.packages[0].name, "example");
+        assert_eq!(
+            mixed.packages[0].path.as_deref(),
+            Some(dir.path().join("src/example").as_path())
+        );
+        assert_eq!(mixed.packages[1].name, "example._native");
+        assert!(mixed.packages[1].path.is_none());
+        assert_eq!(mixed.source_roots[0].0, dir.path().join("python"));
+        Ok(())
+    }
 }
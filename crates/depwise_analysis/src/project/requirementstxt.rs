@@ -1,35 +1,58 @@
+use std::collections::HashSet;
 use std::fs;
+use std::io::{self, BufRead, BufReader, Cursor};
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
-use crate::error::AnalysisError;
-use crate::project::{Dependency, PyPIRequirement};
+use serde::Serialize;
+
+#[cfg(feature = "backends-net")]
+use crate::env_backend::http;
+use crate::error::{AnalysisError, ParseDiagnostic};
+use crate::finding::Location;
+#[cfg(feature = "python-parsing")]
+use crate::name_map::normalize_name;
+use crate::project::{Dependency, ParseOptions, PyPIRequirement, is_windows_path};
+use pep508_rs::Pep508Error;
+#[cfg(feature = "python-parsing")]
+use pep508_rs::VersionOrUrl;
 
 enum RequirementLine {
     Dependency(Dependency),
-    RequirementFile(PathBuf),
+    /// A `-r` or `--requirement` include of a file path or URL
+    RequirementFile(String),
+    /// A `-c` or `--constraint` include of a constraints file
+    ConstraintsFile(String),
     Url(String),
     Path(PathBuf),
     Noop,
 }
 
 /// Parse a single line from a requirements.txt file
-fn parse_requirement_line(line: &str) -> Result<RequirementLine, AnalysisError> {
-    let trimmed = line.trim();
-
-    // trim off any trailing comments
-    let trimmed = trimmed.rsplit_once("#").unwrap_or((trimmed, "")).0.trim();
+fn parse_requirement_line(line: &str) -> Result<RequirementLine, Pep508Error> {
+    let trimmed = strip_comment(line).trim();
 
     // Skip empty lines and comments
     if trimmed.is_empty() || trimmed.starts_with('#') {
         return Ok(RequirementLine::Noop);
     }
 
-    // if the line starts with -r, it's a requirement file
-    if trimmed.starts_with("-r ") {
-        return Ok(RequirementLine::RequirementFile(PathBuf::from(
-            trimmed.split_whitespace().nth(1).unwrap(),
-        )));
+    // -r or --requirement includes a requirements file
+    if let Some(include) = option_value(trimmed, "-r", "--requirement") {
+        return Ok(RequirementLine::RequirementFile(include.to_string()));
+    }
+
+    // and -c or --constraint, a constraints file
+    if let Some(include) = option_value(trimmed, "-c", "--constraint") {
+        return Ok(RequirementLine::ConstraintsFile(include.to_string()));
+    }
+
+    // An editable install installs the same package as the path or URL would
+    if let Some(target) = option_value(trimmed, "-e", "--editable") {
+        return Ok(match target {
+            target if target.contains("://") => url_or_path(target),
+            target => RequirementLine::Path(local_path(target)),
+        });
     }
 
     // ignore other - and -- options
@@ -37,6 +60,9 @@ fn parse_requirement_line(line: &str) -> Result<RequirementLine, AnalysisError>
         return Ok(RequirementLine::Noop);
     }
 
+    // Per-requirement options like `--hash=sha256:...` follow the requirement
+    let trimmed = strip_options(trimmed);
+
     // Parse the requirement
     match PyPIRequirement::from_str(trimmed) {
         Ok(requirement) => Ok(RequirementLine::Dependency(Dependency::PyPI(requirement))),
@@ -44,16 +70,13 @@ fn parse_requirement_line(line: &str) -> Result<RequirementLine, AnalysisError>
             // If we can't parse the line as a PyPI requirement, check if it's a url or path
 
             // if the line starts with a protocol then it's a url
-            if trimmed.starts_with("http:")
-                || trimmed.starts_with("https:")
-                || trimmed.starts_with("ftp:")
-                || trimmed.starts_with("file:")
-            {
-                return Ok(RequirementLine::Url(trimmed.to_string()));
+            if is_url(trimmed) {
+                return Ok(url_or_path(trimmed));
             }
 
             // if the line looks like a path then it's a path
             if trimmed.starts_with("/")
+                || is_windows_path(trimmed)
                 || trimmed.starts_with(".")
                 || trimmed.ends_with(".whl")
                 || trimmed.ends_with(".tar.gz")
@@ -63,87 +86,566 @@ fn parse_requirement_line(line: &str) -> Result<RequirementLine, AnalysisError>
                 || trimmed.ends_with(".egg")
                 || trimmed.ends_with(".tar.xz")
             {
-                return Ok(RequirementLine::Path(PathBuf::from(trimmed)));
+                return Ok(RequirementLine::Path(local_path(trimmed)));
             }
 
-            Err(error.into())
+            Err(error)
         }
     }
 }
 
-/// Parse a requirements.txt file and return a list of dependencies
-pub(crate) fn parse(file_path: &Path) -> Result<Vec<Dependency>, AnalysisError> {
-    parse_dependencies_file_with_visited(&file_path, &mut Vec::new())
+/// A URL requirement, or the path of a `file:` URL on this machine
+fn url_or_path(url: &str) -> RequirementLine {
+    match file_url_path(url) {
+        Some(path) => RequirementLine::Path(path),
+        None => RequirementLine::Url(url.to_string()),
+    }
 }
 
-/// Helper function that tracks visited files to prevent infinite recursion
-fn parse_dependencies_file_with_visited(
-    file_path: &Path,
-    visited: &mut Vec<PathBuf>,
-) -> Result<Vec<Dependency>, AnalysisError> {
-    // Check if we've already visited this file to prevent infinite recursion
-    if visited.contains(&file_path.to_path_buf()) {
-        return Err(AnalysisError::DependencyParseError(format!(
-            "Circular dependency detected in requirements file: {}",
-            file_path.display()
-        )));
+/// The path of a local `file:` URL, like `/wheels` for `file:///wheels` or `C:/wheels` for
+/// `file:///C:/wheels`. URLs of other hosts, and those with a fragment like `#egg=name`
+/// naming their package, stay URLs.
+fn file_url_path(url: &str) -> Option<PathBuf> {
+    let rest = url.strip_prefix("file://")?;
+    let rest = rest.strip_prefix("localhost").unwrap_or(rest);
+    if !rest.starts_with('/') || rest.contains(['#', '?']) {
+        return None;
     }
+    // The drive of a Windows path follows the slash ending the host
+    let path = Some(&rest[1..])
+        .filter(|path| is_windows_path(path))
+        .unwrap_or(rest);
+    Some(local_path(path))
+}
 
-    // Add this file to the visited list
-    visited.push(file_path.to_path_buf());
+/// A path as written in a requirements file, with Windows separators replaced by `/` so its
+/// file name is found on any platform
+fn local_path(path: &str) -> PathBuf {
+    PathBuf::from(path.replace('\\', "/"))
+}
 
-    let content = fs::read_to_string(file_path).map_err(|e| {
-        AnalysisError::FileReadError(file_path.to_string_lossy().to_string(), e.to_string())
-    })?;
+/// The value of an option line, spelled with its `short` or `long` name, like `.` in `-e .`,
+/// `-e.`, `--editable .` or `--editable=.`
+fn option_value<'a>(line: &'a str, short: &str, long: &str) -> Option<&'a str> {
+    let value = line
+        .strip_prefix(long)
+        .or_else(|| line.strip_prefix(short))?;
+    // `-e.` is valid, but `--editablex` isn't the option
+    let value = match value.strip_prefix('=') {
+        Some(value) => value,
+        None if line.starts_with("--") && !value.starts_with(char::is_whitespace) => {
+            return None;
+        }
+        None => value,
+    };
+    Some(strip_options(value.trim())).filter(|value| !value.is_empty())
+}
 
-    parse_dependencies_with_visited(
-        &content,
-        file_path.parent().unwrap_or_else(|| Path::new(".")),
-        visited,
-    )
+/// Remove a comment from a line. As in pip, a comment starts with a `#` at the start of the
+/// line or after whitespace, so URL fragments like `#egg=name` are kept.
+fn strip_comment(line: &str) -> &str {
+    let mut previous = ' ';
+    for (i, c) in line.char_indices() {
+        if c == '#' && previous.is_whitespace() {
+            return &line[..i];
+        }
+        previous = c;
+    }
+    line
 }
 
-/// Parse requirements.txt content and return a list of dependencies
-pub(crate) fn parse_dependencies(content: &str) -> Result<Vec<Dependency>, AnalysisError> {
-    parse_dependencies_with_visited(content, Path::new("."), &mut Vec::new())
+/// Remove the options that follow a requirement, e.g. `--hash=sha256:...`
+fn strip_options(requirement: &str) -> &str {
+    requirement
+        .match_indices("--")
+        .find(|(i, _)| requirement[..*i].ends_with(char::is_whitespace))
+        .map_or(requirement, |(i, _)| requirement[..i].trim_end())
 }
 
-/// Helper function that tracks visited files to prevent infinite recursion
-fn parse_dependencies_with_visited(
+/// Join lines ending in a backslash with the lines after them, as pip does, along with the
+/// number of the line each starts on. Comment lines are never continued. Lines are read as
+/// they're needed, so a file is never held in memory as a whole.
+fn logical_lines(
+    lines: impl Iterator<Item = io::Result<String>>,
+) -> impl Iterator<Item = io::Result<(usize, String)>> {
+    let mut lines = lines.enumerate();
+    std::iter::from_fn(move || {
+        let mut current = String::new();
+        let mut start = None;
+        for (i, line) in lines.by_ref() {
+            let line = match line {
+                Ok(line) => line,
+                Err(e) => return Some(Err(e)),
+            };
+            let is_comment = start.is_none() && line.trim_start().starts_with('#');
+            let start = *start.get_or_insert(i + 1);
+            match line.trim_end().strip_suffix('\\') {
+                Some(continued) if !is_comment => {
+                    current.push_str(continued);
+                    current.push(' ');
+                }
+                _ => {
+                    current.push_str(&line);
+                    return Some(Ok((start, current)));
+                }
+            }
+        }
+        start.map(|start| Ok((start, current)))
+    })
+}
+
+fn is_url(text: &str) -> bool {
+    ["http:", "https:", "ftp:", "file:"]
+        .iter()
+        .any(|scheme| text.starts_with(scheme))
+}
+
+/// Where a requirements file is read from
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum RequirementsFile {
+    Path(PathBuf),
+    Url(String),
+}
+
+impl RequirementsFile {
+    /// Resolve the argument of an `-r` in this file. Relative paths are relative to the
+    /// directory of the file, or to the URL it was fetched from.
+    fn join(&self, include: &str) -> Self {
+        if is_url(include) {
+            return Self::Url(include.to_string());
+        }
+        match self {
+            Self::Path(path) => Self::Path(path.parent().unwrap_or(Path::new(".")).join(include)),
+            Self::Url(url) => {
+                let base = url.rsplit_once('/').map_or(url.as_str(), |(base, _)| base);
+                Self::Url(format!("{}/{}", base, include.trim_start_matches("./")))
+            }
+        }
+    }
+
+    /// Open the file for reading line by line. A local file is read as it's parsed, while a
+    /// remote one is downloaded first.
+    fn open(&self, options: &ParseOptions) -> Result<Box<dyn BufRead>, AnalysisError> {
+        match self {
+            Self::Path(path) => match fs::File::open(path) {
+                Ok(file) => Ok(Box::new(BufReader::new(file))),
+                Err(e) => Err(self.read_error(e)),
+            },
+            Self::Url(url) if options.offline => Err(AnalysisError::FetchError(
+                url.clone(),
+                "remote requirements files can't be fetched while offline".to_string(),
+            )),
+            #[cfg(feature = "backends-net")]
+            Self::Url(url) => Ok(Box::new(Cursor::new(http::get(
+                url,
+                options.timeout,
+                &options.cancel,
+            )?))),
+            // What a requirements file includes is only known once it's read
+            #[cfg(not(feature = "backends-net"))]
+            Self::Url(url) => Err(AnalysisError::FeatureDisabled(
+                format!("Downloading {}", url),
+                "backends-net",
+            )),
+        }
+    }
+
+    fn read_error(&self, error: io::Error) -> AnalysisError {
+        AnalysisError::FileReadError(self.to_string(), error.to_string())
+    }
+}
+
+impl RequirementsFile {
+    /// The directory relative paths in the file are resolved against. A fetched file has
+    /// none on disk, so its paths are resolved against the working directory.
+    fn directory(&self) -> PathBuf {
+        match self {
+            Self::Path(path) => path.parent().unwrap_or(Path::new(".")).to_path_buf(),
+            Self::Url(_) => PathBuf::from("."),
+        }
+    }
+
+    /// The path diagnostics about the file are reported at
+    fn diagnostic_path(&self) -> PathBuf {
+        match self {
+            Self::Path(path) => path.clone(),
+            Self::Url(url) => PathBuf::from(url),
+        }
+    }
+}
+
+impl std::fmt::Display for RequirementsFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Path(path) => write!(f, "{}", path.display()),
+            Self::Url(url) => write!(f, "{}", url),
+        }
+    }
+}
+
+/// Where a requirement is declared, and the chain of `-r` includes its file was reached
+/// through from the requirements file that was parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Provenance {
+    /// The line declaring the requirement
+    pub declared: Location,
+    /// The `-r` lines followed to get to its file, starting in the parsed file
+    pub included_via: Vec<Location>,
+}
+
+impl Provenance {
+    /// Describe it like `requirements/base.txt:12 (included via requirements.txt:3 →
+    /// requirements/prod.txt:1)`, with paths relative to `base` when they're under it
+    pub fn describe(&self, base: &Path) -> String {
+        let relative = |location: &Location| {
+            let path = location.path.strip_prefix(base).unwrap_or(&location.path);
+            format!("{}:{}", path.display(), location.line)
+        };
+        if self.included_via.is_empty() {
+            return relative(&self.declared);
+        }
+        let via: Vec<String> = self.included_via.iter().map(relative).collect();
+        format!(
+            "{} (included via {})",
+            relative(&self.declared),
+            via.join(" → ")
+        )
+    }
+}
+
+/// A requirements file and the files it includes with `-r` and `-c`, in the order they're
+/// included. A file included again after it was read, as in a diamond, is listed each time
+/// but its own includes only the first time.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[non_exhaustive]
+pub struct IncludeTree {
+    /// The path or URL of the file
+    pub file: String,
+    /// The line of the including file the file is included on, `None` for the parsed file
+    pub line: Option<usize>,
+    /// Whether it's a constraints file, included with `-c`, which declares no dependencies
+    /// and isn't read
+    pub constraints: bool,
+    pub includes: Vec<IncludeTree>,
+}
+
+impl IncludeTree {
+    fn new(file: &RequirementsFile, line: Option<usize>, constraints: bool) -> Self {
+        IncludeTree {
+            file: file.to_string(),
+            line,
+            constraints,
+            includes: Vec::new(),
+        }
+    }
+}
+
+/// The dependencies of a requirements file and the files it includes
+#[derive(Debug, Clone)]
+pub(crate) struct Requirements {
+    pub(crate) dependencies: Vec<Dependency>,
+    /// Where each of `dependencies` is declared, in the same order
+    pub(crate) provenance: Vec<Provenance>,
+    pub(crate) includes: IncludeTree,
+}
+
+/// The requirements files seen while following `-r` includes
+#[derive(Debug)]
+struct Visited {
+    /// Files still being parsed, each included by the one before it; including one of them
+    /// again is a cycle
+    in_progress: Vec<RequirementsFile>,
+    /// The `-r` lines that included each of `in_progress` after the first
+    included_via: Vec<Location>,
+    /// Files that were fully parsed; including one again (a diamond) adds nothing new
+    done: HashSet<RequirementsFile>,
+    /// Whether including a URL is an error instead of fetching it, and how many
+    /// requirements the files may declare in all
+    options: ParseOptions,
+    /// The file the includes were followed from, which the limit is reported for
+    root: PathBuf,
+    /// The requirements that couldn't be parsed and were skipped
+    diagnostics: Vec<ParseDiagnostic>,
+}
+
+impl Visited {
+    fn new(root: &Path, options: ParseOptions) -> Self {
+        Visited {
+            in_progress: Vec::new(),
+            included_via: Vec::new(),
+            done: HashSet::new(),
+            options,
+            root: root.to_path_buf(),
+            diagnostics: Vec::new(),
+        }
+    }
+
+    /// The includes going around from `key` back to `file`, like `a.txt:2 → b.txt:1 → a.txt`
+    fn cycle(&self, key: &RequirementsFile, file: &RequirementsFile) -> String {
+        let start = self
+            .in_progress
+            .iter()
+            .position(|seen| seen == key)
+            .unwrap_or(0);
+        let mut steps: Vec<String> = self.included_via[start.min(self.included_via.len())..]
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        steps.push(file.to_string());
+        steps.join(" → ")
+    }
+}
+
+/// Parse a requirements.txt file and return its dependencies, where each is declared, and
+/// the tree of files it includes.
+///
+/// Files included with `-r` are parsed as well, including remote ones given by URL unless
+/// `options.offline` is set. Requirements that can't be parsed are skipped and added to
+/// `diagnostics`. Declaring more than `options.max_requirements` requirements in all is an
+/// error.
+pub(crate) fn parse(
+    file_path: &Path,
+    options: &ParseOptions,
+    diagnostics: &mut Vec<ParseDiagnostic>,
+) -> Result<Requirements, AnalysisError> {
+    let mut visited = Visited::new(file_path, options.clone());
+    let mut parsed = Vec::new();
+    let includes = parse_dependencies_file_with_visited(
+        &RequirementsFile::Path(file_path.to_path_buf()),
+        None,
+        &mut visited,
+        &mut parsed,
+    )?;
+    diagnostics.append(&mut visited.diagnostics);
+    let (dependencies, provenance) = parsed.into_iter().unzip();
+    Ok(Requirements {
+        dependencies,
+        provenance,
+        includes,
+    })
+}
+
+/// Helper function that tracks visited files to prevent infinite recursion, adding the
+/// file's requirements to `dependencies`. Returns the tree of the files it includes.
+fn parse_dependencies_file_with_visited(
+    file: &RequirementsFile,
+    line: Option<usize>,
+    visited: &mut Visited,
+    dependencies: &mut Vec<(Dependency, Provenance)>,
+) -> Result<IncludeTree, AnalysisError> {
+    // Identify files by their canonical path so `a/../b.txt` and `b.txt` are the same file
+    let key = match file {
+        RequirementsFile::Path(path) => {
+            RequirementsFile::Path(fs::canonicalize(path).unwrap_or_else(|_| path.clone()))
+        }
+        RequirementsFile::Url(_) => file.clone(),
+    };
+    let mut tree = IncludeTree::new(file, line, false);
+    if visited.in_progress.contains(&key) {
+        return Err(AnalysisError::DependencyParseError(format!(
+            "Circular dependency detected in requirements files: {}",
+            visited.cycle(&key, file)
+        )));
+    }
+    if visited.done.contains(&key) {
+        return Ok(tree);
+    }
+
+    let reader = file.open(&visited.options)?;
+
+    visited.in_progress.push(key.clone());
+    parse_dependencies_with_visited(reader, file, visited, dependencies, &mut tree)?;
+    visited.in_progress.pop();
+    visited.done.insert(key);
+    Ok(tree)
+}
+
+/// Parse requirements given inline in another file, such as the `pip:` section of a conda
+/// environment. Files included with `-r` are found relative to `file_path`, and including
+/// `file_path` itself is a cycle. They may declare up to `options.max_requirements`
+/// requirements in all.
+pub(crate) fn parse_lines(
     content: &str,
-    base_dir: &Path,
-    visited: &mut Vec<PathBuf>,
+    file_path: &Path,
+    options: &ParseOptions,
+    diagnostics: &mut Vec<ParseDiagnostic>,
 ) -> Result<Vec<Dependency>, AnalysisError> {
+    let file = RequirementsFile::Path(file_path.to_path_buf());
+    let mut visited = Visited::new(file_path, options.clone());
+    // The lines' own file is being parsed, so including it from them is a cycle
+    visited.in_progress.push(RequirementsFile::Path(
+        fs::canonicalize(file_path).unwrap_or_else(|_| file_path.to_path_buf()),
+    ));
     let mut dependencies = Vec::new();
+    parse_dependencies_with_visited(
+        Cursor::new(content.as_bytes()),
+        &file,
+        &mut visited,
+        &mut dependencies,
+        &mut IncludeTree::new(&file, None, false),
+    )?;
+    diagnostics.append(&mut visited.diagnostics);
+    Ok(dependencies
+        .into_iter()
+        .map(|(dependency, _)| dependency)
+        .collect())
+}
 
-    for line in content.lines() {
-        match parse_requirement_line(line)? {
-            RequirementLine::Dependency(dep) => dependencies.push(dep),
-            RequirementLine::RequirementFile(rel_path) => {
-                let abs_path = base_dir.join(&rel_path);
-                let deps = parse_dependencies_file_with_visited(&abs_path, visited)?;
-                dependencies.extend(deps);
+/// Helper function that tracks visited files to prevent infinite recursion. The lines are
+/// read one at a time and each requirement, including those of included files, is added to
+/// `dependencies` as it's parsed, failing once there are more than the limit. The files
+/// `file` includes are added to `tree`.
+fn parse_dependencies_with_visited(
+    reader: impl BufRead,
+    file: &RequirementsFile,
+    visited: &mut Visited,
+    dependencies: &mut Vec<(Dependency, Provenance)>,
+    tree: &mut IncludeTree,
+) -> Result<(), AnalysisError> {
+    for line in logical_lines(reader.lines()) {
+        let (line_number, line) = line.map_err(|e| file.read_error(e))?;
+        let parsed = match parse_requirement_line(&line) {
+            Ok(parsed) => parsed,
+            Err(error) => {
+                visited.diagnostics.push(ParseDiagnostic::new(
+                    &file.diagnostic_path(),
+                    line_number,
+                    strip_options(strip_comment(&line).trim()),
+                    error.message.to_string(),
+                ));
+                continue;
+            }
+        };
+        let dependency = match parsed {
+            RequirementLine::Dependency(dep) => dep,
+            RequirementLine::RequirementFile(include) => {
+                visited
+                    .included_via
+                    .push(Location::new(file.diagnostic_path(), line_number));
+                let included = parse_dependencies_file_with_visited(
+                    &file.join(&include),
+                    Some(line_number),
+                    visited,
+                    dependencies,
+                );
+                visited.included_via.pop();
+                tree.includes.push(included?);
+                continue;
             }
-            RequirementLine::Url(url) => {
-                dependencies.push(Dependency::PackageUrl(url));
+            RequirementLine::ConstraintsFile(include) => {
+                tree.includes.push(IncludeTree::new(
+                    &file.join(&include),
+                    Some(line_number),
+                    true,
+                ));
+                continue;
             }
-            RequirementLine::Path(path) => {
-                dependencies.push(Dependency::PackagePath(path));
+            RequirementLine::Url(url) => Dependency::package_url(&url),
+            RequirementLine::Path(path) => Dependency::package_path(&path, &file.directory()),
+            RequirementLine::Noop => continue,
+        };
+        if let Some(max) = visited.options.max_requirements
+            && dependencies.len() >= max
+        {
+            return Err(AnalysisError::TooManyRequirements(
+                visited.root.display().to_string(),
+                max,
+            ));
+        }
+        let provenance = Provenance {
+            declared: Location::new(file.diagnostic_path(), line_number),
+            included_via: visited.included_via.clone(),
+        };
+        dependencies.push((dependency, provenance));
+    }
+
+    Ok(())
+}
+
+/// Remove the lines declaring any of the given packages from requirements.txt content.
+///
+/// Comments, includes, options, and the order of the remaining lines are preserved. Lines
+/// that can't be confidently edited (URL and path requirements, or requirements spanning
+/// continuation lines) are left alone. Returns the new content and the normalized name and
+/// text of each removed line.
+#[cfg(feature = "python-parsing")]
+pub(crate) fn remove_dependencies(
+    content: &str,
+    names: &[String],
+) -> (String, Vec<(String, String)>) {
+    let names: Vec<String> = names.iter().map(|name| normalize_name(name)).collect();
+    let mut new_content = String::with_capacity(content.len());
+    let mut removed = Vec::new();
+    let mut in_continuation = false;
+
+    for line in content.split_inclusive('\n') {
+        let is_continued = line.trim_end().ends_with('\\');
+        let can_edit = !in_continuation && !is_continued;
+        in_continuation = is_continued;
+
+        if can_edit
+            && let Ok(RequirementLine::Dependency(Dependency::PyPI(requirement))) =
+                parse_requirement_line(line)
+            && !matches!(requirement.version_or_url, Some(VersionOrUrl::Url(_)))
+        {
+            let name = normalize_name(requirement.name.as_ref());
+            if names.contains(&name) {
+                removed.push((name, line.trim().to_string()));
+                continue;
             }
-            RequirementLine::Noop => {}
         }
+        new_content.push_str(line);
     }
 
-    Ok(dependencies)
+    (new_content, removed)
+}
+
+/// Append requirements to the end of a requirements file, one per line
+#[cfg(feature = "python-parsing")]
+pub(crate) fn add_dependencies(content: &str, requirements: &[String]) -> String {
+    let mut new_content = content.to_string();
+    if !new_content.is_empty() && !new_content.ends_with('\n') {
+        new_content.push('\n');
+    }
+    for requirement in requirements {
+        new_content.push_str(requirement);
+        new_content.push('\n');
+    }
+    new_content
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use pep508_rs::VersionOrUrl;
     use std::fs::File;
     use std::io::Write;
     use tempfile::tempdir;
 
+    /// Parse requirements.txt content and return a list of dependencies. Any requirement that
+    /// can't be parsed is an error.
+    fn parse_dependencies(content: &str) -> Result<Vec<Dependency>, AnalysisError> {
+        let path = PathBuf::from("./requirements.txt");
+        let file = RequirementsFile::Path(path.clone());
+        let mut visited = Visited::new(&path, ParseOptions::default());
+        let mut dependencies = Vec::new();
+        parse_dependencies_with_visited(
+            Cursor::new(content.as_bytes()),
+            &file,
+            &mut visited,
+            &mut dependencies,
+            &mut IncludeTree::new(&file, None, false),
+        )?;
+        if !visited.diagnostics.is_empty() {
+            return Err(AnalysisError::InvalidDeclarations(visited.diagnostics));
+        }
+        Ok(dependencies
+            .into_iter()
+            .map(|(dependency, _)| dependency)
+            .collect())
+    }
+
     #[test]
     fn test_parse_simple_requirements() -> Result<(), AnalysisError> {
         let dir = tempdir().unwrap();
@@ -160,7 +662,8 @@ mod tests {
         let mut other_file = File::create(&other_file_path).unwrap();
         writeln!(other_file, "torch==2.6.0").unwrap();
 
-        let deps = parse(&file_path)?;
+        let deps =
+            parse(&file_path, &ParseOptions::new(false, None), &mut Vec::new())?.dependencies;
         assert_eq!(deps.len(), 4);
 
         // Test that we can parse the content directly
@@ -171,6 +674,343 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_parse_diamond_includes() -> Result<(), AnalysisError> {
+        let dir = tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("extra")).unwrap();
+        let file_path = dir.path().join("requirements.txt");
+        std::fs::write(&file_path, "-r web.txt\n-r extra/cli.txt\n").unwrap();
+        std::fs::write(dir.path().join("web.txt"), "flask\n-r base.txt\n").unwrap();
+        std::fs::write(dir.path().join("extra/cli.txt"), "click\n-r ../base.txt\n").unwrap();
+        std::fs::write(dir.path().join("base.txt"), "requests\n").unwrap();
+
+        let deps =
+            parse(&file_path, &ParseOptions::new(false, None), &mut Vec::new())?.dependencies;
+        let names: Vec<_> = deps.iter().filter_map(|dep| dep.name()).collect();
+        assert_eq!(names, ["flask", "requests", "click"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_collects_invalid_requirements() -> Result<(), AnalysisError> {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("requirements.txt");
+        std::fs::write(
+            &file_path,
+            "requests>=2\nflask>=>2  # typo\n-r base.txt\nnumpy \\\n    ==1.*.2\nrich\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("base.txt"), "click\n@@@\n").unwrap();
+
+        let mut diagnostics = Vec::new();
+        let deps = parse(
+            &file_path,
+            &ParseOptions::new(false, None),
+            &mut diagnostics,
+        )?
+        .dependencies;
+        let names: Vec<_> = deps.iter().filter_map(|dep| dep.name()).collect();
+        assert_eq!(names, ["requests", "click", "rich"]);
+
+        let found: Vec<_> = diagnostics
+            .iter()
+            .map(|d| {
+                (
+                    d.location.path.file_name().unwrap().to_str().unwrap(),
+                    d.location.line,
+                    d.text.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("requirements.txt", 2, "flask>=>2"),
+                ("base.txt", 2, "@@@"),
+                ("requirements.txt", 4, "numpy      ==1.*.2"),
+            ]
+        );
+
+        assert!(matches!(
+            parse_dependencies("flask>=>2\n"),
+            Err(AnalysisError::InvalidDeclarations(d)) if d.len() == 1
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_include_cycle() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("requirements.txt");
+        std::fs::write(&file_path, "flask\n-r dev.txt\n").unwrap();
+        std::fs::write(dir.path().join("dev.txt"), "pytest\n-r requirements.txt\n").unwrap();
+
+        let error =
+            parse(&file_path, &ParseOptions::new(false, None), &mut Vec::new()).unwrap_err();
+        assert!(matches!(error, AnalysisError::DependencyParseError(_)));
+        let dev = dir.path().join("dev.txt");
+        assert!(error.to_string().ends_with(&format!(
+            "Circular dependency detected in requirements files: {}:2 → {}:2 → {}",
+            file_path.display(),
+            dev.display(),
+            dev.parent().unwrap().join("requirements.txt").display()
+        )));
+    }
+
+    #[test]
+    fn test_parse_include_tree() -> Result<(), AnalysisError> {
+        let dir = tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("requirements")).unwrap();
+        let file_path = dir.path().join("requirements.txt");
+        std::fs::write(
+            &file_path,
+            "rich\n-c constraints.txt\n-r requirements/prod.txt\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("requirements/prod.txt"), "-r base.txt\n").unwrap();
+        std::fs::write(
+            dir.path().join("requirements/base.txt"),
+            "# Shared\nflask\n",
+        )
+        .unwrap();
+
+        let requirements = parse(&file_path, &ParseOptions::new(false, None), &mut Vec::new())?;
+        let provenance: Vec<String> = requirements
+            .provenance
+            .iter()
+            .map(|provenance| provenance.describe(dir.path()))
+            .collect();
+        assert_eq!(
+            provenance,
+            [
+                "requirements.txt:1",
+                "requirements/base.txt:2 (included via requirements.txt:3 → requirements/prod.txt:1)",
+            ]
+        );
+
+        let tree = &requirements.includes;
+        assert_eq!(tree.line, None);
+        let includes: Vec<(bool, Option<usize>, usize)> = tree
+            .includes
+            .iter()
+            .map(|include| (include.constraints, include.line, include.includes.len()))
+            .collect();
+        assert_eq!(includes, [(true, Some(2), 0), (false, Some(3), 1)]);
+        assert!(tree.includes[1].includes[0].file.ends_with("base.txt"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_include_options() -> Result<(), AnalysisError> {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("requirements.txt");
+        std::fs::write(
+            &file_path,
+            "-r web.txt\n--requirement cli.txt\n--requirement=base.txt\n-rtest.txt\n\
+             -c pins.txt\n--constraint web-pins.txt\n--constraint=cli-pins.txt\n-ctest-pins.txt\n",
+        )
+        .unwrap();
+        for (file, requirement) in [
+            ("web.txt", "flask"),
+            ("cli.txt", "click"),
+            ("base.txt", "requests"),
+            ("test.txt", "pytest"),
+        ] {
+            std::fs::write(dir.path().join(file), format!("{}\n", requirement)).unwrap();
+        }
+
+        let requirements = parse(&file_path, &ParseOptions::new(false, None), &mut Vec::new())?;
+        let names: Vec<_> = requirements
+            .dependencies
+            .iter()
+            .filter_map(|dep| dep.name())
+            .collect();
+        assert_eq!(names, ["flask", "click", "requests", "pytest"]);
+        let includes: Vec<(String, bool)> = requirements
+            .includes
+            .includes
+            .iter()
+            .map(|include| {
+                let file = Path::new(&include.file).file_name().unwrap_or_default();
+                (file.to_string_lossy().to_string(), include.constraints)
+            })
+            .collect();
+        assert_eq!(
+            includes,
+            [
+                ("web.txt".to_string(), false),
+                ("cli.txt".to_string(), false),
+                ("base.txt".to_string(), false),
+                ("test.txt".to_string(), false),
+                ("pins.txt".to_string(), true),
+                ("web-pins.txt".to_string(), true),
+                ("cli-pins.txt".to_string(), true),
+                ("test-pins.txt".to_string(), true),
+            ]
+        );
+
+        // Only the option's own names are includes
+        for line in ["--requirements base.txt", "--constraints pins.txt"] {
+            assert!(
+                matches!(parse_requirement_line(line), Ok(RequirementLine::Noop)),
+                "{}",
+                line
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_requirements_limit() -> Result<(), AnalysisError> {
+        // 10 files including the same 10 files each, of 20 requirements
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("requirements.txt");
+        let includes: String = (0..10).map(|i| format!("-r group{}.txt\n", i)).collect();
+        std::fs::write(&file_path, &includes).unwrap();
+        for i in 0..10 {
+            let leaves: String = (0..10).map(|j| format!("-r leaf{}.txt\n", j)).collect();
+            std::fs::write(dir.path().join(format!("group{}.txt", i)), leaves).unwrap();
+            let requirements: String = (0..20).map(|j| format!("pkg-{}-{}\n", i, j)).collect();
+            std::fs::write(dir.path().join(format!("leaf{}.txt", i)), requirements).unwrap();
+        }
+
+        let deps =
+            parse(&file_path, &ParseOptions::new(false, None), &mut Vec::new())?.dependencies;
+        assert_eq!(deps.len(), 200);
+        assert_eq!(
+            parse(
+                &file_path,
+                &ParseOptions::new(false, Some(200)),
+                &mut Vec::new()
+            )?
+            .dependencies
+            .len(),
+            200
+        );
+
+        let error = parse(
+            &file_path,
+            &ParseOptions::new(false, Some(150)),
+            &mut Vec::new(),
+        )
+        .unwrap_err();
+        assert!(matches!(error, AnalysisError::TooManyRequirements(_, 150)));
+        assert!(error.to_string().contains("more than 150 requirements"));
+        Ok(())
+    }
+
+    /// Serve files over HTTP on a local port for as long as the test runs, returning the
+    /// base URL
+    #[cfg(feature = "backends-net")]
+    fn serve(files: &'static [(&'static str, &'static str)]) -> String {
+        use std::io::{BufRead, BufReader};
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                let mut request = String::new();
+                BufReader::new(&stream).read_line(&mut request).unwrap();
+                let path = request.split_whitespace().nth(1).unwrap_or("/");
+                let response = match files.iter().find(|(name, _)| path == format!("/{}", name)) {
+                    Some((_, body)) => format!(
+                        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        body.len(),
+                        body
+                    ),
+                    None => {
+                        "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                            .to_string()
+                    }
+                };
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+        format!("http://{}", address)
+    }
+
+    #[test]
+    #[cfg(feature = "backends-net")]
+    fn test_parse_remote_includes() -> Result<(), AnalysisError> {
+        let url = serve(&[
+            ("shared/base.txt", "requests\n-r extra.txt\n"),
+            ("shared/extra.txt", "click\n"),
+            ("shared/a.txt", "flask\n-r b.txt\n"),
+            ("shared/b.txt", "-r a.txt\n"),
+        ]);
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("requirements.txt");
+
+        std::fs::write(&file_path, format!("flask\n-r {}/shared/base.txt\n", url)).unwrap();
+        let deps =
+            parse(&file_path, &ParseOptions::new(false, None), &mut Vec::new())?.dependencies;
+        let names: Vec<_> = deps.iter().filter_map(|dep| dep.name()).collect();
+        assert_eq!(names, ["flask", "requests", "click"]);
+
+        // As with pip, the include can be spelled --requirement=URL
+        std::fs::write(
+            &file_path,
+            format!("flask\n--requirement={}/shared/base.txt\n", url),
+        )
+        .unwrap();
+        let deps =
+            parse(&file_path, &ParseOptions::new(false, None), &mut Vec::new())?.dependencies;
+        let names: Vec<_> = deps.iter().filter_map(|dep| dep.name()).collect();
+        assert_eq!(names, ["flask", "requests", "click"]);
+
+        // Offline, the include is an error that names the URL rather than a missing file
+        let error = parse(&file_path, &ParseOptions::new(true, None), &mut Vec::new()).unwrap_err();
+        assert!(matches!(error, AnalysisError::FetchError(ref u, _) if u.ends_with("base.txt")));
+
+        std::fs::write(&file_path, format!("-r {}/shared/a.txt\n", url)).unwrap();
+        let error =
+            parse(&file_path, &ParseOptions::new(false, None), &mut Vec::new()).unwrap_err();
+        assert!(error.to_string().contains("Circular dependency"));
+
+        std::fs::write(&file_path, format!("-r {}/shared/missing.txt\n", url)).unwrap();
+        let error =
+            parse(&file_path, &ParseOptions::new(false, None), &mut Vec::new()).unwrap_err();
+        assert!(matches!(error, AnalysisError::FetchError(_, _)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_hashed_lock_file() -> Result<(), AnalysisError> {
+        // The layout rules_python and pip-compile use for lock files
+        let content = r#"#
+# This file is autogenerated by pip-compile with Python 3.11
+#
+certifi==2024.2.2 \
+    --hash=sha256:0569859f95fce0b8 \
+    --hash=sha256:dc383c07b76109f3
+    # via requests
+numpy==1.26.4 ; python_version >= "3.9" \
+    --hash=sha256:03a8c78d01d9781b
+    # via
+    #   -r requirements.in
+    #   pandas
+requests==2.31.0 --hash=sha256:58cd2187c01e7046  # pinned
+pandas==2.2.1 \
+    --hash=sha256:8df8612b
+wheel @ https://example.com/wheel-0.43.0-py3-none-any.whl#sha256=55c5
+"#;
+        let deps = parse_dependencies(content)?;
+        let names: Vec<_> = deps.iter().filter_map(|dep| dep.name()).collect();
+        assert_eq!(names, ["certifi", "numpy", "requests", "pandas", "wheel"]);
+        match &deps[4] {
+            Dependency::PyPI(requirement) => assert!(matches!(
+                &requirement.version_or_url,
+                Some(VersionOrUrl::Url(url)) if url.to_string().ends_with("#sha256=55c5")
+            )),
+            _ => panic!("Expected a PyPI dependency"),
+        }
+
+        Ok(())
+    }
+
     #[test]
     fn test_parse_complex_requirements() -> Result<(), AnalysisError> {
         let content = r#"
@@ -186,4 +1026,146 @@ wxPathon @ http://wxpython.org/Phoenix/snapshot-builds/wxPython_Phoenix-3.0.3.de
 
         Ok(())
     }
+
+    #[test]
+    fn test_option_value() {
+        for (line, target) in [
+            ("-e .", Some(".")),
+            ("-e.", Some(".")),
+            ("--editable ./subpkg", Some("./subpkg")),
+            (
+                "--editable=../lib --config-settings editable_mode=compat",
+                Some("../lib"),
+            ),
+            (
+                "-e git+https://github.com/org/repo#egg=repo",
+                Some("git+https://github.com/org/repo#egg=repo"),
+            ),
+            ("-e", None),
+            ("--editablex .", None),
+            ("--extra-index-url https://example.com", None),
+        ] {
+            assert_eq!(option_value(line, "-e", "--editable"), target, "{}", line);
+        }
+        assert!(matches!(
+            parse_requirement_line("-e git+https://github.com/org/repo#egg=repo"),
+            Ok(RequirementLine::Url(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_windows_paths() -> Result<(), AnalysisError> {
+        let content = r#"C:\wheels\pkg-1.0-py3-none-any.whl
+d:/dists/other-2.0.tar.gz
+\\server\share\shared-3.0-py3-none-any.whl
+.\vendor\local-1.0-py3-none-any.whl
+-e C:\src\editable
+file:///C:/wheels/fromurl-1.0-py3-none-any.whl
+file://localhost/opt/wheels/posix-1.0-py3-none-any.whl
+file:///C:/src/named#egg=named
+"#;
+        let deps = parse_dependencies(content)?;
+        let paths: Vec<&Path> = deps
+            .iter()
+            .filter_map(|dep| match dep {
+                Dependency::PackagePath { path, .. } => Some(path.as_path()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                Path::new("C:/wheels/pkg-1.0-py3-none-any.whl"),
+                Path::new("d:/dists/other-2.0.tar.gz"),
+                Path::new("//server/share/shared-3.0-py3-none-any.whl"),
+                Path::new("./vendor/local-1.0-py3-none-any.whl"),
+                Path::new("C:/src/editable"),
+                Path::new("C:/wheels/fromurl-1.0-py3-none-any.whl"),
+                Path::new("/opt/wheels/posix-1.0-py3-none-any.whl"),
+            ]
+        );
+        let names: Vec<Option<&str>> = deps.iter().map(Dependency::name).collect();
+        assert_eq!(
+            names,
+            [
+                Some("pkg"),
+                Some("other"),
+                Some("shared"),
+                Some("local"),
+                None,
+                Some("fromurl"),
+                Some("posix"),
+                Some("named"),
+            ]
+        );
+        assert!(matches!(&deps[7], Dependency::PackageUrl { .. }));
+
+        // The same file, spelled with the other separator or case
+        let other = Dependency::package_path(
+            Path::new(r"c:\WHEELS\pkg-1.0-py3-none-any.whl"),
+            Path::new("."),
+        );
+        assert!(deps[0].same_package(&other));
+        Ok(())
+    }
+
+    #[cfg(feature = "python-parsing")]
+    #[test]
+    fn test_remove_dependencies() {
+        let content = r#"# Runtime dependencies
+-r base.txt
+requests==2.28.1    # HTTP
+flask>=2.0.0
+Django_Rest_Framework
+./wheels/local-1.0-py3-none-any.whl
+wxPython @ http://wxpython.org/wxPython-4.0-cp34-none-win_amd64.whl
+numpy==1.26.0 \
+    --hash=sha256:abc
+pandas
+"#;
+        let unused = vec![
+            "requests".to_string(),
+            "django-rest-framework".to_string(),
+            "wxpython".to_string(),
+            "numpy".to_string(),
+        ];
+        let (new_content, removed) = remove_dependencies(content, &unused);
+
+        assert_eq!(
+            removed,
+            vec![
+                (
+                    "requests".to_string(),
+                    "requests==2.28.1    # HTTP".to_string()
+                ),
+                (
+                    "django-rest-framework".to_string(),
+                    "Django_Rest_Framework".to_string()
+                ),
+            ]
+        );
+        assert_eq!(
+            new_content,
+            r#"# Runtime dependencies
+-r base.txt
+flask>=2.0.0
+./wheels/local-1.0-py3-none-any.whl
+wxPython @ http://wxpython.org/wxPython-4.0-cp34-none-win_amd64.whl
+numpy==1.26.0 \
+    --hash=sha256:abc
+pandas
+"#
+        );
+    }
+
+    #[cfg(feature = "python-parsing")]
+    #[test]
+    fn test_add_dependencies() {
+        let requirements = vec!["requests".to_string(), "pyyaml>=6.0.1".to_string()];
+        assert_eq!(
+            add_dependencies("flask>=2.0.0  # web", &requirements),
+            "flask>=2.0.0  # web\nrequests\npyyaml>=6.0.1\n"
+        );
+        assert_eq!(add_dependencies("", &requirements[..1]), "requests\n");
+    }
 }
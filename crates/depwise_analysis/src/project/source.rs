@@ -0,0 +1,625 @@
+//! Reading the configurations of dependencies out of each kind of dependency file.
+//!
+//! Every kind of [`EnvironmentBuilderSource`] is parsed by a [`DependencySource`], built for
+//! the file by the factory registered for its kind on a [`SourceRegistry`]. The built-in
+//! kinds are registered by [`SourceRegistry::default`]; other kinds, like an organization's
+//! own manifest format, are declared as [`EnvironmentBuilderSource::Custom`] sources and
+//! registered with [`SourceRegistry::register`].
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::cancel::CancellationToken;
+use crate::error::{AnalysisError, ParseDiagnostic};
+use crate::name_map;
+use crate::options::DEFAULT_MAX_REQUIREMENTS;
+use crate::{Dependency, EnvironmentBuilderSource};
+
+#[cfg(feature = "python-parsing")]
+use super::noxfile;
+#[cfg(feature = "conda")]
+use super::{CondaEnvironmentMetadata, condayml};
+use super::{
+    Configuration, Provenance, coremetadata, inlinescript, merge_dependencies, pixitoml,
+    poetrylock, pyprojecttoml, requirementstxt, split_extras, toxini,
+};
+
+/// A dependency file that can be read into configurations of dependencies
+pub trait DependencySource: fmt::Debug {
+    /// Read the configurations the file declares. Dependencies that can't be parsed are an
+    /// error.
+    fn parse(&self) -> Result<Vec<Configuration>, AnalysisError>;
+
+    /// Read the configurations like `parse`, skipping the dependencies that can't be parsed
+    /// and adding them to `diagnostics`. Sources that can't skip a dependency leave this as
+    /// `parse`.
+    fn parse_with_diagnostics(
+        &self,
+        _diagnostics: &mut Vec<ParseDiagnostic>,
+    ) -> Result<Vec<Configuration>, AnalysisError> {
+        self.parse()
+    }
+}
+
+/// Parse a source that collects diagnostics, failing on any of them
+fn parse_strictly(source: &dyn DependencySource) -> Result<Vec<Configuration>, AnalysisError> {
+    let mut diagnostics = Vec::new();
+    let configurations = source.parse_with_diagnostics(&mut diagnostics)?;
+    if !diagnostics.is_empty() {
+        return Err(AnalysisError::InvalidDeclarations(diagnostics));
+    }
+    Ok(configurations)
+}
+
+/// How dependency files are read, whatever their kind
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct ParseOptions {
+    /// Whether remote files, like requirements files included by URL, are left undownloaded,
+    /// which makes them an error
+    pub offline: bool,
+    /// How many requirements a requirements file may declare with the files it includes
+    pub max_requirements: Option<usize>,
+    /// How long downloading a remote file may take
+    pub timeout: Option<Duration>,
+    /// Stops downloading remote files once cancelled
+    pub cancel: CancellationToken,
+}
+
+impl ParseOptions {
+    pub fn new(offline: bool, max_requirements: Option<usize>) -> Self {
+        Self {
+            offline,
+            max_requirements,
+            timeout: None,
+            cancel: CancellationToken::new(),
+        }
+    }
+
+    /// Stop downloads after `timeout`, or once `cancel` is cancelled
+    pub fn with_download_limits(
+        mut self,
+        timeout: Option<Duration>,
+        cancel: CancellationToken,
+    ) -> Self {
+        self.timeout = timeout;
+        self.cancel = cancel;
+        self
+    }
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self::new(false, Some(DEFAULT_MAX_REQUIREMENTS))
+    }
+}
+
+/// Builds the parser of a dependency file of one kind
+pub type SourceFactory =
+    Arc<dyn Fn(&EnvironmentBuilderSource, ParseOptions) -> Box<dyn DependencySource> + Send + Sync>;
+
+/// The parsers of each kind of dependency file, by the kind's name (see
+/// [`EnvironmentBuilderSource::kind`])
+#[derive(Clone)]
+pub struct SourceRegistry {
+    factories: BTreeMap<String, SourceFactory>,
+}
+
+impl SourceRegistry {
+    /// A registry without any kinds, not even the built-in ones
+    pub fn empty() -> Self {
+        Self {
+            factories: BTreeMap::new(),
+        }
+    }
+
+    /// Parse the sources of a kind with the parsers `factory` builds, replacing the parser
+    /// registered for it before, if any
+    pub fn register(
+        &mut self,
+        kind: impl Into<String>,
+        factory: impl Fn(&EnvironmentBuilderSource, ParseOptions) -> Box<dyn DependencySource>
+        + Send
+        + Sync
+        + 'static,
+    ) -> &mut Self {
+        self.factories.insert(kind.into(), Arc::new(factory));
+        self
+    }
+
+    /// The kinds of sources that can be parsed, sorted
+    pub fn kinds(&self) -> impl Iterator<Item = &str> {
+        self.factories.keys().map(String::as_str)
+    }
+
+    /// The parser of a source, or an error if nothing is registered for its kind
+    pub fn open(
+        &self,
+        source: &EnvironmentBuilderSource,
+        options: ParseOptions,
+    ) -> Result<Box<dyn DependencySource>, AnalysisError> {
+        let factory = self.factories.get(source.kind()).ok_or_else(|| {
+            AnalysisError::UnsupportedProjectFormat(format!(
+                "{} ({})",
+                source.path().display(),
+                source.kind()
+            ))
+        })?;
+        Ok(factory(source, options))
+    }
+}
+
+impl Default for SourceRegistry {
+    /// A registry of the built-in kinds of dependency files
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        registry
+            .register("pyproject.toml", |source, _| {
+                Box::new(PyProjectToml(source.clone()))
+            })
+            .register("requirements.txt", |source, options| {
+                Box::new(RequirementsTxt(source.clone(), options))
+            })
+            .register("pixi.toml", |source, _| Box::new(PixiToml(source.clone())))
+            .register("METADATA", |source, _| {
+                Box::new(PackageMetadata(source.clone()))
+            })
+            .register("poetry.lock", |source, _| {
+                Box::new(PoetryLock(source.clone()))
+            })
+            .register("script", |source, _| Box::new(InlineScript(source.clone())))
+            .register("tox.ini", |source, options| {
+                Box::new(TestRunner(source.clone(), options))
+            });
+        #[cfg(feature = "conda")]
+        registry.register("environment.yml", |source, options| {
+            Box::new(CondaEnvironmentYml(source.clone(), options))
+        });
+        #[cfg(feature = "python-parsing")]
+        registry.register("noxfile.py", |source, options| {
+            Box::new(TestRunner(source.clone(), options))
+        });
+        registry
+    }
+}
+
+impl fmt::Debug for SourceRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.kinds()).finish()
+    }
+}
+
+/// A configuration of the dependencies of an extra of `source`, installed with `dependencies`
+fn extra_configuration(
+    source: &EnvironmentBuilderSource,
+    extra: &str,
+    dependencies: Vec<Dependency>,
+) -> Configuration {
+    Configuration {
+        name: format!("{}[{}]", source.path().display(), extra),
+        extra: Some(extra.to_string()),
+        ..Configuration::new(dependencies, String::new(), source.clone())
+    }
+}
+
+/// The `[project]` dependencies, extras, and hatch environments of a pyproject.toml
+#[derive(Debug)]
+struct PyProjectToml(EnvironmentBuilderSource);
+
+impl DependencySource for PyProjectToml {
+    fn parse(&self) -> Result<Vec<Configuration>, AnalysisError> {
+        parse_strictly(self)
+    }
+
+    fn parse_with_diagnostics(
+        &self,
+        diagnostics: &mut Vec<ParseDiagnostic>,
+    ) -> Result<Vec<Configuration>, AnalysisError> {
+        let source = &self.0;
+        let path = source.path();
+        let pyproject = pyprojecttoml::parse(path)?;
+        diagnostics.extend_from_slice(pyproject.diagnostics());
+        let mut configurations = vec![Configuration::new(
+            pyproject.required_dependencies().clone(),
+            path.display().to_string(),
+            source.clone(),
+        )];
+
+        let mut extras = pyproject.optional_configurations();
+        extras.sort();
+        for extra in extras {
+            let dependencies = pyproject.get_dependencies_for_configuration(&[extra]);
+            configurations.push(extra_configuration(source, extra, dependencies));
+        }
+
+        for (environment, dependencies) in pyproject.hatch_environments() {
+            configurations.push(Configuration {
+                name: format!("{} (hatch env {})", path.display(), environment),
+                environment: Some(environment.clone()),
+                ..Configuration::new(dependencies.clone(), String::new(), source.clone())
+            });
+        }
+        for configuration in &mut configurations {
+            configuration.requires_python = pyproject.requires_python().cloned();
+        }
+        Ok(configurations)
+    }
+}
+
+/// The requirements of a requirements file and the files it includes, split by the extras
+/// their markers select
+#[derive(Debug)]
+struct RequirementsTxt(EnvironmentBuilderSource, ParseOptions);
+
+impl DependencySource for RequirementsTxt {
+    fn parse(&self) -> Result<Vec<Configuration>, AnalysisError> {
+        parse_strictly(self)
+    }
+
+    fn parse_with_diagnostics(
+        &self,
+        diagnostics: &mut Vec<ParseDiagnostic>,
+    ) -> Result<Vec<Configuration>, AnalysisError> {
+        let (source, options) = (&self.0, &self.1);
+        let path = source.path();
+        let requirements = requirementstxt::parse(path, options, diagnostics)?;
+        // The first declaration of a package is the one that counts
+        let mut provenance: Vec<(String, Provenance)> = Vec::new();
+        for (dependency, declared) in requirements
+            .dependencies
+            .iter()
+            .zip(requirements.provenance)
+        {
+            if let Some(name) = dependency.name().map(name_map::normalize_name)
+                && !provenance.iter().any(|(seen, _)| *seen == name)
+            {
+                provenance.push((name, declared));
+            }
+        }
+        // Lines with an `extra == "..."` marker belong to that extra, like the
+        // `Requires-Dist` entries they're usually generated from
+        let (required, extras) = split_extras(requirements.dependencies);
+        let mut configurations = vec![Configuration::new(
+            required.clone(),
+            path.display().to_string(),
+            source.clone(),
+        )];
+        for (extra, extra_dependencies) in extras {
+            let mut dependencies = required.clone();
+            merge_dependencies(&mut dependencies, extra_dependencies);
+            configurations.push(extra_configuration(source, extra.as_ref(), dependencies));
+        }
+        for configuration in &mut configurations {
+            configuration.provenance = provenance.clone();
+            configuration.includes = Some(requirements.includes.clone());
+        }
+        Ok(configurations)
+    }
+}
+
+/// The conda and `pip:` dependencies of a conda environment file
+#[cfg(feature = "conda")]
+#[derive(Debug)]
+struct CondaEnvironmentYml(EnvironmentBuilderSource, ParseOptions);
+
+#[cfg(feature = "conda")]
+impl DependencySource for CondaEnvironmentYml {
+    fn parse(&self) -> Result<Vec<Configuration>, AnalysisError> {
+        parse_strictly(self)
+    }
+
+    fn parse_with_diagnostics(
+        &self,
+        diagnostics: &mut Vec<ParseDiagnostic>,
+    ) -> Result<Vec<Configuration>, AnalysisError> {
+        let (source, options) = (&self.0, &self.1);
+        let path = source.path();
+        let mut environment = condayml::parse(path, options)?;
+        diagnostics.append(&mut environment.diagnostics);
+        let mut configuration = Configuration::new(
+            environment.dependencies,
+            path.display().to_string(),
+            source.clone(),
+        );
+        configuration.python_requirement = environment.python;
+        configuration.virtual_packages = environment.virtual_packages;
+        configuration.conda_environment = Some(CondaEnvironmentMetadata {
+            name: environment.name,
+            channels: environment.channels,
+        });
+        Ok(vec![configuration])
+    }
+}
+
+/// The environments and features of a pixi manifest, on each platform it supports
+#[derive(Debug)]
+struct PixiToml(EnvironmentBuilderSource);
+
+impl DependencySource for PixiToml {
+    fn parse(&self) -> Result<Vec<Configuration>, AnalysisError> {
+        parse_strictly(self)
+    }
+
+    fn parse_with_diagnostics(
+        &self,
+        diagnostics: &mut Vec<ParseDiagnostic>,
+    ) -> Result<Vec<Configuration>, AnalysisError> {
+        let source = &self.0;
+        let path = source.path();
+        let mut pixi = pixitoml::parse(path)?;
+        diagnostics.append(&mut pixi.diagnostics);
+        // With dependencies for only some platforms, each supported platform gets its own
+        // configurations. A single platform needs no name, and without any listed every
+        // target's dependencies are kept.
+        let platforms: Vec<Option<&str>> = match &pixi.platforms[..] {
+            platforms if pixi.has_targets() && platforms.len() > 1 => platforms
+                .iter()
+                .map(|platform| Some(platform.as_str()))
+                .collect(),
+            platforms => vec![platforms.first().map(String::as_str)],
+        };
+        let split = platforms.len() > 1;
+        let mut configurations = Vec::new();
+        for platform in platforms {
+            let base = pixitoml::for_platform(&pixi.dependencies, &pixi.targets, platform);
+            let feature = |name: &str| {
+                pixi.features
+                    .iter()
+                    .find(|feature| feature.name == name)
+                    .map(|feature| {
+                        pixitoml::for_platform(&feature.dependencies, &feature.targets, platform)
+                    })
+                    .unwrap_or_default()
+            };
+            // Each environment of `[environments]` installs the features it lists, or
+            // without any environments declared each feature is checked on its own
+            let mut selected = Vec::new();
+            if !pixi.environments.iter().any(|e| e.name == "default") {
+                selected.push((None, base.clone()));
+            }
+            for environment in &pixi.environments {
+                let mut dependencies = match environment.no_default_feature {
+                    true => Vec::new(),
+                    false => base.clone(),
+                };
+                for name in &environment.features {
+                    merge_dependencies(&mut dependencies, feature(name));
+                }
+                let name = Some(&environment.name).filter(|name| *name != "default");
+                selected.push((name.map(|name| ("environment", name.clone())), dependencies));
+            }
+            if pixi.environments.is_empty() {
+                for feature in &pixi.features {
+                    let mut dependencies = base.clone();
+                    merge_dependencies(
+                        &mut dependencies,
+                        pixitoml::for_platform(&feature.dependencies, &feature.targets, platform),
+                    );
+                    selected.push((Some(("feature", feature.name.clone())), dependencies));
+                }
+            }
+            let platform = platform.filter(|_| split);
+            for (environment, dependencies) in selected {
+                let mut qualifiers: Vec<String> = environment
+                    .iter()
+                    .map(|(kind, name)| format!("{} {}", kind, name))
+                    .collect();
+                qualifiers.extend(platform.map(str::to_string));
+                let name = match qualifiers.is_empty() {
+                    true => path.display().to_string(),
+                    false => format!("{} ({})", path.display(), qualifiers.join(", ")),
+                };
+                configurations.push(Configuration {
+                    environment: environment.map(|(_, name)| name),
+                    python_requirement: pixi.python.clone(),
+                    platform: platform.map(str::to_string),
+                    ..Configuration::new(dependencies, name, source.clone())
+                });
+            }
+        }
+        Ok(configurations)
+    }
+}
+
+/// The `Requires-Dist` entries of a distribution's core metadata, by extra
+#[derive(Debug)]
+struct PackageMetadata(EnvironmentBuilderSource);
+
+impl DependencySource for PackageMetadata {
+    fn parse(&self) -> Result<Vec<Configuration>, AnalysisError> {
+        let source = &self.0;
+        let path = source.path();
+        let metadata = coremetadata::parse(path)?;
+        let required = metadata.required_dependencies();
+        let mut configurations = vec![Configuration::new(
+            required.clone(),
+            path.display().to_string(),
+            source.clone(),
+        )];
+        for extra in &metadata.extras {
+            let mut dependencies = required.clone();
+            merge_dependencies(&mut dependencies, metadata.extra_dependencies(extra));
+            configurations.push(extra_configuration(source, extra, dependencies));
+        }
+        Ok(configurations)
+    }
+}
+
+/// The packages a `poetry.lock` pins, for the main group, each extra, and each other group
+#[derive(Debug)]
+struct PoetryLock(EnvironmentBuilderSource);
+
+impl DependencySource for PoetryLock {
+    fn parse(&self) -> Result<Vec<Configuration>, AnalysisError> {
+        let source = &self.0;
+        let path = source.path();
+        let lock = poetrylock::parse(path)?;
+        let required = lock.required_dependencies();
+        let mut configurations = vec![Configuration::new(
+            required.clone(),
+            path.display().to_string(),
+            source.clone(),
+        )];
+        for (extra, _) in &lock.extras {
+            let mut dependencies = required.clone();
+            merge_dependencies(&mut dependencies, lock.extra_dependencies(extra));
+            configurations.push(extra_configuration(source, extra, dependencies));
+        }
+        // The other dependency groups are installed on top of the main one
+        for group in lock.groups() {
+            let mut dependencies = required.clone();
+            merge_dependencies(&mut dependencies, lock.group_dependencies(group));
+            configurations.push(Configuration {
+                environment: Some(group.to_string()),
+                ..Configuration::new(
+                    dependencies,
+                    format!("{} (group {})", path.display(), group),
+                    source.clone(),
+                )
+            });
+        }
+        Ok(configurations)
+    }
+}
+
+/// The dependencies of a script's PEP 723 inline metadata
+#[derive(Debug)]
+struct InlineScript(EnvironmentBuilderSource);
+
+impl DependencySource for InlineScript {
+    fn parse(&self) -> Result<Vec<Configuration>, AnalysisError> {
+        let source = &self.0;
+        let dependencies = inlinescript::parse(source.path())?;
+        Ok(vec![Configuration::new(
+            dependencies,
+            source.path().display().to_string(),
+            source.clone(),
+        )])
+    }
+}
+
+/// The packages each test environment of a `tox.ini` or session of a `noxfile.py` installs
+#[derive(Debug)]
+struct TestRunner(EnvironmentBuilderSource, ParseOptions);
+
+impl TestRunner {
+    /// The packages each environment of the test runner installs
+    fn environments(
+        &self,
+        diagnostics: &mut Vec<ParseDiagnostic>,
+    ) -> Result<Vec<(String, Vec<Dependency>)>, AnalysisError> {
+        let (path, options) = (self.0.path(), &self.1);
+        match &self.0 {
+            #[cfg(feature = "python-parsing")]
+            EnvironmentBuilderSource::Noxfile(_) => noxfile::parse(path, options, diagnostics),
+            _ => toxini::parse(path, options, diagnostics),
+        }
+    }
+}
+
+impl DependencySource for TestRunner {
+    fn parse(&self) -> Result<Vec<Configuration>, AnalysisError> {
+        parse_strictly(self)
+    }
+
+    fn parse_with_diagnostics(
+        &self,
+        diagnostics: &mut Vec<ParseDiagnostic>,
+    ) -> Result<Vec<Configuration>, AnalysisError> {
+        let source = &self.0;
+        let path = source.path();
+        let kind = match source {
+            EnvironmentBuilderSource::ToxIni(_) => "testenv",
+            _ => "session",
+        };
+        Ok(self
+            .environments(diagnostics)?
+            .into_iter()
+            .map(|(environment, dependencies)| Configuration {
+                environment: Some(environment.clone()),
+                ..Configuration::new(
+                    dependencies,
+                    match environment.as_str() {
+                        "testenv" => format!("{} (testenv)", path.display()),
+                        _ => format!("{} ({} {})", path.display(), kind, environment),
+                    },
+                    source.clone(),
+                )
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::project::PyPIRequirement;
+
+    /// A manifest listing a requirement on each line, after a `dep:` prefix
+    #[derive(Debug)]
+    struct DepList(EnvironmentBuilderSource);
+
+    impl DependencySource for DepList {
+        fn parse(&self) -> Result<Vec<Configuration>, AnalysisError> {
+            let path = self.0.path();
+            let content = fs::read_to_string(path).map_err(|e| {
+                AnalysisError::FileReadError(path.display().to_string(), e.to_string())
+            })?;
+            let dependencies = content
+                .lines()
+                .filter_map(|line| line.strip_prefix("dep:"))
+                .map(|requirement| PyPIRequirement::from_str(requirement.trim()))
+                .map(|requirement| requirement.map(Dependency::PyPI))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(vec![Configuration::new(
+                dependencies,
+                path.display().to_string(),
+                self.0.clone(),
+            )])
+        }
+    }
+
+    #[test]
+    fn test_register_source() -> Result<(), AnalysisError> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("deps.list");
+        fs::write(&path, "# our own format\ndep: requests>=2\ndep: pyyaml\n").unwrap();
+        let source = EnvironmentBuilderSource::Custom {
+            kind: "deps.list".to_string(),
+            path: path.clone(),
+        };
+
+        let mut registry = SourceRegistry::default();
+        let error = registry.open(&source, ParseOptions::default()).unwrap_err();
+        assert!(matches!(error, AnalysisError::UnsupportedProjectFormat(_)));
+
+        registry.register("deps.list", |source, _| Box::new(DepList(source.clone())));
+        assert!(registry.kinds().any(|kind| kind == "deps.list"));
+        let configurations = registry.open(&source, ParseOptions::default())?.parse()?;
+        assert_eq!(configurations.len(), 1);
+        assert_eq!(configurations[0].source(), &source);
+        let names: Vec<&str> = configurations[0]
+            .dependencies()
+            .iter()
+            .filter_map(Dependency::name)
+            .collect();
+        assert_eq!(names, ["requests", "pyyaml"]);
+
+        // The built-in kinds are parsed through the registry too
+        let requirements = dir.path().join("requirements.txt");
+        fs::write(&requirements, "click\n").unwrap();
+        let configurations = registry
+            .open(
+                &EnvironmentBuilderSource::RequirementsTxt(requirements),
+                ParseOptions::default(),
+            )?
+            .parse()?;
+        assert_eq!(configurations[0].dependencies().len(), 1);
+        Ok(())
+    }
+}
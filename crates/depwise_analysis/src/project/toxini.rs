@@ -0,0 +1,237 @@
+use std::fs;
+use std::path::Path;
+
+use tracing::debug;
+
+use crate::error::{AnalysisError, ParseDiagnostic};
+use crate::project::{Dependency, ParseOptions, requirementstxt};
+
+/// The `deps` of each `[testenv]` and `[testenv:<name>]` section of a `tox.ini`, in order,
+/// by environment name (`testenv` for the base section)
+pub(crate) fn parse(
+    file_path: &Path,
+    options: &ParseOptions,
+    diagnostics: &mut Vec<ParseDiagnostic>,
+) -> Result<Vec<(String, Vec<Dependency>)>, AnalysisError> {
+    let content = fs::read_to_string(file_path).map_err(|e| {
+        AnalysisError::FileReadError(file_path.to_string_lossy().to_string(), e.to_string())
+    })?;
+    let sections = sections(&content);
+    let mut environments = Vec::new();
+    for section in &sections {
+        let name = match section.name.as_str() {
+            "testenv" => "testenv",
+            name => match name.strip_prefix("testenv:") {
+                Some(name) => name.trim(),
+                None => continue,
+            },
+        };
+        let Some(deps) = section.value("deps") else {
+            continue;
+        };
+        let mut dependencies = Vec::new();
+        for (line, item) in expand(deps, &sections) {
+            let Some(requirement) = requirement_line(&item) else {
+                continue;
+            };
+            // Padded to the item's line, so diagnostics point at it
+            let content = format!("{}{}", "\n".repeat(line - 1), requirement);
+            dependencies.extend(requirementstxt::parse_lines(
+                &content,
+                file_path,
+                options,
+                diagnostics,
+            )?);
+        }
+        environments.push((name.to_string(), dependencies));
+    }
+    Ok(environments)
+}
+
+/// A `[section]` of an INI file
+struct Section {
+    name: String,
+    /// The keys and their values, each value line with its line number
+    entries: Vec<(String, Vec<(usize, String)>)>,
+}
+
+impl Section {
+    fn value(&self, key: &str) -> Option<&[(usize, String)]> {
+        self.entries
+            .iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value.as_slice())
+    }
+}
+
+/// Split INI content into sections. Indented lines continue the value of the key before them.
+fn sections(content: &str) -> Vec<Section> {
+    let mut sections: Vec<Section> = Vec::new();
+    for (i, line) in content.lines().enumerate() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with(['#', ';']) {
+            continue;
+        }
+        if let Some(name) = trimmed.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+            sections.push(Section {
+                name: name.trim().to_string(),
+                entries: Vec::new(),
+            });
+            continue;
+        }
+        let Some(section) = sections.last_mut() else {
+            continue;
+        };
+        if line.starts_with([' ', '\t']) {
+            if let Some((_, value)) = section.entries.last_mut() {
+                value.push((i + 1, trimmed.to_string()));
+            }
+            continue;
+        }
+        if let Some((key, value)) = trimmed.split_once(['=', ':']) {
+            let value = value.trim();
+            let lines = match value.is_empty() {
+                true => Vec::new(),
+                false => vec![(i + 1, value.to_string())],
+            };
+            section.entries.push((key.trim().to_string(), lines));
+        }
+    }
+    sections
+}
+
+/// Replace `{[section]deps}` references with the deps of that section
+fn expand(deps: &[(usize, String)], sections: &[Section]) -> Vec<(usize, String)> {
+    let mut expanded = Vec::new();
+    for (line, item) in deps {
+        let reference = item
+            .strip_prefix("{[")
+            .and_then(|item| item.strip_suffix("]deps}"));
+        match reference {
+            Some(name) => {
+                let section = sections.iter().find(|section| section.name == name);
+                match section.and_then(|section| section.value("deps")) {
+                    // The referenced deps are reported on the line referencing them
+                    Some(referenced) => expanded.extend(
+                        referenced
+                            .iter()
+                            .filter(|(_, item)| !item.starts_with("{["))
+                            .map(|(_, item)| (*line, item.clone())),
+                    ),
+                    None => debug!("No deps in [{}] to substitute", name),
+                }
+            }
+            None => expanded.push((*line, item.clone())),
+        }
+    }
+    expanded
+}
+
+/// Turn a `deps` item into a requirements file line: drop its factor conditions (`py38:`),
+/// resolve `{toxinidir}`, and skip the project itself and items using other substitutions
+fn requirement_line(item: &str) -> Option<String> {
+    let item = match item.split_once(": ") {
+        Some((factors, requirement))
+            if factors
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "_-,.!".contains(c)) =>
+        {
+            requirement.trim()
+        }
+        _ => item,
+    };
+    let item = item.replace("{toxinidir}/", "").replace("{toxinidir}", ".");
+    if item.contains('{') {
+        debug!("Skipping tox deps item with a substitution: {}", item);
+        return None;
+    }
+    pip_argument(&item)
+}
+
+/// Turn a `pip install` argument into a requirements file line: spell requirement file
+/// options as `-r <file>`, and skip the project itself
+pub(crate) fn pip_argument(argument: &str) -> Option<String> {
+    let argument = argument.trim();
+    if matches!(
+        argument,
+        "." | "-e ." | "-e." | "--editable ." | "--editable=."
+    ) {
+        return None;
+    }
+    let file = argument
+        .strip_prefix("--requirement")
+        .or_else(|| argument.strip_prefix("-r"))
+        .map(|file| file.trim_start_matches('=').trim());
+    match file {
+        Some(file) => Some(format!("-r {}", file)),
+        None => Some(argument.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_tox_ini() -> Result<(), AnalysisError> {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("requirements-dev.txt"),
+            "pytest-cov\nhypothesis\n",
+        )
+        .unwrap();
+        let tox_ini = dir.path().join("tox.ini");
+        fs::write(
+            &tox_ini,
+            "[tox]
+envlist = py311, lint
+
+[testenv]
+deps =
+    pytest>=7
+    py38: mock
+    -r{toxinidir}/requirements-dev.txt
+    -e .
+commands = pytest {posargs}
+
+[testenv:lint]
+deps = ruff
+       {[testenv]deps}
+       flask >=> 2
+skip_install = true
+",
+        )
+        .unwrap();
+
+        let mut diagnostics = Vec::new();
+        let environments = parse(&tox_ini, &ParseOptions::new(true, None), &mut diagnostics)?;
+        let found: Vec<(&str, Vec<&str>)> = environments
+            .iter()
+            .map(|(name, dependencies)| {
+                (
+                    name.as_str(),
+                    dependencies.iter().filter_map(Dependency::name).collect(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (
+                    "testenv",
+                    vec!["pytest", "mock", "pytest-cov", "hypothesis"]
+                ),
+                (
+                    "lint",
+                    vec!["ruff", "pytest", "mock", "pytest-cov", "hypothesis"]
+                ),
+            ]
+        );
+        let invalid: Vec<(usize, &str)> = diagnostics
+            .iter()
+            .map(|diagnostic| (diagnostic.location.line, diagnostic.text.as_str()))
+            .collect();
+        assert_eq!(invalid, [(15, "flask >=> 2")]);
+        Ok(())
+    }
+}
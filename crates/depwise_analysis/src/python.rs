@@ -0,0 +1,286 @@
+//! The imports of Python sources, for tools that need them without the rest of the analysis.
+//!
+//! ```
+//! use depwise_analysis::python::scan_source;
+//!
+//! let scanned = scan_source(
+//!     r#""""Load the settings."""
+//! import os
+//! try:
+//!     from yaml import CSafeLoader as Loader
+//! except ImportError:
+//!     from yaml import SafeLoader as Loader
+//! "#,
+//! )?;
+//! assert!(scanned.has_docstring);
+//! assert_eq!(scanned.encoding, "utf-8");
+//! let modules: Vec<&str> = scanned
+//!     .imports
+//!     .iter()
+//!     .filter_map(|import| import.module_name.as_deref())
+//!     .collect();
+//! assert_eq!(modules, ["os", "yaml", "yaml"]);
+//! assert!(scanned.imports[1].is_likely_exception_guarded);
+//! # Ok::<(), depwise_analysis::error::AnalysisError>(())
+//! ```
+//!
+//! Everything here serializes with serde, so a scan can be handed to other tools as JSON.
+
+use std::fs;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::error::AnalysisError;
+use crate::import_lines;
+pub use crate::parser::PythonImport;
+use crate::parser::PythonParser;
+pub use crate::walker::defining_module;
+
+/// What a Python source imports
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[non_exhaustive]
+pub struct FileImports {
+    /// The import statements, in the order they appear
+    pub imports: Vec<PythonImport>,
+    /// The encoding the source declares with a `# -*- coding: ... -*-` comment (PEP 263),
+    /// normalized like `latin-1`, or `utf-8` when it declares none
+    pub encoding: String,
+    /// Whether the module starts with a docstring
+    pub has_docstring: bool,
+    /// Why the source couldn't be fully read or parsed. The imports of a source with a
+    /// syntax error are found line by line, as the analysis finds them.
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// A problem reading or parsing a source, at a line and column starting from 1
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[non_exhaustive]
+pub struct Diagnostic {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+/// Scan the imports of Python source code.
+///
+/// A syntax error isn't an error here: a diagnostic says where the source stops parsing,
+/// and the import statements are still read line by line, the way the analysis reads a
+/// file using syntax the parser doesn't know.
+///
+/// ```
+/// use depwise_analysis::python::scan_source;
+///
+/// let scanned = scan_source("import os\ndef broken(:\n")?;
+/// assert_eq!(scanned.imports[0].module_name.as_deref(), Some("os"));
+/// assert_eq!(scanned.diagnostics[0].line, 2);
+/// # Ok::<(), depwise_analysis::error::AnalysisError>(())
+/// ```
+pub fn scan_source(source: &str) -> Result<FileImports, AnalysisError> {
+    let encoding = declared_encoding(source).unwrap_or_else(|| "utf-8".to_string());
+    let (imports, has_docstring, diagnostics) = match PythonParser::new(source).scan_module() {
+        Ok((imports, has_docstring)) => (imports, has_docstring, Vec::new()),
+        Err(diagnostic) => (import_lines::scan(source), false, vec![diagnostic]),
+    };
+    Ok(FileImports {
+        imports,
+        encoding,
+        has_docstring,
+        diagnostics,
+    })
+}
+
+/// The imports of Python source code, read the way the analysis reads them: a source that
+/// never mentions `import` isn't parsed at all, so only sources that may import something
+/// can fail to parse.
+///
+/// ```
+/// use depwise_analysis::python::imports;
+///
+/// let found = imports("from yaml import safe_load\n")?;
+/// assert_eq!(found[0].module_name.as_deref(), Some("yaml"));
+/// assert!(imports("X = 1\n")?.is_empty());
+/// # Ok::<(), depwise_analysis::error::AnalysisError>(())
+/// ```
+pub fn imports(source: &str) -> Result<Vec<PythonImport>, AnalysisError> {
+    PythonParser::new(source).parse_imports()
+}
+
+/// Scan the imports of a Python file, decoded with the encoding it declares.
+///
+/// UTF-8 and Latin-1 are decoded. A file in another encoding, or with bytes its encoding
+/// can't decode, is read as UTF-8 with the bytes replaced, and a diagnostic says so. Only
+/// failing to read the file is an error.
+pub fn scan_file(path: &Path) -> Result<FileImports, AnalysisError> {
+    let bytes = fs::read(path).map_err(|e| {
+        AnalysisError::FileReadError(path.to_string_lossy().to_string(), e.to_string())
+    })?;
+    let bytes = bytes.strip_prefix(b"\xef\xbb\xbf").unwrap_or(&bytes);
+    // The declaration is ASCII, so it can be read before knowing the encoding
+    let head = String::from_utf8_lossy(&bytes[..bytes.len().min(1024)]);
+    let (source, problem) = match declared_encoding(&head).as_deref() {
+        Some("latin-1") => (bytes.iter().map(|&byte| byte as char).collect(), None),
+        declared => match std::str::from_utf8(bytes) {
+            Ok(source) => (source.to_string(), None),
+            Err(e) => {
+                let message = match declared {
+                    None | Some("utf-8") => "invalid UTF-8, replaced".to_string(),
+                    Some(encoding) => format!("`{}` isn't supported, read as UTF-8", encoding),
+                };
+                let line = bytes[..e.valid_up_to()]
+                    .iter()
+                    .filter(|&&byte| byte == b'\n')
+                    .count()
+                    + 1;
+                (
+                    String::from_utf8_lossy(bytes).into_owned(),
+                    Some((line, message)),
+                )
+            }
+        },
+    };
+
+    let mut scanned = scan_source(&source)?;
+    if let Some((line, message)) = problem {
+        scanned.diagnostics.insert(
+            0,
+            Diagnostic {
+                line,
+                column: 1,
+                message,
+            },
+        );
+    }
+    Ok(scanned)
+}
+
+/// The encoding declared by a `coding` comment on one of the first two lines (PEP 263)
+fn declared_encoding(source: &str) -> Option<String> {
+    for line in source.lines().take(2) {
+        let comment = line.trim_start_matches([' ', '\t', '\x0c']);
+        if !comment.starts_with('#') {
+            // Only a comment line can come before the declaration
+            if comment.is_empty() {
+                continue;
+            }
+            return None;
+        }
+        let Some(position) = comment.find("coding") else {
+            continue;
+        };
+        let rest = &comment[position + "coding".len()..];
+        let Some(rest) = rest.strip_prefix([':', '=']) else {
+            continue;
+        };
+        let name: String = rest
+            .trim_start_matches([' ', '\t'])
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+            .collect();
+        if name.is_empty() {
+            continue;
+        }
+        return Some(normalize_encoding(&name));
+    }
+    None
+}
+
+/// The name of an encoding without its spelling variants, like `latin-1` for `ISO-8859-1`
+fn normalize_encoding(name: &str) -> String {
+    let name = name.to_ascii_lowercase().replace('_', "-");
+    match name.as_str() {
+        "utf8" | "utf-8" | "utf-8-sig" => "utf-8".to_string(),
+        "latin-1" | "latin1" | "iso-8859-1" | "iso8859-1" | "iso-latin-1" | "l1" => {
+            "latin-1".to_string()
+        }
+        _ => name,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_scan_source() -> Result<(), AnalysisError> {
+        let scanned = scan_source("#!/usr/bin/env python\n# -*- coding: UTF8 -*-\nimport os\n")?;
+        assert_eq!(scanned.encoding, "utf-8");
+        assert!(!scanned.has_docstring);
+        assert_eq!(scanned.imports.len(), 1);
+
+        // Sources without imports are still parsed, for their docstring and diagnostics
+        let scanned = scan_source("'''Nothing to see.'''\n")?;
+        assert!(scanned.has_docstring);
+        let scanned = scan_source("x = 1\n\ndef f(:\n    pass\n")?;
+        let [diagnostic] = scanned.diagnostics.as_slice() else {
+            panic!("Expected a syntax error");
+        };
+        assert_eq!((diagnostic.line, diagnostic.column), (3, 7));
+
+        // The syntax error doesn't hide the imports around it
+        let scanned = scan_source("import os\nx = = 1\nimport yaml\n")?;
+        assert_eq!(scanned.diagnostics.len(), 1);
+        let modules: Vec<_> = scanned
+            .imports
+            .iter()
+            .map(|import| import.module_name.as_deref())
+            .collect();
+        assert_eq!(modules, [Some("os"), Some("yaml")]);
+
+        let json = serde_json::to_value(scan_source("from . import sibling\n")?).unwrap();
+        assert_eq!(json["encoding"], "utf-8");
+        assert_eq!(json["imports"][0]["is_relative"], true);
+        assert_eq!(json["imports"][0]["imported_names"][0], "sibling");
+        Ok(())
+    }
+
+    #[test]
+    fn test_declared_encoding() {
+        assert_eq!(
+            declared_encoding("# coding=latin-1\n"),
+            Some("latin-1".into())
+        );
+        assert_eq!(
+            declared_encoding("#!/usr/bin/python\n# vim: set fileencoding=ISO-8859-1 :\n"),
+            Some("latin-1".into())
+        );
+        assert_eq!(declared_encoding("import os\n# coding: latin-1\n"), None);
+        assert_eq!(declared_encoding("\n\n# coding: latin-1\n"), None);
+    }
+
+    #[test]
+    fn test_scan_file() -> Result<(), AnalysisError> {
+        let dir = tempdir().unwrap();
+        let latin = dir.path().join("latin.py");
+        fs::write(
+            &latin,
+            b"# -*- coding: latin-1 -*-\n\"\"\"Caf\xe9.\"\"\"\nimport json\n",
+        )
+        .unwrap();
+        let scanned = scan_file(&latin)?;
+        assert_eq!(scanned.encoding, "latin-1");
+        assert!(scanned.has_docstring);
+        assert_eq!(scanned.imports.len(), 1);
+        assert!(scanned.diagnostics.is_empty());
+
+        let broken = dir.path().join("broken.py");
+        fs::write(&broken, b"import json\ns = '\xff'\n").unwrap();
+        let scanned = scan_file(&broken)?;
+        assert_eq!(scanned.imports.len(), 1);
+        assert_eq!(
+            scanned.diagnostics,
+            [Diagnostic {
+                line: 2,
+                column: 1,
+                message: "invalid UTF-8, replaced".to_string(),
+            }]
+        );
+
+        assert!(matches!(
+            scan_file(&dir.path().join("missing.py")),
+            Err(AnalysisError::FileReadError(..))
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,175 @@
+use std::fmt;
+#[cfg(feature = "backends-exec")]
+use std::fs;
+#[cfg(feature = "backends-exec")]
+use std::path::Path;
+use std::str::FromStr;
+
+use pep508_rs::pep440_rs::{Operator, VersionSpecifiers};
+
+use crate::error::AnalysisError;
+
+/// A Python minor version, like `3.11`, that a project targets
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PythonVersion {
+    pub major: u8,
+    pub minor: u8,
+}
+
+impl PythonVersion {
+    pub const fn new(major: u8, minor: u8) -> Self {
+        Self { major, minor }
+    }
+
+    /// Find the version a specifier like `3.10`, `3.11.*` or `>=3.9,<3.13` is about. For a
+    /// range the first version mentioned, usually the lower bound, is used.
+    pub fn from_spec(spec: &str) -> Option<Self> {
+        let start = spec.find(|c: char| c.is_ascii_digit())?;
+        let mut parts = spec[start..].split('.');
+        let major = parts.next()?.parse().ok()?;
+        let minor: String = parts
+            .next()?
+            .chars()
+            .take_while(char::is_ascii_digit)
+            .collect();
+        Some(Self::new(major, minor.parse().ok()?))
+    }
+
+    /// The oldest version specifiers like `>=3.9,<4` allow, such as the `requires-python` of a
+    /// project: the highest of their lower bounds, or `None` if they have none
+    pub fn minimum_of(specifiers: &VersionSpecifiers) -> Option<Self> {
+        specifiers
+            .iter()
+            .filter(|specifier| {
+                matches!(
+                    specifier.operator(),
+                    Operator::Equal
+                        | Operator::EqualStar
+                        | Operator::ExactEqual
+                        | Operator::TildeEqual
+                        | Operator::GreaterThan
+                        | Operator::GreaterThanEqual
+                )
+            })
+            .filter_map(|specifier| {
+                let release = specifier.version().release();
+                let major = u8::try_from(*release.first()?).ok()?;
+                let minor = u8::try_from(release.get(1).copied().unwrap_or(0)).ok()?;
+                Some(Self::new(major, minor))
+            })
+            .max()
+    }
+}
+
+/// The file pyenv reads a project's interpreter from
+#[cfg(feature = "backends-exec")]
+pub(crate) const PYTHON_VERSION_FILE: &str = ".python-version";
+
+/// The interpreter the `.python-version` file in `root` names, like `3.11.4` or
+/// `pypy3.10-7.3.12`. pyenv files may list several versions, one per line, of which the
+/// first is the one `python` runs; blank lines and comments are skipped.
+#[cfg(feature = "backends-exec")]
+pub(crate) fn read_python_version_file(root: &Path) -> Option<String> {
+    let content = fs::read_to_string(root.join(PYTHON_VERSION_FILE)).ok()?;
+    content
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default().trim())
+        .find(|line| !line.is_empty())
+        .map(str::to_string)
+}
+
+impl FromStr for PythonVersion {
+    type Err = AnalysisError;
+
+    /// Parse a version like `3.11` or `3.11.4`; the patch version is ignored
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || AnalysisError::InvalidPythonVersion(s.to_string());
+        let mut parts = s.trim().split('.');
+        let major = parts
+            .next()
+            .and_then(|p| p.parse().ok())
+            .ok_or_else(invalid)?;
+        let minor = parts
+            .next()
+            .and_then(|p| p.parse().ok())
+            .ok_or_else(invalid)?;
+        if parts.any(|patch| patch.parse::<u32>().is_err()) {
+            return Err(invalid());
+        }
+        Ok(Self::new(major, minor))
+    }
+}
+
+impl fmt::Display for PythonVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_python_version() {
+        assert_eq!(
+            "3.11".parse::<PythonVersion>().ok(),
+            Some(PythonVersion::new(3, 11))
+        );
+        assert_eq!(
+            "3.9.18".parse::<PythonVersion>().ok(),
+            Some(PythonVersion::new(3, 9))
+        );
+        assert!("3".parse::<PythonVersion>().is_err());
+        assert!("3.x".parse::<PythonVersion>().is_err());
+
+        assert_eq!(
+            PythonVersion::from_spec("3.10"),
+            Some(PythonVersion::new(3, 10))
+        );
+        assert_eq!(
+            PythonVersion::from_spec("3.11.*"),
+            Some(PythonVersion::new(3, 11))
+        );
+        assert_eq!(
+            PythonVersion::from_spec(">=3.9,<3.13"),
+            Some(PythonVersion::new(3, 9))
+        );
+        assert_eq!(PythonVersion::from_spec("*"), None);
+        assert_eq!(
+            PythonVersion::from_spec("pypy3.10-7.3.12"),
+            Some(PythonVersion::new(3, 10))
+        );
+        assert_eq!(PythonVersion::from_spec("system"), None);
+    }
+
+    #[test]
+    fn test_minimum_of() {
+        let minimum = |spec: &str| PythonVersion::minimum_of(&spec.parse().unwrap());
+        assert_eq!(minimum(">=3.9"), Some(PythonVersion::new(3, 9)));
+        assert_eq!(minimum("<4,>=3.8.1"), Some(PythonVersion::new(3, 8)));
+        assert_eq!(minimum("~=3.10"), Some(PythonVersion::new(3, 10)));
+        assert_eq!(
+            minimum(">=3.7,!=3.9.*,>=3.8"),
+            Some(PythonVersion::new(3, 8))
+        );
+        assert_eq!(minimum("<3.13"), None);
+    }
+
+    #[cfg(feature = "backends-exec")]
+    #[test]
+    fn test_read_python_version_file() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(read_python_version_file(dir.path()), None);
+
+        fs::write(
+            dir.path().join(PYTHON_VERSION_FILE),
+            "# the project's interpreter\n\n3.11.4\n3.10\n",
+        )
+        .unwrap();
+        assert_eq!(
+            read_python_version_file(dir.path()).as_deref(),
+            Some("3.11.4")
+        );
+    }
+}
@@ -0,0 +1,472 @@
+//! Export the dependencies of a project as a CycloneDX 1.5 SBOM.
+//!
+//! When the backend resolved an environment, the bill of materials lists everything the
+//! declared dependencies install: the packages of the environment they require, directly or
+//! not, with the versions and archive hashes it records, and what each depends on in the
+//! `dependencies` graph. Packages a conda environment installs are `pkg:conda` components
+//! and the rest `pkg:pypi` ones, so a pixi environment lists both. A declared dependency
+//! the environment doesn't have, or every one when no environment was resolved, is listed
+//! as declared, with a version when it's pinned exactly (`==`).
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+
+use pep508_rs::VersionOrUrl;
+use pep508_rs::pep440_rs::Operator;
+use serde_json::{Value, json};
+
+use crate::error::AnalysisError;
+use crate::project::{self, Dependency};
+use crate::{EnvironmentBuilderSource, ResolvedEnvironment, name_map};
+
+/// The CycloneDX specification version documents are written for
+pub const SPEC_VERSION: &str = "1.5";
+
+/// The CycloneDX names of the hash algorithms environments record digests with
+const HASH_ALGORITHMS: [(&str, &str); 5] = [
+    ("md5", "MD5"),
+    ("sha1", "SHA-1"),
+    ("sha256", "SHA-256"),
+    ("sha384", "SHA-384"),
+    ("sha512", "SHA-512"),
+];
+
+/// A package listed in the bill of materials
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Component {
+    /// The purl package type, `pypi` or `conda`
+    ecosystem: &'static str,
+    name: String,
+    version: Option<String>,
+    /// The digests of its archive, by CycloneDX algorithm name
+    hashes: BTreeMap<&'static str, String>,
+    /// The components it depends on, by identity
+    depends_on: BTreeSet<(&'static str, String)>,
+}
+
+impl Component {
+    fn purl(&self) -> String {
+        match &self.version {
+            Some(version) => format!("pkg:{}/{}@{}", self.ecosystem, self.name, version),
+            None => format!("pkg:{}/{}", self.ecosystem, self.name),
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        let purl = self.purl();
+        let mut component = json!({
+            "type": "library",
+            "bom-ref": purl,
+            "name": self.name,
+            "purl": purl,
+        });
+        if let Some(version) = &self.version {
+            component["version"] = json!(version);
+        }
+        if !self.hashes.is_empty() {
+            component["hashes"] = self
+                .hashes
+                .iter()
+                .map(|(alg, content)| json!({ "alg": alg, "content": content }))
+                .collect();
+        }
+        component
+    }
+}
+
+/// Build a CycloneDX document for the dependencies declared in the sources.
+///
+/// `name` names the project component the document describes, and `environment` is the one
+/// the analysis resolved (see [`AnalysisReport::environment`](crate::AnalysisReport)), if
+/// any. With `offline` set, remote requirements files aren't downloaded.
+pub fn cyclonedx(
+    name: &str,
+    sources: &[EnvironmentBuilderSource],
+    environment: Option<&ResolvedEnvironment>,
+    offline: bool,
+) -> Result<Value, AnalysisError> {
+    let mut dependencies = Vec::new();
+    for source in sources {
+        for configuration in project::extract_configurations(source.clone(), offline)? {
+            // The interpreter a conda environment pins is one of its packages
+            dependencies.extend(
+                configuration
+                    .python_requirement()
+                    .cloned()
+                    .map(Dependency::Conda),
+            );
+            dependencies.extend(configuration.dependencies().iter().cloned());
+        }
+    }
+    Ok(document(name, &dependencies, environment))
+}
+
+/// Build the document from declared dependencies and the environment they were resolved to
+fn document(
+    name: &str,
+    dependencies: &[Dependency],
+    environment: Option<&ResolvedEnvironment>,
+) -> Value {
+    // Key components by their identity so a dependency declared in several configurations
+    // is listed once, keeping the first version found for it
+    let mut components: BTreeMap<(&'static str, String), Component> = BTreeMap::new();
+    let mut direct = BTreeSet::new();
+    // The distributions of the environment to list, and the versions they were pinned to
+    // for the environments that don't record one
+    let mut queue = VecDeque::new();
+    let mut pinned: HashMap<&str, String> = HashMap::new();
+    for dependency in dependencies {
+        let Some(declared) = declared_component(dependency) else {
+            continue;
+        };
+        if let Some(environment) = environment
+            && let Some(key) = installed_name(environment, dependency, &declared.name)
+        {
+            direct.insert(installed_identity(environment, key));
+            if let Some(version) = declared.version {
+                pinned.entry(key).or_insert(version);
+            }
+            queue.push_back(key);
+            continue;
+        }
+        direct.insert((declared.ecosystem, declared.name.clone()));
+        let entry = components
+            .entry((declared.ecosystem, declared.name.clone()))
+            .or_insert_with(|| declared.clone());
+        if entry.version.is_none() {
+            entry.version = declared.version;
+        }
+    }
+
+    // Everything the declared dependencies require is installed with them
+    if let Some(environment) = environment {
+        let mut listed = BTreeSet::new();
+        while let Some(key) = queue.pop_front() {
+            if !listed.insert(key) {
+                continue;
+            }
+            let distribution = &environment.distributions[key];
+            let (ecosystem, name) = installed_identity(environment, key);
+            let requires = distribution
+                .requires
+                .iter()
+                .filter(|required| environment.distributions.contains_key(*required));
+            let component = Component {
+                ecosystem,
+                name: name.clone(),
+                version: distribution
+                    .version
+                    .clone()
+                    .or_else(|| pinned.get(key).cloned()),
+                hashes: HASH_ALGORITHMS
+                    .iter()
+                    .filter_map(|(algorithm, alg)| {
+                        Some((*alg, distribution.hashes.get(*algorithm)?.clone()))
+                    })
+                    .collect(),
+                depends_on: requires
+                    .clone()
+                    .map(|required| installed_identity(environment, required))
+                    .collect(),
+            };
+            queue.extend(requires.map(String::as_str));
+            components.insert((ecosystem, name), component);
+        }
+    }
+
+    let purl = |identity: &(&'static str, String)| components[identity].purl();
+    let root_ref = format!("project:{}", name);
+    let mut graph = vec![json!({
+        "ref": root_ref,
+        "dependsOn": direct.iter().map(purl).collect::<BTreeSet<_>>(),
+    })];
+    graph.extend(components.values().map(|component| {
+        json!({
+            "ref": component.purl(),
+            "dependsOn": component.depends_on.iter().map(purl).collect::<BTreeSet<_>>(),
+        })
+    }));
+
+    json!({
+        "bomFormat": "CycloneDX",
+        "specVersion": SPEC_VERSION,
+        "version": 1,
+        "metadata": {
+            "tools": {
+                "components": [{
+                    "type": "application",
+                    "name": "depwise",
+                    "version": env!("CARGO_PKG_VERSION"),
+                }],
+            },
+            "component": {
+                "type": "application",
+                "bom-ref": root_ref,
+                "name": name,
+            },
+        },
+        "components": components.values().map(Component::to_json).collect::<Vec<_>>(),
+        "dependencies": graph,
+    })
+}
+
+/// The component a dependency declares, before it's looked up in the environment
+fn declared_component(dependency: &Dependency) -> Option<Component> {
+    match dependency {
+        Dependency::PyPI(requirement) => Some(Component {
+            ecosystem: "pypi",
+            name: name_map::normalize_name(requirement.name.as_ref()),
+            version: exact_version(dependency),
+            ..Component::default()
+        }),
+        Dependency::Conda(spec) => Some(Component {
+            ecosystem: "conda",
+            name: spec.name().to_lowercase(),
+            version: exact_version(dependency),
+            ..Component::default()
+        }),
+        Dependency::PackageUrl { package, .. }
+        | Dependency::PackagePath { package, .. }
+        | Dependency::PackageVcs { package, .. } => {
+            // Without a name there's nothing to identify the package by
+            let package = package.as_ref()?;
+            Some(Component {
+                ecosystem: "pypi",
+                name: package.name.clone(),
+                version: package.version.clone(),
+                ..Component::default()
+            })
+        }
+    }
+}
+
+/// The name of the distribution a dependency installed in the environment, if it did
+fn installed_name<'a>(
+    environment: &'a ResolvedEnvironment,
+    dependency: &Dependency,
+    name: &str,
+) -> Option<&'a str> {
+    let conda_package = match dependency {
+        Dependency::Conda(spec) => Some(spec.name()),
+        _ => None,
+    };
+    // A conda package is found by its own name, or by the name of the distribution it
+    // installs, like `pyyaml` for `yaml`
+    let names = [conda_package.and_then(name_map::conda_to_pypi), Some(name)];
+    environment
+        .distributions
+        .iter()
+        .find(|(_, distribution)| {
+            conda_package.is_some() && distribution.conda_package.as_deref() == conda_package
+        })
+        .or_else(|| {
+            environment.distributions.iter().find(|(installed, _)| {
+                let installed = name_map::normalize_name(installed);
+                names.iter().flatten().any(|name| installed == *name)
+            })
+        })
+        .map(|(installed, _)| installed.as_str())
+}
+
+/// The identity of a distribution of the environment as a component: the conda package it
+/// was installed from, or its PyPI name
+fn installed_identity(environment: &ResolvedEnvironment, name: &str) -> (&'static str, String) {
+    match &environment.distributions[name].conda_package {
+        Some(package) => ("conda", package.clone()),
+        None => ("pypi", name_map::normalize_name(name)),
+    }
+}
+
+/// The version a dependency is pinned to with `==`, if any
+fn exact_version(dependency: &Dependency) -> Option<String> {
+    match dependency {
+        Dependency::PyPI(requirement) => match &requirement.version_or_url {
+            Some(VersionOrUrl::VersionSpecifier(specifiers)) => match &specifiers[..] {
+                [specifier] if *specifier.operator() == Operator::Equal => {
+                    Some(specifier.version().to_string())
+                }
+                _ => None,
+            },
+            _ => None,
+        },
+        Dependency::Conda(spec) => {
+            let constraint = spec.raw_spec().split("::").last()?.trim();
+            let version = constraint
+                .strip_prefix(spec.name())?
+                .trim()
+                .strip_prefix("==")?;
+            let version = version.trim();
+            (!version.is_empty() && !version.contains(['*', ',', '|', ' ', '[']))
+                .then(|| version.to_string())
+        }
+        Dependency::PackageUrl { .. }
+        | Dependency::PackagePath { .. }
+        | Dependency::PackageVcs { .. } => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::env_backend::InstalledDistribution;
+    use crate::project::{CondaMatchSpec, PyPIRequirement};
+
+    fn pypi(requirement: &str) -> Dependency {
+        Dependency::PyPI(PyPIRequirement::from_str(requirement).unwrap())
+    }
+
+    #[test]
+    fn test_cyclonedx_document() {
+        let dependencies = vec![
+            pypi("requests==2.31.0"),
+            pypi("Flask_Login>=0.6"),
+            pypi("requests"),
+            Dependency::Conda(CondaMatchSpec::new("conda-forge::numpy==1.26.4")),
+            Dependency::Conda(CondaMatchSpec::new("python>=3.11")),
+        ];
+        let sbom = document("example", &dependencies, None);
+
+        assert_eq!(sbom["bomFormat"], "CycloneDX");
+        assert_eq!(sbom["specVersion"], "1.5");
+        assert_eq!(sbom["version"], 1);
+        assert_eq!(sbom["metadata"]["component"]["name"], "example");
+
+        let purls: Vec<&str> = sbom["components"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|c| c["purl"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            purls,
+            [
+                "pkg:conda/numpy@1.26.4",
+                "pkg:conda/python",
+                "pkg:pypi/flask-login",
+                "pkg:pypi/requests@2.31.0",
+            ]
+        );
+        assert_graph(&sbom);
+        assert_eq!(
+            sbom["dependencies"][0]["dependsOn"]
+                .as_array()
+                .unwrap()
+                .len(),
+            4
+        );
+    }
+
+    #[test]
+    fn test_cyclonedx_environment() {
+        // A pixi environment, with conda packages and a PyPI one installed on top
+        let mut environment = ResolvedEnvironment::default();
+        let mut install = |name: &str, version: &str, conda: Option<&str>, requires: &[&str]| {
+            environment.distributions.insert(
+                name.to_string(),
+                InstalledDistribution {
+                    version: Some(version.to_string()),
+                    conda_package: conda.map(str::to_string),
+                    requires: requires.iter().map(|r| r.to_string()).collect(),
+                    hashes: BTreeMap::from([
+                        ("sha256".to_string(), format!("{:064}", version.len())),
+                        ("blake2b".to_string(), "ab".to_string()),
+                    ]),
+                    ..InstalledDistribution::default()
+                },
+            );
+        };
+        install("python", "3.12.3", Some("python"), &[]);
+        install("numpy", "1.26.4", Some("numpy"), &["python"]);
+        install("PyYAML", "6.0.1", Some("pyyaml"), &["python", "yaml"]);
+        install("yaml", "0.2.5", Some("yaml"), &[]);
+        install("pip", "24.0", Some("pip"), &["python"]);
+        install("rich", "13.7.1", None, &["markdown-it-py", "Pygments"]);
+        install("markdown-it-py", "3.0.0", None, &[]);
+        install("Pygments", "2.18.0", None, &[]);
+
+        let dependencies = vec![
+            Dependency::Conda(CondaMatchSpec::new("python>=3.11")),
+            Dependency::Conda(CondaMatchSpec::new("numpy")),
+            Dependency::Conda(CondaMatchSpec::new("pyyaml")),
+            pypi("rich"),
+            pypi("flask==3.0.0"),
+        ];
+        let sbom = document("example", &dependencies, Some(&environment));
+
+        // Everything the declared dependencies install is listed, but not what they don't
+        // require, like pip
+        let components = sbom["components"].as_array().unwrap();
+        let purls: Vec<&str> = components
+            .iter()
+            .map(|c| c["purl"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            purls,
+            [
+                "pkg:conda/numpy@1.26.4",
+                "pkg:conda/python@3.12.3",
+                "pkg:conda/pyyaml@6.0.1",
+                "pkg:conda/yaml@0.2.5",
+                "pkg:pypi/flask@3.0.0",
+                "pkg:pypi/markdown-it-py@3.0.0",
+                "pkg:pypi/pygments@2.18.0",
+                "pkg:pypi/rich@13.7.1",
+            ]
+        );
+        // Hashes CycloneDX has no name for are left out
+        assert_eq!(
+            components[2]["hashes"],
+            json!([{ "alg": "SHA-256", "content": format!("{:064}", 5) }])
+        );
+        assert!(components[4].get("hashes").is_none());
+
+        assert_graph(&sbom);
+        let depends_on = |purl: &str| {
+            let graph = sbom["dependencies"].as_array().unwrap();
+            let node = graph.iter().find(|node| node["ref"] == purl).unwrap();
+            node["dependsOn"].clone()
+        };
+        assert_eq!(
+            depends_on("project:example"),
+            json!([
+                "pkg:conda/numpy@1.26.4",
+                "pkg:conda/python@3.12.3",
+                "pkg:conda/pyyaml@6.0.1",
+                "pkg:pypi/flask@3.0.0",
+                "pkg:pypi/rich@13.7.1",
+            ])
+        );
+        assert_eq!(
+            depends_on("pkg:conda/pyyaml@6.0.1"),
+            json!(["pkg:conda/python@3.12.3", "pkg:conda/yaml@0.2.5"])
+        );
+        assert_eq!(
+            depends_on("pkg:pypi/rich@13.7.1"),
+            json!(["pkg:pypi/markdown-it-py@3.0.0", "pkg:pypi/pygments@2.18.0"])
+        );
+        assert_eq!(depends_on("pkg:pypi/flask@3.0.0"), json!([]));
+    }
+
+    /// Every component has a unique bom-ref, and every reference in the dependency graph
+    /// points at the project or a component
+    fn assert_graph(sbom: &Value) {
+        let mut refs = HashSet::new();
+        refs.insert(sbom["metadata"]["component"]["bom-ref"].as_str().unwrap());
+        for component in sbom["components"].as_array().unwrap() {
+            for field in ["type", "name", "bom-ref"] {
+                assert!(component[field].is_string(), "{} missing", field);
+            }
+            assert!(refs.insert(component["bom-ref"].as_str().unwrap()));
+        }
+        let graph = sbom["dependencies"].as_array().unwrap();
+        assert_eq!(graph.len(), refs.len());
+        for node in graph {
+            assert!(refs.contains(node["ref"].as_str().unwrap()));
+            for dependency in node["dependsOn"].as_array().unwrap() {
+                assert!(refs.contains(dependency.as_str().unwrap()));
+            }
+        }
+    }
+}
@@ -0,0 +1,71 @@
+#[cfg(feature = "python-parsing")]
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// How much work an analysis did and where its time went, to tune excludes and caches.
+///
+/// Every Python file found is counted once: as cached, parsed, without imports, or skipped.
+/// The counts are exact. The times are wall time, and the time spent resolving the
+/// environment is only counted by the first check after it was resolved.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Stats {
+    /// Python files found in the project, after excludes
+    pub files_discovered: usize,
+    /// Files whose parse was kept from an earlier check of the same session
+    pub files_cached: usize,
+    /// Files read and parsed
+    pub files_parsed: usize,
+    /// Files read but never parsed, since they don't mention `import` anywhere
+    pub files_without_imports: usize,
+    /// Files that couldn't be read or parsed
+    pub files_skipped: usize,
+    /// The size of the files parsed, in bytes
+    pub bytes_parsed: u64,
+    /// Finding the project's Python files
+    pub discovery: Duration,
+    /// Reading and parsing the files that weren't cached
+    pub parsing: Duration,
+    /// Reading the dependency sources and asking the backend what they provide
+    pub resolving: Duration,
+    /// Matching the imports against the declared dependencies
+    pub matching: Duration,
+    /// Channel repodata downloaded by the simulated backend
+    pub repodata_downloads: usize,
+    /// Channel repodata the simulated backend read from its cache instead
+    pub repodata_cache_hits: usize,
+}
+
+/// The counters of [`Stats`] bumped by the threads parsing files
+#[cfg(feature = "python-parsing")]
+#[derive(Debug, Default)]
+pub(crate) struct ScanCounters {
+    files_parsed: AtomicUsize,
+    files_without_imports: AtomicUsize,
+    files_skipped: AtomicUsize,
+    bytes_parsed: AtomicU64,
+}
+
+#[cfg(feature = "python-parsing")]
+impl ScanCounters {
+    pub(crate) fn parsed(&self, bytes: usize) {
+        self.files_parsed.fetch_add(1, Ordering::Relaxed);
+        self.bytes_parsed.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn without_imports(&self) {
+        self.files_without_imports.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn skipped(&self) {
+        self.files_skipped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Add the counts to `stats`, once every thread is done
+    pub(crate) fn add_to(&self, stats: &mut Stats) {
+        stats.files_parsed += self.files_parsed.load(Ordering::Relaxed);
+        stats.files_without_imports += self.files_without_imports.load(Ordering::Relaxed);
+        stats.files_skipped += self.files_skipped.load(Ordering::Relaxed);
+        stats.bytes_parsed += self.bytes_parsed.load(Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,708 @@
+use crate::PythonVersion;
+
+/// Top-level modules of the Python standard library (from `sys.stdlib_module_names`).
+///
+/// Kept sorted so lookups can use a binary search.
+const STDLIB_MODULES: &[&str] = &[
+    "__future__",
+    "_abc",
+    "_aix_support",
+    "_ast",
+    "_asyncio",
+    "_bisect",
+    "_blake2",
+    "_bootsubprocess",
+    "_bz2",
+    "_codecs",
+    "_codecs_cn",
+    "_codecs_hk",
+    "_codecs_iso2022",
+    "_codecs_jp",
+    "_codecs_kr",
+    "_codecs_tw",
+    "_collections",
+    "_collections_abc",
+    "_compat_pickle",
+    "_compression",
+    "_contextvars",
+    "_crypt",
+    "_csv",
+    "_ctypes",
+    "_curses",
+    "_curses_panel",
+    "_datetime",
+    "_dbm",
+    "_decimal",
+    "_elementtree",
+    "_frozen_importlib",
+    "_frozen_importlib_external",
+    "_functools",
+    "_gdbm",
+    "_hashlib",
+    "_heapq",
+    "_imp",
+    "_io",
+    "_json",
+    "_locale",
+    "_lsprof",
+    "_lzma",
+    "_markupbase",
+    "_md5",
+    "_msi",
+    "_multibytecodec",
+    "_multiprocessing",
+    "_opcode",
+    "_operator",
+    "_osx_support",
+    "_overlapped",
+    "_pickle",
+    "_posixshmem",
+    "_posixsubprocess",
+    "_py_abc",
+    "_pydecimal",
+    "_pyio",
+    "_queue",
+    "_random",
+    "_scproxy",
+    "_sha1",
+    "_sha256",
+    "_sha3",
+    "_sha512",
+    "_signal",
+    "_sitebuiltins",
+    "_socket",
+    "_sqlite3",
+    "_sre",
+    "_ssl",
+    "_stat",
+    "_statistics",
+    "_string",
+    "_strptime",
+    "_struct",
+    "_symtable",
+    "_thread",
+    "_threading_local",
+    "_tkinter",
+    "_tokenize",
+    "_tracemalloc",
+    "_typing",
+    "_uuid",
+    "_warnings",
+    "_weakref",
+    "_weakrefset",
+    "_winapi",
+    "_zoneinfo",
+    "abc",
+    "aifc",
+    "antigravity",
+    "argparse",
+    "array",
+    "ast",
+    "asynchat",
+    "asyncio",
+    "asyncore",
+    "atexit",
+    "audioop",
+    "base64",
+    "bdb",
+    "binascii",
+    "bisect",
+    "builtins",
+    "bz2",
+    "cProfile",
+    "calendar",
+    "cgi",
+    "cgitb",
+    "chunk",
+    "cmath",
+    "cmd",
+    "code",
+    "codecs",
+    "codeop",
+    "collections",
+    "colorsys",
+    "compileall",
+    "concurrent",
+    "configparser",
+    "contextlib",
+    "contextvars",
+    "copy",
+    "copyreg",
+    "crypt",
+    "csv",
+    "ctypes",
+    "curses",
+    "dataclasses",
+    "datetime",
+    "dbm",
+    "decimal",
+    "difflib",
+    "dis",
+    "distutils",
+    "doctest",
+    "email",
+    "encodings",
+    "ensurepip",
+    "enum",
+    "errno",
+    "faulthandler",
+    "fcntl",
+    "filecmp",
+    "fileinput",
+    "fnmatch",
+    "fractions",
+    "ftplib",
+    "functools",
+    "gc",
+    "genericpath",
+    "getopt",
+    "getpass",
+    "gettext",
+    "glob",
+    "graphlib",
+    "grp",
+    "gzip",
+    "hashlib",
+    "heapq",
+    "hmac",
+    "html",
+    "http",
+    "idlelib",
+    "imaplib",
+    "imghdr",
+    "imp",
+    "importlib",
+    "inspect",
+    "io",
+    "ipaddress",
+    "itertools",
+    "json",
+    "keyword",
+    "lib2to3",
+    "linecache",
+    "locale",
+    "logging",
+    "lzma",
+    "mailbox",
+    "mailcap",
+    "marshal",
+    "math",
+    "mimetypes",
+    "mmap",
+    "modulefinder",
+    "msilib",
+    "msvcrt",
+    "multiprocessing",
+    "netrc",
+    "nis",
+    "nntplib",
+    "nt",
+    "ntpath",
+    "nturl2path",
+    "numbers",
+    "opcode",
+    "operator",
+    "optparse",
+    "os",
+    "ossaudiodev",
+    "pathlib",
+    "pdb",
+    "pickle",
+    "pickletools",
+    "pipes",
+    "pkgutil",
+    "platform",
+    "plistlib",
+    "poplib",
+    "posix",
+    "posixpath",
+    "pprint",
+    "profile",
+    "pstats",
+    "pty",
+    "pwd",
+    "py_compile",
+    "pyclbr",
+    "pydoc",
+    "pydoc_data",
+    "pyexpat",
+    "queue",
+    "quopri",
+    "random",
+    "re",
+    "readline",
+    "reprlib",
+    "resource",
+    "rlcompleter",
+    "runpy",
+    "sched",
+    "secrets",
+    "select",
+    "selectors",
+    "shelve",
+    "shlex",
+    "shutil",
+    "signal",
+    "site",
+    "smtpd",
+    "smtplib",
+    "sndhdr",
+    "socket",
+    "socketserver",
+    "spwd",
+    "sqlite3",
+    "sre_compile",
+    "sre_constants",
+    "sre_parse",
+    "ssl",
+    "stat",
+    "statistics",
+    "string",
+    "stringprep",
+    "struct",
+    "subprocess",
+    "sunau",
+    "symtable",
+    "sys",
+    "sysconfig",
+    "syslog",
+    "tabnanny",
+    "tarfile",
+    "telnetlib",
+    "tempfile",
+    "termios",
+    "textwrap",
+    "this",
+    "threading",
+    "time",
+    "timeit",
+    "tkinter",
+    "token",
+    "tokenize",
+    "tomllib",
+    "trace",
+    "traceback",
+    "tracemalloc",
+    "tty",
+    "turtle",
+    "turtledemo",
+    "types",
+    "typing",
+    "unicodedata",
+    "unittest",
+    "urllib",
+    "uu",
+    "uuid",
+    "venv",
+    "warnings",
+    "wave",
+    "weakref",
+    "webbrowser",
+    "winreg",
+    "winsound",
+    "wsgiref",
+    "xdrlib",
+    "xml",
+    "xmlrpc",
+    "zipapp",
+    "zipfile",
+    "zipimport",
+    "zlib",
+    "zoneinfo",
+];
+
+/// Standard library modules added after Python 3.8, with the version that added them
+const ADDED: &[(&str, PythonVersion)] = &[
+    ("graphlib", PythonVersion::new(3, 9)),
+    ("tomllib", PythonVersion::new(3, 11)),
+    ("zoneinfo", PythonVersion::new(3, 9)),
+];
+
+/// Standard library modules that were removed, with the first version without them and
+/// what replaces them, if anything does
+const REMOVED: &[(&str, PythonVersion, Option<&str>)] = &[
+    ("aifc", PythonVersion::new(3, 13), None),
+    ("asynchat", PythonVersion::new(3, 12), Some("asyncio")),
+    ("asyncore", PythonVersion::new(3, 12), Some("asyncio")),
+    ("audioop", PythonVersion::new(3, 13), None),
+    ("cgi", PythonVersion::new(3, 13), Some("email.message")),
+    ("cgitb", PythonVersion::new(3, 13), None),
+    ("chunk", PythonVersion::new(3, 13), None),
+    ("crypt", PythonVersion::new(3, 13), Some("hashlib")),
+    ("distutils", PythonVersion::new(3, 12), Some("setuptools")),
+    ("imghdr", PythonVersion::new(3, 13), None),
+    ("imp", PythonVersion::new(3, 12), Some("importlib")),
+    ("lib2to3", PythonVersion::new(3, 13), None),
+    ("mailcap", PythonVersion::new(3, 13), Some("mimetypes")),
+    ("msilib", PythonVersion::new(3, 13), None),
+    ("nis", PythonVersion::new(3, 13), None),
+    ("nntplib", PythonVersion::new(3, 13), None),
+    ("ossaudiodev", PythonVersion::new(3, 13), None),
+    ("pipes", PythonVersion::new(3, 13), Some("shlex")),
+    ("smtpd", PythonVersion::new(3, 12), Some("aiosmtpd")),
+    ("sndhdr", PythonVersion::new(3, 13), None),
+    ("spwd", PythonVersion::new(3, 13), None),
+    ("sunau", PythonVersion::new(3, 13), None),
+    ("telnetlib", PythonVersion::new(3, 13), Some("telnetlib3")),
+    ("uu", PythonVersion::new(3, 13), Some("base64")),
+    ("xdrlib", PythonVersion::new(3, 13), None),
+];
+
+/// Standard library functions and classes that were removed from modules that are still
+/// there, as `module.name`
+const REMOVED_NAMES: &[(&str, PythonVersion, Option<&str>)] = &[
+    (
+        "asyncio.coroutine",
+        PythonVersion::new(3, 11),
+        Some("async def"),
+    ),
+    (
+        "collections.Mapping",
+        PythonVersion::new(3, 10),
+        Some("collections.abc.Mapping"),
+    ),
+    (
+        "collections.MutableMapping",
+        PythonVersion::new(3, 10),
+        Some("collections.abc.MutableMapping"),
+    ),
+    (
+        "collections.Sequence",
+        PythonVersion::new(3, 10),
+        Some("collections.abc.Sequence"),
+    ),
+    (
+        "inspect.getargspec",
+        PythonVersion::new(3, 11),
+        Some("inspect.signature"),
+    ),
+];
+
+/// A distribution on PyPI that backports a part of the standard library to the Python
+/// versions before it was added
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Backport {
+    /// The module, or `module.name` for a function or class, in the standard library
+    pub name: &'static str,
+    /// The first Python version with it
+    pub added: PythonVersion,
+    /// The module the backport is imported as
+    pub module: &'static str,
+    /// The distribution that installs the backport
+    pub distribution: &'static str,
+}
+
+const fn backport(
+    name: &'static str,
+    added: PythonVersion,
+    module: &'static str,
+    distribution: &'static str,
+) -> Backport {
+    Backport {
+        name,
+        added,
+        module,
+        distribution,
+    }
+}
+
+/// Parts of the standard library that projects supporting older Pythons import from a
+/// backport when they're missing, sorted by name
+const BACKPORTS: &[Backport] = &[
+    backport(
+        "asyncio.timeout",
+        PythonVersion::new(3, 11),
+        "async_timeout",
+        "async-timeout",
+    ),
+    backport(
+        "functools.cached_property",
+        PythonVersion::new(3, 8),
+        "cached_property",
+        "cached-property",
+    ),
+    backport(
+        "importlib.metadata",
+        PythonVersion::new(3, 8),
+        "importlib_metadata",
+        "importlib-metadata",
+    ),
+    backport(
+        "importlib.resources.files",
+        PythonVersion::new(3, 9),
+        "importlib_resources",
+        "importlib-resources",
+    ),
+    backport("tomllib", PythonVersion::new(3, 11), "tomli", "tomli"),
+    backport(
+        "typing.Literal",
+        PythonVersion::new(3, 8),
+        "typing_extensions",
+        "typing-extensions",
+    ),
+    backport(
+        "typing.ParamSpec",
+        PythonVersion::new(3, 10),
+        "typing_extensions",
+        "typing-extensions",
+    ),
+    backport(
+        "typing.Protocol",
+        PythonVersion::new(3, 8),
+        "typing_extensions",
+        "typing-extensions",
+    ),
+    backport(
+        "typing.Self",
+        PythonVersion::new(3, 11),
+        "typing_extensions",
+        "typing-extensions",
+    ),
+    backport(
+        "typing.TypeAlias",
+        PythonVersion::new(3, 10),
+        "typing_extensions",
+        "typing-extensions",
+    ),
+    backport(
+        "typing.TypedDict",
+        PythonVersion::new(3, 8),
+        "typing_extensions",
+        "typing-extensions",
+    ),
+    backport(
+        "typing.override",
+        PythonVersion::new(3, 12),
+        "typing_extensions",
+        "typing-extensions",
+    ),
+    backport(
+        "zoneinfo",
+        PythonVersion::new(3, 9),
+        "backports.zoneinfo",
+        "backports.zoneinfo",
+    ),
+];
+
+/// Whether a dotted name is `prefix` or is under it
+fn is_within(name: &str, prefix: &str) -> bool {
+    name.strip_prefix(prefix)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+}
+
+/// Find the backport a pair of imports falls back between: an import of `module` from the
+/// standard library, along with the `names` imported from it, and an import of `other` in
+/// its place.
+///
+/// ```
+/// use depwise_analysis::stdlib::backport_of;
+///
+/// let names = vec!["cached_property".to_string()];
+/// let backport = backport_of("functools", &names, "cached_property").unwrap();
+/// assert_eq!(backport.distribution, "cached-property");
+/// assert!(backport_of("functools", &names, "boltons").is_none());
+/// ```
+pub fn backport_of(module: &str, names: &[String], other: &str) -> Option<Backport> {
+    BACKPORTS.iter().copied().find(|backport| {
+        is_within(other, backport.module)
+            && (is_within(module, backport.name)
+                || names
+                    .iter()
+                    .any(|name| is_within(&format!("{}.{}", module, name), backport.name)))
+    })
+}
+
+/// The backport of [`backport_of`] a module is, or is under, like `tomli` or
+/// `backports.zoneinfo`
+pub fn backport_module(module: &str) -> Option<&'static str> {
+    BACKPORTS
+        .iter()
+        .map(|backport| backport.module)
+        .find(|backport| is_within(module, backport))
+}
+
+/// A part of the standard library that was removed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Removal {
+    /// The module, or `module.name` for a function or class, that was removed
+    pub name: &'static str,
+    /// The first Python version without it
+    pub version: PythonVersion,
+    /// What to use instead, if anything in the standard library or on PyPI replaces it
+    pub replacement: Option<&'static str>,
+}
+
+/// Find out whether an import uses a part of the standard library that was removed by a
+/// given Python version: the module itself, or one of the `names` imported from it.
+///
+/// ```
+/// use depwise_analysis::PythonVersion;
+/// use depwise_analysis::stdlib::removal;
+///
+/// let imp = removal("imp", &[], PythonVersion::new(3, 12)).unwrap();
+/// assert_eq!(imp.replacement, Some("importlib"));
+/// assert!(removal("imp", &[], PythonVersion::new(3, 11)).is_none());
+/// ```
+pub fn removal(module: &str, names: &[String], version: PythonVersion) -> Option<Removal> {
+    let top_level = module.split('.').next().unwrap_or(module);
+    let found = |table: &'static [(&'static str, PythonVersion, Option<&'static str>)],
+                 name: &str| {
+        table
+            .binary_search_by_key(&name, |(name, _, _)| name)
+            .ok()
+            .map(|i| table[i])
+            .filter(|(_, removed, _)| version >= *removed)
+            .map(|(name, version, replacement)| Removal {
+                name,
+                version,
+                replacement,
+            })
+    };
+    found(REMOVED, top_level).or_else(|| {
+        names
+            .iter()
+            .find_map(|name| found(REMOVED_NAMES, &format!("{}.{}", module, name)))
+    })
+}
+
+/// Check if a module is part of the Python standard library.
+///
+/// Dotted module paths are classified by their top-level package, so both `xml` and
+/// `xml.etree.ElementTree` are standard library modules.
+pub fn is_stdlib(module: &str) -> bool {
+    let top_level = module.split('.').next().unwrap_or(module);
+    STDLIB_MODULES.binary_search(&top_level).is_ok()
+}
+
+/// Check if a module is part of the standard library of a given Python version.
+///
+/// Unlike [`is_stdlib`], modules that were added after or removed before that version
+/// aren't, so `tomllib` is a third-party import on Python 3.10 and `distutils` is one on
+/// Python 3.12.
+pub fn is_stdlib_in(module: &str, version: PythonVersion) -> bool {
+    let top_level = module.split('.').next().unwrap_or(module);
+    let lookup = |table: &[(&str, PythonVersion)]| {
+        table
+            .binary_search_by_key(&top_level, |(name, _)| name)
+            .ok()
+            .map(|i| table[i].1)
+    };
+    if lookup(ADDED).is_some_and(|added| version < added) {
+        return false;
+    }
+    if removal(top_level, &[], version).is_some() {
+        return false;
+    }
+    is_stdlib(module)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stdlib_modules_sorted() {
+        assert!(STDLIB_MODULES.windows(2).all(|w| w[0] < w[1]));
+        assert!(ADDED.windows(2).all(|w| w[0].0 < w[1].0));
+        assert!(ADDED.iter().all(|(name, _)| is_stdlib(name)));
+        for table in [REMOVED, REMOVED_NAMES] {
+            assert!(table.windows(2).all(|w| w[0].0 < w[1].0));
+            assert!(table.iter().all(|(name, _, _)| is_stdlib(name)));
+        }
+        assert!(BACKPORTS.windows(2).all(|w| w[0].name < w[1].name));
+        assert!(BACKPORTS.iter().all(|backport| is_stdlib(backport.name)));
+    }
+
+    #[test]
+    fn test_backport_of() {
+        let backport = |module, names: &[&str], other| {
+            let names: Vec<String> = names.iter().map(|name| name.to_string()).collect();
+            backport_of(module, &names, other).map(|backport| backport.distribution)
+        };
+        assert_eq!(backport("tomllib", &[], "tomli"), Some("tomli"));
+        assert_eq!(
+            backport("importlib", &["metadata"], "importlib_metadata"),
+            Some("importlib-metadata")
+        );
+        assert_eq!(
+            backport("typing", &["Any", "Self"], "typing_extensions"),
+            Some("typing-extensions")
+        );
+        assert_eq!(
+            backport("zoneinfo", &["ZoneInfo"], "backports.zoneinfo"),
+            Some("backports.zoneinfo")
+        );
+        // importlib.resources was there long before `files`
+        assert_eq!(
+            backport("importlib.resources", &["path"], "importlib_resources"),
+            None
+        );
+        assert_eq!(backport("tomllib", &[], "tomlkit"), None);
+        assert_eq!(
+            backport_module("backports.zoneinfo._common"),
+            Some("backports.zoneinfo")
+        );
+        assert_eq!(backport_module("backports"), None);
+    }
+
+    #[test]
+    fn test_is_stdlib_in_version() {
+        let py310 = PythonVersion::new(3, 10);
+        let py312 = PythonVersion::new(3, 12);
+        assert!(!is_stdlib_in("tomllib", py310));
+        assert!(is_stdlib_in("tomllib", py312));
+        assert!(is_stdlib_in("distutils.core", py310));
+        assert!(!is_stdlib_in("distutils.core", py312));
+        assert!(is_stdlib_in("telnetlib", py312));
+        assert!(!is_stdlib_in("telnetlib", PythonVersion::new(3, 13)));
+        assert!(is_stdlib_in("os", py312));
+        assert!(!is_stdlib_in("requests", py312));
+    }
+
+    #[test]
+    fn test_removal() {
+        let py312 = PythonVersion::new(3, 12);
+        let removal = |module, names: &[&str], version| {
+            let names: Vec<String> = names.iter().map(|name| name.to_string()).collect();
+            removal(module, &names, version).map(|removal| (removal.name, removal.replacement))
+        };
+        assert_eq!(
+            removal("distutils.core", &[], py312),
+            Some(("distutils", Some("setuptools")))
+        );
+        assert_eq!(removal("cgi", &[], py312), None);
+        assert_eq!(
+            removal("cgi", &[], PythonVersion::new(3, 13)),
+            Some(("cgi", Some("email.message")))
+        );
+        assert_eq!(
+            removal("nntplib", &[], PythonVersion::new(3, 13)),
+            Some(("nntplib", None))
+        );
+        assert_eq!(
+            removal("asyncio", &["sleep", "coroutine"], py312),
+            Some(("asyncio.coroutine", Some("async def")))
+        );
+        assert_eq!(removal("asyncio", &["sleep"], py312), None);
+        assert_eq!(removal("os", &[], py312), None);
+    }
+
+    #[test]
+    fn test_is_stdlib() {
+        assert!(is_stdlib("os"));
+        assert!(is_stdlib("__future__"));
+        assert!(is_stdlib("tomllib"));
+        assert!(!is_stdlib("requests"));
+        assert!(!is_stdlib("numpy"));
+    }
+
+    #[test]
+    fn test_is_stdlib_dotted() {
+        assert!(is_stdlib("xml"));
+        assert!(is_stdlib("xml.etree.ElementTree"));
+        assert!(is_stdlib("concurrent.futures"));
+        assert!(is_stdlib("os.path"));
+        assert!(!is_stdlib("requests.adapters"));
+        assert!(!is_stdlib("xmlsec"));
+    }
+}
@@ -0,0 +1,727 @@
+use std::collections::BTreeSet;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use tracing::{debug, trace};
+
+use crate::error::AnalysisError;
+use crate::parser::PythonParser;
+
+/// Directories that never contain project sources, left out by default along with hidden
+/// directories like `.venv` and `.git`
+pub const DEFAULT_EXCLUDED_DIRECTORIES: &[&str] =
+    &["__pycache__", "node_modules", "site-packages", "venv"];
+
+fn is_skipped_directory(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with('.') || DEFAULT_EXCLUDED_DIRECTORIES.contains(&name))
+}
+
+/// Check whether a directory is a virtual environment: a venv with a `pyvenv.cfg`, a conda
+/// environment with a `conda-meta` directory, or a directory holding a `site-packages`, like
+/// `lib/python3.12` of a venv whose root was excluded. Their packages are never project code.
+pub fn is_virtual_environment(dir: &Path) -> bool {
+    virtual_environment_marker(dir).is_some()
+}
+
+/// The entry that makes a directory a virtual environment, if it is one
+fn virtual_environment_marker(dir: &Path) -> Option<&'static str> {
+    if dir.join("pyvenv.cfg").is_file() {
+        Some("pyvenv.cfg")
+    } else if dir.join("conda-meta").is_dir() {
+        Some("conda-meta")
+    } else if dir.join("site-packages").is_dir() {
+        Some("site-packages")
+    } else {
+        None
+    }
+}
+
+/// What identifies a directory however it's reached: its device and inode
+#[cfg(unix)]
+type DirectoryId = (u64, u64);
+
+/// What identifies a directory however it's reached: its canonical path
+#[cfg(not(unix))]
+type DirectoryId = PathBuf;
+
+#[cfg(unix)]
+fn directory_id(dir: &Path) -> Option<DirectoryId> {
+    use std::os::unix::fs::MetadataExt;
+    let metadata = fs::metadata(dir).ok()?;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn directory_id(dir: &Path) -> Option<DirectoryId> {
+    fs::canonicalize(dir).ok()
+}
+
+/// Check whether a file is in a directory that is left out by default, like `.venv` or
+/// `node_modules`. The path should be relative to the project root.
+pub fn is_default_excluded(path: &Path) -> bool {
+    path.parent()
+        .into_iter()
+        .flat_map(Path::components)
+        .any(|component| is_skipped_directory(Path::new(component.as_os_str())))
+}
+
+fn is_python_file(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext == "py" || ext == "pyi")
+}
+
+/// Check whether a file is documentation whose code blocks can be analyzed: Markdown or
+/// reStructuredText
+pub fn is_doc_file(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext == "md" || ext == "markdown" || ext == "rst")
+}
+
+/// Check whether a file looks like test code: it is under a `test` or `tests` directory, or
+/// is named `test_*.py`, `*_test.py`, `conftest.py`, or `noxfile.py`. The path should be
+/// relative to the project root so the directories the project itself is in aren't
+/// considered.
+pub fn is_test_file(path: &Path) -> bool {
+    let in_test_directory = path
+        .parent()
+        .into_iter()
+        .flat_map(Path::components)
+        .any(|component| matches!(component.as_os_str().to_str(), Some("test" | "tests")));
+    let is_test_module = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .is_some_and(|stem| {
+            stem.starts_with("test_")
+                || stem.ends_with("_test")
+                || matches!(stem, "conftest" | "noxfile")
+        });
+    in_test_directory || (is_python_file(path) && is_test_module)
+}
+
+/// Check whether a file is under one of the script directories, like `scripts`, given
+/// relative to the project root. The path should be relative to the project root too.
+pub fn is_script_file(path: &Path, script_directories: &[PathBuf]) -> bool {
+    let Some(parent) = path.parent() else {
+        return false;
+    };
+    script_directories
+        .iter()
+        .any(|directory| parent.starts_with(directory))
+}
+
+/// Check whether a file was generated, by matching it against shell-style patterns like
+/// `*_pb2.py`. A pattern without a `/` is matched against the file name, and one with a `/`
+/// against the path starting at any directory, so `migrations/*.py` matches the migrations
+/// of every Django app. The path should be relative to the project root.
+pub fn is_generated_file(path: &Path, patterns: &[String]) -> bool {
+    let path = path.to_string_lossy().replace('\\', "/");
+    let file_name = path.rsplit('/').next().unwrap_or(&path);
+    let tails: Vec<&str> = std::iter::once(path.as_str())
+        .chain(path.match_indices('/').map(|(i, _)| &path[i + 1..]))
+        .collect();
+    patterns.iter().any(|pattern| {
+        if pattern.contains('/') {
+            tails.iter().any(|tail| wildcard_match(pattern, tail))
+        } else {
+            wildcard_match(pattern, file_name)
+        }
+    })
+}
+
+/// Match a name against a shell-style pattern the way `fnmatch` does: `*` matches any run of
+/// characters, dots included, and `?` matches a single character
+pub(crate) fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // Where the last `*` was and how much of the name it matched, to backtrack to
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    p = star_p + 1;
+                    n = star_n + 1;
+                    star = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+fn read_dir_sorted(dir: &Path) -> Result<Vec<PathBuf>, AnalysisError> {
+    let entries = fs::read_dir(dir).map_err(|e| {
+        AnalysisError::FileReadError(dir.to_string_lossy().to_string(), e.to_string())
+    })?;
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .collect();
+    paths.sort();
+    Ok(paths)
+}
+
+/// The files found under a directory, and the virtual environments skipped on the way
+#[derive(Debug, Default)]
+pub struct Discovery {
+    /// The files found, in a stable order
+    pub files: Vec<PathBuf>,
+    /// The virtual environments (see [`is_virtual_environment`]) that weren't walked, with
+    /// the entry that gave each away, in a stable order. Directories left out by name, like
+    /// `.venv` with the default excludes, aren't looked at and aren't listed.
+    pub virtual_environments: Vec<(PathBuf, &'static str)>,
+}
+
+/// Find all of the Python source files under a path, in a stable order.
+///
+/// Files under any of the `excludes` paths (relative to `root`) are left out, and so are the
+/// directories in `DEFAULT_EXCLUDED_DIRECTORIES` and hidden directories unless
+/// `default_excludes` is false. Virtual environments are always left out, and listed in the
+/// result. Symlinked directories are only walked with `follow_symlinks`, and a directory
+/// reached again through a link is walked once. If the path is a file it is returned as is.
+pub fn discover_python_files(
+    root: &Path,
+    excludes: &[PathBuf],
+    default_excludes: bool,
+    follow_symlinks: bool,
+) -> Result<Discovery, AnalysisError> {
+    if root.is_file() {
+        return Ok(Discovery {
+            files: vec![root.to_path_buf()],
+            ..Default::default()
+        });
+    }
+    discover_files(
+        root,
+        excludes,
+        default_excludes,
+        follow_symlinks,
+        is_python_file,
+    )
+}
+
+/// Find the documentation files (see [`is_doc_file`]) under a directory, in a stable order,
+/// leaving out the same paths as [`discover_python_files`]
+pub fn discover_doc_files(
+    root: &Path,
+    excludes: &[PathBuf],
+    default_excludes: bool,
+    follow_symlinks: bool,
+) -> Result<Vec<PathBuf>, AnalysisError> {
+    if root.is_file() {
+        return Ok(Vec::new());
+    }
+    Ok(discover_files(
+        root,
+        excludes,
+        default_excludes,
+        follow_symlinks,
+        is_doc_file,
+    )?
+    .files)
+}
+
+fn discover_files(
+    root: &Path,
+    excludes: &[PathBuf],
+    default_excludes: bool,
+    follow_symlinks: bool,
+    is_wanted: fn(&Path) -> bool,
+) -> Result<Discovery, AnalysisError> {
+    let excludes: Vec<PathBuf> = excludes.iter().map(|path| root.join(path)).collect();
+    let mut discovery = Discovery::default();
+    let mut visited: HashSet<DirectoryId> = directory_id(root).into_iter().collect();
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let mut subdirs = Vec::new();
+        for path in read_dir_sorted(&dir)? {
+            if excludes.iter().any(|exclude| path.starts_with(exclude)) {
+                trace!("Excluding {}", path.display());
+            } else if path.is_dir() {
+                if default_excludes && is_skipped_directory(&path) {
+                    trace!("Skipping directory {}", path.display());
+                } else if !follow_symlinks && path.is_symlink() {
+                    trace!("Skipping symlinked directory {}", path.display());
+                } else if let Some(marker) = virtual_environment_marker(&path) {
+                    debug!("Skipping virtual environment {}", path.display());
+                    discovery.virtual_environments.push((path, marker));
+                } else if directory_id(&path).is_some_and(|id| !visited.insert(id)) {
+                    trace!("Skipping {}, which was already walked", path.display());
+                } else {
+                    subdirs.push(path);
+                }
+            } else if is_wanted(&path) {
+                discovery.files.push(path);
+            }
+        }
+        pending.extend(subdirs.into_iter().rev());
+    }
+    discovery.files.sort();
+    discovery.virtual_environments.sort();
+    Ok(discovery)
+}
+
+/// Find the modules that belong to the project itself.
+///
+/// Both flat layouts and `src/` layouts are supported. Directories without an `__init__.py`
+/// are treated as namespace packages (PEP 420), in which case the regular packages found
+/// inside of them are reported with their full dotted name.
+pub fn discover_first_party_modules(root: &Path) -> Vec<String> {
+    if root.is_file() {
+        return root
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .into_iter()
+            .collect();
+    }
+    discover_modules(&[root.to_path_buf(), root.join("src")])
+}
+
+/// Find the modules in the given source roots, the directories packages are imported from.
+/// Roots that don't exist are skipped.
+pub fn discover_modules(source_roots: &[PathBuf]) -> Vec<String> {
+    let mut modules = Vec::new();
+    for source_root in source_roots {
+        if source_root.is_dir() {
+            collect_modules(source_root, &[], &mut modules);
+        }
+    }
+    modules.sort();
+    modules.dedup();
+    modules
+}
+
+fn collect_modules(dir: &Path, prefix: &[String], modules: &mut Vec<String>) {
+    let Ok(paths) = read_dir_sorted(dir) else {
+        return;
+    };
+
+    for path in paths {
+        let Some(name) = path.file_name().map(|n| n.to_string_lossy().to_string()) else {
+            continue;
+        };
+
+        if path.is_dir() {
+            if is_skipped_directory(&path)
+                || name.contains('-')
+                || name == "src"
+                || is_virtual_environment(&path)
+            {
+                continue;
+            }
+            let mut module = prefix.to_vec();
+            module.push(name);
+            if path.join("__init__.py").exists() || contains_python_files(&path) {
+                modules.push(module.join("."));
+            } else if path.is_symlink() {
+                // A link may lead back to a directory above it
+                trace!("Not looking for packages under {}", path.display());
+            } else {
+                collect_modules(&path, &module, modules);
+            }
+        } else if is_python_file(&path)
+            && prefix.is_empty()
+            && let Some(stem) = path.file_stem()
+        {
+            let stem = stem.to_string_lossy();
+            if stem != "__init__" && stem != "setup" {
+                modules.push(stem.to_string());
+            }
+        }
+    }
+}
+
+/// Find the first-party module that defines a name imported from one of the project's
+/// modules, following the re-exports of package `__init__.py` files: with `from .b import
+/// Thing` in `a/__init__.py`, `from a import Thing` refers to `a.b`. The module itself is
+/// returned when it doesn't re-export the name from another first-party module, and `None`
+/// when it isn't a first-party module under `root` or `root/src`.
+pub fn defining_module(root: &Path, module: &str, name: &str) -> Option<String> {
+    let mut module = module.to_string();
+    let mut name = name.to_string();
+    let mut visited = BTreeSet::new();
+    loop {
+        let parts: PathBuf = module.split('.').collect();
+        let mut init = None;
+        let mut found = false;
+        for base in [root.to_path_buf(), root.join("src")] {
+            let package = base.join(&parts);
+            if package.join("__init__.py").is_file() {
+                init = Some(package.join("__init__.py"));
+            } else if !package.with_extension("py").is_file() {
+                continue;
+            }
+            found = true;
+            break;
+        }
+        if !found {
+            return (!visited.is_empty()).then_some(module);
+        }
+        // Only a package can re-export, and a cycle of re-exports ends where it started
+        let Some(init) = init.filter(|_| visited.insert((module.clone(), name.clone()))) else {
+            return Some(module);
+        };
+        let Some((source, imported)) = reexport(&init, &module, &name) else {
+            return Some(module);
+        };
+        trace!("{} re-exports {} from {}", module, name, source);
+        let top_level = module.split('.').next().unwrap_or_default();
+        if source.split('.').next() != Some(top_level) {
+            // Re-exported from another distribution, which is as far as first-party goes
+            return Some(module);
+        }
+        match imported {
+            Some(imported) => name = imported,
+            // `from . import b` binds the submodule itself
+            None => return Some(source),
+        }
+        module = source;
+    }
+}
+
+/// The module a package's `__init__.py` re-exports a name from, and the name it has there,
+/// which is `None` when the name is a submodule of that module, as in `from . import b`
+fn reexport(init: &Path, package: &str, name: &str) -> Option<(String, Option<String>)> {
+    let source = fs::read_to_string(init).ok()?;
+    let imports = PythonParser::new(&source).parse_imports().ok()?;
+    for import in imports
+        .iter()
+        .filter(|import| import.is_from_import && import.is_top_level_import)
+    {
+        let Some(imported) = import
+            .imported_names
+            .iter()
+            .zip(&import.imported_name_aliases)
+            .find(|(imported, alias)| alias.as_deref().unwrap_or(imported) == name)
+            .map(|(imported, _)| imported.clone())
+        else {
+            continue;
+        };
+        let from = if import.is_relative {
+            // Relative to the package itself, `..` being its parent
+            let segments: Vec<&str> = package.split('.').collect();
+            let kept = segments.len().checked_sub(import.relative_level - 1)?;
+            let mut from: Vec<&str> = segments[..kept].to_vec();
+            from.extend(
+                import
+                    .module_name
+                    .as_deref()
+                    .map(|m| m.split('.'))
+                    .into_iter()
+                    .flatten(),
+            );
+            from.join(".")
+        } else {
+            import.module_name.clone()?
+        };
+        if from.is_empty() {
+            return None;
+        }
+        return Some(match import.module_name {
+            None => (format!("{}.{}", from, imported), None),
+            Some(_) => (from, Some(imported)),
+        });
+    }
+    None
+}
+
+fn contains_python_files(dir: &Path) -> bool {
+    read_dir_sorted(dir)
+        .map(|paths| paths.iter().any(|p| p.is_file() && is_python_file(p)))
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::DEFAULT_GENERATED_PATTERNS;
+    use std::fs::{File, create_dir_all};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_discover_python_files() -> Result<(), AnalysisError> {
+        let dir = tempdir().unwrap();
+        create_dir_all(dir.path().join("pkg/sub")).unwrap();
+        create_dir_all(dir.path().join(".venv/lib")).unwrap();
+        create_dir_all(dir.path().join("pkg/__pycache__")).unwrap();
+        File::create(dir.path().join("main.py")).unwrap();
+        File::create(dir.path().join("pkg/__init__.py")).unwrap();
+        File::create(dir.path().join("pkg/sub/mod.py")).unwrap();
+        File::create(dir.path().join("pkg/README.md")).unwrap();
+        File::create(dir.path().join(".venv/lib/site.py")).unwrap();
+        File::create(dir.path().join("pkg/__pycache__/mod.py")).unwrap();
+        create_dir_all(dir.path().join("pkg/fixtures")).unwrap();
+        File::create(dir.path().join("pkg/fixtures/broken.py")).unwrap();
+
+        let files =
+            discover_python_files(dir.path(), &[PathBuf::from("pkg/fixtures")], true, false)?.files;
+        let relative: Vec<_> = files
+            .iter()
+            .map(|f| f.strip_prefix(dir.path()).unwrap().to_path_buf())
+            .collect();
+        assert_eq!(
+            relative,
+            vec![
+                PathBuf::from("main.py"),
+                PathBuf::from("pkg/__init__.py"),
+                PathBuf::from("pkg/sub/mod.py"),
+            ]
+        );
+
+        // Without the default excludes only the given paths are left out
+        let files =
+            discover_python_files(dir.path(), &[PathBuf::from("pkg/fixtures")], false, false)?
+                .files;
+        let relative: Vec<_> = files
+            .iter()
+            .map(|f| f.strip_prefix(dir.path()).unwrap().to_path_buf())
+            .collect();
+        assert_eq!(
+            relative,
+            vec![
+                PathBuf::from(".venv/lib/site.py"),
+                PathBuf::from("main.py"),
+                PathBuf::from("pkg/__init__.py"),
+                PathBuf::from("pkg/__pycache__/mod.py"),
+                PathBuf::from("pkg/sub/mod.py"),
+            ]
+        );
+        // A venv's packages are left out even when its root is excluded by the user's paths
+        create_dir_all(dir.path().join("env/lib/python3.12/site-packages/requests")).unwrap();
+        File::create(
+            dir.path()
+                .join("env/lib/python3.12/site-packages/requests/api.py"),
+        )
+        .unwrap();
+        let discovery = discover_python_files(dir.path(), &[], false, false)?;
+        assert!(
+            !discovery
+                .files
+                .iter()
+                .any(|f| f.starts_with(dir.path().join("env")))
+        );
+        assert_eq!(
+            discovery.virtual_environments,
+            [(dir.path().join("env/lib/python3.12"), "site-packages")]
+        );
+        assert!(is_default_excluded(Path::new(".venv/lib/site.py")));
+        assert!(is_default_excluded(Path::new("web/node_modules/x.py")));
+        assert!(!is_default_excluded(Path::new("pkg/.hidden.py")));
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_discover_python_files_with_symlinks() -> Result<(), AnalysisError> {
+        use std::os::unix::fs::symlink;
+
+        let dir = tempdir().unwrap();
+        let root = dir.path().join("project");
+        create_dir_all(root.join("pkg")).unwrap();
+        create_dir_all(root.join("env/lib")).unwrap();
+        create_dir_all(root.join("conda/conda-meta")).unwrap();
+        create_dir_all(dir.path().join("sibling")).unwrap();
+        File::create(root.join("main.py")).unwrap();
+        File::create(root.join("pkg/mod.py")).unwrap();
+        File::create(root.join("env/pyvenv.cfg")).unwrap();
+        File::create(root.join("env/lib/site.py")).unwrap();
+        File::create(root.join("conda/numpy.py")).unwrap();
+        File::create(dir.path().join("sibling/dep.py")).unwrap();
+        symlink(".", root.join("pkg/itself")).unwrap();
+        symlink("..", root.join("pkg/parent")).unwrap();
+        symlink("../sibling", root.join("linked")).unwrap();
+
+        let relative = |files: Vec<PathBuf>| -> Vec<PathBuf> {
+            files
+                .iter()
+                .map(|f| f.strip_prefix(&root).unwrap().to_path_buf())
+                .collect()
+        };
+        // Virtual environments are left out even without the default excludes
+        let discovery = discover_python_files(&root, &[], false, false)?;
+        assert_eq!(
+            relative(discovery.files),
+            vec![PathBuf::from("main.py"), PathBuf::from("pkg/mod.py")]
+        );
+        assert_eq!(
+            discovery.virtual_environments,
+            [
+                (root.join("conda"), "conda-meta"),
+                (root.join("env"), "pyvenv.cfg")
+            ]
+        );
+
+        // The links back into the project are walked once, through the real directories
+        let files = discover_python_files(&root, &[], true, true)?.files;
+        assert_eq!(
+            relative(files),
+            vec![
+                PathBuf::from("linked/dep.py"),
+                PathBuf::from("main.py"),
+                PathBuf::from("pkg/mod.py"),
+            ]
+        );
+
+        assert_eq!(
+            discover_first_party_modules(&root),
+            vec!["linked".to_string(), "main".to_string(), "pkg".to_string()]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_test_file() {
+        for path in [
+            "tests/test_app.py",
+            "tests/helpers/factories.py",
+            "test/data.py",
+            "pkg/test_models.py",
+            "pkg/models_test.py",
+            "conftest.py",
+            "noxfile.py",
+        ] {
+            assert!(is_test_file(Path::new(path)), "{}", path);
+        }
+        for path in [
+            "main.py",
+            "pkg/testing.py",
+            "pkg/contest.py",
+            "latest/app.py",
+        ] {
+            assert!(!is_test_file(Path::new(path)), "{}", path);
+        }
+    }
+
+    #[test]
+    fn test_is_script_file() {
+        let directories = [PathBuf::from("scripts"), PathBuf::from("tools/release")];
+        for path in [
+            "scripts/bump.py",
+            "scripts/ci/lint.py",
+            "tools/release/publish.py",
+        ] {
+            assert!(is_script_file(Path::new(path), &directories), "{}", path);
+        }
+        for path in ["scripts.py", "pkg/scripts/run.py", "tools/format.py"] {
+            assert!(!is_script_file(Path::new(path), &directories), "{}", path);
+        }
+    }
+
+    #[test]
+    fn test_wildcard_match() {
+        assert!(wildcard_match("acme*", "acme"));
+        assert!(wildcard_match("acme*", "acme.tools"));
+        assert!(wildcard_match("acme.*", "acme.tools"));
+        assert!(!wildcard_match("acme.*", "acme"));
+        assert!(wildcard_match("*.tests", "acme.tests"));
+        assert!(wildcard_match("app?", "app2"));
+        assert!(!wildcard_match("tests", "tests_helpers"));
+    }
+
+    #[test]
+    fn test_is_generated_file() {
+        let patterns: Vec<String> = DEFAULT_GENERATED_PATTERNS
+            .iter()
+            .map(|pattern| pattern.to_string())
+            .collect();
+        for path in [
+            "foo_pb2.py",
+            "api/v1/service_pb2_grpc.py",
+            "shop/migrations/0001_initial.py",
+            "migrations/versions/1a2b3c_add_users.py",
+            "alembic/versions/1a2b3c_add_users.py",
+        ] {
+            assert!(is_generated_file(Path::new(path), &patterns), "{}", path);
+        }
+        for path in [
+            "pb2.py",
+            "shop/models.py",
+            "alembic/env.py",
+            "migrations.py",
+        ] {
+            assert!(!is_generated_file(Path::new(path), &patterns), "{}", path);
+        }
+    }
+
+    #[test]
+    fn test_discover_first_party_modules() {
+        let dir = tempdir().unwrap();
+        create_dir_all(dir.path().join("src/app")).unwrap();
+        create_dir_all(dir.path().join("src/acme/tools")).unwrap();
+        File::create(dir.path().join("script.py")).unwrap();
+        File::create(dir.path().join("setup.py")).unwrap();
+        File::create(dir.path().join("src/app/__init__.py")).unwrap();
+        File::create(dir.path().join("src/acme/tools/__init__.py")).unwrap();
+
+        assert_eq!(
+            discover_first_party_modules(dir.path()),
+            vec![
+                "acme.tools".to_string(),
+                "app".to_string(),
+                "script".to_string()
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "python-parsing")]
+    fn test_defining_module() {
+        let dir = tempdir().unwrap();
+        let write = |path: &str, content: &str| {
+            let path = dir.path().join(path);
+            create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        };
+        write(
+            "a/__init__.py",
+            "from .b import Thing\nfrom .c import helper as util\nfrom . import d\nfrom numpy import array\n",
+        );
+        write("a/b.py", "class Thing:\n    pass\n");
+        write("a/c.py", "def helper():\n    pass\n");
+        write("a/d.py", "");
+        write(
+            "a/sub/__init__.py",
+            "from a import Thing\nfrom ..c import helper\n",
+        );
+        write("src/cyc/__init__.py", "from .inner import T\n");
+        write("src/cyc/inner/__init__.py", "from .. import T\n");
+
+        let root = dir.path();
+        // `from a import Thing` is attributed to the submodule that defines it
+        assert_eq!(defining_module(root, "a", "Thing").as_deref(), Some("a.b"));
+        assert_eq!(defining_module(root, "a", "util").as_deref(), Some("a.c"));
+        assert_eq!(defining_module(root, "a", "d").as_deref(), Some("a.d"));
+        // Re-exports of re-exports are followed
+        assert_eq!(
+            defining_module(root, "a.sub", "Thing").as_deref(),
+            Some("a.b")
+        );
+        assert_eq!(
+            defining_module(root, "a.sub", "helper").as_deref(),
+            Some("a.c")
+        );
+        // Names from other distributions, or not re-exported, stay with the package
+        assert_eq!(defining_module(root, "a", "array").as_deref(), Some("a"));
+        assert_eq!(defining_module(root, "a", "other").as_deref(), Some("a"));
+        assert_eq!(
+            defining_module(root, "a.b", "Thing").as_deref(),
+            Some("a.b")
+        );
+        // A cycle of re-exports ends
+        assert!(defining_module(root, "cyc", "T").is_some());
+        assert_eq!(defining_module(root, "requests", "get"), None);
+    }
+}
@@ -0,0 +1,130 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::EnvironmentBackend;
+
+/// A problem with the analysis itself rather than with the project: something was skipped or
+/// worked around, so the findings may be incomplete
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum AnalysisWarning {
+    /// A Python file that couldn't be read or parsed, whose imports weren't checked
+    SkippedFile { path: PathBuf, reason: String },
+    /// A Python file the parser couldn't read, whose import statements were found line by
+    /// line instead, so some may be missing or misplaced
+    DegradedParse { path: PathBuf, reason: String },
+    /// A dependency source that couldn't be read, whose dependencies weren't checked
+    SkippedSource { path: PathBuf, reason: String },
+    /// No dependency source was given and none was found, so nothing is declared
+    NoDependencySource { path: PathBuf, reason: String },
+    /// The backend's tool couldn't be run to inspect its environment, so the modules
+    /// dependencies provide were matched with the curated mapping, like the simulated backend
+    /// does
+    BackendFallback {
+        backend: EnvironmentBackend,
+        reason: String,
+    },
+    /// The repodata of a conda environment file's channels couldn't be read, so the simulated
+    /// backend matched its packages with the curated mapping alone
+    RepodataFallback { path: PathBuf, reason: String },
+    /// Entries of the name map file that disagree with each other, which were both kept
+    NameMapConflict { path: PathBuf, reason: String },
+    /// A directory under the project that is a virtual environment, whose files weren't
+    /// checked. `marker` is the entry that gave it away, like `pyvenv.cfg`.
+    SkippedVirtualEnvironment { path: PathBuf, marker: String },
+    /// The analysis is offline, so the declared versions weren't looked up on PyPI to check
+    /// that they're published and not yanked
+    SkippedReleaseCheck,
+}
+
+impl AnalysisWarning {
+    /// The stable code for the kind of warning
+    pub fn code(&self) -> &'static str {
+        match self {
+            AnalysisWarning::SkippedFile { .. } => "skipped-file",
+            AnalysisWarning::DegradedParse { .. } => "degraded-parse",
+            AnalysisWarning::SkippedSource { .. } => "skipped-source",
+            AnalysisWarning::NoDependencySource { .. } => "no-dependency-source",
+            AnalysisWarning::BackendFallback { .. } => "backend-fallback",
+            AnalysisWarning::RepodataFallback { .. } => "repodata-fallback",
+            AnalysisWarning::NameMapConflict { .. } => "name-map-conflict",
+            AnalysisWarning::SkippedVirtualEnvironment { .. } => "virtual-environment",
+            AnalysisWarning::SkippedReleaseCheck => "release-check-skipped",
+        }
+    }
+
+    /// The file or directory the warning is about, if it is about one
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            AnalysisWarning::SkippedFile { path, .. }
+            | AnalysisWarning::DegradedParse { path, .. }
+            | AnalysisWarning::SkippedSource { path, .. }
+            | AnalysisWarning::NoDependencySource { path, .. }
+            | AnalysisWarning::RepodataFallback { path, .. }
+            | AnalysisWarning::NameMapConflict { path, .. }
+            | AnalysisWarning::SkippedVirtualEnvironment { path, .. } => Some(path),
+            AnalysisWarning::BackendFallback { .. } | AnalysisWarning::SkippedReleaseCheck => None,
+        }
+    }
+
+    /// What happened, without the path
+    pub fn message(&self) -> String {
+        match self {
+            AnalysisWarning::SkippedFile { reason, .. } => {
+                format!("skipped, its imports weren't checked: {}", reason)
+            }
+            AnalysisWarning::DegradedParse { reason, .. } => format!(
+                "only its import statements were read, which may miss some: {}",
+                reason
+            ),
+            AnalysisWarning::SkippedSource { reason, .. } => {
+                format!("skipped, its dependencies weren't checked: {}", reason)
+            }
+            AnalysisWarning::NoDependencySource { reason, .. } => {
+                format!("no dependency source was found: {}", reason)
+            }
+            AnalysisWarning::BackendFallback { backend, reason } => {
+                let name = match backend {
+                    EnvironmentBackend::Auto => "auto",
+                    EnvironmentBackend::Simulated => "simulated",
+                    #[cfg(feature = "backends-exec")]
+                    EnvironmentBackend::UV => "uv",
+                    #[cfg(feature = "backends-exec")]
+                    EnvironmentBackend::Pixi => "pixi",
+                    #[cfg(feature = "backends-exec")]
+                    EnvironmentBackend::Current => "current",
+                };
+                format!(
+                    "the {} backend couldn't inspect its environment, so dependencies were \
+                     matched with the built-in mapping like the simulated backend: {}",
+                    name, reason
+                )
+            }
+            AnalysisWarning::RepodataFallback { reason, .. } => format!(
+                "the repodata of the channels couldn't be read, so conda packages were matched \
+                 with the built-in mapping alone: {}",
+                reason
+            ),
+            AnalysisWarning::NameMapConflict { reason, .. } => {
+                format!("conflicting entries, both were kept: {}", reason)
+            }
+            AnalysisWarning::SkippedVirtualEnvironment { marker, .. } => format!(
+                "skipped, it has a `{}` so it looks like a virtual environment rather than \
+                 project sources",
+                marker
+            ),
+            AnalysisWarning::SkippedReleaseCheck => "the declared versions weren't checked for \
+                 releases on PyPI (DW404), since the analysis is offline"
+                .to_string(),
+        }
+    }
+}
+
+impl fmt::Display for AnalysisWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(path) = self.path() {
+            write!(f, "{}: ", path.display())?;
+        }
+        write!(f, "warning [{}] {}", self.code(), self.message())
+    }
+}
@@ -0,0 +1,56 @@
+//! What's left of the library without its default features: reading dependency files.
+//! Run with `cargo test -p depwise-analysis --no-default-features` to check that the
+//! slimmest build compiles and works.
+
+use std::fs;
+
+use depwise_analysis::project::extract_configurations;
+use depwise_analysis::{AnalysisError, EnvironmentBuilderSource};
+
+#[test]
+fn test_requirements_txt() -> Result<(), AnalysisError> {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("requirements.txt");
+    fs::write(&path, "requests>=2\n# a comment\nnumpy\n").unwrap();
+
+    let configurations =
+        extract_configurations(EnvironmentBuilderSource::RequirementsTxt(path), true)?;
+    let names: Vec<_> = configurations[0]
+        .dependencies()
+        .iter()
+        .filter_map(|dependency| dependency.name())
+        .collect();
+    assert_eq!(names, vec!["requests", "numpy"]);
+    Ok(())
+}
+
+#[test]
+fn test_pyproject_toml() -> Result<(), AnalysisError> {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("pyproject.toml");
+    fs::write(
+        &path,
+        "[project]\nname = \"app\"\ndependencies = [\"rich\"]\n",
+    )
+    .unwrap();
+
+    let configurations =
+        extract_configurations(EnvironmentBuilderSource::PyProjectToml(path), true)?;
+    assert_eq!(configurations[0].dependencies()[0].name(), Some("rich"));
+    Ok(())
+}
+
+#[cfg(not(feature = "backends-net"))]
+#[test]
+fn test_remote_include_disabled() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("requirements.txt");
+    fs::write(&path, "-r https://example.com/base.txt\nnumpy\n").unwrap();
+
+    let error =
+        extract_configurations(EnvironmentBuilderSource::RequirementsTxt(path), false).unwrap_err();
+    assert!(matches!(
+        error,
+        AnalysisError::FeatureDisabled(_, "backends-net")
+    ));
+}
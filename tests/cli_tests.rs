@@ -1,6 +1,9 @@
 use assert_cmd::prelude::*;
 use predicates::prelude::*;
-use std::process::Command;
+use serde_json::json;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
 
 #[test]
 fn check_version() -> Result<(), Box<dyn std::error::Error>> {
@@ -25,3 +28,2167 @@ fn check_help() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+#[test]
+fn check_fix_removes_unused_requirements() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    let requirements = dir.path().join("requirements.txt");
+    std::fs::write(&requirements, "# runtime\nrequests\nflask>=2.0\n")?;
+    std::fs::write(dir.path().join("main.py"), "import requests\n")?;
+
+    let mut cmd = Command::cargo_bin("depwise")?;
+    cmd.arg("check")
+        .arg(dir.path())
+        .arg("--fix")
+        .arg("--dry-run");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Would remove `flask>=2.0`"));
+    assert_eq!(
+        std::fs::read_to_string(&requirements)?,
+        "# runtime\nrequests\nflask>=2.0\n"
+    );
+
+    // With a JSON report, stdout is only the report
+    let mut cmd = Command::cargo_bin("depwise")?;
+    cmd.arg("check")
+        .arg(dir.path())
+        .args(["--fix", "--dry-run", "--format", "json"]);
+    let output = cmd.assert().success().get_output().clone();
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert!(report.is_object());
+    assert!(String::from_utf8(output.stderr)?.contains("Would remove `flask>=2.0`"));
+
+    let mut cmd = Command::cargo_bin("depwise")?;
+    cmd.arg("check").arg(dir.path()).arg("--fix");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Removed `flask>=2.0`"));
+    assert_eq!(
+        std::fs::read_to_string(&requirements)?,
+        "# runtime\nrequests\n"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn check_add_missing_appends_requirements() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    let requirements = dir.path().join("requirements.txt");
+    std::fs::write(&requirements, "flask>=2.0\n")?;
+    std::fs::write(
+        dir.path().join("main.py"),
+        "import os\nimport flask\nimport requests\n\ntry:\n    import ujson\nexcept ImportError:\n    ujson = None\n",
+    )?;
+
+    let mut cmd = Command::cargo_bin("depwise")?;
+    cmd.arg("check")
+        .arg(dir.path())
+        .arg("--add-missing")
+        .arg("--backend")
+        .arg("simulated");
+    cmd.assert()
+        .code(1)
+        .stdout(predicate::str::contains("Added `requests`"));
+    assert_eq!(
+        std::fs::read_to_string(&requirements)?,
+        "flask>=2.0\nrequests\n"
+    );
+
+    let mut cmd = Command::cargo_bin("depwise")?;
+    cmd.arg("check")
+        .arg(dir.path())
+        .arg("--add-missing")
+        .arg("--include-optional")
+        .arg("--dry-run")
+        .arg("--backend")
+        .arg("simulated");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Would add `ujson`"));
+    assert_eq!(
+        std::fs::read_to_string(&requirements)?,
+        "flask>=2.0\nrequests\n"
+    );
+
+    // With a JSON report, stdout is only the report
+    let mut cmd = Command::cargo_bin("depwise")?;
+    cmd.arg("check")
+        .arg(dir.path())
+        .args(["--add-missing", "--include-optional", "--dry-run"])
+        .args(["--format", "json", "--offline", "--backend", "simulated"]);
+    let output = cmd.assert().success().get_output().clone();
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert!(report.is_object());
+    assert!(String::from_utf8(output.stderr)?.contains("Would add `ujson`"));
+
+    // Offline, the simulated backend has no version to pin to
+    let mut cmd = Command::cargo_bin("depwise")?;
+    cmd.arg("check")
+        .arg(dir.path())
+        .args(["--add-missing", "--include-optional", "--dry-run"])
+        .args(["--pin-style", "exact", "--offline"])
+        .args(["--backend", "simulated"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Would add `ujson` to"));
+
+    let mut cmd = Command::cargo_bin("depwise")?;
+    cmd.arg("check")
+        .arg(dir.path())
+        .args(["--pin-style", "exact"]);
+    cmd.assert().failure();
+
+    Ok(())
+}
+
+#[test]
+fn check_json_fixes_match_fix() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    let pyproject = dir.path().join("pyproject.toml");
+    let original = r#"[project]
+name = "example"
+version = "0.1.0"
+dependencies = [
+    "requests",
+    "flask>=2.0",
+    "rich",  # pretty output
+    "click",
+]
+"#;
+    std::fs::write(&pyproject, original)?;
+    std::fs::write(
+        dir.path().join("main.py"),
+        "import click\nimport requests\nimport yaml\nimport numpy\n",
+    )?;
+
+    let output = Command::cargo_bin("depwise")?
+        .arg("check")
+        .arg(dir.path())
+        .args(["--backend", "simulated", "--format", "json"])
+        .output()?;
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    // Splice the edits of a rule's findings into the file, from the last one back
+    let splice = |code: &str| {
+        let mut edits = Vec::new();
+        for finding in report["findings"].as_array().unwrap() {
+            if finding["code"] != code {
+                continue;
+            }
+            for edit in finding["fix"]["edits"].as_array().unwrap() {
+                assert_eq!(edit["path"], "pyproject.toml");
+                edits.push((
+                    edit["start"].as_u64().unwrap() as usize,
+                    edit["end"].as_u64().unwrap() as usize,
+                    edit["replacement"].as_str().unwrap().to_string(),
+                ));
+            }
+        }
+        let mut content = original.to_string();
+        for (start, end, replacement) in edits.iter().rev() {
+            content.replace_range(start..end, replacement);
+        }
+        content
+    };
+    let removed = splice("DW102");
+    let added = splice("DW101");
+
+    let mut cmd = Command::cargo_bin("depwise")?;
+    cmd.arg("check")
+        .arg(dir.path())
+        .args(["--backend", "simulated", "--fix"]);
+    cmd.assert().code(1);
+    assert_eq!(std::fs::read_to_string(&pyproject)?, removed);
+    assert!(!removed.contains("flask") && !removed.contains("rich"));
+
+    std::fs::write(&pyproject, original)?;
+    let mut cmd = Command::cargo_bin("depwise")?;
+    cmd.arg("check")
+        .arg(dir.path())
+        .args(["--backend", "simulated", "--add-missing"]);
+    cmd.assert().code(1);
+    assert_eq!(std::fs::read_to_string(&pyproject)?, added);
+    assert!(added.contains("pyyaml") && added.contains("numpy"));
+    Ok(())
+}
+
+#[test]
+fn check_emit_sbom_writes_cyclonedx() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    std::fs::write(
+        dir.path().join("requirements.txt"),
+        "flask==3.0.0\nrequests>=2\n",
+    )?;
+    std::fs::write(
+        dir.path().join("main.py"),
+        "import flask\nimport requests\n",
+    )?;
+    let sbom = dir.path().join("sbom.json");
+
+    let mut cmd = Command::cargo_bin("depwise")?;
+    cmd.arg("check")
+        .arg(dir.path())
+        .arg("--emit-sbom")
+        .arg(&sbom)
+        .arg("--backend")
+        .arg("simulated");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Wrote SBOM to"));
+
+    let document = read_cyclonedx(&sbom)?;
+    assert_eq!(document["bomFormat"], "CycloneDX");
+    assert_eq!(document["specVersion"], "1.5");
+    let purls: Vec<_> = document["components"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|component| component["purl"].clone())
+        .collect();
+    assert_eq!(
+        purls,
+        [json!("pkg:pypi/flask@3.0.0"), json!("pkg:pypi/requests")]
+    );
+    assert_eq!(document["dependencies"][0]["dependsOn"], json!(purls));
+
+    // The JSON report is all that's printed
+    let output = Command::cargo_bin("depwise")?
+        .arg("check")
+        .arg(dir.path())
+        .arg("--emit-sbom")
+        .arg(&sbom)
+        .args(["--backend", "simulated", "--format", "json"])
+        .output()?;
+    assert!(output.status.success(), "{:?}", output);
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert!(report["findings"].is_array(), "{}", report);
+
+    Ok(())
+}
+
+/// Read a written SBOM, checking it against the CycloneDX 1.5 schema. The subset of the
+/// schema covering the fields depwise writes rejects any others. The official schema, with
+/// the SPDX and JSF schemas it references, is checked too once `fetch-schemas.sh` has
+/// vendored it.
+fn read_cyclonedx(path: &Path) -> Result<serde_json::Value, Box<dyn std::error::Error>> {
+    let schemas = Path::new(env!("CARGO_MANIFEST_DIR")).join("data/cyclonedx");
+    let read_schema = |name: &str| -> Result<serde_json::Value, Box<dyn std::error::Error>> {
+        Ok(serde_json::from_str(&std::fs::read_to_string(
+            schemas.join(name),
+        )?)?)
+    };
+    let sbom: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+    let mut validators = vec![jsonschema::validator_for(&read_schema(
+        "bom-1.5.subset.schema.json",
+    )?)?];
+    if schemas.join("bom-1.5.schema.json").exists() {
+        let mut options = jsonschema::options();
+        for referenced in ["spdx.schema.json", "jsf-0.82.schema.json"] {
+            options = options.with_resource(
+                format!("http://cyclonedx.org/schema/{}", referenced),
+                jsonschema::Resource::from_contents(read_schema(referenced)?)?,
+            );
+        }
+        validators.push(options.build(&read_schema("bom-1.5.schema.json")?)?);
+    }
+    for validator in validators {
+        let errors: Vec<String> = validator
+            .iter_errors(&sbom)
+            .map(|error| format!("{} at {}", error, error.instance_path))
+            .collect();
+        assert!(errors.is_empty(), "{:#?}\n{}", errors, sbom);
+    }
+    Ok(sbom)
+}
+
+#[cfg(unix)]
+#[test]
+fn check_emit_sbom_pixi_environment() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    let bin = tempfile::tempdir()?;
+    std::fs::write(
+        dir.path().join("pixi.toml"),
+        r#"[workspace]
+platforms = ["linux-64"]
+
+[dependencies]
+python = "3.12.*"
+pyyaml = "*"
+
+[pypi-dependencies]
+rich = "*"
+"#,
+    )?;
+    std::fs::write(dir.path().join("main.py"), "import yaml\nimport rich\n")?;
+    // The environment pixi installs: conda packages, with PyPI ones on top
+    fake_tool(
+        bin.path(),
+        "pixi",
+        r#"#!/bin/sh
+cat <<'EOF'
+{"python_version": "3.12.3", "distributions": {
+  "python": {"version": "3.12.3", "conda_package": "python", "requires": ["libzlib"],
+    "hashes": {"md5": "0c1f1e2f3a4b5c6d7e8f90a1b2c3d4e5"}},
+  "libzlib": {"version": "1.3.1", "conda_package": "libzlib"},
+  "PyYAML": {"version": "6.0.1", "top_level_modules": ["yaml"], "conda_package": "pyyaml",
+    "requires": ["python", "yaml"],
+    "hashes": {"sha256": "b3c1a1e0f7d6d1b1d3c5a4b2e1f0a9c8d7e6f5a4b3c2d1e0f9a8b7c6d5e4f3a2"}},
+  "yaml": {"version": "0.2.5", "conda_package": "yaml"},
+  "rich": {"version": "13.7.1", "top_level_modules": ["rich"], "requires": ["Pygments"]},
+  "Pygments": {"version": "2.18.0", "top_level_modules": ["pygments"]},
+  "pip": {"version": "24.0", "top_level_modules": ["pip"], "conda_package": "pip"}
+}}
+EOF
+"#,
+    )?;
+    let sbom = dir.path().join("sbom.json");
+
+    Command::cargo_bin("depwise")?
+        .env(
+            "PATH",
+            format!("{}:{}", bin.path().display(), std::env::var("PATH")?),
+        )
+        .arg("check")
+        .arg(dir.path())
+        .arg("--emit-sbom")
+        .arg(&sbom)
+        .args(["--backend", "pixi"])
+        .assert()
+        .success();
+
+    // Everything the declared dependencies install is listed, but not pip, which they
+    // don't require
+    let sbom = read_cyclonedx(&sbom)?;
+    let purls: Vec<&str> = sbom["components"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|component| component["purl"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        purls,
+        [
+            "pkg:conda/libzlib@1.3.1",
+            "pkg:conda/python@3.12.3",
+            "pkg:conda/pyyaml@6.0.1",
+            "pkg:conda/yaml@0.2.5",
+            "pkg:pypi/pygments@2.18.0",
+            "pkg:pypi/rich@13.7.1",
+        ]
+    );
+    assert_eq!(
+        sbom["components"][2]["hashes"],
+        json!([{
+            "alg": "SHA-256",
+            "content": "b3c1a1e0f7d6d1b1d3c5a4b2e1f0a9c8d7e6f5a4b3c2d1e0f9a8b7c6d5e4f3a2",
+        }])
+    );
+    let graph = sbom["dependencies"].as_array().unwrap();
+    let depends_on = |purl: &str| {
+        graph
+            .iter()
+            .find(|node| node["ref"] == purl)
+            .map(|node| node["dependsOn"].clone())
+    };
+    assert_eq!(
+        depends_on(sbom["metadata"]["component"]["bom-ref"].as_str().unwrap()),
+        Some(json!([
+            "pkg:conda/python@3.12.3",
+            "pkg:conda/pyyaml@6.0.1",
+            "pkg:pypi/rich@13.7.1",
+        ]))
+    );
+    assert_eq!(
+        depends_on("pkg:conda/pyyaml@6.0.1"),
+        Some(json!(["pkg:conda/python@3.12.3", "pkg:conda/yaml@0.2.5"]))
+    );
+    assert_eq!(
+        depends_on("pkg:conda/python@3.12.3"),
+        Some(json!(["pkg:conda/libzlib@1.3.1"]))
+    );
+    Ok(())
+}
+
+#[test]
+fn check_timeout_exits_with_code_3() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    std::fs::write(dir.path().join("requirements.txt"), "requests\n")?;
+    std::fs::write(dir.path().join("main.py"), "import requests\n")?;
+
+    let mut cmd = Command::cargo_bin("depwise")?;
+    cmd.arg("check").arg(dir.path()).arg("--timeout").arg("0");
+    cmd.assert()
+        .code(3)
+        .stderr(predicate::str::contains("Timed out after 0s while"));
+    Ok(())
+}
+
+#[test]
+fn check_unused_imports() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    std::fs::write(dir.path().join("requirements.txt"), "requests\n")?;
+    std::fs::write(dir.path().join("main.py"), "import os\nimport requests\n")?;
+
+    let mut cmd = Command::cargo_bin("depwise")?;
+    cmd.arg("check").arg(dir.path());
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("DW201").not());
+
+    let mut cmd = Command::cargo_bin("depwise")?;
+    cmd.arg("check")
+        .arg(dir.path())
+        .arg("--check-unused-imports");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "main.py:1: warning DW201 [unused-import] `os` is imported but never used",
+        ))
+        .stdout(predicate::str::contains(
+            "DW102 [unused-dependency] `requests`",
+        ));
+
+    Ok(())
+}
+
+#[test]
+fn check_conda_environment() -> Result<(), Box<dyn std::error::Error>> {
+    let project = Path::new(env!("CARGO_MANIFEST_DIR")).join("data/conda_project");
+    // Without cached repodata, conda packages are matched with the built-in mapping
+    let cache = tempfile::tempdir()?;
+
+    let mut cmd = Command::cargo_bin("depwise")?;
+    cmd.arg("check")
+        .arg(&project)
+        .arg("--condayml")
+        .arg(project.join("environment.yml"))
+        .args(["--backend", "simulated", "--offline"])
+        .env("XDG_CACHE_HOME", cache.path());
+    cmd.assert()
+        .code(1)
+        // python=3.10 predates tomllib
+        .stdout(predicate::str::contains(
+            "main.py:1: error DW101 [missing-dependency] `tomllib`",
+        ))
+        .stdout(predicate::str::contains(
+            "main.py:5: error DW101 [missing-dependency] `yaml`",
+        ))
+        .stdout(predicate::str::contains(
+            "DW102 [unused-dependency] `scipy`",
+        ))
+        .stdout(predicate::str::contains("`requests`").not())
+        .stdout(predicate::str::contains("`numpy`").not());
+
+    Ok(())
+}
+
+#[test]
+fn check_pixi_manifest() -> Result<(), Box<dyn std::error::Error>> {
+    let project = Path::new(env!("CARGO_MANIFEST_DIR")).join("data/pixi_project");
+
+    let mut cmd = Command::cargo_bin("depwise")?;
+    cmd.arg("check")
+        .arg(&project)
+        .arg("--pixi")
+        .arg(project.join("pixi.toml"))
+        .args(["--backend", "simulated"]);
+    cmd.assert()
+        .code(1)
+        .stdout(predicate::str::contains(
+            "main.py:4: error DW101 [missing-dependency] `toml`",
+        ))
+        .stdout(predicate::str::contains(
+            "DW102 [unused-dependency] `pandas`",
+        ))
+        .stdout(predicate::str::contains("`tomllib`").not())
+        .stdout(predicate::str::contains("`rich`").not());
+
+    Ok(())
+}
+
+#[test]
+fn check_reports_every_invalid_requirement() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    std::fs::write(
+        dir.path().join("requirements.txt"),
+        "requests\nflask>=>2\nrich\nnumpy===\n",
+    )?;
+    std::fs::write(dir.path().join("main.py"), "import requests\nimport rich\n")?;
+
+    let mut cmd = Command::cargo_bin("depwise")?;
+    cmd.arg("check").arg(dir.path());
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "requirements.txt:2: warning DW304 [invalid-dependency] `flask>=>2`",
+        ))
+        .stdout(predicate::str::contains(
+            "requirements.txt:4: warning DW304 [invalid-dependency] `numpy===`",
+        ))
+        .stdout(predicate::str::contains("DW102").not());
+
+    let mut cmd = Command::cargo_bin("depwise")?;
+    cmd.arg("check").arg(dir.path()).arg("--strict-parse");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("InvalidDeclarations"));
+
+    Ok(())
+}
+
+#[test]
+fn check_summary_and_severity_filters() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    std::fs::write(dir.path().join("requirements.txt"), "flask\nrich\n")?;
+    std::fs::write(dir.path().join("main.py"), "import requests\n")?;
+
+    let mut cmd = Command::cargo_bin("depwise")?;
+    cmd.arg("check").arg(dir.path()).arg("--summary-only");
+    cmd.assert()
+        .code(1)
+        .stdout(predicate::str::contains("DW101 missing-dependency: 1\n"))
+        .stdout(predicate::str::contains("DW102 unused-dependency: 2\n"))
+        .stdout(predicate::str::contains("3 findings\n"))
+        .stdout(predicate::str::contains("`flask`").not());
+
+    let mut cmd = Command::cargo_bin("depwise")?;
+    cmd.arg("check")
+        .arg(dir.path())
+        .args(["--min-severity", "error"]);
+    cmd.assert()
+        .code(1)
+        .stdout(predicate::str::contains("DW101"))
+        .stdout(predicate::str::contains("DW102").not());
+
+    // JSON output keeps the full findings under --summary-only
+    let output = Command::cargo_bin("depwise")?
+        .arg("check")
+        .arg(dir.path())
+        .args(["--format", "json", "--summary-only"])
+        .output()?;
+    assert_eq!(output.status.code(), Some(1));
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(report["findings"].as_array().map(Vec::len), Some(3));
+    assert_eq!(report["summary"]["DW102"], 2);
+
+    // but not the ones below --min-severity
+    let output = Command::cargo_bin("depwise")?
+        .arg("check")
+        .arg(dir.path())
+        .args([
+            "--format",
+            "json",
+            "--summary-only",
+            "--min-severity",
+            "error",
+        ])
+        .output()?;
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    let codes: Vec<&str> = report["findings"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|finding| finding["code"].as_str())
+        .collect();
+    assert_eq!(codes, ["DW101"]);
+    assert!(report["summary"].get("DW102").is_none());
+
+    Ok(())
+}
+
+#[test]
+fn check_relative_and_absolute_paths() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    std::fs::create_dir(dir.path().join("app"))?;
+    std::fs::write(dir.path().join("requirements.txt"), "flask\n")?;
+    std::fs::write(
+        dir.path().join("app/main.py"),
+        "import flask\nimport yaml\n",
+    )?;
+    let absolute = std::fs::canonicalize(dir.path())?
+        .join("app")
+        .join("main.py");
+
+    let finding = |args: &[&str]| -> Result<String, Box<dyn std::error::Error>> {
+        let output = Command::cargo_bin("depwise")?
+            .arg("check")
+            .arg(std::fs::canonicalize(dir.path())?)
+            .args(args)
+            .output()?;
+        let stdout = String::from_utf8(output.stdout)?;
+        Ok(stdout
+            .lines()
+            .find(|line| line.contains("DW101"))
+            .unwrap_or_default()
+            .trim()
+            .to_string())
+    };
+
+    let relative = format!(
+        "{}:2: error DW101 [missing-dependency] `yaml`",
+        Path::new("app").join("main.py").display()
+    );
+    assert!(finding(&[])?.starts_with(&relative));
+    assert!(finding(&["--relative-paths"])?.starts_with(&relative));
+    assert!(finding(&["--absolute-paths"])?.starts_with(&format!(
+        "{}:2: error DW101 [missing-dependency] `yaml`",
+        absolute.display()
+    )));
+
+    // JSON output renders paths the same way
+    let output = Command::cargo_bin("depwise")?
+        .arg("check")
+        .arg(std::fs::canonicalize(dir.path())?)
+        .args(["--format", "json", "--absolute-paths"])
+        .output()?;
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(
+        report["findings"][0]["path"].as_str(),
+        Some(absolute.to_str().unwrap())
+    );
+
+    Ok(())
+}
+
+#[test]
+fn check_json_output_is_reproducible() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    std::fs::create_dir_all(dir.path().join("app/handlers"))?;
+    std::fs::write(dir.path().join("requirements.txt"), "flask\nnumpy\nrich\n")?;
+    for (file, source) in [
+        ("app/main.py", "import yaml\nimport flask\n"),
+        ("app/handlers/upload.py", "import boto3\nimport yaml\n"),
+        ("app/handlers/auth.py", "import jwt\nimport boto3\n"),
+        ("tools.py", "import attr\n"),
+    ] {
+        std::fs::write(dir.path().join(file), source)?;
+    }
+
+    let run = || -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let output = Command::cargo_bin("depwise")?
+            .arg("check")
+            .arg(dir.path())
+            .args(["--backend", "simulated", "--format", "json"])
+            .output()?;
+        Ok(output.stdout)
+    };
+    let first = run()?;
+    assert_eq!(first, run()?);
+
+    // Findings are ordered by rule code, then subject, then location
+    let report: serde_json::Value = serde_json::from_slice(&first)?;
+    let findings: Vec<(&str, &str)> = report["findings"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|f| (f["code"].as_str().unwrap(), f["subject"].as_str().unwrap()))
+        .collect();
+    assert_eq!(
+        findings,
+        [
+            ("DW101", "attr"),
+            ("DW101", "boto3"),
+            ("DW101", "jwt"),
+            ("DW101", "yaml"),
+            ("DW102", "numpy"),
+            ("DW102", "rich"),
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn check_configured_package_layout() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    std::fs::create_dir_all(dir.path().join("lib/example/plugins"))?;
+    std::fs::write(dir.path().join("lib/example/__init__.py"), "")?;
+    std::fs::write(dir.path().join("lib/example/plugins/__init__.py"), "")?;
+    std::fs::write(
+        dir.path().join("pyproject.toml"),
+        r#"[project]
+name = "example"
+dependencies = []
+
+[tool.setuptools]
+package-dir = {"" = "lib"}
+packages = ["example", "example.reports"]
+"#,
+    )?;
+    std::fs::write(dir.path().join("main.py"), "import example\n")?;
+
+    let mut cmd = Command::cargo_bin("depwise")?;
+    cmd.arg("check")
+        .arg(dir.path())
+        .args(["--backend", "simulated"]);
+    cmd.assert()
+        .stdout(predicate::str::contains("DW101").not())
+        .stdout(predicate::str::contains(format!(
+            "pyproject.toml:7: info DW305 [package-layout-mismatch] `example.plugins` is in `{}` but isn't listed in `packages`",
+            Path::new("lib").join("example").join("plugins").display()
+        )))
+        .stdout(predicate::str::contains(format!(
+            "`example.reports` is declared as a package but `{}` doesn't exist",
+            Path::new("lib").join("example").join("reports").display()
+        )));
+
+    Ok(())
+}
+
+#[test]
+fn check_package_selected_extras() -> Result<(), Box<dyn std::error::Error>> {
+    let package = Path::new(env!("CARGO_MANIFEST_DIR")).join("data/unpacked_wheel");
+
+    let mut cmd = Command::cargo_bin("depwise")?;
+    cmd.arg("check-package")
+        .arg(&package)
+        .args(["--backend", "simulated"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "DW102 [unused-dependency] `sphinx`",
+        ))
+        .stdout(predicate::str::contains("`pytest`").not());
+
+    // Only the test extra is checked, so the docs extra's dependencies aren't reported
+    let mut cmd = Command::cargo_bin("depwise")?;
+    cmd.arg("check-package")
+        .arg(&package)
+        .args(["--backend", "simulated", "--extras", "test"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("`sphinx`").not())
+        .stdout(predicate::str::contains("`pytest`").not())
+        .stdout(predicate::str::contains("`requests`").not());
+
+    Ok(())
+}
+
+#[test]
+fn check_package_wheel_archive() -> Result<(), Box<dyn std::error::Error>> {
+    let wheel =
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("data/wheel/example-1.0-py3-none-any.whl");
+
+    // The wheel is checked like the unpacked one
+    let mut cmd = Command::cargo_bin("depwise")?;
+    cmd.arg("check-package")
+        .arg(&wheel)
+        .args(["--backend", "simulated"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "DW102 [unused-dependency] `sphinx`",
+        ))
+        .stdout(predicate::str::contains("`pytest`").not());
+
+    let mut cmd = Command::cargo_bin("depwise")?;
+    cmd.arg("check-package")
+        .arg(&wheel)
+        .args(["--backend", "simulated", "--extras", "test"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("`sphinx`").not())
+        .stdout(predicate::str::contains("`pytest`").not())
+        .stdout(predicate::str::contains("`requests`").not());
+
+    Ok(())
+}
+
+#[test]
+fn check_inline_script() -> Result<(), Box<dyn std::error::Error>> {
+    let script = Path::new(env!("CARGO_MANIFEST_DIR")).join("data/inline_script/fetch.py");
+
+    let mut cmd = Command::cargo_bin("depwise")?;
+    cmd.arg("check")
+        .arg(&script)
+        .args(["--backend", "simulated"]);
+    cmd.assert()
+        .code(1)
+        .stdout(predicate::str::contains(
+            "fetch.py:10: error DW101 [missing-dependency] `yaml`",
+        ))
+        .stdout(predicate::str::contains("DW102 [unused-dependency] `rich`"))
+        .stdout(predicate::str::contains("`requests`").not());
+
+    Ok(())
+}
+
+#[test]
+fn check_stats_deps() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    std::fs::write(
+        dir.path().join("requirements.txt"),
+        "requests
+flask
+ujson
+",
+    )?;
+    std::fs::write(
+        dir.path().join("main.py"),
+        "import requests
+",
+    )?;
+    std::fs::write(
+        dir.path().join("client.py"),
+        "import requests
+try:
+    import ujson
+except ImportError:
+    ujson = None
+",
+    )?;
+
+    let mut cmd = Command::cargo_bin("depwise")?;
+    cmd.arg("check")
+        .arg(dir.path())
+        .arg("--stats-deps")
+        .args(["--backend", "simulated"]);
+    cmd.assert().success().stdout(predicate::str::contains(
+        "DEPENDENCY  FILES  IMPORTS  NOTES\n\
+         requests        2        2\n\
+         ujson           1        1  guarded-only\n\
+         flask           0        0  unused\n",
+    ));
+
+    // JSON output lists the usage instead of printing the table after the report
+    let output = Command::cargo_bin("depwise")?
+        .arg("check")
+        .arg(dir.path())
+        .args(["--stats-deps", "--backend", "simulated", "--format", "json"])
+        .output()?;
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    let dependencies = report["dependencies"].as_array().unwrap();
+    let usage: Vec<(&str, u64)> = dependencies
+        .iter()
+        .map(|usage| {
+            (
+                usage["name"].as_str().unwrap(),
+                usage["import_sites"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(usage, [("requests", 2), ("ujson", 1), ("flask", 0)]);
+    assert_eq!(dependencies[1]["guarded_only"], true);
+
+    Ok(())
+}
+
+#[test]
+fn check_stats() -> Result<(), Box<dyn std::error::Error>> {
+    let project = Path::new(env!("CARGO_MANIFEST_DIR")).join("data/flat");
+
+    let output = Command::cargo_bin("depwise")?
+        .arg("check")
+        .arg(&project)
+        .args(["--backend", "simulated", "--format", "json", "--stats"])
+        .output()?;
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    let stats = &report["stats"];
+    // One of the five files never mentions `import`, so it isn't parsed
+    assert_eq!(stats["files_discovered"], 5);
+    assert_eq!(stats["files_cached"], 0);
+    assert_eq!(stats["files_parsed"], 4);
+    assert_eq!(stats["files_without_imports"], 1);
+    assert_eq!(stats["files_skipped"], 0);
+    assert_eq!(stats["bytes_parsed"], 712);
+    let phases: Vec<&String> = stats["timings_ms"].as_object().unwrap().keys().collect();
+    assert_eq!(
+        phases,
+        ["discovery", "matching", "parsing", "rendering", "resolving"]
+    );
+
+    let mut cmd = Command::cargo_bin("depwise")?;
+    cmd.arg("check")
+        .arg(&project)
+        .args(["--backend", "simulated", "--stats"]);
+    cmd.assert()
+        .stdout(predicate::str::contains(
+            "Stats:\n  files: 5 discovered, 0 cached, 4 parsed, 1 without imports, 0 skipped\n  parsed: 712 bytes\n  time: discovery ",
+        ));
+
+    // Without `--stats` the JSON output is unchanged
+    let output = Command::cargo_bin("depwise")?
+        .arg("check")
+        .arg(&project)
+        .args(["--backend", "simulated", "--format", "json"])
+        .output()?;
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert!(report.get("stats").is_none());
+
+    Ok(())
+}
+
+/// Send a JSON-RPC message to the server framed with a Content-Length header
+fn send(stdin: &mut impl Write, message: serde_json::Value) -> std::io::Result<()> {
+    let body = message.to_string();
+    write!(stdin, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    stdin.flush()
+}
+
+/// Read the next JSON-RPC message from the server
+fn receive(stdout: &mut impl BufRead) -> Result<serde_json::Value, Box<dyn std::error::Error>> {
+    let mut content_length = 0;
+    loop {
+        let mut line = String::new();
+        stdout.read_line(&mut line)?;
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(length) = line.strip_prefix("Content-Length:") {
+            content_length = length.trim().parse()?;
+        }
+    }
+    let mut body = vec![0; content_length];
+    stdout.read_exact(&mut body)?;
+    Ok(serde_json::from_slice(&body)?)
+}
+
+#[test]
+fn serve_answers_over_stdio() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    std::fs::write(dir.path().join("requirements.txt"), "requests\n")?;
+    let main = dir.path().join("main.py");
+    std::fs::write(&main, "import requests\n")?;
+
+    let mut child = Command::cargo_bin("depwise")?
+        .arg("serve")
+        .arg(dir.path())
+        .arg("--backend")
+        .arg("simulated")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    let mut stdin = child.stdin.take().unwrap();
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+
+    send(
+        &mut stdin,
+        json!({"jsonrpc": "2.0", "id": 1, "method": "initialize"}),
+    )?;
+    assert_eq!(receive(&mut stdout)?["result"]["name"], "depwise");
+
+    send(
+        &mut stdin,
+        json!({"jsonrpc": "2.0", "id": 2, "method": "depwise/check"}),
+    )?;
+    assert_eq!(receive(&mut stdout)?["result"]["diagnostics"], json!([]));
+
+    // Edit the file, only the diagnostics of that file are recomputed
+    std::fs::write(&main, "import requests\nimport yaml\n")?;
+    send(
+        &mut stdin,
+        json!({"jsonrpc": "2.0", "method": "depwise/didChange", "params": {"path": "main.py"}}),
+    )?;
+    send(
+        &mut stdin,
+        json!({"jsonrpc": "2.0", "id": 3, "method": "depwise/diagnostics", "params": {"path": "main.py"}}),
+    )?;
+    let response = receive(&mut stdout)?;
+    assert_eq!(response["id"], 3);
+    let diagnostics = response["result"]["diagnostics"].as_array().unwrap();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0]["code"], "DW101");
+    assert_eq!(diagnostics[0]["subject"], "yaml");
+    assert_eq!(diagnostics[0]["line"], 2);
+
+    send(
+        &mut stdin,
+        json!({"jsonrpc": "2.0", "id": 4, "method": "depwise/dependencyUsage"}),
+    )?;
+    assert_eq!(
+        receive(&mut stdout)?["result"]["dependencies"],
+        json!([{
+            "name": "requests",
+            "files": 1,
+            "import_sites": 1,
+            "guarded_only": false,
+            "test_only": false,
+            "type_checking_only": false,
+            "type_only": false,
+            "script_only": false,
+            "main_guard_only": false,
+        }])
+    );
+
+    send(
+        &mut stdin,
+        json!({"jsonrpc": "2.0", "id": 4, "method": "depwise/unknown"}),
+    )?;
+    assert_eq!(receive(&mut stdout)?["error"]["code"], -32601);
+
+    // A message over the size limit is skipped with an error instead of being read
+    let length = 16 * 1024 * 1024 + 1;
+    write!(stdin, "Content-Length: {}\r\n\r\n", length)?;
+    stdin.write_all(&vec![b' '; length])?;
+    stdin.flush()?;
+    let response = receive(&mut stdout)?;
+    assert_eq!(response["id"], serde_json::Value::Null);
+    assert_eq!(response["error"]["code"], -32600);
+
+    // A length that can't be read is a parse error, and the server picks up again at the
+    // next message
+    write!(stdin, "Content-Length: 99999999999999999999999\r\n\r\n{{}}")?;
+    write!(stdin, "Content-Length: many\r\n\r\n{{}}")?;
+    stdin.flush()?;
+    for _ in 0..2 {
+        let response = receive(&mut stdout)?;
+        assert_eq!(response["id"], serde_json::Value::Null);
+        assert_eq!(response["error"]["code"], -32700);
+    }
+
+    send(
+        &mut stdin,
+        json!({"jsonrpc": "2.0", "id": 5, "method": "shutdown"}),
+    )?;
+    assert_eq!(receive(&mut stdout)?["result"], serde_json::Value::Null);
+    send(&mut stdin, json!({"jsonrpc": "2.0", "method": "exit"}))?;
+
+    assert!(child.wait()?.success());
+    Ok(())
+}
+
+#[test]
+fn serve_applies_project_config() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    std::fs::write(dir.path().join("requirements.txt"), "requests\n")?;
+    std::fs::write(
+        dir.path().join("depwise.toml"),
+        "[depwise]\nprovided-modules = [\"pyspark\"]\n",
+    )?;
+    std::fs::write(
+        dir.path().join("main.py"),
+        "import requests\nimport pyspark\n",
+    )?;
+
+    let mut child = Command::cargo_bin("depwise")?
+        .arg("serve")
+        .arg(dir.path())
+        .args(["--backend", "simulated"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    let mut stdin = child.stdin.take().unwrap();
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+
+    // pyspark is provided by the runtime, as `depwise check` would read it
+    send(
+        &mut stdin,
+        json!({"jsonrpc": "2.0", "id": 1, "method": "depwise/check"}),
+    )?;
+    assert_eq!(receive(&mut stdout)?["result"]["diagnostics"], json!([]));
+
+    send(&mut stdin, json!({"jsonrpc": "2.0", "method": "exit"}))?;
+    assert!(child.wait()?.success());
+    Ok(())
+}
+
+#[test]
+fn check_explain_lists_candidate_distributions() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    std::fs::write(dir.path().join("requirements.txt"), "flask\n")?;
+    std::fs::write(dir.path().join("main.py"), "import yaml\n")?;
+
+    Command::cargo_bin("depwise")?
+        .arg("check")
+        .arg(dir.path())
+        .args(["--backend", "simulated", "--explain"])
+        .assert()
+        .stdout(predicate::str::contains("      imported at main.py:1\n"))
+        .stdout(predicate::str::contains(
+            "      candidate distributions from the built-in name map: `pyyaml`\n",
+        ))
+        .stdout(predicate::str::contains(
+            "      expected to be imported as `flask`, but no checked file imports it\n",
+        ));
+
+    // Without the flag only the findings are printed
+    Command::cargo_bin("depwise")?
+        .arg("check")
+        .arg(dir.path())
+        .args(["--backend", "simulated"])
+        .assert()
+        .stdout(predicate::str::contains("candidate distributions").not());
+    Ok(())
+}
+
+#[test]
+fn check_imports_installed_through_extras() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    std::fs::write(dir.path().join("requirements.txt"), "pandas[parquet]\n")?;
+    std::fs::write(
+        dir.path().join("main.py"),
+        "import pandas\nimport pyarrow\n",
+    )?;
+
+    Command::cargo_bin("depwise")?
+        .arg("check")
+        .arg(dir.path())
+        .args(["--backend", "simulated", "--stats-deps"])
+        .assert()
+        .stdout(predicate::str::contains(
+            "warning DW106 [imported-through-extra] `pyarrow` is imported but only installed through `pandas[parquet]`; declare `pyarrow` directly",
+        ))
+        .stdout(predicate::str::contains("\npandas[parquet]      1        1"));
+    Ok(())
+}
+
+#[test]
+fn env_dump_manifest_is_checked_against() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    std::fs::write(dir.path().join("requirements.txt"), "pyyaml\n")?;
+    std::fs::write(
+        dir.path().join("main.py"),
+        "import yaml\nimport acmeclient\n",
+    )?;
+
+    let output = Command::cargo_bin("depwise")?
+        .arg("env-dump")
+        .arg(dir.path())
+        .args(["--backend", "simulated"])
+        .output()?;
+    assert!(output.status.success());
+    let dumped = String::from_utf8(output.stdout)?;
+    assert!(dumped.contains("\"pyyaml\""), "{}", dumped);
+
+    // A manifest lets modules be tied to distributions the curated mapping doesn't know
+    let manifest = dir.path().join("env.json");
+    std::fs::write(
+        &manifest,
+        r#"{"distributions": {"PyYAML": {"version": "6.0.1", "top_level_modules": ["yaml"]}, "acme-sdk": {"version": "1.1", "top_level_modules": ["acmeclient"]}}}"#,
+    )?;
+    Command::cargo_bin("depwise")?
+        .arg("check")
+        .arg(dir.path())
+        .arg("--env-manifest")
+        .arg(&manifest)
+        .assert()
+        .stdout(predicate::str::contains(
+            "`acmeclient` is imported but no declared dependency provides it (expected `acme-sdk`)",
+        ));
+    Ok(())
+}
+
+#[test]
+fn check_pixi_platform_targets() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    std::fs::write(
+        dir.path().join("pixi.toml"),
+        r#"[workspace]
+platforms = ["linux-64", "win-64"]
+
+[dependencies]
+numpy = "*"
+
+[target.win-64.pypi-dependencies]
+pywin32 = "*"
+"#,
+    )?;
+    std::fs::write(
+        dir.path().join("main.py"),
+        "import numpy\nimport win32api\n",
+    )?;
+
+    // The Windows-only dependency only satisfies the import on Windows
+    Command::cargo_bin("depwise")?
+        .arg("check")
+        .arg(dir.path())
+        .args(["--backend", "simulated"])
+        .assert()
+        .stdout(predicate::str::contains(
+            "pixi.toml (linux-64):\n  main.py:2: error DW101 [missing-dependency] `win32api`",
+        ))
+        .stdout(predicate::str::contains("pixi.toml (win-64)").not());
+
+    Command::cargo_bin("depwise")?
+        .arg("check")
+        .arg(dir.path())
+        .args(["--backend", "simulated", "--platform", "win-64"])
+        .assert()
+        .stdout(predicate::str::contains("pixi.toml (linux-64)").not())
+        .stdout(predicate::str::contains("DW101").not());
+    Ok(())
+}
+
+#[test]
+fn env_dump_current_interpreter() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    std::fs::write(dir.path().join("requirements.txt"), "")?;
+
+    let output = Command::cargo_bin("depwise")?
+        .arg("env-dump")
+        .arg(dir.path())
+        .args(["--backend", "current"])
+        .output()?;
+    assert!(output.status.success(), "{:?}", output);
+    let manifest: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert!(manifest["python_version"].is_string(), "{}", manifest);
+    assert!(manifest["distributions"].is_object(), "{}", manifest);
+
+    let path = dir.path().join("env.json");
+    Command::cargo_bin("depwise")?
+        .arg("env-dump")
+        .arg(dir.path())
+        .args(["--backend", "current", "--output"])
+        .arg(&path)
+        .assert()
+        .success()
+        .stdout("");
+    let written: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path)?)?;
+    assert_eq!(written["python_version"], manifest["python_version"]);
+    Ok(())
+}
+
+#[test]
+fn check_script_without_metadata_uses_enclosing_project() -> Result<(), Box<dyn std::error::Error>>
+{
+    let dir = tempfile::tempdir()?;
+    std::fs::write(
+        dir.path().join("pyproject.toml"),
+        "[project]\nname = \"example\"\ndependencies = [\"requests\", \"flask\"]\n",
+    )?;
+    std::fs::create_dir(dir.path().join("tools"))?;
+    std::fs::write(
+        dir.path().join("tools/sync.py"),
+        "import requests\nimport rich\n",
+    )?;
+    std::fs::write(
+        dir.path().join("tools/fetch.py"),
+        "# /// script\n# dependencies = [\"httpx\"]\n# ///\nimport httpx\n",
+    )?;
+
+    // Only the script is scanned, so the project's other dependencies aren't unused
+    let mut cmd = Command::cargo_bin("depwise")?;
+    cmd.arg("check")
+        .arg(dir.path().join("tools/sync.py"))
+        .args(["--backend", "simulated"]);
+    cmd.assert()
+        .code(1)
+        .stdout(predicate::str::contains(
+            "has no inline script metadata, checking it against",
+        ))
+        .stdout(predicate::str::contains(
+            "sync.py:2: error DW101 [missing-dependency] `rich`",
+        ))
+        .stdout(predicate::str::contains("`flask`").not());
+
+    // A script with metadata is checked against its own block, even inside a project
+    let mut cmd = Command::cargo_bin("depwise")?;
+    cmd.arg("check")
+        .arg(dir.path().join("tools/fetch.py"))
+        .args(["--backend", "simulated"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Note:").not())
+        .stdout(predicate::str::contains("DW10").not());
+
+    Ok(())
+}
+
+#[test]
+fn check_fail_on_warnings() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    std::fs::write(dir.path().join("requirements.txt"), "requests\n")?;
+    std::fs::write(dir.path().join("main.py"), "import requests\n")?;
+    std::fs::write(dir.path().join("broken.py"), "import os\n\ndef broken(:\n")?;
+
+    let mut cmd = Command::cargo_bin("depwise")?;
+    cmd.arg("check")
+        .arg(dir.path())
+        .args(["--backend", "simulated"]);
+    cmd.assert().success().stdout(predicate::str::contains(
+        "Warnings:\n  broken.py: warning [degraded-parse] only its import statements were read",
+    ));
+
+    let mut cmd = Command::cargo_bin("depwise")?;
+    cmd.arg("check")
+        .arg(dir.path())
+        .args(["--backend", "simulated", "--format", "json"]);
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let report: serde_json::Value = serde_json::from_slice(&output)?;
+    assert_eq!(report["warnings"][0]["code"], "degraded-parse");
+    assert_eq!(report["warnings"][0]["path"], "broken.py");
+
+    let mut cmd = Command::cargo_bin("depwise")?;
+    cmd.arg("check")
+        .arg(dir.path())
+        .args(["--backend", "simulated", "--fail-on-warnings"]);
+    cmd.assert().code(4).stderr(predicate::str::contains(
+        "Failing because of 1 warning (--fail-on-warnings)",
+    ));
+
+    std::fs::remove_file(dir.path().join("broken.py"))?;
+    let mut cmd = Command::cargo_bin("depwise")?;
+    cmd.arg("check")
+        .arg(dir.path())
+        .args(["--backend", "simulated", "--fail-on-warnings"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Warnings:").not());
+    Ok(())
+}
+
+#[test]
+fn check_fail_on() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    std::fs::write(dir.path().join("requirements.txt"), "requests\nrich\n")?;
+    std::fs::write(dir.path().join("main.py"), "import requests\nimport yaml\n")?;
+
+    // A missing dependency is an error, which fails the check by default
+    let mut cmd = Command::cargo_bin("depwise")?;
+    cmd.arg("check")
+        .arg(dir.path())
+        .args(["--backend", "simulated", "--summary-only"]);
+    cmd.assert()
+        .code(1)
+        .stdout(predicate::str::contains("2 findings\n"))
+        .stderr(predicate::str::contains(
+            "Failing because of 1 finding (--fail-on)",
+        ));
+
+    let mut cmd = Command::cargo_bin("depwise")?;
+    cmd.arg("check")
+        .arg(dir.path())
+        .args(["--backend", "simulated", "--fail-on", "never"]);
+    cmd.assert().success();
+
+    // An unused dependency is a warning, which only fails the check when asked to, even
+    // when it's hidden by --min-severity
+    std::fs::write(dir.path().join("main.py"), "import requests\n")?;
+    let mut cmd = Command::cargo_bin("depwise")?;
+    cmd.arg("check")
+        .arg(dir.path())
+        .args(["--backend", "simulated", "--summary-only"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("1 finding\n"));
+
+    let mut cmd = Command::cargo_bin("depwise")?;
+    cmd.arg("check")
+        .arg(dir.path())
+        .args(["--backend", "simulated", "--fail-on", "warning"])
+        .args(["--min-severity", "error"]);
+    cmd.assert()
+        .code(1)
+        .stdout(predicate::str::contains("`rich`").not());
+    Ok(())
+}
+
+#[test]
+fn check_max_file_size() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    std::fs::write(dir.path().join("requirements.txt"), "requests\n")?;
+    std::fs::write(dir.path().join("main.py"), "import requests\n")?;
+    std::fs::write(dir.path().join("generated.py"), "import yaml\n".repeat(100))?;
+
+    let mut cmd = Command::cargo_bin("depwise")?;
+    cmd.arg("check")
+        .arg(dir.path())
+        .args(["--backend", "simulated", "--max-file-size", "1000"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("DW101").not())
+        .stdout(predicate::str::contains(
+            "generated.py: warning [skipped-file] skipped, its imports weren't checked: 1200 bytes is over the maximum file size of 1000 bytes",
+        ));
+
+    // 0 turns the limit off
+    let mut cmd = Command::cargo_bin("depwise")?;
+    cmd.arg("check")
+        .arg(dir.path())
+        .args(["--backend", "simulated", "--max-file-size", "0"]);
+    cmd.assert()
+        .code(1)
+        .stdout(predicate::str::contains("DW101"));
+    Ok(())
+}
+
+#[test]
+fn check_profile() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    std::fs::write(dir.path().join("requirements.txt"), "requests\n")?;
+    std::fs::write(
+        dir.path().join("main.py"),
+        "import requests\nimport pyspark\nimport internal_runtime\n",
+    )?;
+
+    let mut cmd = Command::cargo_bin("depwise")?;
+    cmd.arg("check").arg(dir.path()).args([
+        "--backend",
+        "simulated",
+        "--profile",
+        "databricks",
+        "--provided",
+        "internal_runtime",
+    ]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("DW101").not());
+
+    let mut cmd = Command::cargo_bin("depwise")?;
+    cmd.arg("check")
+        .arg(dir.path())
+        .args(["--backend", "simulated", "--profile", "databricks"]);
+    cmd.assert()
+        .code(1)
+        .stdout(predicate::str::contains("pyspark").not())
+        .stdout(predicate::str::contains("internal_runtime"));
+
+    let mut cmd = Command::cargo_bin("depwise")?;
+    cmd.arg("check")
+        .arg(dir.path())
+        .args(["--backend", "simulated", "--profile", "lambda"]);
+    cmd.assert().failure();
+    Ok(())
+}
+
+#[test]
+fn check_broken_entry_points() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    std::fs::write(
+        dir.path().join("pyproject.toml"),
+        "[project]\nname = \"mypkg\"\ndependencies = []\n\n[project.scripts]\nmycli = \"mypkg.cli:main\"\nserve = \"mypkg.server:run\"\n",
+    )?;
+    std::fs::create_dir_all(dir.path().join("mypkg/commands"))?;
+    std::fs::write(dir.path().join("mypkg/__init__.py"), "")?;
+    std::fs::write(dir.path().join("mypkg/commands/__init__.py"), "")?;
+    std::fs::write(
+        dir.path().join("mypkg/commands/cli.py"),
+        "def main():\n    pass\n",
+    )?;
+    std::fs::write(
+        dir.path().join("mypkg/server.py"),
+        "def serve():\n    pass\n",
+    )?;
+
+    let mut cmd = Command::cargo_bin("depwise")?;
+    cmd.arg("check")
+        .arg(dir.path())
+        .args(["--backend", "simulated"]);
+    cmd.assert()
+        .code(1)
+        .stdout(predicate::str::contains(
+            "pyproject.toml:6: error DW307 [broken-entry-point] `mycli` in `[project.scripts]` refers to `mypkg.cli`, which isn't a module of the project; did you mean `mypkg.commands.cli`?",
+        ))
+        .stdout(predicate::str::contains(
+            "pyproject.toml:7: error DW307 [broken-entry-point] `serve` in `[project.scripts]` refers to `mypkg.server:run`, but `mypkg.server` doesn't define `run`",
+        ));
+    Ok(())
+}
+
+#[test]
+fn check_aggregate_projects() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    for project in ["api", "worker"] {
+        let root = dir.path().join(project);
+        std::fs::create_dir_all(&root)?;
+        std::fs::write(root.join("requirements.txt"), "requests\n")?;
+        std::fs::write(root.join("main.py"), "import requests\nimport yaml\n")?;
+    }
+    let api = dir.path().join("api");
+    let worker = dir.path().join("worker");
+
+    // Separately, each project reports the missing import
+    let mut cmd = Command::cargo_bin("depwise")?;
+    cmd.arg("check")
+        .arg(&api)
+        .arg(&worker)
+        .args(["--backend", "simulated", "--format", "json"]);
+    let output = cmd.assert().code(1).get_output().stdout.clone();
+    let report: serde_json::Value = serde_json::from_slice(&output)?;
+    let projects = report["projects"].as_array().unwrap();
+    assert_eq!(projects.len(), 2);
+    for (project, path) in projects.iter().zip([&api, &worker]) {
+        assert_eq!(project["path"], path.display().to_string());
+        assert_eq!(project["summary"]["DW101"], 1);
+        assert_eq!(project["findings"][0]["path"], "main.py");
+    }
+
+    // Aggregated, it's one finding listing both
+    let mut cmd = Command::cargo_bin("depwise")?;
+    cmd.current_dir(dir.path())
+        .args(["check", "api", "worker", "--aggregate"])
+        .args(["--backend", "simulated", "--format", "json"]);
+    let output = cmd.assert().code(1).get_output().stdout.clone();
+    let report: serde_json::Value = serde_json::from_slice(&output)?;
+    let findings = report["findings"].as_array().unwrap();
+    assert_eq!(findings.len(), 1);
+    assert_eq!(findings[0]["subject"], "yaml");
+    assert_eq!(findings[0]["path"], "api/main.py");
+    assert_eq!(
+        findings[0]["projects"],
+        serde_json::json!(["api", "worker"])
+    );
+    // The fingerprint is the one the project reports on its own
+    assert_eq!(
+        findings[0]["fingerprint"],
+        projects[0]["findings"][0]["fingerprint"]
+    );
+    assert_eq!(report["summary"]["DW101"], 1);
+
+    let mut cmd = Command::cargo_bin("depwise")?;
+    cmd.current_dir(dir.path())
+        .args(["check", "api", "worker", "--aggregate"])
+        .args(["--backend", "simulated"]);
+    cmd.assert()
+        .code(1)
+        .stdout(predicate::str::contains("Checking dependencies for worker"))
+        .stdout(predicate::str::contains(
+            "api/main.py:2: error DW101 [missing-dependency]",
+        ))
+        .stdout(predicate::str::contains("    in api, worker\n"))
+        .stdout(predicate::str::contains("worker/main.py").not());
+    Ok(())
+}
+
+#[test]
+fn check_editable_requirements() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    std::fs::write(
+        dir.path().join("pyproject.toml"),
+        "[project]\nname = \"myapp\"\nversion = \"0.1.0\"\n",
+    )?;
+    std::fs::create_dir(dir.path().join("myapp"))?;
+    std::fs::write(dir.path().join("myapp/__init__.py"), "")?;
+    std::fs::write(
+        dir.path().join("requirements.txt"),
+        "requests\n-e .\n--editable=./vendor/helper\n",
+    )?;
+    std::fs::create_dir_all(dir.path().join("vendor/helper"))?;
+    std::fs::write(
+        dir.path().join("vendor/helper/pyproject.toml"),
+        "[project]\nname = \"helper\"\nversion = \"0.1.0\"\n",
+    )?;
+    std::fs::write(
+        dir.path().join("main.py"),
+        "import requests\nimport myapp\n",
+    )?;
+
+    // The project's own editable install is never unused, a vendored one still is
+    let mut cmd = Command::cargo_bin("depwise")?;
+    cmd.arg("check")
+        .arg(dir.path())
+        .arg("-r")
+        .arg(dir.path().join("requirements.txt"))
+        .args(["--backend", "simulated"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("`myapp`").not())
+        .stdout(predicate::str::contains(
+            "warning DW102 [unused-dependency] `helper` is declared but never imported",
+        ));
+    Ok(())
+}
+
+#[test]
+fn check_group_by() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    std::fs::write(dir.path().join("requirements.txt"), "Flask\nrequests\n")?;
+    std::fs::write(dir.path().join("main.py"), "import requests\nimport yaml\n")?;
+    std::fs::write(dir.path().join("util.py"), "import yaml\n")?;
+
+    let output = |group_by: &str| -> Result<String, Box<dyn std::error::Error>> {
+        let mut cmd = Command::cargo_bin("depwise")?;
+        cmd.current_dir(dir.path()).args([
+            "check",
+            ".",
+            "--backend",
+            "simulated",
+            "--group-by",
+            group_by,
+        ]);
+        let stdout = cmd.assert().code(1).get_output().stdout.clone();
+        Ok(String::from_utf8(stdout)?)
+    };
+
+    assert_eq!(
+        output("source")?,
+        "Checking dependencies for .
+./requirements.txt:
+  main.py:2: error DW101 [missing-dependency] `yaml` is imported but no declared dependency provides it (expected `pyyaml`)
+  warning DW102 [unused-dependency] `flask` is declared but never imported
+"
+    );
+    assert_eq!(
+        output("file")?,
+        "Checking dependencies for .
+main.py:
+  main.py:2: error DW101 [missing-dependency] `yaml` is imported but no declared dependency provides it (expected `pyyaml`) (./requirements.txt)
+requirements.txt:
+  ./requirements.txt: warning DW102 [unused-dependency] `flask` is declared but never imported
+"
+    );
+    assert_eq!(
+        output("dependency")?,
+        "Checking dependencies for .
+flask:
+  declared at requirements.txt:1
+  ./requirements.txt: warning DW102 [unused-dependency] `flask` is declared but never imported
+pyyaml:
+  imported at main.py:2, util.py:1
+  main.py:2: error DW101 [missing-dependency] `yaml` is imported but no declared dependency provides it (expected `pyyaml`) (./requirements.txt)
+"
+    );
+    assert_eq!(
+        output("rule")?,
+        "Checking dependencies for .
+DW101 [missing-dependency]:
+  main.py:2: error DW101 [missing-dependency] `yaml` is imported but no declared dependency provides it (expected `pyyaml`) (./requirements.txt)
+DW102 [unused-dependency]:
+  ./requirements.txt: warning DW102 [unused-dependency] `flask` is declared but never imported
+"
+    );
+
+    // JSON output stays flat
+    let mut cmd = Command::cargo_bin("depwise")?;
+    cmd.arg("check").arg(dir.path()).args([
+        "--backend",
+        "simulated",
+        "--format",
+        "json",
+        "--group-by",
+        "rule",
+    ]);
+    let stdout = cmd.assert().code(1).get_output().stdout.clone();
+    let report: serde_json::Value = serde_json::from_slice(&stdout)?;
+    assert_eq!(report["findings"].as_array().map(Vec::len), Some(2));
+    Ok(())
+}
+
+#[test]
+fn check_group_by_dependency_resolves_modules() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    std::fs::write(dir.path().join("requirements.txt"), "requests\nPyYAML\n")?;
+    std::fs::write(dir.path().join("main.py"), "import requests\nimport yaml\n")?;
+
+    // An import that's never used doesn't use its dependency either. The unused import of
+    // `yaml` belongs with the unused `pyyaml` and where that is declared.
+    let mut cmd = Command::cargo_bin("depwise")?;
+    cmd.current_dir(dir.path()).args([
+        "check",
+        ".",
+        "--backend",
+        "simulated",
+        "--check-unused-imports",
+        "--group-by",
+        "dependency",
+    ]);
+    let stdout = cmd.assert().success().get_output().stdout.clone();
+    assert_eq!(
+        String::from_utf8(stdout)?,
+        "Checking dependencies for .
+pyyaml:
+  declared at requirements.txt:2
+  ./requirements.txt: warning DW102 [unused-dependency] `pyyaml` is declared but never imported
+  main.py:2: warning DW201 [unused-import] `yaml` is imported but never used
+requests:
+  declared at requirements.txt:1
+  ./requirements.txt: warning DW102 [unused-dependency] `requests` is declared but never imported
+  main.py:1: warning DW201 [unused-import] `requests` is imported but never used
+"
+    );
+    Ok(())
+}
+
+#[test]
+fn check_no_default_excludes() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    std::fs::write(dir.path().join("requirements.txt"), "requests\n")?;
+    std::fs::write(dir.path().join("main.py"), "import requests\n")?;
+    std::fs::create_dir_all(dir.path().join(".venv"))?;
+    std::fs::write(dir.path().join(".venv/foo.py"), "import yaml\n")?;
+    std::fs::create_dir_all(dir.path().join("build"))?;
+    std::fs::write(dir.path().join("build/gen.py"), "import numpy\n")?;
+
+    let check = |args: &[&str]| -> Result<String, Box<dyn std::error::Error>> {
+        let mut cmd = Command::cargo_bin("depwise")?;
+        cmd.arg("check")
+            .arg(dir.path())
+            .args(["--backend", "simulated", "--exclude", "build"])
+            .args(["--fail-on", "never"])
+            .args(args);
+        let stdout = cmd.assert().success().get_output().stdout.clone();
+        Ok(String::from_utf8(stdout)?)
+    };
+
+    let output = check(&[])?;
+    assert!(!output.contains("`yaml`"), "{}", output);
+    assert!(!output.contains("`numpy`"), "{}", output);
+
+    // The built-in excludes are dropped, the ones given with --exclude are kept
+    let output = check(&["--no-default-excludes"])?;
+    assert!(
+        output.contains(".venv/foo.py:1: error DW101 [missing-dependency] `yaml`"),
+        "{}",
+        output
+    );
+    assert!(!output.contains("`numpy`"), "{}", output);
+    Ok(())
+}
+
+/// Put an executable script named `name` in `dir`, for faking a backend's tools
+#[cfg(unix)]
+fn fake_tool(dir: &std::path::Path, name: &str, script: &str) -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    let path = dir.join(name);
+    std::fs::write(&path, script)?;
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))
+}
+
+#[cfg(unix)]
+#[test]
+fn env_dump_resolution_failure() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    let bin = tempfile::tempdir()?;
+    std::fs::write(
+        dir.path().join("requirements.txt"),
+        "requests\nnumpyy==1.26\n",
+    )?;
+    fake_tool(
+        bin.path(),
+        "uv",
+        "#!/bin/sh
+echo 'Using CPython 3.12.3' >&2
+echo '  × No solution found when resolving dependencies:' >&2
+echo '  ╰─▶ Because there is no version of numpyy==1.26 and you require numpyy==1.26, we can conclude that your requirements are unsatisfiable.' >&2
+exit 1
+",
+    )?;
+    let path = format!("{}:{}", bin.path().display(), std::env::var("PATH")?);
+
+    let mut cmd = Command::cargo_bin("depwise")?;
+    cmd.env("PATH", &path)
+        .arg("env-dump")
+        .arg(dir.path())
+        .args(["--backend", "uv"]);
+    cmd.assert()
+        .code(1)
+        .stderr(predicate::str::contains(format!(
+            "uv failed to resolve the project at {}",
+            dir.path().display()
+        )))
+        .stderr(predicate::str::contains(
+            "requirements.txt:2: error DW403 [unresolvable-dependency] no distribution of `numpyy` matches the requirement",
+        ))
+        .stderr(predicate::str::contains("Using CPython").not());
+
+    // The raw output is only printed when asked for
+    let mut cmd = Command::cargo_bin("depwise")?;
+    cmd.env("PATH", &path)
+        .arg("env-dump")
+        .arg(dir.path())
+        .args(["--backend", "uv", "--verbose"]);
+    cmd.assert()
+        .code(1)
+        .stderr(predicate::str::contains("uv output:\nUsing CPython 3.12.3"));
+
+    // A resolver that hangs is stopped, with what it was resolving
+    fake_tool(bin.path(), "uv", "#!/bin/sh\nsleep 30 &\nwait\n")?;
+    let started = std::time::Instant::now();
+    let mut cmd = Command::cargo_bin("depwise")?;
+    cmd.env("PATH", &path)
+        .arg("env-dump")
+        .arg(dir.path())
+        .args(["--backend", "uv", "--backend-timeout", "1"]);
+    cmd.assert()
+        .code(1)
+        .stderr(predicate::str::contains(format!(
+            "uv did not finish resolving the project at {} within 1s and was stopped",
+            dir.path().display()
+        )));
+    assert!(started.elapsed() < std::time::Duration::from_secs(20));
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn check_resolution_failure() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    let bin = tempfile::tempdir()?;
+    std::fs::write(
+        dir.path().join("requirements.txt"),
+        "requests\nnumpyy==1.26\n",
+    )?;
+    std::fs::write(dir.path().join("main.py"), "import requests\n")?;
+    fake_tool(
+        bin.path(),
+        "uv",
+        "#!/bin/sh
+echo 'Using CPython 3.12.3' >&2
+echo '  × No solution found when resolving dependencies:' >&2
+echo '  ╰─▶ Because there is no version of numpyy==1.26 and you require numpyy==1.26, we can conclude that your requirements are unsatisfiable.' >&2
+exit 1
+",
+    )?;
+    let path = format!("{}:{}", bin.path().display(), std::env::var("PATH")?);
+
+    // The problems are reported on their declarations, like any other finding
+    Command::cargo_bin("depwise")?
+        .env("PATH", &path)
+        .arg("check")
+        .arg(dir.path())
+        .args(["--backend", "uv"])
+        .assert()
+        .code(1)
+        .stdout(predicate::str::contains(format!(
+            "uv failed to resolve the project at {0}, so the imports weren't checked\n{0}/requirements.txt:\n  requirements.txt:2: error DW403 [unresolvable-dependency] no distribution of `numpyy` matches the requirement",
+            dir.path().display()
+        )))
+        .stdout(predicate::str::contains("Using CPython").not());
+
+    Command::cargo_bin("depwise")?
+        .env("PATH", &path)
+        .arg("check")
+        .arg(dir.path())
+        .args(["--backend", "uv", "--verbose"])
+        .assert()
+        .code(1)
+        .stdout(predicate::str::contains("uv output:\nUsing CPython 3.12.3"));
+
+    // JSON output always has what the tool printed
+    let output = Command::cargo_bin("depwise")?
+        .env("PATH", &path)
+        .arg("check")
+        .arg(dir.path())
+        .args(["--backend", "uv", "--format", "json"])
+        .output()?;
+    assert_eq!(output.status.code(), Some(1), "{:?}", output);
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(report["findings"][0]["code"], "DW403", "{}", report);
+    assert_eq!(report["resolution_failure"]["program"], "uv");
+    assert!(
+        report["resolution_failure"]["output"]
+            .as_str()
+            .is_some_and(|output| output.starts_with("Using CPython 3.12.3")),
+        "{}",
+        report
+    );
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn check_uv_environment() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    let bin = tempfile::tempdir()?;
+    std::fs::write(dir.path().join("requirements.txt"), "acme-sdk==1.0\n")?;
+    std::fs::write(dir.path().join("main.py"), "import acmeclient\n")?;
+    fake_tool(
+        bin.path(),
+        "uv",
+        r#"#!/bin/sh
+echo '{"python_version": "3.12.3", "distributions": {"acme-sdk": {"version": "1.1", "top_level_modules": ["acmeclient"]}}}'
+"#,
+    )?;
+    let path = format!("{}:{}", bin.path().display(), std::env::var("PATH")?);
+
+    // The modules and versions come from the environment uv resolves
+    Command::cargo_bin("depwise")?
+        .env("PATH", &path)
+        .arg("check")
+        .arg(dir.path())
+        .args(["--backend", "uv"])
+        .assert()
+        .stdout(predicate::str::contains("DW101").not())
+        .stdout(predicate::str::contains("backend-fallback").not())
+        .stdout(predicate::str::contains(
+            "`acme-sdk==1.0` is declared but acme-sdk 1.1 is installed",
+        ));
+
+    // The automatic backend describes the environment the way the analysis matches with it
+    let output = Command::cargo_bin("depwise")?
+        .env("PATH", &path)
+        .arg("env-dump")
+        .arg(dir.path())
+        .output()?;
+    assert!(output.status.success(), "{:?}", output);
+    let manifest: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert!(manifest["python_version"].is_null(), "{}", manifest);
+    assert!(
+        manifest["distributions"]["acme-sdk"].is_object(),
+        "{}",
+        manifest
+    );
+    Ok(())
+}
+
+#[test]
+fn check_fingerprints() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    std::fs::write(dir.path().join("requirements.txt"), "requests\nflask\n")?;
+    std::fs::write(dir.path().join("main.py"), "import requests\nimport yaml\n")?;
+
+    let findings = || -> Result<Vec<serde_json::Value>, Box<dyn std::error::Error>> {
+        let mut cmd = Command::cargo_bin("depwise")?;
+        cmd.arg("check")
+            .arg(dir.path())
+            .args(["--backend", "simulated", "--format", "json"]);
+        let stdout = cmd.assert().code(1).get_output().stdout.clone();
+        let report: serde_json::Value = serde_json::from_slice(&stdout)?;
+        Ok(report["findings"].as_array().cloned().unwrap_or_default())
+    };
+
+    let before = findings()?;
+    assert_eq!(before.len(), 2);
+    let fingerprints: Vec<&str> = before
+        .iter()
+        .map(|finding| finding["fingerprint"].as_str().unwrap())
+        .collect();
+    assert_eq!(fingerprints[0].len(), 16);
+    assert_ne!(fingerprints[0], fingerprints[1]);
+
+    // Moving the import keeps its fingerprint
+    std::fs::write(
+        dir.path().join("main.py"),
+        "\"\"\"The app\"\"\"\n\nimport requests\n\nimport yaml\n",
+    )?;
+    let after = findings()?;
+    assert_eq!(before[0]["subject"], "yaml");
+    assert_eq!(after[0]["line"], 5);
+    assert_ne!(before[0]["line"], after[0]["line"]);
+    assert_eq!(before[0]["fingerprint"], after[0]["fingerprint"]);
+    assert_eq!(before[1]["fingerprint"], after[1]["fingerprint"]);
+    Ok(())
+}
+
+#[test]
+fn check_changed_since() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    let git = |args: &[&str]| -> Result<(), Box<dyn std::error::Error>> {
+        let status = Command::new("git")
+            .arg("-C")
+            .arg(dir.path())
+            .args([
+                "-c",
+                "user.name=depwise",
+                "-c",
+                "user.email=depwise@example.com",
+            ])
+            .args(args)
+            .stdout(Stdio::null())
+            .status()?;
+        assert!(status.success(), "git {:?} failed", args);
+        Ok(())
+    };
+    std::fs::write(dir.path().join("requirements.txt"), "requests\nrich\n")?;
+    std::fs::write(dir.path().join("old.py"), "import yaml\nimport rich\n")?;
+    std::fs::write(dir.path().join("main.py"), "import requests\n")?;
+    git(&["init", "-q", "-b", "main"])?;
+    git(&["add", "-A"])?;
+    git(&["commit", "-q", "-m", "base"])?;
+    git(&["checkout", "-q", "-b", "feature"])?;
+    std::fs::write(dir.path().join("main.py"), "import requests\nimport toml\n")?;
+    git(&["commit", "-q", "-am", "change"])?;
+    // Uncommitted work is checked too
+    std::fs::write(dir.path().join("new.py"), "import click\n")?;
+
+    let mut cmd = Command::cargo_bin("depwise")?;
+    cmd.arg("check")
+        .arg(dir.path())
+        .args(["--backend", "simulated", "--format", "json"])
+        .args(["--changed-since", "main"]);
+    let stdout = cmd.assert().code(1).get_output().stdout.clone();
+    let report: serde_json::Value = serde_json::from_slice(&stdout)?;
+    let subjects: Vec<&str> = report["findings"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|finding| finding["subject"].as_str().unwrap())
+        .collect();
+    // yaml in the unchanged old.py isn't reported, nor is rich as unused
+    assert_eq!(subjects, vec!["click", "toml"]);
+
+    let mut cmd = Command::cargo_bin("depwise")?;
+    cmd.arg("check")
+        .arg(dir.path())
+        .args(["--backend", "simulated", "--changed-since", "main"])
+        .env("PATH", dir.path().join("no-git"));
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("--changed-since needs git"));
+    Ok(())
+}
+
+#[test]
+fn check_since_reuses_state() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    let state_dir = tempfile::tempdir()?;
+    let state = state_dir.path().join("state.json");
+    let git = |args: &[&str]| -> Result<(), Box<dyn std::error::Error>> {
+        let status = Command::new("git")
+            .arg("-C")
+            .arg(dir.path())
+            .args([
+                "-c",
+                "user.name=depwise",
+                "-c",
+                "user.email=depwise@example.com",
+            ])
+            .args(args)
+            .stdout(Stdio::null())
+            .status()?;
+        assert!(status.success(), "git {:?} failed", args);
+        Ok(())
+    };
+    let check = |args: &[&str]| -> Result<serde_json::Value, Box<dyn std::error::Error>> {
+        let mut cmd = Command::cargo_bin("depwise")?;
+        cmd.arg("check")
+            .arg(dir.path())
+            .args(["--backend", "simulated", "--format", "json", "--stats"])
+            .args(["--fail-on", "never"])
+            .arg("--state-file")
+            .arg(&state)
+            .args(args);
+        let stdout = cmd.assert().success().get_output().stdout.clone();
+        Ok(serde_json::from_slice(&stdout)?)
+    };
+    let subjects = |report: &serde_json::Value| -> Vec<String> {
+        report["findings"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|finding| finding["subject"].as_str().unwrap().to_string())
+            .collect()
+    };
+    std::fs::write(dir.path().join("requirements.txt"), "requests\nrich\n")?;
+    std::fs::write(dir.path().join("old.py"), "import rich\n")?;
+    std::fs::write(dir.path().join("main.py"), "import requests\n")?;
+    git(&["init", "-q", "-b", "main"])?;
+    git(&["add", "-A"])?;
+    git(&["commit", "-q", "-m", "base"])?;
+
+    // A full run saves the state
+    let report = check(&[])?;
+    assert!(subjects(&report).is_empty());
+    assert!(state.exists());
+
+    git(&["checkout", "-q", "-b", "feature"])?;
+    std::fs::write(dir.path().join("main.py"), "import toml\n")?;
+    git(&["commit", "-q", "-am", "change"])?;
+
+    // Only main.py is parsed, and rich is still used by the saved old.py
+    let report = check(&["--since", "main"])?;
+    assert_eq!(report["stats"]["files_cached"], 1);
+    assert_eq!(subjects(&report), ["toml", "requests"]);
+
+    // A changed manifest parses every file again
+    std::fs::write(
+        dir.path().join("requirements.txt"),
+        "requests\nrich\ntoml\n",
+    )?;
+    let report = check(&["--since", "main"])?;
+    assert_eq!(report["stats"]["files_cached"], 0);
+    assert_eq!(subjects(&report), ["requests"]);
+
+    let mut cmd = Command::cargo_bin("depwise")?;
+    cmd.arg("check")
+        .arg(dir.path())
+        .args(["--backend", "simulated", "--since", "main"]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("--state-file"));
+    Ok(())
+}
+
+#[test]
+fn check_include_docs() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    std::fs::write(
+        dir.path().join("pyproject.toml"),
+        "[project]\nname = \"example\"\ndependencies = []\n\n[project.optional-dependencies]\ndocs = [\"sphinx\"]\n",
+    )?;
+    std::fs::write(dir.path().join("main.py"), "import os\n")?;
+    std::fs::write(
+        dir.path().join("README.md"),
+        "# Example\n\n```python\nimport sphinx\n```\n",
+    )?;
+
+    let mut cmd = Command::cargo_bin("depwise")?;
+    cmd.arg("check")
+        .arg(dir.path())
+        .args(["--backend", "simulated"]);
+    cmd.assert().success().stdout(predicate::str::contains(
+        "`sphinx` is declared but never imported",
+    ));
+
+    let mut cmd = Command::cargo_bin("depwise")?;
+    cmd.arg("check")
+        .arg(dir.path())
+        .args(["--backend", "simulated", "--include-docs"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("sphinx").not());
+    Ok(())
+}
+
+#[test]
+fn check_name_map() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    std::fs::write(dir.path().join("requirements.txt"), "acme-sdk\n")?;
+    std::fs::write(dir.path().join("main.py"), "import acme_client\n")?;
+    std::fs::write(
+        dir.path().join("names.json"),
+        r#"{"distributions": {"acme-sdk": ["acme_client"]}}"#,
+    )?;
+
+    let mut cmd = Command::cargo_bin("depwise")?;
+    cmd.arg("check")
+        .arg(dir.path())
+        .args(["--backend", "simulated"]);
+    cmd.assert()
+        .stdout(predicate::str::contains("`acme_client` is imported"));
+
+    let mut cmd = Command::cargo_bin("depwise")?;
+    cmd.arg("check")
+        .arg(dir.path())
+        .args(["--backend", "simulated", "--name-map"])
+        .arg(dir.path().join("names.json"));
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("acme").not());
+    Ok(())
+}
+
+#[test]
+fn list_deps_shows_includes() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    std::fs::create_dir(dir.path().join("requirements"))?;
+    std::fs::write(
+        dir.path().join("requirements.txt"),
+        "click\n-r requirements/prod.txt\n",
+    )?;
+    std::fs::write(dir.path().join("requirements/prod.txt"), "-r base.txt\n")?;
+    std::fs::write(dir.path().join("requirements/base.txt"), "\nflask\n")?;
+    std::fs::write(dir.path().join("main.py"), "import click\n")?;
+
+    let output = Command::cargo_bin("depwise")?
+        .arg("list-deps")
+        .arg(dir.path())
+        .args(["--format", "json"])
+        .output()?;
+    assert!(output.status.success());
+    let listed: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    let flask = &listed["configurations"][0]["dependencies"][1];
+    assert_eq!(flask["requirement"], "flask");
+    assert!(
+        flask["declared_in"]
+            .as_str()
+            .unwrap()
+            .ends_with("base.txt:2")
+    );
+    assert_eq!(flask["included_via"].as_array().unwrap().len(), 2);
+    let prod = &listed["includes"]["includes"][0];
+    assert_eq!(prod["line"], 2);
+    assert_eq!(prod["includes"][0]["line"], 1);
+
+    let mut cmd = Command::cargo_bin("depwise")?;
+    cmd.arg("check")
+        .arg(dir.path())
+        .args(["--backend", "simulated"]);
+    cmd.assert().stdout(predicate::str::contains(
+        "`flask` is declared in requirements/base.txt:2 (included via requirements.txt:2 → requirements/prod.txt:1) but never imported",
+    ));
+
+    Ok(())
+}
@@ -0,0 +1,9 @@
+#!/bin/sh
+# Download the official CycloneDX 1.5 JSON schema and the schemas it references next to
+# this script, for `read_cyclonedx` in the CLI tests to validate written SBOMs against.
+set -eu
+cd "$(dirname "$0")"
+base=https://raw.githubusercontent.com/CycloneDX/specification/1.5/schema
+for schema in bom-1.5.schema.json spdx.schema.json jsf-0.82.schema.json; do
+    curl --fail --silent --show-error --location --output "$schema" "$base/$schema"
+done
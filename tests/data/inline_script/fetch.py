@@ -0,0 +1,12 @@
+# /// script
+# requires-python = ">=3.11"
+# dependencies = [
+#   "requests<3",
+#   "rich",
+# ]
+# ///
+
+import requests
+import yaml
+
+print(requests.get("https://example.com").status_code)
@@ -0,0 +1,5 @@
+import tomllib
+
+import numpy
+import toml
+from rich import print